@@ -0,0 +1,106 @@
+//! Telling text from binary content before it is chunked.
+//!
+//! A mislabeled file (a `.txt` that is really an export or a dump) decodes
+//! into garbage that would be embedded and pollute the index. Its text, as
+//! decoded by [`crate::encoding`], is rejected when too many of its
+//! characters are control characters or stand for invalid bytes.
+
+/// Content with a larger share of control characters (other than tabs,
+/// line breaks and form feeds) is binary.
+const MAX_CONTROL_RATIO: f64 = 0.1;
+
+/// Content with a larger share of bytes that are not valid in its encoding
+/// is binary. A few are tolerated: one bad byte shouldn't lose a file.
+const MAX_INVALID_RATIO: f64 = 0.05;
+
+/// Why content was taken for binary.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum NotText {
+    #[error("{:.0}% control characters", .0 * 100.0)]
+    Control(f64),
+    #[error("{:.0}% invalid bytes", .0 * 100.0)]
+    Invalid(f64),
+}
+
+/// Check decoded `text`, such as a converter's output, for the marks of
+/// binary content: control characters, and replacement characters standing
+/// for invalid bytes.
+pub fn check(text: &str) -> Result<(), NotText> {
+    let mut chars = 0;
+    let mut control = 0;
+    let mut invalid = 0;
+    for c in text.chars() {
+        chars += 1;
+        match c {
+            '\t' | '\n' | '\r' | '\u{c}' => {}
+            char::REPLACEMENT_CHARACTER => invalid += 1,
+            c if c.is_control() => control += 1,
+            _ => {}
+        }
+    }
+    if chars == 0 {
+        return Ok(());
+    }
+    let ratio = |n: usize| n as f64 / chars as f64;
+    if ratio(control) > MAX_CONTROL_RATIO {
+        return Err(NotText::Control(ratio(control)));
+    }
+    if ratio(invalid) > MAX_INVALID_RATIO {
+        return Err(NotText::Invalid(ratio(invalid)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes from a fixed-seed xorshift generator, as a stand-in for a dump.
+    fn random_bytes(len: usize) -> Vec<u8> {
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<String, NotText> {
+        let (text, _) = crate::encoding::decode(bytes, None);
+        check(&text).map(|()| text)
+    }
+
+    #[test]
+    fn test_text() {
+        let text = "# Notes\n\nCafé opening hours:\tMon–Fri 9–17.\r\n\u{c}Page two.\n";
+        assert_eq!(decode(text.as_bytes()).unwrap(), text);
+        let with_bom = [&[0xEF, 0xBB, 0xBF], text.as_bytes()].concat();
+        assert_eq!(decode(&with_bom).unwrap(), text);
+        assert_eq!(decode(b"").unwrap(), "");
+
+        // A stray invalid byte is replaced, not fatal.
+        let mut bytes = "日本語のテキストです。".repeat(10).into_bytes();
+        bytes.push(0xFF);
+        assert!(decode(&bytes).unwrap().ends_with("です。\u{fffd}"));
+    }
+
+    #[test]
+    fn test_binary() {
+        assert!(decode(&random_bytes(4096)).is_err());
+
+        // Valid UTF-8, but mostly NULs and other control bytes.
+        let mut dump = b"SQLite format 3\0".to_vec();
+        dump.extend([0, 0, 1, 0, 2, 0x10, 0, 0, 0x1f, 0].repeat(100));
+        let err = decode(&dump).unwrap_err();
+        assert!(matches!(err, NotText::Control(_)));
+        assert!(err.to_string().ends_with("% control characters"));
+
+        // A converter that printed a binary's bytes.
+        let garbage = String::from_utf8_lossy(&random_bytes(1024)).into_owned();
+        assert!(check(&garbage).is_err());
+        assert!(check("Plain \x1b[1mbold\x1b[0m output of a tool.").is_ok());
+    }
+}
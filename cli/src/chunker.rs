@@ -1,23 +1,256 @@
-/// Split text into chunks with overlap.
-pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
+use std::ops::Range;
+
+use anyhow::Result;
+use mcp_hybrid_search_common::config::AppConfig;
+use mcp_hybrid_search_common::types::ChunkLocation;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::tokens;
+
+/// How documents are split into chunks (`chunking_strategy` in the config).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Fixed-size character windows.
+    Chars,
+    /// Split markdown at headings, then by size within long sections.
+    Markdown,
+    /// Pack whole sentences and paragraphs up to the chunk size.
+    Sentence,
+}
+
+impl Strategy {
+    pub fn from_config(config: &AppConfig) -> Result<Self> {
+        match config.chunking_strategy.as_str() {
+            "chars" | "" => Ok(Self::Chars),
+            "markdown" => Ok(Self::Markdown),
+            "sentence" => Ok(Self::Sentence),
+            other => anyhow::bail!(
+                "Unknown chunking_strategy '{}'. Supported values: chars, markdown, sentence",
+                other
+            ),
+        }
+    }
+}
+
+/// What `chunk_size` and `chunk_overlap` count (`chunk_unit` in the config).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Chars,
+    /// Estimated embedding model tokens, see [`tokens`].
+    Tokens,
+}
+
+impl Unit {
+    pub fn from_config(config: &AppConfig) -> Result<Self> {
+        match config.chunk_unit.as_str() {
+            "chars" | "" => Ok(Self::Chars),
+            "tokens" => Ok(Self::Tokens),
+            other => anyhow::bail!(
+                "Unknown chunk_unit '{}'. Supported values: chars, tokens",
+                other
+            ),
+        }
+    }
+
+    pub fn len(self, text: &str) -> usize {
+        match self {
+            Self::Chars => text.chars().count(),
+            Self::Tokens => tokens::count(text),
+        }
+    }
+
+    /// `text` split into the smallest pieces it is cut at, each worth
+    /// [`Unit::weight`] units.
+    fn pieces(self, text: &str) -> Vec<&str> {
+        match self {
+            Self::Chars => text
+                .char_indices()
+                .map(|(i, c)| &text[i..i + c.len_utf8()])
+                .collect(),
+            Self::Tokens => tokens::tokenize(text),
+        }
+    }
+
+    /// Units in one of [`Unit::pieces`].
+    fn weight(self, piece: &str) -> usize {
+        match self {
+            Self::Chars => 1,
+            Self::Tokens => tokens::weight(piece),
+        }
+    }
+
+    /// Cut `text` into consecutive runs of at most `max` units (a piece
+    /// worth more goes alone), returning each run's byte length and units.
+    fn runs(self, text: &str, max: usize) -> Vec<(usize, usize)> {
+        let mut runs = Vec::new();
+        let (mut len, mut units) = (0, 0);
+        for piece in self.pieces(text) {
+            let weight = self.weight(piece);
+            if units > 0 && units + weight > max {
+                runs.push((len, units));
+                (len, units) = (0, 0);
+            }
+            len += piece.len();
+            units += weight;
+        }
+        if units > 0 {
+            runs.push((len, units));
+        }
+        runs
+    }
+}
+
+/// A chunk of a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub text: String,
+    /// Headings the chunk falls under, e.g. "Deployment > Rollback".
+    pub section: Option<String>,
+    /// Bytes of the split text the chunk covers: where `text` was cut
+    /// from, or for code chunks, everything after the `path:line` header.
+    pub range: Range<usize>,
+}
+
+impl Chunk {
+    /// The chunk of `range` of `text`.
+    fn at(text: &str, range: Range<usize>) -> Self {
+        Self {
+            text: text[range.clone()].to_string(),
+            section: None,
+            range,
+        }
+    }
+
+    /// The chunk of `range` of `text` without surrounding whitespace, if
+    /// anything else is left.
+    fn trimmed(text: &str, range: Range<usize>) -> Option<Self> {
+        let slice = &text[range.clone()];
+        let start = range.start + slice.len() - slice.trim_start().len();
+        let end = range.end - (slice.len() - slice.trim_end().len());
+        (start < end).then(|| Self::at(text, start..end))
+    }
+
+    /// The chunk with its range moved `by` bytes, for when the text it was
+    /// cut from starts `by` bytes into the document.
+    fn shifted(mut self, by: usize) -> Self {
+        self.range = self.range.start + by..self.range.end + by;
+        self
+    }
+}
+
+/// Maps the byte ranges of chunks to [`ChunkLocation`]s in the text they
+/// were cut from. Each lookup scans from the previous one, so chunks in
+/// document order cost a single pass over the text.
+pub struct Locator<'a> {
+    text: &'a str,
+    byte: usize,
+    chars: usize,
+    newlines: usize,
+}
+
+impl<'a> Locator<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            byte: 0,
+            chars: 0,
+            newlines: 0,
+        }
+    }
+
+    pub fn locate(&mut self, range: &Range<usize>) -> ChunkLocation {
+        let (start_char, newlines) = self.seek(range.start);
+        let (end_char, _) = self.seek(range.end);
+        ChunkLocation {
+            start_char: Some(start_char as u32),
+            end_char: Some(end_char as u32),
+            start_line: Some(newlines as u32 + 1),
+            converted: false,
+        }
+    }
+
+    /// The number of characters and of newlines before byte `byte`.
+    fn seek(&mut self, byte: usize) -> (usize, usize) {
+        if byte >= self.byte {
+            let skipped = &self.text[self.byte..byte];
+            self.chars += skipped.chars().count();
+            self.newlines += skipped.matches('\n').count();
+        } else {
+            let skipped = &self.text[byte..self.byte];
+            self.chars -= skipped.chars().count();
+            self.newlines -= skipped.matches('\n').count();
+        }
+        self.byte = byte;
+        (self.chars, self.newlines)
+    }
+}
+
+/// Split `text` with the configured strategy. Only markdown input (`.md`
+/// files and markitdown output) is split at headings; anything else falls
+/// back to the character splitter.
+pub fn chunk_document(text: &str, markdown: bool, config: &AppConfig) -> Result<Vec<Chunk>> {
+    let (size, overlap) = (config.chunk_size, config.chunk_overlap);
+    let unit = Unit::from_config(config)?;
+    Ok(match Strategy::from_config(config)? {
+        Strategy::Markdown if markdown => chunk_markdown(text, size, overlap, unit),
+        Strategy::Sentence => chunk_sentences(text, size, overlap, unit),
+        _ => chunk_text(text, size, overlap, unit),
+    })
+}
+
+/// Separator between the pages of text read from a PDF.
+pub const PAGE_BREAK: char = '\u{c}';
+
+/// Split text of pages separated by [`PAGE_BREAK`] one page at a time, so
+/// no chunk spans two pages. Each chunk's section is its page, "Page 3".
+pub fn chunk_pages(text: &str, config: &AppConfig) -> Result<Vec<Chunk>> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    for (i, page) in text.split(PAGE_BREAK).enumerate() {
+        let section = format!("Page {}", i + 1);
+        for chunk in chunk_document(page, false, config)? {
+            chunks.push(Chunk {
+                section: Some(section.clone()),
+                ..chunk.shifted(start)
+            });
+        }
+        start += page.len() + PAGE_BREAK.len_utf8();
+    }
+    Ok(chunks)
+}
+
+/// Split text into chunks of `chunk_size` units, overlapping by `overlap`.
+pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize, unit: Unit) -> Vec<Chunk> {
     if text.is_empty() {
         return vec![];
     }
 
-    let chars: Vec<char> = text.chars().collect();
-    let total = chars.len();
+    let pieces = unit.pieces(text);
+    let total = pieces.len();
 
-    if total <= chunk_size {
-        return vec![text.to_string()];
+    // Byte offset and units before every piece, then at the end of the text.
+    let mut offsets = Vec::with_capacity(total + 1);
+    let mut units = Vec::with_capacity(total + 1);
+    offsets.push(0);
+    units.push(0);
+    for piece in &pieces {
+        offsets.push(offsets[offsets.len() - 1] + piece.len());
+        units.push(units[units.len() - 1] + unit.weight(piece));
+    }
+
+    if units[total] <= chunk_size {
+        return vec![Chunk::at(text, 0..text.len())];
     }
 
     let mut chunks = Vec::new();
     let mut start = 0;
 
     while start < total {
-        let end = (start + chunk_size).min(total);
-        let chunk: String = chars[start..end].iter().collect();
-        chunks.push(chunk.trim().to_string());
+        // The most pieces that fit in chunk_size, and at least one.
+        let end = (units.partition_point(|&u| u <= units[start] + chunk_size) - 1)
+            .clamp(start + 1, total);
+        // Whitespace-only windows are dropped.
+        chunks.extend(Chunk::trimmed(text, offsets[start]..offsets[end]));
 
         if end >= total {
             break;
@@ -29,14 +262,472 @@ pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String>
         } else {
             chunk_size
         };
-        start += step;
+        start = units
+            .partition_point(|&u| u < units[start] + step)
+            .clamp(start + 1, end);
+    }
+
+    chunks
+}
+
+/// Split text into chunks of whole sentences, up to `chunk_size` units
+/// each. Consecutive chunks share trailing sentences totalling at most
+/// `overlap` units. A sentence longer than `chunk_size` is split again at
+/// Unicode (UAX #29) sentence boundaries, which know the terminators of other
+/// scripts, and whatever is still too long is cut into `chunk_size` pieces,
+/// so no chunk exceeds `chunk_size`.
+pub fn chunk_sentences(text: &str, chunk_size: usize, overlap: usize, unit: Unit) -> Vec<Chunk> {
+    let fits = |sentence: &str| unit.len(sentence.trim()) <= chunk_size;
+    // Byte range of each sentence and its length.
+    let mut sentences: Vec<(Range<usize>, usize)> = Vec::new();
+    let mut offset = 0;
+    for sentence in split_sentences(text).into_iter().flat_map(|sentence| {
+        if fits(sentence) {
+            vec![sentence]
+        } else {
+            sentence.split_sentence_bounds().collect()
+        }
+    }) {
+        if fits(sentence) {
+            sentences.push((offset..offset + sentence.len(), unit.len(sentence)));
+        } else {
+            let mut start = offset;
+            for (len, units) in unit.runs(sentence, chunk_size) {
+                sentences.push((start..start + len, units));
+                start += len;
+            }
+        }
+        offset += sentence.len();
+    }
+
+    let mut chunks = Vec::new();
+    // Indices into `sentences` of the chunk being built, and its length.
+    let mut current: std::collections::VecDeque<usize> = Default::default();
+    let mut current_len = 0;
+    for (i, (_, len)) in sentences.iter().enumerate() {
+        if current_len + len > chunk_size && !current.is_empty() {
+            chunks.extend(join_sentences(text, &sentences, &current));
+            // Keep trailing sentences as overlap while they, and the next
+            // sentence, still fit.
+            let mut kept = 0;
+            let mut kept_len = 0;
+            for &j in current.iter().rev() {
+                let next = kept_len + sentences[j].1;
+                if next > overlap || next + len > chunk_size {
+                    break;
+                }
+                kept += 1;
+                kept_len = next;
+            }
+            current.drain(..current.len() - kept);
+            current_len = kept_len;
+        }
+        current.push_back(i);
+        current_len += len;
+    }
+    if !current.is_empty() {
+        chunks.extend(join_sentences(text, &sentences, &current));
+    }
+    chunks
+}
+
+/// The chunk spanning the consecutive `sentences` at `indices`.
+fn join_sentences(
+    text: &str,
+    sentences: &[(Range<usize>, usize)],
+    indices: &std::collections::VecDeque<usize>,
+) -> Option<Chunk> {
+    let (first, last) = (indices.front()?, indices.back()?);
+    Chunk::trimmed(text, sentences[*first].0.start..sentences[*last].0.end)
+}
+
+/// Split `text` after sentence-ending punctuation (`.`, `!` and `?` followed
+/// by whitespace; `。`, `！`, `？` and `．` anywhere) and at blank lines. Each
+/// piece keeps its trailing whitespace and closing quotes, so the pieces
+/// concatenate back to `text`.
+fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let ends = match c {
+            '。' | '！' | '？' | '．' => true,
+            '.' | '!' | '?' => chars
+                .peek()
+                .is_none_or(|(_, next)| next.is_whitespace() || CLOSING_PUNCTUATION.contains(next)),
+            '\n' => chars.peek().is_some_and(|(_, next)| *next == '\n'),
+            _ => false,
+        };
+        if !ends {
+            continue;
+        }
+        let mut end = i + c.len_utf8();
+        // Take closing quotes/brackets, then whitespace, with the sentence.
+        while let Some(&(j, next)) = chars.peek() {
+            if !CLOSING_PUNCTUATION.contains(&next) {
+                break;
+            }
+            end = j + next.len_utf8();
+            chars.next();
+        }
+        while let Some(&(j, next)) = chars.peek() {
+            if !next.is_whitespace() {
+                break;
+            }
+            end = j + next.len_utf8();
+            chars.next();
+        }
+        sentences.push(&text[start..end]);
+        start = end;
+    }
+    if start < text.len() {
+        sentences.push(&text[start..]);
+    }
+    sentences
+}
+
+/// Characters that may follow a sentence's final punctuation.
+const CLOSING_PUNCTUATION: &[char] = &['"', '\'', ')', ']', '」', '』', '）', '”', '’'];
+
+/// Split source code between blocks of lines, preferring a blank line
+/// followed by an unindented line (usually where a function or type starts),
+/// then any blank line, then single lines. Each chunk starts with a
+/// `path:line` header so search snippets can be located; the header counts
+/// towards `chunk_size`. Code chunks don't overlap.
+pub fn chunk_code(text: &str, path: &str, chunk_size: usize, unit: Unit) -> Vec<Chunk> {
+    let total_lines = text.lines().count().max(1);
+    let header_len = unit.len(&format!("{}:{}\n", path, total_lines));
+    let budget = chunk_size.saturating_sub(header_len).max(1);
+
+    let mut pieces = Vec::new();
+    for block in code_blocks(text) {
+        if unit.len(block.text) <= budget {
+            pieces.push(block);
+            continue;
+        }
+        let mut offset = block.offset;
+        for (i, line) in block.text.split_inclusive('\n').enumerate() {
+            let line_no = block.line + i;
+            if unit.len(line) <= budget {
+                pieces.push(CodeBlock {
+                    line: line_no,
+                    offset,
+                    text: line,
+                    top_level: false,
+                });
+            } else {
+                let mut start = 0;
+                for (len, _) in unit.runs(line, budget) {
+                    pieces.push(CodeBlock {
+                        line: line_no,
+                        offset: offset + start,
+                        text: &line[start..start + len],
+                        top_level: false,
+                    });
+                    start += len;
+                }
+            }
+            offset += line.len();
+        }
+    }
+
+    let mut chunks = Vec::new();
+    let mut current: Vec<CodeBlock> = Vec::new();
+    let blocks_len = |blocks: &[CodeBlock]| blocks.iter().map(|b| unit.len(b.text)).sum::<usize>();
+    for piece in pieces {
+        let len = unit.len(piece.text);
+        if !current.is_empty() && blocks_len(&current) + len > budget {
+            // Cut before the last top-level block instead, if that still
+            // fills at least half of this chunk.
+            let cut = current
+                .iter()
+                .rposition(|b| b.top_level)
+                .filter(|&i| i > 0 && blocks_len(&current[..i]) >= budget / 2)
+                .unwrap_or(current.len());
+            let rest = current.split_off(cut);
+            chunks.extend(code_chunk(&current, path));
+            current = rest;
+            if blocks_len(&current) + len > budget {
+                chunks.extend(code_chunk(&current, path));
+                current.clear();
+            }
+        }
+        current.push(piece);
+    }
+    chunks.extend(code_chunk(&current, path));
+    chunks
+}
+
+struct CodeBlock<'a> {
+    /// 1-based line number of the block's first line.
+    line: usize,
+    /// Byte offset of the block in the file.
+    offset: usize,
+    text: &'a str,
+    /// Starts with an unindented line after a blank line.
+    top_level: bool,
+}
+
+/// Lines of `text` cut before every non-blank line that follows a blank
+/// one. Blank lines stay with the block before them.
+fn code_blocks(text: &str) -> Vec<CodeBlock<'_>> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    let mut start_line = 1;
+    let mut offset = 0;
+    let mut after_blank = false;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let blank = line.trim().is_empty();
+        if after_blank && !blank && start < offset {
+            blocks.push(CodeBlock {
+                line: start_line,
+                offset: start,
+                text: &text[start..offset],
+                top_level: is_top_level(&text[start..offset]),
+            });
+            start = offset;
+            start_line = i + 1;
+        }
+        offset += line.len();
+        after_blank = blank;
+    }
+    if start < text.len() {
+        blocks.push(CodeBlock {
+            line: start_line,
+            offset: start,
+            text: &text[start..],
+            top_level: is_top_level(&text[start..]),
+        });
+    }
+    blocks
+}
+
+fn is_top_level(block: &str) -> bool {
+    block.starts_with(|c: char| !c.is_whitespace())
+}
+
+/// Join `blocks` under a `path:line` header, skipping leading blank lines.
+fn code_chunk(blocks: &[CodeBlock], path: &str) -> Option<Chunk> {
+    let first = blocks.first()?;
+    let body: String = blocks.iter().map(|b| b.text).collect();
+    let skipped = body
+        .split_inclusive('\n')
+        .take_while(|line| line.trim().is_empty())
+        .count();
+    let start: usize = body
+        .split_inclusive('\n')
+        .take(skipped)
+        .map(|line| line.len())
+        .sum();
+    let body = body[start..].trim_end();
+    if body.is_empty() {
+        return None;
+    }
+    let body_start = first.offset + start;
+    Some(Chunk {
+        text: format!("{}:{}\n{}", path, first.line + skipped, body),
+        section: None,
+        range: body_start..body_start + body.len(),
+    })
+}
+
+/// Split markdown at ATX headings (`#` .. `######`), recording the heading
+/// path of each section. Sections longer than `chunk_size` are split between
+/// paragraphs, and paragraphs still too long go through [`chunk_text`].
+/// Fenced code blocks are kept whole, even when that makes a chunk longer
+/// than `chunk_size`.
+pub fn chunk_markdown(text: &str, chunk_size: usize, overlap: usize, unit: Unit) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    for section in split_sections(text) {
+        let path = (!section.headings.is_empty()).then(|| section.headings.join(" > "));
+        let body = &text[section.range.clone()];
+        for chunk in split_section(body, chunk_size, overlap, unit) {
+            chunks.push(Chunk {
+                section: path.clone(),
+                ..chunk.shifted(section.range.start)
+            });
+        }
+    }
+    if chunks.is_empty() {
+        // Nothing but headings.
+        return chunk_text(text, chunk_size, overlap, unit);
+    }
+    chunks
+}
+
+struct Section {
+    headings: Vec<String>,
+    /// Bytes of the text in the section, its heading included.
+    range: Range<usize>,
+}
+
+/// Cut `text` before every heading outside a code fence. Sections with
+/// nothing but their heading are dropped; their title still shows up in the
+/// path of the sections below them.
+fn split_sections(text: &str) -> Vec<Section> {
+    let mut sections = Vec::new();
+    // (level, title) of the headings enclosing the current line.
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    // Start of the current section, and of the line being read.
+    let mut start = 0;
+    let mut offset = 0;
+    let mut has_body = false;
+    let mut fence: Option<Fence> = None;
+
+    for line in text.split_inclusive('\n') {
+        let mut is_heading = false;
+        if let Some(open) = &fence {
+            if open.is_closed_by(line) {
+                fence = None;
+            }
+        } else if let Some(open) = Fence::open(line) {
+            fence = Some(open);
+        } else if let Some((level, title)) = heading(line) {
+            if has_body {
+                sections.push(Section {
+                    headings: stack.iter().map(|(_, t)| t.clone()).collect(),
+                    range: start..offset,
+                });
+            }
+            start = offset;
+            has_body = false;
+            while stack.last().is_some_and(|(l, _)| *l >= level) {
+                stack.pop();
+            }
+            stack.push((level, title));
+            is_heading = true;
+        }
+        has_body |= !is_heading && !line.trim().is_empty();
+        offset += line.len();
+    }
+    if has_body {
+        sections.push(Section {
+            headings: stack.into_iter().map(|(_, t)| t).collect(),
+            range: start..text.len(),
+        });
+    }
+    sections
+}
+
+/// Pack the paragraphs of an oversized section into chunks of up to
+/// `chunk_size` units.
+fn split_section(text: &str, chunk_size: usize, overlap: usize, unit: Unit) -> Vec<Chunk> {
+    if unit.len(text.trim()) <= chunk_size {
+        return Chunk::trimmed(text, 0..text.len()).into_iter().collect();
     }
 
-    // Remove empty chunks
-    chunks.retain(|c| !c.is_empty());
+    let mut chunks = Vec::new();
+    // The blocks packed so far. Blocks are contiguous, so this always ends
+    // where the next one starts.
+    let mut current = 0..0;
+    for (block, is_fence) in blocks(text) {
+        let range = current.end..current.end + block.len();
+        let len = unit.len(block);
+        if unit.len(&text[current.clone()]) + len <= chunk_size {
+            current.end = range.end;
+            continue;
+        }
+        chunks.extend(Chunk::trimmed(text, current));
+        if len <= chunk_size || is_fence {
+            current = range;
+        } else {
+            let pieces = chunk_text(block, chunk_size, overlap, unit);
+            chunks.extend(pieces.into_iter().map(|c| c.shifted(range.start)));
+            current = range.end..range.end;
+        }
+    }
+    chunks.extend(Chunk::trimmed(text, current));
     chunks
 }
 
+/// Paragraphs (ending after their trailing blank lines) and fenced code
+/// blocks, flagged `true`, in order. Concatenated they give back `text`.
+fn blocks(text: &str) -> Vec<(&str, bool)> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut fence: Option<Fence> = None;
+    let mut after_blank = false;
+
+    for line in text.split_inclusive('\n') {
+        if let Some(open) = &fence {
+            offset += line.len();
+            if open.is_closed_by(line) {
+                blocks.push((&text[start..offset], true));
+                start = offset;
+                fence = None;
+            }
+            continue;
+        }
+        let blank = line.trim().is_empty();
+        let opens = Fence::open(line);
+        if (opens.is_some() || (after_blank && !blank)) && start < offset {
+            blocks.push((&text[start..offset], false));
+            start = offset;
+        }
+        offset += line.len();
+        fence = opens;
+        after_blank = blank;
+    }
+    if start < text.len() {
+        // An unclosed fence runs to the end of the text.
+        blocks.push((&text[start..], fence.is_some()));
+    }
+    blocks
+}
+
+/// A `` ``` `` or `~~~` fence opening a code block.
+struct Fence {
+    marker: char,
+    len: usize,
+}
+
+impl Fence {
+    fn open(line: &str) -> Option<Self> {
+        let rest = strip_indent(line)?;
+        let marker = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+        let len = rest.chars().take_while(|c| *c == marker).count();
+        // A backtick fence's info string can't contain backticks.
+        if len < 3 || (marker == '`' && rest[len..].contains('`')) {
+            return None;
+        }
+        Some(Self { marker, len })
+    }
+
+    fn is_closed_by(&self, line: &str) -> bool {
+        strip_indent(line).is_some_and(|rest| {
+            let rest = rest.trim_end();
+            rest.chars().count() >= self.len && rest.chars().all(|c| c == self.marker)
+        })
+    }
+}
+
+/// `line` without up to three spaces of indentation; `None` when it is
+/// indented further (an indented code block).
+fn strip_indent(line: &str) -> Option<&str> {
+    let rest = line.trim_start_matches(' ');
+    (line.len() - rest.len() <= 3).then_some(rest)
+}
+
+/// The level and text of an ATX heading line.
+fn heading(line: &str) -> Option<(usize, String)> {
+    let rest = strip_indent(line)?;
+    let level = rest.chars().take_while(|c| *c == '#').count();
+    let after = &rest[level..];
+    if !(1..=6).contains(&level) || !(after.trim().is_empty() || after.starts_with([' ', '\t'])) {
+        return None;
+    }
+    // Drop an optional closing sequence: `## Title ##`.
+    let title = after.trim();
+    let without_closing = title.trim_end_matches('#');
+    let title = if without_closing.is_empty() || without_closing.ends_with([' ', '\t']) {
+        without_closing.trim_end()
+    } else {
+        title
+    };
+    Some((level, title.to_string()))
+}
+
 /// Extract a title from a markdown/text document.
 /// Uses the first heading or first non-empty line.
 pub fn extract_title(text: &str, file_name: &str) -> String {
@@ -60,32 +751,32 @@ mod tests {
 
     #[test]
     fn test_chunk_empty_text() {
-        let chunks = chunk_text("", 100, 20);
+        let chunks = chunk_text("", 100, 20, Unit::Chars);
         assert!(chunks.is_empty());
     }
 
     #[test]
     fn test_chunk_short_text() {
-        let chunks = chunk_text("hello world", 100, 20);
+        let chunks = chunk_text("hello world", 100, 20, Unit::Chars);
         assert_eq!(chunks.len(), 1);
-        assert_eq!(chunks[0], "hello world");
+        assert_eq!(chunks[0].text, "hello world");
     }
 
     #[test]
     fn test_chunk_exact_size() {
         let text = "a".repeat(100);
-        let chunks = chunk_text(&text, 100, 20);
+        let chunks = chunk_text(&text, 100, 20, Unit::Chars);
         assert_eq!(chunks.len(), 1);
     }
 
     #[test]
     fn test_chunk_with_overlap() {
         let text = "a".repeat(300);
-        let chunks = chunk_text(&text, 100, 20);
+        let chunks = chunk_text(&text, 100, 20, Unit::Chars);
         assert!(chunks.len() >= 3);
         // Each chunk should be at most chunk_size characters
         for chunk in &chunks {
-            assert!(chunk.chars().count() <= 100);
+            assert!(chunk.text.chars().count() <= 100);
         }
     }
 
@@ -93,18 +784,366 @@ mod tests {
     fn test_chunk_overlap_larger_than_size() {
         // overlap >= chunk_size should not cause infinite loop
         let text = "a".repeat(300);
-        let chunks = chunk_text(&text, 100, 150);
+        let chunks = chunk_text(&text, 100, 150, Unit::Chars);
         assert!(!chunks.is_empty());
     }
 
     #[test]
     fn test_chunk_japanese_text() {
         let text = "あ".repeat(300);
-        let chunks = chunk_text(&text, 100, 20);
+        let chunks = chunk_text(&text, 100, 20, Unit::Chars);
         assert!(chunks.len() >= 3);
         for chunk in &chunks {
-            assert!(chunk.chars().count() <= 100);
+            assert!(chunk.text.chars().count() <= 100);
+        }
+    }
+
+    // --- chunk_sentences ---
+
+    fn assert_within(chunks: &[Chunk], chunk_size: usize) {
+        assert!(!chunks.is_empty());
+        for chunk in chunks {
+            let chars = chunk.text.chars().count();
+            assert!(chars <= chunk_size, "too long: {:?}", chunk.text);
+        }
+    }
+
+    #[test]
+    fn test_sentences_english() {
+        let text = "The cat sat. The dog ran! Did it rain? \"Yes.\" It did.\n\nNew paragraph here.";
+        assert_eq!(
+            split_sentences(text),
+            vec![
+                "The cat sat. ",
+                "The dog ran! ",
+                "Did it rain? ",
+                "\"Yes.\" ",
+                "It did.\n\n",
+                "New paragraph here."
+            ]
+        );
+        // Numbers and abbreviations without a following space don't split.
+        assert_eq!(
+            split_sentences("Version 1.2 is out."),
+            vec!["Version 1.2 is out."]
+        );
+
+        let chunks = chunk_sentences(text, 30, 15, Unit::Chars);
+        assert_within(&chunks, 30);
+        assert_eq!(chunks[0].text, "The cat sat. The dog ran!");
+        // Overlap is whole trailing sentences.
+        assert!(chunks[1].text.starts_with("The dog ran!"));
+        assert!(chunks
+            .iter()
+            .all(|c| !c.text.starts_with("at") && !c.text.starts_with("og")));
+    }
+
+    #[test]
+    fn test_sentences_japanese() {
+        let text = "今日は晴れです。明日は雨が降るでしょう！「本当ですか？」と聞いた。";
+        assert_eq!(
+            split_sentences(text),
+            vec![
+                "今日は晴れです。",
+                "明日は雨が降るでしょう！",
+                "「本当ですか？」",
+                "と聞いた。"
+            ]
+        );
+        let chunks = chunk_sentences(&text.repeat(5), 40, 10, Unit::Chars);
+        assert_within(&chunks, 40);
+        for chunk in &chunks {
+            assert!(
+                chunk.text.ends_with(['。', '！', '」']),
+                "cut mid-sentence: {}",
+                chunk.text
+            );
+        }
+    }
+
+    #[test]
+    fn test_sentences_mixed() {
+        let text = "Qdrant stores vectors. 検索はハイブリッドです。BM25 comes from Tantivy. 結果はRRFで統合します。"
+            .repeat(4);
+        let chunks = chunk_sentences(&text, 50, 20, Unit::Chars);
+        assert_within(&chunks, 50);
+        let rejoined: String = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert!(rejoined.contains("BM25 comes from Tantivy."));
+    }
+
+    #[test]
+    fn test_sentences_unicode_fallback() {
+        // Devanagari sentences end at a danda, which the rules above miss.
+        let text = "यह पहला वाक्य है। यह दूसरा वाक्य है। यह तीसरा वाक्य है।";
+        assert_eq!(split_sentences(text), vec![text]);
+        let chunks = chunk_sentences(text, 20, 0, Unit::Chars);
+        assert_within(&chunks, 20);
+        assert_eq!(chunks.len(), 3);
+        for chunk in &chunks {
+            assert!(
+                chunk.text.ends_with('।'),
+                "cut mid-sentence: {}",
+                chunk.text
+            );
+        }
+    }
+
+    #[test]
+    fn test_sentences_hard_cap() {
+        let long = "a".repeat(5000);
+        let text = format!("Short one. {} Another short one.", long);
+        let chunks = chunk_sentences(&text, 1000, 200, Unit::Chars);
+        assert_within(&chunks, 1000);
+        let total_a: usize = chunks.iter().map(|c| c.text.matches('a').count()).sum();
+        assert!(total_a >= 5000);
+        assert!(chunk_sentences("", 100, 20, Unit::Chars).is_empty());
+    }
+
+    // --- chunk_unit = "tokens" ---
+
+    #[test]
+    fn test_token_windows() {
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(40);
+        let chunks = chunk_text(&text, 50, 10, Unit::Tokens);
+        assert!(chunks.len() > 5);
+        for chunk in &chunks[..chunks.len() - 1] {
+            let count = tokens::count(&chunk.text);
+            assert!(
+                (45..=50).contains(&count),
+                "{} tokens: {:?}",
+                count,
+                chunk.text
+            );
+        }
+        // Fewer characters fit in the same budget for CJK, at two tokens
+        // each.
+        let japanese = "検索".repeat(200);
+        let chunks = chunk_text(&japanese, 100, 20, Unit::Tokens);
+        assert!(chunks.iter().all(|c| tokens::count(&c.text) <= 100));
+        assert_eq!(chunks[0].text.chars().count(), 50);
+        // Overlapping by 20 tokens is 10 characters.
+        assert_eq!(chunks[1].text.chars().next(), japanese.chars().nth(40));
+        let chunks = chunk_sentences(&japanese, 30, 0, Unit::Tokens);
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert_eq!(tokens::count(&chunk.text), 30, "{:?}", chunk.text);
+        }
+    }
+
+    #[test]
+    fn test_token_budget_in_other_strategies() {
+        let text = "Sentences are packed whole. Each one costs some tokens! ".repeat(20);
+        for chunk in chunk_sentences(&text, 40, 10, Unit::Tokens) {
+            assert!(tokens::count(&chunk.text) <= 40, "{:?}", chunk.text);
+        }
+        let markdown = format!("# Notes\n\n{}", text);
+        for chunk in chunk_markdown(&markdown, 40, 10, Unit::Tokens) {
+            assert!(tokens::count(&chunk.text) <= 40, "{:?}", chunk.text);
+        }
+
+        let mut config = AppConfig {
+            chunk_unit: "tokens".to_string(),
+            chunk_size: 40,
+            ..AppConfig::default()
+        };
+        let chunks = chunk_document(&text, false, &config).unwrap();
+        assert!(chunks.iter().all(|c| tokens::count(&c.text) <= 40));
+        config.chunk_unit = "bytes".to_string();
+        assert!(chunk_document(&text, false, &config).is_err());
+    }
+
+    // --- chunk_code ---
+
+    const CODE: &str = "use std::fmt;\n\nfn first() {\n    let a = 1;\n\n    let b = 2;\n}\n\n/// Docs.\nfn second() {\n    todo!()\n}\n";
+
+    #[test]
+    fn test_code_small_file_is_one_chunk() {
+        let chunks = chunk_code(CODE, "/src/lib.rs", 1000, Unit::Chars);
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].text.starts_with("/src/lib.rs:1\nuse std::fmt;"));
+    }
+
+    #[test]
+    fn test_code_splits_at_function_boundaries() {
+        let chunks = chunk_code(CODE, "a.rs", 70, Unit::Chars);
+        let headers: Vec<&str> = chunks
+            .iter()
+            .map(|c| c.text.lines().next().unwrap())
+            .collect();
+        assert_eq!(headers, vec!["a.rs:1", "a.rs:9"]);
+        assert!(chunks[0].text.ends_with("let b = 2;\n}"));
+        assert!(chunks[1].text.contains("/// Docs.\nfn second()"));
+        for chunk in &chunks {
+            assert!(chunk.text.chars().count() <= 70);
+        }
+    }
+
+    #[test]
+    fn test_code_long_blocks_and_lines() {
+        let body: String = (0..40).map(|i| format!("    call({});\n", i)).collect();
+        let text = format!("fn big() {{\n{}}}\n{}\n", body, "x".repeat(300));
+        let chunks = chunk_code(&text, "big.rs", 100, Unit::Chars);
+        assert!(chunks.len() > 5);
+        for chunk in &chunks {
+            assert!(chunk.text.chars().count() <= 100, "{:?}", chunk.text);
         }
+        // Line numbers follow the lines in each chunk.
+        let second = &chunks[1].text;
+        let (header, first_line) = second.split_once('\n').unwrap();
+        let line: usize = header.rsplit(':').next().unwrap().parse().unwrap();
+        assert_eq!(
+            text.lines().nth(line - 1).unwrap(),
+            first_line.lines().next().unwrap()
+        );
+        assert!(chunk_code("", "empty.rs", 100, Unit::Chars).is_empty());
+    }
+
+    // --- chunk_markdown ---
+
+    fn sections(chunks: &[Chunk]) -> Vec<Option<&str>> {
+        chunks.iter().map(|c| c.section.as_deref()).collect()
+    }
+
+    #[test]
+    fn test_markdown_splits_at_headings() {
+        let text = "Intro text.\n\n# Deployment\n\n## Rollback\n\nUndo it.\n\n## Canary ##\n\nSlowly.\n\n# FAQ\n\nAsk.\n";
+        let chunks = chunk_markdown(text, 1000, 200, Unit::Chars);
+        assert_eq!(
+            sections(&chunks),
+            vec![
+                None,
+                Some("Deployment > Rollback"),
+                Some("Deployment > Canary"),
+                Some("FAQ")
+            ]
+        );
+        assert_eq!(chunks[0].text, "Intro text.");
+        assert_eq!(chunks[1].text, "## Rollback\n\nUndo it.");
+    }
+
+    #[test]
+    fn test_markdown_ignores_headings_in_fences() {
+        let text = "# Setup\n\n```sh\n# not a heading\n```\n\n~~~\n## nor this\n~~~\n";
+        let chunks = chunk_markdown(text, 1000, 200, Unit::Chars);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].section.as_deref(), Some("Setup"));
+        assert!(chunks[0].text.contains("## nor this"));
+    }
+
+    #[test]
+    fn test_markdown_long_section_keeps_fences_whole() {
+        let code = format!("```rust\n{}```\n", "let x = 1;\n".repeat(20));
+        let text = format!(
+            "# Guide\n\n{}\n\n{}\n{}",
+            "word ".repeat(30),
+            code,
+            "あ".repeat(250)
+        );
+        let chunks = chunk_markdown(&text, 100, 20, Unit::Chars);
+        assert!(chunks.len() > 3);
+        assert!(chunks.iter().all(|c| c.section.as_deref() == Some("Guide")));
+        let fenced: Vec<_> = chunks.iter().filter(|c| c.text.contains("```")).collect();
+        assert_eq!(fenced.len(), 1);
+        assert!(fenced[0].text.starts_with("```rust") && fenced[0].text.ends_with("```"));
+        for chunk in chunks.iter().filter(|c| !c.text.contains("```")) {
+            assert!(chunk.text.chars().count() <= 100);
+        }
+    }
+
+    #[test]
+    fn test_markdown_headings_only() {
+        let chunks = chunk_markdown("# Title\n", 1000, 200, Unit::Chars);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].text.trim(), "# Title");
+    }
+
+    #[test]
+    fn test_heading() {
+        assert_eq!(heading("### Title ###\n"), Some((3, "Title".to_string())));
+        assert_eq!(heading("# C#\n"), Some((1, "C#".to_string())));
+        assert_eq!(heading("#hashtag\n"), None);
+        assert_eq!(heading("    # indented code\n"), None);
+        assert_eq!(heading("####### seven\n"), None);
+    }
+
+    #[test]
+    fn test_strategy_from_config() {
+        let mut config = AppConfig::default();
+        assert_eq!(Strategy::from_config(&config).unwrap(), Strategy::Chars);
+        config.chunking_strategy = "markdown".to_string();
+        assert_eq!(Strategy::from_config(&config).unwrap(), Strategy::Markdown);
+        // Non-markdown input still uses the character splitter.
+        let chunks = chunk_document("# A\n\ntext", false, &config).unwrap();
+        assert_eq!(chunks[0].section, None);
+        config.chunking_strategy = "words".to_string();
+        assert!(Strategy::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_chunk_pages() {
+        let config = AppConfig {
+            chunk_size: 20,
+            chunk_overlap: 0,
+            ..AppConfig::default()
+        };
+        let text = format!(
+            "First page.{}{}Third page, which is longer than one chunk.",
+            PAGE_BREAK, PAGE_BREAK
+        );
+        let chunks = chunk_pages(&text, &config).unwrap();
+        assert_eq!(chunks[0].text, "First page.");
+        assert!(chunks.len() > 2);
+        for chunk in &chunks[1..] {
+            assert_eq!(chunk.section.as_deref(), Some("Page 3"));
+            assert!(!chunk.text.contains(PAGE_BREAK));
+        }
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.range.clone()], chunk.text);
+        }
+        assert_eq!(chunks[0].section.as_deref(), Some("Page 1"));
+    }
+
+    // --- ranges ---
+
+    #[test]
+    fn test_chunk_ranges() {
+        let text = "# Guide\n\nIntro.  \n\n## Setup\n\n検索を設定する。 Then run it.\n\n```sh\nmake\n```\n";
+        let unit = Unit::Chars;
+        let split: [Vec<Chunk>; 4] = [
+            chunk_text(text, 20, 5, unit),
+            chunk_sentences(text, 20, 5, unit),
+            chunk_markdown(text, 20, 5, unit),
+            chunk_markdown(text, 1000, 0, unit),
+        ];
+        for chunks in split {
+            assert!(!chunks.is_empty());
+            for chunk in chunks {
+                assert_eq!(&text[chunk.range.clone()], chunk.text);
+            }
+        }
+
+        let chunks = chunk_code(CODE, "a.rs", 70, unit);
+        let (_, body) = chunks[1].text.split_once('\n').unwrap();
+        assert_eq!(&CODE[chunks[1].range.clone()], body);
+    }
+
+    #[test]
+    fn test_locator() {
+        let text = "line one\nzwei: ü\n\nthree";
+        let mut locator = Locator::new(text);
+        let three = text.find("three").unwrap();
+        let location = locator.locate(&(three..text.len()));
+        assert_eq!(location.start_line, Some(4));
+        assert_eq!(location.start_char, Some(18));
+        assert_eq!(location.end_char, Some(23));
+        // Looking back works as well.
+        let u = text.find('ü').unwrap();
+        let location = locator.locate(&(u..u + 'ü'.len_utf8()));
+        assert_eq!(location.start_line, Some(2));
+        assert_eq!(
+            (location.start_char, location.end_char),
+            (Some(15), Some(16))
+        );
+        assert_eq!(locator.locate(&(0..4)).start_line, Some(1));
     }
 
     // --- extract_title ---
@@ -0,0 +1,398 @@
+//! External commands that convert files to text for ingest: the
+//! `[[converters]]` of the config, consulted first, and markitdown.
+//!
+//! A command is split into words like a shell would, but no shell runs it,
+//! so a path with spaces or quotes in it stays a single argument. It
+//! inherits ingest's environment plus its `env` table, gets no stdin, and
+//! its stdout is the document text. Commands that run past their timeout,
+//! or print more than `max_file_size_bytes`, are killed.
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use mcp_hybrid_search_common::config::{AppConfig, ConverterConfig};
+
+/// markitdown had no limit before converters could be configured; this
+/// only stops a conversion that hangs.
+const MARKITDOWN_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How often a running command is checked on.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Bytes of a failed command's stderr kept for its error.
+const MAX_STDERR_BYTES: u64 = 4096;
+
+/// A command's output over `max_file_size_bytes`. Reported as a skip, like
+/// a file that is too large.
+#[derive(Debug, thiserror::Error)]
+#[error("{path}: {converter} output is over max_file_size_bytes ({limit})")]
+pub struct OutputTooLarge {
+    path: String,
+    converter: String,
+    limit: u64,
+}
+
+/// A command that converts files to text.
+#[derive(Debug, Clone)]
+pub struct Converter {
+    /// The program's file name, recorded as the chunks' `converter`.
+    pub name: String,
+    args: Vec<String>,
+    timeout: Duration,
+    env: BTreeMap<String, String>,
+}
+
+impl Converter {
+    /// A converter running `command`, split into words.
+    pub fn new(command: &str, timeout: Duration) -> Result<Self> {
+        let args =
+            split_command(command).with_context(|| format!("Invalid command '{}'", command))?;
+        let Some(program) = args.first() else {
+            anyhow::bail!("empty command");
+        };
+        Ok(Self {
+            name: program_name(program),
+            args,
+            timeout,
+            env: BTreeMap::new(),
+        })
+    }
+
+    pub fn from_config(config: &ConverterConfig) -> Result<Self> {
+        let converter = Self::new(&config.command, Duration::from_secs(config.timeout_secs))
+            .with_context(|| format!("Converter for {}", config.extensions.join(", ")))?;
+        Ok(Self {
+            env: config.env.clone(),
+            ..converter
+        })
+    }
+
+    pub fn markitdown() -> Self {
+        Self {
+            name: "markitdown".to_string(),
+            args: vec!["markitdown".to_string()],
+            timeout: MARKITDOWN_TIMEOUT,
+            env: BTreeMap::new(),
+        }
+    }
+
+    /// Convert the file at `path`, failing if the command exits non-zero,
+    /// times out or prints nothing. Output over `max_bytes` is an
+    /// [`OutputTooLarge`] error.
+    pub fn run(&self, path: &str, max_bytes: u64) -> Result<String> {
+        tracing::info!("Converting with {}: {}", self.name, path);
+        let mut args = self.args.clone();
+        if args.iter().any(|arg| arg.contains("{path}")) {
+            for arg in &mut args {
+                *arg = arg.replace("{path}", path);
+            }
+        } else {
+            args.push(path.to_string());
+        }
+
+        let mut child = Command::new(&args[0])
+            .args(&args[1..])
+            .envs(&self.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to run {}: {}", self.name, e))?;
+
+        // Read both pipes while waiting, so a chatty command can't block on
+        // a full pipe.
+        let over_limit = Arc::new(AtomicBool::new(false));
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stdout = {
+            let over_limit = Arc::clone(&over_limit);
+            std::thread::spawn(move || {
+                let mut out = Vec::new();
+                let read = stdout.take(max_bytes + 1).read_to_end(&mut out);
+                if out.len() as u64 > max_bytes {
+                    over_limit.store(true, Ordering::Relaxed);
+                }
+                read.map(|_| out)
+            })
+        };
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let stderr = std::thread::spawn(move || {
+            let mut err = Vec::new();
+            let mut stderr = stderr;
+            stderr
+                .by_ref()
+                .take(MAX_STDERR_BYTES)
+                .read_to_end(&mut err)
+                .ok();
+            // Drain the rest so the command doesn't block on it.
+            std::io::copy(&mut stderr, &mut std::io::sink()).ok();
+            err
+        });
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if over_limit.load(Ordering::Relaxed) {
+                child.kill().ok();
+                child.wait().ok();
+                return Err(OutputTooLarge {
+                    path: path.to_string(),
+                    converter: self.name.clone(),
+                    limit: max_bytes,
+                }
+                .into());
+            }
+            if Instant::now() >= deadline {
+                child.kill().ok();
+                child.wait().ok();
+                anyhow::bail!(
+                    "{} timed out after {}s for {}",
+                    self.name,
+                    self.timeout.as_secs(),
+                    path
+                );
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        };
+
+        let stdout = stdout
+            .join()
+            .map_err(|_| anyhow::anyhow!("reading {} output failed", self.name))??;
+        let stderr = stderr.join().unwrap_or_default();
+        // Checked first: a command cut off at the limit may die of SIGPIPE.
+        if stdout.len() as u64 > max_bytes {
+            return Err(OutputTooLarge {
+                path: path.to_string(),
+                converter: self.name.clone(),
+                limit: max_bytes,
+            }
+            .into());
+        }
+        if !status.success() {
+            anyhow::bail!(
+                "{} failed for {} ({}): {}",
+                self.name,
+                path,
+                status,
+                String::from_utf8_lossy(&stderr).trim()
+            );
+        }
+        let text = String::from_utf8_lossy(&stdout).into_owned();
+        if text.trim().is_empty() {
+            anyhow::bail!("{} returned empty output for {}", self.name, path);
+        }
+        Ok(text)
+    }
+}
+
+/// The first `[[converters]]` entry of `config` for files with extension
+/// `ext`.
+pub fn configured<'a>(config: &'a AppConfig, ext: &str) -> Option<&'a ConverterConfig> {
+    config.converters.iter().find(|converter| {
+        converter
+            .extensions
+            .iter()
+            .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(ext))
+    })
+}
+
+/// The converter for files with extension `ext`, if one is configured.
+pub fn find(config: &AppConfig, ext: &str) -> Result<Option<Converter>> {
+    configured(config, ext)
+        .map(Converter::from_config)
+        .transpose()
+}
+
+/// Fail on a `[[converters]]` entry whose command can't be parsed.
+pub fn check(config: &AppConfig) -> Result<()> {
+    for converter in &config.converters {
+        Converter::from_config(converter)?;
+    }
+    Ok(())
+}
+
+/// Whether markitdown is in PATH.
+pub fn markitdown_installed() -> bool {
+    Command::new("markitdown").arg("--help").output().is_ok()
+}
+
+fn program_name(program: &str) -> String {
+    Path::new(program)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| program.to_string())
+}
+
+/// Split a command line into words: whitespace separates words, single
+/// quotes keep everything literally, double quotes keep whitespace, and a
+/// backslash escapes the next character outside single quotes.
+fn split_command(command: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => anyhow::bail!("unterminated single quote"),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => anyhow::bail!("unterminated double quote"),
+                        },
+                        Some(c) => word.push(c),
+                        None => anyhow::bail!("unterminated double quote"),
+                    }
+                }
+            }
+            '\\' => {
+                let escaped = chars.next().context("trailing backslash")?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn converter(command: &str, timeout_secs: u64) -> Converter {
+        Converter::from_config(&ConverterConfig {
+            extensions: vec!["dwg".to_string()],
+            command: command.to_string(),
+            timeout_secs,
+            env: BTreeMap::from([("GREETING".to_string(), "hello from env".to_string())]),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command(r#"ourtool --stdout {path}"#).unwrap(),
+            vec!["ourtool", "--stdout", "{path}"]
+        );
+        assert_eq!(
+            split_command(r#"  tool 'a b' "c \"d\"" e\ f '' "#).unwrap(),
+            vec!["tool", "a b", "c \"d\"", "e f", ""]
+        );
+        assert!(split_command("tool 'open").is_err());
+        assert!(split_command("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_run() {
+        let dir = std::env::temp_dir().join(format!("convert-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("floor plan.dwg");
+        std::fs::write(&file, "# Floor plan\n\nLevel 2").unwrap();
+        let path = file.to_string_lossy();
+
+        // The path is one argument, spaces and all.
+        let cat = converter("cat {path}", 10);
+        assert_eq!(cat.name, "cat");
+        assert_eq!(cat.run(&path, 1 << 20).unwrap(), "# Floor plan\n\nLevel 2");
+        // Appended when the command doesn't place it.
+        assert_eq!(
+            converter("/bin/cat", 10).run(&path, 1 << 20).unwrap().len(),
+            21
+        );
+
+        let env = converter(r#"sh -c 'printf "$GREETING: %s" "$0"' {path}"#, 10);
+        assert_eq!(
+            env.run(&path, 1 << 20).unwrap(),
+            format!("hello from env: {}", path)
+        );
+
+        let err = converter("sh -c 'echo broken >&2; exit 3'", 10)
+            .run(&path, 1 << 20)
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("sh failed for"), "{}", message);
+        assert!(message.contains("broken"), "{}", message);
+
+        let err = converter("sh -c 'exit 0'", 10)
+            .run(&path, 1 << 20)
+            .unwrap_err();
+        assert!(err.to_string().contains("empty output"), "{}", err);
+
+        let err = converter("sh -c 'sleep 5'", 1)
+            .run(&path, 1 << 20)
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out after 1s"), "{}", err);
+
+        let err = converter("yes", 10).run(&path, 100).unwrap_err();
+        assert!(err.is::<OutputTooLarge>(), "{}", err);
+
+        let err = converter("no-such-converter-tool", 10)
+            .run(&path, 100)
+            .unwrap_err();
+        assert!(err.to_string().contains("Failed to run"), "{}", err);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_find() {
+        let config = AppConfig {
+            converters: vec![
+                ConverterConfig {
+                    extensions: vec![".DWG".to_string(), "one".to_string()],
+                    command: "ourtool --stdout {path}".to_string(),
+                    timeout_secs: 30,
+                    env: BTreeMap::new(),
+                },
+                ConverterConfig {
+                    extensions: vec!["one".to_string()],
+                    command: "other".to_string(),
+                    timeout_secs: 30,
+                    env: BTreeMap::new(),
+                },
+            ],
+            ..AppConfig::default()
+        };
+        assert_eq!(find(&config, "dwg").unwrap().unwrap().name, "ourtool");
+        assert_eq!(find(&config, "one").unwrap().unwrap().name, "ourtool");
+        assert!(find(&config, "pdf").unwrap().is_none());
+        assert!(check(&config).is_ok());
+
+        let broken = AppConfig {
+            converters: vec![ConverterConfig {
+                command: "  ".to_string(),
+                ..config.converters[0].clone()
+            }],
+            ..AppConfig::default()
+        };
+        let err = check(&broken).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "Converter for .DWG, one: empty command"
+        );
+    }
+}
@@ -1,195 +1,171 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
 use anyhow::Result;
 use mcp_hybrid_search_common::config::AppConfig;
-use serde::{Deserialize, Serialize};
+use mcp_hybrid_search_common::embedding::{self, Purpose};
+
+use crate::embedding_cache::{self, EmbeddingCache};
+use crate::rate_limit::RateLimiter;
+use crate::tokens;
 
-/// Get embedding for a single text, dispatching based on config.embedding_provider.
-pub async fn get_embedding(config: &AppConfig, text: &str) -> Result<Vec<f32>> {
-    let embeddings = get_embeddings(config, &[text.to_string()]).await?;
-    embeddings
+/// Get embedding for a search query, dispatching based on config.embedding_provider.
+/// Queries bypass the cache and the rate limits.
+pub async fn get_query_embedding(config: &AppConfig, text: &str) -> Result<Vec<f32>> {
+    embedding::embed(config, Purpose::Query, &[text.to_string()])
+        .await?
         .into_iter()
         .next()
         .ok_or_else(|| anyhow::anyhow!("No embedding returned"))
 }
 
-/// Get embeddings for multiple texts, dispatching based on config.embedding_provider.
+/// Get embeddings for documents being ingested, dispatching based on
+/// config.embedding_provider. Texts in the embedding cache are not sent to
+/// the provider.
 pub async fn get_embeddings(config: &AppConfig, texts: &[String]) -> Result<Vec<Vec<f32>>> {
-    match config.embedding_provider.as_str() {
-        "openai" => get_embeddings_openai(config, texts).await,
-        "gemini" => get_embeddings_gemini(config, texts).await,
-        "local" => get_embeddings_local(config, texts),
-        other => anyhow::bail!(
-            "Unknown embedding_provider '{}'. Supported: openai, gemini, local",
-            other
-        ),
-    }
-}
-
-// --- OpenAI provider ---
-
-#[derive(Serialize)]
-struct EmbeddingRequest {
-    model: String,
-    input: Vec<String>,
-}
-
-#[derive(Deserialize)]
-struct EmbeddingResponse {
-    data: Vec<EmbeddingData>,
-}
-
-#[derive(Deserialize)]
-struct EmbeddingData {
-    embedding: Vec<f32>,
-}
-
-async fn get_embeddings_openai(config: &AppConfig, texts: &[String]) -> Result<Vec<Vec<f32>>> {
-    let api_key = std::env::var("OPENAI_API_KEY")
-        .map_err(|_| anyhow::anyhow!("OPENAI_API_KEY environment variable not set"))?;
-
-    let base_url =
-        std::env::var("OPENAI_API_BASE").unwrap_or_else(|_| "https://api.openai.com/v1".into());
-
-    let client = reqwest::Client::new();
-    let request = EmbeddingRequest {
-        model: config.embedding_model.clone(),
-        input: texts.to_vec(),
+    let truncated = truncate_to_token_limit(texts, config.embedding_max_tokens);
+    let texts = truncated.as_deref().unwrap_or(texts);
+    let Some(cache) = cache(config) else {
+        return request_embeddings(config, texts).await;
     };
 
-    let response = client
-        .post(format!("{}/embeddings", base_url))
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        anyhow::bail!("OpenAI API error ({}): {}", status, body);
+    let keys: Vec<embedding_cache::Key> = texts
+        .iter()
+        .map(|t| embedding_cache::key(config, t))
+        .collect();
+    let mut vectors: Vec<Option<Vec<f32>>> = keys
+        .iter()
+        .map(|key| {
+            cache.get(key).unwrap_or_else(|e| {
+                tracing::warn!("Embedding cache read failed: {:#}", e);
+                None
+            })
+        })
+        .collect();
+    let misses: Vec<usize> = (0..texts.len()).filter(|&i| vectors[i].is_none()).collect();
+    if !misses.is_empty() {
+        let uncached: Vec<String> = misses.iter().map(|&i| texts[i].clone()).collect();
+        let embeddings = request_embeddings(config, &uncached).await?;
+        for (i, embedding) in misses.into_iter().zip(embeddings) {
+            if let Err(e) = cache.put(keys[i], &embedding) {
+                tracing::warn!("Embedding cache write failed: {:#}", e);
+            }
+            vectors[i] = Some(embedding);
+        }
     }
-
-    let resp: EmbeddingResponse = response.json().await?;
-    Ok(resp.data.into_iter().map(|d| d.embedding).collect())
-}
-
-// --- Gemini provider ---
-
-#[derive(Serialize)]
-struct GeminiBatchEmbedRequest {
-    requests: Vec<GeminiEmbedRequest>,
-}
-
-#[derive(Serialize)]
-struct GeminiEmbedRequest {
-    model: String,
-    content: GeminiContent,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    output_dimensionality: Option<usize>,
-}
-
-#[derive(Serialize)]
-struct GeminiContent {
-    parts: Vec<GeminiPart>,
+    Ok(vectors.into_iter().flatten().collect())
+}
+
+/// The embedding cache, opened on first use, or `None` when it's off or
+/// can't be opened (embedding then works without it).
+fn cache(config: &AppConfig) -> Option<&'static EmbeddingCache> {
+    static CACHE: OnceLock<Option<EmbeddingCache>> = OnceLock::new();
+    CACHE
+        .get_or_init(|| {
+            if !config.embedding_cache {
+                return None;
+            }
+            EmbeddingCache::open(&config.embedding_cache_path())
+                .inspect_err(|e| tracing::warn!("Embedding cache disabled: {:#}", e))
+                .ok()
+        })
+        .as_ref()
 }
 
-#[derive(Serialize)]
-struct GeminiPart {
-    text: String,
+/// Send `texts` to the configured provider, within the rate limits.
+async fn request_embeddings(config: &AppConfig, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    rate_limiter(config)
+        .acquire(texts.iter().map(|t| tokens::count(t)).sum())
+        .await;
+    embedding::embed(config, Purpose::Document, texts).await
 }
 
-#[derive(Deserialize)]
-struct GeminiBatchEmbedResponse {
-    embeddings: Vec<GeminiEmbeddingValues>,
+/// The limiter every embedding request of the process goes through, set up
+/// from `config` on first use.
+fn rate_limiter(config: &AppConfig) -> &'static RateLimiter {
+    static LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+    LIMITER.get_or_init(|| {
+        let (requests, tokens) = rate_limits(config);
+        RateLimiter::new(requests, tokens)
+    })
 }
 
-#[derive(Deserialize)]
-struct GeminiEmbeddingValues {
-    values: Vec<f32>,
+/// Time embedding requests have spent waiting on the rate limits so far.
+pub fn rate_limit_wait(config: &AppConfig) -> Duration {
+    rate_limiter(config).waited()
 }
 
-async fn get_embeddings_gemini(config: &AppConfig, texts: &[String]) -> Result<Vec<Vec<f32>>> {
-    let api_key = std::env::var("GEMINI_API_KEY")
-        .map_err(|_| anyhow::anyhow!("GEMINI_API_KEY environment variable not set"))?;
-
-    let base_url = std::env::var("GEMINI_API_BASE")
-        .unwrap_or_else(|_| "https://generativelanguage.googleapis.com/v1beta".into());
-
-    let model = &config.embedding_model;
-    let model_path = if model.starts_with("models/") {
-        model.clone()
-    } else {
-        format!("models/{}", model)
+/// Requests and tokens per minute to send at most (0: unlimited): the
+/// configured limits, else the provider's usual ones.
+fn rate_limits(config: &AppConfig) -> (u32, u32) {
+    let (requests, tokens) = match config.embedding_provider.as_str() {
+        "openai" => (3000, 1_000_000),
+        "gemini" => (1500, 0),
+        _ => (0, 0),
     };
+    (
+        config.embedding_requests_per_minute.unwrap_or(requests),
+        config.embedding_tokens_per_minute.unwrap_or(tokens),
+    )
+}
 
-    let requests: Vec<GeminiEmbedRequest> = texts
+/// Copies of `texts` cut to `max_tokens` (estimated) tokens, or `None` when
+/// they all fit. The stored chunk text is left untouched; only the embedding
+/// input is shortened.
+fn truncate_to_token_limit(texts: &[String], max_tokens: usize) -> Option<Vec<String>> {
+    if max_tokens == 0 || texts.iter().all(|t| tokens::count(t) <= max_tokens) {
+        return None;
+    }
+    let mut affected = 0;
+    let truncated = texts
         .iter()
-        .map(|t| GeminiEmbedRequest {
-            model: model_path.clone(),
-            content: GeminiContent {
-                parts: vec![GeminiPart { text: t.clone() }],
-            },
-            output_dimensionality: Some(config.embedding_dimension),
+        .map(|t| {
+            let cut = tokens::truncate(t, max_tokens);
+            if cut.len() < t.len() {
+                affected += 1;
+            }
+            cut.to_string()
         })
         .collect();
-
-    let batch_request = GeminiBatchEmbedRequest { requests };
-
-    let client = reqwest::Client::new();
-    let url = format!("{}/{}:batchEmbedContents", base_url, model_path);
-
-    let response = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("x-goog-api-key", &api_key)
-        .json(&batch_request)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        anyhow::bail!("Gemini API error ({}): {}", status, body);
+    // Ingest has logged which chunks these are.
+    tracing::debug!(
+        "Truncated {} of {} texts to embedding_max_tokens = {} before embedding",
+        affected,
+        texts.len(),
+        max_tokens
+    );
+    Some(truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limits() {
+        assert_eq!(rate_limits(&AppConfig::default()), (3000, 1_000_000));
+        let gemini = AppConfig {
+            embedding_provider: "gemini".to_string(),
+            embedding_tokens_per_minute: Some(50_000),
+            ..AppConfig::default()
+        };
+        assert_eq!(rate_limits(&gemini), (1500, 50_000));
+        let disabled = AppConfig {
+            embedding_requests_per_minute: Some(0),
+            embedding_tokens_per_minute: Some(0),
+            ..AppConfig::default()
+        };
+        assert_eq!(rate_limits(&disabled), (0, 0));
     }
 
-    let resp: GeminiBatchEmbedResponse = response.json().await?;
-    Ok(resp.embeddings.into_iter().map(|e| e.values).collect())
-}
-
-// --- Local provider (fastembed) ---
-
-#[cfg(feature = "local-embed")]
-fn get_embeddings_local(config: &AppConfig, texts: &[String]) -> Result<Vec<Vec<f32>>> {
-    use fastembed::{InitOptions, TextEmbedding};
-
-    let model_type = resolve_local_model(&config.embedding_model)?;
-    let mut model = TextEmbedding::try_new(InitOptions::new(model_type))?;
+    #[test]
+    fn test_truncate_to_token_limit() {
+        let texts = vec!["short".to_string(), "word ".repeat(50)];
+        assert!(truncate_to_token_limit(&texts, 0).is_none());
+        assert!(truncate_to_token_limit(&texts, 100).is_none());
 
-    // E5 models expect "passage: " prefix for documents and "query: " for queries.
-    // During ingest we treat all texts as passages.
-    let prefixed: Vec<String> = texts.iter().map(|t| format!("passage: {}", t)).collect();
-
-    let embeddings = model.embed(prefixed, None)?;
-    Ok(embeddings)
-}
-
-#[cfg(feature = "local-embed")]
-fn resolve_local_model(model_name: &str) -> Result<fastembed::EmbeddingModel> {
-    use fastembed::EmbeddingModel;
-    match model_name {
-        "multilingual-e5-small" => Ok(EmbeddingModel::MultilingualE5Small),
-        "multilingual-e5-base" => Ok(EmbeddingModel::MultilingualE5Base),
-        _ => anyhow::bail!(
-            "Unknown local embedding model '{}'. Supported: multilingual-e5-small, multilingual-e5-base",
-            model_name
-        ),
+        let truncated = truncate_to_token_limit(&texts, 10).unwrap();
+        assert_eq!(truncated[0], "short");
+        assert_eq!(tokens::count(&truncated[1]), 10);
     }
 }
-
-#[cfg(not(feature = "local-embed"))]
-fn get_embeddings_local(_config: &AppConfig, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
-    anyhow::bail!(
-        "embedding_provider = \"local\" requires the 'local-embed' feature. \
-         Build with: cargo build --features local-embed"
-    )
-}
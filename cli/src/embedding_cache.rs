@@ -0,0 +1,253 @@
+//! On-disk cache of embeddings, so text embedded before (re-chunking with a
+//! different `chunk_overlap` leaves most chunks byte-identical) isn't sent
+//! to the provider again.
+//!
+//! Vectors are keyed by the SHA-256 of the provider, model, dimension,
+//! document prefix and embedded text, and appended to a single file of records: the key, the
+//! vector's length and its `f32`s. Opening the file reads only the keys and
+//! where their vectors are; a hit reads its vector from disk. A record cut
+//! short by an interrupted write is dropped on the next open.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use mcp_hybrid_search_common::config::AppConfig;
+use mcp_hybrid_search_common::embedding::{input_prefix, Purpose};
+use sha2::{Digest, Sha256};
+
+/// Start of a cache file, with the format's version.
+const MAGIC: &[u8; 8] = b"RAGEMB1\n";
+
+pub type Key = [u8; 32];
+
+/// The key `text` is cached under for the configured provider and model.
+pub fn key(config: &AppConfig, text: &str) -> Key {
+    let mut data = Vec::with_capacity(text.len() + 64);
+    for part in [
+        config.embedding_provider.as_str(),
+        config.embedding_model.as_str(),
+        &config.embedding_dimension.to_string(),
+        input_prefix(config, Purpose::Document),
+    ] {
+        data.extend_from_slice(part.as_bytes());
+        data.push(0);
+    }
+    // A model loaded from a file is keyed by the file, not the model name.
+    if let (Some(path), "local") = (&config.local_model_path, config.embedding_provider.as_str()) {
+        data.extend_from_slice(path.as_bytes());
+        data.push(0);
+    }
+    data.extend_from_slice(text.as_bytes());
+    Sha256::digest(&data).into()
+}
+
+/// Where a cached vector is in the file.
+#[derive(Clone, Copy)]
+struct Entry {
+    offset: u64,
+    len: u32,
+}
+
+struct Store {
+    file: File,
+    index: HashMap<Key, Entry>,
+    /// Where the next record goes.
+    end: u64,
+}
+
+/// The cache file, shared by the concurrent requests of a run.
+pub struct EmbeddingCache {
+    path: PathBuf,
+    store: Mutex<Store>,
+}
+
+/// What `ragctl cache stats` reports.
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+impl EmbeddingCache {
+    /// Open the cache at `path`, creating it if needed.
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Could not open embedding cache {}", path.display()))?;
+        if file.metadata()?.len() < MAGIC.len() as u64 {
+            file.set_len(0)?;
+            file.write_all(MAGIC)?;
+            file.seek(SeekFrom::Start(0))?;
+        }
+        let (index, end) = load(&mut file)
+            .with_context(|| format!("Could not read embedding cache {}", path.display()))?;
+        if end < file.metadata()?.len() {
+            file.set_len(end)?;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            store: Mutex::new(Store { file, index, end }),
+        })
+    }
+
+    /// The vector cached under `key`, if any.
+    pub fn get(&self, key: &Key) -> Result<Option<Vec<f32>>> {
+        let mut store = self.store.lock().unwrap();
+        let Some(entry) = store.index.get(key).copied() else {
+            return Ok(None);
+        };
+        let mut bytes = vec![0u8; entry.len as usize * 4];
+        store.file.seek(SeekFrom::Start(entry.offset))?;
+        store.file.read_exact(&mut bytes)?;
+        Ok(Some(
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        ))
+    }
+
+    /// Cache `vector` under `key`.
+    pub fn put(&self, key: Key, vector: &[f32]) -> Result<()> {
+        let mut record = Vec::with_capacity(36 + vector.len() * 4);
+        record.extend_from_slice(&key);
+        record.extend_from_slice(&(vector.len() as u32).to_le_bytes());
+        for value in vector {
+            record.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut store = self.store.lock().unwrap();
+        let offset = store.end;
+        store.file.seek(SeekFrom::Start(offset))?;
+        // One write, so a crash leaves at most one partial record at the end.
+        store.file.write_all(&record)?;
+        store.end += record.len() as u64;
+        store.index.insert(
+            key,
+            Entry {
+                offset: offset + 36,
+                len: vector.len() as u32,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Result<CacheStats> {
+        let store = self.store.lock().unwrap();
+        Ok(CacheStats {
+            entries: store.index.len(),
+            bytes: std::fs::metadata(&self.path)?.len(),
+        })
+    }
+}
+
+/// The index of the records in `file`, and where the last complete one
+/// ends. A file that isn't a cache of this version is an error, not
+/// overwritten.
+fn load(file: &mut File) -> Result<(HashMap<Key, Entry>, u64)> {
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let mut index = HashMap::new();
+    if !bytes.starts_with(MAGIC) {
+        anyhow::bail!("not an embedding cache (delete it or run `ragctl cache clear`)");
+    }
+    let mut pos = MAGIC.len();
+    while pos + 36 <= bytes.len() {
+        let key: Key = bytes[pos..pos + 32].try_into().unwrap();
+        let len = u32::from_le_bytes(bytes[pos + 32..pos + 36].try_into().unwrap());
+        let end = pos + 36 + len as usize * 4;
+        if end > bytes.len() {
+            break;
+        }
+        index.insert(
+            key,
+            Entry {
+                offset: (pos + 36) as u64,
+                len,
+            },
+        );
+        pos = end;
+    }
+    Ok((index, pos as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let dir = std::env::temp_dir().join(format!("embedding-cache-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("embedding_cache.bin");
+        let config = AppConfig::default();
+        let (a, b) = (key(&config, "alpha"), key(&config, "beta"));
+
+        let cache = EmbeddingCache::open(&path).unwrap();
+        assert!(cache.get(&a).unwrap().is_none());
+        cache.put(a, &[0.5, -1.25, 3.0]).unwrap();
+        cache.put(b, &[2.0]).unwrap();
+        assert_eq!(cache.get(&a).unwrap().unwrap(), vec![0.5, -1.25, 3.0]);
+        drop(cache);
+
+        // Reopened, with a partial record from an interrupted write.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[7u8; 40]).unwrap();
+        let cache = EmbeddingCache::open(&path).unwrap();
+        assert_eq!(cache.stats().unwrap().entries, 2);
+        assert_eq!(cache.get(&b).unwrap().unwrap(), vec![2.0]);
+        let c = key(&config, "gamma");
+        cache.put(c, &[1.0, 1.0]).unwrap();
+        drop(cache);
+        let cache = EmbeddingCache::open(&path).unwrap();
+        assert_eq!(cache.get(&c).unwrap().unwrap(), vec![1.0, 1.0]);
+        let stats = cache.stats().unwrap();
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.bytes, 8 + (36 + 12) + (36 + 4) + (36 + 8));
+
+        // Another file is left alone.
+        std::fs::write(&path, "not a cache").unwrap();
+        assert!(EmbeddingCache::open(&path).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_key() {
+        let config = AppConfig::default();
+        assert_eq!(key(&config, "text"), key(&config, "text"));
+        assert_ne!(key(&config, "text"), key(&config, "text "));
+        let other_model = AppConfig {
+            embedding_model: "text-embedding-3-large".to_string(),
+            ..AppConfig::default()
+        };
+        assert_ne!(key(&config, "text"), key(&other_model, "text"));
+        let other_dimension = AppConfig {
+            embedding_dimension: 768,
+            ..AppConfig::default()
+        };
+        assert_ne!(key(&config, "text"), key(&other_dimension, "text"));
+        let local = AppConfig {
+            embedding_provider: "local".to_string(),
+            ..AppConfig::default()
+        };
+        let fine_tuned = AppConfig {
+            local_model_path: Some("/models/tuned.onnx".to_string()),
+            ..local.clone()
+        };
+        assert_ne!(key(&local, "text"), key(&fine_tuned, "text"));
+        let prefixed = AppConfig {
+            embedding_document_prefix: Some("search_document: ".to_string()),
+            ..AppConfig::default()
+        };
+        assert_ne!(key(&config, "text"), key(&prefixed, "text"));
+    }
+}
@@ -0,0 +1,177 @@
+//! Encoding detection for text files.
+//!
+//! A byte order mark decides first, then UTF-16 is recognized by the zero
+//! bytes of its ASCII characters, and UTF-8 is taken as it is, even with
+//! the odd malformed sequence.
+//! Anything else is left to `chardetng`, which guesses among the legacy
+//! encodings browsers support (Shift_JIS, EUC-KR, GBK, windows-1252, ...).
+//! `ingest_default_encoding` overrides the guess for corpora it gets wrong.
+
+use anyhow::Result;
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use mcp_hybrid_search_common::config::AppConfig;
+
+/// Byte pairs looked at to recognize UTF-16 without a byte order mark.
+const UTF16_SAMPLE_PAIRS: usize = 512;
+
+/// Valid multibyte characters needed per malformed sequence for bytes to
+/// still be taken as UTF-8.
+const MULTIBYTE_PER_INVALID: usize = 50;
+
+/// The encoding `ingest_default_encoding` names, if set.
+pub fn configured(config: &AppConfig) -> Result<Option<&'static Encoding>> {
+    let Some(label) = config.ingest_default_encoding.as_deref() else {
+        return Ok(None);
+    };
+    match Encoding::for_label(label.trim().as_bytes()) {
+        Some(encoding) => Ok(Some(encoding)),
+        None => anyhow::bail!("Unknown ingest_default_encoding '{}'", label),
+    }
+}
+
+/// Decode `bytes` with the encoding of their byte order mark, else
+/// `forced`, else the detected one, replacing malformed sequences.
+pub fn decode(bytes: &[u8], forced: Option<&'static Encoding>) -> (String, &'static Encoding) {
+    let encoding = match Encoding::for_bom(bytes) {
+        Some((encoding, _)) => encoding,
+        None => forced.unwrap_or_else(|| detect(bytes)),
+    };
+    let (text, _) = encoding.decode_with_bom_removal(bytes);
+    (text.into_owned(), encoding)
+}
+
+/// The encoding `bytes` are most likely in.
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if let Some(encoding) = utf16(bytes) {
+        return encoding;
+    }
+    if mostly_utf8(bytes) {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+    detector.feed(bytes, true);
+    detector.guess(None, Utf8Detection::Allow)
+}
+
+/// Valid UTF-8, or UTF-8 with a few malformed sequences among many valid
+/// multibyte characters, such as a file cut off mid-character. Legacy
+/// encodings hardly ever form valid multibyte UTF-8, so they are left to
+/// `chardetng`, which would not pick UTF-8 once it saw an error.
+fn mostly_utf8(bytes: &[u8]) -> bool {
+    let (mut multibyte, mut invalid) = (0, 0);
+    for chunk in bytes.utf8_chunks() {
+        multibyte += chunk.valid().chars().filter(|c| !c.is_ascii()).count();
+        invalid += usize::from(!chunk.invalid().is_empty());
+    }
+    invalid * MULTIBYTE_PER_INVALID <= multibyte
+}
+
+/// UTF-16 without a byte order mark, recognized by the zero high bytes of
+/// its ASCII characters: mostly in the odd bytes for little-endian, the
+/// even ones for big-endian, and rarely in the other.
+fn utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let pairs: Vec<&[u8]> = bytes.chunks(2).take(UTF16_SAMPLE_PAIRS).collect();
+    let zeros = |i: usize| pairs.iter().filter(|pair| pair[i] == 0).count();
+    let (even, odd) = (zeros(0), zeros(1));
+    let mostly = |n: usize| n * 2 > pairs.len();
+    let rarely = |n: usize| n * 10 < pairs.len();
+    if mostly(odd) && rarely(even) {
+        Some(UTF_16LE)
+    } else if mostly(even) && rarely(odd) {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{BIG5, EUC_JP, EUC_KR, GBK, SHIFT_JIS};
+
+    fn encode(text: &str, encoding: &'static Encoding) -> Vec<u8> {
+        let (bytes, _, errors) = encoding.encode(text);
+        assert!(!errors, "{} can't encode {}", encoding.name(), text);
+        bytes.into_owned()
+    }
+
+    #[test]
+    fn test_detect_legacy() {
+        let documents = [
+            (
+                "# 設計メモ\n\nこのドキュメントはシステムの構成と運用手順について説明します。",
+                SHIFT_JIS,
+            ),
+            (
+                "# 設計メモ\n\nこのドキュメントはシステムの構成と運用手順について説明します。",
+                EUC_JP,
+            ),
+            (
+                "# 설치 안내\n\n이 문서는 서버를 설치하고 설정하는 방법을 설명합니다.",
+                EUC_KR,
+            ),
+            ("# 安装说明\n\n本文档介绍如何安装和配置服务器。", GBK),
+            ("# 安裝說明\n\n本文件介紹如何安裝和設定伺服器。", BIG5),
+            (
+                "Caf\u{e9} menu: cr\u{e8}me br\u{fb}l\u{e9}e, 4\u{20ac}",
+                encoding_rs::WINDOWS_1252,
+            ),
+        ];
+        for (text, encoding) in documents {
+            let bytes = encode(text, encoding);
+            assert_eq!(detect(&bytes).name(), encoding.name(), "{}", text);
+            assert_eq!(decode(&bytes, None).0, text);
+        }
+        assert_eq!(detect(b"plain ASCII").name(), "UTF-8");
+        assert_eq!(detect("déjà vu".as_bytes()).name(), "UTF-8");
+    }
+
+    #[test]
+    fn test_utf16_text() {
+        let text = "Quarterly report\r\nRevenue grew by four percent — 年度報告.";
+        let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        for (bytes, bom, name) in [
+            (le, [0xFF, 0xFE], "UTF-16LE"),
+            (be, [0xFE, 0xFF], "UTF-16BE"),
+        ] {
+            let with_bom = [&bom, bytes.as_slice()].concat();
+            for bytes in [bytes, with_bom] {
+                let (decoded, encoding) = decode(&bytes, None);
+                assert_eq!(decoded, text);
+                assert_eq!(encoding.name(), name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forced_encoding() {
+        let config = AppConfig {
+            ingest_default_encoding: Some("euc-kr".to_string()),
+            ..AppConfig::default()
+        };
+        let forced = configured(&config).unwrap();
+        assert_eq!(forced.map(Encoding::name), Some("EUC-KR"));
+        // Too short to detect; forcing decides.
+        let bytes = encode("안내", EUC_KR);
+        assert_eq!(decode(&bytes, forced).0, "안내");
+        // A byte order mark still wins.
+        let (text, encoding) = decode(b"\xEF\xBB\xBFnotes", forced);
+        assert_eq!((text.as_str(), encoding.name()), ("notes", "UTF-8"));
+
+        assert!(configured(&AppConfig::default()).unwrap().is_none());
+        let unknown = AppConfig {
+            ingest_default_encoding: Some("klingon".to_string()),
+            ..AppConfig::default()
+        };
+        assert!(configured(&unknown).is_err());
+    }
+}
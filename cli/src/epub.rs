@@ -0,0 +1,418 @@
+//! Reading EPUB books for ingest.
+//!
+//! An EPUB is a zip archive: `META-INF/container.xml` points at the OPF
+//! package document, whose spine lists the chapter files in reading order.
+//! Chapter files are XHTML and are reduced to plain text here. Chapter
+//! titles come from the table of contents (the EPUB 3 nav document or the
+//! EPUB 2 NCX), falling back to the chapter's first heading.
+//!
+//! Encrypted (DRM) books are rejected.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::html::{self, collapse_whitespace, decode_entities, strip_tags, tags};
+use crate::zip::Archive;
+
+/// A book's metadata title and its chapters in reading order.
+#[derive(Debug, PartialEq)]
+pub struct Book {
+    pub title: Option<String>,
+    pub chapters: Vec<Chapter>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Chapter {
+    pub title: Option<String>,
+    pub text: String,
+}
+
+/// Read the EPUB at `path`. Archive members that decompress to more than
+/// `max_entry_bytes` are an error, so a small file can't expand without
+/// bound.
+pub fn read(path: &Path, max_entry_bytes: u64) -> Result<Book> {
+    let data = std::fs::read(path)?;
+    let archive = Archive::open(data, max_entry_bytes)
+        .with_context(|| format!("{} is not a valid EPUB (zip) file", path.display()))?;
+    parse_book(&archive).with_context(|| format!("Could not read EPUB {}", path.display()))
+}
+
+fn parse_book(archive: &Archive) -> Result<Book> {
+    if archive.contains("META-INF/encryption.xml") {
+        anyhow::bail!("the book is encrypted");
+    }
+    let container = archive.read_text("META-INF/container.xml")?;
+    let opf_path = tags(&container)
+        .find(|tag| !tag.closing && tag.name == "rootfile")
+        .and_then(|tag| tag.attr("full-path"))
+        .context("container.xml names no package document")?;
+    let opf = archive.read_text(&opf_path)?;
+    let package = Package::parse(&opf, parent_dir(&opf_path));
+
+    let toc = match (&package.nav, &package.ncx) {
+        (Some(nav), _) => archive
+            .read_text(nav)
+            .map(|xml| nav_titles(&xml, parent_dir(nav))),
+        (None, Some(ncx)) => archive
+            .read_text(ncx)
+            .map(|xml| ncx_titles(&xml, parent_dir(ncx))),
+        (None, None) => Ok(HashMap::new()),
+    };
+    let toc = toc.unwrap_or_else(|e| {
+        tracing::warn!("Ignoring unreadable table of contents ({:#})", e);
+        HashMap::new()
+    });
+
+    let mut chapters = Vec::new();
+    for href in &package.spine {
+        let xhtml = archive.read_text(href)?;
+        let (text, heading) = html::to_text(&xhtml);
+        if text.is_empty() {
+            // Cover pages and image-only spreads.
+            continue;
+        }
+        chapters.push(Chapter {
+            title: toc.get(href).cloned().or(heading),
+            text,
+        });
+    }
+    Ok(Book {
+        title: package.title,
+        chapters,
+    })
+}
+
+/// The parts of the OPF package document ingest needs. Paths are archive
+/// paths, already resolved against the package document's directory.
+#[derive(Debug, Default)]
+struct Package {
+    title: Option<String>,
+    /// Chapter documents in reading order.
+    spine: Vec<String>,
+    nav: Option<String>,
+    ncx: Option<String>,
+}
+
+impl Package {
+    fn parse(opf: &str, dir: &str) -> Self {
+        let mut package = Package::default();
+        // id -> (href, media type)
+        let mut manifest: HashMap<String, (String, String)> = HashMap::new();
+        let mut spine_ids = Vec::new();
+        let mut ncx_id = None;
+        let mut title_start = None;
+
+        for tag in tags(opf) {
+            match (tag.name, tag.closing) {
+                ("title", false) if package.title.is_none() => title_start = Some(tag.end),
+                ("title", true) => {
+                    if let Some(start) = title_start.take() {
+                        let title = collapse_whitespace(&decode_entities(&opf[start..tag.start]));
+                        if !title.is_empty() {
+                            package.title = Some(title);
+                        }
+                    }
+                }
+                ("item", false) => {
+                    let (Some(id), Some(href)) = (tag.attr("id"), tag.attr("href")) else {
+                        continue;
+                    };
+                    let href = resolve(dir, &href);
+                    let properties = tag.attr("properties").unwrap_or_default();
+                    if properties.split_whitespace().any(|p| p == "nav") {
+                        package.nav = Some(href.clone());
+                    }
+                    manifest.insert(id, (href, tag.attr("media-type").unwrap_or_default()));
+                }
+                ("spine", false) => ncx_id = tag.attr("toc"),
+                ("itemref", false) => spine_ids.extend(tag.attr("idref")),
+                _ => {}
+            }
+        }
+
+        package.ncx = ncx_id
+            .and_then(|id| manifest.get(&id))
+            .map(|(href, _)| href.clone());
+        package.spine = spine_ids
+            .iter()
+            .filter_map(|id| manifest.get(id))
+            .filter(|(_, media_type)| {
+                matches!(media_type.as_str(), "application/xhtml+xml" | "text/html")
+            })
+            .map(|(href, _)| href.clone())
+            .collect();
+        package
+    }
+}
+
+/// Chapter titles from an EPUB 3 nav document: the links in its `toc` nav.
+fn nav_titles(xml: &str, dir: &str) -> HashMap<String, String> {
+    let mut titles = HashMap::new();
+    // Depth inside the toc nav (navs nest only rarely, but links in a
+    // page-list or landmarks nav must not be taken as chapter titles).
+    let mut nav_depth = 0;
+    let mut toc_depth = None;
+    let mut link: Option<(String, usize)> = None;
+
+    for tag in tags(xml) {
+        match (tag.name, tag.closing) {
+            ("nav", false) => {
+                nav_depth += 1;
+                let toc = tag
+                    .attr("epub:type")
+                    .is_some_and(|t| t.split_whitespace().any(|t| t == "toc"));
+                if toc && toc_depth.is_none() {
+                    toc_depth = Some(nav_depth);
+                }
+            }
+            ("nav", true) => {
+                if toc_depth == Some(nav_depth) {
+                    toc_depth = None;
+                }
+                nav_depth -= 1;
+            }
+            ("a", false) if toc_depth.is_some() => {
+                link = tag.attr("href").map(|href| (href, tag.end));
+            }
+            ("a", true) => {
+                if let Some((href, start)) = link.take() {
+                    let label = collapse_whitespace(&strip_tags(&xml[start..tag.start]));
+                    add_title(&mut titles, dir, &href, label);
+                }
+            }
+            _ => {}
+        }
+    }
+    titles
+}
+
+/// Chapter titles from an EPUB 2 NCX: each navPoint's label and target.
+fn ncx_titles(xml: &str, dir: &str) -> HashMap<String, String> {
+    let mut titles = HashMap::new();
+    let mut label = String::new();
+    let mut text_start = None;
+
+    for tag in tags(xml) {
+        match (tag.name, tag.closing) {
+            ("text", false) => text_start = Some(tag.end),
+            ("text", true) => {
+                if let Some(start) = text_start.take() {
+                    label = collapse_whitespace(&decode_entities(&xml[start..tag.start]));
+                }
+            }
+            ("content", false) => {
+                if let Some(src) = tag.attr("src") {
+                    add_title(&mut titles, dir, &src, std::mem::take(&mut label));
+                }
+            }
+            _ => {}
+        }
+    }
+    titles
+}
+
+/// Record `label` for the document `href` points into. The first entry for
+/// a document wins: it is the outermost one, e.g. the chapter rather than
+/// one of its sections.
+fn add_title(titles: &mut HashMap<String, String>, dir: &str, href: &str, label: String) {
+    let document = href.split('#').next().unwrap_or(href);
+    if label.is_empty() || document.is_empty() {
+        return;
+    }
+    titles.entry(resolve(dir, document)).or_insert(label);
+}
+
+fn parent_dir(path: &str) -> &str {
+    path.rfind('/').map_or("", |i| &path[..i])
+}
+
+/// Resolve a relative href against the archive directory `dir`, decoding
+/// percent escapes (`Chapter%201.xhtml`).
+fn resolve(dir: &str, href: &str) -> String {
+    let mut parts: Vec<String> = if href.starts_with('/') {
+        Vec::new()
+    } else {
+        dir.split('/')
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(percent_decode(part)),
+        }
+    }
+    parts.join("/")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::zip::tests::zip;
+
+    const CONTAINER: &str = r#"<?xml version="1.0"?>
+<container xmlns="urn:oasis:names:tc:opendocument:xmlns:container" version="1.0">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+
+    const OPF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>The Field Guide &amp; Notes</dc:title>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="cover" href="text/cover.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c1" href="text/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c2" href="text/c2.xhtml" media-type="application/xhtml+xml"/>
+    <item id="img" href="images/cover.jpg" media-type="image/jpeg"/>
+  </manifest>
+  <spine>
+    <itemref idref="cover"/>
+    <itemref idref="c2"/>
+    <itemref idref="c1"/>
+  </spine>
+</package>"#;
+
+    const NAV: &str = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<body>
+  <nav epub:type="toc"><ol>
+    <li><a href="text/chapter%201.xhtml">Getting <em>started</em></a>
+      <ol><li><a href="text/chapter%201.xhtml#setup">Setup</a></li></ol></li>
+  </ol></nav>
+  <nav epub:type="landmarks"><ol><li><a href="text/c2.xhtml">Start here</a></li></ol></nav>
+</body></html>"#;
+
+    const COVER: &str = r#"<html><body><img src="../images/cover.jpg"/></body></html>"#;
+
+    const CHAPTER_1: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>ignored</title><style>p { margin: 0 }</style></head>
+<body>
+  <h1>Chapter One</h1>
+  <p>First   paragraph,
+     wrapped.</p>
+  <p>Second&#160;one &mdash; with <b>bold</b> text.</p>
+  <pre>keep
+  this</pre>
+</body>
+</html>"#;
+
+    const CHAPTER_2: &str = r#"<html><body><section><h2>Preface</h2><p>Why &lt;this&gt; <!-- draft --> book.</p>
+<ul><li>one</li><li>two</li></ul></section></body></html>"#;
+
+    /// Write a small EPUB 3 book to a temporary file.
+    pub(crate) fn sample_epub() -> std::path::PathBuf {
+        let data = zip(&[
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", CONTAINER),
+            ("OEBPS/content.opf", OPF),
+            ("OEBPS/nav.xhtml", NAV),
+            ("OEBPS/text/cover.xhtml", COVER),
+            ("OEBPS/text/chapter 1.xhtml", CHAPTER_1),
+            ("OEBPS/text/c2.xhtml", CHAPTER_2),
+        ]);
+        let path = std::env::temp_dir().join(format!("epub-{}.epub", uuid::Uuid::new_v4()));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_read_book() {
+        let path = sample_epub();
+        let book = read(&path, 1 << 20).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(book.title.as_deref(), Some("The Field Guide & Notes"));
+        assert_eq!(
+            book.chapters,
+            vec![
+                // Not in the toc (the landmarks link doesn't count), so the
+                // heading is used.
+                Chapter {
+                    title: Some("Preface".to_string()),
+                    text: "Preface\n\nWhy <this> book.\n\none\ntwo".to_string(),
+                },
+                Chapter {
+                    title: Some("Getting started".to_string()),
+                    text: "Chapter One\n\nFirst paragraph, wrapped.\n\nSecond one — with bold text.\n\nkeep\n  this".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ncx_titles() {
+        let ncx = r#"<ncx><navMap>
+  <navPoint id="n1"><navLabel><text>Part I</text></navLabel><content src="part1.html"/>
+    <navPoint id="n2"><navLabel><text>Section</text></navLabel><content src="part1.html#s"/></navPoint>
+  </navPoint>
+  <navPoint id="n3"><navLabel><text>Appendix &amp; Index</text></navLabel><content src="../back/app.html"/></navPoint>
+</navMap></ncx>"#;
+        let titles = ncx_titles(ncx, "OEBPS/text");
+        assert_eq!(titles.len(), 2);
+        assert_eq!(titles["OEBPS/text/part1.html"], "Part I");
+        assert_eq!(titles["OEBPS/back/app.html"], "Appendix & Index");
+    }
+
+    #[test]
+    fn test_invalid_archives() {
+        let path = std::env::temp_dir().join(format!("epub-{}.epub", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "not a zip").unwrap();
+        let err = read(&path, 1 << 20).unwrap_err();
+        assert!(format!("{:#}", err).contains("not a valid EPUB"));
+
+        let encrypted = zip(&[
+            ("META-INF/container.xml", CONTAINER),
+            ("META-INF/encryption.xml", "<encryption/>"),
+        ]);
+        std::fs::write(&path, encrypted).unwrap();
+        let err = read(&path, 1 << 20).unwrap_err();
+        assert!(format!("{:#}", err).contains("encrypted"));
+
+        // A member expanding past the limit.
+        std::fs::write(&path, zip(&[("META-INF/container.xml", CONTAINER)])).unwrap();
+        let err = read(&path, 16).unwrap_err();
+        assert!(format!("{:#}", err).contains("expands beyond 16 bytes"));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(
+            resolve("OEBPS/text", "../img/a%20b.png"),
+            "OEBPS/img/a b.png"
+        );
+    }
+}
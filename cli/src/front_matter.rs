@@ -0,0 +1,142 @@
+//! YAML front matter at the top of markdown notes.
+//!
+//! The block is parsed with `serde_yaml_ng`. Only the keys ingest
+//! uses are read: `title` (a string) and `tags` (a list, or a
+//! comma-separated string). A block that isn't a YAML mapping is reported
+//! as malformed.
+
+use serde_yaml_ng::Value;
+
+/// Fields taken from a note's front matter.
+#[derive(Debug, Default, PartialEq)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Split a leading `---` block off `content`. Returns `Ok(None)` when there
+/// is no front matter, and the parsed fields plus the remaining body
+/// otherwise.
+pub fn extract(content: &str) -> Result<Option<(FrontMatter, &str)>, String> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let Some(rest) = strip_delimiter(content) else {
+        return Ok(None);
+    };
+    let opening = content.len() - rest.len();
+
+    // Find the closing `---` (or YAML's `...`) line.
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed == "---" || trimmed == "..." {
+            // Parsed with the opening line so errors carry file line numbers.
+            let front_matter = parse(&content[..opening + offset])?;
+            return Ok(Some((front_matter, &rest[offset + line.len()..])));
+        }
+        offset += line.len();
+    }
+    // No closing line: the opening `---` was a horizontal rule.
+    Ok(None)
+}
+
+fn strip_delimiter(content: &str) -> Option<&str> {
+    let rest = content.strip_prefix("---")?;
+    let rest = rest.trim_start_matches([' ', '\t']);
+    rest.strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))
+}
+
+fn parse(block: &str) -> Result<FrontMatter, String> {
+    let entries = match serde_yaml_ng::from_str(block).map_err(|e| e.to_string())? {
+        Value::Mapping(entries) => entries,
+        // Only comments, or nothing at all.
+        Value::Null => Default::default(),
+        _ => return Err("front matter must be `key: value` pairs".to_string()),
+    };
+
+    let mut front_matter = FrontMatter::default();
+    for (key, value) in entries {
+        match (key.as_str(), value) {
+            (Some("title"), value) => {
+                front_matter.title = scalar(&value).filter(|title| !title.is_empty());
+            }
+            (Some("tags"), Value::Sequence(items)) => {
+                for item in items {
+                    let Some(tag) = scalar(&item) else {
+                        return Err("tags must be a list of strings".to_string());
+                    };
+                    front_matter.tags.push(tag);
+                }
+            }
+            (Some("tags"), Value::String(tags)) => front_matter
+                .tags
+                .extend(tags.split(',').map(|tag| tag.trim().to_string())),
+            _ => {}
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    front_matter
+        .tags
+        .retain(|t| !t.is_empty() && seen.insert(t.clone()));
+    Ok(front_matter)
+}
+
+/// The text of a string, number or boolean.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(list: &[&str]) -> Vec<String> {
+        list.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_no_front_matter() {
+        assert_eq!(extract("# Title\n\nBody").unwrap(), None);
+        // A horizontal rule with no closing line is just text.
+        assert_eq!(extract("---\n# Title\n").unwrap(), None);
+        assert_eq!(extract("----\nx: y\n---\n").unwrap(), None);
+    }
+
+    #[test]
+    fn test_flow_list_and_title() {
+        let content = "---\ntitle: \"Billing: overview\"\ntags: [architecture, 'billing']\ndate: 2024-01-01\n---\n# Heading\nBody\n";
+        let (front_matter, body) = extract(content).unwrap().unwrap();
+        assert_eq!(front_matter.title.as_deref(), Some("Billing: overview"));
+        assert_eq!(front_matter.tags, tags(&["architecture", "billing"]));
+        assert_eq!(body, "# Heading\nBody\n");
+    }
+
+    #[test]
+    fn test_block_list_and_nested_keys() {
+        let content = "---\r\naliases:\r\n  - other name\r\ntags:\r\n  - a\r\n  - \"b c\"  \r\nmeta:\r\n  nested: 1\r\n...\r\nBody";
+        let (front_matter, body) = extract(content).unwrap().unwrap();
+        assert_eq!(front_matter.title, None);
+        assert_eq!(front_matter.tags, tags(&["a", "b c"]));
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn test_comma_separated_tags() {
+        let (front_matter, _) = extract("---\ntags: a, b # note\n---\n").unwrap().unwrap();
+        assert_eq!(front_matter.tags, tags(&["a", "b"]));
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(extract("---\njust some words\n---\nBody").is_err());
+        assert!(extract("---\ntags: [a, b\n---\nBody").is_err());
+        assert!(extract("---\ntitle: \"open\n---\nBody").is_err());
+        assert!(extract("---\n  - orphan\n---\nBody").is_err());
+    }
+}
@@ -0,0 +1,230 @@
+//! Shallow clones for `ragctl ingest --git`.
+//!
+//! Each repository is cloned once, at depth 1, into its own directory under
+//! the git cache dir. Later runs fetch the branch tip at depth 1 and check it
+//! out, so the working tree always matches the latest commit. Changed files
+//! are found by their blob hashes in that commit. Runs the `git` command,
+//! which must be in PATH.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+
+/// A repository to ingest.
+#[derive(Debug, Clone)]
+pub struct Repo {
+    pub url: String,
+    /// Branch to check out; the remote's default branch when unset.
+    pub branch: Option<String>,
+}
+
+impl Repo {
+    /// The repository part of its files' `source_path`: the URL without a
+    /// trailing slash or `.git`.
+    pub fn name(&self) -> &str {
+        let url = self.url.trim_end_matches('/');
+        url.strip_suffix(".git").unwrap_or(url)
+    }
+}
+
+/// A working tree synced to the latest commit of a branch.
+#[derive(Debug)]
+pub struct Checkout {
+    pub dir: PathBuf,
+    /// The checked-out commit, abbreviated to 12 hex digits.
+    pub commit: String,
+}
+
+/// Length commit hashes are abbreviated to in `source_path`.
+const COMMIT_LEN: usize = 12;
+
+/// Clone `repo` into `cache_dir`, or fetch and check out its latest commit
+/// when it was cloned before.
+pub fn sync(cache_dir: &Path, repo: &Repo) -> Result<Checkout> {
+    let dir = cache_dir.join(cache_name(repo.name()));
+    if dir.join(".git").is_dir() {
+        tracing::info!("Fetching {}", repo.url);
+        let refspec = repo.branch.as_deref().unwrap_or("HEAD");
+        git(Some(&dir), &["fetch", "--depth", "1", "origin", refspec])?;
+        git(
+            Some(&dir),
+            &["checkout", "--force", "--detach", "FETCH_HEAD"],
+        )?;
+    } else {
+        tracing::info!("Cloning {} into {}", repo.url, dir.display());
+        std::fs::create_dir_all(cache_dir)
+            .with_context(|| format!("Could not create {}", cache_dir.display()))?;
+        // A clone that failed halfway would otherwise block the next one.
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+        let dir_arg = dir.to_string_lossy();
+        let mut args = vec!["clone", "--depth", "1", "--single-branch"];
+        if let Some(branch) = &repo.branch {
+            args.extend(["--branch", branch]);
+        }
+        args.extend(["--", &repo.url, &dir_arg]);
+        git(None, &args)?;
+    }
+
+    let commit = git(Some(&dir), &["rev-parse", "HEAD"])?;
+    Ok(Checkout {
+        dir,
+        commit: commit.trim().chars().take(COMMIT_LEN).collect(),
+    })
+}
+
+/// Blob hash of every file in the checked-out commit, keyed by its path
+/// relative to the repository root. Submodules are left out.
+pub fn blob_hashes(dir: &Path) -> Result<HashMap<String, String>> {
+    let listing = git(Some(dir), &["ls-tree", "-r", "-z", "HEAD"])?;
+    Ok(listing
+        .split('\0')
+        .filter_map(|entry| {
+            // "<mode> <type> <hash>\t<path>"
+            let (info, path) = entry.split_once('\t')?;
+            let mut fields = info.split(' ');
+            let (_mode, kind, hash) = (fields.next()?, fields.next()?, fields.next()?);
+            (kind == "blob").then(|| (path.to_string(), hash.to_string()))
+        })
+        .collect())
+}
+
+/// Directory name for a repository's clone: its name with everything but
+/// letters, digits, `-` and `.` replaced by `_`.
+fn cache_name(repo: &str) -> String {
+    let name: String = repo
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    // Never "." or "..".
+    name.trim_start_matches('.').to_string()
+}
+
+/// Run git with `args`, in `dir` if given, returning its stdout.
+fn git(dir: Option<&Path>, args: &[&str]) -> Result<String> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.arg("-C").arg(dir);
+    }
+    // Fail instead of waiting for a password on stdin.
+    command.args(args).env("GIT_TERMINAL_PROMPT", "0");
+    let output = command.output().map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            anyhow::anyhow!("git not found in PATH; it is needed to ingest a repository")
+        } else {
+            anyhow::anyhow!("Failed to run git: {}", e)
+        }
+    })?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Commit `files` (path, contents; `None` deletes) to the repository at
+    /// `dir`, creating it on the first call.
+    pub(crate) fn commit(dir: &Path, files: &[(&str, Option<&str>)]) {
+        if !dir.join(".git").exists() {
+            std::fs::create_dir_all(dir).unwrap();
+            git(Some(dir), &["init", "--quiet", "--initial-branch", "main"]).unwrap();
+        }
+        for (path, contents) in files {
+            let file = dir.join(path);
+            match contents {
+                Some(contents) => {
+                    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+                    std::fs::write(file, contents).unwrap();
+                }
+                None => std::fs::remove_file(file).unwrap(),
+            }
+        }
+        git(Some(dir), &["add", "--all"]).unwrap();
+        git(
+            Some(dir),
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "--quiet",
+                "--message",
+                "update",
+            ],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_sync() {
+        let root = std::env::temp_dir().join(format!("git-{}", uuid::Uuid::new_v4()));
+        let origin = root.join("origin");
+        commit(
+            &origin,
+            &[("README.md", Some("# Repo")), ("docs/a.md", Some("A"))],
+        );
+        let repo = Repo {
+            url: format!("file://{}", origin.display()),
+            branch: Some("main".to_string()),
+        };
+        let cache = root.join("cache");
+
+        let first = sync(&cache, &repo).unwrap();
+        assert_eq!(first.commit.len(), COMMIT_LEN);
+        assert_eq!(
+            std::fs::read_to_string(first.dir.join("docs/a.md")).unwrap(),
+            "A"
+        );
+        let before = blob_hashes(&first.dir).unwrap();
+        assert_eq!(before.len(), 2);
+
+        commit(
+            &origin,
+            &[("docs/a.md", Some("A, edited")), ("README.md", None)],
+        );
+        let second = sync(&cache, &repo).unwrap();
+        assert_eq!(second.dir, first.dir);
+        assert_ne!(second.commit, first.commit);
+        assert!(!second.dir.join("README.md").exists());
+        let after = blob_hashes(&second.dir).unwrap();
+        assert_eq!(after.keys().collect::<Vec<_>>(), vec!["docs/a.md"]);
+        assert_ne!(after["docs/a.md"], before["docs/a.md"]);
+
+        let missing = Repo {
+            url: format!("file://{}", root.join("missing").display()),
+            branch: None,
+        };
+        let err = sync(&cache, &missing).unwrap_err();
+        assert!(err.to_string().contains("git clone failed"), "{}", err);
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_names() {
+        let repo = Repo {
+            url: "https://github.com/org/repo.git/".to_string(),
+            branch: None,
+        };
+        assert_eq!(repo.name(), "https://github.com/org/repo");
+        assert_eq!(cache_name(repo.name()), "https___github.com_org_repo");
+        assert_eq!(cache_name("../up"), "_up");
+    }
+}
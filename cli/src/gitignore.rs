@@ -0,0 +1,361 @@
+//! `.gitignore` / `.ignore` matching for the ingest walker.
+//!
+//! Supports the gitignore syntax people actually write: comments, `!`
+//! negation, trailing `/` for directories, anchored patterns (containing a
+//! `/`), `*`, `?`, `[...]` classes and `**`. Ignore files are read from each
+//! directory inside a source tree and apply to everything below it; a rule
+//! in a deeper file, or later in the same file, overrides earlier ones.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Per-directory ignore files, in the order they are applied.
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+#[derive(Debug)]
+struct Rule {
+    pattern: Vec<Part>,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the path relative to the ignore file's directory
+    /// instead of the file name alone.
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+        Some(Self {
+            pattern: parse_pattern(line),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, relative: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let text = if self.anchored {
+            relative
+        } else {
+            relative.rsplit('/').next().unwrap_or(relative)
+        };
+        glob_match(&self.pattern, text)
+    }
+}
+
+/// Whether the file at `relative` (with `/` separators) matches `pattern`,
+/// read like a `.gitignore` line: without a `/` it matches the file name at
+/// any depth.
+pub fn glob_matches(pattern: &str, relative: &str) -> bool {
+    Rule::parse(pattern).is_some_and(|rule| !rule.negated && rule.matches(relative, false))
+}
+
+/// One `/`-separated part of a pattern.
+#[derive(Debug)]
+enum Part {
+    /// `**`: any number of whole path components.
+    AnyDirs,
+    Glob(Vec<Token>),
+}
+
+#[derive(Debug)]
+enum Token {
+    Char(char),
+    /// `?`
+    Any,
+    /// `*`
+    Star,
+    Class(CharClass),
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Char(expected) => c == *expected,
+            Self::Any => true,
+            Self::Star => false,
+            Self::Class(class) => class.contains(c),
+        }
+    }
+}
+
+/// Split a pattern into its parts.
+fn parse_pattern(pattern: &str) -> Vec<Part> {
+    pattern
+        .split('/')
+        .map(|part| match part {
+            "**" => Part::AnyDirs,
+            _ => Part::Glob(parse_tokens(&part.chars().collect::<Vec<_>>())),
+        })
+        .collect()
+}
+
+fn parse_tokens(mut pattern: &[char]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    while let Some((&c, tail)) = pattern.split_first() {
+        let (token, rest) = match (c, tail) {
+            ('*', _) => (Token::Star, tail),
+            ('?', _) => (Token::Any, tail),
+            ('[', _) => match parse_class(tail) {
+                Some((class, rest)) => (Token::Class(class), rest),
+                // An unclosed `[` is a literal.
+                None => (Token::Char('['), tail),
+            },
+            ('\\', [escaped, rest @ ..]) => (Token::Char(*escaped), rest),
+            _ => (Token::Char(c), tail),
+        };
+        tokens.push(token);
+        pattern = rest;
+    }
+    tokens
+}
+
+/// Match `text` against a parsed gitignore glob. `*`, `?` and classes never
+/// match `/`; a `**` part matches any number of directories, and a trailing
+/// one everything below. Both levels backtrack only to the last `**` or
+/// `*`, which keeps matching linear in the path for each of them.
+fn glob_match(parts: &[Part], text: &str) -> bool {
+    // `t` is the byte offset of the next path component, past the end once
+    // they're all matched.
+    let (mut p, mut t) = (0, 0);
+    let mut any_dirs: Option<(usize, usize)> = None;
+    let component_end = |t: usize| text[t..].find('/').map_or(text.len(), |i| t + i);
+    loop {
+        let done = t > text.len();
+        match parts.get(p) {
+            Some(Part::AnyDirs) if p + 1 == parts.len() => return !done,
+            Some(Part::AnyDirs) => {
+                any_dirs = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some(Part::Glob(tokens)) if !done => {
+                let end = component_end(t);
+                if segment_match(tokens, &text[t..end]) {
+                    p += 1;
+                    t = end + 1;
+                    continue;
+                }
+            }
+            None if done => return true,
+            _ => {}
+        }
+        // Let the last `**` take one more component.
+        match any_dirs {
+            Some((star, from)) if from <= text.len() => {
+                let next = component_end(from) + 1;
+                any_dirs = Some((star, next));
+                p = star + 1;
+                t = next;
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// Match one path component (without `/`) against `tokens`.
+fn segment_match(tokens: &[Token], text: &str) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    loop {
+        match tokens.get(p) {
+            Some(Token::Star) => {
+                star = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some(token) => {
+                if let Some(c) = text[t..].chars().next().filter(|&c| token.matches(c)) {
+                    p += 1;
+                    t += c.len_utf8();
+                    continue;
+                }
+            }
+            None if t == text.len() => return true,
+            None => {}
+        }
+        // Let the last `*` take one more character.
+        match star {
+            Some((star_p, from)) if from < text.len() => {
+                let next = from + text[from..].chars().next().map_or(1, char::len_utf8);
+                star = Some((star_p, next));
+                p = star_p + 1;
+                t = next;
+            }
+            _ => return false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CharClass {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl CharClass {
+    fn contains(&self, c: char) -> bool {
+        self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != self.negated
+    }
+}
+
+/// Parse the body of a `[...]` class (after the `[`), returning the class
+/// and the pattern after the closing `]`.
+fn parse_class(pattern: &[char]) -> Option<(CharClass, &[char])> {
+    let (negated, mut rest) = match pattern {
+        ['!' | '^', rest @ ..] => (true, rest),
+        _ => (false, pattern),
+    };
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        match rest {
+            [] => return None,
+            [']', tail @ ..] if !first => return Some((CharClass { negated, ranges }, tail)),
+            [lo, '-', hi, tail @ ..] if *hi != ']' => {
+                ranges.push((*lo, *hi));
+                rest = tail;
+            }
+            [c, tail @ ..] => {
+                ranges.push((*c, *c));
+                rest = tail;
+            }
+        }
+        first = false;
+    }
+}
+
+/// Loads ignore files lazily as the walker reaches each directory.
+pub struct IgnoreMatcher {
+    root: PathBuf,
+    rules: HashMap<PathBuf, Vec<Rule>>,
+}
+
+impl IgnoreMatcher {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            rules: HashMap::new(),
+        }
+    }
+
+    /// Whether `path` (inside the root) is excluded by an ignore file in the
+    /// root or any directory between the root and `path`.
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        let mut ignored = false;
+        let mut dir = self.root.clone();
+        let components: Vec<_> = relative.components().collect();
+        for depth in 0..components.len() {
+            let below: PathBuf = components[depth..].iter().collect();
+            let below = below.to_string_lossy().replace('\\', "/");
+            for rule in self.rules_for(&dir) {
+                if rule.matches(&below, is_dir) {
+                    ignored = !rule.negated;
+                }
+            }
+            dir.push(components[depth]);
+        }
+        ignored
+    }
+
+    fn rules_for(&mut self, dir: &Path) -> &[Rule] {
+        self.rules.entry(dir.to_path_buf()).or_insert_with(|| {
+            IGNORE_FILES
+                .iter()
+                .filter_map(|name| std::fs::read_to_string(dir.join(name)).ok())
+                .flat_map(|content| content.lines().filter_map(Rule::parse).collect::<Vec<_>>())
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(line: &str) -> Rule {
+        Rule::parse(line).unwrap()
+    }
+
+    #[test]
+    fn test_parse_skips_blank_and_comments() {
+        assert!(Rule::parse("").is_none());
+        assert!(Rule::parse("   ").is_none());
+        assert!(Rule::parse("# comment").is_none());
+        assert!(rule("\\#notes").matches("#notes", false));
+    }
+
+    #[test]
+    fn test_basename_and_anchored_patterns() {
+        assert!(rule("*.bak").matches("a/b/file.bak", false));
+        assert!(!rule("*.bak").matches("file.bak.md", false));
+        assert!(rule("/build").matches("build", true));
+        assert!(!rule("/build").matches("src/build", true));
+        assert!(rule("docs/*.md").matches("docs/a.md", false));
+        assert!(!rule("docs/*.md").matches("docs/sub/a.md", false));
+    }
+
+    #[test]
+    fn test_dir_only_and_negation() {
+        assert!(rule("drafts/").matches("notes/drafts", true));
+        assert!(!rule("drafts/").matches("notes/drafts", false));
+        let keep = rule("!keep.md");
+        assert!(keep.negated);
+        assert!(keep.matches("keep.md", false));
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*.md", "guides/setup.md"));
+        assert!(glob_matches("openapi/**", "openapi/v1/spec.yaml"));
+        assert!(!glob_matches("openapi/**", "guides/openapi/spec.yaml"));
+        assert!(!glob_matches("!*.md", "setup.md"));
+    }
+
+    #[test]
+    fn test_glob_syntax() {
+        assert!(rule("**/tmp").matches("a/b/tmp", true));
+        assert!(rule("**/tmp").matches("tmp", true));
+        assert!(rule("logs/**").matches("logs/2024/app.md", false));
+        assert!(rule("a/**/z.md").matches("a/z.md", false));
+        assert!(rule("a/**/z.md").matches("a/b/c/z.md", false));
+        assert!(rule("file?.md").matches("file1.md", false));
+        assert!(rule("[abc].md").matches("b.md", false));
+        assert!(!rule("[!abc].md").matches("b.md", false));
+        assert!(rule("[0-9]*.md").matches("2024-notes.md", false));
+        assert!(rule("[oops").matches("[oops", false));
+        assert!(rule("café-*.md").matches("notes/café-ü.md", false));
+    }
+
+    #[test]
+    fn test_many_wildcards_stay_fast() {
+        // Exponential with naive backtracking.
+        let name = format!("{}.md", "a".repeat(200));
+        assert!(!rule("*a*a*a*a*a*a*a*a*a*a*b").matches(&name, false));
+        let deep = format!("{}z.md", "d/".repeat(200));
+        assert!(!rule("**/d/**/d/**/d/**/d/**/y.md").matches(&deep, false));
+        assert!(rule("**/d/**/d/**/d/**/d/**/z.md").matches(&deep, false));
+    }
+}
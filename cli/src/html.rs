@@ -0,0 +1,603 @@
+//! Turning HTML and XHTML into text for ingest.
+//!
+//! A forgiving tag scanner rather than a full parser: it is enough for EPUB
+//! chapter files and for pages fetched by `ragctl ingest --url`, where
+//! broken markup should degrade the text, not fail the document. Web pages
+//! are reduced to their main content: the `<main>` (or lone `<article>`)
+//! element when there is one, minus navigation, sidebars, footers and other
+//! page chrome, rendered as markdown so the heading-aware chunker can use
+//! their structure.
+
+/// A start, end or empty-element tag found by [`tags`].
+pub struct Tag<'a> {
+    /// Element name without a namespace prefix (`title` for `dc:title`);
+    /// empty for comments and declarations.
+    pub name: &'a str,
+    pub closing: bool,
+    pub self_closing: bool,
+    attrs: &'a str,
+    /// Byte offsets of the `<` and just past the `>`.
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Tag<'_> {
+    /// The value of attribute `name` (matched with its prefix, if any).
+    pub fn attr(&self, name: &str) -> Option<String> {
+        let mut rest = self.attrs;
+        loop {
+            rest = rest.trim_start();
+            let name_end = rest.find(|c: char| c == '=' || c.is_whitespace())?;
+            let attr_name = &rest[..name_end];
+            rest = rest[name_end..].trim_start();
+            let Some(value) = rest.strip_prefix('=') else {
+                // An attribute without a value (HTML's `<input disabled>`).
+                continue;
+            };
+            let value = value.trim_start();
+            let quote = value.chars().next()?;
+            let (raw, after) = if quote == '"' || quote == '\'' {
+                let inner = &value[1..];
+                let close = inner.find(quote)?;
+                (&inner[..close], &inner[close + 1..])
+            } else {
+                let close = value.find(char::is_whitespace).unwrap_or(value.len());
+                (&value[..close], &value[close..])
+            };
+            if attr_name.eq_ignore_ascii_case(name) {
+                return Some(decode_entities(raw));
+            }
+            rest = after;
+        }
+    }
+}
+
+/// Elements whose content is raw text in HTML: a `<` inside them doesn't
+/// start a tag. Prefixed names (`w:style` in a Word document) are other
+/// vocabularies' elements and are not raw text.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+/// The tags of an XML or HTML document in order. Comments, CDATA
+/// sections, processing instructions and declarations come back as tags with
+/// an empty name, so callers skip over them like any other markup.
+pub fn tags(xml: &str) -> impl Iterator<Item = Tag<'_>> {
+    let mut pos = 0;
+    std::iter::from_fn(move || loop {
+        let start = pos + xml[pos..].find('<')?;
+        let rest = &xml[start..];
+        let terminator = if rest.starts_with("<!--") {
+            Some("-->")
+        } else if rest.starts_with("<![CDATA[") {
+            Some("]]>")
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            Some(">")
+        } else {
+            None
+        };
+        if let Some(terminator) = terminator {
+            pos = start + rest.find(terminator)? + terminator.len();
+            return Some(Tag {
+                name: "",
+                closing: false,
+                self_closing: true,
+                attrs: "",
+                start,
+                end: pos,
+            });
+        }
+
+        let closing = rest.starts_with("</");
+        let body_start = start + if closing { 2 } else { 1 };
+        // Find the closing `>`, stepping over quoted attribute values.
+        let mut quote = None;
+        let mut end = None;
+        for (i, c) in xml[body_start..].char_indices() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '>') => {
+                    end = Some(body_start + i);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let end = end?;
+        pos = end + 1;
+
+        let body = &xml[body_start..end];
+        let self_closing = body.ends_with('/');
+        let body = body.strip_suffix('/').unwrap_or(body);
+        let name_end = body.find(char::is_whitespace).unwrap_or(body.len());
+        let qualified = &body[..name_end];
+        if qualified.is_empty() {
+            // A stray `<` in text.
+            continue;
+        }
+        let name = qualified.rsplit(':').next().unwrap_or(qualified);
+        if !closing && !self_closing && name.len() == qualified.len() {
+            if let Some(raw) = RAW_TEXT_ELEMENTS
+                .iter()
+                .find(|raw| name.eq_ignore_ascii_case(raw))
+            {
+                // Resume at the element's end tag.
+                pos = find_end_tag(xml, pos, raw).unwrap_or(xml.len());
+            }
+        }
+        return Some(Tag {
+            name,
+            closing,
+            self_closing,
+            attrs: &body[name_end..],
+            start,
+            end: end + 1,
+        });
+    })
+}
+
+/// Offset of the first `</name` at or after `from`, ignoring case.
+fn find_end_tag(xml: &str, from: usize, name: &str) -> Option<usize> {
+    let bytes = xml.as_bytes();
+    let mut pos = from;
+    loop {
+        let start = pos + xml[pos..].find("</")?;
+        let name_start = start + 2;
+        if bytes
+            .get(name_start..name_start + name.len())
+            .is_some_and(|n| n.eq_ignore_ascii_case(name.as_bytes()))
+        {
+            return Some(start);
+        }
+        pos = name_start;
+    }
+}
+
+/// Elements whose content is never text.
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "head", "script", "style", "svg", "math", "template", "noscript",
+];
+
+/// Page chrome left out of web pages.
+const CHROME_ELEMENTS: &[&str] = &[
+    "nav", "header", "footer", "aside", "form", "button", "iframe", "select", "dialog",
+];
+
+/// Containers left out of web pages when a `class` or `id` word names page
+/// chrome, e.g. `<div class="sidebar">`.
+const CHROME_CONTAINERS: &[&str] = &["div", "section", "ul", "ol", "table"];
+
+const CHROME_NAMES: &[&str] = &[
+    "nav",
+    "navbar",
+    "navigation",
+    "menu",
+    "sidebar",
+    "breadcrumb",
+    "breadcrumbs",
+    "footer",
+    "cookie",
+    "cookies",
+    "banner",
+    "comments",
+    "share",
+    "social",
+    "related",
+    "advert",
+    "ads",
+    "promo",
+    "popup",
+    "modal",
+];
+
+/// Elements that end a line rather than a paragraph.
+const LINE_ELEMENTS: &[&str] = &["br", "li", "tr", "dt", "dd", "figcaption"];
+
+/// Elements that start a new paragraph.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "aside",
+    "header",
+    "footer",
+    "nav",
+    "blockquote",
+    "pre",
+    "ul",
+    "ol",
+    "dl",
+    "table",
+    "figure",
+    "hr",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "body",
+];
+
+/// Headings that can stand in for a missing chapter title.
+const TITLE_HEADINGS: &[&str] = &["h1", "h2", "h3"];
+
+/// Plain text of an XHTML document, with paragraphs separated by blank
+/// lines, and the text of its first `h1`-`h3` heading.
+pub fn to_text(xhtml: &str) -> (String, Option<String>) {
+    render(xhtml, false)
+}
+
+/// The main content of a web page as markdown: headings, list items and
+/// preformatted blocks keep their markdown form, everything else becomes
+/// paragraphs of text.
+pub fn to_markdown(html: &str) -> String {
+    let mut main = None;
+    let mut articles = Vec::new();
+    for tag in start_tags(html) {
+        if tag.name.eq_ignore_ascii_case("main") || tag.attr("role").as_deref() == Some("main") {
+            main = Some(tag);
+            break;
+        }
+        if tag.name.eq_ignore_ascii_case("article") {
+            articles.push(tag);
+        }
+    }
+    // Several articles are a listing (blog index, search results), not one
+    // document, so the whole page is kept then.
+    let content = match (&main, articles.as_slice()) {
+        (Some(tag), _) | (None, [tag]) => content(html, tag),
+        _ => html,
+    };
+    render(content, true).0
+}
+
+/// The text of a page's `<title>`.
+pub fn title(html: &str) -> Option<String> {
+    let tag = start_tags(html).find(|tag| tag.name.eq_ignore_ascii_case("title"))?;
+    let title = collapse_whitespace(&decode_entities(content(html, &tag)));
+    (!title.is_empty()).then_some(title)
+}
+
+fn start_tags(html: &str) -> impl Iterator<Item = Tag<'_>> {
+    tags(html).filter(|tag| !tag.closing && !tag.self_closing && !tag.name.is_empty())
+}
+
+/// The content of the element `tag` starts, up to its end tag (or the end
+/// of the document when that is missing).
+fn content<'a>(html: &'a str, tag: &Tag) -> &'a str {
+    let mut depth = 0;
+    let end = tags(&html[tag.end..])
+        .find(|t| {
+            if !t.name.eq_ignore_ascii_case(tag.name) || t.self_closing {
+                return false;
+            }
+            if !t.closing {
+                depth += 1;
+                return false;
+            }
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+            false
+        })
+        .map_or(html.len(), |t| tag.end + t.start);
+    &html[tag.end..end]
+}
+
+fn is_chrome(name: &str, tag: &Tag) -> bool {
+    if CHROME_ELEMENTS.contains(&name) {
+        return true;
+    }
+    CHROME_CONTAINERS.contains(&name)
+        && ["class", "id"].iter().any(|attr| {
+            tag.attr(attr).is_some_and(|value| {
+                value
+                    .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+                    .any(|word| CHROME_NAMES.contains(&word.to_ascii_lowercase().as_str()))
+            })
+        })
+}
+
+/// Render `html` as text, or as markdown with page chrome removed when
+/// `web` is set. Also returns the first `h1`-`h3` heading.
+fn render(html: &str, web: bool) -> (String, Option<String>) {
+    let mut text = TextBuilder::default();
+    // The element being skipped, and how deeply it is nested in itself.
+    let mut skipping: Option<(String, usize)> = None;
+    let mut pre_depth = 0usize;
+    let mut heading_start: Option<usize> = None;
+    let mut heading = None;
+    let mut pos = 0;
+
+    for tag in tags(html) {
+        let name = tag.name.to_ascii_lowercase();
+        if let Some((skipped, depth)) = &mut skipping {
+            if name == *skipped && !tag.self_closing {
+                if !tag.closing {
+                    *depth += 1;
+                } else if *depth > 1 {
+                    *depth -= 1;
+                } else {
+                    skipping = None;
+                }
+            }
+            pos = tag.end;
+            continue;
+        }
+        text.push(&decode_entities(&html[pos..tag.start]), pre_depth > 0);
+        pos = tag.end;
+
+        let opening = !tag.closing && !tag.self_closing;
+        if opening && (SKIPPED_ELEMENTS.contains(&name.as_str()) || web && is_chrome(&name, &tag)) {
+            skipping = Some((name, 1));
+            continue;
+        }
+        if name == "pre" && !tag.self_closing {
+            if tag.closing {
+                pre_depth = pre_depth.saturating_sub(1);
+            } else {
+                pre_depth += 1;
+            }
+        }
+        // Markup closing an element goes before the line break after it,
+        // markup opening one after the break before it.
+        if web && tag.closing {
+            markdown_markup(&mut text, &name, &tag);
+        }
+        if LINE_ELEMENTS.contains(&name.as_str()) {
+            text.break_line(1);
+        } else if BLOCK_ELEMENTS.contains(&name.as_str()) {
+            text.break_line(2);
+        }
+        if web && !tag.closing {
+            markdown_markup(&mut text, &name, &tag);
+        }
+        if TITLE_HEADINGS.contains(&name.as_str()) && heading.is_none() {
+            if !tag.closing {
+                heading_start = Some(tag.end);
+            } else if let Some(start) = heading_start.take() {
+                let title = collapse_whitespace(&strip_tags(&html[start..tag.start]));
+                if !title.is_empty() {
+                    heading = Some(title);
+                }
+            }
+        }
+    }
+    if skipping.is_none() {
+        text.push(&decode_entities(&html[pos..]), false);
+    }
+    (text.out.trim().to_string(), heading)
+}
+
+/// Markdown syntax written for `tag`, after the line breaks around it.
+fn markdown_markup(text: &mut TextBuilder, name: &str, tag: &Tag) {
+    let level = match name.as_bytes() {
+        [b'h', level @ b'1'..=b'6'] => Some((level - b'0') as usize),
+        _ => None,
+    };
+    match (name, tag.closing) {
+        (_, false) if level.is_some() => {
+            text.push_markup(&format!("{} ", "#".repeat(level.unwrap_or(1))));
+        }
+        ("li", false) => text.push_markup("- "),
+        ("td" | "th", false) if !text.at_line_start() => text.push_markup(" | "),
+        ("pre", false) => text.push_markup("```\n"),
+        ("pre", true) => {
+            if !text.at_line_start() {
+                text.push_markup("\n");
+            }
+            text.push_markup("```");
+        }
+        _ => {}
+    }
+}
+
+/// Accumulates text, collapsing whitespace outside `<pre>`.
+#[derive(Default)]
+struct TextBuilder {
+    out: String,
+    pending_space: bool,
+}
+
+impl TextBuilder {
+    fn push(&mut self, text: &str, preformatted: bool) {
+        if preformatted {
+            self.out.push_str(text);
+            self.pending_space = false;
+            return;
+        }
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.pending_space = true;
+                continue;
+            }
+            if self.pending_space && !self.out.is_empty() && !self.out.ends_with(['\n', ' ']) {
+                self.out.push(' ');
+            }
+            self.pending_space = false;
+            self.out.push(c);
+        }
+    }
+
+    /// Append markup (a `# ` heading marker, a list bullet) as is.
+    fn push_markup(&mut self, markup: &str) {
+        self.out.push_str(markup);
+        self.pending_space = false;
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    /// End the current line, leaving `newlines` line breaks (1 or 2) before
+    /// the next text.
+    fn break_line(&mut self, newlines: usize) {
+        self.pending_space = false;
+        let trimmed = self.out.trim_end_matches([' ', '\t']).len();
+        self.out.truncate(trimmed);
+        if self.out.is_empty() {
+            return;
+        }
+        let present = self.out.len() - self.out.trim_end_matches('\n').len();
+        for _ in present..newlines {
+            self.out.push('\n');
+        }
+    }
+}
+
+/// Text of a fragment with its tags removed, e.g. a link label holding
+/// `<span>` elements.
+pub fn strip_tags(fragment: &str) -> String {
+    let mut text = String::new();
+    let mut pos = 0;
+    for tag in tags(fragment) {
+        text.push_str(&fragment[pos..tag.start]);
+        pos = tag.end;
+    }
+    text.push_str(&fragment[pos..]);
+    decode_entities(&text)
+}
+
+pub fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// XML's five named entities plus the HTML ones common in books and web
+/// pages.
+const NAMED_ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", '\u{a0}'),
+    ("shy", '\u{ad}'),
+    ("ndash", '–'),
+    ("mdash", '—'),
+    ("hellip", '…'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+    ("laquo", '«'),
+    ("raquo", '»'),
+    ("copy", '©'),
+    ("reg", '®'),
+    ("trade", '™'),
+    ("deg", '°'),
+    ("times", '×'),
+    ("middot", '·'),
+    ("bull", '•'),
+];
+
+/// Replace character references (`&amp;`, `&#8212;`, `&#x2014;`). Unknown
+/// entities are left as written.
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&len| len <= 32)
+            .and_then(|len| {
+                let entity = &rest[1..1 + len];
+                let c = if let Some(hex) = entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                {
+                    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+                } else if let Some(dec) = entity.strip_prefix('#') {
+                    dec.parse().ok().and_then(char::from_u32)
+                } else {
+                    NAMED_ENTITIES
+                        .iter()
+                        .find(|(name, _)| *name == entity)
+                        .map(|&(_, c)| c)
+                };
+                c.map(|c| (c, len + 2))
+            });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(
+            decode_entities("a &amp; b &#65;&#x42; &unknown; & c"),
+            "a & b AB &unknown; & c"
+        );
+    }
+
+    #[test]
+    fn test_to_text() {
+        let (text, heading) = to_text(
+            "<?xml version=\"1.0\"?><!DOCTYPE html><html><head><title>x</title></head>\
+             <body><h2>Intro</h2><p>One <!-- c --> two.</p><pre>a\n  b</pre></body></html>",
+        );
+        assert_eq!(text, "Intro\n\nOne two.\n\na\n  b");
+        assert_eq!(heading.as_deref(), Some("Intro"));
+    }
+
+    #[test]
+    fn test_to_markdown_main_content() {
+        let page = r#"<!doctype html>
+<HTML><head><title>Deploys &mdash; Wiki</title>
+<script>if (a<b) { document.write("</div>"); }</script></head>
+<body>
+  <header><a href="/">Wiki home</a></header>
+  <nav><ul><li>Pages</li></ul></nav>
+  <main>
+    <div class="page-sidebar"><p>Recent changes</p></div>
+    <H1>Deploys</H1>
+    <p>Ship   it on <b>Tuesdays</b>.</p>
+    <h2>Steps</h2>
+    <ol><li>Build</li><li>Roll out</li></ol>
+    <pre><code>make deploy</code></pre>
+    <table><tr><th>Env</th><th>Owner</th></tr><tr><td>prod</td><td>ops</td></tr></table>
+  </main>
+  <footer>© 2024</footer>
+</body></HTML>"#;
+        assert_eq!(title(page).as_deref(), Some("Deploys — Wiki"));
+        assert_eq!(
+            to_markdown(page),
+            "# Deploys\n\nShip it on Tuesdays.\n\n## Steps\n\n- Build\n- Roll out\n\n\
+             ```\nmake deploy\n```\n\nEnv | Owner\nprod | ops"
+        );
+    }
+
+    #[test]
+    fn test_to_markdown_without_main() {
+        // A single article is the content; chrome around it is dropped.
+        let page = "<body><div id=\"menu\">Menu</div><article><p>Body text</p></article>\
+                    <div class=\"comments\"><p>First!</p></div></body>";
+        assert_eq!(to_markdown(page), "Body text");
+
+        // With several articles, the whole body is kept.
+        let page = "<body><article>One</article><article>Two</article></body>";
+        assert_eq!(to_markdown(page), "One\n\nTwo");
+    }
+}
@@ -1,39 +1,664 @@
-use std::collections::HashMap;
-use std::path::Path;
-use std::process::Command;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
+use futures::stream::{self, StreamExt};
 use mcp_hybrid_search_common::config::AppConfig;
-use mcp_hybrid_search_common::types::{ChunkPayload, SearchResult};
+use mcp_hybrid_search_common::embedding::{
+    check_dimension, local_execution_provider, probe_dimension,
+};
+use mcp_hybrid_search_common::retry::ApiError;
+use mcp_hybrid_search_common::types::{self, ChunkPayload, SearchResult};
+use notify_debouncer_mini::notify::{RecursiveMode, Watcher};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult};
+use serde::Serialize;
+use sha1::{Digest, Sha1};
 use walkdir::WalkDir;
 
+use crate::binary::{self, NotText};
 use crate::chunker;
+use crate::convert::{self, Converter};
 use crate::embedding;
+use crate::encoding;
+use crate::epub;
+use crate::front_matter;
+use crate::git;
+use crate::gitignore::{self, IgnoreMatcher};
+use crate::language;
+use crate::ocr;
+use crate::office;
+#[cfg(feature = "pdf-native")]
+use crate::pdf;
+use crate::progress::Progress;
 use crate::qdrant_client;
+use crate::structured::{self, Node};
 use crate::tantivy_index;
+use crate::tokens;
+use crate::web::{self, Fetched, Validators};
 
 /// Text files that can be read directly.
 const TEXT_EXTENSIONS: &[&str] = &["md", "txt"];
 
-/// Binary/rich files that require markitdown conversion.
+/// Binary/rich files that require markitdown conversion, unless read by the
+/// built-in readers (PDF, DOCX and XLSX).
 const MARKITDOWN_EXTENSIONS: &[&str] = &["pdf", "xlsx", "xls", "docx", "pptx", "csv", "html"];
 
+/// Structured documents, flattened to `path: value` lines before chunking.
+const STRUCTURED_EXTENSIONS: &[&str] = &["json", "yaml", "yml", "toml"];
+
+/// Arrays longer than this are cut to their first elements when flattened.
+const MAX_FLATTENED_ITEMS: usize = 20;
+
+/// Set by the first ctrl-c of `ragctl ingest`: the batch in progress
+/// finishes and is saved, and the rest are left for the next run.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// How ingest reads a file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileKind {
+    Text,
+    /// One of `code_extensions`: read as text, split with the code chunker.
+    Code,
+    /// JSON, YAML or TOML: flattened before chunking.
+    Structured,
+    /// Read natively, one chapter at a time.
+    Epub,
+    /// A PDF read by the built-in reader, one page at a time.
+    Pdf,
+    /// A Word document read by the built-in reader, as markdown.
+    Docx,
+    /// An Excel workbook read by the built-in reader, as markdown tables.
+    Xlsx,
+    /// Converted by a `[[converters]]` command.
+    External,
+    /// An image, read by the `ocr_command`.
+    Image,
+    Markitdown,
+}
+
+impl FileKind {
+    /// What converts files of this kind to text, recorded as the chunks'
+    /// `converter`; `None` for files read as they are. External and OCR
+    /// commands are recorded by their program's name instead.
+    fn converter(self) -> Option<&'static str> {
+        match self {
+            FileKind::Pdf | FileKind::Docx | FileKind::Xlsx => Some("native"),
+            FileKind::External => Some("external"),
+            FileKind::Image => Some("ocr"),
+            FileKind::Markitdown => Some("markitdown"),
+            FileKind::Text | FileKind::Code | FileKind::Structured | FileKind::Epub => None,
+        }
+    }
+}
+
+/// The kind of file with extension `ext`, or `None` when ingest can't read it.
+/// A configured converter takes precedence over every other kind.
+fn file_kind(config: &AppConfig, ext: &str) -> Option<FileKind> {
+    let native = config.converter == "native";
+    if convert::configured(config, ext).is_some() {
+        Some(FileKind::External)
+    } else if TEXT_EXTENSIONS.contains(&ext) {
+        Some(FileKind::Text)
+    } else if STRUCTURED_EXTENSIONS.contains(&ext) {
+        Some(FileKind::Structured)
+    } else if ext == "epub" {
+        Some(FileKind::Epub)
+    } else if config.ocr_command.is_some() && ocr::is_image(ext) {
+        Some(FileKind::Image)
+    } else if native && ext == "pdf" && cfg!(feature = "pdf-native") {
+        Some(FileKind::Pdf)
+    } else if native && ext == "docx" {
+        Some(FileKind::Docx)
+    } else if native && ext == "xlsx" {
+        Some(FileKind::Xlsx)
+    } else if MARKITDOWN_EXTENSIONS.contains(&ext) {
+        Some(FileKind::Markitdown)
+    } else if config
+        .code_extensions
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case(ext))
+    {
+        Some(FileKind::Code)
+    } else {
+        None
+    }
+}
+
+/// How often watch mode checks whether source directories appeared or
+/// were removed. Changes inside them arrive as file system events.
+const WATCH_SOURCES_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Switches shared by one-shot and watch-mode ingest.
+#[derive(Clone)]
+pub struct IngestOptions {
+    /// Remove the chunks of previously ingested files that were deleted.
+    pub prune: bool,
+    /// Walk into dot-directories and pick up dotfiles.
+    pub hidden: bool,
+    /// Ignore `.gitignore` / `.ignore` files.
+    pub no_ignore: bool,
+    /// Draw a progress line when stdout is a terminal.
+    pub progress: bool,
+    /// Write each pass's summary to this JSON file.
+    pub report: Option<String>,
+    /// Reprocess every file, changed or not.
+    pub force: bool,
+    /// Reprocess the files under these paths, changed or not. Canonical
+    /// where they exist, like state keys (see [`IngestOptions::canonical`]).
+    pub force_paths: Vec<PathBuf>,
+    /// Stop at the first failed file, URL or batch instead of carrying on.
+    pub fail_fast: bool,
+    /// Don't compare `embedding_dimension` with the collection and the
+    /// provider before ingesting.
+    pub skip_dimension_check: bool,
+}
+
+impl IngestOptions {
+    /// `paths` for [`IngestOptions::force_paths`]: state keys are canonical
+    /// paths, so each is canonicalized when it still exists.
+    pub fn canonical(paths: &[String]) -> Vec<PathBuf> {
+        paths
+            .iter()
+            .map(|path| std::fs::canonicalize(path).unwrap_or_else(|_| path.into()))
+            .collect()
+    }
+
+    fn is_forced(&self, file: &str) -> bool {
+        self.force
+            || self
+                .force_paths
+                .iter()
+                .any(|prefix| Path::new(file).starts_with(prefix))
+    }
+}
+
+/// What to ingest.
+#[derive(Debug, Default)]
+pub struct Sources {
+    /// Source directories and files.
+    pub paths: Vec<String>,
+    /// Web pages.
+    pub urls: Vec<String>,
+    /// A git repository, cloned into the git cache dir.
+    pub git: Option<git::Repo>,
+    /// Text read from stdin.
+    pub stdin: Option<Piped>,
+}
+
+/// Text piped into `ragctl ingest --stdin`.
+#[derive(Debug)]
+pub struct Piped {
+    /// The `source_path` to index it under, replacing what was indexed
+    /// under it before.
+    pub path: String,
+    /// Title for its chunks; found in the text, like a file's, when unset.
+    pub title: Option<String>,
+    pub text: String,
+}
+
+/// Outcome of one ingest pass, printed at the end and written by `--report`.
+#[derive(Debug, Default, Serialize)]
+pub struct IngestSummary {
+    pub files_ok: usize,
+    pub files_failed: usize,
+    /// Files over `max_file_size_bytes`, unreadable or binary.
+    pub files_skipped: usize,
+    pub files_unchanged: usize,
+    pub files_pruned: usize,
+    pub urls_ok: usize,
+    pub urls_failed: usize,
+    /// URLs the server reported as not modified.
+    pub urls_unchanged: usize,
+    pub chunks_indexed: usize,
+    /// Chunks dropped as copies of already indexed ones (`dedupe_chunks`).
+    pub chunks_deduplicated: usize,
+    /// Failed embedding requests, upserts and index writes.
+    pub errors: usize,
+    pub elapsed_secs: f64,
+    pub embed_requests: usize,
+    pub avg_embed_latency_ms: f64,
+    /// Points written to Qdrant, and how many a second its upserts took.
+    pub points_upserted: usize,
+    pub upsert_points_per_sec: f64,
+    /// Time embedding requests waited on the client-side rate limits, summed
+    /// over concurrent requests.
+    pub rate_limit_wait_secs: f64,
+    /// The batch sizes the pass ran with, so a run can be reproduced.
+    pub batch_sizes: BatchSizes,
+    /// Each failed or skipped source, written to `ingest_errors.json`.
+    #[serde(skip)]
+    pub failures: Vec<Failure>,
+}
+
+/// Files listed per stage in the printed summary; the rest are only in
+/// `ingest_errors.json`.
+const MAX_LISTED_FAILURES: usize = 10;
+
+/// What ingesting a source was doing when it failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Fetching a URL.
+    Fetch,
+    /// Reading, converting or chunking it.
+    Convert,
+    /// Skipped: too large, or unreadable as text.
+    Skip,
+    /// Embedding its chunks.
+    Embed,
+    /// Writing its chunks to Qdrant or Tantivy.
+    Index,
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Fetch => "fetch",
+            Self::Convert => "convert",
+            Self::Skip => "skip",
+            Self::Embed => "embed",
+            Self::Index => "index",
+        })
+    }
+}
+
+/// A source that failed or was skipped, as recorded in `ingest_errors.json`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Failure {
+    pub path: String,
+    pub stage: Stage,
+    pub message: String,
+}
+
+impl IngestSummary {
+    /// Record that `path` failed at `stage`. With `--fail-fast`, anything
+    /// but a skip aborts the pass.
+    fn fail(
+        &mut self,
+        options: &IngestOptions,
+        path: &str,
+        stage: Stage,
+        message: impl std::fmt::Display,
+    ) -> Result<()> {
+        let failure = Failure {
+            path: path.to_string(),
+            stage,
+            message: message.to_string(),
+        };
+        if options.fail_fast && stage != Stage::Skip {
+            anyhow::bail!(
+                "{} ({} failed, --fail-fast): {}",
+                failure.path,
+                stage,
+                failure.message
+            );
+        }
+        self.failures.push(failure);
+        Ok(())
+    }
+
+    /// Whether anything failed, skips aside.
+    fn has_errors(&self) -> bool {
+        self.files_failed + self.urls_failed + self.errors > 0
+    }
+}
+
+/// `ingest_file_batch_size`, `embedding_batch_size`,
+/// `qdrant_upsert_batch_size` and `qdrant_upsert_parallelism` as
+/// configured.
+#[derive(Debug, Default, Serialize)]
+pub struct BatchSizes {
+    pub files: usize,
+    pub embedding: usize,
+    pub qdrant_upsert: usize,
+    pub qdrant_upsert_parallelism: usize,
+}
+
+impl BatchSizes {
+    fn new(config: &AppConfig) -> Self {
+        Self {
+            files: config.ingest_file_batch_size,
+            embedding: config.embedding_batch_size,
+            qdrant_upsert: config.qdrant_upsert_batch_size,
+            qdrant_upsert_parallelism: config.qdrant_upsert_parallelism,
+        }
+    }
+}
+
+impl std::fmt::Display for IngestSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Ingest summary:")?;
+        writeln!(
+            f,
+            "  Files:     {} ok, {} failed, {} skipped, {} unchanged, {} pruned",
+            self.files_ok,
+            self.files_failed,
+            self.files_skipped,
+            self.files_unchanged,
+            self.files_pruned
+        )?;
+        if self.urls_ok + self.urls_failed + self.urls_unchanged > 0 {
+            writeln!(
+                f,
+                "  URLs:      {} ok, {} failed, {} unchanged",
+                self.urls_ok, self.urls_failed, self.urls_unchanged
+            )?;
+        }
+        write!(f, "  Chunks:    {} indexed", self.chunks_indexed)?;
+        if self.chunks_deduplicated > 0 {
+            write!(f, ", {} duplicates dropped", self.chunks_deduplicated)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "  Errors:    {}", self.errors)?;
+        write!(
+            f,
+            "  Embedding: {} requests, {:.1}ms average",
+            self.embed_requests, self.avg_embed_latency_ms
+        )?;
+        if self.rate_limit_wait_secs > 0.0 {
+            write!(
+                f,
+                ", {:.1}s waiting on rate limits",
+                self.rate_limit_wait_secs
+            )?;
+        }
+        writeln!(f)?;
+        if self.points_upserted > 0 {
+            writeln!(
+                f,
+                "  Upserts:   {} points, {:.0} points/s",
+                self.points_upserted, self.upsert_points_per_sec
+            )?;
+        }
+        let batches = &self.batch_sizes;
+        write!(
+            f,
+            "  Batches:   {} files, {} texts per embedding request, {} points per upsert",
+            batches.files, batches.embedding, batches.qdrant_upsert
+        )?;
+        if batches.qdrant_upsert_parallelism > 1 {
+            write!(
+                f,
+                ", {} upserts at a time",
+                batches.qdrant_upsert_parallelism
+            )?;
+        }
+        writeln!(f)?;
+        write!(f, "  Elapsed:   {:.1}s", self.elapsed_secs)?;
+
+        let mut failures: Vec<&Failure> = self.failures.iter().collect();
+        failures.sort_by_key(|failure| failure.stage);
+        for group in failures.chunk_by(|a, b| a.stage == b.stage) {
+            match group[0].stage {
+                Stage::Skip => write!(f, "\n  Skipped ({}):", group.len())?,
+                stage => write!(f, "\n  Failed to {} ({}):", stage, group.len())?,
+            }
+            for failure in group.iter().take(MAX_LISTED_FAILURES) {
+                write!(f, "\n    {}: {}", failure.path, failure.message)?;
+            }
+            if group.len() > MAX_LISTED_FAILURES {
+                write!(
+                    f,
+                    "\n    ... and {} more",
+                    group.len() - MAX_LISTED_FAILURES
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A file skipped because it, or the text converted from it, is larger than
+/// `max_file_size_bytes`. Reported as a skip rather than an error, and not
+/// recorded in the ingest state so raising the limit picks it up later.
+#[derive(Debug, thiserror::Error)]
+#[error("{path}: {what} is {size} bytes, over max_file_size_bytes ({limit})")]
+struct TooLarge {
+    path: String,
+    what: &'static str,
+    size: u64,
+    limit: u64,
+}
+
+/// A file skipped because it, or the text converted from it, looks binary.
+/// Reported as a skip, and not recorded in the ingest state.
+#[derive(Debug, thiserror::Error)]
+#[error("{path}: {what} looks binary ({reason}); --allow-binary indexes it anyway")]
+struct Binary {
+    path: String,
+    what: &'static str,
+    reason: NotText,
+}
+
 /// Ingest state file: tracks which files have been ingested and when.
 fn state_file_path(config: &AppConfig) -> std::path::PathBuf {
-    let tantivy_parent = Path::new(&config.tantivy_index_dir)
-        .parent()
-        .unwrap_or_else(|| Path::new("."));
-    tantivy_parent.join("ingest_state.json")
+    config.ingest_state_path()
+}
+
+/// State: maps absolute file path (or URL, or `repo:path` for a file from a
+/// git repository) -> what was ingested for it.
+type IngestState = HashMap<String, StateEntry>;
+
+/// The file's modified time when it was last ingested (as an RFC3339
+/// string), and the chunking parameters used, so a later change to them can be
+/// detected. Entries written before the parameters were recorded are bare
+/// timestamp strings and load with them unset. For a URL, `mtime` is the
+/// fetch time and the page's cache validators are kept for the next fetch.
+/// For a file from a git repository, `mtime` is the ingest time, and the
+/// commit it was ingested at and its blob hash are kept. For piped input,
+/// the key is the `--path` it was given and the SHA-1 of the text is kept.
+/// With `dedupe_chunks`, the hashes of the chunks indexed for the source
+/// and of those dropped as duplicates are kept too. The ids of the chunks
+/// indexed for the source are kept so they can be deleted exactly; entries
+/// written before they were recorded leave them unset, and their chunks
+/// are found by `source_path` instead.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, serde::Deserialize)]
+#[serde(from = "StateEntryRepr")]
+struct StateEntry {
+    mtime: String,
+    chunk_size: Option<usize>,
+    chunk_overlap: Option<usize>,
+    chunking_strategy: Option<String>,
+    chunk_unit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blob: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_hash: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    chunk_hashes: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    duplicate_hashes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chunk_ids: Option<Vec<String>>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum StateEntryRepr {
+    Legacy(String),
+    Entry(Box<StateEntryFields>),
+}
+
+#[derive(serde::Deserialize)]
+struct StateEntryFields {
+    mtime: String,
+    chunk_size: Option<usize>,
+    chunk_overlap: Option<usize>,
+    chunking_strategy: Option<String>,
+    chunk_unit: Option<String>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    commit: Option<String>,
+    blob: Option<String>,
+    content_hash: Option<String>,
+    #[serde(default)]
+    chunk_hashes: Vec<String>,
+    #[serde(default)]
+    duplicate_hashes: Vec<String>,
+    chunk_ids: Option<Vec<String>>,
+}
+
+impl From<StateEntryRepr> for StateEntry {
+    fn from(repr: StateEntryRepr) -> Self {
+        let fields = match repr {
+            StateEntryRepr::Legacy(mtime) => {
+                return Self {
+                    mtime,
+                    ..Self::default()
+                }
+            }
+            StateEntryRepr::Entry(fields) => *fields,
+        };
+        let StateEntryFields {
+            mtime,
+            chunk_size,
+            chunk_overlap,
+            chunking_strategy,
+            chunk_unit,
+            etag,
+            last_modified,
+            commit,
+            blob,
+            content_hash,
+            chunk_hashes,
+            duplicate_hashes,
+            chunk_ids,
+        } = fields;
+        Self {
+            mtime,
+            chunk_size,
+            chunk_overlap,
+            chunking_strategy,
+            chunk_unit,
+            etag,
+            last_modified,
+            commit,
+            blob,
+            content_hash,
+            chunk_hashes,
+            duplicate_hashes,
+            chunk_ids,
+        }
+    }
+}
+
+impl StateEntry {
+    fn new(config: &AppConfig, mtime: String) -> Self {
+        Self {
+            mtime,
+            chunk_size: Some(config.chunk_size),
+            chunk_overlap: Some(config.chunk_overlap),
+            chunking_strategy: Some(config.chunking_strategy.clone()),
+            chunk_unit: Some(config.chunk_unit.clone()),
+            etag: None,
+            last_modified: None,
+            commit: None,
+            blob: None,
+            content_hash: None,
+            chunk_hashes: Vec::new(),
+            duplicate_hashes: Vec::new(),
+            chunk_ids: None,
+        }
+    }
+
+    fn with_hashes(self, hashes: ChunkHashes) -> Self {
+        Self {
+            chunk_hashes: hashes.kept,
+            duplicate_hashes: hashes.dropped,
+            ..self
+        }
+    }
+
+    fn with_chunk_ids(self, chunk_ids: Vec<String>) -> Self {
+        Self {
+            chunk_ids: Some(chunk_ids),
+            ..self
+        }
+    }
+
+    /// The `source_path` of the chunks ingested for state key `key`.
+    fn source_path(&self, key: &str) -> String {
+        match &self.commit {
+            Some(commit) => format!("{}@{}", key, commit),
+            None => key.to_string(),
+        }
+    }
+
+    /// Whether the entry was chunked with `config`'s parameters. Entries
+    /// that predate recording a parameter count as current for it.
+    fn chunked_with(&self, config: &AppConfig) -> bool {
+        fn same<T: PartialEq + ?Sized>(recorded: Option<&T>, current: &T) -> bool {
+            recorded.is_none_or(|r| r == current)
+        }
+        same(self.chunk_size.as_ref(), &config.chunk_size)
+            && same(self.chunk_overlap.as_ref(), &config.chunk_overlap)
+            && same(
+                self.chunking_strategy.as_deref(),
+                config.chunking_strategy.as_str(),
+            )
+            && same(self.chunk_unit.as_deref(), config.chunk_unit.as_str())
+    }
+
+    fn validators(&self) -> Validators {
+        Validators {
+            etag: self.etag.clone(),
+            last_modified: self.last_modified.clone(),
+        }
+    }
+}
+
+/// Version of the ingest state file written. Version 1 files, which are a
+/// bare map of entries without a version, still load.
+const STATE_VERSION: u32 = 2;
+
+#[derive(Serialize)]
+struct StateFile<'a> {
+    version: u32,
+    files: &'a IngestState,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum StateFileRepr {
+    Versioned { version: u32, files: IngestState },
+    Unversioned(IngestState),
 }
 
-/// State: maps file path -> last modified timestamp (as string).
-type IngestState = HashMap<String, String>;
+fn parse_state(content: &str) -> serde_json::Result<IngestState> {
+    Ok(match serde_json::from_str(content)? {
+        StateFileRepr::Versioned { version, files } => {
+            if version > STATE_VERSION {
+                tracing::warn!(
+                    "Ingest state was written by a newer ragctl (version {}); reading what it can",
+                    version
+                );
+            }
+            files
+        }
+        StateFileRepr::Unversioned(files) => files,
+    })
+}
 
 fn load_state(config: &AppConfig) -> IngestState {
     let path = state_file_path(config);
     if path.exists() {
         match std::fs::read_to_string(&path) {
-            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+            Ok(content) => parse_state(&content).unwrap_or_default(),
             Err(_) => HashMap::new(),
         }
     } else {
@@ -46,8 +671,15 @@ fn save_state(config: &AppConfig, state: &IngestState) -> Result<()> {
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
     }
-    let json = serde_json::to_string_pretty(state)?;
-    std::fs::write(path, json)?;
+    let json = serde_json::to_string_pretty(&StateFile {
+        version: STATE_VERSION,
+        files: state,
+    })?;
+    // Write and rename, so an interrupted save leaves the old state whole.
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(tmp, path)?;
     Ok(())
 }
 
@@ -62,338 +694,3821 @@ fn file_modified_time(path: &str) -> Option<String> {
         })
 }
 
-/// Run the ingest pipeline for the given source directories.
-pub async fn run_ingest(config: &AppConfig, sources: &[String]) -> Result<()> {
-    // Ensure Qdrant collection exists
-    qdrant_client::ensure_collection(config).await?;
+/// Files recorded in the ingest state that no longer exist on disk. URLs
+/// are pruned only when their server reports them gone, files from a git
+/// repository when a commit removes them, and piped input never.
+fn deleted_files(state: &IngestState) -> Vec<String> {
+    let mut deleted: Vec<String> = state
+        .iter()
+        .filter(|(path, entry)| {
+            !web::is_url(path)
+                && entry.blob.is_none()
+                && entry.content_hash.is_none()
+                && !Path::new(path).exists()
+        })
+        .map(|(path, _)| path.clone())
+        .collect();
+    deleted.sort();
+    deleted
+}
 
-    // Check markitdown availability
-    let markitdown_available = check_markitdown();
-    if !markitdown_available {
-        tracing::warn!(
-            "markitdown not found in PATH. PDF/Excel/Word files will be skipped. \
-             Install with: pip install markitdown"
-        );
+/// Remove the chunks of deleted source files from Qdrant and Tantivy, and
+/// drop them from the ingest state. Files whose Qdrant delete fails stay in
+/// the state so the next run retries them.
+async fn prune_deleted_files(config: &AppConfig, state: &mut IngestState) -> Result<usize> {
+    let deleted = deleted_files(state);
+    if deleted.is_empty() {
+        return Ok(0);
     }
+    tracing::info!("Pruning {} deleted files", deleted.len());
+    prune(config, state, deleted).await
+}
 
-    // Load previous ingest state for diff detection
-    let mut state = load_state(config);
+/// Delete the chunks of the state entries `keys` from both indexes and the
+/// ingest state.
+async fn prune(config: &AppConfig, state: &mut IngestState, keys: Vec<String>) -> Result<usize> {
+    let mut pruned = Vec::new();
+    for key in keys {
+        let previous = Previous::new(&key, state.get(&key));
+        match previous.delete_from_qdrant(config).await {
+            Ok(()) => pruned.push((key, previous)),
+            Err(e) => tracing::error!(
+                "Failed to delete {} from Qdrant: {}",
+                previous.source_path,
+                e
+            ),
+        }
+    }
+    delete_from_tantivy(config, pruned.iter().map(|(_, previous)| previous))?;
 
-    // Collect files
-    let files = collect_files(sources, markitdown_available);
-    tracing::info!("Found {} candidate files", files.len());
+    for (key, previous) in &pruned {
+        tracing::info!("Pruned deleted file: {}", previous.source_path);
+        state.remove(key);
+    }
+    save_state(config, state)?;
+    Ok(pruned.len())
+}
 
-    if files.is_empty() {
-        tracing::warn!("No files found in the specified source directories");
-        return Ok(());
+/// Run the ingest pipeline for the given sources.
+pub async fn run_ingest(
+    config: &AppConfig,
+    sources: &Sources,
+    options: &IngestOptions,
+) -> Result<()> {
+    // Fail on bad chunking settings before touching any file.
+    chunker::Strategy::from_config(config)?;
+    chunker::Unit::from_config(config)?;
+    convert::check(config)?;
+    ocr::command(config)?;
+    encoding::configured(config)?;
+    if config.embedding_provider == "local" {
+        let provider = local_execution_provider(config)?;
+        tracing::info!("Local embeddings run on {}", provider.name());
     }
+    if !options.skip_dimension_check {
+        check_dimensions(config).await?;
+    }
+    // Ensure Qdrant collection exists
+    qdrant_client::ensure_collection(config).await?;
+    let markitdown_available = markitdown_available();
+    let config = &resolve_converter(config, markitdown_available)?;
 
-    // Filter to only changed/new files
-    let total_candidates = files.len();
-    let files_to_process: Vec<String> = files
-        .into_iter()
-        .filter(|f| {
-            let current_mtime = file_modified_time(f).unwrap_or_default();
-            match state.get(f) {
-                Some(prev_mtime) if prev_mtime == &current_mtime => {
-                    tracing::debug!("Skipping unchanged file: {}", f);
-                    false
-                }
-                _ => true,
-            }
-        })
-        .collect();
+    // Load previous ingest state for diff detection
+    let mut state = load_state(config);
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        INTERRUPTED.store(true, Ordering::Relaxed);
+        tracing::warn!("Interrupted; finishing the current batch (ctrl-c again to stop now)");
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+    let summary = ingest_pass(config, sources, options, markitdown_available, &mut state).await?;
+    if interrupted() {
+        anyhow::bail!("Interrupted; the next run resumes with the files not yet ingested");
+    }
+    // The pass may have re-ingested the last chunks without path prefixes.
+    if let Err(e) = qdrant_client::index_path_prefixes(config).await {
+        tracing::warn!("Could not index path prefixes: {:#}", e);
+    }
+    if config.fail_on_ingest_errors && summary.has_errors() {
+        let failed = summary.files_failed + summary.urls_failed;
+        if failed == 0 {
+            anyhow::bail!("Ingest finished with {} errors", summary.errors);
+        }
+        anyhow::bail!(
+            "Ingest finished with {} failed sources; see {}",
+            failed,
+            config.ingest_errors_path().display()
+        );
+    }
+    Ok(())
+}
 
-    let skipped = total_candidates - files_to_process.len();
-    tracing::info!(
-        "{} files need processing ({} unchanged, skipped)",
-        files_to_process.len(),
-        skipped
-    );
+/// Fail before anything is embedded when `embedding_dimension` disagrees
+/// with the collection's vector size or the size of the provider's vectors.
+/// A value that can't be fetched is logged and left out of the check.
+async fn check_dimensions(config: &AppConfig) -> Result<()> {
+    let collection = qdrant_client::collection_dimension(config)
+        .await
+        .inspect_err(|e| tracing::warn!("Could not fetch the collection's vector size: {:#}", e))
+        .ok()
+        .flatten();
+    let provider = probe_dimension(config)
+        .await
+        .inspect_err(|e| tracing::warn!("Could not probe the embedding dimension: {:#}", e))
+        .ok();
+    check_dimension(config, collection, provider)
+}
 
-    if files_to_process.is_empty() {
-        tracing::info!("All files are up to date. Nothing to ingest.");
-        return Ok(());
+/// Ingest once, then keep the index current until ctrl-c: watch the source
+/// paths for file system events and, once they have been quiet for
+/// `watch_debounce_ms`, re-ingest what changed. A pass in progress finishes
+/// before shutdown. URLs and the git repository are ingested by the first
+/// pass only.
+pub async fn run_watch(
+    config: &AppConfig,
+    sources: &Sources,
+    options: &IngestOptions,
+) -> Result<()> {
+    chunker::Strategy::from_config(config)?;
+    chunker::Unit::from_config(config)?;
+    convert::check(config)?;
+    ocr::command(config)?;
+    encoding::configured(config)?;
+    if !options.skip_dimension_check {
+        check_dimensions(config).await?;
     }
+    qdrant_client::ensure_collection(config).await?;
+    let markitdown_available = markitdown_available();
+    let config = &resolve_converter(config, markitdown_available)?;
+    let mut state = load_state(config);
+    ingest_pass(config, sources, options, markitdown_available, &mut state).await?;
+    if !sources.urls.is_empty() {
+        tracing::info!(
+            "Not watching {} web pages; only the initial pass fetches them",
+            sources.urls.len()
+        );
+    }
+    let sources = &sources.paths;
+    // Forcing applies to the initial pass only.
+    let options = &IngestOptions {
+        force: false,
+        force_paths: Vec::new(),
+        ..options.clone()
+    };
 
-    let mut total_chunks = 0;
-    let mut total_errors = 0;
-    let mut processed_files: Vec<String> = Vec::new();
-
-    // Process files in batches
-    let batch_size = 10;
-    for batch in files_to_process.chunks(batch_size) {
-        let mut all_chunks = Vec::new();
+    let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = new_debouncer(
+        Duration::from_millis(config.watch_debounce_ms),
+        move |result: DebounceEventResult| {
+            let _ = tx.send(result);
+        },
+    )?;
+    let mut present = present_sources(sources);
+    for source in &present {
+        watch_source(watcher.watcher(), source);
+    }
+    for source in sources.iter().filter(|source| !present.contains(*source)) {
+        tracing::warn!(
+            "Source directory {} does not exist; it is watched once it appears",
+            source
+        );
+    }
+    tracing::info!(
+        "Watching {} source directories for changes (press ctrl-c to stop)",
+        present.len()
+    );
 
-        for file_path in batch {
-            match process_file(config, file_path) {
-                Ok(chunks) => {
-                    all_chunks.extend(chunks);
-                    processed_files.push(file_path.clone());
-                }
-                Err(e) => {
-                    tracing::error!("Error processing {}: {}", file_path, e);
-                    total_errors += 1;
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        let mut changed = tokio::select! {
+            result = &mut ctrl_c => {
+                result?;
+                break;
+            }
+            Some(result) = events.recv() => {
+                if let Err(e) = result {
+                    tracing::warn!("File watcher error: {}", e);
                 }
+                true
             }
-        }
+            _ = tokio::time::sleep(WATCH_SOURCES_INTERVAL) => false,
+        };
+        // Events that queued up during the last pass need one rescan only.
+        while events.try_recv().is_ok() {}
 
-        if all_chunks.is_empty() {
+        let now_present = present_sources(sources);
+        for source in now_present.difference(&present) {
+            tracing::info!("Source directory appeared: {}", source);
+            watch_source(watcher.watcher(), source);
+            changed = true;
+        }
+        for source in present.difference(&now_present) {
+            tracing::info!("Source directory removed: {}", source);
+            watcher.watcher().unwatch(Path::new(source)).ok();
+            changed = true;
+        }
+        present = now_present;
+        if !changed {
             continue;
         }
 
-        // Get embeddings for all chunks in this batch.
-        // We embed in sub-batches and collect only successfully embedded chunks.
-        let embed_batch_size = 20;
-        let mut embedded_chunks: Vec<ChunkPayload> = Vec::new();
-        let mut all_embeddings: Vec<Vec<f32>> = Vec::new();
-
-        for (sub_chunks, text_batch) in chunk_sub_batches(&all_chunks, embed_batch_size) {
-            match embedding::get_embeddings(config, &text_batch).await {
-                Ok(embeddings) => {
-                    embedded_chunks.extend(sub_chunks.to_vec());
-                    all_embeddings.extend(embeddings);
-                }
-                Err(e) => {
-                    tracing::error!(
-                        "Embedding error (skipping {} chunks): {}",
-                        sub_chunks.len(),
-                        e
-                    );
-                    total_errors += 1;
-                }
+        let watched: Vec<String> = present.iter().cloned().collect();
+        let files = match collect_files(config, &watched, markitdown_available, options) {
+            Ok(files) => files,
+            Err(e) => {
+                tracing::error!("{:#}", e);
+                continue;
             }
-        }
-
-        if embedded_chunks.is_empty() {
-            tracing::warn!("No chunks were successfully embedded in this batch");
+        };
+        let changes = pending_changes(&state, &files, options.prune);
+        if changes.is_empty() {
             continue;
         }
 
-        // Upsert to Qdrant
+        let deleted = changes.iter().filter(|(_, m)| m.is_none()).count();
+        tracing::info!(
+            "Detected {} changed and {} deleted files, updating index",
+            changes.len() - deleted,
+            deleted
+        );
+        let watched = Sources {
+            paths: watched,
+            ..Sources::default()
+        };
         if let Err(e) =
-            qdrant_client::upsert_chunks(config, &embedded_chunks, &all_embeddings).await
+            ingest_pass(config, &watched, options, markitdown_available, &mut state).await
         {
-            tracing::error!("Qdrant upsert error: {}", e);
-            total_errors += 1;
-        }
-
-        // Index in Tantivy (all chunks, not just embedded — BM25 doesn't need vectors)
-        if let Err(e) = tantivy_index::index_chunks(config, &all_chunks) {
-            tracing::error!("Tantivy index error: {}", e);
-            total_errors += 1;
+            tracing::error!("Incremental ingest failed: {:#}", e);
         }
-
-        total_chunks += embedded_chunks.len();
-        tracing::info!(
-            "Processed batch: {} chunks (total: {})",
-            all_chunks.len(),
-            total_chunks
-        );
     }
 
-    // Update state for successfully processed files
-    for file_path in &processed_files {
-        if let Some(mtime) = file_modified_time(file_path) {
-            state.insert(file_path.clone(), mtime);
-        }
-    }
     save_state(config, &state)?;
-
-    tracing::info!(
-        "Ingest complete: {} files processed, {} chunks indexed, {} errors",
-        processed_files.len(),
-        total_chunks,
-        total_errors
-    );
+    tracing::info!("Stopped watching; ingest state saved");
     Ok(())
 }
 
-/// Split chunks into sub-batches for embedding, returning (chunk_slice, texts) pairs.
-fn chunk_sub_batches(
-    chunks: &[ChunkPayload],
-    batch_size: usize,
-) -> Vec<(&[ChunkPayload], Vec<String>)> {
-    chunks
-        .chunks(batch_size)
-        .map(|sub| {
-            let texts: Vec<String> = sub.iter().map(|c| c.text.clone()).collect();
-            (sub, texts)
-        })
+/// Start watching `source` and everything below it, logging a failure so the
+/// other sources stay watched.
+fn watch_source(watcher: &mut dyn Watcher, source: &str) {
+    if let Err(e) = watcher.watch(Path::new(source), RecursiveMode::Recursive) {
+        tracing::warn!("Could not watch {}: {}", source, e);
+    }
+}
+
+/// Sources that currently exist on disk.
+fn present_sources(sources: &[String]) -> BTreeSet<String> {
+    sources
+        .iter()
+        .filter(|source| Path::new(source).exists())
+        .cloned()
         .collect()
 }
 
-/// Check if markitdown CLI is available.
-fn check_markitdown() -> bool {
-    Command::new("markitdown").arg("--help").output().is_ok()
+/// A file that needs ingesting, with its current mtime, or a deleted file
+/// (`None`) that needs pruning.
+type Change = (String, Option<String>);
+
+/// New or modified files among `files`, plus deleted files when `prune` is
+/// set, sorted by path.
+fn pending_changes(state: &IngestState, files: &[String], prune: bool) -> Vec<Change> {
+    let mut changes: Vec<Change> = files
+        .iter()
+        .filter_map(|f| {
+            let mtime = file_modified_time(f);
+            (state.get(f).map(|e| &e.mtime) != mtime.as_ref()).then(|| (f.clone(), mtime))
+        })
+        .collect();
+    if prune {
+        changes.extend(deleted_files(state).into_iter().map(|f| (f, None)));
+    }
+    changes.sort();
+    changes
+}
+
+/// Check markitdown availability, warning when it is missing.
+fn markitdown_available() -> bool {
+    let available = convert::markitdown_installed();
+    if !available {
+        tracing::warn!(
+            "markitdown not found in PATH. Files that need it will be skipped. \
+             Install with: pip install markitdown"
+        );
+    }
+    available
 }
 
-/// Convert a file to markdown text using markitdown CLI.
-fn convert_with_markitdown(file_path: &str) -> Result<String> {
-    tracing::info!("Converting with markitdown: {}", file_path);
-    let output = Command::new("markitdown")
-        .arg(file_path)
-        .output()
-        .map_err(|e| anyhow::anyhow!("Failed to run markitdown: {}", e))?;
+/// `config` with its `converter` resolved to `native` or `markitdown`:
+/// `auto` uses the built-in readers only when markitdown is missing.
+fn resolve_converter(config: &AppConfig, markitdown_available: bool) -> Result<AppConfig> {
+    let native = match config.converter.as_str() {
+        "markitdown" => false,
+        "native" => true,
+        "auto" | "" => !markitdown_available,
+        other => anyhow::bail!(
+            "Unknown converter '{}'. Supported values: auto, native, markitdown",
+            other
+        ),
+    };
+    if native && !cfg!(feature = "pdf-native") {
+        tracing::warn!(
+            "PDFs need markitdown in a build without the 'pdf-native' feature. \
+             Build with: cargo build --features pdf-native"
+        );
+    } else if native && config.converter != "native" {
+        tracing::info!("Reading PDF, DOCX and XLSX files with the built-in readers");
+    }
+    Ok(AppConfig {
+        converter: if native { "native" } else { "markitdown" }.to_string(),
+        ..config.clone()
+    })
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("markitdown failed for {}: {}", file_path, stderr);
+/// Whether processing failed in a way reported as a skip rather than an
+/// error: the file or its converted text is too large or looks binary, or a
+/// PDF has no text that can be read.
+fn is_skip(error: &anyhow::Error) -> bool {
+    #[cfg(feature = "pdf-native")]
+    if error.is::<pdf::Unreadable>() {
+        return true;
     }
+    error.is::<TooLarge>() || error.is::<Binary>() || error.is::<convert::OutputTooLarge>()
+}
+
+/// Prune, then ingest every new or changed file under the source paths,
+/// every changed page and every changed file in the git repository,
+/// updating and saving `state`. Prints the summary and writes the report,
+/// if any.
+async fn ingest_pass(
+    config: &AppConfig,
+    sources: &Sources,
+    options: &IngestOptions,
+    markitdown_available: bool,
+    state: &mut IngestState,
+) -> Result<IngestSummary> {
+    let started = Instant::now();
+    let waited_before = embedding::rate_limit_wait(config);
+    let stats = EmbedStats::default();
+    let mut summary = IngestSummary {
+        batch_sizes: BatchSizes::new(config),
+        ..IngestSummary::default()
+    };
+    let result = ingest_sources(
+        config,
+        sources,
+        options,
+        markitdown_available,
+        state,
+        &stats,
+        &mut summary,
+    )
+    .await;
+    // Also after a --fail-fast abort, which is when the report is wanted.
+    write_errors(config, &summary.failures)?;
+    result?;
 
-    let text = String::from_utf8_lossy(&output.stdout).to_string();
-    if text.trim().is_empty() {
-        anyhow::bail!("markitdown returned empty output for {}", file_path);
+    let waited = embedding::rate_limit_wait(config).saturating_sub(waited_before);
+    summary.rate_limit_wait_secs = waited.as_secs_f64();
+    summary.embed_requests = stats.requests.get();
+    if summary.embed_requests > 0 {
+        // The latency of the provider, not of the queue in front of it.
+        let time = stats.time.get().saturating_sub(waited);
+        summary.avg_embed_latency_ms = time.as_secs_f64() * 1000.0 / summary.embed_requests as f64;
+    }
+    summary.points_upserted = stats.points_upserted.get();
+    let upsert_time = stats.upsert_time.get().as_secs_f64();
+    if upsert_time > 0.0 {
+        summary.upsert_points_per_sec = summary.points_upserted as f64 / upsert_time;
     }
+    if config.qdrant_wait_for_green && summary.points_upserted > 0 {
+        wait_for_green(config).await?;
+    }
+    summary.elapsed_secs = started.elapsed().as_secs_f64();
 
-    Ok(text)
+    println!("{}", summary);
+    if let Some(path) = &options.report {
+        std::fs::write(path, serde_json::to_string_pretty(&summary)?)?;
+        tracing::info!("Wrote ingest report to {}", path);
+    }
+    Ok(summary)
 }
 
-/// Collect all supported files from source directories.
-fn collect_files(sources: &[String], markitdown_available: bool) -> Vec<String> {
-    let mut files = Vec::new();
+/// How long an ingest waits for the collection to turn green.
+const GREEN_TIMEOUT: Duration = Duration::from_secs(600);
 
-    for source in sources {
-        let path = Path::new(source);
-        if !path.exists() {
-            tracing::warn!("Source path does not exist: {}", source);
-            continue;
-        }
+/// `qdrant_wait_for_green`: hold the end of the ingest until Qdrant has
+/// indexed it. Taking too long is only warned about; the points are in.
+async fn wait_for_green(config: &AppConfig) -> Result<()> {
+    tracing::info!(
+        "Waiting for Qdrant collection '{}' to finish indexing",
+        config.collection_name
+    );
+    if !qdrant_client::wait_for_green(config, GREEN_TIMEOUT).await? {
+        tracing::warn!(
+            "Qdrant collection '{}' is still indexing after {}s; searches may not see all \
+             the new points yet",
+            config.collection_name,
+            GREEN_TIMEOUT.as_secs()
+        );
+    }
+    Ok(())
+}
 
-        for entry in WalkDir::new(path).follow_links(true) {
-            match entry {
-                Ok(entry) => {
-                    if entry.file_type().is_file() {
-                        if let Some(ext) = entry.path().extension() {
-                            let ext_str = ext.to_string_lossy().to_lowercase();
-                            if TEXT_EXTENSIONS.contains(&ext_str.as_str()) {
-                                files.push(entry.path().to_string_lossy().to_string());
-                            } else if MARKITDOWN_EXTENSIONS.contains(&ext_str.as_str()) {
-                                if markitdown_available {
-                                    files.push(entry.path().to_string_lossy().to_string());
-                                } else {
-                                    tracing::warn!(
-                                        "Skipping {} (markitdown not available)",
-                                        entry.path().display()
-                                    );
-                                }
-                            }
-                        }
+async fn ingest_sources(
+    config: &AppConfig,
+    sources: &Sources,
+    options: &IngestOptions,
+    markitdown_available: bool,
+    state: &mut IngestState,
+    stats: &EmbedStats,
+    summary: &mut IngestSummary,
+) -> Result<()> {
+    run_pass(
+        config,
+        &sources.paths,
+        options,
+        markitdown_available,
+        state,
+        stats,
+        summary,
+    )
+    .await?;
+    if !sources.urls.is_empty() {
+        ingest_urls(config, &sources.urls, options, state, stats, summary).await?;
+    }
+    if let Some(repo) = &sources.git {
+        ingest_git(
+            config,
+            repo,
+            options,
+            markitdown_available,
+            state,
+            stats,
+            summary,
+        )
+        .await?;
+    }
+    if let Some(piped) = &sources.stdin {
+        ingest_piped(config, piped, options, state, stats, summary).await?;
+    }
+    Ok(())
+}
+
+/// Write the failures of a pass to `ingest_errors.json`, or remove the
+/// file of an earlier pass when there were none.
+fn write_errors(config: &AppConfig, failures: &[Failure]) -> Result<()> {
+    let path = config.ingest_errors_path();
+    if failures.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(failures)?)?;
+    tracing::info!("Wrote {} failures to {}", failures.len(), path.display());
+    Ok(())
+}
+
+async fn run_pass(
+    config: &AppConfig,
+    sources: &[String],
+    options: &IngestOptions,
+    markitdown_available: bool,
+    state: &mut IngestState,
+    stats: &EmbedStats,
+    summary: &mut IngestSummary,
+) -> Result<()> {
+    if options.prune {
+        summary.files_pruned = prune_deleted_files(config, state).await?;
+    }
+
+    // Collect files
+    let files = collect_files(config, sources, markitdown_available, options)?;
+    tracing::info!("Found {} candidate files", files.len());
+
+    if files.is_empty() {
+        if !sources.is_empty() {
+            tracing::warn!("No files found in the specified source directories");
+        }
+        return Ok(());
+    }
+
+    let roots = SourceRoots::new(config);
+    let total_candidates = files.len();
+    let files_to_process = files_to_process(config, &roots, files, options, state);
+
+    summary.files_unchanged = total_candidates - files_to_process.len();
+    tracing::info!(
+        "{} files need processing ({} unchanged, skipped)",
+        files_to_process.len(),
+        summary.files_unchanged
+    );
+
+    if files_to_process.is_empty() {
+        tracing::info!("All files are up to date. Nothing to ingest.");
+        return Ok(());
+    }
+
+    let mut progress = Progress::new(files_to_process.len(), options.progress);
+    let mut dedupe = Dedupe::new(config, state);
+
+    // Process files in batches, saving the state after each so an
+    // interrupted run resumes where it stopped.
+    for (i, batch) in files_to_process
+        .chunks(config.ingest_file_batch_size)
+        .enumerate()
+    {
+        if interrupted() {
+            let remaining = files_to_process.len() - i * config.ingest_file_batch_size;
+            tracing::warn!("Interrupted; {} files left for the next run", remaining);
+            break;
+        }
+        let mut processed_files = Vec::new();
+        let mut all_chunks = Vec::new();
+        // Files indexed before, whose old chunks the new ones replace.
+        let mut reindexed = Vec::new();
+        let mut recognized = recognize_images(config, batch)?;
+
+        for file_path in batch {
+            progress.start_file(file_path);
+            let file_config = roots.config_for(file_path, config);
+            match process_file_with(file_config, file_path, recognized.remove(file_path)) {
+                Ok(mut chunks) => {
+                    let hashes = dedupe_chunks(&mut dedupe, file_path, state, &mut chunks, summary);
+                    processed_files.push((file_path.clone(), hashes, chunk_ids(&chunks)));
+                    all_chunks.extend(chunks);
+                    if state.contains_key(file_path) || options.is_forced(file_path) {
+                        reindexed.push(Previous::new(file_path, state.get(file_path)));
                     }
                 }
+                Err(e) if is_skip(&e) => {
+                    tracing::warn!("Skipping {}", e);
+                    summary.files_skipped += 1;
+                    summary.fail(options, file_path, Stage::Skip, e)?;
+                }
                 Err(e) => {
-                    tracing::warn!("Error walking directory: {}", e);
+                    tracing::error!("Error processing {}: {}", file_path, e);
+                    summary.files_failed += 1;
+                    summary.fail(options, file_path, Stage::Convert, format!("{:#}", e))?;
                 }
             }
+            progress.finish_file();
         }
+
+        let indexed = index_batch(config, &all_chunks, &reindexed, stats, summary).await;
+        progress.add_chunks(indexed.chunks);
+        record_batch(
+            config,
+            &roots,
+            options,
+            processed_files,
+            &indexed,
+            state,
+            summary,
+        )?;
+        save_state(config, state)?;
     }
+    progress.finish();
+
+    Ok(())
+}
 
+/// The `files` that need ingesting: new or changed ones, ones whose
+/// chunking parameters changed or whose deduplicated chunks need indexing,
+/// plus forced ones.
+fn files_to_process(
+    config: &AppConfig,
+    roots: &SourceRoots,
+    files: Vec<String>,
+    options: &IngestOptions,
+    state: &IngestState,
+) -> Vec<String> {
+    let missing = missing_chunks(config, state);
     files
+        .into_iter()
+        .filter(|f| {
+            if options.is_forced(f) {
+                return true;
+            }
+            let current_mtime = file_modified_time(f).unwrap_or_default();
+            match state.get(f) {
+                Some(prev)
+                    if prev.mtime == current_mtime
+                        && prev.chunked_with(roots.config_for(f, config))
+                        && !missing.contains(f) =>
+                {
+                    tracing::debug!("Skipping unchanged file: {}", f);
+                    false
+                }
+                _ => true,
+            }
+        })
+        .collect()
 }
 
-/// Process a single file into chunks.
-fn process_file(config: &AppConfig, file_path: &str) -> Result<Vec<ChunkPayload>> {
-    let path = Path::new(file_path);
-    let ext = path
-        .extension()
-        .map(|e| e.to_string_lossy().to_lowercase())
-        .unwrap_or_else(|| "txt".to_string());
+/// Record in `state` the files of a batch whose chunks all made it into
+/// both indexes. The others are counted as failed and left out, so the next
+/// run retries them.
+fn record_batch(
+    config: &AppConfig,
+    roots: &SourceRoots,
+    options: &IngestOptions,
+    processed_files: Vec<(String, ChunkHashes, Vec<String>)>,
+    indexed: &Indexed,
+    state: &mut IngestState,
+    summary: &mut IngestSummary,
+) -> Result<()> {
+    for (file_path, hashes, ids) in processed_files {
+        if let Some((stage, message)) = indexed.failed(&file_path) {
+            tracing::error!(
+                "Failed to index {}; it is retried on the next run",
+                file_path
+            );
+            summary.files_failed += 1;
+            summary.fail(options, &file_path, *stage, message)?;
+            continue;
+        }
+        summary.files_ok += 1;
+        if let Some(mtime) = file_modified_time(&file_path) {
+            let entry = StateEntry::new(roots.config_for(&file_path, config), mtime);
+            state.insert(file_path, entry.with_hashes(hashes).with_chunk_ids(ids));
+        }
+    }
+    Ok(())
+}
 
-    // Read or convert file content
-    let content = if MARKITDOWN_EXTENSIONS.contains(&ext.as_str()) {
-        convert_with_markitdown(file_path)?
-    } else {
-        std::fs::read_to_string(file_path)?
-    };
+/// Time spent in embedding requests over a pass, and how many were sent;
+/// and the same for Qdrant upserts, with the points they wrote.
+#[derive(Default)]
+struct EmbedStats {
+    time: Cell<Duration>,
+    requests: Cell<usize>,
+    upsert_time: Cell<Duration>,
+    points_upserted: Cell<usize>,
+}
 
-    let file_name = path
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_default();
+/// The chunks indexed for a source before, which a re-index replaces or a
+/// prune deletes.
+#[derive(Debug)]
+struct Previous {
+    /// The `source_path` they are stored under.
+    source_path: String,
+    /// Their ids, when the ingest state recorded them. Otherwise they are
+    /// found by `source_path`.
+    chunk_ids: Option<Vec<String>>,
+}
+
+impl Previous {
+    /// The chunks of state key `key`, whose entry is `entry`. A key without
+    /// an entry (a forced file indexed by an older ragctl) is found by path.
+    fn new(key: &str, entry: Option<&StateEntry>) -> Self {
+        match entry {
+            Some(entry) => Self {
+                source_path: entry.source_path(key),
+                chunk_ids: entry.chunk_ids.clone(),
+            },
+            None => Self {
+                source_path: key.to_string(),
+                chunk_ids: None,
+            },
+        }
+    }
 
-    let title = chunker::extract_title(&content, &file_name);
-    let chunks = chunker::chunk_text(&content, config.chunk_size, config.chunk_overlap);
+    async fn delete_from_qdrant(&self, config: &AppConfig) -> Result<()> {
+        match &self.chunk_ids {
+            Some(ids) => qdrant_client::delete_chunks(config, ids).await,
+            None => qdrant_client::delete_by_source_path(config, &self.source_path)
+                .await
+                .map(|_| ()),
+        }
+    }
+}
 
-    let now = chrono::Utc::now().to_rfc3339();
+/// Delete the chunks of each of `previous` from Tantivy.
+fn delete_from_tantivy<'a>(
+    config: &AppConfig,
+    previous: impl IntoIterator<Item = &'a Previous>,
+) -> Result<()> {
+    let mut ids = Vec::new();
+    let mut paths = Vec::new();
+    for previous in previous {
+        match &previous.chunk_ids {
+            Some(chunk_ids) => ids.extend(chunk_ids.iter().cloned()),
+            None => paths.push(previous.source_path.clone()),
+        }
+    }
+    tantivy_index::delete_chunks(config, &ids)?;
+    tantivy_index::delete_by_source_paths(config, &paths)
+}
 
-    let payloads: Vec<ChunkPayload> = chunks
+/// The ids of `chunks`, as recorded in the ingest state.
+fn chunk_ids(chunks: &[ChunkPayload]) -> Vec<String> {
+    chunks.iter().map(|c| c.chunk_id.clone()).collect()
+}
+
+/// The embeddings of the titles of `chunks`, when the collection has a
+/// title vector. Titles that fail to embed are left out, with a warning,
+/// rather than failing their chunks: searches use the text vectors unless
+/// asked for titles.
+async fn embed_titles(
+    config: &AppConfig,
+    chunks: &[ChunkPayload],
+    stats: &EmbedStats,
+) -> HashMap<String, Vec<f32>> {
+    let mut embedded = HashMap::new();
+    match qdrant_client::vector_layout(config).await {
+        Ok(Some(layout)) if layout.title => {}
+        Ok(_) => return embedded,
+        Err(e) => {
+            tracing::warn!("Could not read the Qdrant collection's vectors: {:#}", e);
+            return embedded;
+        }
+    }
+    let mut titles: Vec<String> = chunks
         .iter()
-        .enumerate()
-        .map(|(i, chunk_text)| {
-            let chunk_id = uuid::Uuid::new_v4().to_string();
-            ChunkPayload {
-                chunk_id,
-                source_path: file_path.to_string(),
-                source_type: ext.clone(),
-                title: title.clone(),
-                chunk_index: i as u32,
-                text: chunk_text.clone(),
-                updated_at: now.clone(),
-            }
-        })
+        .map(|chunk| chunk.title.clone())
+        .filter(|title| !title.trim().is_empty())
         .collect();
+    titles.sort();
+    titles.dedup();
+    for batch in titles.chunks(config.embedding_batch_size) {
+        let started = Instant::now();
+        let result = embedding::get_embeddings(config, batch).await;
+        stats.time.set(stats.time.get() + started.elapsed());
+        stats.requests.set(stats.requests.get() + 1);
+        match result {
+            Ok(vectors) => embedded.extend(batch.iter().cloned().zip(vectors)),
+            Err(e) => tracing::warn!("Could not embed {} titles: {:#}", batch.len(), e),
+        }
+    }
+    embedded
+}
 
-    Ok(payloads)
+/// What [`index_batch`] got done.
+#[derive(Debug, Default)]
+struct Indexed {
+    /// Chunks embedded and upserted.
+    chunks: usize,
+    /// The `source_path`s with a chunk that failed to embed or index, with
+    /// the first error. They must not be recorded as ingested, so the next
+    /// run retries them.
+    failed: HashMap<String, (Stage, String)>,
 }
 
-/// Reciprocal Rank Fusion (RRF) merge of vector and BM25 results.
-pub fn rrf_merge(
-    vector_results: &[SearchResult],
-    bm25_results: &[SearchResult],
-    top_k: usize,
-) -> Vec<SearchResult> {
-    let k = 60.0; // RRF constant
+impl Indexed {
+    fn failed(&self, source_path: &str) -> Option<&(Stage, String)> {
+        self.failed.get(source_path)
+    }
 
-    let mut scores: HashMap<String, f64> = HashMap::new();
-    let mut result_map: HashMap<String, SearchResult> = HashMap::new();
+    /// Record that the sources of `chunks` failed at `stage`.
+    fn fail(&mut self, chunks: &[ChunkPayload], stage: Stage, message: &str) {
+        for chunk in chunks {
+            self.failed
+                .entry(chunk.source_path.clone())
+                .or_insert_with(|| (stage, message.to_string()));
+        }
+    }
+}
 
-    // Score vector results
-    for (rank, result) in vector_results.iter().enumerate() {
-        let rrf_score = 1.0 / (k + rank as f64 + 1.0);
-        *scores.entry(result.chunk_id.clone()).or_insert(0.0) += rrf_score;
-        result_map
-            .entry(result.chunk_id.clone())
-            .or_insert_with(|| result.clone());
+/// Embed `chunks`, delete the previous chunks of the `reindexed` sources and
+/// index the new ones in Qdrant and Tantivy. Failures are logged and counted
+/// in `summary`.
+async fn index_batch(
+    config: &AppConfig,
+    chunks: &[ChunkPayload],
+    reindexed: &[Previous],
+    stats: &EmbedStats,
+    summary: &mut IngestSummary,
+) -> Indexed {
+    if chunks.is_empty() {
+        // Sources that now have no chunks (empty, or all duplicates).
+        delete_old_chunks(config, reindexed, summary).await;
+        return Indexed::default();
     }
+    let marked = mark_truncated(chunks, config.embedding_max_tokens);
+    let chunks = &*marked;
 
-    // Score BM25 results
-    for (rank, result) in bm25_results.iter().enumerate() {
-        let rrf_score = 1.0 / (k + rank as f64 + 1.0);
-        *scores.entry(result.chunk_id.clone()).or_insert(0.0) += rrf_score;
-        result_map
-            .entry(result.chunk_id.clone())
-            .or_insert_with(|| result.clone());
+    // Get embeddings for all chunks in this batch.
+    // We embed in sub-batches and collect only successfully embedded chunks.
+    let (embedded_chunks, all_embeddings, failed) = embed_sub_batches(
+        chunks,
+        config.embedding_batch_size,
+        config.embedding_concurrency,
+        |texts| async move {
+            let started = Instant::now();
+            let result = embedding::get_embeddings(config, &texts).await;
+            stats.time.set(stats.time.get() + started.elapsed());
+            stats.requests.set(stats.requests.get() + 1);
+            result
+        },
+    )
+    .await;
+    summary.errors += failed.len();
+    let mut indexed = Indexed {
+        chunks: embedded_chunks.len(),
+        ..Indexed::default()
+    };
+    for (sub_chunks, message) in &failed {
+        indexed.fail(sub_chunks, Stage::Embed, message);
     }
 
-    // Sort by fused score
-    let mut scored: Vec<(String, f64)> = scores.into_iter().collect();
-    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    if embedded_chunks.is_empty() {
+        tracing::warn!("No chunks were successfully embedded in this batch");
+        return indexed;
+    }
 
-    scored
-        .into_iter()
-        .take(top_k)
-        .filter_map(|(id, score)| {
-            result_map.remove(&id).map(|mut r| {
-                r.score = score;
-                r
-            })
-        })
+    // Upsert to Qdrant. Chunk ids derive from path and index, so this
+    // overwrites the previous version of a re-processed file in place.
+    let titles = embed_titles(config, &embedded_chunks, stats).await;
+    let started = Instant::now();
+    let upserted =
+        qdrant_client::upsert_chunks(config, &embedded_chunks, &all_embeddings, &titles).await;
+    stats
+        .upsert_time
+        .set(stats.upsert_time.get() + started.elapsed());
+    if upserted.is_ok() {
+        stats
+            .points_upserted
+            .set(stats.points_upserted.get() + embedded_chunks.len());
+    }
+    if let Err(e) = upserted {
+        tracing::error!("Qdrant upsert error: {}", e);
+        summary.errors += 1;
+        indexed.chunks = 0;
+        indexed.fail(
+            &embedded_chunks,
+            Stage::Index,
+            &format!("Qdrant upsert: {:#}", e),
+        );
+    }
+
+    // Index in Tantivy (all chunks, not just embedded — BM25 doesn't need vectors)
+    if let Err(e) = tantivy_index::index_chunks(config, chunks) {
+        tracing::error!("Tantivy index error: {}", e);
+        summary.errors += 1;
+        indexed.fail(chunks, Stage::Index, &format!("Tantivy index: {:#}", e));
+    }
+
+    delete_stale_chunks(config, chunks, &embedded_chunks, reindexed, summary).await;
+
+    summary.chunks_indexed += indexed.chunks;
+    tracing::debug!(
+        "Processed batch: {} chunks (total: {})",
+        chunks.len(),
+        summary.chunks_indexed
+    );
+    indexed
+}
+
+/// Delete the chunks of re-processed sources that the new version no longer
+/// has: the tail of a file that got shorter, and chunks stored under a
+/// previous `source_path` (an older commit of a git file). Qdrant keeps only
+/// the points that were just upserted, Tantivy every chunk of the batch.
+/// Sources whose previous chunk ids were recorded lose exactly the ids not
+/// kept; the others lose every chunk under their `source_path` not kept,
+/// which also catches chunks indexed with random ids before ids were
+/// derived from path and index.
+async fn delete_stale_chunks(
+    config: &AppConfig,
+    chunks: &[ChunkPayload],
+    embedded: &[ChunkPayload],
+    reindexed: &[Previous],
+    summary: &mut IngestSummary,
+) {
+    let ids_of = |chunks: &[ChunkPayload], source_path: &str| -> Vec<String> {
+        chunks
+            .iter()
+            .filter(|c| c.source_path == source_path)
+            .map(|c| c.chunk_id.clone())
+            .collect()
+    };
+    let stale = |old: &[String], keep: &[ChunkPayload]| -> Vec<String> {
+        let keep: HashSet<&str> = keep.iter().map(|c| c.chunk_id.as_str()).collect();
+        old.iter()
+            .filter(|id| !keep.contains(id.as_str()))
+            .cloned()
+            .collect()
+    };
+    let mut current = Vec::new();
+    let mut stale_ids = Vec::new();
+    for previous in reindexed {
+        let source_path = &previous.source_path;
+        let deleted = match &previous.chunk_ids {
+            Some(old) => {
+                stale_ids.extend(stale(old, chunks));
+                qdrant_client::delete_chunks(config, &stale(old, embedded)).await
+            }
+            None => {
+                current.push((source_path.clone(), ids_of(chunks, source_path)));
+                let keep = ids_of(embedded, source_path);
+                qdrant_client::delete_stale_chunks(config, source_path, &keep).await
+            }
+        };
+        if let Err(e) = deleted {
+            tracing::error!(
+                "Failed to delete old chunks of {} from Qdrant: {}",
+                source_path,
+                e
+            );
+            summary.errors += 1;
+        }
+    }
+    let deleted = tantivy_index::delete_stale_chunks(config, &current)
+        .and_then(|()| tantivy_index::delete_chunks(config, &stale_ids));
+    if let Err(e) = deleted {
+        tracing::error!("Failed to delete old chunks from Tantivy: {}", e);
+        summary.errors += 1;
+    }
+}
+
+/// Delete the chunks indexed for the `reindexed` sources from both indexes.
+async fn delete_old_chunks(
+    config: &AppConfig,
+    reindexed: &[Previous],
+    summary: &mut IngestSummary,
+) {
+    if reindexed.is_empty() {
+        return;
+    }
+    for previous in reindexed {
+        if let Err(e) = previous.delete_from_qdrant(config).await {
+            tracing::error!(
+                "Failed to delete old chunks of {} from Qdrant: {}",
+                previous.source_path,
+                e
+            );
+            summary.errors += 1;
+        }
+    }
+    if let Err(e) = delete_from_tantivy(config, reindexed) {
+        tracing::error!("Failed to delete old chunks from Tantivy: {}", e);
+        summary.errors += 1;
+    }
+}
+
+/// With `dedupe_chunks`, drops chunks whose text is already indexed for
+/// another source. The first source indexed with a text holds it, and later
+/// copies are dropped before embedding, so searches find the text under the
+/// holder's `source_path` only.
+struct Dedupe {
+    /// Chunk hash -> state key of the source holding the chunk.
+    holders: HashMap<String, String>,
+}
+
+/// Hashes of the chunks kept for a source and of those dropped as
+/// duplicates, recorded in its state entry.
+#[derive(Debug, Default)]
+struct ChunkHashes {
+    kept: Vec<String>,
+    dropped: Vec<String>,
+}
+
+impl Dedupe {
+    fn new(config: &AppConfig, state: &IngestState) -> Option<Self> {
+        if !config.dedupe_chunks {
+            return None;
+        }
+        let mut holders = HashMap::new();
+        for (key, entry) in state {
+            for hash in &entry.chunk_hashes {
+                holders.entry(hash.clone()).or_insert_with(|| key.clone());
+            }
+        }
+        Some(Self { holders })
+    }
+
+    /// Drop the chunks of source `key` that another source holds, or that
+    /// repeat an earlier chunk of its own, and take hold of the rest.
+    /// `previous` is the source's state entry from its last ingest, whose
+    /// chunks the new ones replace.
+    fn filter(
+        &mut self,
+        key: &str,
+        previous: Option<&StateEntry>,
+        chunks: &mut Vec<ChunkPayload>,
+    ) -> ChunkHashes {
+        if let Some(previous) = previous {
+            self.release(key, previous);
+        }
+        let mut hashes = ChunkHashes::default();
+        chunks.retain(|chunk| {
+            let hash = chunk_hash(chunk);
+            if self.holders.contains_key(&hash) {
+                hashes.dropped.push(hash);
+                false
+            } else {
+                self.holders.insert(hash.clone(), key.to_string());
+                hashes.kept.push(hash);
+                true
+            }
+        });
+        hashes
+    }
+
+    /// Forget the chunks source `key` held according to `entry`.
+    fn release(&mut self, key: &str, entry: &StateEntry) {
+        for hash in &entry.chunk_hashes {
+            if self.holders.get(hash).is_some_and(|holder| holder == key) {
+                self.holders.remove(hash);
+            }
+        }
+    }
+}
+
+/// Run `chunks` of source `key` through `dedupe`, if enabled, counting the
+/// dropped chunks in `summary`.
+fn dedupe_chunks(
+    dedupe: &mut Option<Dedupe>,
+    key: &str,
+    state: &IngestState,
+    chunks: &mut Vec<ChunkPayload>,
+    summary: &mut IngestSummary,
+) -> ChunkHashes {
+    let Some(dedupe) = dedupe else {
+        return ChunkHashes::default();
+    };
+    let hashes = dedupe.filter(key, state.get(key), chunks);
+    summary.chunks_deduplicated += hashes.dropped.len();
+    hashes
+}
+
+/// Hash of a chunk's text with runs of whitespace collapsed. The `path:line`
+/// header of a code chunk is left out, so copies in different files match.
+fn chunk_hash(chunk: &ChunkPayload) -> String {
+    let text = chunk.text.as_str();
+    let body = text
+        .strip_prefix(chunk.source_path.as_str())
+        .and_then(|rest| rest.strip_prefix(':'))
+        .and_then(|rest| rest.split_once('\n'))
+        .filter(|(line, _)| !line.is_empty() && line.bytes().all(|b| b.is_ascii_digit()))
+        .map_or(text, |(_, body)| body);
+    let normalized = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    content_hash(&normalized)[..16].to_string()
+}
+
+/// Sources that dropped duplicate chunks no source holds any more, and need
+/// re-processing to index them. With `dedupe_chunks` off, every source that
+/// dropped any.
+fn missing_chunks(config: &AppConfig, state: &IngestState) -> HashSet<String> {
+    let held: HashSet<&String> = if config.dedupe_chunks {
+        state.values().flat_map(|e| &e.chunk_hashes).collect()
+    } else {
+        HashSet::new()
+    };
+    state
+        .iter()
+        .filter(|(_, entry)| entry.duplicate_hashes.iter().any(|h| !held.contains(h)))
+        .map(|(key, _)| key.clone())
         .collect()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Fetch each of `urls` and index the pages that changed since the last
+/// fetch. Pages the server reports gone are pruned when `options.prune` is
+/// set.
+async fn ingest_urls(
+    config: &AppConfig,
+    urls: &[String],
+    options: &IngestOptions,
+    state: &mut IngestState,
+    stats: &EmbedStats,
+    summary: &mut IngestSummary,
+) -> Result<()> {
+    let client = web::client()?;
+    let mut progress = Progress::new(urls.len(), options.progress);
+    let missing = missing_chunks(config, state);
+    let mut dedupe = Dedupe::new(config, state);
+    for url in urls {
+        if interrupted() {
+            break;
+        }
+        progress.start_file(url);
+        let forced = options.is_forced(url);
+        // A forced fetch sends no validators, so it can't come back 304.
+        let previous = match state.get(url) {
+            Some(entry) if !forced && !missing.contains(url) => entry.validators(),
+            _ => Validators::default(),
+        };
+        let fetched = web::fetch(&client, url, &previous, config.max_file_size_bytes).await;
+        progress.finish_file();
 
-    fn make_result(id: &str) -> SearchResult {
-        SearchResult {
-            chunk_id: id.to_string(),
-            score: 0.0,
-            title: format!("Title {}", id),
-            source_path: "/test".to_string(),
-            source_type: "md".to_string(),
-            snippet: "snippet".to_string(),
+        let page = match fetched {
+            Ok(Fetched::Page(page)) => page,
+            Ok(Fetched::NotModified) => {
+                tracing::debug!("Skipping unchanged page: {}", url);
+                summary.urls_unchanged += 1;
+                continue;
+            }
+            Ok(Fetched::Gone) if options.prune && state.contains_key(url) => {
+                tracing::info!("{} no longer exists, pruning it", url);
+                if let (Some(dedupe), Some(entry)) = (&mut dedupe, state.get(url)) {
+                    dedupe.release(url, entry);
+                }
+                summary.files_pruned += prune(config, state, vec![url.clone()]).await?;
+                continue;
+            }
+            Ok(Fetched::Gone) => {
+                tracing::error!("Error fetching {}: not found", url);
+                summary.urls_failed += 1;
+                summary.fail(options, url, Stage::Fetch, "not found")?;
+                continue;
+            }
+            Err(e) => {
+                tracing::error!("Error fetching {}: {:#}", url, e);
+                summary.urls_failed += 1;
+                summary.fail(options, url, Stage::Fetch, format!("{:#}", e))?;
+                continue;
+            }
+        };
+
+        let mut chunks = match process_page(config, url, &page) {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                tracing::error!("Error processing {}: {}", url, e);
+                summary.urls_failed += 1;
+                summary.fail(options, url, Stage::Convert, format!("{:#}", e))?;
+                continue;
+            }
+        };
+        let hashes = dedupe_chunks(&mut dedupe, url, state, &mut chunks, summary);
+        let reindexed = if state.contains_key(url) || forced {
+            vec![Previous::new(url, state.get(url))]
+        } else {
+            Vec::new()
+        };
+        let indexed = index_batch(config, &chunks, &reindexed, stats, summary).await;
+        progress.add_chunks(indexed.chunks);
+        if let Some((stage, message)) = indexed.failed(url) {
+            tracing::error!("Failed to index {}; it is retried on the next run", url);
+            summary.urls_failed += 1;
+            summary.fail(options, url, *stage, message)?;
+            continue;
+        }
+
+        summary.urls_ok += 1;
+        let fetched_at = chrono::Utc::now().to_rfc3339();
+        state.insert(
+            url.clone(),
+            StateEntry {
+                etag: page.validators.etag,
+                last_modified: page.validators.last_modified,
+                ..StateEntry::new(config, fetched_at)
+                    .with_hashes(hashes)
+                    .with_chunk_ids(chunk_ids(&chunks))
+            },
+        );
+        save_state(config, state)?;
+    }
+    progress.finish();
+    Ok(())
+}
+
+/// Chunk a fetched page. Pages are markdown after conversion, so the
+/// markdown strategy splits them at headings.
+fn process_page(config: &AppConfig, url: &str, page: &web::Page) -> Result<Vec<ChunkPayload>> {
+    let title = page
+        .title
+        .clone()
+        .unwrap_or_else(|| chunker::extract_title(&page.text, url));
+    let chunks = chunker::chunk_document(&page.text, true, config)?;
+    Ok(build_payloads(url, "url", &title, &[], &chunks, None))
+}
+
+/// Index piped text under its path, unless the same text was indexed under
+/// it last time.
+async fn ingest_piped(
+    config: &AppConfig,
+    piped: &Piped,
+    options: &IngestOptions,
+    state: &mut IngestState,
+    stats: &EmbedStats,
+    summary: &mut IngestSummary,
+) -> Result<()> {
+    let path = &piped.path;
+    let hash = content_hash(&piped.text);
+    let forced = options.is_forced(path);
+    let unchanged = state.get(path).and_then(|e| e.content_hash.as_ref()) == Some(&hash);
+    if unchanged && !forced && !missing_chunks(config, state).contains(path) {
+        tracing::info!("Input for {} is unchanged, skipping", path);
+        summary.files_unchanged += 1;
+        return Ok(());
+    }
+
+    let mut chunks = match process_piped(config, piped) {
+        Ok(chunks) => chunks,
+        Err(e) if is_skip(&e) => {
+            tracing::warn!("Skipping {}", e);
+            summary.files_skipped += 1;
+            return summary.fail(options, path, Stage::Skip, e);
         }
+        Err(e) => {
+            tracing::error!("Error processing {}: {}", path, e);
+            summary.files_failed += 1;
+            return summary.fail(options, path, Stage::Convert, format!("{:#}", e));
+        }
+    };
+    let mut dedupe = Dedupe::new(config, state);
+    let hashes = dedupe_chunks(&mut dedupe, path, state, &mut chunks, summary);
+    let reindexed = if state.contains_key(path) || forced {
+        vec![Previous::new(path, state.get(path))]
+    } else {
+        Vec::new()
+    };
+    let indexed = index_batch(config, &chunks, &reindexed, stats, summary).await;
+    if let Some((stage, message)) = indexed.failed(path) {
+        tracing::error!("Failed to index {}", path);
+        summary.files_failed += 1;
+        return summary.fail(options, path, *stage, message);
+    }
+
+    summary.files_ok += 1;
+    let now = chrono::Utc::now().to_rfc3339();
+    state.insert(
+        path.clone(),
+        StateEntry {
+            content_hash: Some(hash),
+            ..StateEntry::new(config, now)
+                .with_hashes(hashes)
+                .with_chunk_ids(chunk_ids(&chunks))
+        },
+    );
+    save_state(config, state)
+}
+
+/// Chunk piped text the way a file at its path would be.
+fn process_piped(config: &AppConfig, piped: &Piped) -> Result<Vec<ChunkPayload>> {
+    let ext = Path::new(&piped.path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "txt".to_string());
+    let size = piped.text.len() as u64;
+    check_size(&piped.path, "input", size, config.max_file_size_bytes)?;
+    check_text(config, &piped.path, "input", &piped.text)?;
+    // Converters and the EPUB reader need a file; the text is read as is.
+    let kind = match file_kind(config, &ext) {
+        Some(kind @ (FileKind::Code | FileKind::Structured)) => kind,
+        _ => FileKind::Text,
+    };
+    let mut chunks = process_text(config, &piped.path, &ext, kind, &piped.text)?;
+    if let Some(title) = &piped.title {
+        for chunk in &mut chunks {
+            chunk.title = title.clone();
+        }
+    }
+    Ok(chunks)
+}
+
+/// Hex SHA-1 of `text`.
+fn content_hash(text: &str) -> String {
+    Sha1::digest(text.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Sync `repo` and index the files whose blob changed since the last run.
+/// Files the new commit removed are pruned when `options.prune` is set.
+/// Each file's `source_path` is `repo:path@commit`; an unchanged file keeps
+/// the commit it was last indexed at.
+async fn ingest_git(
+    config: &AppConfig,
+    repo: &git::Repo,
+    options: &IngestOptions,
+    markitdown_available: bool,
+    state: &mut IngestState,
+    stats: &EmbedStats,
+    summary: &mut IngestSummary,
+) -> Result<()> {
+    let checkout = git::sync(&config.git_cache_dir(), repo)?;
+    let blobs = git::blob_hashes(&checkout.dir)?;
+    let dir = std::fs::canonicalize(&checkout.dir)?;
+    let files = collect_files(
+        config,
+        &[dir.to_string_lossy().to_string()],
+        markitdown_available,
+        options,
+    )?;
+    let missing = missing_chunks(config, state);
+    let plan = plan_git(state, repo.name(), &dir, &files, &blobs, &missing, options);
+    summary.files_unchanged += plan.unchanged;
+    tracing::info!(
+        "{} at {}: {} files need processing ({} unchanged, skipped)",
+        repo.name(),
+        checkout.commit,
+        plan.changed.len(),
+        plan.unchanged
+    );
+
+    if options.prune && !plan.removed.is_empty() {
+        tracing::info!(
+            "Pruning {} files removed from {}",
+            plan.removed.len(),
+            repo.name()
+        );
+        summary.files_pruned += prune(config, state, plan.removed).await?;
+    }
+
+    let mut progress = Progress::new(plan.changed.len(), options.progress);
+    let mut dedupe = Dedupe::new(config, state);
+    for batch in plan.changed.chunks(config.ingest_file_batch_size) {
+        if interrupted() {
+            break;
+        }
+        let mut all_chunks = Vec::new();
+        let mut reindexed = Vec::new();
+        let mut processed = Vec::new();
+
+        for change in batch {
+            progress.start_file(&change.key);
+            match process_file(config, &change.file) {
+                Ok(mut chunks) => {
+                    // Before the paths change, so code chunk headers match.
+                    let hashes =
+                        dedupe_chunks(&mut dedupe, &change.key, state, &mut chunks, summary);
+                    let source_path = format!("{}@{}", change.key, checkout.commit);
+                    for chunk in &mut chunks {
+                        chunk.chunk_id = types::chunk_id(&source_path, chunk.chunk_index);
+                        chunk.source_path = source_path.clone();
+                    }
+                    if let Some(previous) = state.get(&change.key) {
+                        reindexed.push(Previous::new(&change.key, Some(previous)));
+                    }
+                    processed.push((change, hashes, chunk_ids(&chunks)));
+                    all_chunks.extend(chunks);
+                }
+                Err(e) if is_skip(&e) => {
+                    tracing::warn!("Skipping {}", e);
+                    summary.files_skipped += 1;
+                    summary.fail(options, &change.key, Stage::Skip, e)?;
+                }
+                Err(e) => {
+                    tracing::error!("Error processing {}: {}", change.key, e);
+                    summary.files_failed += 1;
+                    summary.fail(options, &change.key, Stage::Convert, format!("{:#}", e))?;
+                }
+            }
+            progress.finish_file();
+        }
+
+        let indexed = index_batch(config, &all_chunks, &reindexed, stats, summary).await;
+        progress.add_chunks(indexed.chunks);
+
+        let now = chrono::Utc::now().to_rfc3339();
+        for (change, hashes, ids) in processed {
+            let source_path = format!("{}@{}", change.key, checkout.commit);
+            if let Some((stage, message)) = indexed.failed(&source_path) {
+                tracing::error!(
+                    "Failed to index {}; it is retried on the next run",
+                    change.key
+                );
+                summary.files_failed += 1;
+                summary.fail(options, &change.key, *stage, message)?;
+                continue;
+            }
+            state.insert(
+                change.key.clone(),
+                StateEntry {
+                    commit: Some(checkout.commit.clone()),
+                    blob: Some(change.blob.clone()),
+                    ..StateEntry::new(config, now.clone())
+                        .with_hashes(hashes)
+                        .with_chunk_ids(ids)
+                },
+            );
+            summary.files_ok += 1;
+        }
+        save_state(config, state)?;
+    }
+    progress.finish();
+    Ok(())
+}
+
+/// A file in a repository checkout that needs ingesting.
+#[derive(Debug, PartialEq)]
+struct GitChange {
+    /// State key, `repo:path`.
+    key: String,
+    /// Path of the file in the checkout.
+    file: String,
+    blob: String,
+}
+
+/// What an ingest of a repository checkout has to do.
+#[derive(Debug, Default, PartialEq)]
+struct GitPlan {
+    changed: Vec<GitChange>,
+    unchanged: usize,
+    /// State keys of files no longer in the commit.
+    removed: Vec<String>,
+}
+
+/// Compare the collected `files` of the checkout in `dir` (and the commit's
+/// `blobs`) with the files of repository `name` in `state`. Files in
+/// `missing` need re-processing even when unchanged.
+fn plan_git(
+    state: &IngestState,
+    name: &str,
+    dir: &Path,
+    files: &[String],
+    blobs: &HashMap<String, String>,
+    missing: &HashSet<String>,
+    options: &IngestOptions,
+) -> GitPlan {
+    let prefix = format!("{}:", name);
+    let mut plan = GitPlan::default();
+    for file in files {
+        let Some(relative) = relative_path(Path::new(file), dir) else {
+            continue;
+        };
+        // Untracked files, if any, are not part of the commit.
+        let Some(blob) = blobs.get(&relative) else {
+            continue;
+        };
+        let key = format!("{}{}", prefix, relative);
+        let unchanged = state.get(&key).and_then(|e| e.blob.as_ref()) == Some(blob);
+        if unchanged && !options.is_forced(file) && !missing.contains(&key) {
+            tracing::debug!("Skipping unchanged file: {}", key);
+            plan.unchanged += 1;
+        } else {
+            plan.changed.push(GitChange {
+                key,
+                file: file.clone(),
+                blob: blob.clone(),
+            });
+        }
+    }
+    plan.removed = state
+        .iter()
+        .filter_map(|(key, entry)| {
+            let path = key.strip_prefix(&prefix)?;
+            (entry.blob.is_some() && !blobs.contains_key(path)).then(|| key.clone())
+        })
+        .collect();
+    plan.removed.sort();
+    plan
+}
+
+/// Split chunks into sub-batches for embedding, returning (chunk_slice, texts) pairs.
+fn chunk_sub_batches(
+    chunks: &[ChunkPayload],
+    batch_size: usize,
+) -> Vec<(&[ChunkPayload], Vec<String>)> {
+    chunks
+        .chunks(batch_size)
+        .map(|sub| {
+            let texts: Vec<String> = sub.iter().map(|c| c.text.clone()).collect();
+            (sub, texts)
+        })
+        .collect()
+}
+
+/// Flag the chunks over `max_tokens` (estimated), which are embedded from
+/// their start only, logging each one. `chunks` is borrowed back when none
+/// is.
+fn mark_truncated(chunks: &[ChunkPayload], max_tokens: usize) -> Cow<'_, [ChunkPayload]> {
+    let over = |chunk: &ChunkPayload| max_tokens > 0 && tokens::count(&chunk.text) > max_tokens;
+    if !chunks.iter().any(over) {
+        return Cow::Borrowed(chunks);
+    }
+    let mut chunks = chunks.to_vec();
+    for chunk in chunks.iter_mut().filter(|c| over(c)) {
+        tracing::warn!(
+            "Chunk {} of {} has ~{} tokens; embedding its first {} (embedding_max_tokens)",
+            chunk.chunk_index,
+            chunk.source_path,
+            tokens::count(&chunk.text),
+            max_tokens
+        );
+        chunk.embedding_truncated = true;
+    }
+    Cow::Owned(chunks)
+}
+
+/// Embed `chunks` in sub-batches of `batch_size`, with up to `concurrency`
+/// requests in flight. Returns the embedded chunks, the vector for each (same
+/// index), and the sub-batches that failed with their errors; a failed
+/// sub-batch only skips its own chunks. A sub-batch the provider rejects as
+/// malformed (400) is embedded again one chunk at a time, so that the chunk
+/// it chokes on doesn't take the others down with it.
+async fn embed_sub_batches<F, Fut>(
+    chunks: &[ChunkPayload],
+    batch_size: usize,
+    concurrency: usize,
+    embed: F,
+) -> (
+    Vec<ChunkPayload>,
+    Vec<Vec<f32>>,
+    Vec<(&[ChunkPayload], String)>,
+)
+where
+    F: Fn(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<Vec<Vec<f32>>>>,
+{
+    let mut embedded_chunks = Vec::new();
+    let mut embeddings = Vec::new();
+    let mut failed = Vec::new();
+
+    // Each future carries its own sub-batch, so results can complete in any
+    // order without losing the chunk <-> vector pairing.
+    let embed = &embed;
+    let mut results = stream::iter(chunk_sub_batches(chunks, batch_size))
+        .map(|(sub_chunks, texts)| async move {
+            match embed(texts.clone()).await {
+                Err(e) if sub_chunks.len() > 1 && ApiError::is_bad_request(&e) => {
+                    tracing::warn!(
+                        "Embedding provider rejected a batch of {} chunks, embedding them one at a time: {:#}",
+                        sub_chunks.len(),
+                        e
+                    );
+                    let mut results = Vec::new();
+                    for (chunk, text) in sub_chunks.chunks(1).zip(texts) {
+                        results.push((chunk, embed(vec![text]).await));
+                    }
+                    results
+                }
+                result => vec![(sub_chunks, result)],
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .flat_map(stream::iter);
+
+    while let Some((sub_chunks, result)) = results.next().await {
+        match result {
+            Ok(vectors) if vectors.len() == sub_chunks.len() => {
+                embedded_chunks.extend_from_slice(sub_chunks);
+                embeddings.extend(vectors);
+            }
+            Ok(vectors) => {
+                let message = format!(
+                    "got {} vectors for {} texts",
+                    vectors.len(),
+                    sub_chunks.len()
+                );
+                tracing::error!(
+                    "Embedding error (skipping {} chunks): {}",
+                    sub_chunks.len(),
+                    message
+                );
+                failed.push((sub_chunks, message));
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Embedding error (skipping {} chunks): {}",
+                    sub_chunks.len(),
+                    e
+                );
+                failed.push((sub_chunks, format!("{:#}", e)));
+            }
+        }
+    }
+
+    (embedded_chunks, embeddings, failed)
+}
+
+/// The `[[sources]]` roots of the config that exist, deepest first, each
+/// with the config its files are chunked with: the global one with the
+/// root's chunk settings applied.
+struct SourceRoots {
+    roots: Vec<SourceRoot>,
+}
+
+struct SourceRoot {
+    path: PathBuf,
+    config: AppConfig,
+    include: Vec<String>,
+}
+
+impl SourceRoots {
+    fn new(config: &AppConfig) -> Self {
+        let mut roots: Vec<SourceRoot> = config
+            .sources
+            .iter()
+            .filter(|source| !source.path.is_empty())
+            .filter_map(|source| {
+                let path = std::fs::canonicalize(&source.path).ok()?;
+                let config = AppConfig {
+                    chunk_size: source.chunk_size.unwrap_or(config.chunk_size),
+                    chunk_overlap: source.chunk_overlap.unwrap_or(config.chunk_overlap),
+                    sources: Vec::new(),
+                    ..config.clone()
+                };
+                Some(SourceRoot {
+                    path,
+                    config,
+                    include: source.include.clone(),
+                })
+            })
+            .collect();
+        // A root nested in another one takes its own files.
+        roots.sort_by_key(|root| std::cmp::Reverse(root.path.components().count()));
+        Self { roots }
+    }
+
+    fn root_for(&self, file: &Path) -> Option<&SourceRoot> {
+        self.roots.iter().find(|root| file.starts_with(&root.path))
+    }
+
+    /// The config `file` is chunked with: its root's, or `global` for a
+    /// file outside every configured root.
+    fn config_for<'a>(&'a self, file: &str, global: &'a AppConfig) -> &'a AppConfig {
+        self.root_for(Path::new(file))
+            .map_or(global, |root| &root.config)
+    }
+
+    /// Whether `file` matches the include patterns of its root, if it has
+    /// any.
+    fn includes(&self, file: &Path) -> bool {
+        let Some(root) = self.root_for(file).filter(|root| !root.include.is_empty()) else {
+            return true;
+        };
+        let Some(relative) = relative_path(file, &root.path) else {
+            return true;
+        };
+        root.include
+            .iter()
+            .any(|pattern| gitignore::glob_matches(pattern, &relative))
+    }
+}
+
+/// `path` relative to `root`, with `/` separators.
+fn relative_path(path: &Path, root: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    Some(
+        relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
+
+/// Supported files under `sources`. Unless `options` say otherwise, hidden
+/// files and directories and paths excluded by `.gitignore` / `.ignore` are
+/// skipped; an ignored directory is not descended into. Files under a
+/// `[[sources]]` root with `include` patterns must match one of them. A
+/// source that is a file is taken as is, and is an error if ingest can't
+/// handle its type.
+fn collect_files(
+    config: &AppConfig,
+    sources: &[String],
+    markitdown_available: bool,
+    options: &IngestOptions,
+) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    let roots = SourceRoots::new(config);
+
+    for source in sources {
+        // Absolute paths keep ingest state keys (and source_path payloads)
+        // stable no matter which directory ragctl runs from.
+        let Ok(path) = std::fs::canonicalize(source) else {
+            tracing::warn!("Source path does not exist: {}", source);
+            continue;
+        };
+        if path.is_file() {
+            check_file_type(config, &path, markitdown_available)?;
+            files.push(path.to_string_lossy().to_string());
+            continue;
+        }
+
+        let mut matcher = IgnoreMatcher::new(&path);
+        // Canonical paths already walked, so a followed link back into the
+        // tree (or to a file reached before) is skipped, not walked again.
+        let mut visited = HashSet::from([path.clone()]);
+        let mut walker = WalkDir::new(&path)
+            .follow_links(config.follow_symlinks)
+            .same_file_system(true);
+        if let Some(depth) = config.max_depth {
+            walker = walker.max_depth(depth);
+        }
+        let walker = walker.into_iter().filter_entry(|entry| {
+            if entry.depth() == 0 {
+                return true;
+            }
+            if !options.hidden && entry.file_name().to_string_lossy().starts_with('.') {
+                return false;
+            }
+            // Symlinks are matched by their own path, not the target's.
+            if !options.no_ignore && matcher.is_ignored(entry.path(), entry.file_type().is_dir()) {
+                return false;
+            }
+            if entry.path_is_symlink() && !config.follow_symlinks {
+                tracing::debug!("Skipping symlink {}", entry.path().display());
+                return false;
+            }
+            !config.follow_symlinks || first_visit(&mut visited, entry)
+        });
+        for entry in walker {
+            match entry {
+                Ok(entry) => {
+                    if entry.file_type().is_file() && roots.includes(entry.path()) {
+                        if let Some(ext) = entry.path().extension() {
+                            let ext_str = ext.to_string_lossy().to_lowercase();
+                            match file_kind(config, &ext_str) {
+                                Some(FileKind::Markitdown) if !markitdown_available => {
+                                    tracing::warn!(
+                                        "Skipping {} (markitdown not available)",
+                                        entry.path().display()
+                                    );
+                                }
+                                Some(_) => {
+                                    files.push(entry.path().to_string_lossy().to_string());
+                                }
+                                None => {}
+                            }
+                        }
+                    }
+                }
+                Err(e) => match (e.path(), e.loop_ancestor()) {
+                    (Some(link), Some(target)) => log_cycle(link, target),
+                    _ => tracing::warn!("Error walking directory: {}", e),
+                },
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Whether `entry`, reached with `follow_symlinks`, resolves to a path not
+/// in `visited` yet; records it if so. A file reached twice (through a
+/// link next to it, say) is ingested once, and a linked directory walked
+/// before is not walked again.
+fn first_visit(visited: &mut HashSet<PathBuf>, entry: &walkdir::DirEntry) -> bool {
+    let Ok(canonical) = std::fs::canonicalize(entry.path()) else {
+        // A dangling link; the walk reports it.
+        return true;
+    };
+    if visited.insert(canonical.clone()) {
+        return true;
+    }
+    if entry.file_type().is_dir() {
+        log_cycle(entry.path(), &canonical);
+    } else {
+        tracing::debug!(
+            "Skipping {}: {} was already collected",
+            entry.path().display(),
+            canonical.display()
+        );
+    }
+    false
+}
+
+/// Warn about a symlinked directory that leads back to one already walked,
+/// once per link: watch mode collects files every second.
+fn log_cycle(link: &Path, target: &Path) {
+    static LOGGED: std::sync::Mutex<BTreeSet<PathBuf>> = std::sync::Mutex::new(BTreeSet::new());
+    if LOGGED.lock().unwrap().insert(link.to_path_buf()) {
+        tracing::warn!(
+            "Skipping {}: {} was already visited (symlink cycle?)",
+            link.display(),
+            target.display()
+        );
+    }
+}
+
+/// OCR the images among `files` up front, `ocr_concurrency` at a time,
+/// since recognizing one is slow and ingest otherwise reads files one by
+/// one. Images over `max_file_size_bytes` are left for [`process_file`] to
+/// skip.
+fn recognize_images(
+    config: &AppConfig,
+    files: &[String],
+) -> Result<HashMap<String, Result<String>>> {
+    let Some(command) = ocr::command(config)? else {
+        return Ok(HashMap::new());
+    };
+    let limit = config.max_file_size_bytes;
+    let images: Vec<&str> = files
+        .iter()
+        .filter(|file| {
+            let ext = Path::new(file)
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            file_kind(config, &ext) == Some(FileKind::Image)
+                && std::fs::metadata(file).is_ok_and(|m| m.len() <= limit)
+        })
+        .map(String::as_str)
+        .collect();
+    Ok(ocr::run_all(
+        &command,
+        &images,
+        limit,
+        config.ocr_concurrency,
+    ))
+}
+
+/// Fail with a clear message when `path` is not a type ingest can read.
+fn check_file_type(config: &AppConfig, path: &Path, markitdown_available: bool) -> Result<()> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match file_kind(config, &ext) {
+        Some(FileKind::Markitdown) if !markitdown_available => anyhow::bail!(
+            "{} needs markitdown, which was not found in PATH (pip install markitdown)",
+            path.display()
+        ),
+        Some(_) => Ok(()),
+        None => {
+            let mut supported: Vec<&str> = [
+                TEXT_EXTENSIONS,
+                MARKITDOWN_EXTENSIONS,
+                &["epub"],
+                STRUCTURED_EXTENSIONS,
+            ]
+            .concat();
+            supported.extend(config.code_extensions.iter().map(String::as_str));
+            supported.extend(
+                config
+                    .converters
+                    .iter()
+                    .flat_map(|c| c.extensions.iter().map(|e| e.trim_start_matches('.'))),
+            );
+            anyhow::bail!(
+                "Unsupported file type: {} (supported: {})",
+                path.display(),
+                supported.join(", ")
+            )
+        }
+    }
+}
+
+fn check_size(path: &str, what: &'static str, size: u64, limit: u64) -> Result<()> {
+    if size > limit {
+        return Err(TooLarge {
+            path: path.to_string(),
+            what,
+            size,
+            limit,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+fn binary(path: &str, what: &'static str, reason: NotText) -> anyhow::Error {
+    Binary {
+        path: path.to_string(),
+        what,
+        reason,
+    }
+    .into()
+}
+
+/// Fail with [`Binary`] when `text`, the `what` of `path`, looks binary,
+/// unless `allow_binary` is set.
+fn check_text(config: &AppConfig, path: &str, what: &'static str, text: &str) -> Result<()> {
+    if config.allow_binary {
+        return Ok(());
+    }
+    binary::check(text).map_err(|reason| binary(path, what, reason))
+}
+
+/// Read a text file in its detected encoding, or `ingest_default_encoding`,
+/// and return the text with the name of the encoding.
+fn read_text(config: &AppConfig, path: &str) -> Result<(String, &'static str)> {
+    let bytes = std::fs::read(path)?;
+    let (text, encoding) = encoding::decode(&bytes, encoding::configured(config)?);
+    check_text(config, path, "file", &text)?;
+    Ok((text, encoding.name()))
+}
+
+/// Process a single file into chunks.
+fn process_file(config: &AppConfig, file_path: &str) -> Result<Vec<ChunkPayload>> {
+    process_file_with(config, file_path, None)
+}
+
+/// [`process_file`], with the text of an image already `recognized` by
+/// [`recognize_images`].
+fn process_file_with(
+    config: &AppConfig,
+    file_path: &str,
+    recognized: Option<Result<String>>,
+) -> Result<Vec<ChunkPayload>> {
+    let path = Path::new(file_path);
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "txt".to_string());
+
+    let limit = config.max_file_size_bytes;
+    let size = std::fs::metadata(path)?.len();
+    check_size(file_path, "file", size, limit)?;
+    let kind = file_kind(config, &ext).unwrap_or(FileKind::Text);
+
+    let mut payloads = if kind == FileKind::Epub {
+        let (title, chunks) = chunk_epub(config, file_path, &file_name(file_path))?;
+        build_payloads(file_path, &ext, &title, &[], &chunks, None)
+    } else if kind == FileKind::Pdf {
+        let (title, text, ocr) = pdf_text(config, file_path, &file_name(file_path))?;
+        let chunks = chunker::chunk_pages(&text, config)?;
+        let mut payloads = build_payloads(file_path, &ext, &title, &[], &chunks, Some(&text));
+        mark_converted(&mut payloads, kind);
+        if let Some(ocr) = ocr {
+            mark_command(&mut payloads, &ocr, true);
+        }
+        payloads
+    } else {
+        // Read or convert file content
+        let command = match kind {
+            FileKind::External => convert::find(config, &ext)?,
+            FileKind::Image => ocr::command(config)?,
+            FileKind::Markitdown => Some(Converter::markitdown()),
+            _ => None,
+        };
+        let converted = match (kind, &command, recognized) {
+            (_, _, Some(text)) => Some(text?),
+            (_, Some(command), None) => Some(command.run(file_path, limit)?),
+            (FileKind::Docx, _, _) => Some(office::read_docx(path, limit)?),
+            (FileKind::Xlsx, _, _) => Some(office::read_xlsx(path, limit, config.max_sheet_rows)?),
+            _ => None,
+        };
+        let (content, encoding) = match converted {
+            // A small spreadsheet can expand into far more markdown.
+            Some(text) => {
+                check_size(file_path, "converted text", text.len() as u64, limit)?;
+                check_text(config, file_path, "converted text", &text)?;
+                (text, None)
+            }
+            None => {
+                let (text, encoding) = read_text(config, file_path)?;
+                (text, Some(encoding))
+            }
+        };
+        let mut payloads = process_text(config, file_path, &ext, kind, &content)?;
+        for payload in &mut payloads {
+            payload.encoding = encoding.map(str::to_string);
+        }
+        match (kind, &command) {
+            (FileKind::External, Some(command)) => mark_command(&mut payloads, command, false),
+            (FileKind::Image, Some(command)) => mark_command(&mut payloads, command, true),
+            _ => {}
+        }
+        payloads
+    };
+
+    let mtime = file_modified_time(file_path);
+    for payload in &mut payloads {
+        payload.file_mtime = mtime.clone();
+        payload.file_size_bytes = Some(size);
+    }
+    Ok(payloads)
+}
+
+/// Chunk the text of a file, or of piped input, the way its `kind` is
+/// split.
+fn process_text(
+    config: &AppConfig,
+    file_path: &str,
+    ext: &str,
+    kind: FileKind,
+    content: &str,
+) -> Result<Vec<ChunkPayload>> {
+    let file_name = file_name(file_path);
+    let (front_matter, body) = match ext {
+        "md" => match front_matter::extract(content) {
+            Ok(Some((front_matter, body))) => (front_matter, body),
+            Ok(None) => (Default::default(), content),
+            Err(e) => {
+                tracing::warn!(
+                    "Malformed front matter in {} ({}), indexing it as text",
+                    file_path,
+                    e
+                );
+                (Default::default(), content)
+            }
+        },
+        _ => (Default::default(), content),
+    };
+
+    let (title, mut chunks) = if kind == FileKind::Code {
+        // A source file's first line rarely makes a useful title.
+        let unit = chunker::Unit::from_config(config)?;
+        let chunks = chunker::chunk_code(body, file_path, config.chunk_size, unit);
+        (file_name, chunks)
+    } else if kind == FileKind::Structured {
+        let text = match flatten_structured(ext, body) {
+            Ok(text) => text,
+            Err(e) => {
+                tracing::warn!(
+                    "Could not parse {} ({:#}), indexing it as text",
+                    file_path,
+                    e
+                );
+                body.to_string()
+            }
+        };
+        (file_name, chunker::chunk_document(&text, false, config)?)
+    } else {
+        // OCR's first line is often noise; the file name is a safer title.
+        let title = match front_matter.title {
+            Some(title) => title,
+            None if kind == FileKind::Image => file_name,
+            None => chunker::extract_title(body, &file_name),
+        };
+        let markdown = ext == "md" || (kind.converter().is_some() && kind != FileKind::Image);
+        (title, chunker::chunk_document(body, markdown, config)?)
+    };
+
+    // Flattened documents have no position in the file. Otherwise chunk
+    // ranges are into the body, and locations count from the start of the
+    // file, front matter included.
+    let source = (kind != FileKind::Structured).then_some(content);
+    let skipped = content.len() - body.len();
+    for chunk in &mut chunks {
+        chunk.range = chunk.range.start + skipped..chunk.range.end + skipped;
+    }
+    let mut payloads = build_payloads(file_path, ext, &title, &front_matter.tags, &chunks, source);
+    mark_converted(&mut payloads, kind);
+    Ok(payloads)
+}
+
+/// Record on the chunks of a file of `kind` what converted it to text, if
+/// anything did; their locations are then into the converted text.
+fn mark_converted(payloads: &mut [ChunkPayload], kind: FileKind) {
+    let Some(converter) = kind.converter() else {
+        return;
+    };
+    for payload in payloads {
+        payload.location.converted = true;
+        payload.converter = Some(converter.to_string());
+    }
+}
+
+/// Record an external or OCR `command` as what converted the chunks.
+fn mark_command(payloads: &mut [ChunkPayload], command: &Converter, ocr: bool) {
+    for payload in payloads {
+        payload.converter = Some(command.name.clone());
+        payload.ocr = ocr;
+    }
+}
+
+fn file_name(file_path: &str) -> String {
+    Path::new(file_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn build_payloads(
+    file_path: &str,
+    ext: &str,
+    title: &str,
+    tags: &[String],
+    chunks: &[chunker::Chunk],
+    source: Option<&str>,
+) -> Vec<ChunkPayload> {
+    let now = chrono::Utc::now().to_rfc3339();
+    // Chunk ranges are into `source`; chunks without one get no location.
+    let mut locator = source.map(chunker::Locator::new);
+    // Every chunk of a document gets the language of its start.
+    let sample: String = chunks
+        .iter()
+        .flat_map(|chunk| chunk.text.chars().chain(['\n']))
+        .take(language::SAMPLE_CHARS)
+        .collect();
+    let language = language::detect(&sample);
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| ChunkPayload {
+            chunk_id: types::chunk_id(file_path, i as u32),
+            source_path: file_path.to_string(),
+            source_type: ext.to_string(),
+            title: title.to_string(),
+            chunk_index: i as u32,
+            text: chunk.text.clone(),
+            updated_at: now.clone(),
+            tags: tags.to_vec(),
+            section: chunk.section.clone(),
+            location: locator
+                .as_mut()
+                .map(|locator| locator.locate(&chunk.range))
+                .unwrap_or_default(),
+            file_mtime: None,
+            file_size_bytes: None,
+            language: Some(language.to_string()),
+            converter: None,
+            ocr: false,
+            encoding: None,
+            embedding_truncated: false,
+        })
+        .collect()
+}
+
+/// Split a book chapter by chapter, so no chunk spans two chapters. Long
+/// chapters go through the configured splitter, and every chunk records
+/// its chapter title as `section`.
+fn chunk_epub(
+    config: &AppConfig,
+    file_path: &str,
+    file_name: &str,
+) -> Result<(String, Vec<chunker::Chunk>)> {
+    let limit = config.max_file_size_bytes;
+    let book = epub::read(Path::new(file_path), limit)?;
+    let text_len = book.chapters.iter().map(|c| c.text.len() as u64).sum();
+    check_size(file_path, "extracted text", text_len, limit)?;
+
+    let mut chunks = Vec::new();
+    for chapter in &book.chapters {
+        for chunk in chunker::chunk_document(&chapter.text, false, config)? {
+            chunks.push(chunker::Chunk {
+                section: chapter.title.clone(),
+                ..chunk
+            });
+        }
+    }
+    let title = book.title.unwrap_or_else(|| file_name.to_string());
+    Ok((title, chunks))
+}
+
+/// The title of the PDF at `file_path` and its text, pages separated by
+/// [`chunker::PAGE_BREAK`].
+#[cfg(feature = "pdf-native")]
+fn read_pdf(config: &AppConfig, file_path: &str, file_name: &str) -> Result<(String, String)> {
+    let limit = config.max_file_size_bytes;
+    let document = pdf::read(Path::new(file_path), limit)?;
+    let text = document.pages.join(&chunker::PAGE_BREAK.to_string());
+    check_size(file_path, "extracted text", text.len() as u64, limit)?;
+    let title = document
+        .title
+        .unwrap_or_else(|| chunker::extract_title(&text, file_name));
+    Ok((title, text))
+}
+
+#[cfg(not(feature = "pdf-native"))]
+fn read_pdf(_config: &AppConfig, _file_path: &str, _file_name: &str) -> Result<(String, String)> {
+    anyhow::bail!(
+        "Reading PDFs natively requires the 'pdf-native' feature. \
+         Build with: cargo build --features pdf-native"
+    )
+}
+
+/// Whether reading a PDF failed because it has no text layer.
+#[cfg(feature = "pdf-native")]
+fn is_textless(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref(), Some(pdf::Unreadable::NoText(_)))
+}
+
+#[cfg(not(feature = "pdf-native"))]
+fn is_textless(_error: &anyhow::Error) -> bool {
+    false
+}
+
+/// [`read_pdf`], except that a PDF with (almost) no text layer is read by
+/// the `ocr_command` when one is set. Also returns the command when it ran.
+fn pdf_text(
+    config: &AppConfig,
+    file_path: &str,
+    file_name: &str,
+) -> Result<(String, String, Option<Converter>)> {
+    let native = read_pdf(config, file_path, file_name);
+    let scanned = match &native {
+        Ok((_, text)) => ocr::is_scanned(text),
+        Err(e) => is_textless(e),
+    };
+    let command = if scanned { ocr::command(config)? } else { None };
+    let Some(command) = command else {
+        let (title, text) = native?;
+        return Ok((title, text, None));
+    };
+    tracing::info!("{} has no text layer, running OCR", file_path);
+    let text = command.run(file_path, config.max_file_size_bytes)?;
+    let first_page = text.split(chunker::PAGE_BREAK).next().unwrap_or_default();
+    let title = chunker::extract_title(first_page, file_name);
+    Ok((title, text, Some(command)))
+}
+
+/// Parse a JSON, YAML or TOML document and render it as one `path: value`
+/// line per scalar (`server.listen_port: 7070`), which reads and embeds
+/// better than the punctuation-heavy source.
+fn flatten_structured(ext: &str, content: &str) -> Result<String> {
+    let node = match ext {
+        "json" => structured::parse_json(content)?,
+        "toml" => structured::parse_toml(content)?,
+        _ => structured::parse_yaml(content)?,
+    };
+    let mut lines = Vec::new();
+    flatten_node(&node, "", &mut lines);
+    Ok(lines.join("\n"))
+}
+
+fn flatten_node(node: &Node, path: &str, lines: &mut Vec<String>) {
+    let line = |value: &str| match path {
+        "" => value.to_string(),
+        _ => format!("{}: {}", path, value),
+    };
+    match node {
+        Node::Map(entries) if !entries.is_empty() => {
+            for (key, value) in entries {
+                // Quote keys that would read as nested paths.
+                let key = if key.is_empty() || key.contains(['.', '[']) {
+                    format!("{:?}", key)
+                } else {
+                    key.clone()
+                };
+                let child = match path {
+                    "" => key,
+                    _ => format!("{}.{}", path, key),
+                };
+                flatten_node(value, &child, lines);
+            }
+        }
+        Node::List(items) if !items.is_empty() => {
+            if items.len() > MAX_FLATTENED_ITEMS {
+                lines.push(line(&format!(
+                    "{} items, first {} shown",
+                    items.len(),
+                    MAX_FLATTENED_ITEMS
+                )));
+            }
+            for (i, item) in items.iter().take(MAX_FLATTENED_ITEMS).enumerate() {
+                flatten_node(item, &format!("{}[{}]", path, i), lines);
+            }
+        }
+        Node::Map(_) => lines.push(line("{}")),
+        Node::List(_) => lines.push(line("[]")),
+        Node::Scalar(value) => lines.push(line(value)),
+    }
+}
+
+/// Reciprocal Rank Fusion (RRF) merge of vector and BM25 results.
+pub fn rrf_merge(
+    vector_results: &[SearchResult],
+    bm25_results: &[SearchResult],
+    top_k: usize,
+) -> Vec<SearchResult> {
+    let k = 60.0; // RRF constant
+
+    let mut scores: HashMap<String, f64> = HashMap::new();
+    let mut result_map: HashMap<String, SearchResult> = HashMap::new();
+
+    // Score vector results
+    for (rank, result) in vector_results.iter().enumerate() {
+        let rrf_score = 1.0 / (k + rank as f64 + 1.0);
+        *scores.entry(result.chunk_id.clone()).or_insert(0.0) += rrf_score;
+        result_map
+            .entry(result.chunk_id.clone())
+            .or_insert_with(|| result.clone());
+    }
+
+    // Score BM25 results
+    for (rank, result) in bm25_results.iter().enumerate() {
+        let rrf_score = 1.0 / (k + rank as f64 + 1.0);
+        *scores.entry(result.chunk_id.clone()).or_insert(0.0) += rrf_score;
+        result_map
+            .entry(result.chunk_id.clone())
+            .or_insert_with(|| result.clone());
+    }
+
+    // Sort by fused score
+    let mut scored: Vec<(String, f64)> = scores.into_iter().collect();
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    scored
+        .into_iter()
+        .take(top_k)
+        .filter_map(|(id, score)| {
+            result_map.remove(&id).map(|mut r| {
+                r.score = score;
+                r
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_hybrid_search_common::config::SourceConfig;
+
+    fn make_result(id: &str) -> SearchResult {
+        SearchResult {
+            chunk_id: id.to_string(),
+            score: 0.0,
+            title: format!("Title {}", id),
+            source_path: "/test".to_string(),
+            source_type: "md".to_string(),
+            section: None,
+            location: Default::default(),
+            file_mtime: None,
+            snippet: "snippet".to_string(),
+            text: None,
+            document_match_count: None,
+        }
+    }
+
+    fn options() -> IngestOptions {
+        IngestOptions {
+            prune: true,
+            hidden: false,
+            no_ignore: false,
+            progress: false,
+            report: None,
+            force: false,
+            force_paths: Vec::new(),
+            fail_fast: false,
+            skip_dimension_check: false,
+        }
+    }
+
+    fn entry(mtime: &str) -> StateEntry {
+        StateEntry::new(&AppConfig::default(), mtime.to_string())
+    }
+
+    fn relative_files(config: &AppConfig, dir: &Path, options: &IngestOptions) -> Vec<String> {
+        let root = std::fs::canonicalize(dir).unwrap();
+        let mut files: Vec<String> =
+            collect_files(config, &[dir.to_string_lossy().to_string()], false, options)
+                .unwrap()
+                .into_iter()
+                .map(|f| {
+                    Path::new(&f)
+                        .strip_prefix(&root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_collect_files_respects_ignore_files() {
+        let dir = std::env::temp_dir().join(format!("ingest-ignore-{}", uuid::Uuid::new_v4()));
+        for sub in [".git", ".obsidian", "drafts", "notes/archive", "vendor"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        for file in [
+            "README.md",
+            ".hidden.md",
+            ".git/HEAD.md",
+            ".obsidian/workspace.md",
+            "drafts/wip.md",
+            "notes/a.md",
+            "notes/a.md.bak.md",
+            "notes/keep.bak.md",
+            "notes/archive/old.md",
+            "vendor/lib.md",
+        ] {
+            std::fs::write(dir.join(file), "# Doc").unwrap();
+        }
+        std::fs::write(
+            dir.join(".gitignore"),
+            "# fixtures\ndrafts/\n*.bak.md\nlinked-notes\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes/.gitignore"), "!keep.bak.md\n/archive\n").unwrap();
+        std::fs::write(dir.join(".ignore"), "vendor\n").unwrap();
+        // An ignored symlink is skipped even though its target is not.
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("notes"), dir.join("linked-notes")).unwrap();
+
+        assert_eq!(
+            relative_files(&AppConfig::default(), &dir, &options()),
+            vec!["README.md", "notes/a.md", "notes/keep.bak.md"]
+        );
+
+        let hidden = IngestOptions {
+            hidden: true,
+            ..options()
+        };
+        let files = relative_files(&AppConfig::default(), &dir, &hidden);
+        assert!(files.contains(&".hidden.md".to_string()));
+        assert!(files.contains(&".obsidian/workspace.md".to_string()));
+        assert!(!files.contains(&"drafts/wip.md".to_string()));
+
+        let no_ignore = IngestOptions {
+            no_ignore: true,
+            ..options()
+        };
+        let files = relative_files(&AppConfig::default(), &dir, &no_ignore);
+        assert!(files.contains(&"drafts/wip.md".to_string()));
+        assert!(files.contains(&"vendor/lib.md".to_string()));
+        assert!(!files.contains(&".hidden.md".to_string()));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_files_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let base = std::env::temp_dir().join(format!("ingest-links-{}", uuid::Uuid::new_v4()));
+        let (dir, outside) = (base.join("docs"), base.join("outside"));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(dir.join("a.md"), "# A").unwrap();
+        std::fs::write(dir.join("sub/b.md"), "# B").unwrap();
+        std::fs::write(outside.join("x.md"), "# X").unwrap();
+        symlink(&dir, dir.join("sub/loop")).unwrap();
+        symlink(&outside, dir.join("ext")).unwrap();
+        symlink(dir.join("a.md"), dir.join("alias.md")).unwrap();
+
+        // Links are not followed by default: neither the cycle nor the
+        // directory outside the tree is walked.
+        let config = AppConfig::default();
+        assert_eq!(
+            relative_files(&config, &dir, &options()),
+            vec!["a.md", "sub/b.md"]
+        );
+
+        // Followed, each target is collected once and the cycle ends.
+        let follow = AppConfig {
+            follow_symlinks: true,
+            ..AppConfig::default()
+        };
+        let files = relative_files(&follow, &dir, &options());
+        assert_eq!(files.len(), 3, "{:?}", files);
+        assert!(files.contains(&"ext/x.md".to_string()));
+        assert!(files.contains(&"sub/b.md".to_string()));
+        assert!(files.contains(&"a.md".to_string()) != files.contains(&"alias.md".to_string()));
+        // Walking again finds the same files.
+        assert_eq!(relative_files(&follow, &dir, &options()), files);
+
+        let shallow = AppConfig {
+            max_depth: Some(1),
+            ..AppConfig::default()
+        };
+        assert_eq!(relative_files(&shallow, &dir, &options()), vec!["a.md"]);
+
+        std::fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_oversized_file_is_skipped() {
+        let dir = std::env::temp_dir().join(format!("ingest-size-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("big.md").to_string_lossy().to_string();
+        std::fs::write(&file, "# Big\n\n".repeat(10)).unwrap();
+
+        let mut config = AppConfig {
+            max_file_size_bytes: 69,
+            ..AppConfig::default()
+        };
+        let err = process_file(&config, &file).unwrap_err();
+        let too_large = err.downcast_ref::<TooLarge>().unwrap();
+        assert_eq!((too_large.size, too_large.limit), (70, 69));
+        assert_eq!(too_large.what, "file");
+
+        config.max_file_size_bytes = 70;
+        assert!(!process_file(&config, &file).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    /// Chunks of `/test` whose texts are their numbers.
+    fn numbered_chunks(count: u32) -> Vec<ChunkPayload> {
+        (0..count)
+            .map(|i| ChunkPayload {
+                chunk_id: format!("c{}", i),
+                source_path: "/test".to_string(),
+                source_type: "md".to_string(),
+                title: "Title".to_string(),
+                chunk_index: i,
+                text: i.to_string(),
+                updated_at: String::new(),
+                tags: Vec::new(),
+                section: None,
+                location: Default::default(),
+                file_mtime: None,
+                file_size_bytes: None,
+                language: None,
+                converter: None,
+                ocr: false,
+                encoding: None,
+                embedding_truncated: false,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_embed_sub_batches_concurrently() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let chunks = numbered_chunks(10);
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+
+        // Each "vector" is the chunk's number; later sub-batches finish first.
+        let (embedded, vectors, failed) = embed_sub_batches(&chunks, 2, 3, |texts| {
+            let in_flight = &in_flight;
+            let max_in_flight = &max_in_flight;
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(now, Ordering::SeqCst);
+                let first: u64 = texts[0].parse().unwrap();
+                tokio::time::sleep(Duration::from_millis(50 - first * 5)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                if first == 4 {
+                    anyhow::bail!("rate limited");
+                }
+                Ok(texts.iter().map(|t| vec![t.parse().unwrap()]).collect())
+            }
+        })
+        .await;
+
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].1, "rate limited");
+        assert_eq!(failed[0].0.len(), 2);
+        assert_eq!(embedded.len(), 8);
+        for (chunk, vector) in embedded.iter().zip(&vectors) {
+            assert_eq!(vector[0], chunk.text.parse::<f32>().unwrap());
+        }
+        assert!(!embedded.iter().any(|c| c.text == "4" || c.text == "5"));
+    }
+
+    #[tokio::test]
+    async fn test_embed_sub_batches_one_at_a_time_on_bad_request() {
+        let chunks = numbered_chunks(6);
+        let requests = Cell::new(0);
+        // Chunk 4 is too long for the model; a request with it is rejected.
+        let (embedded, vectors, failed) = embed_sub_batches(&chunks, 3, 1, |texts| {
+            requests.set(requests.get() + 1);
+            async move {
+                if texts.iter().any(|t| t == "4") {
+                    return Err(anyhow::Error::new(ApiError {
+                        provider: "OpenAI".to_string(),
+                        status: reqwest::StatusCode::BAD_REQUEST,
+                        body: "maximum context length exceeded".to_string(),
+                    }));
+                }
+                Ok(texts.iter().map(|t| vec![t.parse().unwrap()]).collect())
+            }
+        })
+        .await;
+
+        assert_eq!(requests.get(), 5);
+        assert_eq!(embedded.len(), 5);
+        assert_eq!(vectors.len(), 5);
+        assert!(!embedded.iter().any(|c| c.text == "4"));
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0[0].text, "4");
+        assert!(failed[0].1.contains("400 Bad Request"), "{}", failed[0].1);
+    }
+
+    #[test]
+    fn test_mark_truncated() {
+        let mut chunks = numbered_chunks(2);
+        chunks[1].text = "word ".repeat(20);
+        assert!(matches!(mark_truncated(&chunks, 0), Cow::Borrowed(_)));
+        assert!(matches!(mark_truncated(&chunks, 50), Cow::Borrowed(_)));
+        let marked = mark_truncated(&chunks, 10);
+        assert!(!marked[0].embedding_truncated);
+        assert!(marked[1].embedding_truncated);
+    }
+
+    #[test]
+    fn test_summary_report() {
+        let summary = IngestSummary {
+            files_ok: 3,
+            files_failed: 1,
+            chunks_indexed: 42,
+            embed_requests: 2,
+            avg_embed_latency_ms: 150.0,
+            elapsed_secs: 1.25,
+            batch_sizes: BatchSizes::new(&AppConfig::default()),
+            ..IngestSummary::default()
+        };
+        let text = summary.to_string();
+        assert!(text.contains("3 ok, 1 failed, 0 skipped, 0 unchanged"));
+        assert!(text.contains("2 requests, 150.0ms average\n"));
+        assert!(
+            text.contains("10 files, 20 texts per embedding request, 100 points per upsert"),
+            "{}",
+            text
+        );
+        assert!(text.ends_with("Elapsed:   1.2s"));
+        assert!(!text.contains("Upserts:"));
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["chunks_indexed"], 42);
+        assert_eq!(json["files_failed"], 1);
+        assert_eq!(json["batch_sizes"]["embedding"], 20);
+        assert_eq!(json["rate_limit_wait_secs"], 0.0);
+
+        let throttled = IngestSummary {
+            rate_limit_wait_secs: 3.5,
+            ..summary
+        };
+        assert!(throttled
+            .to_string()
+            .contains("2 requests, 150.0ms average, 3.5s waiting on rate limits\n"));
+
+        let upserted = IngestSummary {
+            points_upserted: 42,
+            upsert_points_per_sec: 1234.4,
+            batch_sizes: BatchSizes {
+                qdrant_upsert_parallelism: 4,
+                ..BatchSizes::new(&AppConfig::default())
+            },
+            ..throttled
+        };
+        let text = upserted.to_string();
+        assert!(
+            text.contains("\n  Upserts:   42 points, 1234 points/s\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("100 points per upsert, 4 upserts at a time\n"));
+    }
+
+    #[test]
+    fn test_failure_report() {
+        let mut summary = IngestSummary::default();
+        let options = options();
+        for i in 0..12 {
+            let path = format!("/docs/{}.pdf", i);
+            summary
+                .fail(&options, &path, Stage::Convert, "markitdown failed")
+                .unwrap();
+        }
+        summary
+            .fail(&options, "/docs/big.md", Stage::Skip, "1 MB")
+            .unwrap();
+        summary
+            .fail(&options, "/docs/a.md", Stage::Embed, "rate limited")
+            .unwrap();
+        let text = summary.to_string();
+        assert!(text.contains("\n  Failed to convert (12):\n    /docs/0.pdf: markitdown failed"));
+        assert!(text.contains("/docs/9.pdf") && !text.contains("/docs/10.pdf"));
+        assert!(text.contains("    ... and 2 more\n  Skipped (1):\n    /docs/big.md: 1 MB"));
+        assert!(text.ends_with("  Failed to embed (1):\n    /docs/a.md: rate limited"));
+
+        let dir = std::env::temp_dir().join(format!("ingest-errors-{}", uuid::Uuid::new_v4()));
+        let config = AppConfig {
+            tantivy_index_dir: dir.join("tantivy").to_string_lossy().into_owned(),
+            ..AppConfig::default()
+        };
+        write_errors(&config, &summary.failures).unwrap();
+        let path = config.ingest_errors_path();
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written.as_array().unwrap().len(), 14);
+        assert_eq!(
+            written[13],
+            serde_json::json!({"path": "/docs/a.md", "stage": "embed", "message": "rate limited"})
+        );
+        // A clean pass removes the report of the last one.
+        write_errors(&config, &[]).unwrap();
+        assert!(!path.exists());
+
+        // --fail-fast stops at the first error, but not at a skip.
+        let fail_fast = IngestOptions {
+            fail_fast: true,
+            ..options
+        };
+        let mut summary = IngestSummary::default();
+        summary
+            .fail(&fail_fast, "/docs/big.md", Stage::Skip, "1 MB")
+            .unwrap();
+        let err = summary
+            .fail(
+                &fail_fast,
+                "/docs/a.md",
+                Stage::Index,
+                "Tantivy index: locked",
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/docs/a.md (index failed, --fail-fast): Tantivy index: locked"
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_collect_explicit_file() {
+        let dir = std::env::temp_dir().join(format!("ingest-file-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Explicit files bypass the hidden-file and ignore rules.
+        let note = dir.join(".note.md");
+        let pdf = dir.join("slides.PDF");
+        let image = dir.join("photo.png");
+        for file in [&note, &pdf, &image] {
+            std::fs::write(file, "x").unwrap();
+        }
+        std::fs::write(dir.join(".gitignore"), "*.md\n").unwrap();
+        let arg = |p: &Path| vec![p.to_string_lossy().to_string()];
+
+        let files = collect_files(&AppConfig::default(), &arg(&note), false, &options()).unwrap();
+        assert_eq!(files, arg(&std::fs::canonicalize(&note).unwrap()));
+        assert_eq!(
+            collect_files(&AppConfig::default(), &arg(&pdf), true, &options())
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let err = collect_files(&AppConfig::default(), &arg(&pdf), false, &options()).unwrap_err();
+        assert!(err.to_string().contains("needs markitdown"));
+        let err = collect_files(&AppConfig::default(), &arg(&image), true, &options()).unwrap_err();
+        assert!(err.to_string().starts_with("Unsupported file type"));
+        assert!(err.to_string().contains("md, txt, pdf"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_reindex_modified_file() {
+        let dir = std::env::temp_dir().join(format!("ingest-reindex-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = AppConfig {
+            chunk_size: 40,
+            chunk_overlap: 0,
+            tantivy_index_dir: dir.join("tantivy").to_string_lossy().into_owned(),
+            ..AppConfig::default()
+        };
+        let file = dir.join("notes.md");
+        let path = file.to_string_lossy().to_string();
+        let paragraphs: Vec<String> = (0..6)
+            .map(|i| format!("Paragraph {} has some words in it.", i))
+            .collect();
+
+        std::fs::write(&file, paragraphs.join("\n\n")).unwrap();
+        let old = process_file(&config, &path).unwrap();
+        tantivy_index::index_chunks(&config, &old).unwrap();
+        // A chunk indexed before ids were derived from the path.
+        let legacy = ChunkPayload {
+            chunk_id: uuid::Uuid::new_v4().to_string(),
+            ..old[0].clone()
+        };
+        tantivy_index::index_chunks(&config, &[legacy]).unwrap();
+
+        std::fs::write(&file, paragraphs[..2].join("\n\n")).unwrap();
+        let new = process_file(&config, &path).unwrap();
+        assert!(new.len() < old.len());
+        assert_eq!(new[0].chunk_id, old[0].chunk_id);
+        tantivy_index::index_chunks(&config, &new).unwrap();
+        let ids = new.iter().map(|c| c.chunk_id.clone()).collect();
+        tantivy_index::delete_stale_chunks(&config, &[(path.clone(), ids)]).unwrap();
+        assert_eq!(
+            tantivy_index::get_index_count(&config).unwrap(),
+            new.len() as u64
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    /// Re-ingesting a file that got shorter through `run_ingest` must drop
+    /// its old tail from Qdrant as well as from Tantivy.
+    #[tokio::test]
+    #[ignore = "needs a Qdrant at QDRANT_URL"]
+    async fn test_reindex_modified_file_through_ingest() {
+        let dir = std::env::temp_dir().join(format!("ingest-reindex-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("docs")).unwrap();
+        let config = AppConfig {
+            qdrant_url: std::env::var("QDRANT_URL")
+                .unwrap_or_else(|_| "http://localhost:6334".to_string()),
+            collection_name: format!("test-reindex-{}", uuid::Uuid::new_v4()),
+            embedding_provider: "mock".to_string(),
+            embedding_dimension: 8,
+            chunk_size: 40,
+            chunk_overlap: 0,
+            tantivy_index_dir: dir.join("tantivy").to_string_lossy().into_owned(),
+            ..AppConfig::default()
+        };
+        let file = dir.join("docs").join("notes.md");
+        let paragraphs: Vec<String> = (0..6)
+            .map(|i| format!("Paragraph {} has some words in it.", i))
+            .collect();
+        let sources = Sources {
+            paths: vec![dir.join("docs").to_string_lossy().into_owned()],
+            ..Sources::default()
+        };
+        let path = std::fs::canonicalize(dir.join("docs"))
+            .unwrap()
+            .join("notes.md")
+            .to_string_lossy()
+            .into_owned();
+        let filters = types::SearchFilters {
+            path_prefix: Some(path.clone()),
+            ..types::SearchFilters::default()
+        };
+
+        std::fs::write(&file, paragraphs.join("\n\n")).unwrap();
+        run_ingest(&config, &sources, &options()).await.unwrap();
+        let old = qdrant_client::count_points(&config, &filters)
+            .await
+            .unwrap();
+
+        std::fs::write(&file, paragraphs[..2].join("\n\n")).unwrap();
+        run_ingest(&config, &sources, &options()).await.unwrap();
+        let new = process_file(&config, &path).unwrap().len() as u64;
+        assert!(new < old, "{} < {}", new, old);
+        assert_eq!(
+            qdrant_client::count_points(&config, &filters)
+                .await
+                .unwrap(),
+            new
+        );
+        assert_eq!(tantivy_index::get_index_count(&config).unwrap(), new);
+
+        qdrant_client::delete_collection(&config).await.unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_state_entry_formats() {
+        // A version 1 file: a bare map of entries.
+        let state = parse_state(
+            r#"{
+                "/docs/old.md": "2024-01-01T00:00:00+00:00",
+                "/docs/new.md": {"mtime": "t2", "chunk_size": 500, "chunk_overlap": 50},
+                "https://wiki/page": {"mtime": "t3", "etag": "\"v1\""}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(state["/docs/old.md"].mtime, "2024-01-01T00:00:00+00:00");
+        assert_eq!(state["/docs/old.md"].chunk_size, None);
+        assert_eq!(state["/docs/new.md"].chunk_size, Some(500));
+        assert_eq!(state["/docs/new.md"].chunk_overlap, Some(50));
+        assert_eq!(state["/docs/new.md"].chunking_strategy, None);
+        assert_eq!(state["/docs/new.md"].chunk_ids, None);
+        assert_eq!(
+            state["https://wiki/page"].validators(),
+            Validators {
+                etag: Some("\"v1\"".to_string()),
+                last_modified: None,
+            }
+        );
+
+        let json = serde_json::to_value(entry("t3")).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "mtime": "t3",
+                "chunk_size": 1000,
+                "chunk_overlap": 200,
+                "chunking_strategy": "chars",
+                "chunk_unit": "chars"
+            })
+        );
+    }
+
+    #[test]
+    fn test_state_file_versions() {
+        let dir = std::env::temp_dir().join(format!("ingest-state-{}", uuid::Uuid::new_v4()));
+        let config = AppConfig {
+            tantivy_index_dir: dir.join("tantivy").to_string_lossy().into_owned(),
+            ..AppConfig::default()
+        };
+        let path = state_file_path(&config);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            r#"{"/docs/a.md": "t1", "/docs/b.md": {"mtime": "t2", "chunk_size": 500}}"#,
+        )
+        .unwrap();
+
+        // Old files load, and are rewritten in the current version.
+        let mut state = load_state(&config);
+        assert_eq!(state.len(), 2);
+        assert_eq!(state["/docs/a.md"].mtime, "t1");
+        state.insert(
+            "/docs/c.md".to_string(),
+            entry("t3").with_chunk_ids(vec!["id-0".to_string(), "id-1".to_string()]),
+        );
+        save_state(&config, &state).unwrap();
+        let saved: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["version"], STATE_VERSION);
+        assert_eq!(
+            saved["files"]["/docs/c.md"]["chunk_ids"],
+            serde_json::json!(["id-0", "id-1"])
+        );
+        assert_eq!(load_state(&config), state);
+
+        // Recorded ids are deleted exactly; entries without them by path.
+        let recorded = Previous::new("/docs/c.md", state.get("/docs/c.md"));
+        assert_eq!(recorded.chunk_ids.as_deref().map(<[_]>::len), Some(2));
+        let legacy = Previous::new("/docs/a.md", state.get("/docs/a.md"));
+        assert_eq!(legacy.source_path, "/docs/a.md");
+        assert!(legacy.chunk_ids.is_none());
+
+        // A newer version's entries are still read.
+        let newer = parse_state(r#"{"version": 99, "files": {"/docs/a.md": "t1"}}"#).unwrap();
+        assert_eq!(newer["/docs/a.md"].mtime, "t1");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_interrupted_run_resumes() {
+        let dir = std::env::temp_dir().join(format!("ingest-resume-{}", uuid::Uuid::new_v4()));
+        let docs = dir.join("docs");
+        std::fs::create_dir_all(&docs).unwrap();
+        let config = AppConfig {
+            tantivy_index_dir: dir.join("tantivy").to_string_lossy().into_owned(),
+            ingest_file_batch_size: 2,
+            ..AppConfig::default()
+        };
+        let files: Vec<String> = ["a.md", "b.md", "c.md", "d.md"]
+            .iter()
+            .map(|name| {
+                let path = docs.join(name);
+                std::fs::write(&path, format!("# {}", name)).unwrap();
+                path.to_string_lossy().into_owned()
+            })
+            .collect();
+        let roots = SourceRoots::new(&config);
+        let options = options();
+
+        // The first run indexes its first batch, where b.md's upsert fails,
+        // and is interrupted before the second.
+        let mut state = load_state(&config);
+        let pending = files_to_process(&config, &roots, files.clone(), &options, &state);
+        assert_eq!(pending, files);
+        let batch = pending
+            .chunks(config.ingest_file_batch_size)
+            .next()
+            .unwrap()
+            .iter()
+            .map(|f| (f.clone(), ChunkHashes::default(), vec![format!("{}-0", f)]))
+            .collect();
+        let indexed = Indexed {
+            chunks: 1,
+            failed: HashMap::from([(
+                files[1].clone(),
+                (Stage::Index, "Qdrant upsert: unavailable".to_string()),
+            )]),
+        };
+        let mut summary = IngestSummary::default();
+        record_batch(
+            &config,
+            &roots,
+            &options,
+            batch,
+            &indexed,
+            &mut state,
+            &mut summary,
+        )
+        .unwrap();
+        save_state(&config, &state).unwrap();
+        assert_eq!((summary.files_ok, summary.files_failed), (1, 1));
+
+        // The second run picks up the failed file and the unprocessed ones.
+        let state = load_state(&config);
+        let pending = files_to_process(&config, &roots, files.clone(), &options, &state);
+        assert_eq!(pending, files[1..]);
+        assert!(!state_file_path(&config).with_extension("json.tmp").exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_is_forced() {
+        let dir = std::env::temp_dir().join(format!("ingest-force-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("notes")).unwrap();
+        let root = std::fs::canonicalize(&dir).unwrap();
+        let file = |name: &str| root.join(name).to_string_lossy().to_string();
+
+        assert!(!options().is_forced(&file("notes/a.md")));
+        let all = IngestOptions {
+            force: true,
+            ..options()
+        };
+        assert!(all.is_forced(&file("a.md")));
+
+        let notes = IngestOptions {
+            force_paths: IngestOptions::canonical(&[dir
+                .join("notes")
+                .join("..")
+                .join("notes")
+                .to_string_lossy()
+                .to_string()]),
+            ..options()
+        };
+        assert!(notes.is_forced(&file("notes/a.md")));
+        assert!(!notes.is_forced(&file("a.md")));
+        // Prefixes match whole path components.
+        assert!(!notes.is_forced(&file("notes-archive/a.md")));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_process_file_front_matter() {
+        let dir = std::env::temp_dir().join(format!("ingest-fm-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = AppConfig::default();
+
+        let note = dir.join("note.md").to_string_lossy().to_string();
+        std::fs::write(
+            &note,
+            "---\ntitle: Billing design\ntags: [architecture, billing]\n---\n# Heading\n\nInvoices.",
+        )
+        .unwrap();
+        let chunks = process_file(&config, &note).unwrap();
+        assert_eq!(chunks[0].title, "Billing design");
+        assert_eq!(chunks[0].tags, vec!["architecture", "billing"]);
+        assert!(!chunks.iter().any(|c| c.text.contains("tags:")));
+        assert_eq!(chunks[0].file_mtime, file_modified_time(&note));
+        assert!(chunks[0].file_mtime.is_some());
+        assert_eq!(chunks[0].file_size_bytes, Some(80));
+        // Locations count the front matter.
+        assert_eq!(chunks[0].location.start_line, Some(5));
+        assert_eq!(chunks[0].location.start_char, Some(60));
+        assert!(!chunks[0].location.converted);
+        // Too little text to tell.
+        assert_eq!(chunks[0].language.as_deref(), Some("und"));
+
+        let japanese = dir.join("japanese.md").to_string_lossy().to_string();
+        std::fs::write(
+            &japanese,
+            "# 請求\n\n請求書は毎月一日に発行されます。支払いが遅れた場合は担当者に連絡してください。",
+        )
+        .unwrap();
+        let chunks = process_file(&config, &japanese).unwrap();
+        assert_eq!(chunks[0].language.as_deref(), Some("ja"));
+
+        let broken = dir.join("broken.md").to_string_lossy().to_string();
+        std::fs::write(&broken, "---\nnot yaml at all\n---\n# Heading\n").unwrap();
+        let chunks = process_file(&config, &broken).unwrap();
+        assert!(chunks[0].tags.is_empty());
+        assert!(chunks[0].text.contains("not yaml at all"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_code_files() {
+        let dir = std::env::temp_dir().join(format!("ingest-code-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.rs"), "// comment\n\nfn main() {}\n").unwrap();
+        std::fs::write(dir.join("app.py"), "def main():\n    pass\n").unwrap();
+        std::fs::write(dir.join("data.bin"), "").unwrap();
+
+        let config = AppConfig {
+            code_extensions: vec!["rs".to_string()],
+            ..AppConfig::default()
+        };
+        let files = collect_files(
+            &config,
+            &[dir.to_string_lossy().to_string()],
+            false,
+            &options(),
+        )
+        .unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].ends_with("main.rs"));
+
+        let chunks = process_file(&config, &files[0]).unwrap();
+        assert_eq!(chunks[0].source_type, "rs");
+        assert_eq!(chunks[0].title, "main.rs");
+        assert!(chunks[0]
+            .text
+            .starts_with(&format!("{}:1\n// comment", files[0])));
+        assert_eq!(chunks[0].location.start_line, Some(1));
+        assert_eq!(chunks[0].location.end_char, Some(24));
+
+        let err = check_file_type(&config, &dir.join("data.bin"), false).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("html, epub, json, yaml, yml, toml, rs)"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_flatten_structured() {
+        let json = r#"{"server": {"listen_port": 7070, "hosts": ["a", "b"]}, "a.b": null, "tags": [], "meta": {}}"#;
+        assert_eq!(
+            flatten_structured("json", json).unwrap(),
+            "server.listen_port: 7070\nserver.hosts[0]: a\nserver.hosts[1]: b\n\"a.b\": null\ntags: []\nmeta: {}"
+        );
+        let toml = "title = \"x\"\n[[servers]]\nname = \"alpha\"\n";
+        assert_eq!(
+            flatten_structured("toml", toml).unwrap(),
+            "title: x\nservers[0].name: alpha"
+        );
+        let yaml = "server:\n  listen_port: 7070\n";
+        assert_eq!(
+            flatten_structured("yml", yaml).unwrap(),
+            "server.listen_port: 7070"
+        );
+
+        let big: Vec<u32> = (0..100).collect();
+        let flat =
+            flatten_structured("json", &serde_json::json!({ "ids": big }).to_string()).unwrap();
+        let lines: Vec<&str> = flat.lines().collect();
+        assert_eq!(lines[0], "ids: 100 items, first 20 shown");
+        assert_eq!(lines.len(), 21);
+        assert_eq!(lines[20], "ids[19]: 19");
+    }
+
+    #[test]
+    fn test_process_structured_file() {
+        let dir = std::env::temp_dir().join(format!("ingest-structured-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = AppConfig::default();
+
+        let good = dir.join("config.yaml").to_string_lossy().to_string();
+        std::fs::write(&good, "server:\n  listen_port: 7070\n").unwrap();
+        let chunks = process_file(&config, &good).unwrap();
+        assert_eq!(chunks[0].source_type, "yaml");
+        assert_eq!(chunks[0].title, "config.yaml");
+        assert_eq!(chunks[0].text, "server.listen_port: 7070");
+        assert_eq!(chunks[0].location, Default::default());
+
+        let broken = dir.join("broken.json").to_string_lossy().to_string();
+        std::fs::write(&broken, "{\"unterminated\": ").unwrap();
+        let chunks = process_file(&config, &broken).unwrap();
+        assert_eq!(chunks[0].source_type, "json");
+        assert_eq!(chunks[0].text, "{\"unterminated\": ");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_process_page() {
+        let page = web::Page {
+            title: None,
+            text: "# Runbook\n\nShip it.".to_string(),
+            validators: Validators::default(),
+        };
+        let chunks = process_page(&AppConfig::default(), "https://wiki/runbook", &page).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].source_path, "https://wiki/runbook");
+        assert_eq!(chunks[0].source_type, "url");
+        assert_eq!(chunks[0].title, "Runbook");
+    }
+
+    #[test]
+    fn test_dedupe() {
+        let config = AppConfig {
+            dedupe_chunks: true,
+            ..AppConfig::default()
+        };
+        let payloads = |path: &str, texts: &[&str]| {
+            let chunks: Vec<chunker::Chunk> = texts
+                .iter()
+                .map(|text| chunker::Chunk {
+                    text: text.to_string(),
+                    section: None,
+                    range: 0..text.len(),
+                })
+                .collect();
+            build_payloads(path, "md", "t", &[], &chunks, None)
+        };
+        let texts = |chunks: &[ChunkPayload]| -> Vec<String> {
+            chunks.iter().map(|c| c.text.clone()).collect()
+        };
+        let mut state = IngestState::new();
+        let mut summary = IngestSummary::default();
+        let mut dedupe = Dedupe::new(&config, &state);
+
+        let mut a = payloads("/a.md", &["Licensed under MIT.", "About A", "About A"]);
+        let hashes = dedupe_chunks(&mut dedupe, "/a.md", &state, &mut a, &mut summary);
+        assert_eq!(texts(&a), vec!["Licensed under MIT.", "About A"]);
+        state.insert("/a.md".into(), entry("t1").with_hashes(hashes));
+
+        let mut b = payloads("/b.md", &["Licensed  under\nMIT.", "About B"]);
+        let hashes = dedupe_chunks(&mut dedupe, "/b.md", &state, &mut b, &mut summary);
+        assert_eq!(texts(&b), vec!["About B"]);
+        assert_eq!(hashes.dropped.len(), 1);
+        state.insert("/b.md".into(), entry("t1").with_hashes(hashes));
+        assert_eq!(summary.chunks_deduplicated, 2);
+
+        // Code chunks match without their path:line headers.
+        assert_eq!(
+            chunk_hash(&payloads("/x.rs", &["/x.rs:1\n// MIT"])[0]),
+            chunk_hash(&payloads("/y.rs", &["/y.rs:1\n// MIT"])[0])
+        );
+
+        // Re-processing the holder keeps its chunks, in a later run too.
+        let mut dedupe = Dedupe::new(&config, &state);
+        let mut a = payloads("/a.md", &["Licensed under MIT.", "About A, edited"]);
+        dedupe_chunks(&mut dedupe, "/a.md", &state, &mut a, &mut summary);
+        assert_eq!(a.len(), 2);
+
+        assert!(missing_chunks(&config, &state).is_empty());
+        assert_eq!(missing_chunks(&AppConfig::default(), &state).len(), 2);
+        state.remove("/a.md");
+        assert_eq!(
+            missing_chunks(&config, &state),
+            HashSet::from(["/b.md".to_string()])
+        );
+        assert!(Dedupe::new(&AppConfig::default(), &state).is_none());
+    }
+
+    #[test]
+    fn test_process_piped() {
+        let config = AppConfig::default();
+        let piped = |path: &str, title: Option<&str>, text: &str| Piped {
+            path: path.to_string(),
+            title: title.map(str::to_string),
+            text: text.to_string(),
+        };
+
+        let report = "---\ntags: [cron]\n---\n# Nightly\n\nAll jobs passed.";
+        let chunks = process_piped(&config, &piped("virtual/today.md", None, report)).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].source_path, "virtual/today.md");
+        assert_eq!(chunks[0].source_type, "md");
+        assert_eq!(chunks[0].title, "Nightly");
+        assert_eq!(chunks[0].tags, vec!["cron"]);
+        assert!(!chunks[0].text.contains("tags:"));
+
+        let titled = piped("virtual/today.md", Some("cron report"), report);
+        assert_eq!(
+            process_piped(&config, &titled).unwrap()[0].title,
+            "cron report"
+        );
+
+        let json = piped("status.json", None, r#"{"ok": true}"#);
+        assert_eq!(process_piped(&config, &json).unwrap()[0].text, "ok: true");
+
+        let small = AppConfig {
+            max_file_size_bytes: 10,
+            ..AppConfig::default()
+        };
+        let err = process_piped(&small, &piped("big.txt", None, report)).unwrap_err();
+        assert!(err.is::<TooLarge>());
+
+        assert_eq!(
+            content_hash("abc"),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+    }
+
+    #[test]
+    fn test_process_epub_file() {
+        let path = crate::epub::tests::sample_epub();
+        let file = path.to_string_lossy().to_string();
+        let config = AppConfig {
+            chunk_size: 40,
+            chunk_overlap: 0,
+            ..Default::default()
+        };
+        let chunks = process_file(&config, &file).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert!(chunks.iter().all(|c| c.source_type == "epub"));
+        assert!(chunks.iter().all(|c| c.title == "The Field Guide & Notes"));
+        let sections: Vec<_> = chunks.iter().map(|c| c.section.as_deref()).collect();
+        // The short preface is one chunk; the longer chapter is split, and
+        // no chunk spans both.
+        assert_eq!(sections[0], Some("Preface"));
+        assert!(sections[1..].iter().all(|s| *s == Some("Getting started")));
+        assert!(sections.len() > 2);
+        assert!(chunks[0].text.ends_with("two"));
+    }
+
+    #[cfg(feature = "pdf-native")]
+    #[test]
+    fn test_process_pdf_file() {
+        let path = std::env::temp_dir().join(format!("ingest-{}.pdf", uuid::Uuid::new_v4()));
+        std::fs::write(&path, crate::pdf::tests::sample()).unwrap();
+        let file = path.to_string_lossy().to_string();
+        let config = AppConfig {
+            converter: "native".to_string(),
+            ..Default::default()
+        };
+        let chunks = process_file(&config, &file).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert!(chunks.iter().all(|c| c.source_type == "pdf"));
+        assert!(chunks.iter().all(|c| c.title == "Runbook"));
+        let sections: Vec<_> = chunks.iter().map(|c| c.section.as_deref()).collect();
+        assert_eq!(sections, vec![Some("Page 1"), Some("Page 2")]);
+        assert!(chunks[0].text.starts_with("Deployment runbook"));
+        assert!(chunks[0].location.converted);
+        assert_eq!(chunks[0].converter.as_deref(), Some("native"));
+        assert_eq!(chunks[1].location.start_line, Some(3));
+
+        let encrypted = crate::pdf::tests::build(&[], "/Encrypt << /V 2 >>");
+        std::fs::write(&path, encrypted).unwrap();
+        let err = process_file(&config, &file).unwrap_err();
+        std::fs::remove_file(&path).ok();
+        assert!(is_skip(&err));
+        assert!(err.to_string().contains("encrypted"), "{}", err);
+    }
+
+    #[test]
+    fn test_process_ocr() {
+        let dir = std::env::temp_dir().join(format!("ingest-ocr-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Stands in for tesseract: "recognizes" an image's bytes as its text,
+        // and pretends every PDF holds a two-page scan.
+        let config = AppConfig {
+            ocr_command: Some(
+                r#"sh -c 'case "$0" in *.pdf) printf "Scanned invoice\fTotal due 120";; *) cat "$0";; esac' {path}"#
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+        let image = dir.join("whiteboard.png");
+        std::fs::write(&image, "Sprint goals\n\nShip the importer.").unwrap();
+        let image = image.to_string_lossy().to_string();
+        assert_eq!(file_kind(&AppConfig::default(), "png"), None);
+
+        let chunks = process_file(&config, &image).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].title, "whiteboard.png");
+        assert_eq!(chunks[0].source_type, "png");
+        assert_eq!(chunks[0].text, "Sprint goals\n\nShip the importer.");
+        assert_eq!(chunks[0].converter.as_deref(), Some("sh"));
+        assert!(chunks[0].ocr && chunks[0].location.converted);
+
+        let mut recognized =
+            recognize_images(&config, &[image.clone(), "notes.md".to_string()]).unwrap();
+        assert_eq!(recognized.len(), 1);
+        let prefetched = process_file_with(&config, &image, recognized.remove(&image)).unwrap();
+        assert_eq!(prefetched[0].text, chunks[0].text);
+
+        #[cfg(feature = "pdf-native")]
+        {
+            let scan = dir.join("invoice.pdf");
+            let pdf = crate::pdf::tests::build(
+                &[
+                    b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+                    b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+                    b"<< /Type /Page /Parent 2 0 R >>".to_vec(),
+                ],
+                "",
+            );
+            std::fs::write(&scan, pdf).unwrap();
+            let native = AppConfig {
+                converter: "native".to_string(),
+                ..config.clone()
+            };
+            let chunks = process_file(&native, &scan.to_string_lossy()).unwrap();
+            let sections: Vec<_> = chunks.iter().map(|c| c.section.as_deref()).collect();
+            assert_eq!(sections, vec![Some("Page 1"), Some("Page 2")]);
+            assert_eq!(chunks[0].title, "Scanned invoice");
+            assert!(chunks.iter().all(|c| c.ocr && c.source_type == "pdf"));
+
+            // Without OCR, a scan is still skipped.
+            let no_ocr = AppConfig {
+                ocr_command: None,
+                ..native
+            };
+            let err = process_file(&no_ocr, &scan.to_string_lossy()).unwrap_err();
+            assert!(is_skip(&err));
+        }
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_process_office_files() {
+        let dir = std::env::temp_dir().join(format!("ingest-office-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let docx = dir.join("runbook.docx");
+        std::fs::write(&docx, crate::office::tests::sample_docx()).unwrap();
+        let xlsx = dir.join("invoices.xlsx");
+        std::fs::write(&xlsx, crate::office::tests::sample_xlsx()).unwrap();
+        let config = AppConfig {
+            converter: "native".to_string(),
+            chunking_strategy: "markdown".to_string(),
+            max_sheet_rows: 2,
+            ..Default::default()
+        };
+
+        let chunks = process_file(&config, &docx.to_string_lossy()).unwrap();
+        assert!(chunks.iter().all(|c| c.title == "Release & Rollback"));
+        assert!(chunks
+            .iter()
+            .all(|c| c.converter.as_deref() == Some("native")));
+        assert!(chunks.iter().all(|c| c.location.converted));
+        let steps = chunks.iter().find(|c| c.text.contains("Drain")).unwrap();
+        assert_eq!(steps.section.as_deref(), Some("Steps"));
+
+        let chunks = process_file(&config, &xlsx.to_string_lossy()).unwrap();
+        let text: String = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert!(text.contains("| 東京支店 | TRUE | 1200.5 |"), "{}", text);
+        assert!(text.contains("3 rows, first 2 shown"), "{}", text);
+        assert_eq!(chunks[0].source_type, "xlsx");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_process_external_converter() {
+        let dir = std::env::temp_dir().join(format!("ingest-convert-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let drawing = dir.join("plan.dwg");
+        std::fs::write(&drawing, "# Floor plan\n\nFire exits on level 2.").unwrap();
+        let notes = dir.join("notes.md");
+        std::fs::write(&notes, "# Notes\n\nRead as is.").unwrap();
+        let config = AppConfig {
+            converters: vec![mcp_hybrid_search_common::config::ConverterConfig {
+                extensions: vec!["dwg".to_string(), "MD".to_string()],
+                command: "cat {path}".to_string(),
+                timeout_secs: 10,
+                env: Default::default(),
+            }],
+            ..Default::default()
+        };
+        // A configured converter goes before the built-in kinds.
+        assert_eq!(file_kind(&config, "md"), Some(FileKind::External));
+        assert!(check_file_type(&config, &drawing, false).is_ok());
+
+        let chunks = process_file(&config, &drawing.to_string_lossy()).unwrap();
+        assert_eq!(chunks[0].title, "Floor plan");
+        assert_eq!(chunks[0].source_type, "dwg");
+        assert!(chunks
+            .iter()
+            .all(|c| c.converter.as_deref() == Some("cat") && c.location.converted));
+
+        let failing = AppConfig {
+            converters: vec![mcp_hybrid_search_common::config::ConverterConfig {
+                command: "sh -c 'exit 1'".to_string(),
+                ..config.converters[0].clone()
+            }],
+            ..Default::default()
+        };
+        let err = process_file(&failing, &drawing.to_string_lossy()).unwrap_err();
+        assert!(!is_skip(&err));
+        assert!(err.to_string().contains("sh failed for"), "{}", err);
+
+        let files = collect_files(
+            &AppConfig::default(),
+            &[dir.to_string_lossy().to_string()],
+            false,
+            &options(),
+        )
+        .unwrap();
+        assert_eq!(files.len(), 1, "{:?}", files);
+        let err = check_file_type(&config, &dir.join("plan.one"), false).unwrap_err();
+        assert!(err.to_string().contains("dwg, MD"), "{}", err);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_process_legacy_encoding() {
+        let dir = std::env::temp_dir().join(format!("ingest-encoding-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = AppConfig::default();
+        let write = |name: &str, bytes: &[u8]| {
+            let path = dir.join(name);
+            std::fs::write(&path, bytes).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+
+        let (sjis, _, _) = encoding_rs::SHIFT_JIS
+            .encode("# 運用手順書\n\nこの手順書はバックアップの取得方法を説明します。");
+        let chunks = process_file(&config, &write("runbook.md", &sjis)).unwrap();
+        assert_eq!(chunks[0].title, "運用手順書");
+        assert!(chunks[0].text.contains("バックアップ"));
+        assert_eq!(chunks[0].encoding.as_deref(), Some("Shift_JIS"));
+
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain(
+                "# Release notes\n\nVersion 2 drops the legacy API."
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes),
+            )
+            .collect();
+        let chunks = process_file(&config, &write("notes.md", &utf16)).unwrap();
+        assert_eq!(chunks[0].title, "Release notes");
+        assert_eq!(chunks[0].encoding.as_deref(), Some("UTF-16LE"));
+
+        // A forced encoding decodes what detection would get wrong.
+        let (euc_kr, _, _) = encoding_rs::EUC_KR.encode("안내");
+        let path = write("short.txt", &euc_kr);
+        let forced = AppConfig {
+            ingest_default_encoding: Some("euc-kr".to_string()),
+            ..AppConfig::default()
+        };
+        let chunks = process_file(&forced, &path).unwrap();
+        assert_eq!(chunks[0].text, "안내");
+        assert_eq!(chunks[0].encoding.as_deref(), Some("EUC-KR"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_process_binary_file() {
+        let dir = std::env::temp_dir().join(format!("ingest-binary-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dump = dir.join("export.txt");
+        std::fs::write(&dump, [0u8, 1, 2, 0, 0, 3, 0x1f, 7].repeat(64)).unwrap();
+        let config = AppConfig::default();
+
+        let err = process_file(&config, &dump.to_string_lossy()).unwrap_err();
+        assert!(is_skip(&err));
+        assert!(err.to_string().contains("file looks binary"), "{}", err);
+        let allowed = AppConfig {
+            allow_binary: true,
+            ..AppConfig::default()
+        };
+        assert!(process_file(&allowed, &dump.to_string_lossy()).is_ok());
+
+        // UTF-16 text is decoded rather than taken for binary.
+        let notes = dir.join("notes.txt");
+        let utf16: Vec<u8> = [0xFEFFu16]
+            .into_iter()
+            .chain("Backup runs nightly at two.".encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        std::fs::write(&notes, utf16).unwrap();
+        let chunks = process_file(&config, &notes.to_string_lossy()).unwrap();
+        assert_eq!(chunks[0].text, "Backup runs nightly at two.");
+
+        // Converter output is checked too.
+        let converting = AppConfig {
+            converters: vec![mcp_hybrid_search_common::config::ConverterConfig {
+                extensions: vec!["bin".to_string()],
+                command: "head -c 512 /dev/urandom".to_string(),
+                timeout_secs: 10,
+                env: Default::default(),
+            }],
+            ..AppConfig::default()
+        };
+        let blob = dir.join("blob.bin");
+        std::fs::write(&blob, "x").unwrap();
+        let err = process_file(&converting, &blob.to_string_lossy()).unwrap_err();
+        assert!(
+            err.to_string().contains("converted text looks binary"),
+            "{}",
+            err
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_resolve_converter() {
+        let converter = |value: &str, markitdown_available| {
+            let config = AppConfig {
+                converter: value.to_string(),
+                ..Default::default()
+            };
+            resolve_converter(&config, markitdown_available).map(|c| c.converter)
+        };
+        assert_eq!(converter("auto", true).unwrap(), "markitdown");
+        assert_eq!(converter("auto", false).unwrap(), "native");
+        assert_eq!(converter("markitdown", false).unwrap(), "markitdown");
+        assert_eq!(converter("native", true).unwrap(), "native");
+        assert!(converter("pdftotext", true).is_err());
+
+        let native = resolve_converter(&AppConfig::default(), false).unwrap();
+        assert_eq!(
+            file_kind(&native, "pdf"),
+            Some(if cfg!(feature = "pdf-native") {
+                FileKind::Pdf
+            } else {
+                FileKind::Markitdown
+            })
+        );
+        assert_eq!(file_kind(&native, "docx"), Some(FileKind::Docx));
+        assert_eq!(file_kind(&native, "xlsx"), Some(FileKind::Xlsx));
+        // No built-in reader for these.
+        assert_eq!(file_kind(&native, "pptx"), Some(FileKind::Markitdown));
+        assert_eq!(file_kind(&native, "xls"), Some(FileKind::Markitdown));
+        let markitdown = resolve_converter(&AppConfig::default(), true).unwrap();
+        assert_eq!(file_kind(&markitdown, "docx"), Some(FileKind::Markitdown));
+    }
+
+    #[test]
+    fn test_deleted_files() {
+        let dir = std::env::temp_dir().join(format!("ingest-prune-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let kept = dir.join("kept.md");
+        std::fs::write(&kept, "# Kept").unwrap();
+        let gone = dir.join("gone.md").to_string_lossy().to_string();
+
+        let mut state = IngestState::new();
+        state.insert(kept.to_string_lossy().to_string(), entry("t1"));
+        state.insert(gone.clone(), entry("t2"));
+        state.insert("https://wiki.example.com/page".to_string(), entry("t3"));
+        state.insert(
+            "git@example.com:org/repo:gone.md".to_string(),
+            StateEntry {
+                commit: Some("0123456789ab".to_string()),
+                blob: Some("f00d".to_string()),
+                ..entry("t4")
+            },
+        );
+        state.insert(
+            "virtual/report.md".to_string(),
+            StateEntry {
+                content_hash: Some(content_hash("report")),
+                ..entry("t5")
+            },
+        );
+        assert_eq!(deleted_files(&state), vec![gone]);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_plan_git() {
+        let root = std::env::temp_dir().join(format!("ingest-git-{}", uuid::Uuid::new_v4()));
+        let origin = root.join("origin");
+        git::tests::commit(
+            &origin,
+            &[
+                ("README.md", Some("# Repo")),
+                ("docs/a.md", Some("A")),
+                ("src/lib.rs", Some("fn a() {}")),
+                ("logo.bin", Some("")),
+            ],
+        );
+        let repo = git::Repo {
+            url: format!("file://{}", origin.display()),
+            branch: None,
+        };
+        let plan = |state: &IngestState, options: &IngestOptions| {
+            let checkout = git::sync(&root.join("cache"), &repo).unwrap();
+            let dir = std::fs::canonicalize(&checkout.dir).unwrap();
+            let files = collect_files(
+                &AppConfig::default(),
+                &[dir.to_string_lossy().to_string()],
+                false,
+                options,
+            )
+            .unwrap();
+            let blobs = git::blob_hashes(&dir).unwrap();
+            (
+                checkout.commit,
+                plan_git(
+                    state,
+                    repo.name(),
+                    &dir,
+                    &files,
+                    &blobs,
+                    &HashSet::new(),
+                    options,
+                ),
+            )
+        };
+
+        let mut state = IngestState::new();
+        state.insert("/docs/local.md".to_string(), entry("t1"));
+        let (commit, first) = plan(&state, &options());
+        let mut keys: Vec<&str> = first.changed.iter().map(|c| c.key.as_str()).collect();
+        keys.sort();
+        let key = |path: &str| format!("{}:{}", repo.name(), path);
+        assert_eq!(
+            keys,
+            vec![key("README.md"), key("docs/a.md"), key("src/lib.rs")]
+        );
+        assert!(first.removed.is_empty());
+
+        for change in &first.changed {
+            state.insert(
+                change.key.clone(),
+                StateEntry {
+                    commit: Some(commit.clone()),
+                    blob: Some(change.blob.clone()),
+                    ..entry("t2")
+                },
+            );
+        }
+        assert_eq!(
+            state[&key("docs/a.md")].source_path(&key("docs/a.md")),
+            format!("{}:docs/a.md@{}", repo.name(), commit)
+        );
+
+        git::tests::commit(&origin, &[("docs/a.md", Some("B")), ("README.md", None)]);
+        let (_, second) = plan(&state, &options());
+        assert_eq!(second.changed.len(), 1);
+        assert_eq!(second.changed[0].key, key("docs/a.md"));
+        assert_eq!(second.unchanged, 1);
+        assert_eq!(second.removed, vec![key("README.md")]);
+
+        let forced = IngestOptions {
+            force: true,
+            ..options()
+        };
+        assert_eq!(plan(&state, &forced).1.changed.len(), 2);
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_source_roots() {
+        let dir = std::env::temp_dir().join(format!("ingest-roots-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("api").join("ref")).unwrap();
+        std::fs::write(dir.join("api").join("ref").join("users.md"), "# Users").unwrap();
+        std::fs::write(dir.join("api").join("notes.txt"), "notes").unwrap();
+        std::fs::write(dir.join("design.md"), "# Design").unwrap();
+        let source = |path: &Path, chunk_size, include: &[&str]| SourceConfig {
+            path: path.to_string_lossy().to_string(),
+            urls: Vec::new(),
+            chunk_size,
+            chunk_overlap: None,
+            include: include.iter().map(|p| p.to_string()).collect(),
+        };
+        let config = AppConfig {
+            sources: vec![
+                source(&dir, Some(2000), &[]),
+                source(&dir.join("api"), Some(500), &["*.md"]),
+            ],
+            ..AppConfig::default()
+        };
+
+        let mut files = collect_files(
+            &config,
+            &[dir.to_string_lossy().to_string()],
+            false,
+            &options(),
+        )
+        .unwrap();
+        files.sort();
+        let root = std::fs::canonicalize(&dir).unwrap();
+        let relative: Vec<_> = files
+            .iter()
+            .map(|f| relative_path(Path::new(f), &root).unwrap())
+            .collect();
+        assert_eq!(relative, vec!["api/ref/users.md", "design.md"]);
+
+        let roots = SourceRoots::new(&config);
+        assert_eq!(roots.config_for(&files[0], &config).chunk_size, 500);
+        assert_eq!(roots.config_for(&files[1], &config).chunk_size, 2000);
+        assert_eq!(roots.config_for(&files[1], &config).chunk_overlap, 200);
+        assert_eq!(
+            roots.config_for("/elsewhere/a.md", &config).chunk_size,
+            1000
+        );
+
+        let recorded = StateEntry::new(roots.config_for(&files[0], &config), "t1".into());
+        assert!(recorded.chunked_with(roots.config_for(&files[0], &config)));
+        assert!(!recorded.chunked_with(roots.config_for(&files[1], &config)));
+        let legacy: StateEntry = serde_json::from_str("\"t1\"").unwrap();
+        assert!(legacy.chunked_with(&config));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_collect_files_uses_absolute_paths() {
+        let dir = std::env::temp_dir().join(format!("ingest-collect-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub").join("a.md"), "# A").unwrap();
+        std::fs::write(dir.join("sub").join("b.bin"), "").unwrap();
+
+        let source = dir.join("sub").join("..").to_string_lossy().to_string();
+        let files = collect_files(&AppConfig::default(), &[source], false, &options()).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(Path::new(&files[0]).is_absolute());
+        assert!(!files[0].contains(".."));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_watch_source() {
+        let dir = std::env::temp_dir().join(format!("ingest-notify-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = new_debouncer(Duration::from_millis(100), tx).unwrap();
+        watch_source(watcher.watcher(), &dir.to_string_lossy());
+
+        let file = dir.join("sub").join("new.md");
+        std::fs::write(&file, "# New").unwrap();
+        let events = rx.recv_timeout(Duration::from_secs(10)).unwrap().unwrap();
+        assert!(
+            events.iter().any(|e| e.path.ends_with("sub/new.md")),
+            "{:?}",
+            events
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_pending_changes() {
+        let dir = std::env::temp_dir().join(format!("ingest-watch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let unchanged = dir.join("unchanged.md").to_string_lossy().to_string();
+        let created = dir.join("created.md").to_string_lossy().to_string();
+        let gone = dir.join("gone.md").to_string_lossy().to_string();
+        std::fs::write(&unchanged, "# Unchanged").unwrap();
+        std::fs::write(&created, "# Created").unwrap();
+
+        let mut state = IngestState::new();
+        state.insert(
+            unchanged.clone(),
+            entry(&file_modified_time(&unchanged).unwrap()),
+        );
+        state.insert(gone.clone(), entry("t1"));
+        let files = vec![unchanged, created.clone()];
+
+        let changes = pending_changes(&state, &files, true);
+        assert_eq!(
+            changes,
+            vec![
+                (created.clone(), file_modified_time(&created)),
+                (gone, None)
+            ]
+        );
+        assert_eq!(pending_changes(&state, &files, false).len(), 1);
+
+        std::fs::remove_dir_all(&dir).ok();
     }
 
     #[test]
@@ -0,0 +1,229 @@
+//! Language detection for the `language` chunk field.
+//!
+//! An offline detector in two steps. The script most letters are written in
+//! decides for scripts that mostly belong to one language: CJK text with
+//! kana is Japanese, without it Chinese; Hangul is Korean, Cyrillic Russian,
+//! and so on. Latin text is told apart by counting common function words
+//! ("the", "und", "les", ...). Text that is too short, or Latin text without
+//! enough of those words, is undetermined.
+
+/// ISO 639-2 code for an undetermined language.
+pub const UNDETERMINED: &str = "und";
+
+/// Characters of a document looked at; the start of a document is enough.
+pub const SAMPLE_CHARS: usize = 10_000;
+
+/// Letters (CJK characters weighing three) below which a text is
+/// undetermined.
+const MIN_LETTERS: usize = 20;
+
+/// Function words a Latin text must contain before its language is guessed.
+const MIN_WORD_HITS: usize = 3;
+
+/// Common function words of the Latin-script languages told apart, most
+/// frequent first.
+const FUNCTION_WORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "to", "is", "in", "that", "it", "for", "with", "was", "on", "are",
+            "this", "be", "not", "you", "have",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "und", "das", "ist", "nicht", "mit", "den", "von", "zu", "ein", "eine",
+            "sich", "auch", "auf", "für", "dem", "wird",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "des", "est", "une", "du", "que", "pour", "dans", "pas",
+            "qui", "sur", "au", "sont", "avec", "ce",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "la", "los", "las", "y", "es", "que", "en", "una", "por", "con", "para", "del",
+            "se", "está", "como", "pero", "su",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "di", "che", "e", "è", "per", "una", "non", "sono", "della", "con", "gli", "le",
+            "nel", "alla", "questo", "anche", "si",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "os", "que", "do", "da", "em", "um", "uma", "para", "com", "não", "é", "dos", "das",
+            "mais", "como", "são", "pelo", "também",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "het", "een", "en", "van", "is", "dat", "niet", "op", "te", "zijn", "voor", "met",
+            "ook", "er", "aan", "wordt", "bij", "naar",
+        ],
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Script {
+    Latin,
+    /// Han ideographs and kana together, told apart afterwards.
+    Cjk,
+    Hangul,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+}
+
+fn script(c: char) -> Option<Script> {
+    Some(match c {
+        'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' => Script::Latin,
+        '\u{3040}'..='\u{30FF}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' => Script::Cjk,
+        '\u{1100}'..='\u{11FF}' | '\u{AC00}'..='\u{D7AF}' => Script::Hangul,
+        '\u{0400}'..='\u{04FF}' => Script::Cyrillic,
+        '\u{0370}'..='\u{03FF}' => Script::Greek,
+        '\u{0600}'..='\u{06FF}' => Script::Arabic,
+        '\u{0590}'..='\u{05FF}' => Script::Hebrew,
+        '\u{0900}'..='\u{097F}' => Script::Devanagari,
+        '\u{0E00}'..='\u{0E7F}' => Script::Thai,
+        _ => return None,
+    })
+}
+
+fn is_kana(c: char) -> bool {
+    ('\u{3040}'..='\u{30FF}').contains(&c)
+}
+
+/// ISO 639-1 code of the language `text` is written in, or
+/// [`UNDETERMINED`]. Only the first [`SAMPLE_CHARS`] characters are read.
+pub fn detect(text: &str) -> &'static str {
+    let sample = || text.chars().take(SAMPLE_CHARS);
+    let mut counts: Vec<(Script, usize)> = Vec::new();
+    let mut kana = 0;
+    for c in sample() {
+        let Some(script) = script(c) else {
+            continue;
+        };
+        kana += usize::from(is_kana(c));
+        match counts.iter_mut().find(|(s, _)| *s == script) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((script, 1)),
+        }
+    }
+    // A CJK character carries about as much as a short Latin word, so a few
+    // English terms in Japanese prose don't outweigh it.
+    let weight = |(script, count): &(Script, usize)| match script {
+        Script::Cjk | Script::Hangul => count * 3,
+        _ => *count,
+    };
+    let Some(&best) = counts.iter().max_by_key(|entry| weight(entry)) else {
+        return UNDETERMINED;
+    };
+    if weight(&best) < MIN_LETTERS {
+        return UNDETERMINED;
+    }
+    let (script, count) = best;
+
+    match script {
+        // Japanese mixes kana into its kanji; Chinese has none.
+        Script::Cjk if kana * 10 >= count => "ja",
+        Script::Cjk => "zh",
+        Script::Hangul => "ko",
+        Script::Cyrillic => "ru",
+        Script::Greek => "el",
+        Script::Arabic => "ar",
+        Script::Hebrew => "he",
+        Script::Devanagari => "hi",
+        Script::Thai => "th",
+        Script::Latin => detect_latin(&sample().collect::<String>()),
+    }
+}
+
+/// The Latin-script language whose function words `text` uses most.
+fn detect_latin(text: &str) -> &'static str {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut best = (UNDETERMINED, 0);
+    for (language, function_words) in FUNCTION_WORDS {
+        let hits = words
+            .iter()
+            .filter(|w| function_words.contains(&w.as_str()))
+            .count();
+        if hits > best.1 {
+            best = (language, hits);
+        }
+    }
+    if best.1 < MIN_WORD_HITS {
+        UNDETERMINED
+    } else {
+        best.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let cases = [
+            (
+                "The deployment runbook explains how to roll back a release that is failing in production.",
+                "en",
+            ),
+            (
+                "Die Anleitung beschreibt, wie man ein fehlerhaftes Release auf die vorige Version zurücksetzt und das System prüft.",
+                "de",
+            ),
+            (
+                "Le guide explique comment revenir à la version précédente quand une mise en production échoue dans les tests.",
+                "fr",
+            ),
+            (
+                "La guía explica cómo volver a la versión anterior cuando el despliegue falla y los usuarios no pueden entrar.",
+                "es",
+            ),
+            (
+                "本番環境でリリースが失敗した場合は、Kubernetes のロールバック手順に従って前のバージョンに戻してください。",
+                "ja",
+            ),
+            ("如果生产环境中的发布失败，请按照回滚步骤恢复到以前的版本并检查日志。", "zh"),
+            ("배포가 실패하면 롤백 절차에 따라 이전 버전으로 되돌리고 로그를 확인하십시오.", "ko"),
+            (
+                "Если выпуск в рабочей среде не удался, верните предыдущую версию и проверьте журналы.",
+                "ru",
+            ),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(detect(text), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_undetermined() {
+        assert_eq!(detect(""), UNDETERMINED);
+        assert_eq!(detect("fn main() {}"), UNDETERMINED);
+        assert_eq!(detect("1234 5678 !!! ---"), UNDETERMINED);
+        // Latin letters, but no function words of any language.
+        assert_eq!(
+            detect("kubectl rollout undo deployment nginx revision latest"),
+            UNDETERMINED
+        );
+    }
+}
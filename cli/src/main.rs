@@ -1,12 +1,34 @@
+mod binary;
 mod chunker;
+mod convert;
 mod embedding;
+mod embedding_cache;
+mod encoding;
+mod epub;
+mod front_matter;
+mod git;
+mod gitignore;
+mod html;
 mod ingest;
+mod language;
+mod ocr;
+mod office;
+#[cfg(feature = "pdf-native")]
+mod pdf;
+mod progress;
 mod qdrant_client;
+mod rate_limit;
+mod snapshot;
+mod structured;
 mod tantivy_index;
+mod tokens;
+mod web;
+mod zip;
 
 use clap::{Parser, Subcommand};
 use mcp_hybrid_search_common::config::AppConfig;
-use tracing_subscriber::EnvFilter;
+use mcp_hybrid_search_common::logging;
+use mcp_hybrid_search_common::types::{SearchFilters, VectorSearchParams};
 
 #[derive(Parser)]
 #[command(name = "ragctl", about = "CLI indexer for mcp-server-hybrid-search")]
@@ -19,10 +41,16 @@ struct Cli {
     #[arg(long, global = true)]
     project: Option<String>,
 
+    /// Log format: "text" or "json" (overrides config)
+    #[arg(long, global = true)]
+    log_format: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+// Parsed once per run, so the size of the Ingest variant doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize the default source directory and data directories
@@ -30,11 +58,44 @@ enum Commands {
 
     /// Ingest documents from source directories
     Ingest {
-        /// Source directories (can be specified multiple times).
+        /// Source directories or files (can be specified multiple times).
         /// Defaults to ~/.local/share/mcp-hybrid-search if omitted.
         #[arg(long = "source")]
         sources: Vec<String>,
 
+        /// Web page to fetch and index (can be specified multiple times).
+        /// Replaces the config's `[[sources]]` urls; without --source, no
+        /// directory is ingested
+        #[arg(long = "url")]
+        urls: Vec<String>,
+
+        /// Git repository to clone (or fetch) and index. Without --source,
+        /// no directory is ingested
+        #[arg(long)]
+        git: Option<String>,
+
+        /// Branch of the --git repository (defaults to the remote's default
+        /// branch)
+        #[arg(long, requires = "git")]
+        branch: Option<String>,
+
+        /// Read one document from stdin and index it under --path
+        #[arg(
+            long,
+            requires = "path",
+            conflicts_with_all = ["sources", "urls", "git", "watch"]
+        )]
+        stdin: bool,
+
+        /// Source path for the --stdin document. Ingesting the same path
+        /// again replaces its chunks
+        #[arg(long, requires = "stdin")]
+        path: Option<String>,
+
+        /// Title for the --stdin document (defaults to its first heading)
+        #[arg(long, requires = "stdin")]
+        title: Option<String>,
+
         /// Qdrant URL (overrides config)
         #[arg(long)]
         qdrant: Option<String>,
@@ -44,15 +105,96 @@ enum Commands {
         index_dir: Option<String>,
 
         /// Chunk size in characters
-        #[arg(long)]
+        #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
         chunk_size: Option<usize>,
 
         /// Chunk overlap in characters
         #[arg(long)]
         chunk_overlap: Option<usize>,
+
+        /// Skip files larger than this many bytes (overrides config)
+        #[arg(long)]
+        max_file_size: Option<u64>,
+
+        /// Index files that look binary instead of skipping them
+        #[arg(long)]
+        allow_binary: bool,
+
+        /// Reprocess all files, even unchanged ones
+        #[arg(long)]
+        force: bool,
+
+        /// Reprocess the files under this path, even unchanged ones (can be
+        /// specified multiple times)
+        #[arg(long)]
+        force_path: Vec<String>,
+
+        /// Keep the chunks of source files that were deleted since the last run
+        #[arg(long)]
+        no_prune: bool,
+
+        /// Include hidden files and directories (names starting with a dot)
+        #[arg(long)]
+        hidden: bool,
+
+        /// Don't honor .gitignore and .ignore files
+        #[arg(long)]
+        no_ignore: bool,
+
+        /// Follow symlinks while walking source directories
+        #[arg(long)]
+        follow_symlinks: bool,
+
+        /// Don't descend more than this many directories below a source
+        /// directory (overrides config)
+        #[arg(long)]
+        max_depth: Option<usize>,
+
+        /// Log progress periodically instead of drawing a progress line
+        #[arg(long)]
+        no_progress: bool,
+
+        /// Write the ingest summary to this JSON file
+        #[arg(long)]
+        report: Option<String>,
+
+        /// Stop at the first file, URL or batch that fails
+        #[arg(long)]
+        fail_fast: bool,
+
+        /// Don't check embedding_dimension against the Qdrant collection
+        /// and the embedding provider before ingesting
+        #[arg(long)]
+        skip_dimension_check: bool,
+
+        /// Keep running and re-ingest files as they change (stop with ctrl-c)
+        #[arg(long)]
+        watch: bool,
+
+        /// Quiet period in milliseconds before re-ingesting in watch mode
+        /// (overrides config)
+        #[arg(long)]
+        debounce_ms: Option<u64>,
+    },
+    /// Ingest a single file (shorthand for `ingest --source <path> --no-prune`)
+    IngestFile {
+        /// File to ingest
+        path: String,
+
+        /// Qdrant URL (overrides config)
+        #[arg(long)]
+        qdrant: Option<String>,
+
+        /// Tantivy index directory (overrides config)
+        #[arg(long)]
+        index_dir: Option<String>,
     },
     /// Show index status
     Status {
+        /// Also count the Qdrant points of each source type
+        #[arg(long)]
+        detailed: bool,
+
         /// Qdrant URL (overrides config)
         #[arg(long)]
         qdrant: Option<String>,
@@ -75,6 +217,11 @@ enum Commands {
         #[arg(long)]
         force: bool,
     },
+    /// Inspect or clear the embedding cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
     /// Export all indexed data (chunks + embeddings) to a JSON file
     Export {
         /// Output file path
@@ -85,6 +232,11 @@ enum Commands {
         #[arg(long)]
         qdrant: Option<String>,
     },
+    /// Back up or restore the project with Qdrant's snapshots
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommand,
+    },
     /// Import data from an exported JSON file
     Import {
         /// Input file path
@@ -115,6 +267,30 @@ enum Commands {
         #[arg(long, default_value = "10")]
         top_k: usize,
 
+        /// Only match documents with this front matter tag (can be
+        /// specified multiple times; all must match)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Print how long each search phase took
+        #[arg(long)]
+        verbose: bool,
+
+        /// Match the query against chunk titles instead of their text, in
+        /// collections with title vectors
+        #[arg(long)]
+        titles: bool,
+
+        /// Compare the query with every vector instead of using the HNSW
+        /// index, to check recall (overrides config)
+        #[arg(long)]
+        exact: bool,
+
+        /// Candidates kept while searching the HNSW index; higher is more
+        /// accurate and slower (overrides config; ignored with --exact)
+        #[arg(long)]
+        hnsw_ef: Option<u64>,
+
         /// Qdrant URL (overrides config)
         #[arg(long)]
         qdrant: Option<String>,
@@ -125,18 +301,72 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum SnapshotCommand {
+    /// Snapshot the Qdrant collection, Tantivy index and ingest state
+    Create {
+        /// Directory to write the snapshot to (default: `snapshots` next to
+        /// the ingest state)
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+
+        /// Leave the Qdrant snapshot on the server instead of downloading it
+        #[arg(long)]
+        server_side: bool,
+
+        /// Qdrant URL (overrides config)
+        #[arg(long)]
+        qdrant: Option<String>,
+
+        /// Tantivy index directory (overrides config)
+        #[arg(long)]
+        index_dir: Option<String>,
+    },
+    /// Replace the Qdrant collection, Tantivy index and ingest state with a
+    /// snapshot
+    Restore {
+        /// The snapshot's manifest (.json), or its .snapshot or
+        /// .local.tar.gz next to it
+        file: std::path::PathBuf,
+
+        /// Qdrant's snapshot directory on the server, for snapshots made
+        /// with --server-side
+        #[arg(long, default_value = snapshot::DEFAULT_SERVER_DIR)]
+        server_dir: String,
+
+        /// Skip confirmation prompt
+        #[arg(long)]
+        force: bool,
+
+        /// Qdrant URL (overrides config)
+        #[arg(long)]
+        qdrant: Option<String>,
+
+        /// Tantivy index directory (overrides config)
+        #[arg(long)]
+        index_dir: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Show how many embeddings are cached and the cache's size
+    Stats,
+    /// Delete all cached embeddings
+    Clear,
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().ok();
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .init();
-
     let cli = Cli::parse();
     let mut config = AppConfig::load(cli.config.as_deref())?;
+    if let Some(log_format) = cli.log_format {
+        config.log_format = log_format;
+    }
+    logging::init(&config.log_format, "info", std::io::stdout)?;
+
     config = config.with_project(cli.project.as_deref());
 
     match cli.command {
@@ -145,10 +375,31 @@ async fn main() -> anyhow::Result<()> {
         }
         Commands::Ingest {
             sources,
+            urls,
+            git,
+            branch,
+            stdin,
+            path,
+            title,
             qdrant,
             index_dir,
             chunk_size,
             chunk_overlap,
+            max_file_size,
+            allow_binary,
+            force,
+            force_path,
+            no_prune,
+            hidden,
+            no_ignore,
+            follow_symlinks,
+            max_depth,
+            no_progress,
+            report,
+            fail_fast,
+            skip_dimension_check,
+            watch,
+            debounce_ms,
         } => {
             if let Some(url) = qdrant {
                 config.qdrant_url = url;
@@ -162,9 +413,96 @@ async fn main() -> anyhow::Result<()> {
             if let Some(overlap) = chunk_overlap {
                 config.chunk_overlap = overlap;
             }
+            if let Some(bytes) = max_file_size {
+                config.max_file_size_bytes = bytes;
+            }
+            if let Some(ms) = debounce_ms {
+                config.watch_debounce_ms = ms;
+            }
+            if allow_binary {
+                config.allow_binary = true;
+            }
+            if follow_symlinks {
+                config.follow_symlinks = true;
+            }
+            if max_depth.is_some() {
+                config.max_depth = max_depth;
+            }
+
+            let sources = if let Some(path) = path.filter(|_| stdin) {
+                ingest::Sources {
+                    stdin: Some(ingest::Piped {
+                        path,
+                        title,
+                        text: read_stdin()?,
+                    }),
+                    ..ingest::Sources::default()
+                }
+            } else if urls.is_empty() && git.is_none() {
+                ingest::Sources {
+                    paths: resolve_sources(sources, &config),
+                    urls: config.source_urls(),
+                    ..ingest::Sources::default()
+                }
+            } else {
+                ingest::Sources {
+                    paths: sources,
+                    urls,
+                    git: git.map(|url| git::Repo { url, branch }),
+                    stdin: None,
+                }
+            };
+            if let Some(url) = sources.urls.iter().find(|url| !web::is_url(url)) {
+                anyhow::bail!("Not an http(s) URL: {}", url);
+            }
+            let options = ingest::IngestOptions {
+                prune: !no_prune,
+                hidden,
+                no_ignore,
+                progress: !no_progress,
+                report,
+                force,
+                force_paths: ingest::IngestOptions::canonical(&force_path),
+                fail_fast,
+                skip_dimension_check,
+            };
+            if watch {
+                ingest::run_watch(&config, &sources, &options).await?;
+            } else {
+                ingest::run_ingest(&config, &sources, &options).await?;
+            }
+        }
+        Commands::IngestFile {
+            path,
+            qdrant,
+            index_dir,
+        } => {
+            if let Some(url) = qdrant {
+                config.qdrant_url = url;
+            }
+            if let Some(dir) = index_dir {
+                config.tantivy_index_dir = dir;
+            }
+            if !std::path::Path::new(&path).is_file() {
+                anyhow::bail!("Not a file: {}", path);
+            }
 
-            let sources = resolve_sources(sources);
-            ingest::run_ingest(&config, &sources).await?;
+            let options = ingest::IngestOptions {
+                prune: false,
+                hidden: false,
+                no_ignore: false,
+                progress: true,
+                report: None,
+                force: false,
+                force_paths: Vec::new(),
+                fail_fast: false,
+                skip_dimension_check: false,
+            };
+            let sources = ingest::Sources {
+                paths: vec![path],
+                ..ingest::Sources::default()
+            };
+            ingest::run_ingest(&config, &sources, &options).await?;
         }
         Commands::Reset {
             qdrant,
@@ -179,14 +517,19 @@ async fn main() -> anyhow::Result<()> {
             }
             run_reset(&config, force).await?;
         }
-        Commands::Status { qdrant, index_dir } => {
+        Commands::Cache { command } => run_cache(&config, command)?,
+        Commands::Status {
+            detailed,
+            qdrant,
+            index_dir,
+        } => {
             if let Some(url) = qdrant {
                 config.qdrant_url = url;
             }
             if let Some(dir) = index_dir {
                 config.tantivy_index_dir = dir;
             }
-            run_status(&config).await?;
+            run_status(&config, detailed).await?;
         }
         Commands::Export { output, qdrant } => {
             if let Some(url) = qdrant {
@@ -194,6 +537,37 @@ async fn main() -> anyhow::Result<()> {
             }
             run_export(&config, &output).await?;
         }
+        Commands::Snapshot { command } => match command {
+            SnapshotCommand::Create {
+                output,
+                server_side,
+                qdrant,
+                index_dir,
+            } => {
+                if let Some(url) = qdrant {
+                    config.qdrant_url = url;
+                }
+                if let Some(dir) = index_dir {
+                    config.tantivy_index_dir = dir;
+                }
+                snapshot::run_create(&config, output, server_side).await?;
+            }
+            SnapshotCommand::Restore {
+                file,
+                server_dir,
+                force,
+                qdrant,
+                index_dir,
+            } => {
+                if let Some(url) = qdrant {
+                    config.qdrant_url = url;
+                }
+                if let Some(dir) = index_dir {
+                    config.tantivy_index_dir = dir;
+                }
+                snapshot::run_restore(&config, &file, &server_dir, force).await?;
+            }
+        },
         Commands::Import {
             input,
             qdrant,
@@ -216,6 +590,11 @@ async fn main() -> anyhow::Result<()> {
         Commands::Search {
             query,
             top_k,
+            tags,
+            verbose,
+            titles,
+            exact,
+            hnsw_ef,
             qdrant,
             index_dir,
         } => {
@@ -225,7 +604,15 @@ async fn main() -> anyhow::Result<()> {
             if let Some(dir) = index_dir {
                 config.tantivy_index_dir = dir;
             }
-            run_search(&config, &query, top_k).await?;
+            let filters = SearchFilters {
+                tags,
+                ..SearchFilters::default()
+            };
+            let params = VectorSearchParams {
+                exact: exact.then_some(true),
+                hnsw_ef,
+            };
+            run_search(&config, &query, top_k, &filters, verbose, titles, params).await?;
         }
     }
 
@@ -267,10 +654,7 @@ async fn run_reset(config: &AppConfig, force: bool) -> anyhow::Result<()> {
     }
 
     // Delete ingest state file
-    let state_parent = tantivy_path
-        .parent()
-        .unwrap_or_else(|| std::path::Path::new("."));
-    let state_file = state_parent.join("ingest_state.json");
+    let state_file = config.ingest_state_path();
     if state_file.exists() {
         std::fs::remove_file(&state_file)?;
         println!("Deleted ingest state file");
@@ -280,11 +664,63 @@ async fn run_reset(config: &AppConfig, force: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Resolve source directories. If none specified, use the default.
-fn resolve_sources(sources: Vec<String>) -> Vec<String> {
+/// Show or delete the embedding cache.
+fn run_cache(config: &AppConfig, command: CacheCommand) -> anyhow::Result<()> {
+    let path = config.embedding_cache_path();
+    if !path.exists() {
+        println!("No embedding cache at {}", path.display());
+        return Ok(());
+    }
+    let cache = embedding_cache::EmbeddingCache::open(&path);
+    match command {
+        CacheCommand::Stats => {
+            let stats = cache?.stats()?;
+            println!("Embedding cache: {}", path.display());
+            println!("  Entries: {}", stats.entries);
+            println!("  Size:    {:.1} MB", stats.bytes as f64 / 1_000_000.0);
+            if !config.embedding_cache {
+                println!("  (disabled by embedding_cache = false)");
+            }
+        }
+        CacheCommand::Clear => {
+            // A cache that can't be read is cleared all the same.
+            let entries = cache.and_then(|cache| cache.stats()).map(|s| s.entries);
+            std::fs::remove_file(&path)?;
+            match entries {
+                Ok(entries) => println!("Deleted embedding cache ({} entries)", entries),
+                Err(_) => println!("Deleted embedding cache at {}", path.display()),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The document piped into `ingest --stdin`.
+fn read_stdin() -> anyhow::Result<String> {
+    let mut text = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut text)
+        .map_err(|e| anyhow::anyhow!("Could not read stdin: {}", e))?;
+    if text.trim().is_empty() {
+        anyhow::bail!("Nothing to ingest: stdin is empty");
+    }
+    Ok(text)
+}
+
+/// Resolve source directories. If none specified, use the `[[sources]]`
+/// from the config, or else the default.
+fn resolve_sources(sources: Vec<String>, config: &AppConfig) -> Vec<String> {
     if !sources.is_empty() {
         return sources;
     }
+    let configured: Vec<String> = config
+        .sources
+        .iter()
+        .filter(|s| !s.path.is_empty())
+        .map(|s| s.path.clone())
+        .collect();
+    if !configured.is_empty() {
+        return configured;
+    }
 
     let default_dir = AppConfig::default_source_dir();
     let default_str = default_dir.to_string_lossy().to_string();
@@ -332,7 +768,7 @@ fn run_init(config: &AppConfig) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn run_status(config: &AppConfig) -> anyhow::Result<()> {
+async fn run_status(config: &AppConfig, detailed: bool) -> anyhow::Result<()> {
     println!("=== Index Status ===");
     println!(
         "Source directory: {}",
@@ -351,6 +787,32 @@ async fn run_status(config: &AppConfig) -> anyhow::Result<()> {
             println!("Qdrant: error - {}", e);
         }
     }
+    match qdrant_client::collection_settings(config).await {
+        Ok(Some(settings)) => {
+            println!("Qdrant settings: {}", settings);
+            let drift = qdrant_client::settings_drift(&config.qdrant, &settings);
+            if !drift.is_empty() {
+                let drift: Vec<String> = drift.iter().map(ToString::to_string).collect();
+                println!(
+                    "  differs from [qdrant]: {} (the next ingest updates it)",
+                    drift.join(", ")
+                );
+            }
+        }
+        Ok(None) => {}
+        Err(e) => {
+            println!("Qdrant settings: error - {}", e);
+        }
+    }
+    if let Ok(Some(layout)) = qdrant_client::vector_layout(config).await {
+        println!("Qdrant vectors: {}", layout);
+        if config.qdrant.title_vector && !layout.title {
+            println!("  [qdrant] title_vector is set but the collection has none");
+        }
+        if config.sparse_lexical() && !layout.sparse {
+            println!("  lexical_backend is qdrant-sparse but the collection has no sparse vector");
+        }
+    }
 
     // Tantivy status
     match tantivy_index::get_index_count(config) {
@@ -361,10 +823,55 @@ async fn run_status(config: &AppConfig) -> anyhow::Result<()> {
             println!("Tantivy: error - {}", e);
         }
     }
+    match tantivy_index::language_counts(config) {
+        Ok(counts) if !counts.is_empty() => {
+            let counts: Vec<String> = counts
+                .iter()
+                .map(|(language, count)| format!("{} {}", language, count))
+                .collect();
+            println!("Languages: {}", counts.join(", "));
+        }
+        Ok(_) => {}
+        Err(e) => {
+            println!("Languages: error - {}", e);
+        }
+    }
+    if detailed {
+        print_source_type_counts(config).await;
+    }
 
     Ok(())
 }
 
+/// The Qdrant points of each source type the Tantivy index has, next to
+/// Tantivy's documents, so a backend that missed some files stands out.
+async fn print_source_type_counts(config: &AppConfig) {
+    let types = match tantivy_index::source_type_counts(config) {
+        Ok(types) => types,
+        Err(e) => {
+            println!("Source types: error - {}", e);
+            return;
+        }
+    };
+    println!("Source types:");
+    for (source_type, documents) in types {
+        let filters = SearchFilters {
+            source_type: Some(source_type.clone()),
+            ..SearchFilters::default()
+        };
+        match qdrant_client::count_points(config, &filters).await {
+            Ok(points) if points == documents => {
+                println!("  {}: {} points", source_type, points)
+            }
+            Ok(points) => println!(
+                "  {}: {} points (Tantivy: {} documents)",
+                source_type, points, documents
+            ),
+            Err(e) => println!("  {}: error - {}", source_type, e),
+        }
+    }
+}
+
 async fn run_export(config: &AppConfig, output_path: &str) -> anyhow::Result<()> {
     println!("Exporting data from Qdrant...");
 
@@ -384,6 +891,7 @@ async fn run_export(config: &AppConfig, output_path: &str) -> anyhow::Result<()>
 
 async fn run_import(config: &AppConfig, input_path: &str) -> anyhow::Result<()> {
     use mcp_hybrid_search_common::types::ExportedChunk;
+    use std::collections::HashMap;
 
     let content = std::fs::read_to_string(input_path)?;
     let chunks: Vec<ExportedChunk> = serde_json::from_str(&content)?;
@@ -405,9 +913,13 @@ async fn run_import(config: &AppConfig, input_path: &str) -> anyhow::Result<()>
     for batch in chunks.chunks(batch_size) {
         let payloads: Vec<_> = batch.iter().map(|c| c.payload.clone()).collect();
         let embeddings: Vec<_> = batch.iter().map(|c| c.embedding.clone()).collect();
+        let titles: HashMap<_, _> = batch
+            .iter()
+            .filter_map(|c| Some((c.payload.title.clone(), c.title_embedding.clone()?)))
+            .collect();
 
         // Upsert to Qdrant
-        qdrant_client::upsert_chunks(config, &payloads, &embeddings).await?;
+        qdrant_client::upsert_chunks(config, &payloads, &embeddings, &titles).await?;
 
         // Index in Tantivy
         tantivy_index::index_chunks(config, &payloads)?;
@@ -439,29 +951,80 @@ async fn run_list_projects(config: &AppConfig) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn run_search(config: &AppConfig, query: &str, top_k: usize) -> anyhow::Result<()> {
-    use mcp_hybrid_search_common::types::SearchFilters;
+async fn run_search(
+    config: &AppConfig,
+    query: &str,
+    top_k: usize,
+    filters: &SearchFilters,
+    verbose: bool,
+    titles: bool,
+    params: VectorSearchParams,
+) -> anyhow::Result<()> {
+    use mcp_hybrid_search_common::types::SearchTimings;
+    use std::time::Instant;
+
+    let started = Instant::now();
+    let params = params.checked().resolve(config.qdrant.search_params());
+    let mut timings = SearchTimings {
+        search_params: params,
+        ..SearchTimings::default()
+    };
 
     // Get embedding for query
-    let query_embedding = embedding::get_embedding(config, query).await?;
-
-    // Vector search
-    let vector_results =
-        qdrant_client::search(config, &query_embedding, 30, &SearchFilters::default()).await?;
-
-    // BM25 search
-    let bm25_results = tantivy_index::search(config, query, 30, &SearchFilters::default())?;
-
-    // RRF fusion
-    let merged = crate::ingest::rrf_merge(&vector_results, &bm25_results, top_k);
+    let phase = Instant::now();
+    let query_embedding = embedding::get_query_embedding(config, query).await?;
+    timings.embed_ms = SearchTimings::since(phase);
+
+    let merged = if config.sparse_lexical() {
+        // Vector and BM25 search with their fusion, in Qdrant
+        let phase = Instant::now();
+        let merged = qdrant_client::hybrid_search(
+            config,
+            query,
+            &query_embedding,
+            top_k,
+            filters,
+            titles,
+            params,
+        )
+        .await?;
+        timings.vector_ms = SearchTimings::since(phase);
+        merged
+    } else {
+        // Vector search
+        let phase = Instant::now();
+        let vector_results =
+            qdrant_client::search(config, &query_embedding, 30, filters, titles, params).await?;
+        timings.vector_ms = SearchTimings::since(phase);
+
+        // BM25 search
+        let phase = Instant::now();
+        let bm25_results = tantivy_index::search(config, query, 30, filters)?;
+        timings.bm25_ms = SearchTimings::since(phase);
+
+        // RRF fusion
+        let phase = Instant::now();
+        let merged = crate::ingest::rrf_merge(&vector_results, &bm25_results, top_k);
+        timings.fuse_ms = SearchTimings::since(phase);
+        merged
+    };
+    timings.total_ms = SearchTimings::since(started);
+
+    if verbose {
+        println!("Timings: {}", timings);
+    }
 
     println!("=== Search Results ({} hits) ===", merged.len());
     for (i, r) in merged.iter().enumerate() {
+        let title = match &r.section {
+            Some(section) => format!("{} > {}", r.title, section),
+            None => r.title.clone(),
+        };
         println!(
             "\n[{}] score={:.4}  {}\n    {}\n    {}",
             i + 1,
             r.score,
-            r.title,
+            title,
             r.source_path,
             r.snippet
         );
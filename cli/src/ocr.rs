@@ -0,0 +1,115 @@
+//! OCR of images and scanned PDFs with the configured `ocr_command`.
+//!
+//! The command gets a file's path and prints the text it recognizes, e.g.
+//! `tesseract {path} stdout`; it runs like a `[[converters]]` command. Pages
+//! of a multi-page file separated by form feeds, as tesseract prints them,
+//! become the chunks' `Page N` sections.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use mcp_hybrid_search_common::config::AppConfig;
+
+use crate::chunker::PAGE_BREAK;
+use crate::convert::Converter;
+
+/// Image files read by OCR when `ocr_command` is set.
+pub const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "tif", "tiff"];
+
+/// A PDF whose text layer averages fewer non-whitespace characters per
+/// page is taken for a scan. Scans often carry a stamped page number or a
+/// scanner's header, so "no text at all" is too strict.
+const MIN_CHARS_PER_PAGE: usize = 20;
+
+/// The configured OCR command, or `None` when OCR is off.
+pub fn command(config: &AppConfig) -> Result<Option<Converter>> {
+    let Some(command) = config.ocr_command.as_deref() else {
+        return Ok(None);
+    };
+    let timeout = Duration::from_secs(config.ocr_timeout_secs);
+    Converter::new(command, timeout)
+        .context("Invalid ocr_command")
+        .map(Some)
+}
+
+pub fn is_image(ext: &str) -> bool {
+    IMAGE_EXTENSIONS.contains(&ext)
+}
+
+/// Whether the text extracted from a PDF, pages separated by
+/// [`PAGE_BREAK`], is too sparse to be anything but a scan.
+pub fn is_scanned(text: &str) -> bool {
+    let pages = text.split(PAGE_BREAK).count();
+    let chars = text.chars().filter(|c| !c.is_whitespace()).count();
+    chars < pages * MIN_CHARS_PER_PAGE
+}
+
+/// Run `command` on each of `files`, up to `concurrency` at a time, and
+/// return the text or error for each.
+pub fn run_all(
+    command: &Converter,
+    files: &[&str],
+    max_bytes: u64,
+    concurrency: usize,
+) -> HashMap<String, Result<String>> {
+    let pending = Mutex::new(files.iter());
+    let results = Mutex::new(HashMap::new());
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, files.len().max(1)) {
+            scope.spawn(|| loop {
+                let Some(file) = pending.lock().unwrap().next() else {
+                    break;
+                };
+                let text = command.run(file, max_bytes);
+                results.lock().unwrap().insert(file.to_string(), text);
+            });
+        }
+    });
+    results.into_inner().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_scanned() {
+        assert!(is_scanned(""));
+        assert!(is_scanned("  3 \u{c}\n4"));
+        assert!(!is_scanned(
+            "Quarterly report: revenue grew by four percent."
+        ));
+        // One page of text among blank ones still averages too little.
+        let mostly_blank = format!("{}\u{c}\u{c}\u{c}", "x".repeat(30));
+        assert!(is_scanned(&mostly_blank));
+    }
+
+    #[test]
+    fn test_run_all() {
+        let config = AppConfig {
+            ocr_command: Some(r#"sh -c 'sleep 0.3; printf "text of %s" "$0"' {path}"#.to_string()),
+            ..AppConfig::default()
+        };
+        let ocr = command(&config).unwrap().unwrap();
+        assert_eq!(ocr.name, "sh");
+
+        let files = ["a.png", "b.png", "c.png", "d.png"];
+        let started = std::time::Instant::now();
+        let results = run_all(&ocr, &files, 1024, 2);
+        // Two at a time: two rounds of 0.3s, not one or four.
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(600), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(1100), "{:?}", elapsed);
+        assert_eq!(results.len(), 4);
+        assert_eq!(results["c.png"].as_ref().unwrap(), "text of c.png");
+
+        assert!(command(&AppConfig::default()).unwrap().is_none());
+        let broken = AppConfig {
+            ocr_command: Some("tesseract 'stdout".to_string()),
+            ..AppConfig::default()
+        };
+        assert!(command(&broken).is_err());
+    }
+}
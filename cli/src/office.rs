@@ -0,0 +1,537 @@
+//! Reading Word (DOCX) and Excel (XLSX) files for ingest without markitdown
+//! (`converter = "native"`).
+//!
+//! Both are zip archives of XML parts, rendered as markdown the way
+//! markitdown would. A document's paragraphs become markdown paragraphs,
+//! with those in a heading style as `#` lines (so the title and the
+//! markdown chunker pick them up), list paragraphs as `-` items and tables
+//! as markdown tables. A workbook becomes one `## Sheet` section per sheet
+//! holding a table of its rows, the first row as the header. Cells hold
+//! their stored values: dates are serial numbers and formulas their last
+//! computed result.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::html::{decode_entities, tags};
+use crate::zip::Archive;
+
+/// Read the DOCX at `path` as markdown. Archive members that decompress to
+/// more than `max_entry_bytes` are an error.
+pub fn read_docx(path: &Path, max_entry_bytes: u64) -> Result<String> {
+    let archive = open(path, max_entry_bytes, "DOCX")?;
+    docx_markdown(&archive).with_context(|| format!("Could not read DOCX {}", path.display()))
+}
+
+/// Read the XLSX at `path` as markdown, keeping the first `max_rows` rows
+/// of each sheet. Archive members that decompress to more than
+/// `max_entry_bytes` are an error.
+pub fn read_xlsx(path: &Path, max_entry_bytes: u64, max_rows: usize) -> Result<String> {
+    let archive = open(path, max_entry_bytes, "XLSX")?;
+    xlsx_markdown(&archive, max_rows)
+        .with_context(|| format!("Could not read XLSX {}", path.display()))
+}
+
+fn open(path: &Path, max_entry_bytes: u64, format: &str) -> Result<Archive> {
+    let data = std::fs::read(path)?;
+    // Password-protected Office files are not zip archives at all.
+    Archive::open(data, max_entry_bytes)
+        .with_context(|| format!("{} is not a valid {} (zip) file", path.display(), format))
+}
+
+/// Heading levels of the styles in `word/styles.xml`, by style id. Ids are
+/// localized (`berschrift1` in German Word), so the level comes from the
+/// style's name (`heading 1`, `Title`) or its outline level.
+fn heading_styles(xml: &str) -> HashMap<String, usize> {
+    let mut levels = HashMap::new();
+    let mut style = None;
+    for tag in tags(xml) {
+        match (tag.name, tag.closing) {
+            ("style", false) => style = tag.attr("w:styleId"),
+            ("style", true) => style = None,
+            ("name" | "outlineLvl", false) => {
+                let (Some(id), Some(value)) = (&style, tag.attr("w:val")) else {
+                    continue;
+                };
+                let level = if tag.name == "outlineLvl" {
+                    value.parse::<usize>().ok().map(|l| l + 1)
+                } else {
+                    name_level(&value)
+                };
+                if let Some(level) = level.filter(|l| (1..=9).contains(l)) {
+                    levels.insert(id.clone(), level);
+                }
+            }
+            _ => {}
+        }
+    }
+    levels
+}
+
+/// The heading level of a style named `name` (or, without `styles.xml`,
+/// with this id): `heading 2` / `Heading2` is 2, `Title` 1.
+fn name_level(name: &str) -> Option<usize> {
+    let name = name.to_ascii_lowercase();
+    if name == "title" {
+        return Some(1);
+    }
+    name.strip_prefix("heading")?.trim().parse().ok()
+}
+
+#[derive(Default)]
+struct Paragraph {
+    text: String,
+    heading: Option<usize>,
+    list: bool,
+}
+
+/// A rendered block of a document, and whether it is a list item (list
+/// items are separated by a single newline).
+type Block = (String, bool);
+
+fn docx_markdown(archive: &Archive) -> Result<String> {
+    let styles = if archive.contains("word/styles.xml") {
+        heading_styles(&archive.read_text("word/styles.xml")?)
+    } else {
+        HashMap::new()
+    };
+    let xml = archive.read_text("word/document.xml")?;
+
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut paragraph: Option<Paragraph> = None;
+    // The table being read: its rows, and the row and cell being read.
+    // Nested tables are read into the cell of the outer one.
+    let mut table_depth = 0;
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut cell: Option<String> = None;
+    let (mut in_text, mut in_properties) = (false, false);
+    let mut text_start = 0;
+
+    for tag in tags(&xml) {
+        if in_text {
+            if let Some(paragraph) = &mut paragraph {
+                paragraph
+                    .text
+                    .push_str(&decode_entities(&xml[text_start..tag.start]));
+            }
+        }
+        text_start = tag.end;
+        let opening = !tag.closing;
+        match tag.name {
+            // Runs of text; deleted text (`delText`) and field codes
+            // (`instrText`) are left out.
+            "t" => in_text = opening && !tag.self_closing,
+            "p" if opening && !tag.self_closing => paragraph = Some(Paragraph::default()),
+            "p" if tag.closing => {
+                let Some(paragraph) = paragraph.take() else {
+                    continue;
+                };
+                match &mut cell {
+                    Some(cell) => {
+                        let text = paragraph.text.trim();
+                        if !text.is_empty() {
+                            if !cell.is_empty() {
+                                cell.push(' ');
+                            }
+                            cell.push_str(text);
+                        }
+                    }
+                    None => blocks.extend(render_paragraph(paragraph)),
+                }
+            }
+            // Tab stops are declared in the paragraph properties too.
+            "pPr" => in_properties = opening && !tag.self_closing,
+            "pStyle" if opening => {
+                if let (Some(paragraph), Some(id)) = (&mut paragraph, tag.attr("w:val")) {
+                    paragraph.heading = styles.get(&id).copied().or_else(|| name_level(&id));
+                }
+            }
+            "numPr" if opening => {
+                if let Some(paragraph) = &mut paragraph {
+                    paragraph.list = true;
+                }
+            }
+            "tab" if opening && !in_properties => {
+                if let Some(paragraph) = &mut paragraph {
+                    paragraph.text.push(' ');
+                }
+            }
+            "br" | "cr" if opening => {
+                if let Some(paragraph) = &mut paragraph {
+                    paragraph.text.push('\n');
+                }
+            }
+            "tbl" if opening => {
+                table_depth += 1;
+                if table_depth == 1 {
+                    rows.clear();
+                }
+            }
+            "tbl" if tag.closing => {
+                table_depth -= 1;
+                if table_depth == 0 {
+                    let table = markdown_table(&rows);
+                    if !table.is_empty() {
+                        blocks.push((table, false));
+                    }
+                }
+            }
+            "tr" if table_depth == 1 => {
+                if opening {
+                    row.clear();
+                } else {
+                    rows.push(std::mem::take(&mut row));
+                }
+            }
+            "tc" if table_depth == 1 => {
+                if opening {
+                    cell = Some(String::new());
+                } else {
+                    row.extend(cell.take());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut out = String::new();
+    let mut previous_list = false;
+    for (block, list) in blocks {
+        if !out.is_empty() {
+            out.push_str(if list && previous_list { "\n" } else { "\n\n" });
+        }
+        out.push_str(&block);
+        previous_list = list;
+    }
+    Ok(out)
+}
+
+fn render_paragraph(paragraph: Paragraph) -> Option<Block> {
+    let text = paragraph.text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some(match paragraph.heading {
+        Some(level) => (
+            format!("{} {}", "#".repeat(level.min(6)), text.replace('\n', " ")),
+            false,
+        ),
+        None if paragraph.list => (format!("- {}", text.replace('\n', " ")), true),
+        None => (text.to_string(), false),
+    })
+}
+
+/// `rows` as a markdown table, the first row as the header. Empty rows are
+/// dropped and short rows padded.
+fn markdown_table(rows: &[Vec<String>]) -> String {
+    let rows: Vec<&Vec<String>> = rows
+        .iter()
+        .filter(|row| row.iter().any(|cell| !cell.trim().is_empty()))
+        .collect();
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut lines = Vec::with_capacity(rows.len() + 1);
+    for (i, row) in rows.iter().enumerate() {
+        let cells: Vec<String> = (0..width)
+            .map(|c| {
+                let cell = row.get(c).map_or("", |cell| cell.trim());
+                cell.replace('|', "\\|").replace(['\r', '\n'], " ")
+            })
+            .collect();
+        lines.push(format!("| {} |", cells.join(" | ")));
+        if i == 0 {
+            lines.push(format!("|{}", " --- |".repeat(width)));
+        }
+    }
+    lines.join("\n")
+}
+
+fn xlsx_markdown(archive: &Archive, max_rows: usize) -> Result<String> {
+    let strings = if archive.contains("xl/sharedStrings.xml") {
+        shared_strings(&archive.read_text("xl/sharedStrings.xml")?)
+    } else {
+        Vec::new()
+    };
+    let targets = relationships(&archive.read_text("xl/_rels/workbook.xml.rels")?);
+    let workbook = archive.read_text("xl/workbook.xml")?;
+
+    let mut sections = Vec::new();
+    for sheet in tags(&workbook).filter(|t| t.name == "sheet" && !t.closing) {
+        let name = sheet.attr("name").unwrap_or_default();
+        let Some(target) = sheet.attr("r:id").and_then(|id| targets.get(&id)) else {
+            continue;
+        };
+        let part = match target.strip_prefix('/') {
+            Some(absolute) => absolute.to_string(),
+            None => format!("xl/{}", target),
+        };
+        let rows = sheet_rows(&archive.read_text(&part)?, &strings);
+        if rows.is_empty() {
+            continue;
+        }
+        let mut section = format!("## {}\n\n", name);
+        if rows.len() > max_rows {
+            section.push_str(&format!(
+                "{} rows, first {} shown\n\n",
+                rows.len(),
+                max_rows
+            ));
+        }
+        section.push_str(&markdown_table(&rows[..rows.len().min(max_rows)]));
+        sections.push(section);
+    }
+    Ok(sections.join("\n\n"))
+}
+
+/// Relationship targets of a `.rels` part, by id.
+fn relationships(xml: &str) -> HashMap<String, String> {
+    tags(xml)
+        .filter(|tag| tag.name == "Relationship" && !tag.closing)
+        .filter_map(|tag| Some((tag.attr("Id")?, tag.attr("Target")?)))
+        .collect()
+}
+
+/// The shared string table. Phonetic readings (`rPh`, the furigana of
+/// Japanese cells) are left out.
+fn shared_strings(xml: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut current = String::new();
+    let (mut in_text, mut in_phonetic) = (false, false);
+    let mut text_start = 0;
+    for tag in tags(xml) {
+        if in_text && !in_phonetic {
+            current.push_str(&decode_entities(&xml[text_start..tag.start]));
+        }
+        text_start = tag.end;
+        match tag.name {
+            "si" if tag.closing => strings.push(std::mem::take(&mut current)),
+            "si" => current.clear(),
+            "rPh" => in_phonetic = !tag.closing && !tag.self_closing,
+            "t" => in_text = !tag.closing && !tag.self_closing,
+            _ => {}
+        }
+    }
+    strings
+}
+
+/// The non-empty rows of a worksheet, each cell at its column.
+fn sheet_rows(xml: &str, strings: &[String]) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    // The cell being read: its column, type and value.
+    let mut column = 0;
+    let mut kind = None;
+    let mut value = String::new();
+    let mut in_value = false;
+    let mut in_phonetic = false;
+    let mut text_start = 0;
+    for tag in tags(xml) {
+        if in_value && !in_phonetic {
+            value.push_str(&decode_entities(&xml[text_start..tag.start]));
+        }
+        text_start = tag.end;
+        match (tag.name, tag.closing) {
+            ("row", false) => row.clear(),
+            ("row", true) => {
+                while row.last().is_some_and(|cell| cell.is_empty()) {
+                    row.pop();
+                }
+                if !row.is_empty() {
+                    rows.push(std::mem::take(&mut row));
+                }
+            }
+            ("c", false) => {
+                column = tag
+                    .attr("r")
+                    .and_then(|r| column_index(&r))
+                    .unwrap_or(row.len());
+                kind = tag.attr("t");
+                value.clear();
+            }
+            ("c", true) => {
+                let text = match kind.as_deref() {
+                    Some("s") => value
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| strings.get(i))
+                        .cloned()
+                        .unwrap_or_default(),
+                    Some("b") => match value.trim() {
+                        "1" => "TRUE".to_string(),
+                        _ => "FALSE".to_string(),
+                    },
+                    _ => value.clone(),
+                };
+                if !text.is_empty() {
+                    if row.len() <= column {
+                        row.resize(column + 1, String::new());
+                    }
+                    row[column] = text;
+                }
+            }
+            // Stored values and inline strings; formulas (`f`) are left out.
+            ("v" | "t", closing) => in_value = !closing && !tag.self_closing,
+            ("rPh", closing) => in_phonetic = !closing && !tag.self_closing,
+            _ => {}
+        }
+    }
+    rows
+}
+
+/// The 0-based column of a cell reference: `A1` is 0, `AB7` 27.
+fn column_index(reference: &str) -> Option<usize> {
+    let letters: Vec<u8> = reference
+        .bytes()
+        .take_while(u8::is_ascii_alphabetic)
+        .collect();
+    if letters.is_empty() || letters.len() > 3 {
+        return None;
+    }
+    let index = letters.iter().fold(0, |acc, letter| {
+        acc * 26 + usize::from(letter.to_ascii_uppercase() - b'A') + 1
+    });
+    Some(index - 1)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::zip::tests::zip;
+
+    const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+  <w:style w:type="paragraph" w:styleId="Titel"><w:name w:val="Title"/></w:style>
+  <w:style w:type="paragraph" w:styleId="berschrift1"><w:name w:val="heading 1"/></w:style>
+  <w:style w:type="paragraph" w:styleId="Custom"><w:name w:val="Section"/><w:pPr><w:outlineLvl w:val="1"/></w:pPr></w:style>
+</w:styles>"#;
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+<w:body>
+  <w:p><w:pPr><w:pStyle w:val="Titel"/></w:pPr><w:r><w:t>Release &amp; Rollback</w:t></w:r></w:p>
+  <w:p><w:r><w:t xml:space="preserve">Deploy on </w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t>Tuesdays</w:t></w:r><w:del><w:r><w:delText>Fridays</w:delText></w:r></w:del><w:r><w:t>.</w:t></w:r></w:p>
+  <w:p/>
+  <w:p><w:pPr><w:pStyle w:val="berschrift1"/><w:tabs><w:tab w:val="left" w:pos="720"/></w:tabs></w:pPr><w:r><w:t>Steps</w:t></w:r></w:p>
+  <w:p><w:pPr><w:numPr><w:ilvl w:val="0"/><w:numId w:val="1"/></w:numPr></w:pPr><w:r><w:t>Drain</w:t><w:tab/><w:t>traffic</w:t></w:r></w:p>
+  <w:p><w:pPr><w:numPr><w:ilvl w:val="0"/><w:numId w:val="1"/></w:numPr></w:pPr><w:r><w:t>Roll back</w:t></w:r></w:p>
+  <w:p><w:pPr><w:pStyle w:val="Custom"/></w:pPr><w:r><w:t>Contacts</w:t></w:r></w:p>
+  <w:tbl>
+    <w:tr><w:tc><w:p><w:r><w:t>Team</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>Channel</w:t></w:r></w:p></w:tc></w:tr>
+    <w:tr><w:tc><w:p><w:r><w:t>SRE</w:t></w:r></w:p><w:p><w:r><w:t>on call</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>#ops|alerts</w:t></w:r></w:p></w:tc></w:tr>
+  </w:tbl>
+  <w:p><w:r><w:t>Line one</w:t><w:br/><w:t>line two</w:t></w:r></w:p>
+  <w:sectPr/>
+</w:body>
+</w:document>"#;
+
+    /// A small DOCX document.
+    pub(crate) fn sample_docx() -> Vec<u8> {
+        zip(&[("word/styles.xml", STYLES), ("word/document.xml", DOCUMENT)])
+    }
+
+    const WORKBOOK: &str = r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+<sheets><sheet name="Invoices" sheetId="1" r:id="rId1"/><sheet name="Empty" sheetId="2" r:id="rId2"/><sheet name="Notes" sheetId="3" r:id="rId3"/></sheets></workbook>"#;
+
+    const RELS: &str = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/>
+<Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet2.xml"/>
+<Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="/xl/worksheets/sheet3.xml"/>
+</Relationships>"#;
+
+    const SHARED_STRINGS: &str = r#"<sst xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" count="4" uniqueCount="4">
+<si><t>Customer</t></si><si><t>Paid</t></si>
+<si><r><t>東京</t></r><r><t>支店</t></r><rPh sb="0" eb="2"><t>トウキョウ</t></rPh></si>
+<si><t>Acme &amp; Co</t></si></sst>"#;
+
+    const SHEET_1: &str = r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>
+<row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" t="s"><v>1</v></c><c r="C1" t="inlineStr"><is><t>Amount</t></is></c></row>
+<row r="2"><c r="A2" t="s"><v>2</v></c><c r="B2" t="b"><v>1</v></c><c r="C2"><f>SUM(D2:E2)</f><v>1200.5</v></c></row>
+<row r="3"><c r="A3" s="1"/></row>
+<row r="4"><c r="A4" t="s"><v>3</v></c><c r="C4"><v>80</v></c></row>
+</sheetData></worksheet>"#;
+
+    const SHEET_2: &str = r#"<worksheet><sheetData/></worksheet>"#;
+
+    const SHEET_3: &str = r#"<worksheet><sheetData><row r="2"><c r="B2" t="str"><v>see | Invoices</v></c></row></sheetData></worksheet>"#;
+
+    /// A small XLSX workbook.
+    pub(crate) fn sample_xlsx() -> Vec<u8> {
+        zip(&[
+            ("xl/workbook.xml", WORKBOOK),
+            ("xl/_rels/workbook.xml.rels", RELS),
+            ("xl/sharedStrings.xml", SHARED_STRINGS),
+            ("xl/worksheets/sheet1.xml", SHEET_1),
+            ("xl/worksheets/sheet2.xml", SHEET_2),
+            ("xl/worksheets/sheet3.xml", SHEET_3),
+        ])
+    }
+
+    #[test]
+    fn test_docx_markdown() {
+        let archive = Archive::open(sample_docx(), 1 << 20).unwrap();
+        assert_eq!(
+            docx_markdown(&archive).unwrap(),
+            "# Release & Rollback\n\n\
+             Deploy on Tuesdays.\n\n\
+             # Steps\n\n\
+             - Drain traffic\n\
+             - Roll back\n\n\
+             ## Contacts\n\n\
+             | Team | Channel |\n\
+             | --- | --- |\n\
+             | SRE on call | #ops\\|alerts |\n\n\
+             Line one\nline two"
+        );
+
+        // Without styles.xml, built-in heading ids still count.
+        let archive = Archive::open(
+            zip(&[(
+                "word/document.xml",
+                r#"<w:document><w:body><w:p><w:pPr><w:pStyle w:val="Heading2"/></w:pPr><w:r><w:t>Intro</w:t></w:r></w:p></w:body></w:document>"#,
+            )]),
+            1 << 20,
+        )
+        .unwrap();
+        assert_eq!(docx_markdown(&archive).unwrap(), "## Intro");
+    }
+
+    #[test]
+    fn test_xlsx_markdown() {
+        let archive = Archive::open(sample_xlsx(), 1 << 20).unwrap();
+        assert_eq!(
+            xlsx_markdown(&archive, 100).unwrap(),
+            "## Invoices\n\n\
+             | Customer | Paid | Amount |\n\
+             | --- | --- | --- |\n\
+             | 東京支店 | TRUE | 1200.5 |\n\
+             | Acme & Co |  | 80 |\n\n\
+             ## Notes\n\n\
+             |  | see \\| Invoices |\n\
+             | --- | --- |"
+        );
+
+        let capped = xlsx_markdown(&archive, 2).unwrap();
+        assert!(capped.contains("3 rows, first 2 shown"), "{}", capped);
+        assert!(!capped.contains("Acme"), "{}", capped);
+    }
+
+    #[test]
+    fn test_column_index() {
+        assert_eq!(column_index("A1"), Some(0));
+        assert_eq!(column_index("Z9"), Some(25));
+        assert_eq!(column_index("AB7"), Some(27));
+        assert_eq!(column_index("12"), None);
+    }
+
+    #[test]
+    fn test_not_a_zip() {
+        let path = std::env::temp_dir().join(format!("office-{}.docx", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"\xd0\xcf\x11\xe0 encrypted").unwrap();
+        let err = read_docx(&path, 1 << 20).unwrap_err();
+        std::fs::remove_file(&path).ok();
+        assert!(err.to_string().contains("not a valid DOCX"), "{}", err);
+    }
+}
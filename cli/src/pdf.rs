@@ -0,0 +1,1462 @@
+//! Reading PDFs for ingest without markitdown (`converter = "native"`).
+//!
+//! Objects are found by scanning the file for `N G obj`, and inside object
+//! streams, rather than through the cross-reference table, so a file whose
+//! table is damaged still reads. Pages are walked from the document catalog
+//! and the text operators of their content streams, and of the form
+//! XObjects those draw, are replayed: moving to another line becomes a
+//! newline, any other repositioning or a wide gap in a `TJ` array a space.
+//! Strings are decoded with the font's `ToUnicode` CMap when it has one,
+//! and as WinAnsi with the font's `/Differences` otherwise. Streams may be
+//! Flate, ASCIIHex or ASCII85 encoded.
+//!
+//! Encrypted PDFs and PDFs without any text (scans, image-only exports) are
+//! [`Unreadable`], which ingest reports as a skip.
+//!
+//! `lopdf` (and `pdf-extract`, built on it) would bring some 70 crates,
+//! decryption and rayon among them, into a default feature that only needs
+//! the text of unencrypted files.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::Result;
+
+/// A document's metadata title and the text of each of its pages.
+#[derive(Debug, PartialEq)]
+pub struct Document {
+    pub title: Option<String>,
+    /// One entry per page, empty for pages without text.
+    pub pages: Vec<String>,
+}
+
+/// A PDF the reader can't take text from.
+#[derive(Debug, thiserror::Error)]
+pub enum Unreadable {
+    #[error("{0}: the PDF is encrypted")]
+    Encrypted(String),
+    #[error("{0}: the PDF has no extractable text (scanned or image-only?)")]
+    NoText(String),
+}
+
+/// Read the text of the PDF at `path`. Streams that decode to more than
+/// `max_stream_bytes` are an error, so a small file can't expand without
+/// bound.
+pub fn read(path: &Path, max_stream_bytes: u64) -> Result<Document> {
+    let data = std::fs::read(path)?;
+    let display = path.display().to_string();
+    // Some writers put junk before the header; readers allow 1 KB of it.
+    if find(&data[..data.len().min(1024)], b"%PDF-").is_none() {
+        anyhow::bail!("{} is not a PDF file", display);
+    }
+    let pdf = Pdf::parse(&data, max_stream_bytes)?;
+    if pdf.trailers.iter().any(|t| t.contains_key("Encrypt")) {
+        return Err(Unreadable::Encrypted(display).into());
+    }
+
+    let pages: Vec<String> = pdf
+        .pages()
+        .into_iter()
+        .map(|(page, resources)| clean_text(&pdf.page_text(page, resources)))
+        .collect();
+    if pages.iter().all(|page| page.is_empty()) {
+        return Err(Unreadable::NoText(display).into());
+    }
+    Ok(Document {
+        title: pdf.title(),
+        pages,
+    })
+}
+
+type Dict = HashMap<String, Object>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Object {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Name(String),
+    String(Vec<u8>),
+    Array(Vec<Object>),
+    Dict(Dict),
+    /// A stream's dictionary and its data, still encoded.
+    Stream(Dict, Vec<u8>),
+    /// A reference to the indirect object with this number.
+    Ref(u32),
+    /// An operator in a content stream or CMap, or any other bare word.
+    Keyword(String),
+}
+
+static NULL: Object = Object::Null;
+
+impl Object {
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Object::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn as_name(&self) -> Option<&str> {
+        match self {
+            Object::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b'\0' | b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(
+        byte,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+fn is_regular(byte: u8) -> bool {
+    !is_whitespace(byte) && !is_delimiter(byte)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Tokenizer for PDF objects, content streams and CMaps, which share one
+/// syntax.
+struct Lexer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.pos >= self.data.len()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(byte) = self.peek() {
+            if is_whitespace(byte) {
+                self.pos += 1;
+            } else if byte == b'%' {
+                while self.peek().is_some_and(|b| b != b'\n' && b != b'\r') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn regular_run(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while self.peek().is_some_and(is_regular) {
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+
+    /// The next object, with `N G R` read as a reference.
+    fn object(&mut self) -> Option<Object> {
+        let object = self.token()?;
+        if let Object::Number(number) = object {
+            if number >= 0.0 && number.fract() == 0.0 {
+                let save = self.pos;
+                if let (Some(Object::Number(_)), Some(Object::Keyword(r))) =
+                    (self.token(), self.token())
+                {
+                    if r == "R" {
+                        return Some(Object::Ref(number as u32));
+                    }
+                }
+                self.pos = save;
+            }
+        }
+        Some(object)
+    }
+
+    /// The next token, without combining references.
+    fn token(&mut self) -> Option<Object> {
+        self.skip_whitespace();
+        let byte = self.peek()?;
+        Some(match byte {
+            b'/' => {
+                self.pos += 1;
+                Object::Name(decode_name(self.regular_run()))
+            }
+            b'(' => Object::String(self.literal_string()),
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                Object::Dict(self.dict_body())
+            }
+            b'<' => Object::String(self.hex_string()),
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        None => break,
+                        Some(b']') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        Some(_) => items.extend(self.object()),
+                    }
+                }
+                Object::Array(items)
+            }
+            b')' | b'>' | b']' | b'{' | b'}' => {
+                self.pos += 1;
+                Object::Keyword((byte as char).to_string())
+            }
+            b'0'..=b'9' | b'+' | b'-' | b'.' => {
+                let run = self.regular_run();
+                let text = String::from_utf8_lossy(run);
+                Object::Number(text.parse().unwrap_or(0.0))
+            }
+            _ => match self.regular_run() {
+                b"true" => Object::Bool(true),
+                b"false" => Object::Bool(false),
+                b"null" => Object::Null,
+                word => Object::Keyword(String::from_utf8_lossy(word).into_owned()),
+            },
+        })
+    }
+
+    /// A dictionary's entries, after its `<<`.
+    fn dict_body(&mut self) -> Dict {
+        let mut dict = Dict::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => break,
+                Some(b'>') => {
+                    self.pos = (self.pos + 2).min(self.data.len());
+                    break;
+                }
+                Some(_) => {}
+            }
+            let Some(key) = self.token() else { break };
+            let Object::Name(key) = key else { continue };
+            if let Some(value) = self.object() {
+                dict.insert(key, value);
+            }
+        }
+        dict
+    }
+
+    fn literal_string(&mut self) -> Vec<u8> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        let mut depth = 1;
+        while let Some(byte) = self.peek() {
+            self.pos += 1;
+            match byte {
+                b'(' => {
+                    depth += 1;
+                    out.push(byte);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    out.push(byte);
+                }
+                b'\\' => {
+                    let Some(escaped) = self.peek() else { break };
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'b' => out.push(b'\x08'),
+                        b'f' => out.push(b'\x0c'),
+                        b'0'..=b'7' => {
+                            let mut value = u32::from(escaped - b'0');
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(digit @ b'0'..=b'7') => {
+                                        value = value * 8 + u32::from(digit - b'0');
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            out.push(value as u8);
+                        }
+                        // A backslash at the end of a line continues it.
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        other => out.push(other),
+                    }
+                }
+                _ => out.push(byte),
+            }
+        }
+        out
+    }
+
+    fn hex_string(&mut self) -> Vec<u8> {
+        self.pos += 1;
+        let mut digits = Vec::new();
+        while let Some(byte) = self.peek() {
+            self.pos += 1;
+            if byte == b'>' {
+                break;
+            }
+            if let Some(digit) = (byte as char).to_digit(16) {
+                digits.push(digit as u8);
+            }
+        }
+        if digits.len() % 2 == 1 {
+            digits.push(0);
+        }
+        digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect()
+    }
+
+    /// The object after `N G obj`, with its stream data if it has one.
+    fn indirect_object(&mut self) -> Option<Object> {
+        let object = self.object()?;
+        let Object::Dict(dict) = object else {
+            return Some(object);
+        };
+        let save = self.pos;
+        self.skip_whitespace();
+        if !self
+            .data
+            .get(self.pos..)
+            .is_some_and(|rest| rest.starts_with(b"stream"))
+        {
+            self.pos = save;
+            return Some(Object::Dict(dict));
+        }
+        self.pos += b"stream".len();
+        if self.peek() == Some(b'\r') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'\n') {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let rest = &self.data[start..];
+        // Trust /Length only when `endstream` follows it; it may also be a
+        // reference, or simply wrong.
+        let declared = dict
+            .get("Length")
+            .and_then(Object::as_number)
+            .map(|n| n as usize)
+            .filter(|&len| {
+                rest.get(len..).is_some_and(|after| {
+                    let skip = after.iter().take_while(|b| is_whitespace(**b)).count();
+                    after[skip..].starts_with(b"endstream")
+                })
+            });
+        let len = declared.unwrap_or_else(|| {
+            let end = find(rest, b"endstream").unwrap_or(rest.len());
+            let data = &rest[..end];
+            let trailing = data
+                .iter()
+                .rev()
+                .take_while(|b| **b == b'\r' || **b == b'\n');
+            end - trailing.count()
+        });
+        self.pos = start + len;
+        Some(Object::Stream(dict, rest[..len].to_vec()))
+    }
+}
+
+/// A name with its `#xx` escapes decoded.
+fn decode_name(raw: &[u8]) -> String {
+    let mut out = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] == b'#' && i + 2 < raw.len() {
+            if let Ok(byte) = u8::from_str_radix(&String::from_utf8_lossy(&raw[i + 1..i + 3]), 16) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(raw[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The number of the object whose `obj` keyword starts at `at`, read
+/// backwards from `N G `.
+fn object_number(data: &[u8], at: usize) -> Option<u32> {
+    let mut i = at;
+    let digits_before = |end: usize| -> usize {
+        data[..end]
+            .iter()
+            .rev()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let spaces_before = |end: usize| -> usize {
+        data[..end]
+            .iter()
+            .rev()
+            .take_while(|b| is_whitespace(**b))
+            .count()
+    };
+
+    i -= spaces_before(i);
+    let generation = digits_before(i);
+    if generation == 0 {
+        return None;
+    }
+    i -= generation;
+    let spaces = spaces_before(i);
+    if spaces == 0 {
+        return None;
+    }
+    i -= spaces;
+    let number = digits_before(i);
+    if number == 0 {
+        return None;
+    }
+    i -= number;
+    if i > 0 && is_regular(data[i - 1]) {
+        return None;
+    }
+    std::str::from_utf8(&data[i..i + number]).ok()?.parse().ok()
+}
+
+struct Pdf {
+    objects: HashMap<u32, Object>,
+    /// Trailer dictionaries and cross-reference stream dictionaries, in
+    /// file order: later ones belong to later incremental updates.
+    trailers: Vec<Dict>,
+    max_stream_bytes: u64,
+}
+
+impl Pdf {
+    fn parse(data: &[u8], max_stream_bytes: u64) -> Result<Self> {
+        let mut objects = HashMap::new();
+        let mut trailers = Vec::new();
+
+        let mut i = 0;
+        while let Some(found) = find(&data[i..], b"obj") {
+            let at = i + found;
+            i = at + 3;
+            if data.get(at + 3).is_some_and(|b| is_regular(*b)) {
+                continue;
+            }
+            let Some(number) = object_number(data, at) else {
+                continue;
+            };
+            let mut lexer = Lexer::new(data, at + 3);
+            let Some(object) = lexer.indirect_object() else {
+                continue;
+            };
+            if let Object::Stream(dict, _) = &object {
+                if dict.get("Type").and_then(Object::as_name) == Some("XRef") {
+                    trailers.push(dict.clone());
+                }
+            }
+            // Later definitions replace earlier ones, as in an update.
+            objects.insert(number, object);
+            // Don't look for objects inside the one just read.
+            i = i.max(lexer.pos);
+        }
+
+        let mut i = 0;
+        while let Some(found) = find(&data[i..], b"trailer") {
+            let at = i + found + b"trailer".len();
+            let mut lexer = Lexer::new(data, at);
+            if let Some(Object::Dict(dict)) = lexer.object() {
+                trailers.push(dict);
+            }
+            i = at;
+        }
+
+        let mut pdf = Self {
+            objects,
+            trailers,
+            max_stream_bytes,
+        };
+        pdf.read_object_streams()?;
+        Ok(pdf)
+    }
+
+    /// Add the objects packed in object streams. Objects defined directly
+    /// in the file take precedence.
+    fn read_object_streams(&mut self) -> Result<()> {
+        let streams: Vec<(Dict, Vec<u8>)> = self
+            .objects
+            .values()
+            .filter_map(|object| match object {
+                Object::Stream(dict, data)
+                    if dict.get("Type").and_then(Object::as_name) == Some("ObjStm") =>
+                {
+                    Some((dict.clone(), data.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        for (dict, raw) in streams {
+            let data = match self.decode(&dict, &raw) {
+                Ok(data) => data,
+                Err(e) => {
+                    tracing::debug!("Skipping unreadable object stream: {:#}", e);
+                    continue;
+                }
+            };
+            let count = self.number(dict.get("N")).unwrap_or(0.0) as usize;
+            let first = self.number(dict.get("First")).unwrap_or(0.0) as usize;
+            let mut header = Lexer::new(&data, 0);
+            let mut entries = Vec::new();
+            for _ in 0..count {
+                match (header.token(), header.token()) {
+                    (Some(Object::Number(number)), Some(Object::Number(offset))) => {
+                        entries.push((number as u32, first + offset as usize))
+                    }
+                    _ => break,
+                }
+            }
+            for (number, offset) in entries {
+                if offset >= data.len() {
+                    continue;
+                }
+                if let Some(object) = Lexer::new(&data, offset).object() {
+                    self.objects.entry(number).or_insert(object);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `object`, following references.
+    fn get<'a>(&'a self, object: Option<&'a Object>) -> &'a Object {
+        let mut object = object.unwrap_or(&NULL);
+        // A bound, in case references form a loop.
+        for _ in 0..16 {
+            match object {
+                Object::Ref(number) => object = self.objects.get(number).unwrap_or(&NULL),
+                _ => return object,
+            }
+        }
+        &NULL
+    }
+
+    fn dict<'a>(&'a self, object: Option<&'a Object>) -> Option<&'a Dict> {
+        match self.get(object) {
+            Object::Dict(dict) | Object::Stream(dict, _) => Some(dict),
+            _ => None,
+        }
+    }
+
+    fn number(&self, object: Option<&Object>) -> Option<f64> {
+        self.get(object).as_number()
+    }
+
+    fn name<'a>(&'a self, object: Option<&'a Object>) -> Option<&'a str> {
+        self.get(object).as_name()
+    }
+
+    /// The newest value of `key` among the trailers.
+    fn trailer(&self, key: &str) -> Option<&Object> {
+        self.trailers.iter().rev().find_map(|t| t.get(key))
+    }
+
+    /// Decoded data of the stream `object`.
+    fn stream_data(&self, object: Option<&Object>) -> Result<Vec<u8>> {
+        match self.get(object) {
+            Object::Stream(dict, raw) => self.decode(dict, raw),
+            _ => anyhow::bail!("not a stream"),
+        }
+    }
+
+    fn decode(&self, dict: &Dict, raw: &[u8]) -> Result<Vec<u8>> {
+        let filters: Vec<&str> = match self.get(dict.get("Filter")) {
+            Object::Name(name) => vec![name.as_str()],
+            Object::Array(items) => items.iter().filter_map(|f| self.name(Some(f))).collect(),
+            _ => Vec::new(),
+        };
+        let mut data = raw.to_vec();
+        for filter in filters {
+            data = match filter {
+                "FlateDecode" | "Fl" => inflate(&data, self.max_stream_bytes)?,
+                "ASCIIHexDecode" | "AHx" => {
+                    let mut hex = vec![b'<'];
+                    hex.extend_from_slice(&data);
+                    hex.push(b'>');
+                    Lexer::new(&hex, 0).hex_string()
+                }
+                "ASCII85Decode" | "A85" => ascii85(&data)?,
+                other => anyhow::bail!("unsupported stream filter {}", other),
+            };
+        }
+        Ok(data)
+    }
+
+    fn title(&self) -> Option<String> {
+        let info = self.dict(self.trailer("Info"))?;
+        match self.get(info.get("Title")) {
+            Object::String(bytes) => {
+                Some(text_string(bytes).trim().to_string()).filter(|t| !t.is_empty())
+            }
+            _ => None,
+        }
+    }
+
+    /// Every page in order, with the resources it inherits or has. Falls
+    /// back to every page object in object number order when the page
+    /// tree can't be walked.
+    fn pages(&self) -> Vec<(&Dict, Option<&Dict>)> {
+        let mut pages = Vec::new();
+        let root = self.dict(self.trailer("Root"));
+        if let Some(tree) = root.and_then(|root| root.get("Pages")) {
+            let mut seen = HashSet::new();
+            self.walk_pages(tree, None, &mut pages, &mut seen);
+        }
+        if pages.is_empty() {
+            let mut numbers: Vec<&u32> = self.objects.keys().collect();
+            numbers.sort();
+            for number in numbers {
+                if let Object::Dict(dict) = &self.objects[number] {
+                    if dict.get("Type").and_then(Object::as_name) == Some("Page") {
+                        pages.push((dict, self.dict(dict.get("Resources"))));
+                    }
+                }
+            }
+        }
+        pages
+    }
+
+    fn walk_pages<'a>(
+        &'a self,
+        node: &'a Object,
+        inherited: Option<&'a Dict>,
+        pages: &mut Vec<(&'a Dict, Option<&'a Dict>)>,
+        seen: &mut HashSet<u32>,
+    ) {
+        if let Object::Ref(number) = node {
+            if !seen.insert(*number) {
+                return;
+            }
+        }
+        let Some(dict) = self.dict(Some(node)) else {
+            return;
+        };
+        let resources = self.dict(dict.get("Resources")).or(inherited);
+        match self.get(dict.get("Kids")) {
+            Object::Array(kids) => {
+                for kid in kids {
+                    self.walk_pages(kid, resources, pages, seen);
+                }
+            }
+            _ => pages.push((dict, resources)),
+        }
+    }
+
+    fn page_text(&self, page: &Dict, resources: Option<&Dict>) -> String {
+        let contents: Vec<&Object> = match self.get(page.get("Contents")) {
+            Object::Array(items) => items.iter().collect(),
+            Object::Stream(..) => vec![page.get("Contents").unwrap_or(&NULL)],
+            _ => Vec::new(),
+        };
+        // A page's content may be split anywhere, even inside an operator,
+        // so the parts are joined before they are read.
+        let mut content = Vec::new();
+        for part in contents {
+            match self.stream_data(Some(part)) {
+                Ok(data) => {
+                    content.extend_from_slice(&data);
+                    content.push(b'\n');
+                }
+                Err(e) => tracing::debug!("Skipping unreadable page content: {:#}", e),
+            }
+        }
+        let mut text = TextWriter::default();
+        self.run_content(&content, resources, &mut text, 0);
+        text.out
+    }
+
+    fn fonts(&self, resources: Option<&Dict>) -> HashMap<String, Font> {
+        let Some(fonts) = resources.and_then(|r| self.dict(r.get("Font"))) else {
+            return HashMap::new();
+        };
+        fonts
+            .iter()
+            .filter_map(|(name, font)| Some((name.clone(), self.font(self.dict(Some(font))?))))
+            .collect()
+    }
+
+    fn font(&self, dict: &Dict) -> Font {
+        let composite = dict.get("Subtype").and_then(Object::as_name) == Some("Type0");
+        let cmap = self
+            .stream_data(dict.get("ToUnicode"))
+            .ok()
+            .map(|data| CMap::parse(&data));
+        let mut differences = HashMap::new();
+        if let Some(encoding) = self.dict(dict.get("Encoding")) {
+            if let Object::Array(items) = self.get(encoding.get("Differences")) {
+                let mut code = 0u32;
+                for item in items {
+                    match self.get(Some(item)) {
+                        Object::Number(n) => code = *n as u32,
+                        Object::Name(name) => {
+                            if let (Ok(byte), Some(text)) = (u8::try_from(code), glyph_text(name)) {
+                                differences.insert(byte, text);
+                            }
+                            code += 1;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Font {
+            composite,
+            cmap,
+            differences,
+        }
+    }
+
+    /// Replay the text operators of `content` into `text`. `depth` counts
+    /// nested form XObjects.
+    fn run_content(
+        &self,
+        content: &[u8],
+        resources: Option<&Dict>,
+        text: &mut TextWriter,
+        depth: usize,
+    ) {
+        let fonts = self.fonts(resources);
+        let mut font: Option<&Font> = None;
+        let mut lexer = Lexer::new(content, 0);
+        let mut operands: Vec<Object> = Vec::new();
+        while !lexer.at_end() {
+            let Some(token) = lexer.token() else { break };
+            let Object::Keyword(operator) = token else {
+                operands.push(token);
+                continue;
+            };
+            let number = |i: usize| operands.get(i).and_then(Object::as_number);
+            match operator.as_str() {
+                "BT" => text.begin(),
+                "Tf" => font = operands.first().and_then(|name| fonts.get(name.as_name()?)),
+                "Td" | "TD" => text.move_by(number(1).unwrap_or(0.0)),
+                "Tm" => text.set_matrix(number(3).unwrap_or(1.0), number(5).unwrap_or(0.0)),
+                "T*" => text.next_line(),
+                "Tj" => {
+                    if let Some(Object::String(bytes)) = operands.last() {
+                        text.show(&decode_text(font, bytes));
+                    }
+                }
+                "'" | "\"" => {
+                    text.next_line();
+                    if let Some(Object::String(bytes)) = operands.last() {
+                        text.show(&decode_text(font, bytes));
+                    }
+                }
+                "TJ" => {
+                    if let Some(Object::Array(items)) = operands.last() {
+                        let mut shown = String::new();
+                        for item in items {
+                            match item {
+                                Object::String(bytes) => shown.push_str(&decode_text(font, bytes)),
+                                // Thousandths of an em; a gap this wide
+                                // separates words.
+                                Object::Number(n) if *n < -200.0 && !shown.ends_with(' ') => {
+                                    shown.push(' ')
+                                }
+                                _ => {}
+                            }
+                        }
+                        text.show(&shown);
+                    }
+                }
+                "Do" if depth < 4 => {
+                    let xobject = operands.first().and_then(Object::as_name).and_then(|name| {
+                        let xobjects = self.dict(resources?.get("XObject"))?;
+                        xobjects.get(name)
+                    });
+                    let form = self.dict(xobject).filter(|dict| {
+                        dict.get("Subtype").and_then(Object::as_name) == Some("Form")
+                    });
+                    if let (Some(form), Ok(data)) = (form, self.stream_data(xobject)) {
+                        let form_resources = self.dict(form.get("Resources")).or(resources);
+                        self.run_content(&data, form_resources, text, depth + 1);
+                    }
+                }
+                // Inline image data is binary up to `EI`.
+                "ID" => {
+                    let rest = &content[lexer.pos..];
+                    let end = (0..rest.len().saturating_sub(1))
+                        .find(|&i| {
+                            rest[i..].starts_with(b"EI")
+                                && i > 0
+                                && is_whitespace(rest[i - 1])
+                                && rest.get(i + 2).is_none_or(|b| !is_regular(*b))
+                        })
+                        .map_or(rest.len(), |i| i + 2);
+                    lexer.pos += end;
+                }
+                _ => {}
+            }
+            operands.clear();
+        }
+    }
+}
+
+/// Text of a page as its operators place it.
+#[derive(Default)]
+struct TextWriter {
+    out: String,
+    /// Vertical position of the current line, in text space.
+    line_y: f64,
+    /// Vertical scale of the text matrix, which `Td` offsets are in.
+    scale_y: f64,
+    /// Line position when text was last shown.
+    shown_y: Option<f64>,
+    /// Text was repositioned since it was last shown.
+    moved: bool,
+    /// `T*` or a quote operator started a new line.
+    new_line: bool,
+}
+
+impl TextWriter {
+    fn begin(&mut self) {
+        self.line_y = 0.0;
+        self.scale_y = 1.0;
+        self.moved = true;
+    }
+
+    fn move_by(&mut self, dy: f64) {
+        self.line_y += dy
+            * if self.scale_y == 0.0 {
+                1.0
+            } else {
+                self.scale_y
+            };
+        self.moved = true;
+    }
+
+    fn set_matrix(&mut self, scale_y: f64, y: f64) {
+        self.scale_y = scale_y;
+        self.line_y = y;
+        self.moved = true;
+    }
+
+    fn next_line(&mut self) {
+        self.new_line = true;
+    }
+
+    fn show(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let other_line = self.shown_y.is_some_and(|y| (y - self.line_y).abs() > 0.5);
+        if !self.out.is_empty() {
+            if self.new_line || other_line {
+                self.out.push('\n');
+            } else if self.moved && !self.out.ends_with(' ') && !text.starts_with(' ') {
+                self.out.push(' ');
+            }
+        }
+        self.out.push_str(text);
+        self.shown_y = Some(self.line_y);
+        self.moved = false;
+        self.new_line = false;
+    }
+}
+
+/// How a font's character codes map to text.
+struct Font {
+    /// Codes are two bytes unless the CMap says otherwise.
+    composite: bool,
+    cmap: Option<CMap>,
+    differences: HashMap<u8, String>,
+}
+
+fn decode_text(font: Option<&Font>, bytes: &[u8]) -> String {
+    let Some(font) = font else {
+        return bytes.iter().filter_map(|b| win_ansi(*b)).collect();
+    };
+    if let Some(cmap) = &font.cmap {
+        let width = cmap
+            .code_bytes
+            .unwrap_or(if font.composite { 2 } else { 1 });
+        return bytes
+            .chunks(width)
+            .filter_map(|code| {
+                let value = code.iter().fold(0u32, |acc, b| acc << 8 | u32::from(*b));
+                match cmap.map.get(&value) {
+                    Some(text) => Some(text.clone()),
+                    None if width == 1 => simple_char(font, code[0]),
+                    None => None,
+                }
+            })
+            .collect();
+    }
+    if font.composite {
+        // Glyph ids without a ToUnicode map can't be turned into text.
+        return String::new();
+    }
+    bytes.iter().filter_map(|b| simple_char(font, *b)).collect()
+}
+
+fn simple_char(font: &Font, byte: u8) -> Option<String> {
+    match font.differences.get(&byte) {
+        Some(text) => Some(text.clone()),
+        None => win_ansi(byte).map(String::from),
+    }
+}
+
+/// A `ToUnicode` CMap: character codes to the text they stand for.
+struct CMap {
+    /// Code length given by the codespace ranges.
+    code_bytes: Option<usize>,
+    map: HashMap<u32, String>,
+}
+
+/// Most codes a single `bfrange` entry may map, against malformed ranges.
+const MAX_RANGE: u32 = 0xFFFF;
+
+impl CMap {
+    fn parse(data: &[u8]) -> Self {
+        let mut lexer = Lexer::new(data, 0);
+        let mut tokens = Vec::new();
+        while !lexer.at_end() {
+            match lexer.token() {
+                Some(token) => tokens.push(token),
+                None => break,
+            }
+        }
+
+        let mut cmap = CMap {
+            code_bytes: None,
+            map: HashMap::new(),
+        };
+        let mut i = 0;
+        let code = |bytes: &[u8]| bytes.iter().fold(0u32, |acc, b| acc << 8 | u32::from(*b));
+        while i < tokens.len() {
+            let Object::Keyword(keyword) = &tokens[i] else {
+                i += 1;
+                continue;
+            };
+            i += 1;
+            let section = |end: &str, i: usize| {
+                let len = tokens[i..]
+                    .iter()
+                    .position(|t| matches!(t, Object::Keyword(k) if k == end))
+                    .unwrap_or(tokens.len() - i);
+                &tokens[i..i + len]
+            };
+            match keyword.as_str() {
+                "begincodespacerange" => {
+                    let entries = section("endcodespacerange", i);
+                    if let Some(Object::String(low)) = entries.first() {
+                        cmap.code_bytes = Some(low.len().max(1));
+                    }
+                    i += entries.len();
+                }
+                "beginbfchar" => {
+                    let entries = section("endbfchar", i);
+                    for pair in entries.chunks(2) {
+                        if let [Object::String(src), dst] = pair {
+                            if let Some(text) = destination(dst) {
+                                cmap.map.insert(code(src), text);
+                            }
+                        }
+                    }
+                    i += entries.len();
+                }
+                "beginbfrange" => {
+                    let entries = section("endbfrange", i);
+                    for triple in entries.chunks(3) {
+                        let [Object::String(low), Object::String(high), dst] = triple else {
+                            continue;
+                        };
+                        let (low, high) = (code(low), code(high));
+                        if high < low || high - low > MAX_RANGE {
+                            continue;
+                        }
+                        match dst {
+                            Object::String(start) => {
+                                let units = utf16_units(start);
+                                for (offset, value) in (low..=high).enumerate() {
+                                    let mut units = units.clone();
+                                    if let Some(last) = units.last_mut() {
+                                        *last = last.wrapping_add(offset as u16);
+                                    }
+                                    cmap.map.insert(value, String::from_utf16_lossy(&units));
+                                }
+                            }
+                            Object::Array(items) => {
+                                for (value, item) in (low..=high).zip(items) {
+                                    if let Some(text) = destination(item) {
+                                        cmap.map.insert(value, text);
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    i += entries.len();
+                }
+                _ => {}
+            }
+        }
+        cmap
+    }
+}
+
+fn destination(object: &Object) -> Option<String> {
+    match object {
+        Object::String(bytes) => Some(String::from_utf16_lossy(&utf16_units(bytes))),
+        Object::Name(name) => glyph_text(name),
+        _ => None,
+    }
+}
+
+fn utf16_units(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks(2)
+        .map(|pair| match pair {
+            [high, low] => u16::from(*high) << 8 | u16::from(*low),
+            [only] => u16::from(*only),
+            _ => 0,
+        })
+        .collect()
+}
+
+/// A PDF text string (such as the title): UTF-16 with a byte order mark,
+/// or PDFDocEncoding, which is close enough to WinAnsi.
+fn text_string(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFE, 0xFF, rest @ ..] => String::from_utf16_lossy(&utf16_units(rest)),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => bytes.iter().filter_map(|b| win_ansi(*b)).collect(),
+    }
+}
+
+/// The WinAnsiEncoding character for `byte`: Latin-1, except for the
+/// punctuation Windows puts in 0x80-0x9F. Control codes have none.
+fn win_ansi(byte: u8) -> Option<char> {
+    const HIGH: [Option<char>; 32] = [
+        Some('€'),
+        None,
+        Some('‚'),
+        Some('ƒ'),
+        Some('„'),
+        Some('…'),
+        Some('†'),
+        Some('‡'),
+        Some('ˆ'),
+        Some('‰'),
+        Some('Š'),
+        Some('‹'),
+        Some('Œ'),
+        None,
+        Some('Ž'),
+        None,
+        None,
+        Some('‘'),
+        Some('’'),
+        Some('“'),
+        Some('”'),
+        Some('•'),
+        Some('–'),
+        Some('—'),
+        Some('˜'),
+        Some('™'),
+        Some('š'),
+        Some('›'),
+        Some('œ'),
+        None,
+        Some('ž'),
+        Some('Ÿ'),
+    ];
+    match byte {
+        b'\t' | b'\n' | b'\r' => Some(' '),
+        0x20..=0x7E | 0xA0..=0xFF => Some(byte as char),
+        0x80..=0x9F => HIGH[usize::from(byte - 0x80)],
+        _ => None,
+    }
+}
+
+/// The text of a glyph named in `/Differences` or a CMap: `uniXXXX` names,
+/// single letters and digits, and common punctuation and ligatures.
+fn glyph_text(name: &str) -> Option<String> {
+    if let Some(hex) = name.strip_prefix("uni").or_else(|| name.strip_prefix('u')) {
+        if (4..=6).contains(&hex.len()) {
+            if let Some(c) = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32) {
+                return Some(c.to_string());
+            }
+        }
+    }
+    if name.len() == 1 && name.as_bytes()[0].is_ascii_alphanumeric() {
+        return Some(name.to_string());
+    }
+    const DIGITS: [&str; 10] = [
+        "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+    ];
+    if let Some(digit) = DIGITS.iter().position(|d| *d == name) {
+        return Some(digit.to_string());
+    }
+    let text = match name {
+        "space" | "nbspace" => " ",
+        "exclam" => "!",
+        "quotedbl" => "\"",
+        "numbersign" => "#",
+        "dollar" => "$",
+        "percent" => "%",
+        "ampersand" => "&",
+        "quotesingle" => "'",
+        "quoteright" => "’",
+        "quoteleft" => "‘",
+        "quotedblleft" => "“",
+        "quotedblright" => "”",
+        "parenleft" => "(",
+        "parenright" => ")",
+        "asterisk" => "*",
+        "plus" => "+",
+        "comma" => ",",
+        "hyphen" | "minus" => "-",
+        "period" => ".",
+        "slash" => "/",
+        "colon" => ":",
+        "semicolon" => ";",
+        "less" => "<",
+        "equal" => "=",
+        "greater" => ">",
+        "question" => "?",
+        "at" => "@",
+        "bracketleft" => "[",
+        "backslash" => "\\",
+        "bracketright" => "]",
+        "underscore" => "_",
+        "braceleft" => "{",
+        "bar" => "|",
+        "braceright" => "}",
+        "asciitilde" => "~",
+        "endash" => "–",
+        "emdash" => "—",
+        "bullet" => "•",
+        "ellipsis" => "…",
+        "copyright" => "©",
+        "registered" => "®",
+        "trademark" => "™",
+        "degree" => "°",
+        "section" => "§",
+        "Euro" | "euro" => "€",
+        "fi" => "fi",
+        "fl" => "fl",
+        "ff" => "ff",
+        "ffi" => "ffi",
+        "ffl" => "ffl",
+        _ => return None,
+    };
+    Some(text.to_string())
+}
+
+fn inflate(data: &[u8], max_bytes: u64) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let result = flate2::read::ZlibDecoder::new(data)
+        .take(max_bytes + 1)
+        .read_to_end(&mut out);
+    match result {
+        Ok(_) => {}
+        // Streams with a damaged end are common; keep what decoded.
+        Err(_) if !out.is_empty() => {}
+        Err(e) => return Err(anyhow::Error::new(e).context("corrupt Flate stream")),
+    }
+    if out.len() as u64 > max_bytes {
+        anyhow::bail!(
+            "a stream decodes to more than max_file_size_bytes ({} bytes)",
+            max_bytes
+        );
+    }
+    Ok(out)
+}
+
+fn ascii85(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut group = Vec::with_capacity(5);
+    let data = data.strip_prefix(b"<~").unwrap_or(data);
+    for &byte in data {
+        match byte {
+            b'~' => break,
+            b'z' if group.is_empty() => out.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group.push(u32::from(byte - b'!'));
+                if group.len() == 5 {
+                    let value = group
+                        .iter()
+                        .fold(0u32, |acc, d| acc.wrapping_mul(85).wrapping_add(*d));
+                    out.extend_from_slice(&value.to_be_bytes());
+                    group.clear();
+                }
+            }
+            _ if is_whitespace(byte) => {}
+            _ => anyhow::bail!("invalid ASCII85 data"),
+        }
+    }
+    if !group.is_empty() {
+        let len = group.len();
+        group.resize(5, 84);
+        let value = group
+            .iter()
+            .fold(0u32, |acc, d| acc.wrapping_mul(85).wrapping_add(*d));
+        out.extend_from_slice(&value.to_be_bytes()[..len - 1]);
+    }
+    Ok(out)
+}
+
+/// Page text with each line trimmed, runs of spaces collapsed and blank
+/// lines kept single.
+fn clean_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut blank = false;
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            blank = !out.is_empty();
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str(if blank { "\n\n" } else { "\n" });
+        }
+        out.push_str(&line);
+        blank = false;
+    }
+    out
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A PDF of `objects`, numbered from 1, with a trailer pointing at
+    /// object 1 as the catalog and `trailer` added to it.
+    pub(crate) fn build(objects: &[Vec<u8>], trailer: &str) -> Vec<u8> {
+        let mut pdf = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        for (i, object) in objects.iter().enumerate() {
+            pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            pdf.extend_from_slice(object);
+            pdf.extend_from_slice(b"\nendobj\n");
+        }
+        pdf.extend_from_slice(
+            format!("trailer\n<< /Root 1 0 R {} >>\n%%EOF\n", trailer).as_bytes(),
+        );
+        pdf
+    }
+
+    pub(crate) fn stream(dict: &str, data: &[u8]) -> Vec<u8> {
+        let mut object = format!("<< {} /Length {} >>\nstream\n", dict, data.len()).into_bytes();
+        object.extend_from_slice(data);
+        object.extend_from_slice(b"\nendstream");
+        object
+    }
+
+    pub(crate) fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// A two-page PDF with a Helvetica font, its first page's content
+    /// Flate-compressed.
+    pub(crate) fn sample() -> Vec<u8> {
+        let page1 = b"BT /F1 12 Tf 72 720 Td (Deployment runbook) Tj 0 -14 Td \
+            [(Roll) -30 (back) -400 (with care.)] TJ ET\n\
+            BT 1 0 0 1 72 600 Tm <48656C6C6F> Tj 1 0 0 1 200 600 Tm (world) Tj ET";
+        let page2 = b"BT /F1 12 Tf 72 720 Td (Second \\(page\\)) Tj T* (caf\\351) Tj ET";
+        build(
+            &[
+                b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+                b"<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 \
+                  /Resources << /Font << /F1 5 0 R >> >> >>"
+                    .to_vec(),
+                b"<< /Type /Page /Parent 2 0 R /Contents 6 0 R >>".to_vec(),
+                b"<< /Type /Page /Parent 2 0 R /Contents [7 0 R] >>".to_vec(),
+                b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica \
+                  /Encoding /WinAnsiEncoding >>"
+                    .to_vec(),
+                stream("/Filter /FlateDecode", &deflate(page1)),
+                stream("", page2),
+                b"<< /Title (Runbook) >>".to_vec(),
+            ],
+            "/Info 8 0 R",
+        )
+    }
+
+    fn read_bytes(data: &[u8]) -> Result<Document> {
+        let path = std::env::temp_dir().join(format!("pdf-{}.pdf", uuid::Uuid::new_v4()));
+        std::fs::write(&path, data).unwrap();
+        let document = read(&path, 1 << 20);
+        std::fs::remove_file(&path).ok();
+        document
+    }
+
+    #[test]
+    fn test_read() {
+        let document = read_bytes(&sample()).unwrap();
+        assert_eq!(document.title.as_deref(), Some("Runbook"));
+        assert_eq!(
+            document.pages,
+            vec![
+                "Deployment runbook\nRollback with care.\nHello world",
+                "Second (page)\ncafé",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_unicode_and_object_streams() {
+        // Two-byte codes mapped to Japanese through a ToUnicode CMap, with
+        // the page tree (objects 6 and 7) packed in an object stream.
+        let cmap = b"/CIDInit /ProcSet findresource begin 12 dict begin begincmap\n\
+            1 begincodespacerange <0000> <FFFF> endcodespacerange\n\
+            2 beginbfchar <0001> <8ACB> <0002> <6C42> endbfchar\n\
+            1 beginbfrange <0010> <0011> <66F8> endbfrange\n\
+            endcmap CMapName currentdict /CMap defineresource pop end end";
+        let content = b"BT /F1 10 Tf 10 10 Td <0001000200100011> Tj ET";
+        let packed = "<< /Type /Pages /Kids [7 0 R] /Count 1 >> \
+            << /Type /Page /Parent 6 0 R /Contents 3 0 R \
+            /Resources << /Font << /F1 4 0 R >> >> >>";
+        let header = format!("6 0 7 {} ", packed.find("<< /Type /Page ").unwrap());
+        let pdf = build(
+            &[
+                b"<< /Type /Catalog /Pages 6 0 R >>".to_vec(),
+                stream("", cmap),
+                stream("", content),
+                b"<< /Type /Font /Subtype /Type0 /BaseFont /Gothic /Encoding /Identity-H \
+                  /ToUnicode 2 0 R >>"
+                    .to_vec(),
+                stream(
+                    &format!(
+                        "/Type /ObjStm /N 2 /First {} /Filter /FlateDecode",
+                        header.len()
+                    ),
+                    &deflate(format!("{}{}", header, packed).as_bytes()),
+                ),
+            ],
+            "",
+        );
+        let document = read_bytes(&pdf).unwrap();
+        assert_eq!(document.title, None);
+        assert_eq!(document.pages, vec!["請求書曹"]);
+    }
+
+    #[test]
+    fn test_unreadable() {
+        let encrypted = build(
+            &[b"<< /Type /Catalog /Pages 2 0 R >>".to_vec()],
+            "/Encrypt << /Filter /Standard /V 2 >>",
+        );
+        let err = read_bytes(&encrypted).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Unreadable>(),
+            Some(Unreadable::Encrypted(_))
+        ));
+
+        let scanned = build(
+            &[
+                b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+                b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+                b"<< /Type /Page /Parent 2 0 R /Contents 4 0 R \
+                  /Resources << /XObject << /Im0 5 0 R >> >> >>"
+                    .to_vec(),
+                stream("", b"q 612 0 0 792 0 0 cm /Im0 Do Q"),
+                stream("/Type /XObject /Subtype /Image /Width 1 /Height 1", b"\xff"),
+            ],
+            "",
+        );
+        let err = read_bytes(&scanned).unwrap_err();
+        assert!(err.to_string().contains("no extractable text"), "{}", err);
+
+        let err = read_bytes(b"hello").unwrap_err();
+        assert!(err.to_string().contains("not a PDF"), "{}", err);
+    }
+
+    #[test]
+    fn test_form_xobjects_and_inline_images() {
+        let pdf = build(
+            &[
+                b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+                b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+                b"<< /Type /Page /Parent 2 0 R /Contents 4 0 R \
+                  /Resources << /XObject << /Fm1 5 0 R >> /Font << /F1 6 0 R >> >> >>"
+                    .to_vec(),
+                stream(
+                    "",
+                    b"BI /W 2 /H 1 /BPC 8 /CS /G ID \x00(EI) EI\n/Fm1 Do \
+                      BT /F1 9 Tf 1 0 0 1 10 50 Tm (\\001nd the footer) Tj ET",
+                ),
+                stream(
+                    "/Type /XObject /Subtype /Form",
+                    b"BT /F1 9 Tf 1 0 0 1 10 100 Tm (Header) Tj ET",
+                ),
+                b"<< /Type /Font /Subtype /Type1 /Encoding << /Differences [ 1 /fi ] >> >>"
+                    .to_vec(),
+            ],
+            "",
+        );
+        let document = read_bytes(&pdf).unwrap();
+        assert_eq!(document.pages, vec!["Header\nfind the footer"]);
+    }
+
+    #[test]
+    fn test_lexer() {
+        let mut lexer = Lexer::new(
+            b"<< /A#20B 12 0 R /C [1 -2.5 (a\\(b\\)\\101) <4142 4>] /D true >> % comment",
+            0,
+        );
+        let Some(Object::Dict(dict)) = lexer.object() else {
+            panic!("expected a dictionary");
+        };
+        assert_eq!(dict["A B"], Object::Ref(12));
+        assert_eq!(
+            dict["C"],
+            Object::Array(vec![
+                Object::Number(1.0),
+                Object::Number(-2.5),
+                Object::String(b"a(b)A".to_vec()),
+                Object::String(b"AB@".to_vec()),
+            ])
+        );
+        assert_eq!(dict["D"], Object::Bool(true));
+        assert!(lexer.at_end());
+
+        // A file cut off inside an object's closing `>>`.
+        let truncated = b"%PDF-1.7\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R >";
+        let mut lexer = Lexer::new(truncated, 17);
+        assert!(matches!(lexer.indirect_object(), Some(Object::Dict(_))));
+        assert!(lexer.at_end());
+        assert!(read_bytes(truncated).is_err());
+
+        assert_eq!(ascii85(b"<~87cURD]j7BEbo7~>").unwrap(), b"Hello world");
+        assert_eq!(clean_text("  a   b \n\n\n c\n"), "a b\n\nc");
+    }
+}
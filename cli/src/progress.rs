@@ -0,0 +1,134 @@
+//! Ingest progress: an indicatif progress bar on a terminal, periodic log
+//! lines otherwise.
+
+use std::io::IsTerminal;
+use std::time::{Duration, Instant};
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/// Time between progress log lines when not drawing a progress bar.
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+const BAR_TEMPLATE: &str = "[{bar:30}] {pos}/{len} files, {msg}";
+
+/// Longest file name shown next to the bar before it is shortened.
+const MAX_NAME_LEN: usize = 40;
+
+pub struct Progress {
+    total: usize,
+    files: usize,
+    chunks: usize,
+    current: String,
+    /// Draw a progress bar rather than logging.
+    bar: Option<ProgressBar>,
+    last_log: Instant,
+}
+
+impl Progress {
+    /// Track `total` files. The bar is only drawn when `enabled` and stdout
+    /// is a terminal.
+    pub fn new(total: usize, enabled: bool) -> Self {
+        let bar = (enabled && std::io::stdout().is_terminal()).then(|| {
+            let bar =
+                ProgressBar::with_draw_target(Some(total as u64), ProgressDrawTarget::stdout());
+            bar.set_style(
+                ProgressStyle::with_template(BAR_TEMPLATE)
+                    .expect("valid progress template")
+                    .progress_chars("# "),
+            );
+            bar
+        });
+        Self {
+            total,
+            files: 0,
+            chunks: 0,
+            current: String::new(),
+            bar,
+            last_log: Instant::now(),
+        }
+    }
+
+    pub fn start_file(&mut self, path: &str) {
+        self.current = path.rsplit(['/', '\\']).next().unwrap_or(path).to_string();
+        self.tick();
+    }
+
+    pub fn finish_file(&mut self) {
+        self.files += 1;
+        if let Some(bar) = &self.bar {
+            bar.inc(1);
+        }
+        self.tick();
+    }
+
+    pub fn add_chunks(&mut self, chunks: usize) {
+        self.chunks += chunks;
+        self.tick();
+    }
+
+    /// Clear the bar so the summary starts on a clean line.
+    pub fn finish(&mut self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+
+    fn tick(&mut self) {
+        if let Some(bar) = &self.bar {
+            // indicatif limits how often this is redrawn.
+            bar.set_message(self.message());
+            return;
+        }
+        if self.last_log.elapsed() < LOG_INTERVAL && self.files < self.total {
+            return;
+        }
+        self.last_log = Instant::now();
+        tracing::info!(
+            "Progress: {}/{} files, {} chunks embedded",
+            self.files,
+            self.total,
+            self.chunks
+        );
+    }
+
+    /// The text after the bar's file count.
+    fn message(&self) -> String {
+        format!(
+            "{} chunks  {}",
+            self.chunks,
+            shorten(&self.current, MAX_NAME_LEN)
+        )
+    }
+}
+
+/// Keep the end of `name` (extension included) when it is too long.
+fn shorten(name: &str, max: usize) -> String {
+    let len = name.chars().count();
+    if len <= max {
+        return name.to_string();
+    }
+    let tail: String = name.chars().skip(len - (max - 3)).collect();
+    format!("...{}", tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_line() {
+        let mut progress = Progress::new(4, false);
+        progress.start_file("/docs/notes/a.md");
+        progress.finish_file();
+        progress.add_chunks(12);
+        assert_eq!(progress.files, 1);
+        assert_eq!(progress.message(), "12 chunks  a.md");
+        assert!(ProgressStyle::with_template(BAR_TEMPLATE).is_ok());
+    }
+
+    #[test]
+    fn test_shorten() {
+        assert_eq!(shorten("short.md", 10), "short.md");
+        assert_eq!(shorten("a-very-long-name.md", 10), "...name.md");
+    }
+}
@@ -1,136 +1,1052 @@
-use anyhow::Result;
-use mcp_hybrid_search_common::config::AppConfig;
-use mcp_hybrid_search_common::types::{ChunkPayload, ExportedChunk, SearchFilters, SearchResult};
+use anyhow::{Context, Result};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use mcp_hybrid_search_common::config::{AppConfig, QdrantCollectionConfig};
+use mcp_hybrid_search_common::qdrant::{connect, count_under_prefix, fetch};
+use mcp_hybrid_search_common::sparse::{self, SPARSE_VECTOR};
+use mcp_hybrid_search_common::types::{
+    path_ancestors, path_prefix_directory, ChunkLocation, ChunkPayload, ExportedChunk,
+    SearchFilters, SearchResult, VectorSearchParams, DEFAULT_SNIPPET_LENGTH, PATH_PREFIXES,
+};
 use qdrant_client::qdrant::{
-    Condition, CreateCollectionBuilder, Distance, Filter, GetPointsBuilder, PointId, PointStruct,
-    ScalarQuantizationBuilder, ScrollPointsBuilder, SearchPointsBuilder, UpsertPointsBuilder,
-    VectorParamsBuilder,
+    create_vector_name_request, quantization_config, quantization_config_diff, vectors_config,
+    vectors_config_diff, BinaryQuantizationBuilder, CollectionParams, CollectionParamsDiffBuilder,
+    CollectionStatus, Condition, CountPointsBuilder, CreateCollectionBuilder,
+    CreateFieldIndexCollectionBuilder, CreateVectorNameRequestBuilder, DeletePointsBuilder,
+    DenseVectorCreationConfigBuilder, Disabled, Distance, FieldType, Filter, GetPointsBuilder,
+    HnswConfigDiff, Modifier, PayloadIncludeSelector, PointId, PointStruct, PointsIdsList,
+    PrefetchQueryBuilder, Query, QueryPointsBuilder, RrfBuilder, ScalarQuantizationBuilder,
+    ScoredPoint, ScrollPointsBuilder, SearchParams, SearchParamsBuilder, SearchPointsBuilder,
+    SnapshotDescription, SparseVectorCreationConfigBuilder, SparseVectorParamsBuilder,
+    SparseVectorsConfigBuilder, UpdateCollectionBuilder, UpsertPointsBuilder, Vector, VectorInput,
+    VectorParamsBuilder, VectorParamsDiff, VectorParamsDiffBuilder, VectorParamsDiffMap,
+    VectorsConfigBuilder,
 };
 use qdrant_client::Qdrant;
 use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 /// Ensure the collection exists, creating it if necessary.
 pub async fn ensure_collection(config: &AppConfig) -> Result<()> {
-    let client = Qdrant::from_url(&config.qdrant_url).build()?;
+    let client = connect(config)?;
 
     let exists = client.collection_exists(&config.collection_name).await?;
 
     if !exists {
-        client
-            .create_collection(
-                CreateCollectionBuilder::new(&config.collection_name)
-                    .vectors_config(VectorParamsBuilder::new(
-                        config.embedding_dimension as u64,
-                        Distance::Cosine,
-                    ))
-                    .quantization_config(ScalarQuantizationBuilder::default()),
-            )
-            .await?;
+        let settings = &config.qdrant;
+        let mut vectors =
+            VectorParamsBuilder::new(config.embedding_dimension as u64, Distance::Cosine);
+        if let Some(on_disk) = settings.on_disk_vectors {
+            vectors = vectors.on_disk(on_disk);
+        }
+        let mut named = VectorsConfigBuilder::default();
+        named.add_named_vector_params(TEXT_VECTOR, vectors.clone());
+        if settings.title_vector {
+            named.add_named_vector_params(TITLE_VECTOR, vectors);
+        }
+        let mut builder =
+            CreateCollectionBuilder::new(&config.collection_name).vectors_config(named);
+        if config.sparse_lexical() {
+            let mut sparse = SparseVectorsConfigBuilder::default();
+            sparse.add_named_vector_params(
+                SPARSE_VECTOR,
+                SparseVectorParamsBuilder::default().modifier(Modifier::Idf),
+            );
+            builder = builder.sparse_vectors_config(sparse);
+        }
+        match quantization(settings) {
+            Some(quantization_config_diff::Quantization::Scalar(scalar)) => {
+                builder = builder.quantization_config(scalar);
+            }
+            Some(quantization_config_diff::Quantization::Binary(binary)) => {
+                builder = builder.quantization_config(binary);
+            }
+            _ => {}
+        }
+        if let Some(hnsw) = hnsw_config(settings) {
+            builder = builder.hnsw_config(hnsw);
+        }
+        if let Some(on_disk) = settings.on_disk_payload {
+            builder = builder.on_disk_payload(on_disk);
+        }
+        if let Some(shards) = settings.shard_number {
+            builder = builder.shard_number(shards);
+        }
+        if let Some(replicas) = settings.replication_factor {
+            builder = builder.replication_factor(replicas);
+        }
+        if let Some(write_consistency) = settings.write_consistency_factor {
+            builder = builder.write_consistency_factor(write_consistency);
+        }
+        let created = client.create_collection(builder).await;
+        if settings.replicated() {
+            // Qdrant's reason is in the error; say which settings it was for.
+            created.with_context(|| {
+                format!(
+                    "Could not create Qdrant collection '{}' with replication_factor {} \
+                     (each replica of a shard needs its own node of a cluster)",
+                    config.collection_name,
+                    settings.replication_factor.unwrap_or(1)
+                )
+            })?;
+        } else {
+            created?;
+        }
         tracing::info!("Created Qdrant collection '{}'", config.collection_name);
     } else {
         tracing::info!(
             "Qdrant collection '{}' already exists",
             config.collection_name
         );
+        update_settings(&client, config).await?;
+        add_vectors(&client, config).await?;
+    }
+
+    // Lets `get_project_info` order points by file_mtime and speeds up
+    // `modified_after` filters. Creating an existing index is a no-op.
+    client
+        .create_field_index(
+            CreateFieldIndexCollectionBuilder::new(
+                &config.collection_name,
+                "file_mtime",
+                FieldType::Datetime,
+            )
+            .wait(true),
+        )
+        .await?;
+    // Lets counts under a `path_prefix` facet on it, and speeds up deleting
+    // a document's chunks.
+    client
+        .create_field_index(
+            CreateFieldIndexCollectionBuilder::new(
+                &config.collection_name,
+                "source_path",
+                FieldType::Keyword,
+            )
+            .wait(true),
+        )
+        .await?;
+    index_path_prefixes(config).await?;
+
+    Ok(())
+}
+
+/// Index the chunks' `path_prefixes` once they all have them. Searches take
+/// the index as the sign they can filter `path_prefix` by it; until then
+/// (with chunks ingested before the field) they check it client-side.
+pub async fn index_path_prefixes(config: &AppConfig) -> Result<()> {
+    let client = connect(config)?;
+    let info = client.collection_info(&config.collection_name).await?;
+    if info
+        .result
+        .is_some_and(|r| r.payload_schema.contains_key(PATH_PREFIXES))
+    {
+        return Ok(());
+    }
+    let missing = client
+        .count(
+            CountPointsBuilder::new(&config.collection_name)
+                .filter(Filter::must([Condition::is_empty(PATH_PREFIXES)]))
+                .exact(true),
+        )
+        .await?
+        .result
+        .map_or(0, |r| r.count);
+    if missing > 0 {
+        tracing::info!(
+            "{} chunks in Qdrant collection '{}' predate path prefixes, so path_prefix filters \
+             are checked client-side; `ragctl ingest --force` re-ingests them",
+            missing,
+            config.collection_name
+        );
+        return Ok(());
+    }
+    client
+        .create_field_index(
+            CreateFieldIndexCollectionBuilder::new(
+                &config.collection_name,
+                PATH_PREFIXES,
+                FieldType::Keyword,
+            )
+            .wait(true),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Name of the vector of a chunk's text.
+pub const TEXT_VECTOR: &str = "text";
+
+/// Name of the optional vector of a chunk's title.
+pub const TITLE_VECTOR: &str = "title";
+
+/// The vectors a collection has, and whether `path_prefix` filters can use
+/// its chunks' `path_prefixes`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VectorLayout {
+    /// Vectors are named; collections created before were a single
+    /// unnamed vector, the text's.
+    pub named: bool,
+    /// Points can have a `title` vector.
+    pub title: bool,
+    /// Points can have a BM25 sparse vector, for
+    /// `lexical_backend = "qdrant-sparse"`.
+    pub sparse: bool,
+    /// Every point has `path_prefixes`, indexed.
+    pub path_prefixes: bool,
+    /// `source_path` is keyword-indexed, so counts can facet on it.
+    pub source_paths: bool,
+}
+
+impl VectorLayout {
+    fn of(params: Option<CollectionParams>) -> Self {
+        let params = params.unwrap_or_default();
+        let sparse = params
+            .sparse_vectors_config
+            .is_some_and(|sparse| sparse.map.contains_key(SPARSE_VECTOR));
+        match params.vectors_config.and_then(|v| v.config) {
+            Some(vectors_config::Config::ParamsMap(map)) => Self {
+                named: true,
+                title: map.map.contains_key(TITLE_VECTOR),
+                sparse,
+                path_prefixes: false,
+                source_paths: false,
+            },
+            _ => Self {
+                named: false,
+                title: false,
+                sparse,
+                path_prefixes: false,
+                source_paths: false,
+            },
+        }
     }
 
+    /// The name to use for the text vector: `None` for an unnamed one.
+    pub fn text_vector(&self) -> Option<&'static str> {
+        self.named.then_some(TEXT_VECTOR)
+    }
+}
+
+impl std::fmt::Display for VectorLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.named, self.title) {
+            (false, _) => f.write_str("unnamed (created before named vectors)")?,
+            (true, false) => f.write_str(TEXT_VECTOR)?,
+            (true, true) => write!(f, "{}, {}", TEXT_VECTOR, TITLE_VECTOR)?,
+        }
+        if self.sparse {
+            write!(f, ", {} (sparse)", SPARSE_VECTOR)?;
+        }
+        Ok(())
+    }
+}
+
+/// The vectors of the collection, or `None` when it doesn't exist.
+pub async fn vector_layout(config: &AppConfig) -> Result<Option<VectorLayout>> {
+    let client = connect(config)?;
+    if !client.collection_exists(&config.collection_name).await? {
+        return Ok(None);
+    }
+    let info = client
+        .collection_info(&config.collection_name)
+        .await?
+        .result;
+    let indexed = |field| {
+        info.as_ref()
+            .is_some_and(|r| r.payload_schema.contains_key(field))
+    };
+    Ok(Some(VectorLayout {
+        path_prefixes: indexed(PATH_PREFIXES),
+        source_paths: indexed("source_path"),
+        ..VectorLayout::of(info.and_then(|r| r.config).and_then(|c| c.params))
+    }))
+}
+
+/// Add the vectors the config asks for to an existing collection: the
+/// `title` vector of `[qdrant]`, and the sparse vector of
+/// `lexical_backend = "qdrant-sparse"`. Existing points get them when their
+/// files are next re-ingested.
+async fn add_vectors(client: &Qdrant, config: &AppConfig) -> Result<()> {
+    let Some(layout) = vector_layout(config).await? else {
+        return Ok(());
+    };
+    if config.qdrant.title_vector && !layout.title {
+        if layout.named {
+            let vector = DenseVectorCreationConfigBuilder::new(
+                config.embedding_dimension as u64,
+                Distance::Cosine,
+            );
+            add_vector(client, config, TITLE_VECTOR, vector).await;
+        } else {
+            tracing::warn!(
+                "Qdrant collection '{}' has a single unnamed vector, so it can't hold title \
+                 vectors; `ragctl reset` recreates it with named ones",
+                config.collection_name
+            );
+        }
+    }
+    if config.sparse_lexical() && !layout.sparse {
+        let vector = SparseVectorCreationConfigBuilder::new().modifier(Modifier::Idf);
+        add_vector(client, config, SPARSE_VECTOR, vector).await;
+    }
+    Ok(())
+}
+
+async fn add_vector(
+    client: &Qdrant,
+    config: &AppConfig,
+    name: &str,
+    vector: impl Into<create_vector_name_request::VectorConfig>,
+) {
+    let request =
+        CreateVectorNameRequestBuilder::new(&config.collection_name, name, vector).wait(true);
+    match client.create_vector_name(request).await {
+        Ok(_) => tracing::info!(
+            "Added the {} vector to Qdrant collection '{}'; existing chunks get it when their \
+             files are next re-ingested (`ragctl ingest --force` for all)",
+            name,
+            config.collection_name
+        ),
+        Err(e) => tracing::warn!(
+            "Could not add the {} vector to Qdrant collection '{}': {}; `ragctl reset` \
+             recreates it with one",
+            name,
+            config.collection_name,
+            e
+        ),
+    }
+}
+
+/// The HNSW settings of `[qdrant]`, if any is set.
+fn hnsw_config(settings: &QdrantCollectionConfig) -> Option<HnswConfigDiff> {
+    if settings.hnsw_m.is_none() && settings.hnsw_ef_construct.is_none() {
+        return None;
+    }
+    Some(HnswConfigDiff {
+        m: settings.hnsw_m,
+        ef_construct: settings.hnsw_ef_construct,
+        ..Default::default()
+    })
+}
+
+/// The quantization `[qdrant]` asks for, `Disabled` for "none" and `None`
+/// when it's unset or one `[qdrant]` can't set.
+fn quantization(
+    settings: &QdrantCollectionConfig,
+) -> Option<quantization_config_diff::Quantization> {
+    let quantization = match settings.quantization.as_deref()? {
+        "scalar" => {
+            let mut scalar = ScalarQuantizationBuilder::default();
+            if let Some(quantile) = settings.quantization_quantile {
+                scalar = scalar.quantile(quantile);
+            }
+            if let Some(always_ram) = settings.always_ram {
+                scalar = scalar.always_ram(always_ram);
+            }
+            scalar.build().into()
+        }
+        "binary" => {
+            let mut binary = BinaryQuantizationBuilder::default();
+            if let Some(always_ram) = settings.always_ram {
+                binary = binary.always_ram(always_ram);
+            }
+            binary.build().into()
+        }
+        "none" => Disabled {}.into(),
+        _ => return None,
+    };
+    Some(quantization)
+}
+
+/// Bring an existing collection's settings in line with `[qdrant]`. Qdrant
+/// changes them in place, rebuilding what it must in the background; a
+/// change it refuses is warned about, as recreating the collection is
+/// `ragctl reset`.
+async fn update_settings(client: &Qdrant, config: &AppConfig) -> Result<()> {
+    let Some(stored) = collection_settings(config).await? else {
+        return Ok(());
+    };
+    let (fixed, drift): (Vec<Drift>, Vec<Drift>) = settings_drift(&config.qdrant, &stored)
+        .into_iter()
+        .partition(|d| d.name == "shard_number");
+    for d in fixed {
+        tracing::warn!(
+            "Qdrant collection '{}' has {}; it is fixed when a collection is created, and \
+             `ragctl reset` recreates it",
+            config.collection_name,
+            d
+        );
+    }
+    if drift.is_empty() {
+        return Ok(());
+    }
+    let settings = &config.qdrant;
+    let mut builder = UpdateCollectionBuilder::new(&config.collection_name);
+    if let Some(hnsw) = hnsw_config(settings) {
+        builder = builder.hnsw_config(hnsw);
+    }
+    if let Some(on_disk) = settings.on_disk_vectors {
+        let diff: VectorParamsDiff = VectorParamsDiffBuilder::default().on_disk(on_disk).into();
+        builder = builder.vectors_config(if stored.vector_names.is_empty() {
+            vectors_config_diff::Config::Params(diff)
+        } else {
+            vectors_config_diff::Config::ParamsMap(VectorParamsDiffMap {
+                map: stored
+                    .vector_names
+                    .iter()
+                    .map(|name| (name.clone(), diff))
+                    .collect(),
+            })
+        });
+    }
+    let mut params = CollectionParamsDiffBuilder::default();
+    if let Some(on_disk) = settings.on_disk_payload {
+        params = params.on_disk_payload(on_disk);
+    }
+    if let Some(replicas) = settings.replication_factor {
+        params = params.replication_factor(replicas);
+    }
+    if let Some(write_consistency) = settings.write_consistency_factor {
+        params = params.write_consistency_factor(write_consistency);
+    }
+    builder = builder.params(params);
+    // Sent only when it changed, as Qdrant re-quantizes every segment. A
+    // quantile or `always_ram` alone tunes the stored quantization.
+    if drift
+        .iter()
+        .any(|d| QUANTIZATION_SETTINGS.contains(&d.name))
+    {
+        let settings = QdrantCollectionConfig {
+            quantization: Some(
+                settings
+                    .quantization
+                    .clone()
+                    .unwrap_or_else(|| stored.quantization.clone()),
+            ),
+            ..settings.clone()
+        };
+        if let Some(quantization) = quantization(&settings) {
+            builder = builder.quantization_config(quantization);
+        }
+    }
+    let changes = drift
+        .iter()
+        .map(Drift::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    match client.update_collection(builder).await {
+        Ok(_) => tracing::info!(
+            "Updated Qdrant collection '{}': {}; Qdrant applies it as it re-optimizes",
+            config.collection_name,
+            changes
+        ),
+        Err(e) => tracing::warn!(
+            "Qdrant collection '{}' differs from [qdrant] ({}) and could not be updated: {}; \
+             `ragctl reset` recreates it",
+            config.collection_name,
+            changes,
+            e
+        ),
+    }
     Ok(())
 }
 
-/// Upsert chunks with their embeddings into Qdrant.
+/// The index and storage settings a collection has in Qdrant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectionSettings {
+    pub hnsw_m: Option<u64>,
+    pub hnsw_ef_construct: Option<u64>,
+    /// Of the text vector.
+    pub on_disk_vectors: bool,
+    /// The named vectors, empty for a single unnamed one.
+    pub vector_names: Vec<String>,
+    pub on_disk_payload: bool,
+    /// "none", "scalar", "binary", or "product" and "other" for what
+    /// `[qdrant]` can't set.
+    pub quantization: String,
+    pub quantization_quantile: Option<f32>,
+    pub always_ram: bool,
+    pub shard_number: u32,
+    pub replication_factor: u32,
+    pub write_consistency_factor: u32,
+}
+
+impl std::fmt::Display for CollectionSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        write!(
+            f,
+            "HNSW m={}, ef_construct={}; vectors on disk: {}; payload on disk: {}; quantization: {}",
+            or_default(self.hnsw_m),
+            or_default(self.hnsw_ef_construct),
+            yes_no(self.on_disk_vectors),
+            yes_no(self.on_disk_payload),
+            self.quantization
+        )?;
+        if self.quantization == "scalar" {
+            write!(f, " (quantile {})", or_default(self.quantization_quantile))?;
+        }
+        if self.quantization != "none" {
+            write!(f, ", always in RAM: {}", yes_no(self.always_ram))?;
+        }
+        write!(
+            f,
+            "; shards: {}, replication factor: {}, write consistency factor: {}",
+            self.shard_number, self.replication_factor, self.write_consistency_factor
+        )
+    }
+}
+
+fn or_default<T: ToString>(value: Option<T>) -> String {
+    value.map_or("default".to_string(), |v| v.to_string())
+}
+
+/// The settings that make up the quantization, which are updated together.
+const QUANTIZATION_SETTINGS: [&str; 3] = ["quantization", "quantization_quantile", "always_ram"];
+
+/// A `[qdrant]` setting the collection doesn't have.
+#[derive(Debug, PartialEq)]
+pub struct Drift {
+    pub name: &'static str,
+    pub stored: String,
+    pub wanted: String,
+}
+
+impl std::fmt::Display for Drift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} -> {}", self.name, self.stored, self.wanted)
+    }
+}
+
+/// The settings `[qdrant]` sets that `stored` doesn't have.
+pub fn settings_drift(wanted: &QdrantCollectionConfig, stored: &CollectionSettings) -> Vec<Drift> {
+    let mut drift = Vec::new();
+    let mut compare = |name: &'static str, stored: String, wanted: Option<String>| {
+        if let Some(wanted) = wanted.filter(|wanted| *wanted != stored) {
+            drift.push(Drift {
+                name,
+                stored,
+                wanted,
+            });
+        }
+    };
+    compare(
+        "hnsw_m",
+        or_default(stored.hnsw_m),
+        wanted.hnsw_m.map(|v| v.to_string()),
+    );
+    compare(
+        "hnsw_ef_construct",
+        or_default(stored.hnsw_ef_construct),
+        wanted.hnsw_ef_construct.map(|v| v.to_string()),
+    );
+    compare(
+        "on_disk_vectors",
+        stored.on_disk_vectors.to_string(),
+        wanted.on_disk_vectors.map(|v| v.to_string()),
+    );
+    compare(
+        "on_disk_payload",
+        stored.on_disk_payload.to_string(),
+        wanted.on_disk_payload.map(|v| v.to_string()),
+    );
+    compare(
+        "quantization",
+        stored.quantization.clone(),
+        wanted.quantization.clone(),
+    );
+    // The quantization's own settings, once it is what `[qdrant]` asks for.
+    let quantization = wanted
+        .quantization
+        .as_deref()
+        .unwrap_or(&stored.quantization);
+    if quantization == "scalar" {
+        compare(
+            "quantization_quantile",
+            or_default(stored.quantization_quantile),
+            wanted.quantization_quantile.map(|v| v.to_string()),
+        );
+    }
+    if matches!(quantization, "scalar" | "binary") {
+        compare(
+            "always_ram",
+            stored.always_ram.to_string(),
+            wanted.always_ram.map(|v| v.to_string()),
+        );
+    }
+    compare(
+        "shard_number",
+        stored.shard_number.to_string(),
+        wanted.shard_number.map(|v| v.to_string()),
+    );
+    compare(
+        "replication_factor",
+        stored.replication_factor.to_string(),
+        wanted.replication_factor.map(|v| v.to_string()),
+    );
+    compare(
+        "write_consistency_factor",
+        stored.write_consistency_factor.to_string(),
+        wanted.write_consistency_factor.map(|v| v.to_string()),
+    );
+    drift
+}
+
+/// The collection's stored settings, or `None` when it doesn't exist.
+pub async fn collection_settings(config: &AppConfig) -> Result<Option<CollectionSettings>> {
+    let client = connect(config)?;
+    if !client.collection_exists(&config.collection_name).await? {
+        return Ok(None);
+    }
+    let info = client.collection_info(&config.collection_name).await?;
+    let collection = info.result.and_then(|r| r.config).unwrap_or_default();
+    let hnsw = collection.hnsw_config.unwrap_or_default();
+    let params = collection.params.unwrap_or_default();
+    // qdrant-client deprecates these flags for `memory`, which the `[qdrant]`
+    // settings don't map to and older servers don't report.
+    #[allow(deprecated)]
+    let (on_disk_vectors, vector_names) = match params.vectors_config.and_then(|v| v.config) {
+        Some(vectors_config::Config::Params(vectors)) => (vectors.on_disk.unwrap_or(false), vec![]),
+        Some(vectors_config::Config::ParamsMap(map)) => (
+            map.map
+                .get(TEXT_VECTOR)
+                .and_then(|vectors| vectors.on_disk)
+                .unwrap_or(false),
+            map.map.keys().cloned().collect(),
+        ),
+        None => (false, vec![]),
+    };
+    #[allow(deprecated)]
+    let on_disk_payload = params.on_disk_payload;
+    #[allow(deprecated)]
+    let (quantization, quantization_quantile, always_ram) =
+        match collection.quantization_config.and_then(|q| q.quantization) {
+            None => ("none", None, false),
+            Some(quantization_config::Quantization::Scalar(scalar)) => (
+                "scalar",
+                scalar.quantile,
+                scalar.always_ram.unwrap_or(false),
+            ),
+            Some(quantization_config::Quantization::Binary(binary)) => {
+                ("binary", None, binary.always_ram.unwrap_or(false))
+            }
+            Some(quantization_config::Quantization::Product(product)) => {
+                ("product", None, product.always_ram.unwrap_or(false))
+            }
+            Some(_) => ("other", None, false),
+        };
+    Ok(Some(CollectionSettings {
+        shard_number: params.shard_number,
+        replication_factor: params.replication_factor.unwrap_or(1),
+        write_consistency_factor: params.write_consistency_factor.unwrap_or(1),
+        hnsw_m: hnsw.m,
+        hnsw_ef_construct: hnsw.ef_construct,
+        on_disk_vectors,
+        vector_names,
+        on_disk_payload,
+        quantization: quantization.to_string(),
+        quantization_quantile,
+        always_ram,
+    }))
+}
+
+/// Vector size of the collection, or `None` when it doesn't exist yet.
+pub async fn collection_dimension(config: &AppConfig) -> Result<Option<u64>> {
+    let client = connect(config)?;
+    if !client.collection_exists(&config.collection_name).await? {
+        return Ok(None);
+    }
+    let info = client.collection_info(&config.collection_name).await?;
+    Ok(info
+        .result
+        .and_then(|r| r.config)
+        .and_then(|c| c.params)
+        .and_then(|p| p.vectors_config)
+        .and_then(|v| v.config)
+        .and_then(|c| match c {
+            vectors_config::Config::Params(params) => Some(params.size),
+            vectors_config::Config::ParamsMap(map) => map.map.get(TEXT_VECTOR).map(|p| p.size),
+        }))
+}
+
+/// Upsert chunks with their embeddings into Qdrant, and the embeddings of
+/// their titles in `titles` when the collection has a title vector.
 pub async fn upsert_chunks(
     config: &AppConfig,
     chunks: &[ChunkPayload],
     embeddings: &[Vec<f32>],
+    titles: &HashMap<String, Vec<f32>>,
 ) -> Result<()> {
-    let client = Qdrant::from_url(&config.qdrant_url).build()?;
+    let client = connect(config)?;
+    let layout = vector_layout(config).await?.unwrap_or(VectorLayout {
+        named: true,
+        title: false,
+        sparse: false,
+        path_prefixes: false,
+        source_paths: false,
+    });
 
     let points: Vec<PointStruct> = chunks
         .iter()
         .zip(embeddings.iter())
         .map(|(chunk, emb)| {
             let payload = serde_json::to_value(chunk).unwrap();
-            let payload_map: std::collections::HashMap<String, Value> =
-                serde_json::from_value(payload).unwrap();
+            let mut payload_map: HashMap<String, Value> = serde_json::from_value(payload).unwrap();
+            payload_map.insert(
+                PATH_PREFIXES.to_string(),
+                path_ancestors(&chunk.source_path).into(),
+            );
 
             let id = Uuid::parse_str(&chunk.chunk_id).unwrap_or_else(|_| Uuid::new_v4());
 
-            PointStruct::new(id.to_string(), emb.clone(), payload_map)
+            if !layout.named && !layout.sparse {
+                return PointStruct::new(id.to_string(), emb.clone(), payload_map);
+            }
+            // An unnamed vector next to named ones is named "".
+            let text_vector = layout.text_vector().unwrap_or_default();
+            let mut vectors =
+                HashMap::from([(text_vector.to_string(), Vector::new_dense(emb.clone()))]);
+            if let Some(title) = titles.get(&chunk.title).filter(|_| layout.title) {
+                vectors.insert(TITLE_VECTOR.to_string(), Vector::new_dense(title.clone()));
+            }
+            if layout.sparse {
+                // Tantivy matches titles too.
+                let sparse = sparse::document_vector(&format!("{}\n{}", chunk.title, chunk.text));
+                vectors.insert(
+                    SPARSE_VECTOR.to_string(),
+                    Vector::new_sparse(sparse.indices, sparse.values),
+                );
+            }
+            PointStruct::new(id.to_string(), vectors, payload_map)
         })
         .collect();
 
-    // Upsert in batches of 100
-    for batch in points.chunks(100) {
-        client
-            .upsert_points(UpsertPointsBuilder::new(
-                &config.collection_name,
-                batch.to_vec(),
-            ))
-            .await?;
+    // Batches don't share points, so their order doesn't matter.
+    stream::iter(points.chunks(config.qdrant_upsert_batch_size))
+        .map(|batch| {
+            let builder = UpsertPointsBuilder::new(&config.collection_name, batch.to_vec())
+                .wait(config.qdrant_upsert_wait);
+            client.upsert_points(builder)
+        })
+        .buffer_unordered(config.qdrant_upsert_parallelism.max(1))
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    Ok(())
+}
+
+/// Wait until the collection's status is green: Qdrant has applied and
+/// indexed its updates. Returns whether it got there within `timeout`.
+pub async fn wait_for_green(config: &AppConfig, timeout: Duration) -> Result<bool> {
+    let client = connect(config)?;
+    let started = Instant::now();
+    loop {
+        let info = client.collection_info(&config.collection_name).await?;
+        if info
+            .result
+            .is_some_and(|r| r.status == CollectionStatus::Green as i32)
+        {
+            return Ok(true);
+        }
+        if started.elapsed() >= timeout {
+            return Ok(false);
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
     }
+}
+
+/// Delete the points of `source_path` whose id is not in `chunk_ids`:
+/// chunks the document no longer has.
+pub async fn delete_stale_chunks(
+    config: &AppConfig,
+    source_path: &str,
+    chunk_ids: &[String],
+) -> Result<()> {
+    let client = connect(config)?;
+    let keep: Vec<PointId> = chunk_ids.iter().map(|id| id.clone().into()).collect();
+    let filter = Filter {
+        must: vec![Condition::matches("source_path", source_path.to_string())],
+        must_not: vec![Condition::has_id(keep)],
+        ..Default::default()
+    };
+    client
+        .delete_points(
+            DeletePointsBuilder::new(&config.collection_name)
+                .points(filter)
+                .wait(true),
+        )
+        .await?;
+    Ok(())
+}
 
+/// Delete the points with the given chunk ids.
+pub async fn delete_chunks(config: &AppConfig, chunk_ids: &[String]) -> Result<()> {
+    if chunk_ids.is_empty() {
+        return Ok(());
+    }
+    let client = connect(config)?;
+    let ids: Vec<PointId> = chunk_ids.iter().map(|id| id.clone().into()).collect();
+    client
+        .delete_points(
+            DeletePointsBuilder::new(&config.collection_name)
+                .points(PointsIdsList { ids })
+                .wait(true),
+        )
+        .await?;
     Ok(())
 }
 
-/// Search Qdrant for similar vectors.
+/// Delete every point whose `source_path` payload equals `source_path`,
+/// returning how many there were.
+pub async fn delete_by_source_path(config: &AppConfig, source_path: &str) -> Result<u64> {
+    let filter = Filter::must([Condition::matches("source_path", source_path.to_string())]);
+    delete_by_filter(config, filter).await
+}
+
+/// Delete the points matching `filter`, returning how many there were
+/// (counted first: Qdrant doesn't report it). A missing collection has
+/// none, with a warning.
+pub async fn delete_by_filter(config: &AppConfig, filter: Filter) -> Result<u64> {
+    let client = connect(config)?;
+    if !client.collection_exists(&config.collection_name).await? {
+        tracing::warn!(
+            "Collection '{}' does not exist; nothing to delete",
+            config.collection_name
+        );
+        return Ok(0);
+    }
+    let count = client
+        .count(
+            CountPointsBuilder::new(&config.collection_name)
+                .filter(filter.clone())
+                .exact(true),
+        )
+        .await?
+        .result
+        .map_or(0, |r| r.count);
+    if count > 0 {
+        client
+            .delete_points(
+                DeletePointsBuilder::new(&config.collection_name)
+                    .points(filter)
+                    .wait(true),
+            )
+            .await?;
+    }
+    Ok(count)
+}
+
+/// Search Qdrant for similar vectors: the chunks' text's, or with
+/// `titles` their titles'.
 pub async fn search(
     config: &AppConfig,
     query_embedding: &[f32],
     top_k: usize,
     filters: &SearchFilters,
+    titles: bool,
+    params: VectorSearchParams,
 ) -> Result<Vec<SearchResult>> {
-    let client = Qdrant::from_url(&config.qdrant_url).build()?;
-
-    let mut conditions = Vec::new();
-
-    if let Some(ref source_type) = filters.source_type {
-        conditions.push(Condition::matches("source_type", source_type.clone()));
-    }
+    let client = connect(config)?;
+    let layout = vector_layout(config).await?;
+    let vector = search_vector(config, layout, titles)?;
+    let (filter, check_prefix) = prefix_filter(filters, layout);
 
     let mut builder = SearchPointsBuilder::new(
         &config.collection_name,
         query_embedding.to_vec(),
-        top_k as u64,
+        fetch(top_k, check_prefix) as u64,
     )
     .with_payload(true);
-
-    if !conditions.is_empty() {
-        builder = builder.filter(Filter::must(conditions));
+    if let Some(vector) = vector {
+        builder = builder.vector_name(vector);
+    }
+    if let Some(params) = search_params(params) {
+        builder = builder.params(params);
+    }
+    if let Some(filter) = filter {
+        builder = builder.filter(filter);
     }
 
     let results = client.search_points(builder).await?;
+    Ok(results
+        .result
+        .iter()
+        .map(to_search_result)
+        .filter(|r| filters.matches_path(&r.source_path))
+        .take(top_k)
+        .collect())
+}
+
+/// Like [`search`] and the BM25 search with their fusion, as one Qdrant
+/// query over the dense and sparse vectors (`lexical_backend =
+/// "qdrant-sparse"`).
+pub async fn hybrid_search(
+    config: &AppConfig,
+    query: &str,
+    query_embedding: &[f32],
+    top_k: usize,
+    filters: &SearchFilters,
+    titles: bool,
+    params: VectorSearchParams,
+) -> Result<Vec<SearchResult>> {
+    let client = connect(config)?;
+    let layout = vector_layout(config).await?;
+    if !layout.is_some_and(|layout| layout.sparse) {
+        anyhow::bail!(
+            "Qdrant collection '{}' has no sparse vectors; run `ragctl ingest --force` to add them",
+            config.collection_name
+        );
+    }
+    let vector = search_vector(config, layout, titles)?;
+    let (filter, check_prefix) = prefix_filter(filters, layout);
+    let terms = sparse::query_vector(query);
+
+    let mut dense = PrefetchQueryBuilder::default()
+        .query(Query::new_nearest(query_embedding.to_vec()))
+        .limit(30u64);
+    if let Some(vector) = vector {
+        dense = dense.using(vector);
+    }
+    if let Some(params) = search_params(params) {
+        dense = dense.params(params);
+    }
+    let mut lexical = PrefetchQueryBuilder::default()
+        .query(Query::new_nearest(VectorInput::new_sparse(
+            terms.indices,
+            terms.values,
+        )))
+        .using(SPARSE_VECTOR)
+        .limit(30u64);
+    if let Some(ref filter) = filter {
+        dense = dense.filter(filter.clone());
+        lexical = lexical.filter(filter.clone());
+    }
+    let builder = QueryPointsBuilder::new(&config.collection_name)
+        .add_prefetch(dense)
+        .add_prefetch(lexical)
+        .query(Query::new_rrf(RrfBuilder::with_k(60)))
+        .limit(fetch(top_k, check_prefix) as u64)
+        .with_payload(true);
 
-    let search_results: Vec<SearchResult> = results
+    let response = client.query(builder).await?;
+    Ok(response
         .result
         .iter()
-        .map(|point| {
-            let payload = &point.payload;
-            let chunk_id = get_payload_str(payload, "chunk_id");
-            let title = get_payload_str(payload, "title");
-            let source_path = get_payload_str(payload, "source_path");
-            let source_type = get_payload_str(payload, "source_type");
-            let text = get_payload_str(payload, "text");
-            let snippet = mcp_hybrid_search_common::types::truncate_snippet(&text, 200);
-
-            SearchResult {
-                chunk_id,
-                score: point.score as f64,
-                title,
-                source_path,
-                source_type,
-                snippet,
-            }
-        })
-        .collect();
+        .map(to_search_result)
+        .filter(|r| filters.matches_path(&r.source_path))
+        .take(top_k)
+        .collect())
+}
 
-    Ok(search_results)
+/// Qdrant's form of `params`, `None` to leave them to Qdrant.
+fn search_params(params: VectorSearchParams) -> Option<SearchParams> {
+    if params.is_default() {
+        return None;
+    }
+    let mut builder = SearchParamsBuilder::default();
+    if let Some(exact) = params.exact {
+        builder = builder.exact(exact);
+    }
+    if let Some(ef) = params.hnsw_ef {
+        builder = builder.hnsw_ef(ef);
+    }
+    Some(builder.build())
+}
+
+/// The dense vector to search: `title` for `titles`, else the text vector
+/// (`None` for an unnamed one).
+fn search_vector(
+    config: &AppConfig,
+    layout: Option<VectorLayout>,
+    titles: bool,
+) -> Result<Option<&'static str>> {
+    if titles {
+        if !layout.is_some_and(|layout| layout.title) {
+            anyhow::bail!(
+                "Qdrant collection '{}' has no title vectors (see `title_vector` under [qdrant])",
+                config.collection_name
+            );
+        }
+        Ok(Some(TITLE_VECTOR))
+    } else {
+        Ok(layout.and_then(|layout| layout.text_vector()))
+    }
+}
+
+/// [`search_filter`] with `path_prefix`: matched by the directory it's in
+/// against `path_prefixes` when the collection has them, exactly for a
+/// prefix ending in a separator. Also returns whether the results still
+/// need checking against the prefix (over-fetching for those that aren't
+/// in it): when it isn't a directory, or the collection's points predate
+/// `path_prefixes`.
+fn prefix_filter(filters: &SearchFilters, layout: Option<VectorLayout>) -> (Option<Filter>, bool) {
+    let mut filter = search_filter(filters);
+    let Some(ref prefix) = filters.path_prefix else {
+        return (filter, false);
+    };
+    let directory =
+        path_prefix_directory(prefix).filter(|_| layout.is_some_and(|l| l.path_prefixes));
+    let Some(directory) = directory else {
+        return (filter, true);
+    };
+    filter
+        .get_or_insert_with(Filter::default)
+        .must
+        .push(Condition::matches(PATH_PREFIXES, directory.to_string()));
+    (filter, directory != prefix)
+}
+
+fn search_filter(filters: &SearchFilters) -> Option<Filter> {
+    let mut conditions = Vec::new();
+
+    if let Some(ref source_type) = filters.source_type {
+        conditions.push(Condition::matches("source_type", source_type.clone()));
+    }
+    for tag in &filters.tags {
+        conditions.push(Condition::matches("tags", tag.clone()));
+    }
+    if let Some(ref language) = filters.language {
+        conditions.push(Condition::matches("language", language.clone()));
+    }
+    // Only OCR chunks store `ocr`.
+    let mut excluded = Vec::new();
+    match filters.ocr {
+        Some(true) => conditions.push(Condition::matches("ocr", true)),
+        Some(false) => excluded.push(Condition::matches("ocr", true)),
+        None => {}
+    }
+
+    (!conditions.is_empty() || !excluded.is_empty()).then(|| Filter {
+        must: conditions,
+        must_not: excluded,
+        ..Default::default()
+    })
+}
+
+fn to_search_result(point: &ScoredPoint) -> SearchResult {
+    let payload = &point.payload;
+    let text = get_payload_str(payload, "text");
+    SearchResult {
+        chunk_id: get_payload_str(payload, "chunk_id"),
+        score: point.score as f64,
+        title: get_payload_str(payload, "title"),
+        source_path: get_payload_str(payload, "source_path"),
+        source_type: get_payload_str(payload, "source_type"),
+        section: Some(get_payload_str(payload, "section")).filter(|s| !s.is_empty()),
+        location: get_payload_location(payload),
+        file_mtime: Some(get_payload_str(payload, "file_mtime")).filter(|s| !s.is_empty()),
+        snippet: mcp_hybrid_search_common::types::truncate_snippet(&text, DEFAULT_SNIPPET_LENGTH),
+        text: None,
+        document_match_count: None,
+    }
 }
 
 /// Get a chunk by its chunk_id from Qdrant.
 #[allow(dead_code)]
 pub async fn get_chunk(config: &AppConfig, chunk_id: &str) -> Result<Option<ChunkPayload>> {
-    let client = Qdrant::from_url(&config.qdrant_url).build()?;
+    let client = connect(config)?;
 
     let point_id: PointId = chunk_id.to_string().into();
 
@@ -148,6 +1064,16 @@ pub async fn get_chunk(config: &AppConfig, chunk_id: &str) -> Result<Option<Chun
             chunk_index: get_payload_str(payload, "chunk_index").parse().unwrap_or(0),
             text: get_payload_str(payload, "text"),
             updated_at: get_payload_str(payload, "updated_at"),
+            tags: get_payload_list(payload, "tags"),
+            section: Some(get_payload_str(payload, "section")).filter(|s| !s.is_empty()),
+            location: get_payload_location(payload),
+            file_mtime: Some(get_payload_str(payload, "file_mtime")).filter(|s| !s.is_empty()),
+            file_size_bytes: get_payload_str(payload, "file_size_bytes").parse().ok(),
+            language: Some(get_payload_str(payload, "language")).filter(|s| !s.is_empty()),
+            converter: Some(get_payload_str(payload, "converter")).filter(|s| !s.is_empty()),
+            ocr: get_payload_bool(payload, "ocr"),
+            encoding: Some(get_payload_str(payload, "encoding")).filter(|s| !s.is_empty()),
+            embedding_truncated: get_payload_bool(payload, "embedding_truncated"),
         };
         Ok(Some(chunk))
     } else {
@@ -157,14 +1083,83 @@ pub async fn get_chunk(config: &AppConfig, chunk_id: &str) -> Result<Option<Chun
 
 /// Delete the Qdrant collection.
 pub async fn delete_collection(config: &AppConfig) -> Result<()> {
-    let client = Qdrant::from_url(&config.qdrant_url).build()?;
+    let client = connect(config)?;
     client.delete_collection(&config.collection_name).await?;
     Ok(())
 }
 
+/// Exact number of points matching `filters`, with the filter searches
+/// use ([`get_collection_info`]'s count is an estimate); 0 when the
+/// collection doesn't exist.
+///
+/// A `path_prefix` that isn't a directory (see [`prefix_filter`]) is
+/// counted by document (see [`count_under_prefix`]), or checked
+/// client-side on the points scrolled when `source_path` isn't indexed.
+pub async fn count_points(config: &AppConfig, filters: &SearchFilters) -> Result<u64> {
+    let Some(layout) = vector_layout(config).await? else {
+        return Ok(0);
+    };
+    let client = connect(config)?;
+    let (filter, check_prefix) = prefix_filter(filters, Some(layout));
+
+    if !check_prefix {
+        let mut builder = CountPointsBuilder::new(&config.collection_name).exact(true);
+        if let Some(filter) = filter {
+            builder = builder.filter(filter);
+        }
+        let response = client.count(builder).await?;
+        return Ok(response.result.map(|r| r.count).unwrap_or(0));
+    }
+    if layout.source_paths {
+        return count_under_prefix(&client, config, filter, filters).await;
+    }
+
+    let mut total = 0;
+    let mut offset: Option<PointId> = None;
+    loop {
+        let mut builder = ScrollPointsBuilder::new(&config.collection_name)
+            .with_payload(PayloadIncludeSelector::new(vec!["source_path".to_string()]))
+            .limit(1000);
+        if let Some(filter) = filter.clone() {
+            builder = builder.filter(filter);
+        }
+        if let Some(next_offset) = offset.take() {
+            builder = builder.offset(next_offset);
+        }
+        let response = client.scroll(builder).await?;
+        total += response
+            .result
+            .iter()
+            .filter(|p| filters.matches_path(&get_payload_str(&p.payload, "source_path")))
+            .count() as u64;
+        offset = response.next_page_offset;
+        if offset.is_none() {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// Have Qdrant write a snapshot of the collection to its snapshot
+/// directory.
+pub async fn create_snapshot(config: &AppConfig) -> Result<SnapshotDescription> {
+    let client = connect(config)?;
+    if !client.collection_exists(&config.collection_name).await? {
+        anyhow::bail!(
+            "Qdrant collection '{}' does not exist",
+            config.collection_name
+        );
+    }
+    client
+        .create_snapshot(config.collection_name.as_str())
+        .await?
+        .snapshot_description
+        .context("Qdrant did not describe the snapshot it created")
+}
+
 /// Get collection point count.
 pub async fn get_collection_info(config: &AppConfig) -> Result<u64> {
-    let client = Qdrant::from_url(&config.qdrant_url).build()?;
+    let client = connect(config)?;
 
     let info = client.collection_info(&config.collection_name).await?;
     Ok(info
@@ -175,7 +1170,12 @@ pub async fn get_collection_info(config: &AppConfig) -> Result<u64> {
 
 /// Scroll all points from the collection, returning chunks with their embeddings.
 pub async fn export_all_chunks(config: &AppConfig) -> Result<Vec<ExportedChunk>> {
-    let client = Qdrant::from_url(&config.qdrant_url).build()?;
+    use qdrant_client::qdrant::vector_output::Vector as VectorOutput;
+
+    let client = connect(config)?;
+    let Some(layout) = vector_layout(config).await? else {
+        return Ok(Vec::new());
+    };
 
     let mut all_chunks = Vec::new();
     let mut offset: Option<PointId> = None;
@@ -207,24 +1207,30 @@ pub async fn export_all_chunks(config: &AppConfig) -> Result<Vec<ExportedChunk>>
                 chunk_index: get_payload_str(payload, "chunk_index").parse().unwrap_or(0),
                 text: get_payload_str(payload, "text"),
                 updated_at: get_payload_str(payload, "updated_at"),
+                tags: get_payload_list(payload, "tags"),
+                section: Some(get_payload_str(payload, "section")).filter(|s| !s.is_empty()),
+                location: get_payload_location(payload),
+                file_mtime: Some(get_payload_str(payload, "file_mtime")).filter(|s| !s.is_empty()),
+                file_size_bytes: get_payload_str(payload, "file_size_bytes").parse().ok(),
+                language: Some(get_payload_str(payload, "language")).filter(|s| !s.is_empty()),
+                converter: Some(get_payload_str(payload, "converter")).filter(|s| !s.is_empty()),
+                ocr: get_payload_bool(payload, "ocr"),
+                encoding: Some(get_payload_str(payload, "encoding")).filter(|s| !s.is_empty()),
+                embedding_truncated: get_payload_bool(payload, "embedding_truncated"),
             };
 
-            let embedding = point
-                .vectors
-                .as_ref()
-                .and_then(|v| v.get_vector())
-                .and_then(|vector| {
-                    use qdrant_client::qdrant::vector_output::Vector;
-                    match vector {
-                        Vector::Dense(dense) => Some(dense.data),
-                        _ => None,
-                    }
-                })
-                .unwrap_or_default();
+            // "" is the unnamed vector, alone or next to a sparse one.
+            let vector = |name: &str| match point.vectors.as_ref()?.get_vector_by_name(name)? {
+                VectorOutput::Dense(dense) => Some(dense.data),
+                _ => None,
+            };
+            let embedding = vector(layout.text_vector().unwrap_or_default()).unwrap_or_default();
+            let title_embedding = vector(TITLE_VECTOR).filter(|_| layout.title);
 
             all_chunks.push(ExportedChunk {
                 payload: chunk_payload,
                 embedding,
+                title_embedding,
             });
         }
 
@@ -241,7 +1247,7 @@ pub async fn export_all_chunks(config: &AppConfig) -> Result<Vec<ExportedChunk>>
 
 /// List all Qdrant collections with their point counts.
 pub async fn list_collections(config: &AppConfig) -> Result<Vec<(String, u64)>> {
-    let client = Qdrant::from_url(&config.qdrant_url).build()?;
+    let client = connect(config)?;
     let response = client.list_collections().await?;
 
     let mut results = Vec::new();
@@ -280,3 +1286,273 @@ fn get_payload_str(
         })
         .unwrap_or_default()
 }
+
+/// The chunk location fields of a payload, unset when missing.
+fn get_payload_location(
+    payload: &std::collections::HashMap<String, qdrant_client::qdrant::Value>,
+) -> ChunkLocation {
+    let number = |key| get_payload_str(payload, key).parse().ok();
+    ChunkLocation {
+        start_char: number("start_char"),
+        end_char: number("end_char"),
+        start_line: number("start_line"),
+        converted: get_payload_bool(payload, "converted"),
+    }
+}
+
+/// Whether a boolean payload field is set and true.
+fn get_payload_bool(
+    payload: &std::collections::HashMap<String, qdrant_client::qdrant::Value>,
+    key: &str,
+) -> bool {
+    use qdrant_client::qdrant::value::Kind;
+    matches!(
+        payload.get(key).and_then(|v| v.kind.as_ref()),
+        Some(Kind::BoolValue(true))
+    )
+}
+
+/// String items of a list payload field (empty when missing).
+fn get_payload_list(
+    payload: &std::collections::HashMap<String, qdrant_client::qdrant::Value>,
+    key: &str,
+) -> Vec<String> {
+    use qdrant_client::qdrant::value::Kind;
+    match payload.get(key).and_then(|v| v.kind.as_ref()) {
+        Some(Kind::ListValue(list)) => list
+            .values
+            .iter()
+            .filter_map(|v| match &v.kind {
+                Some(Kind::StringValue(s)) => Some(s.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_drift() {
+        let stored = CollectionSettings {
+            hnsw_m: Some(16),
+            hnsw_ef_construct: Some(100),
+            on_disk_vectors: false,
+            vector_names: vec![TEXT_VECTOR.to_string()],
+            on_disk_payload: true,
+            quantization: "scalar".to_string(),
+            quantization_quantile: None,
+            always_ram: true,
+            shard_number: 1,
+            replication_factor: 1,
+            write_consistency_factor: 1,
+        };
+        assert_eq!(
+            stored.to_string(),
+            "HNSW m=16, ef_construct=100; vectors on disk: no; payload on disk: yes; \
+             quantization: scalar (quantile default), always in RAM: yes; \
+             shards: 1, replication factor: 1, write consistency factor: 1"
+        );
+        // Unset settings are whatever the collection has.
+        assert!(settings_drift(&QdrantCollectionConfig::default(), &stored).is_empty());
+        let wanted = QdrantCollectionConfig {
+            hnsw_m: Some(32),
+            hnsw_ef_construct: Some(100),
+            on_disk_vectors: Some(true),
+            on_disk_payload: Some(true),
+            quantization_quantile: Some(0.95),
+            ..QdrantCollectionConfig::default()
+        };
+        let drift = |wanted: &QdrantCollectionConfig| -> Vec<String> {
+            settings_drift(wanted, &stored)
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        };
+        assert_eq!(
+            drift(&wanted),
+            vec![
+                "hnsw_m 16 -> 32",
+                "on_disk_vectors false -> true",
+                "quantization_quantile default -> 0.95"
+            ]
+        );
+
+        // Turning quantization off leaves its settings out.
+        let off = QdrantCollectionConfig {
+            quantization: Some("none".to_string()),
+            always_ram: Some(false),
+            ..QdrantCollectionConfig::default()
+        };
+        assert_eq!(drift(&off), vec!["quantization scalar -> none"]);
+        let binary = QdrantCollectionConfig {
+            quantization: Some("binary".to_string()),
+            always_ram: Some(false),
+            ..QdrantCollectionConfig::default()
+        };
+        assert_eq!(
+            drift(&binary),
+            vec!["quantization scalar -> binary", "always_ram true -> false"]
+        );
+        let cluster = QdrantCollectionConfig {
+            shard_number: Some(4),
+            replication_factor: Some(2),
+            write_consistency_factor: Some(1),
+            ..QdrantCollectionConfig::default()
+        };
+        assert_eq!(
+            drift(&cluster),
+            vec!["shard_number 1 -> 4", "replication_factor 1 -> 2"]
+        );
+    }
+
+    #[test]
+    fn test_quantization() {
+        let config = |quantization: Option<&str>| QdrantCollectionConfig {
+            quantization: quantization.map(str::to_string),
+            quantization_quantile: Some(0.9),
+            ..QdrantCollectionConfig::default()
+        };
+        assert!(quantization(&config(None)).is_none());
+        assert!(matches!(
+            quantization(&config(Some("none"))),
+            Some(quantization_config_diff::Quantization::Disabled(_))
+        ));
+        let Some(quantization_config_diff::Quantization::Scalar(scalar)) =
+            quantization(&config(Some("scalar")))
+        else {
+            panic!("not scalar quantization");
+        };
+        assert_eq!(scalar.quantile, Some(0.9));
+        assert!(matches!(
+            quantization(&config(Some("binary"))),
+            Some(quantization_config_diff::Quantization::Binary(_))
+        ));
+    }
+
+    use qdrant_client::qdrant::VectorsConfig;
+
+    #[test]
+    fn test_vector_layout() {
+        let params = || VectorParamsBuilder::new(4, Distance::Cosine).build();
+        let legacy = VectorLayout::of(Some(CollectionParams {
+            vectors_config: Some(VectorsConfig {
+                config: Some(vectors_config::Config::Params(params())),
+            }),
+            ..Default::default()
+        }));
+        assert!(!legacy.named && !legacy.title && !legacy.sparse);
+        assert_eq!(legacy.text_vector(), None);
+        assert_eq!(legacy.to_string(), "unnamed (created before named vectors)");
+
+        let mut named = VectorsConfigBuilder::default();
+        named.add_named_vector_params(TEXT_VECTOR, params());
+        named.add_named_vector_params(TITLE_VECTOR, params());
+        let mut sparse = SparseVectorsConfigBuilder::default();
+        sparse.add_named_vector_params(SPARSE_VECTOR, SparseVectorParamsBuilder::default());
+        let layout = VectorLayout::of(Some(CollectionParams {
+            vectors_config: Some(named.into()),
+            sparse_vectors_config: Some(sparse.into()),
+            ..Default::default()
+        }));
+        assert!(layout.named && layout.title && layout.sparse);
+        assert_eq!(layout.text_vector(), Some("text"));
+        assert_eq!(layout.to_string(), "text, title, bm25 (sparse)");
+    }
+
+    #[test]
+    fn test_prefix_filter() {
+        let layout = |path_prefixes| VectorLayout {
+            named: true,
+            title: false,
+            sparse: false,
+            path_prefixes,
+            source_paths: false,
+        };
+        let filters = |prefix: &str| SearchFilters {
+            path_prefix: Some(prefix.to_string()),
+            ..SearchFilters::default()
+        };
+        let condition = Condition::matches(PATH_PREFIXES, "/docs/".to_string());
+
+        let (filter, check) = prefix_filter(&filters("/docs/"), Some(layout(true)));
+        assert_eq!(filter.unwrap().must, vec![condition.clone()]);
+        assert!(!check);
+        // Narrowed to the directory, then checked.
+        let (filter, check) = prefix_filter(&filters("/docs/ap"), Some(layout(true)));
+        assert_eq!(filter.unwrap().must, vec![condition]);
+        assert!(check);
+        // Points without path_prefixes: only checked.
+        let (filter, check) = prefix_filter(&filters("/docs/"), Some(layout(false)));
+        assert!(filter.is_none());
+        assert!(check);
+        assert_eq!(
+            prefix_filter(&SearchFilters::default(), Some(layout(true))),
+            (None, false)
+        );
+    }
+
+    /// Two documents whose vectors are nearly the same: a search under one's
+    /// directory must not return the other.
+    #[tokio::test]
+    #[ignore = "needs a Qdrant at QDRANT_URL"]
+    async fn test_search_respects_path_prefix() {
+        let config = AppConfig {
+            qdrant_url: std::env::var("QDRANT_URL")
+                .unwrap_or_else(|_| "http://localhost:6334".to_string()),
+            collection_name: format!("test-path-prefix-{}", Uuid::new_v4()),
+            embedding_dimension: 4,
+            ..AppConfig::default()
+        };
+        ensure_collection(&config).await.unwrap();
+        let chunk = |path: &str| -> ChunkPayload {
+            serde_json::from_value(serde_json::json!({
+                "chunk_id": Uuid::new_v4().to_string(),
+                "source_path": path,
+                "source_type": "md",
+                "title": path,
+                "chunk_index": 0,
+                "text": "text",
+                "updated_at": "2026-01-01T00:00:00Z",
+            }))
+            .unwrap()
+        };
+        let chunks = [chunk("/docs/api/a.md"), chunk("/notes/b.md")];
+        let embeddings = [vec![1.0, 0.0, 0.0, 0.0], vec![0.99, 0.1, 0.0, 0.0]];
+        upsert_chunks(&config, &chunks, &embeddings, &HashMap::new())
+            .await
+            .unwrap();
+        assert!(vector_layout(&config).await.unwrap().unwrap().path_prefixes);
+
+        let query = [0.99, 0.1, 0.0, 0.0];
+        for prefix in ["/docs/", "/docs/a", "/docs/api/a.md"] {
+            let filters = SearchFilters {
+                path_prefix: Some(prefix.to_string()),
+                ..SearchFilters::default()
+            };
+            let results = search(&config, &query, 10, &filters, false, Default::default()).await;
+            let paths: Vec<String> = results
+                .unwrap()
+                .into_iter()
+                .map(|r| r.source_path)
+                .collect();
+            assert_eq!(paths, ["/docs/api/a.md"], "{}", prefix);
+            assert_eq!(count_points(&config, &filters).await.unwrap(), 1);
+        }
+        let all = search(
+            &config,
+            &query,
+            10,
+            &SearchFilters::default(),
+            false,
+            Default::default(),
+        )
+        .await;
+        assert_eq!(all.unwrap().len(), 2);
+
+        delete_collection(&config).await.unwrap();
+    }
+}
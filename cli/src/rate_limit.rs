@@ -0,0 +1,142 @@
+//! Client-side rate limiting of embedding requests.
+//!
+//! Token buckets for requests and (estimated) tokens per minute, shared by
+//! every sub-batch ingest has in flight. A request reserves its share up
+//! front, going into debt if the bucket is short, and sleeps until the debt
+//! is refilled; later requests queue up behind it instead of racing into the
+//! provider's 429s. A bucket holds a minute's worth, like the provider's own
+//! per-minute window, so a short run is never slowed down.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Bucket {
+    per_minute: f64,
+    /// Negative when requests have reserved more than there was.
+    available: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(per_minute: u32, now: Instant) -> Self {
+        Self {
+            per_minute: f64::from(per_minute),
+            available: f64::from(per_minute),
+            updated: now,
+        }
+    }
+
+    /// Take `amount` (at most a minute's worth, which a request larger than
+    /// the limit waits for), and return how long until the bucket is out of
+    /// debt.
+    fn reserve(&mut self, amount: f64, now: Instant) -> Duration {
+        let refill = now.duration_since(self.updated).as_secs_f64() * self.per_minute / 60.0;
+        self.available = (self.available + refill).min(self.per_minute);
+        self.updated = now;
+        self.available -= amount.min(self.per_minute);
+        if self.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.available * 60.0 / self.per_minute)
+        }
+    }
+}
+
+/// Limits on embedding requests and tokens per minute.
+pub struct RateLimiter {
+    requests: Option<Mutex<Bucket>>,
+    tokens: Option<Mutex<Bucket>>,
+    waited: Mutex<Duration>,
+}
+
+impl RateLimiter {
+    /// A limiter for `requests_per_minute` and `tokens_per_minute`; 0
+    /// leaves that one unlimited.
+    pub fn new(requests_per_minute: u32, tokens_per_minute: u32) -> Self {
+        let now = Instant::now();
+        let bucket =
+            |per_minute| (per_minute > 0).then(|| Mutex::new(Bucket::new(per_minute, now)));
+        Self {
+            requests: bucket(requests_per_minute),
+            tokens: bucket(tokens_per_minute),
+            waited: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Wait until a request of `tokens` tokens may be sent.
+    pub async fn acquire(&self, tokens: usize) {
+        let wait = self.reserve(tokens, Instant::now());
+        if !wait.is_zero() {
+            tracing::debug!(
+                "Waiting {:.1}s for the embedding rate limit",
+                wait.as_secs_f64()
+            );
+            *self.waited.lock().unwrap() += wait;
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn reserve(&self, tokens: usize, now: Instant) -> Duration {
+        let reserve = |bucket: &Option<Mutex<Bucket>>, amount: f64| {
+            bucket
+                .as_ref()
+                .map_or(Duration::ZERO, |b| b.lock().unwrap().reserve(amount, now))
+        };
+        reserve(&self.requests, 1.0).max(reserve(&self.tokens, tokens as f64))
+    }
+
+    /// Time requests have spent waiting on the limits so far.
+    pub fn waited(&self) -> Duration {
+        *self.waited.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_limit() {
+        let limiter = RateLimiter::new(60, 0);
+        let start = Instant::now();
+        // A minute's worth goes through at once; then one a second.
+        for _ in 0..60 {
+            assert_eq!(limiter.reserve(1_000_000, start), Duration::ZERO);
+        }
+        assert_eq!(limiter.reserve(1, start), Duration::from_secs(1));
+        assert_eq!(limiter.reserve(1, start), Duration::from_secs(2));
+        // Waiting requests paid their debt when it refilled.
+        let later = start + Duration::from_secs(3);
+        assert_eq!(limiter.reserve(1, later), Duration::ZERO);
+        assert_eq!(limiter.reserve(1, later), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_token_limit() {
+        let limiter = RateLimiter::new(0, 6000);
+        let start = Instant::now();
+        assert_eq!(limiter.reserve(5000, start), Duration::ZERO);
+        assert_eq!(limiter.reserve(1200, start), Duration::from_secs(2));
+        // A request over the limit waits for a full bucket, not forever.
+        let later = start + Duration::from_secs(2);
+        assert_eq!(limiter.reserve(20_000, later), Duration::from_secs(60));
+
+        let unlimited = RateLimiter::new(0, 0);
+        assert_eq!(unlimited.reserve(usize::MAX, start), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_waits() {
+        // 100 requests a second.
+        let limiter = RateLimiter::new(6000, 0);
+        for _ in 0..6000 {
+            limiter.acquire(10).await;
+        }
+        assert_eq!(limiter.waited(), Duration::ZERO);
+        let started = Instant::now();
+        limiter.acquire(10).await;
+        limiter.acquire(10).await;
+        assert!(started.elapsed() >= Duration::from_millis(15));
+        assert!(limiter.waited() >= Duration::from_millis(15));
+    }
+}
@@ -0,0 +1,239 @@
+//! Parsing JSON, YAML and TOML documents for ingest.
+//!
+//! Documents are read into a [`Node`] tree that keeps keys in document
+//! order. All three formats go through serde (YAML with `serde_yaml_ng`).
+//! YAML tags and multiple documents are reported as errors.
+
+use std::fmt;
+
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+/// A parsed document. Scalars keep their source text (`7070`, `true`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Scalar(String),
+    List(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+/// Key the toml crate wraps datetimes in when deserializing.
+const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+struct NodeVisitor;
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = Node;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON, YAML or TOML value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Node, E> {
+        Ok(Node::Scalar(v.to_string()))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Node, E> {
+        Ok(Node::Scalar(v.to_string()))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Node, E> {
+        Ok(Node::Scalar(v.to_string()))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Node, E> {
+        Ok(Node::Scalar(v.to_string()))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Node, E> {
+        Ok(Node::Scalar(v.to_string()))
+    }
+
+    fn visit_unit<E>(self) -> Result<Node, E> {
+        Ok(Node::Scalar("null".to_string()))
+    }
+
+    fn visit_none<E>(self) -> Result<Node, E> {
+        Ok(Node::Scalar("null".to_string()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Node::List(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+        let mut entries = Vec::new();
+        // YAML keys may be numbers or booleans; keep their text.
+        while let Some((key, value)) = map.next_entry::<Node, Node>()? {
+            let Node::Scalar(key) = key else {
+                return Err(serde::de::Error::custom("mapping keys must be scalars"));
+            };
+            entries.push((key, value));
+        }
+        let datetime = entries.len() == 1
+            && entries[0].0 == TOML_DATETIME_KEY
+            && matches!(entries[0].1, Node::Scalar(_));
+        if datetime {
+            return Ok(entries.remove(0).1);
+        }
+        Ok(Node::Map(entries))
+    }
+}
+
+pub fn parse_json(content: &str) -> anyhow::Result<Node> {
+    Ok(serde_json::from_str(content)?)
+}
+
+pub fn parse_toml(content: &str) -> anyhow::Result<Node> {
+    Ok(toml::from_str(content)?)
+}
+
+/// YAML is read into a [`serde_yaml_ng::Value`] first and converted from
+/// there, so mappings keep their document order.
+pub fn parse_yaml(content: &str) -> anyhow::Result<Node> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let value: serde_yaml_ng::Value = serde_yaml_ng::from_str(content)?;
+    Ok(Node::deserialize(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scalar(s: &str) -> Node {
+        Node::Scalar(s.to_string())
+    }
+
+    fn map(entries: &[(&str, Node)]) -> Node {
+        Node::Map(
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_json_and_toml_keep_order() {
+        let json = parse_json(r#"{"z": 1, "a": [true, null, 1.5]}"#).unwrap();
+        assert_eq!(
+            json,
+            map(&[
+                ("z", scalar("1")),
+                (
+                    "a",
+                    Node::List(vec![scalar("true"), scalar("null"), scalar("1.5")])
+                )
+            ])
+        );
+        let toml =
+            parse_toml("zeta = 1\nwhen = 1979-05-27T07:32:00Z\n[server]\nport = 7070\n").unwrap();
+        assert_eq!(
+            toml,
+            map(&[
+                ("zeta", scalar("1")),
+                ("when", scalar("1979-05-27T07:32:00Z")),
+                ("server", map(&[("port", scalar("7070"))]))
+            ])
+        );
+    }
+
+    #[test]
+    fn test_yaml_block_structure() {
+        let yaml = "---\n# comment\nserver:\n  listen_port: 7070  # inline comment\n  hosts:\n  - a.example\n  - \"b: quoted\"\nusers:\n  - name: ann\n    roles: [admin, 'ops']\n  - name: bob\n    meta: {team: x, on: true}\nempty:\n";
+        assert_eq!(
+            parse_yaml(yaml).unwrap(),
+            map(&[
+                (
+                    "server",
+                    map(&[
+                        ("listen_port", scalar("7070")),
+                        (
+                            "hosts",
+                            Node::List(vec![scalar("a.example"), scalar("b: quoted")])
+                        )
+                    ])
+                ),
+                (
+                    "users",
+                    Node::List(vec![
+                        map(&[
+                            ("name", scalar("ann")),
+                            ("roles", Node::List(vec![scalar("admin"), scalar("ops")]))
+                        ]),
+                        map(&[
+                            ("name", scalar("bob")),
+                            (
+                                "meta",
+                                map(&[("team", scalar("x")), ("on", scalar("true"))])
+                            )
+                        ])
+                    ])
+                ),
+                ("empty", scalar("null"))
+            ])
+        );
+    }
+
+    #[test]
+    fn test_yaml_block_scalars() {
+        let yaml = "literal: |\n  line one\n  line two\nfolded: >-\n  folded\n  text\n\n  next\nafter: x\n";
+        assert_eq!(
+            parse_yaml(yaml).unwrap(),
+            map(&[
+                ("literal", scalar("line one\nline two\n")),
+                ("folded", scalar("folded text\nnext")),
+                ("after", scalar("x"))
+            ])
+        );
+    }
+
+    #[test]
+    fn test_yaml_non_ascii_scalars() {
+        let yaml = "name: café\ntitle: 日本語\nauthors:\n  - José\ntags: [café, thé]\nmeta: {lieu: Zürich}\nclé: 値\n";
+        assert_eq!(
+            parse_yaml(yaml).unwrap(),
+            map(&[
+                ("name", scalar("café")),
+                ("title", scalar("日本語")),
+                ("authors", Node::List(vec![scalar("José")])),
+                ("tags", Node::List(vec![scalar("café"), scalar("thé")])),
+                ("meta", map(&[("lieu", scalar("Zürich"))])),
+                ("clé", scalar("値")),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_yaml_keys_and_aliases() {
+        let yaml = "base: &base {port: 1}\ncopy: *base\n1: one\ntrue: yes\n";
+        assert_eq!(
+            parse_yaml(yaml).unwrap(),
+            map(&[
+                ("base", map(&[("port", scalar("1"))])),
+                ("copy", map(&[("port", scalar("1"))])),
+                ("1", scalar("one")),
+                ("true", scalar("yes"))
+            ])
+        );
+        assert!(parse_yaml("[a]: 1\n").is_err());
+    }
+
+    #[test]
+    fn test_yaml_unsupported() {
+        assert!(parse_yaml("a: !custom 1\n").is_err());
+        assert!(parse_yaml("a: 1\n---\nb: 2\n").is_err());
+        assert!(parse_yaml("a: [1, 2\n").is_err());
+        assert!(parse_yaml("a: 1\n   b: 2\n").is_err());
+        assert!(parse_yaml("{not: closed").is_err());
+    }
+}
@@ -1,10 +1,13 @@
 use anyhow::Result;
 use mcp_hybrid_search_common::config::AppConfig;
-use mcp_hybrid_search_common::types::{ChunkPayload, SearchFilters, SearchResult};
+use mcp_hybrid_search_common::types::{
+    ChunkLocation, ChunkPayload, SearchFilters, SearchResult, DEFAULT_SNIPPET_LENGTH,
+};
 use std::path::Path;
-use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
+use tantivy::collector::{Count, TopDocs};
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, TermQuery};
 use tantivy::schema::*;
+use tantivy::snippet::SnippetGenerator;
 use tantivy::{doc, Index, IndexWriter, ReloadPolicy};
 
 /// Name used for the custom tokenizer when configured.
@@ -28,9 +31,87 @@ fn build_schema(tokenizer_name: &str) -> Schema {
     schema_builder.add_text_field("title", text_options.clone());
     schema_builder.add_text_field("body", text_options);
     schema_builder.add_text_field("source_type", STRING | STORED);
+    // Multi-valued; indexes created before tags existed don't have it.
+    schema_builder.add_text_field("tags", STRING | STORED);
+    schema_builder.add_text_field("section", STORED);
+    schema_builder.add_u64_field("start_char", STORED);
+    schema_builder.add_u64_field("end_char", STORED);
+    schema_builder.add_u64_field("start_line", STORED);
+    schema_builder.add_bool_field("converted", STORED);
+    schema_builder.add_date_field("file_mtime", INDEXED | STORED);
+    schema_builder.add_text_field("language", STRING | STORED);
+    // Set on OCR chunks only.
+    schema_builder.add_bool_field("ocr", INDEXED | STORED);
     schema_builder.build()
 }
 
+/// `file_mtime` as a Tantivy date, when it is set and valid RFC3339.
+fn parse_mtime(file_mtime: Option<&str>) -> Option<tantivy::DateTime> {
+    let datetime = chrono::DateTime::parse_from_rfc3339(file_mtime?).ok()?;
+    Some(tantivy::DateTime::from_timestamp_nanos(
+        datetime.timestamp_nanos_opt()?,
+    ))
+}
+
+/// A stored `file_mtime` back as RFC3339.
+fn format_mtime(mtime: tantivy::DateTime) -> String {
+    chrono::DateTime::from_timestamp_nanos(mtime.into_timestamp_nanos()).to_rfc3339()
+}
+
+/// The [`ChunkLocation`] fields, which indexes created before locations
+/// were recorded don't have.
+struct LocationFields {
+    start_char: Option<Field>,
+    end_char: Option<Field>,
+    start_line: Option<Field>,
+    converted: Option<Field>,
+}
+
+impl LocationFields {
+    fn new(schema: &Schema) -> Self {
+        Self {
+            start_char: schema.get_field("start_char").ok(),
+            end_char: schema.get_field("end_char").ok(),
+            start_line: schema.get_field("start_line").ok(),
+            converted: schema.get_field("converted").ok(),
+        }
+    }
+
+    fn add(&self, document: &mut TantivyDocument, location: &ChunkLocation) {
+        let numbers = [
+            (self.start_char, location.start_char),
+            (self.end_char, location.end_char),
+            (self.start_line, location.start_line),
+        ];
+        for (field, value) in numbers {
+            if let (Some(field), Some(value)) = (field, value) {
+                document.add_u64(field, value.into());
+            }
+        }
+        if let (Some(field), true) = (self.converted, location.converted) {
+            document.add_bool(field, true);
+        }
+    }
+
+    fn read(&self, doc: &TantivyDocument) -> ChunkLocation {
+        let number = |field: Option<Field>| {
+            doc.get_first(field?)
+                .and_then(|v| v.as_u64())
+                .and_then(|v| u32::try_from(v).ok())
+        };
+        ChunkLocation {
+            start_char: number(self.start_char),
+            end_char: number(self.end_char),
+            start_line: number(self.start_line),
+            converted: self
+                .converted
+                .and_then(|field| doc.get_first(field))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        }
+    }
+}
+
 /// Resolve the tokenizer name to use based on config.
 fn resolve_tokenizer_name(config: &AppConfig) -> &str {
     match config.tokenizer.as_str() {
@@ -148,6 +229,17 @@ pub fn index_chunks(config: &AppConfig, chunks: &[ChunkPayload]) -> Result<()> {
     let title_field = schema.get_field("title").unwrap();
     let body_field = schema.get_field("body").unwrap();
     let source_type_field = schema.get_field("source_type").unwrap();
+    let tags_field = schema.get_field("tags").ok();
+    let section_field = schema.get_field("section").ok();
+    let location_fields = LocationFields::new(&schema);
+    let file_mtime_field = schema.get_field("file_mtime").ok();
+    let language_field = schema.get_field("language").ok();
+    let ocr_field = schema.get_field("ocr").ok();
+    if tags_field.is_none() && chunks.iter().any(|c| !c.tags.is_empty()) {
+        tracing::warn!(
+            "Tantivy index predates tags; run `ragctl reset` and re-ingest to make them searchable"
+        );
+    }
 
     let mut writer: IndexWriter = index.writer(50_000_000)?;
 
@@ -156,15 +248,103 @@ pub fn index_chunks(config: &AppConfig, chunks: &[ChunkPayload]) -> Result<()> {
         let term = tantivy::Term::from_field_text(chunk_id_field, &chunk.chunk_id);
         writer.delete_term(term);
 
-        writer.add_document(doc!(
+        let mut document = doc!(
             chunk_id_field => chunk.chunk_id.clone(),
             source_path_field => chunk.source_path.clone(),
             title_field => chunk.title.clone(),
             body_field => chunk.text.clone(),
             source_type_field => chunk.source_type.clone(),
-        ))?;
+        );
+        if let Some(tags_field) = tags_field {
+            for tag in &chunk.tags {
+                document.add_text(tags_field, tag);
+            }
+        }
+        if let (Some(section_field), Some(section)) = (section_field, &chunk.section) {
+            document.add_text(section_field, section);
+        }
+        location_fields.add(&mut document, &chunk.location);
+        if let (Some(field), Some(mtime)) =
+            (file_mtime_field, parse_mtime(chunk.file_mtime.as_deref()))
+        {
+            document.add_date(field, mtime);
+        }
+        if let (Some(field), Some(language)) = (language_field, &chunk.language) {
+            document.add_text(field, language);
+        }
+        if let (Some(field), true) = (ocr_field, chunk.ocr) {
+            document.add_bool(field, true);
+        }
+        writer.add_document(document)?;
+    }
+
+    writer.commit()?;
+    Ok(())
+}
+
+/// Delete every document whose `source_path` is one of `source_paths`.
+pub fn delete_by_source_paths(config: &AppConfig, source_paths: &[String]) -> Result<()> {
+    if source_paths.is_empty() {
+        return Ok(());
+    }
+    let index = open_or_create_index(config)?;
+    let source_path_field = index.schema().get_field("source_path").unwrap();
+
+    let mut writer: IndexWriter = index.writer(50_000_000)?;
+    for source_path in source_paths {
+        writer.delete_term(tantivy::Term::from_field_text(
+            source_path_field,
+            source_path,
+        ));
+    }
+    writer.commit()?;
+    Ok(())
+}
+
+/// Delete the documents with the given chunk ids.
+pub fn delete_chunks(config: &AppConfig, chunk_ids: &[String]) -> Result<()> {
+    if chunk_ids.is_empty() {
+        return Ok(());
+    }
+    let index = open_or_create_index(config)?;
+    let chunk_id_field = index.schema().get_field("chunk_id").unwrap();
+
+    let mut writer: IndexWriter = index.writer(50_000_000)?;
+    for chunk_id in chunk_ids {
+        writer.delete_term(tantivy::Term::from_field_text(chunk_id_field, chunk_id));
     }
+    writer.commit()?;
+    Ok(())
+}
+
+/// Delete the documents of each source path whose chunk id is not among
+/// the given ones: chunks a document no longer has, such as the tail of a
+/// file that got shorter.
+pub fn delete_stale_chunks(config: &AppConfig, current: &[(String, Vec<String>)]) -> Result<()> {
+    if current.is_empty() {
+        return Ok(());
+    }
+    let index = open_or_create_index(config)?;
+    let schema = index.schema();
+    let chunk_id_field = schema.get_field("chunk_id").unwrap();
+    let source_path_field = schema.get_field("source_path").unwrap();
+    let term_query = |field, text: &str| -> Box<dyn Query> {
+        Box::new(TermQuery::new(
+            tantivy::Term::from_field_text(field, text),
+            IndexRecordOption::Basic,
+        ))
+    };
 
+    let mut writer: IndexWriter = index.writer(50_000_000)?;
+    for (source_path, chunk_ids) in current {
+        let mut clauses = vec![(Occur::Must, term_query(source_path_field, source_path))];
+        clauses.extend(
+            chunk_ids
+                .iter()
+                .map(|id| (Occur::MustNot, term_query(chunk_id_field, id))),
+        );
+        writer.delete_query(Box::new(BooleanQuery::new(clauses)))?;
+    }
     writer.commit()?;
     Ok(())
 }
@@ -184,6 +364,12 @@ pub fn search(
     let title_field = schema.get_field("title").unwrap();
     let body_field = schema.get_field("body").unwrap();
     let source_type_field = schema.get_field("source_type").unwrap();
+    let tags_field = schema.get_field("tags").ok();
+    let section_field = schema.get_field("section").ok();
+    let location_fields = LocationFields::new(&schema);
+    let file_mtime_field = schema.get_field("file_mtime").ok();
+    let language_field = schema.get_field("language").ok();
+    let ocr_field = schema.get_field("ocr").ok();
 
     let reader = index
         .reader_builder()
@@ -196,6 +382,9 @@ pub fn search(
 
     let top_docs = searcher.search(&query, &TopDocs::with_limit(top_k))?;
 
+    let mut snippet_generator = SnippetGenerator::create(&searcher, &*query, body_field)?;
+    snippet_generator.set_max_num_chars(DEFAULT_SNIPPET_LENGTH);
+
     let mut results = Vec::new();
 
     for (score, doc_address) in top_docs {
@@ -206,6 +395,9 @@ pub fn search(
         let title = get_field_text(&retrieved_doc, title_field);
         let body = get_field_text(&retrieved_doc, body_field);
         let source_type = get_field_text(&retrieved_doc, source_type_field);
+        let section = section_field
+            .map(|field| get_field_text(&retrieved_doc, field))
+            .filter(|s| !s.is_empty());
 
         // Apply filters
         if let Some(ref ft) = filters.source_type {
@@ -218,8 +410,40 @@ pub fn search(
                 continue;
             }
         }
+        let tags = tags_field
+            .into_iter()
+            .flat_map(|field| retrieved_doc.get_all(field).filter_map(|v| v.as_str()));
+        if !filters.matches_tags(tags) {
+            continue;
+        }
+        if let Some(ref language) = filters.language {
+            let doc_language = language_field.map(|field| get_field_text(&retrieved_doc, field));
+            if doc_language.as_ref() != Some(language) {
+                continue;
+            }
+        }
+        if let Some(ocr) = filters.ocr {
+            let doc_ocr = ocr_field
+                .and_then(|field| retrieved_doc.get_first(field)?.as_bool())
+                .unwrap_or(false);
+            if doc_ocr != ocr {
+                continue;
+            }
+        }
 
-        let snippet = mcp_hybrid_search_common::types::truncate_snippet(&body, 200);
+        // Center the snippet on matched terms, falling back to a prefix when
+        // the match is only in the title.
+        let highlighted = snippet_generator.snippet(&body);
+        let snippet = if highlighted.is_empty() {
+            mcp_hybrid_search_common::types::truncate_snippet(&body, 200)
+        } else {
+            mcp_hybrid_search_common::types::highlight_ranges(
+                highlighted.fragment(),
+                highlighted.highlighted(),
+                &config.highlight_pre_tag,
+                &config.highlight_post_tag,
+            )
+        };
 
         results.push(SearchResult {
             chunk_id,
@@ -227,15 +451,26 @@ pub fn search(
             title,
             source_path,
             source_type,
+            section,
+            location: location_fields.read(&retrieved_doc),
+            file_mtime: file_mtime_field
+                .and_then(|field| retrieved_doc.get_first(field))
+                .and_then(|v| v.as_datetime())
+                .map(format_mtime),
             snippet,
+            text: None,
+            document_match_count: None,
         });
     }
 
     Ok(results)
 }
 
-/// Get total document count.
+/// Get total document count; 0 when there is no index yet.
 pub fn get_index_count(config: &AppConfig) -> Result<u64> {
+    if !Path::new(&config.tantivy_index_dir).exists() {
+        return Ok(0);
+    }
     let index = open_or_create_index(config)?;
     let reader = index
         .reader_builder()
@@ -245,9 +480,131 @@ pub fn get_index_count(config: &AppConfig) -> Result<u64> {
     Ok(searcher.num_docs())
 }
 
+/// Number of documents per detected language, most common first. Documents
+/// indexed before languages were detected are not counted.
+pub fn language_counts(config: &AppConfig) -> Result<Vec<(String, u64)>> {
+    term_counts(config, "language")
+}
+
+/// Number of documents per source type, most common first.
+pub fn source_type_counts(config: &AppConfig) -> Result<Vec<(String, u64)>> {
+    term_counts(config, "source_type")
+}
+
+/// Number of documents per value of the keyword field `name`, most common
+/// first.
+fn term_counts(config: &AppConfig, name: &str) -> Result<Vec<(String, u64)>> {
+    let index = open_or_create_index(config)?;
+    let Ok(field) = index.schema().get_field(name) else {
+        return Ok(Vec::new());
+    };
+    let reader = index
+        .reader_builder()
+        .reload_policy(ReloadPolicy::OnCommitWithDelay)
+        .try_into()?;
+    let searcher = reader.searcher();
+
+    let mut values = std::collections::BTreeSet::new();
+    for segment in searcher.segment_readers() {
+        let inverted_index = segment.inverted_index(field)?;
+        let mut terms = inverted_index.terms().stream()?;
+        while terms.advance() {
+            values.insert(String::from_utf8_lossy(terms.key()).into_owned());
+        }
+    }
+    // Term dictionaries still hold deleted documents' terms; a query
+    // doesn't count those.
+    let mut counts = Vec::new();
+    for value in values {
+        let term = tantivy::Term::from_field_text(field, &value);
+        let query = TermQuery::new(term, IndexRecordOption::Basic);
+        let count = searcher.search(&query, &Count)? as u64;
+        if count > 0 {
+            counts.push((value, count));
+        }
+    }
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(counts)
+}
+
 fn get_field_text(doc: &tantivy::TantivyDocument, field: tantivy::schema::Field) -> String {
     doc.get_first(field)
         .and_then(|v| v.as_str())
         .unwrap_or("")
         .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_hybrid_search_common::types::chunk_id;
+
+    fn chunks(source_path: &str, count: u32) -> Vec<ChunkPayload> {
+        (0..count)
+            .map(|i| ChunkPayload {
+                chunk_id: chunk_id(source_path, i),
+                source_path: source_path.to_string(),
+                source_type: source_path.rsplit('.').next().unwrap().to_string(),
+                title: "Doc".to_string(),
+                chunk_index: i,
+                text: format!("chunk {}", i),
+                updated_at: String::new(),
+                tags: vec![],
+                section: None,
+                location: ChunkLocation::default(),
+                file_mtime: None,
+                file_size_bytes: None,
+                converter: None,
+                ocr: false,
+                encoding: None,
+                embedding_truncated: false,
+                language: Some(if i == 0 { "ja" } else { "en" }.to_string()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reindex_shorter_file() {
+        let dir = std::env::temp_dir().join(format!("tantivy-{}", uuid::Uuid::new_v4()));
+        let config = AppConfig {
+            tantivy_index_dir: dir.to_string_lossy().into_owned(),
+            ..AppConfig::default()
+        };
+        index_chunks(&config, &chunks("/docs/a.md", 3)).unwrap();
+        index_chunks(&config, &chunks("/docs/b.pdf", 2)).unwrap();
+        assert_eq!(get_index_count(&config).unwrap(), 5);
+        assert_eq!(
+            language_counts(&config).unwrap(),
+            vec![("en".to_string(), 3), ("ja".to_string(), 2)]
+        );
+        assert_eq!(
+            source_type_counts(&config).unwrap(),
+            vec![("md".to_string(), 3), ("pdf".to_string(), 2)]
+        );
+
+        // Same ids, so re-indexing overwrites instead of adding.
+        let shorter = chunks("/docs/a.md", 1);
+        index_chunks(&config, &shorter).unwrap();
+        assert_eq!(get_index_count(&config).unwrap(), 5);
+
+        let keep = vec![shorter[0].chunk_id.clone()];
+        delete_stale_chunks(&config, &[("/docs/a.md".to_string(), keep)]).unwrap();
+        assert_eq!(get_index_count(&config).unwrap(), 3);
+        assert_eq!(
+            language_counts(&config).unwrap(),
+            vec![("ja".to_string(), 2), ("en".to_string(), 1)]
+        );
+
+        delete_chunks(&config, &[chunk_id("/docs/b.pdf", 1)]).unwrap();
+        assert_eq!(get_index_count(&config).unwrap(), 2);
+
+        delete_stale_chunks(&config, &[("/docs/b.pdf".to_string(), vec![])]).unwrap();
+        assert_eq!(get_index_count(&config).unwrap(), 1);
+        assert_eq!(
+            source_type_counts(&config).unwrap(),
+            vec![("md".to_string(), 1)]
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,141 @@
+//! Token counting for `chunk_unit = "tokens"` and the embedding input limit.
+//!
+//! This is an offline estimate of a BPE tokenizer like `cl100k_base`, not
+//! the real vocabulary: English words are counted as one token per four
+//! letters, numbers as one per three digits, and every other character as a
+//! piece of its own. A single space before a word belongs to that word's
+//! first token. ASCII pieces are one token; a non-ASCII character is one
+//! token per two bytes of UTF-8, since `cl100k_base` spends two or three on
+//! many CJK characters and most emoji. Real tokenizers merge more than this,
+//! so the estimate errs on the high side, which keeps chunks under a model's
+//! limit.
+
+/// Longest run of ASCII letters counted as one token.
+const LETTERS_PER_TOKEN: usize = 4;
+
+/// Longest run of digits counted as one token.
+const DIGITS_PER_TOKEN: usize = 3;
+
+/// Split `text` into token-sized pieces. The pieces concatenate back to
+/// `text`.
+pub fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        // A space directly before a word or symbol starts that token.
+        let first = match chars.peek() {
+            Some(&(i, next)) if c == ' ' && !next.is_whitespace() => {
+                chars.next();
+                end = i + next.len_utf8();
+                next
+            }
+            _ => c,
+        };
+
+        if first.is_whitespace() {
+            while let Some(&(i, next)) = chars.peek() {
+                // Leave a last space to lead the following word.
+                let leads_word = next == ' '
+                    && text[i + 1..]
+                        .chars()
+                        .next()
+                        .is_some_and(|after| !after.is_whitespace());
+                if !next.is_whitespace() || leads_word {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+        } else if first.is_ascii_alphabetic() || first.is_ascii_digit() {
+            let (same_class, limit): (fn(&char) -> bool, usize) = if first.is_ascii_digit() {
+                (char::is_ascii_digit, DIGITS_PER_TOKEN)
+            } else {
+                (char::is_ascii_alphabetic, LETTERS_PER_TOKEN)
+            };
+            let mut len = 1;
+            while len < limit {
+                match chars.peek() {
+                    Some(&(i, next)) if same_class(&next) => {
+                        end = i + next.len_utf8();
+                        chars.next();
+                        len += 1;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        tokens.push(&text[start..end]);
+    }
+    tokens
+}
+
+/// Estimated number of tokens in a piece returned by [`tokenize`].
+pub fn weight(piece: &str) -> usize {
+    piece
+        .chars()
+        .filter(|c| !c.is_ascii())
+        .map(|c| c.len_utf8().div_ceil(2))
+        .sum::<usize>()
+        .max(1)
+}
+
+/// Estimated number of tokens in `text`.
+pub fn count(text: &str) -> usize {
+    tokenize(text).into_iter().map(weight).sum()
+}
+
+/// The longest prefix of `text` with at most `max` tokens.
+pub fn truncate(text: &str, max: usize) -> &str {
+    let mut end = 0;
+    let mut tokens = 0;
+    for piece in tokenize(text) {
+        tokens += weight(piece);
+        if tokens > max {
+            break;
+        }
+        end += piece.len();
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("The vectorizer ran 12345 times."),
+            vec!["The", " vect", "oriz", "er", " ran", " 123", "45", " time", "s", "."]
+        );
+        assert_eq!(tokenize("a\n\n  b"), vec!["a", "\n\n ", " b"]);
+        assert_eq!(tokenize("日本語です"), vec!["日", "本", "語", "で", "す"]);
+        assert!(tokenize("").is_empty());
+    }
+
+    #[test]
+    fn test_pieces_rejoin() {
+        let text = "Mixed 日本語 text,\twith  spacing\n\nand 3.14159 numbers 🎉";
+        assert_eq!(tokenize(text).concat(), text);
+    }
+
+    #[test]
+    fn test_count_non_ascii() {
+        // Two tokens for each three-byte CJK character and four-byte emoji,
+        // one for two-byte Latin letters.
+        assert_eq!(count("日本語"), 6);
+        assert_eq!(count("🎉🚀"), 4);
+        assert_eq!(count("é"), 1);
+        assert_eq!(count(" 日"), 2);
+        assert_eq!(count("hello 世界"), 6);
+        assert_eq!(truncate("日本語", 5), "日本");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("one two three four", 2), "one two");
+        assert_eq!(truncate("短い", 10), "短い");
+        assert_eq!(count(truncate(&"word ".repeat(100), 7)), 7);
+    }
+}
@@ -0,0 +1,321 @@
+//! Fetching web pages for `ragctl ingest --url`.
+//!
+//! Pages are requested conditionally with the `ETag` / `Last-Modified`
+//! validators stored in the ingest state, so an unchanged page costs a 304
+//! rather than a re-embed. HTML is reduced to its main content as markdown;
+//! plain text and markdown pages are taken as they are. Any other content
+//! type is an error.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use reqwest::header::{
+    ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::StatusCode;
+
+use crate::html;
+
+/// Limit on the whole request, body included.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Redirects followed before a fetch fails.
+const MAX_REDIRECTS: usize = 5;
+
+const ACCEPTED_TYPES: &str =
+    "text/html, application/xhtml+xml, text/markdown;q=0.9, text/plain;q=0.8";
+
+/// Cache validators from a previous fetch of a page.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// A fetched page, converted to text.
+#[derive(Debug)]
+pub struct Page {
+    /// The `<title>`, for HTML pages that have one.
+    pub title: Option<String>,
+    /// Markdown for HTML pages, the body as is otherwise.
+    pub text: String,
+    pub validators: Validators,
+}
+
+#[derive(Debug)]
+pub enum Fetched {
+    Page(Page),
+    /// The server answered 304 to the conditional request.
+    NotModified,
+    /// The server answered 404 or 410.
+    Gone,
+}
+
+pub fn client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+        .user_agent(concat!("ragctl/", env!("CARGO_PKG_VERSION")))
+        .build()?)
+}
+
+/// Whether `source` names a web page rather than a local path.
+pub fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Fetch `url`, sending `previous` validators so an unchanged page comes back
+/// as [`Fetched::NotModified`]. Bodies over `max_bytes` are an error.
+pub async fn fetch(
+    client: &reqwest::Client,
+    url: &str,
+    previous: &Validators,
+    max_bytes: u64,
+) -> Result<Fetched> {
+    if !is_url(url) {
+        anyhow::bail!("Not an http(s) URL: {}", url);
+    }
+    let mut request = client.get(url).header(ACCEPT, ACCEPTED_TYPES);
+    if let Some(etag) = &previous.etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &previous.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+    let mut response = request.send().await.map_err(|e| describe(url, e))?;
+
+    if response.url().as_str() != url {
+        tracing::info!("{} redirected to {}", url, response.url());
+    }
+    match response.status() {
+        StatusCode::NOT_MODIFIED => return Ok(Fetched::NotModified),
+        StatusCode::NOT_FOUND | StatusCode::GONE => return Ok(Fetched::Gone),
+        status if !status.is_success() => anyhow::bail!("{} returned HTTP {}", url, status),
+        _ => {}
+    }
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let validators = Validators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    };
+    let content_type = header(CONTENT_TYPE).unwrap_or_default();
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let kind = match mime.as_str() {
+        "text/html" | "application/xhtml+xml" => BodyKind::Html,
+        "text/plain" | "text/markdown" | "text/x-markdown" => BodyKind::Text,
+        // No type given: judge by the body.
+        "" => BodyKind::Unknown,
+        _ => anyhow::bail!(
+            "{} is {}, not an HTML or text page",
+            url,
+            content_type.trim()
+        ),
+    };
+    if let Some(length) = header(CONTENT_LENGTH).and_then(|l| l.parse::<u64>().ok()) {
+        check_length(url, length, max_bytes)?;
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| describe(url, e))? {
+        body.extend_from_slice(&chunk);
+        check_length(url, body.len() as u64, max_bytes)?;
+    }
+    let body = String::from_utf8_lossy(&body);
+
+    let is_html = match kind {
+        BodyKind::Html => true,
+        BodyKind::Text => false,
+        BodyKind::Unknown => body.trim_start().starts_with('<'),
+    };
+    let page = if is_html {
+        Page {
+            title: html::title(&body),
+            text: html::to_markdown(&body),
+            validators,
+        }
+    } else {
+        Page {
+            title: None,
+            text: body.into_owned(),
+            validators,
+        }
+    };
+    Ok(Fetched::Page(page))
+}
+
+enum BodyKind {
+    Html,
+    Text,
+    Unknown,
+}
+
+fn check_length(url: &str, length: u64, max_bytes: u64) -> Result<()> {
+    if length > max_bytes {
+        anyhow::bail!(
+            "{} is larger than max_file_size_bytes ({} bytes)",
+            url,
+            max_bytes
+        );
+    }
+    Ok(())
+}
+
+fn describe(url: &str, error: reqwest::Error) -> anyhow::Error {
+    if error.is_timeout() {
+        anyhow::anyhow!(
+            "Timed out fetching {} after {}s",
+            url,
+            FETCH_TIMEOUT.as_secs()
+        )
+    } else if error.is_redirect() {
+        anyhow::anyhow!("{} redirected more than {} times", url, MAX_REDIRECTS)
+    } else {
+        anyhow::Error::new(error).context(format!("Could not fetch {}", url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve one canned response per request on a local port. `respond` gets
+    /// the request path and headers (lowercased) and returns the status
+    /// line, headers and body.
+    async fn serve<F>(respond: F) -> String
+    where
+        F: Fn(&str, &str) -> (String, Vec<(String, String)>, String) + Send + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request).to_ascii_lowercase();
+                let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                let (status, headers, body) = respond(&path, &request);
+                let mut response = format!(
+                    "HTTP/1.1 {}\r\ncontent-length: {}\r\nconnection: close\r\n",
+                    status,
+                    body.len()
+                );
+                for (name, value) in headers {
+                    response.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                response.push_str("\r\n");
+                response.push_str(&body);
+                socket.write_all(response.as_bytes()).await.ok();
+                socket.shutdown().await.ok();
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn header(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let base = serve(|path, request| match path {
+            "/page" if request.contains("if-none-match: \"v1\"") => {
+                ("304 Not Modified".into(), vec![], String::new())
+            }
+            "/page" => (
+                "200 OK".into(),
+                vec![
+                    header("content-type", "text/html; charset=utf-8"),
+                    header("etag", "\"v1\""),
+                ],
+                "<title>Runbook</title><main><h1>Deploys</h1><p>Ship it.</p></main>".into(),
+            ),
+            "/moved" => (
+                "301 Moved Permanently".into(),
+                vec![header("location", "/page")],
+                String::new(),
+            ),
+            "/notes.txt" => (
+                "200 OK".into(),
+                vec![header("content-type", "text/plain")],
+                "plain <b>text</b>".into(),
+            ),
+            "/logo.png" => (
+                "200 OK".into(),
+                vec![header("content-type", "image/png")],
+                "png".into(),
+            ),
+            "/error" => ("500 Internal Server Error".into(), vec![], String::new()),
+            _ => ("404 Not Found".into(), vec![], String::new()),
+        })
+        .await;
+        let client = client().unwrap();
+        let fetch = |path: &str, previous: Validators| {
+            let (client, url) = (client.clone(), format!("{}{}", base, path));
+            async move { fetch(&client, &url, &previous, 1 << 20).await }
+        };
+
+        let Fetched::Page(page) = fetch("/moved", Validators::default()).await.unwrap() else {
+            panic!("expected a page");
+        };
+        assert_eq!(page.title.as_deref(), Some("Runbook"));
+        assert_eq!(page.text, "# Deploys\n\nShip it.");
+        assert_eq!(page.validators.etag.as_deref(), Some("\"v1\""));
+
+        let unchanged = fetch("/page", page.validators).await.unwrap();
+        assert!(matches!(unchanged, Fetched::NotModified));
+
+        let Fetched::Page(page) = fetch("/notes.txt", Validators::default()).await.unwrap() else {
+            panic!("expected a page");
+        };
+        assert_eq!(page.text, "plain <b>text</b>");
+
+        assert!(matches!(
+            fetch("/missing", Validators::default()).await.unwrap(),
+            Fetched::Gone
+        ));
+        let err = fetch("/logo.png", Validators::default()).await.unwrap_err();
+        assert!(
+            err.to_string().contains("not an HTML or text page"),
+            "{}",
+            err
+        );
+        let err = fetch("/error", Validators::default()).await.unwrap_err();
+        assert!(err.to_string().contains("HTTP 500"), "{}", err);
+
+        let err = super::fetch(
+            &client,
+            &format!("{}/page", base),
+            &Validators::default(),
+            10,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("larger than max_file_size_bytes"),
+            "{}",
+            err
+        );
+    }
+}
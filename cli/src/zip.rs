@@ -0,0 +1,214 @@
+//! Reading zip archives: EPUB books and Office (DOCX, XLSX) documents.
+//!
+//! The whole archive is read into memory and indexed by its central
+//! directory. Stored and deflated entries are supported, which is all EPUB
+//! and Office files use; zip64 archives are rejected.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use anyhow::{Context, Result};
+
+/// Zip compression methods allowed in EPUB and Office files.
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x0201_4b50;
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+
+/// A zip archive read into memory, indexed by its central directory.
+pub struct Archive {
+    data: Vec<u8>,
+    entries: HashMap<String, Entry>,
+    max_entry_bytes: u64,
+}
+
+struct Entry {
+    method: u16,
+    compressed_size: usize,
+    /// Offset of the entry's local file header.
+    offset: usize,
+}
+
+impl Archive {
+    pub fn open(data: Vec<u8>, max_entry_bytes: u64) -> Result<Self> {
+        // The end-of-central-directory record is the last 22 bytes, followed
+        // by a comment of up to 64 KiB.
+        let earliest = data.len().saturating_sub(22 + u16::MAX as usize);
+        let eocd = (earliest..=data.len().saturating_sub(22))
+            .rev()
+            .find(|&i| u32_at(&data, i) == Some(END_OF_CENTRAL_DIRECTORY))
+            .context("no zip central directory found")?;
+        let count = u16_at(&data, eocd + 10).context("truncated zip")?;
+        let mut pos = u32_at(&data, eocd + 16).context("truncated zip")? as usize;
+        if count == u16::MAX || pos == u32::MAX as usize {
+            anyhow::bail!("zip64 archives are not supported");
+        }
+
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            let field = |offset: usize| u16_at(&data, pos + offset).context("truncated zip");
+            if u32_at(&data, pos) != Some(CENTRAL_DIRECTORY_HEADER) {
+                anyhow::bail!("corrupt zip central directory");
+            }
+            let method = field(10)?;
+            let compressed_size = u32_at(&data, pos + 20).context("truncated zip")? as usize;
+            let (name_len, extra_len, comment_len) = (
+                field(28)? as usize,
+                field(30)? as usize,
+                field(32)? as usize,
+            );
+            let offset = u32_at(&data, pos + 42).context("truncated zip")? as usize;
+            let name = data
+                .get(pos + 46..pos + 46 + name_len)
+                .context("truncated zip")?;
+            entries.insert(
+                String::from_utf8_lossy(name).into_owned(),
+                Entry {
+                    method,
+                    compressed_size,
+                    offset,
+                },
+            );
+            pos += 46 + name_len + extra_len + comment_len;
+        }
+        Ok(Self {
+            data,
+            entries,
+            max_entry_bytes,
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    pub fn read(&self, name: &str) -> Result<Vec<u8>> {
+        let entry = self
+            .entries
+            .get(name)
+            .with_context(|| format!("{} is missing from the archive", name))?;
+        let header = entry.offset;
+        if u32_at(&self.data, header) != Some(LOCAL_FILE_HEADER) {
+            anyhow::bail!("corrupt zip entry {}", name);
+        }
+        let name_len = u16_at(&self.data, header + 26).context("truncated zip")? as usize;
+        let extra_len = u16_at(&self.data, header + 28).context("truncated zip")? as usize;
+        let start = header + 30 + name_len + extra_len;
+        let compressed = self
+            .data
+            .get(start..start + entry.compressed_size)
+            .with_context(|| format!("truncated zip entry {}", name))?;
+
+        let mut out = Vec::new();
+        match entry.method {
+            STORED => out.extend_from_slice(compressed),
+            DEFLATED => {
+                flate2::read::DeflateDecoder::new(compressed)
+                    .take(self.max_entry_bytes + 1)
+                    .read_to_end(&mut out)
+                    .with_context(|| format!("could not decompress {}", name))?;
+            }
+            method => anyhow::bail!("{} uses unsupported compression method {}", name, method),
+        }
+        if out.len() as u64 > self.max_entry_bytes {
+            anyhow::bail!("{} expands beyond {} bytes", name, self.max_entry_bytes);
+        }
+        Ok(out)
+    }
+
+    pub fn read_text(&self, name: &str) -> Result<String> {
+        let bytes = self.read(name)?;
+        let text = String::from_utf8_lossy(&bytes);
+        Ok(text.strip_prefix('\u{feff}').unwrap_or(&text).to_string())
+    }
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Option<u16> {
+    let bytes = data.get(pos..pos + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A zip archive holding `files`. `mimetype` is stored, as EPUB
+    /// requires; everything else is deflated.
+    pub(crate) fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut central = Vec::new();
+        for (name, content) in files {
+            let (method, body) = if *name == "mimetype" {
+                (STORED, content.as_bytes().to_vec())
+            } else {
+                let mut encoder =
+                    flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(content.as_bytes()).unwrap();
+                (DEFLATED, encoder.finish().unwrap())
+            };
+            let offset = data.len() as u32;
+            // The reader doesn't check CRCs, so they are left as zero.
+            let sizes = [body.len() as u32, content.len() as u32];
+            data.extend(LOCAL_FILE_HEADER.to_le_bytes());
+            data.extend([20, 0, 0, 0]);
+            data.extend(method.to_le_bytes());
+            data.extend([0; 8]);
+            sizes.iter().for_each(|s| data.extend(s.to_le_bytes()));
+            data.extend((name.len() as u16).to_le_bytes());
+            data.extend([0, 0]);
+            data.extend(name.as_bytes());
+            data.extend(&body);
+
+            central.extend(CENTRAL_DIRECTORY_HEADER.to_le_bytes());
+            central.extend([20, 0, 20, 0, 0, 0]);
+            central.extend(method.to_le_bytes());
+            central.extend([0; 8]);
+            sizes.iter().for_each(|s| central.extend(s.to_le_bytes()));
+            central.extend((name.len() as u16).to_le_bytes());
+            central.extend([0; 12]);
+            central.extend(offset.to_le_bytes());
+            central.extend(name.as_bytes());
+        }
+        let central_offset = data.len() as u32;
+        let central_size = central.len() as u32;
+        data.extend(central);
+        data.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        data.extend([0; 4]);
+        data.extend((files.len() as u16).to_le_bytes());
+        data.extend((files.len() as u16).to_le_bytes());
+        data.extend(central_size.to_le_bytes());
+        data.extend(central_offset.to_le_bytes());
+        data.extend([0, 0]);
+        data
+    }
+
+    #[test]
+    fn test_read_entries() {
+        let archive = Archive::open(
+            zip(&[("mimetype", "text/plain"), ("a/b.xml", "\u{feff}<b/>")]),
+            1 << 20,
+        )
+        .unwrap();
+        assert!(archive.contains("a/b.xml"));
+        assert_eq!(archive.read("mimetype").unwrap(), b"text/plain");
+        assert_eq!(archive.read_text("a/b.xml").unwrap(), "<b/>");
+        let err = archive.read("missing.xml").unwrap_err();
+        assert!(
+            err.to_string().contains("missing from the archive"),
+            "{}",
+            err
+        );
+
+        let small = Archive::open(zip(&[("big.txt", &"x".repeat(100))]), 10).unwrap();
+        assert!(small.read("big.txt").is_err());
+        assert!(Archive::open(b"not a zip".to_vec(), 10).is_err());
+    }
+}
@@ -1,14 +1,53 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     #[serde(default = "default_qdrant_url")]
     pub qdrant_url: String,
 
+    /// API key sent to Qdrant as the `api-key` header, as Qdrant Cloud
+    /// requires. Unset, `QDRANT_API_KEY`; without either, none is sent.
+    #[serde(default)]
+    pub qdrant_api_key: Option<Secret>,
+
+    /// Qdrant's REST API, which snapshots are downloaded and uploaded
+    /// through. Unset, `qdrant_url` with gRPC's port 6334 swapped for REST's
+    /// 6333.
+    #[serde(default)]
+    pub qdrant_rest_url: Option<String>,
+
+    /// Index and storage settings of the collection, the `[qdrant]` table.
+    #[serde(default)]
+    pub qdrant: QdrantCollectionConfig,
+
     #[serde(default = "default_collection_name")]
     pub collection_name: String,
 
+    /// Points sent to Qdrant per upsert request.
+    #[serde(default = "default_qdrant_upsert_batch_size")]
+    pub qdrant_upsert_batch_size: usize,
+
+    /// When true, wait for Qdrant to apply each upsert batch before sending
+    /// the next one. When false, Qdrant acknowledges a batch as soon as it is
+    /// logged, which is faster (about twice as fast on a slow disk), but
+    /// points may not show up in searches for a moment after an ingest ends.
+    #[serde(default)]
+    pub qdrant_upsert_wait: bool,
+
+    /// Upsert requests in flight at once.
+    #[serde(default = "default_qdrant_upsert_parallelism")]
+    pub qdrant_upsert_parallelism: usize,
+
+    /// Finish an ingest only once the collection's status is green (Qdrant
+    /// has applied and indexed its updates), so a search right after it
+    /// sees the new points at full speed.
+    #[serde(default)]
+    pub qdrant_wait_for_green: bool,
+
     #[serde(default = "default_tantivy_index_dir")]
     pub tantivy_index_dir: String,
 
@@ -18,9 +57,168 @@ pub struct AppConfig {
     #[serde(default = "default_chunk_overlap")]
     pub chunk_overlap: usize,
 
+    /// How ingest splits documents: `chars` (fixed-size windows),
+    /// `markdown` (at headings, then by size within long sections) or
+    /// `sentence` (whole sentences and paragraphs up to `chunk_size`).
+    #[serde(default = "default_chunking_strategy")]
+    pub chunking_strategy: String,
+
+    /// What `chunk_size` and `chunk_overlap` count: `chars` or (estimated)
+    /// embedding model `tokens`.
+    #[serde(default = "default_chunk_unit")]
+    pub chunk_unit: String,
+
+    /// Source code file extensions ingest reads as plain text and splits
+    /// with the code chunker.
+    #[serde(default = "default_code_extensions")]
+    pub code_extensions: Vec<String>,
+
+    /// Source directories `ragctl ingest` walks when no `--source` is
+    /// given, each with optional chunking settings of its own, and web pages
+    /// it fetches unless `--url` is given.
+    #[serde(default)]
+    pub sources: Vec<SourceConfig>,
+
+    /// Files larger than this, or whose markitdown output is larger, are
+    /// skipped by ingest.
+    #[serde(default = "default_max_file_size_bytes")]
+    pub max_file_size_bytes: u64,
+
+    /// Index files, and converter output, that look binary instead of
+    /// skipping them.
+    #[serde(default)]
+    pub allow_binary: bool,
+
+    /// Encoding to read text files without a byte order mark in (a WHATWG
+    /// label such as `shift_jis` or `euc-kr`), instead of detecting it.
+    #[serde(default)]
+    pub ingest_default_encoding: Option<String>,
+
+    /// How ingest reads PDF, DOCX and XLSX files: `markitdown`, `native`
+    /// (the built-in readers; PDFs need the `pdf-native` feature) or `auto`
+    /// (markitdown when it is installed, the built-in readers otherwise).
+    #[serde(default = "default_converter")]
+    pub converter: String,
+
+    /// Rows of each sheet the native XLSX reader keeps.
+    #[serde(default = "default_max_sheet_rows")]
+    pub max_sheet_rows: usize,
+
+    /// External commands that convert files to text, consulted before the
+    /// built-in readers and markitdown.
+    #[serde(default)]
+    pub converters: Vec<ConverterConfig>,
+
+    /// Command that prints the text it recognizes in an image or a scanned
+    /// PDF, e.g. `tesseract {path} stdout`. OCR is off while unset.
+    #[serde(default)]
+    pub ocr_command: Option<String>,
+
+    /// Seconds an `ocr_command` may run for one file.
+    #[serde(default = "default_ocr_timeout_secs")]
+    pub ocr_timeout_secs: u64,
+
+    /// OCR commands ingest runs at once.
+    #[serde(default = "default_ocr_concurrency")]
+    pub ocr_concurrency: usize,
+
+    /// Index a chunk whose (whitespace-normalized) text is already indexed
+    /// for another source only once, under the first source's path.
+    #[serde(default)]
+    pub dedupe_chunks: bool,
+
+    /// Follow symlinks while walking source directories. Off by default, so
+    /// links out of the tree don't pull in unrelated files.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+
+    /// How many directory levels below a source path ingest walks into.
+    /// Unlimited when unset.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+
+    /// How long `ragctl ingest --watch` waits for source files to stop
+    /// changing before it re-ingests them.
+    #[serde(default = "default_watch_debounce_ms")]
+    pub watch_debounce_ms: u64,
+
+    /// Files `ragctl ingest` reads before embedding and indexing their
+    /// chunks together.
+    #[serde(default = "default_ingest_file_batch_size")]
+    pub ingest_file_batch_size: usize,
+
+    /// Make `ragctl ingest` exit non-zero when a file, URL or index write
+    /// failed. Skipped files don't count.
+    #[serde(default = "default_true")]
+    pub fail_on_ingest_errors: bool,
+
+    /// Host or IP the HTTP server binds to, combined with `listen_port`.
+    #[serde(default = "default_listen_addr")]
+    pub listen_addr: String,
+
     #[serde(default = "default_listen_port")]
     pub listen_port: u16,
 
+    /// Serve on this unix domain socket instead of TCP.
+    #[serde(default)]
+    pub listen_socket: Option<String>,
+
+    /// Keep the TCP listener running when `listen_socket` is set.
+    #[serde(default)]
+    pub listen_socket_with_tcp: bool,
+
+    /// Server transport: "sse" (HTTP) or "stdio".
+    #[serde(default = "default_transport")]
+    pub transport: String,
+
+    /// Serve the legacy `/sse` + `/message` endpoints next to `/mcp`.
+    #[serde(default = "default_true")]
+    pub legacy_sse: bool,
+
+    /// Drop HTTP sessions with no client activity for this long (0 disables).
+    #[serde(default = "default_session_timeout_secs")]
+    pub session_timeout_secs: u64,
+
+    /// Maximum number of concurrent HTTP sessions.
+    #[serde(default = "default_max_sessions")]
+    pub max_sessions: usize,
+
+    /// Serve the plain REST API (`/search`) next to the MCP endpoints.
+    #[serde(default)]
+    pub rest_api_enabled: bool,
+
+    /// Origins allowed to call the HTTP endpoints from a browser. Empty means
+    /// same-origin only; `"*"` allows any origin.
+    #[serde(default)]
+    pub cors_allowed_origins: Vec<String>,
+
+    /// Abort a `tools/call` after this many seconds (0 disables). Clients can
+    /// override it per call with a `timeout_secs` argument.
+    #[serde(default = "default_tool_timeout_secs")]
+    pub tool_timeout_secs: u64,
+
+    /// Bearer token required by the `/admin` endpoints; they are disabled
+    /// while unset.
+    #[serde(default)]
+    pub admin_token: Option<String>,
+
+    /// PEM certificate chain; serve HTTPS when set together with `tls_key_path`.
+    #[serde(default)]
+    pub tls_cert_path: Option<String>,
+
+    /// PEM private key matching `tls_cert_path`.
+    #[serde(default)]
+    pub tls_key_path: Option<String>,
+
+    /// Log output format: "text" (human-readable) or "json" (one object per line).
+    #[serde(default = "default_log_format")]
+    pub log_format: String,
+
+    /// Consecutive failed backend probes before `/health/ready` reports the
+    /// server as not ready.
+    #[serde(default = "default_readiness_failure_threshold")]
+    pub readiness_failure_threshold: u32,
+
     #[serde(default = "default_embedding_provider")]
     pub embedding_provider: String,
 
@@ -30,8 +228,349 @@ pub struct AppConfig {
     #[serde(default = "default_embedding_dimension")]
     pub embedding_dimension: usize,
 
+    /// Send `embedding_dimension` as the `openai` provider's `dimensions`
+    /// parameter even when it is the model's native size, or the model is
+    /// not one ragctl knows (an OpenAI-compatible server's).
+    #[serde(default)]
+    pub embedding_request_dimensions: bool,
+
+    /// Base URL of the `openai` provider's API, to use an OpenAI-compatible
+    /// server (TEI, LiteLLM, vLLM, ...). Unset, `OPENAI_API_BASE`, else
+    /// `https://api.openai.com/v1`.
+    #[serde(default)]
+    pub embedding_base_url: Option<String>,
+
+    /// Environment variable holding the `openai` provider's API key, sent
+    /// as a bearer token. Empty, no `Authorization` header is sent.
+    #[serde(default = "default_embedding_api_key_env")]
+    pub embedding_api_key_env: String,
+
+    /// Headers the `openai` provider sends with each request. `${VAR}` in
+    /// a value is replaced by the environment variable `VAR`.
+    #[serde(default)]
+    pub embedding_extra_headers: BTreeMap<String, String>,
+
+    /// The `openai` provider's API key, used when the
+    /// `embedding_api_key_env` variable isn't set: the environment always
+    /// wins. For projects whose keys differ.
+    #[serde(default)]
+    pub openai_api_key: Option<Secret>,
+
+    /// The `gemini` provider's API key, used when `GEMINI_API_KEY` isn't
+    /// set.
+    #[serde(default)]
+    pub gemini_api_key: Option<Secret>,
+
+    /// ONNX file of a model for the `local` provider to load instead of
+    /// the named `embedding_model`, e.g. a fine-tuned sentence-transformers
+    /// export.
+    #[serde(default)]
+    pub local_model_path: Option<String>,
+
+    /// Directory with the `local_model_path` model's `tokenizer.json`,
+    /// `config.json`, `special_tokens_map.json` and
+    /// `tokenizer_config.json`. Unset, the ONNX file's directory.
+    #[serde(default)]
+    pub local_model_tokenizer_path: Option<String>,
+
+    /// How the `local_model_path` model's token vectors are pooled into
+    /// one: `mean` or `cls`.
+    #[serde(default = "default_local_model_pooling")]
+    pub local_model_pooling: String,
+
+    /// Prefix of documents sent to the embedding provider, whichever it is:
+    /// `passage: ` for E5, `search_document: ` for nomic. Unset, E5 models
+    /// (an `e5` part in `embedding_model`, or in `local_model_path`) get
+    /// `passage: ` and every other model, hosted ones included, nothing;
+    /// `""` turns E5's off too. Ingest and search must agree on it.
+    #[serde(default, alias = "local_document_prefix")]
+    pub embedding_document_prefix: Option<String>,
+
+    /// Prefix of search queries, as `embedding_document_prefix`: `query: `
+    /// for E5, `Represent this sentence for searching relevant passages: `
+    /// for BGE, `search_query: ` for nomic.
+    #[serde(default, alias = "local_query_prefix")]
+    pub embedding_query_prefix: Option<String>,
+
+    /// ONNX Runtime execution provider of the `local` provider: `cpu`,
+    /// `cuda` or `coreml`. One this build or machine can't use falls back
+    /// to `cpu` with a warning.
+    #[serde(default = "default_local_execution_provider")]
+    pub local_execution_provider: String,
+
+    /// Embedding requests `ragctl ingest` keeps in flight at once.
+    #[serde(default = "default_embedding_concurrency")]
+    pub embedding_concurrency: usize,
+
+    /// Texts sent per embedding request.
+    #[serde(default = "default_embedding_batch_size")]
+    pub embedding_batch_size: usize,
+
+    /// Texts longer than this many (estimated) tokens are truncated before
+    /// being sent to the embedding API. 0 disables the check.
+    #[serde(default = "default_embedding_max_tokens")]
+    pub embedding_max_tokens: usize,
+
+    /// Attempts in all at an embedding request that fails with a rate
+    /// limit, a server error or a connection error.
+    #[serde(default = "default_embedding_max_attempts")]
+    pub embedding_max_attempts: u32,
+
+    /// Delay before the first retry of an embedding request, doubled for
+    /// each one after it (and jittered), unless the provider sends
+    /// `Retry-After`.
+    #[serde(default = "default_embedding_retry_base_ms")]
+    pub embedding_retry_base_ms: u64,
+
+    /// Seconds to wait for a connection to the embedding provider.
+    #[serde(default = "default_embedding_connect_timeout_secs")]
+    pub embedding_connect_timeout_secs: u64,
+
+    /// Seconds an embedding request by `ragctl` may take, response body
+    /// included, before it fails (and is retried).
+    #[serde(default = "default_embedding_timeout_secs")]
+    pub embedding_timeout_secs: u64,
+
+    /// Seconds the server's embedding of a search query may take; shorter
+    /// than `embedding_timeout_secs`, as a client is waiting on it.
+    #[serde(default = "default_search_embedding_timeout_secs")]
+    pub search_embedding_timeout_secs: u64,
+
+    /// Embedding requests `ragctl` sends per minute at most. Unset, the
+    /// provider's usual limit applies (3000 for OpenAI, 1500 for Gemini,
+    /// none for local models); 0 disables the limit.
+    #[serde(default)]
+    pub embedding_requests_per_minute: Option<u32>,
+
+    /// Estimated tokens `ragctl` sends to be embedded per minute at most.
+    /// Unset, 1,000,000 for OpenAI and none for other providers; 0
+    /// disables the limit.
+    #[serde(default)]
+    pub embedding_tokens_per_minute: Option<u32>,
+
+    /// Keep the vectors `ragctl` embeds in a cache on disk and reuse them
+    /// for identical text instead of calling the provider again.
+    #[serde(default = "default_true")]
+    pub embedding_cache: bool,
+
+    /// Where the lexical (BM25) half of hybrid search runs: "tantivy", the
+    /// local index, or "qdrant-sparse", sparse vectors in the Qdrant
+    /// collection, for servers without a persistent local disk.
+    #[serde(default = "default_lexical_backend")]
+    pub lexical_backend: String,
+
     #[serde(default = "default_tokenizer")]
     pub tokenizer: String,
+
+    #[serde(default = "default_highlight_marker")]
+    pub highlight_pre_tag: String,
+
+    #[serde(default = "default_highlight_marker")]
+    pub highlight_post_tag: String,
+}
+
+/// The `[qdrant]` table: how the collection is indexed and stored. Unset
+/// settings are left to Qdrant. The collection is created with them, and
+/// an existing one is updated to match when they change.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QdrantCollectionConfig {
+    /// Edges per node of the HNSW graph (Qdrant's default: 16). More is
+    /// more accurate and more memory.
+    #[serde(default)]
+    pub hnsw_m: Option<u64>,
+
+    /// Neighbours considered while building the HNSW graph (default: 100).
+    /// More is more accurate and a slower build.
+    #[serde(default)]
+    pub hnsw_ef_construct: Option<u64>,
+
+    /// Keep vectors on disk, memory-mapped, instead of in RAM.
+    #[serde(default)]
+    pub on_disk_vectors: Option<bool>,
+
+    /// Keep payloads on disk, read when a point is returned.
+    #[serde(default)]
+    pub on_disk_payload: Option<bool>,
+
+    /// Compression of the vectors searched first: "none", "scalar" (int8,
+    /// a quarter of the memory) or "binary" (a 32nd, for large models);
+    /// results are rescored with the original vectors. Unset, a new
+    /// collection isn't quantized.
+    #[serde(default)]
+    pub quantization: Option<String>,
+
+    /// Share of the values scalar quantization's range covers, cutting
+    /// outliers (0.5 to 1; Qdrant's default: 0.99).
+    #[serde(default)]
+    pub quantization_quantile: Option<f32>,
+
+    /// Keep the quantized vectors in RAM, even with `on_disk_vectors`.
+    #[serde(default)]
+    pub always_ram: Option<bool>,
+
+    /// Give each chunk a second vector, its title's, that searches can
+    /// match instead of the text's.
+    #[serde(default)]
+    pub title_vector: bool,
+
+    /// Shards a new collection is split into (Qdrant's default: one per
+    /// node). Fixed once the collection exists.
+    #[serde(default)]
+    pub shard_number: Option<u32>,
+
+    /// Copies of each shard, on different nodes of a cluster (default: 1).
+    #[serde(default)]
+    pub replication_factor: Option<u32>,
+
+    /// Replicas that must acknowledge a write before it succeeds (default:
+    /// 1), at most `replication_factor`.
+    #[serde(default)]
+    pub write_consistency_factor: Option<u32>,
+
+    /// Search without the HNSW graph by default, comparing the query with
+    /// every vector; for checking recall, as it's slow on large collections.
+    #[serde(default)]
+    pub search_exact: Option<bool>,
+
+    /// Candidates a search keeps while walking the HNSW graph (Qdrant's
+    /// default: `hnsw_ef_construct`). More is better recall and slower.
+    #[serde(default)]
+    pub search_hnsw_ef: Option<u64>,
+}
+
+impl QdrantCollectionConfig {
+    pub const QUANTIZATIONS: [&'static str; 3] = ["none", "scalar", "binary"];
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if let Some(quantization) = &self.quantization {
+            if !Self::QUANTIZATIONS.contains(&quantization.as_str()) {
+                anyhow::bail!(
+                    "qdrant.quantization must be one of {}, not '{}'",
+                    Self::QUANTIZATIONS.join(", "),
+                    quantization
+                );
+            }
+        }
+        if let Some(quantile) = self.quantization_quantile {
+            if !(0.5..=1.0).contains(&quantile) {
+                anyhow::bail!(
+                    "qdrant.quantization_quantile must be between 0.5 and 1, not {}",
+                    quantile
+                );
+            }
+        }
+        let counts = [
+            ("shard_number", self.shard_number),
+            ("replication_factor", self.replication_factor),
+            ("write_consistency_factor", self.write_consistency_factor),
+        ];
+        for (name, count) in counts {
+            if count == Some(0) {
+                anyhow::bail!("qdrant.{} must be at least 1", name);
+            }
+        }
+        let replicas = self.replication_factor.unwrap_or(1);
+        if let Some(write_consistency) = self.write_consistency_factor {
+            if write_consistency > replicas {
+                anyhow::bail!(
+                    "qdrant.write_consistency_factor ({}) can't exceed qdrant.replication_factor ({})",
+                    write_consistency,
+                    replicas
+                );
+            }
+        }
+        if self.search_hnsw_ef == Some(0) {
+            anyhow::bail!("qdrant.search_hnsw_ef must be at least 1");
+        }
+        self.search_params().checked();
+        Ok(())
+    }
+
+    /// The default HNSW parameters of searches.
+    pub fn search_params(&self) -> crate::types::VectorSearchParams {
+        crate::types::VectorSearchParams {
+            exact: self.search_exact,
+            hnsw_ef: self.search_hnsw_ef,
+        }
+    }
+
+    /// Whether the settings ask for more than one copy of each shard, which
+    /// only a cluster can hold.
+    pub fn replicated(&self) -> bool {
+        self.replication_factor.unwrap_or(1) > 1
+    }
+}
+
+/// A credential in the config file. Its `Debug` output is redacted, so it
+/// stays out of logged configs; [`Secret::expose`] is the value.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[redacted]")
+    }
+}
+
+/// A `[[sources]]` entry: a directory (or file) at `path`, or web pages at
+/// `urls`. Files under `path` are chunked with its `chunk_size` and
+/// `chunk_overlap` where set, and the global ones otherwise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceConfig {
+    #[serde(default)]
+    pub path: String,
+
+    /// Web pages `ragctl ingest` fetches and indexes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub urls: Vec<String>,
+
+    #[serde(default)]
+    pub chunk_size: Option<usize>,
+
+    #[serde(default)]
+    pub chunk_overlap: Option<usize>,
+
+    /// Glob patterns (`.gitignore` syntax, relative to `path`) a file must
+    /// match to be ingested. Empty means every supported file.
+    #[serde(default)]
+    pub include: Vec<String>,
+}
+
+/// A `[[converters]]` entry: a command that prints the text of a file with
+/// one of `extensions` to stdout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConverterConfig {
+    /// Extensions the command converts, without the dot.
+    pub extensions: Vec<String>,
+
+    /// Command line, split into words like a shell would (without running
+    /// one). `{path}` is replaced by the file's path, which is appended
+    /// when there is no `{path}`.
+    pub command: String,
+
+    /// Seconds before the command is killed and the file fails.
+    #[serde(default = "default_converter_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Variables set for the command on top of ingest's own environment,
+    /// which it inherits.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+fn default_converter_timeout_secs() -> u64 {
+    300
 }
 
 fn default_qdrant_url() -> String {
@@ -58,10 +597,88 @@ fn default_chunk_overlap() -> usize {
     200
 }
 
+fn default_chunking_strategy() -> String {
+    "chars".to_string()
+}
+
+fn default_chunk_unit() -> String {
+    "chars".to_string()
+}
+
+fn default_converter() -> String {
+    "auto".to_string()
+}
+
+fn default_max_sheet_rows() -> usize {
+    1000
+}
+
+fn default_ocr_timeout_secs() -> u64 {
+    300
+}
+
+fn default_ocr_concurrency() -> usize {
+    2
+}
+
+fn default_code_extensions() -> Vec<String> {
+    [
+        "rs", "py", "ts", "tsx", "js", "jsx", "go", "java", "kt", "c", "h", "cpp", "hpp", "cs",
+        "rb", "php", "swift", "scala", "sh",
+    ]
+    .iter()
+    .map(|ext| ext.to_string())
+    .collect()
+}
+
+fn default_max_file_size_bytes() -> u64 {
+    20 * 1024 * 1024
+}
+
+fn default_watch_debounce_ms() -> u64 {
+    2000
+}
+
+fn default_ingest_file_batch_size() -> usize {
+    10
+}
+
+fn default_listen_addr() -> String {
+    "127.0.0.1".to_string()
+}
+
 fn default_listen_port() -> u16 {
     7070
 }
 
+fn default_transport() -> String {
+    "sse".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_session_timeout_secs() -> u64 {
+    3600
+}
+
+fn default_max_sessions() -> usize {
+    100
+}
+
+fn default_tool_timeout_secs() -> u64 {
+    30
+}
+
+fn default_log_format() -> String {
+    "text".to_string()
+}
+
+fn default_readiness_failure_threshold() -> u32 {
+    3
+}
+
 fn default_embedding_provider() -> String {
     "openai".to_string()
 }
@@ -74,23 +691,150 @@ fn default_embedding_dimension() -> usize {
     1536
 }
 
+fn default_embedding_api_key_env() -> String {
+    "OPENAI_API_KEY".to_string()
+}
+
+fn default_local_model_pooling() -> String {
+    "mean".to_string()
+}
+
+fn default_local_execution_provider() -> String {
+    "cpu".to_string()
+}
+
+fn default_embedding_concurrency() -> usize {
+    4
+}
+
+fn default_embedding_batch_size() -> usize {
+    20
+}
+
+fn default_qdrant_upsert_batch_size() -> usize {
+    100
+}
+
+fn default_qdrant_upsert_parallelism() -> usize {
+    1
+}
+
+fn default_embedding_max_tokens() -> usize {
+    8191
+}
+
+fn default_embedding_max_attempts() -> u32 {
+    3
+}
+
+fn default_embedding_retry_base_ms() -> u64 {
+    500
+}
+
+fn default_embedding_connect_timeout_secs() -> u64 {
+    5
+}
+
+fn default_embedding_timeout_secs() -> u64 {
+    30
+}
+
+fn default_search_embedding_timeout_secs() -> u64 {
+    10
+}
+
+fn default_lexical_backend() -> String {
+    AppConfig::LEXICAL_BACKENDS[0].to_string()
+}
+
 fn default_tokenizer() -> String {
     "default".to_string()
 }
 
+fn default_highlight_marker() -> String {
+    "**".to_string()
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             qdrant_url: default_qdrant_url(),
+            qdrant_api_key: None,
+            qdrant_rest_url: None,
+            qdrant: QdrantCollectionConfig::default(),
             collection_name: default_collection_name(),
+            qdrant_upsert_batch_size: default_qdrant_upsert_batch_size(),
+            qdrant_upsert_wait: false,
+            qdrant_upsert_parallelism: default_qdrant_upsert_parallelism(),
+            qdrant_wait_for_green: false,
             tantivy_index_dir: default_tantivy_index_dir(),
             chunk_size: default_chunk_size(),
             chunk_overlap: default_chunk_overlap(),
+            chunking_strategy: default_chunking_strategy(),
+            chunk_unit: default_chunk_unit(),
+            code_extensions: default_code_extensions(),
+            sources: Vec::new(),
+            max_file_size_bytes: default_max_file_size_bytes(),
+            allow_binary: false,
+            ingest_default_encoding: None,
+            converter: default_converter(),
+            max_sheet_rows: default_max_sheet_rows(),
+            converters: Vec::new(),
+            ocr_command: None,
+            ocr_timeout_secs: default_ocr_timeout_secs(),
+            ocr_concurrency: default_ocr_concurrency(),
+            dedupe_chunks: false,
+            follow_symlinks: false,
+            max_depth: None,
+            watch_debounce_ms: default_watch_debounce_ms(),
+            ingest_file_batch_size: default_ingest_file_batch_size(),
+            fail_on_ingest_errors: default_true(),
+            listen_addr: default_listen_addr(),
             listen_port: default_listen_port(),
+            listen_socket: None,
+            listen_socket_with_tcp: false,
+            transport: default_transport(),
+            legacy_sse: default_true(),
+            session_timeout_secs: default_session_timeout_secs(),
+            max_sessions: default_max_sessions(),
+            rest_api_enabled: false,
+            cors_allowed_origins: Vec::new(),
+            tool_timeout_secs: default_tool_timeout_secs(),
+            admin_token: None,
+            tls_cert_path: None,
+            tls_key_path: None,
+            log_format: default_log_format(),
+            readiness_failure_threshold: default_readiness_failure_threshold(),
             embedding_provider: default_embedding_provider(),
             embedding_model: default_embedding_model(),
             embedding_dimension: default_embedding_dimension(),
+            embedding_request_dimensions: false,
+            embedding_base_url: None,
+            embedding_api_key_env: default_embedding_api_key_env(),
+            embedding_extra_headers: BTreeMap::new(),
+            openai_api_key: None,
+            gemini_api_key: None,
+            local_model_path: None,
+            local_model_tokenizer_path: None,
+            local_model_pooling: default_local_model_pooling(),
+            embedding_document_prefix: None,
+            embedding_query_prefix: None,
+            local_execution_provider: default_local_execution_provider(),
+            embedding_concurrency: default_embedding_concurrency(),
+            embedding_batch_size: default_embedding_batch_size(),
+            embedding_max_tokens: default_embedding_max_tokens(),
+            embedding_max_attempts: default_embedding_max_attempts(),
+            embedding_retry_base_ms: default_embedding_retry_base_ms(),
+            embedding_connect_timeout_secs: default_embedding_connect_timeout_secs(),
+            embedding_timeout_secs: default_embedding_timeout_secs(),
+            search_embedding_timeout_secs: default_search_embedding_timeout_secs(),
+            embedding_requests_per_minute: None,
+            embedding_tokens_per_minute: None,
+            embedding_cache: true,
+            lexical_backend: default_lexical_backend(),
             tokenizer: default_tokenizer(),
+            highlight_pre_tag: default_highlight_marker(),
+            highlight_post_tag: default_highlight_marker(),
         }
     }
 }
@@ -102,6 +846,49 @@ impl AppConfig {
         home.join(".local").join("share").join("mcp-hybrid-search")
     }
 
+    /// Path of the ingest state file, stored next to the Tantivy index dir.
+    pub fn ingest_state_path(&self) -> PathBuf {
+        std::path::Path::new(&self.tantivy_index_dir)
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new("."))
+            .join("ingest_state.json")
+    }
+
+    /// Path of the error report of the last ingest pass, next to the ingest
+    /// state file.
+    pub fn ingest_errors_path(&self) -> PathBuf {
+        std::path::Path::new(&self.tantivy_index_dir)
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new("."))
+            .join("ingest_errors.json")
+    }
+
+    /// Default directory of `ragctl snapshot create`, next to the ingest
+    /// state file.
+    pub fn snapshot_dir(&self) -> PathBuf {
+        std::path::Path::new(&self.tantivy_index_dir)
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new("."))
+            .join("snapshots")
+    }
+
+    /// Path of the embedding cache, next to the ingest state file.
+    pub fn embedding_cache_path(&self) -> PathBuf {
+        std::path::Path::new(&self.tantivy_index_dir)
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new("."))
+            .join("embedding_cache.bin")
+    }
+
+    /// Directory `ragctl ingest --git` clones repositories into, next to the
+    /// ingest state file.
+    pub fn git_cache_dir(&self) -> PathBuf {
+        std::path::Path::new(&self.tantivy_index_dir)
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new("."))
+            .join("git")
+    }
+
     /// Override collection name and tantivy index dir for project isolation.
     /// When `project` is `Some("my-proj")`:
     /// - `collection_name` → `"my-proj"`
@@ -118,6 +905,199 @@ impl AppConfig {
         self
     }
 
+    /// Apply a `--listen` override: either `host:port` or just a host, where
+    /// IPv6 literals may be bracketed (`[::1]:7070`, `[::1]`, `::1`).
+    pub fn with_listen(mut self, listen: &str) -> anyhow::Result<Self> {
+        if let Ok(addr) = listen.parse::<SocketAddr>() {
+            self.listen_addr = addr.ip().to_string();
+            self.listen_port = addr.port();
+            return Ok(self);
+        }
+        match listen.rsplit_once(':') {
+            // `[::1]` is a bracketed host, and a colon inside an unbracketed
+            // host means a bare IPv6 address; neither carries a port.
+            Some((host, port))
+                if !listen.ends_with(']') && (!host.contains(':') || host.starts_with('[')) =>
+            {
+                self.listen_port = port
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid port in --listen {}", listen))?;
+                self.listen_addr = host.to_string();
+            }
+            _ => self.listen_addr = listen.to_string(),
+        }
+        Ok(self)
+    }
+
+    /// Resolve `listen_addr` and `listen_port` to the socket address to bind.
+    pub fn listen_socket_addr(&self) -> anyhow::Result<SocketAddr> {
+        let host = self
+            .listen_addr
+            .strip_prefix('[')
+            .and_then(|h| h.strip_suffix(']'))
+            .unwrap_or(&self.listen_addr);
+        (host, self.listen_port)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| anyhow::anyhow!("Invalid listen_addr: {}", self.listen_addr))
+    }
+
+    /// Whether `name` is safe to use as a project name. Project names become a
+    /// directory under the Tantivy base dir, so path separators, `..` and other
+    /// special characters are rejected.
+    pub fn is_valid_project_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= 255
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    }
+
+    /// Fail unless `url` is one the Qdrant client connects to: `http://`,
+    /// or `https://` for TLS, with a host and any port.
+    pub fn check_qdrant_url(url: &str) -> anyhow::Result<()> {
+        let parsed = reqwest::Url::parse(url)
+            .map_err(|e| anyhow::anyhow!("qdrant_url '{}' is not a URL: {}", url, e))?;
+        if !matches!(parsed.scheme(), "http" | "https") || !parsed.has_host() {
+            anyhow::bail!(
+                "qdrant_url '{}' must be http://host:port, or https://host:port for TLS",
+                url
+            );
+        }
+        Ok(())
+    }
+
+    /// Qdrant's REST API: `qdrant_rest_url`, else `qdrant_url` with port
+    /// 6334 swapped for 6333.
+    pub fn rest_url(&self) -> String {
+        if let Some(url) = &self.qdrant_rest_url {
+            return url.trim_end_matches('/').to_string();
+        }
+        match reqwest::Url::parse(&self.qdrant_url) {
+            Ok(mut url) if url.port() == Some(6334) => {
+                url.set_port(Some(6333)).ok();
+                url.as_str().trim_end_matches('/').to_string()
+            }
+            _ => self.qdrant_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Whether `qdrant_url` is on this machine, usually a single node.
+    pub fn local_qdrant(&self) -> bool {
+        reqwest::Url::parse(&self.qdrant_url).is_ok_and(|url| {
+            matches!(
+                url.host_str(),
+                Some("localhost" | "127.0.0.1" | "[::1]" | "0.0.0.0")
+            )
+        })
+    }
+
+    /// The key to send to Qdrant: `qdrant_api_key`, else `QDRANT_API_KEY`.
+    pub fn qdrant_api_key(&self) -> Option<String> {
+        self.qdrant_api_key
+            .as_ref()
+            .map(|key| key.expose().to_string())
+            .or_else(|| std::env::var("QDRANT_API_KEY").ok())
+            .filter(|key| !key.is_empty())
+    }
+
+    pub const LEXICAL_BACKENDS: [&'static str; 2] = ["tantivy", "qdrant-sparse"];
+
+    /// BM25 runs on the collection's sparse vectors rather than Tantivy.
+    pub fn sparse_lexical(&self) -> bool {
+        self.lexical_backend == "qdrant-sparse"
+    }
+
+    /// Fail on settings no value of their type rules out: batch sizes,
+    /// attempts and timeouts of 0, a `qdrant_url` that isn't an `http://`
+    /// or `https://` URL, names no backend or quantization has, and more
+    /// acknowledgements per write than replicas. Replicas asked of a Qdrant
+    /// on this machine are only warned about.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        Self::check_qdrant_url(&self.qdrant_url)?;
+        if let Some(url) = &self.qdrant_rest_url {
+            Self::check_qdrant_url(url)
+                .map_err(|e| anyhow::anyhow!("qdrant_rest_url is invalid: {}", e))?;
+        }
+        self.qdrant.validate()?;
+        // Not an error: a cluster can be reached through a local port.
+        if self.qdrant.replicated() && self.local_qdrant() {
+            tracing::warn!(
+                "qdrant.replication_factor {} needs a Qdrant cluster, but qdrant_url {} is \
+                 this machine; a single node can't hold the replicas",
+                self.qdrant.replication_factor.unwrap_or(1),
+                self.qdrant_url
+            );
+        }
+        if !Self::LEXICAL_BACKENDS.contains(&self.lexical_backend.as_str()) {
+            anyhow::bail!(
+                "lexical_backend must be one of {}, not '{}'",
+                Self::LEXICAL_BACKENDS.join(", "),
+                self.lexical_backend
+            );
+        }
+        let counts = [
+            ("chunk_size", self.chunk_size),
+            ("ingest_file_batch_size", self.ingest_file_batch_size),
+            ("embedding_batch_size", self.embedding_batch_size),
+            ("qdrant_upsert_batch_size", self.qdrant_upsert_batch_size),
+            ("qdrant_upsert_parallelism", self.qdrant_upsert_parallelism),
+            (
+                "embedding_max_attempts",
+                self.embedding_max_attempts as usize,
+            ),
+            (
+                "embedding_connect_timeout_secs",
+                self.embedding_connect_timeout_secs as usize,
+            ),
+            (
+                "embedding_timeout_secs",
+                self.embedding_timeout_secs as usize,
+            ),
+            (
+                "search_embedding_timeout_secs",
+                self.search_embedding_timeout_secs as usize,
+            ),
+        ];
+        for (name, count) in counts {
+            if count == 0 {
+                anyhow::bail!("{} must be at least 1", name);
+            }
+        }
+        for source in &self.sources {
+            match (source.path.is_empty(), source.urls.is_empty()) {
+                (true, true) => anyhow::bail!("each [[sources]] entry needs a path or urls"),
+                (false, false) => anyhow::bail!(
+                    "[[sources]] entry '{}' has both path and urls; give the urls their own entry",
+                    source.path
+                ),
+                _ => {}
+            }
+            let settings = source.chunk_size.is_some()
+                || source.chunk_overlap.is_some()
+                || !source.include.is_empty();
+            if !source.urls.is_empty() && settings {
+                anyhow::bail!(
+                    "chunk_size, chunk_overlap and include only apply to [[sources]] entries with a path"
+                );
+            }
+            if source.chunk_size == Some(0) {
+                anyhow::bail!("chunk_size of source '{}' must be at least 1", source.path);
+            }
+        }
+        Ok(())
+    }
+
+    /// The web pages of the `[[sources]]` entries with `urls`.
+    pub fn source_urls(&self) -> Vec<String> {
+        self.sources
+            .iter()
+            .flat_map(|source| source.urls.iter().cloned())
+            .collect()
+    }
+
     pub fn load(path: Option<&str>) -> anyhow::Result<Self> {
         let config_path = if let Some(p) = path {
             PathBuf::from(p)
@@ -136,6 +1116,13 @@ impl AppConfig {
         if config_path.exists() {
             let content = std::fs::read_to_string(&config_path)?;
             let config: AppConfig = toml::from_str(&content)?;
+            config.validate()?;
+            if credentials_exposed(&config_path, &config) {
+                tracing::warn!(
+                    "{} holds API keys but is readable by every user; chmod 600 it",
+                    config_path.display()
+                );
+            }
             Ok(config)
         } else {
             Ok(AppConfig::default())
@@ -143,6 +1130,29 @@ impl AppConfig {
     }
 }
 
+/// Whether `config`, loaded from `path`, has API keys that any user on the
+/// machine can read.
+fn credentials_exposed(path: &Path, config: &AppConfig) -> bool {
+    let keys = [
+        &config.qdrant_api_key,
+        &config.openai_api_key,
+        &config.gemini_api_key,
+    ];
+    if keys.iter().all(|key| key.is_none()) {
+        return false;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o004 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -151,12 +1161,125 @@ mod tests {
     fn test_default_config() {
         let config = AppConfig::default();
         assert_eq!(config.qdrant_url, "http://localhost:6334");
+        assert!(config.qdrant_api_key.is_none());
+        assert_eq!(config.rest_url(), "http://localhost:6333");
+        assert_eq!(config.qdrant, QdrantCollectionConfig::default());
+        assert_eq!(config.lexical_backend, "tantivy");
+        assert!(!config.sparse_lexical());
         assert_eq!(config.collection_name, "docs");
         assert_eq!(config.chunk_size, 1000);
         assert_eq!(config.chunk_overlap, 200);
+        assert_eq!(config.chunking_strategy, "chars");
+        assert_eq!(config.chunk_unit, "chars");
+        assert!(config.code_extensions.iter().any(|ext| ext == "rs"));
+        assert!(config.source_urls().is_empty());
+        assert!(config.sources.is_empty());
+        assert_eq!(config.max_file_size_bytes, 20 * 1024 * 1024);
+        assert!(!config.allow_binary);
+        assert!(config.ingest_default_encoding.is_none());
+        assert_eq!(config.converter, "auto");
+        assert_eq!(config.max_sheet_rows, 1000);
+        assert!(config.converters.is_empty());
+        assert!(config.ocr_command.is_none());
+        assert_eq!(config.ocr_timeout_secs, 300);
+        assert_eq!(config.ocr_concurrency, 2);
+        assert!(!config.dedupe_chunks);
+        assert_eq!(config.watch_debounce_ms, 2000);
+        assert!(!config.follow_symlinks);
+        assert!(config.max_depth.is_none());
+        assert_eq!(config.listen_addr, "127.0.0.1");
         assert_eq!(config.listen_port, 7070);
+        assert!(config.listen_socket.is_none());
+        assert!(!config.listen_socket_with_tcp);
+        assert_eq!(config.transport, "sse");
+        assert!(config.legacy_sse);
+        assert_eq!(config.session_timeout_secs, 3600);
+        assert_eq!(config.max_sessions, 100);
+        assert!(!config.rest_api_enabled);
+        assert!(config.cors_allowed_origins.is_empty());
+        assert_eq!(config.tool_timeout_secs, 30);
+        assert!(config.admin_token.is_none());
+        assert!(config.tls_cert_path.is_none());
+        assert!(config.tls_key_path.is_none());
+        assert_eq!(config.log_format, "text");
+        assert_eq!(config.readiness_failure_threshold, 3);
         assert_eq!(config.embedding_model, "text-embedding-3-small");
         assert_eq!(config.embedding_dimension, 1536);
+        assert!(!config.embedding_request_dimensions);
+        assert!(config.embedding_base_url.is_none());
+        assert_eq!(config.embedding_api_key_env, "OPENAI_API_KEY");
+        assert!(config.embedding_extra_headers.is_empty());
+        assert!(config.openai_api_key.is_none());
+        assert!(config.gemini_api_key.is_none());
+        assert!(config.local_model_path.is_none());
+        assert!(config.local_model_tokenizer_path.is_none());
+        assert_eq!(config.local_model_pooling, "mean");
+        assert!(config.embedding_document_prefix.is_none());
+        assert!(config.embedding_query_prefix.is_none());
+        assert_eq!(config.local_execution_provider, "cpu");
+        assert_eq!(config.embedding_concurrency, 4);
+        assert_eq!(config.embedding_batch_size, 20);
+        assert_eq!(config.qdrant_upsert_batch_size, 100);
+        assert!(!config.qdrant_upsert_wait);
+        assert_eq!(config.qdrant_upsert_parallelism, 1);
+        assert!(!config.qdrant_wait_for_green);
+        assert_eq!(config.ingest_file_batch_size, 10);
+        assert!(config.fail_on_ingest_errors);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.embedding_max_tokens, 8191);
+        assert_eq!(config.embedding_max_attempts, 3);
+        assert_eq!(config.embedding_retry_base_ms, 500);
+        assert_eq!(config.embedding_connect_timeout_secs, 5);
+        assert_eq!(config.embedding_timeout_secs, 30);
+        assert_eq!(config.search_embedding_timeout_secs, 10);
+        assert!(config.embedding_requests_per_minute.is_none());
+        assert!(config.embedding_tokens_per_minute.is_none());
+        assert!(config.embedding_cache);
+        assert_eq!(config.highlight_pre_tag, "**");
+        assert_eq!(config.highlight_post_tag, "**");
+    }
+
+    #[test]
+    fn test_listen_override() {
+        let config = AppConfig::default().with_listen("0.0.0.0:8080").unwrap();
+        assert_eq!(
+            config.listen_socket_addr().unwrap().to_string(),
+            "0.0.0.0:8080"
+        );
+
+        let config = AppConfig::default().with_listen("0.0.0.0").unwrap();
+        assert_eq!(
+            config.listen_socket_addr().unwrap().to_string(),
+            "0.0.0.0:7070"
+        );
+
+        let config = AppConfig::default().with_listen("[::1]:8080").unwrap();
+        assert_eq!(
+            config.listen_socket_addr().unwrap().to_string(),
+            "[::1]:8080"
+        );
+
+        for host in ["[::1]", "::1"] {
+            let config = AppConfig::default().with_listen(host).unwrap();
+            assert_eq!(
+                config.listen_socket_addr().unwrap().to_string(),
+                "[::1]:7070"
+            );
+        }
+
+        let config = AppConfig::default().with_listen("localhost:9000").unwrap();
+        assert_eq!(config.listen_socket_addr().unwrap().port(), 9000);
+
+        assert!(AppConfig::default().with_listen("127.0.0.1:http").is_err());
+    }
+
+    #[test]
+    fn test_invalid_listen_addr() {
+        let config = AppConfig {
+            listen_addr: "not a host".to_string(),
+            ..AppConfig::default()
+        };
+        assert!(config.listen_socket_addr().is_err());
     }
 
     #[test]
@@ -165,6 +1288,97 @@ mod tests {
         assert_eq!(config.listen_port, 7070);
     }
 
+    #[test]
+    fn test_load_rejects_empty_batches() {
+        let path = std::env::temp_dir().join(format!("config-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            "embedding_batch_size = 8\nqdrant_upsert_batch_size = 0\n",
+        )
+        .unwrap();
+        let err = AppConfig::load(path.to_str()).unwrap_err();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(
+            err.to_string(),
+            "qdrant_upsert_batch_size must be at least 1"
+        );
+
+        let config: AppConfig = toml::from_str("embedding_batch_size = 8").unwrap();
+        assert_eq!(config.embedding_batch_size, 8);
+        assert!(config.validate().is_ok());
+
+        let config: AppConfig = toml::from_str("qdrant_upsert_parallelism = 0").unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "qdrant_upsert_parallelism must be at least 1"
+        );
+
+        let config: AppConfig = toml::from_str("chunk_size = 0").unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "chunk_size must be at least 1"
+        );
+        let config: AppConfig =
+            toml::from_str("[[sources]]\npath = \"/docs\"\nchunk_size = 0\n").unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "chunk_size of source '/docs' must be at least 1"
+        );
+    }
+
+    #[test]
+    fn test_parse_url_sources() {
+        let toml_str = r#"
+            [[sources]]
+            path = "/docs"
+
+            [[sources]]
+            urls = ["https://wiki/a", "https://wiki/b"]
+        "#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.source_urls(), ["https://wiki/a", "https://wiki/b"]);
+
+        let invalid = [
+            ("[[sources]]\nchunk_size = 500\n", "needs a path or urls"),
+            (
+                "[[sources]]\npath = \"/docs\"\nurls = [\"https://wiki/a\"]\n",
+                "has both path and urls",
+            ),
+            (
+                "[[sources]]\nurls = [\"https://wiki/a\"]\ninclude = [\"*.md\"]\n",
+                "only apply to [[sources]] entries with a path",
+            ),
+        ];
+        for (toml_str, message) in invalid {
+            let config: AppConfig = toml::from_str(toml_str).unwrap();
+            let err = config.validate().unwrap_err().to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_check_qdrant_url() {
+        for url in [
+            "http://localhost:6334",
+            "https://qdrant.internal:8443",
+            "https://cluster.cloud.qdrant.io",
+        ] {
+            AppConfig::check_qdrant_url(url).unwrap();
+        }
+        assert_eq!(
+            AppConfig::check_qdrant_url("localhost:6334")
+                .unwrap_err()
+                .to_string(),
+            "qdrant_url 'localhost:6334' must be http://host:port, or https://host:port for TLS"
+        );
+        assert!(AppConfig::check_qdrant_url("grpc://qdrant:6334").is_err());
+        assert!(AppConfig::check_qdrant_url("qdrant")
+            .unwrap_err()
+            .to_string()
+            .starts_with("qdrant_url 'qdrant' is not a URL"));
+    }
+
     #[test]
     fn test_parse_partial_toml() {
         let toml_str = r#"
@@ -179,6 +1393,72 @@ mod tests {
         assert_eq!(config.embedding_dimension, 1536);
     }
 
+    #[test]
+    fn test_parse_sources() {
+        let toml_str = r#"
+            chunk_size = 1000
+
+            [[sources]]
+            path = "/docs/api"
+            chunk_size = 500
+            chunk_overlap = 100
+            include = ["*.md", "openapi/**"]
+
+            [[sources]]
+            path = "/docs/design"
+        "#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.chunk_size, 1000);
+        assert_eq!(
+            config.sources,
+            vec![
+                SourceConfig {
+                    path: "/docs/api".to_string(),
+                    urls: Vec::new(),
+                    chunk_size: Some(500),
+                    chunk_overlap: Some(100),
+                    include: vec!["*.md".to_string(), "openapi/**".to_string()],
+                },
+                SourceConfig {
+                    path: "/docs/design".to_string(),
+                    urls: Vec::new(),
+                    chunk_size: None,
+                    chunk_overlap: None,
+                    include: Vec::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_converters() {
+        let toml_str = r#"
+            [[converters]]
+            extensions = ["dwg", "one"]
+            command = "ourtool --stdout {path}"
+            timeout_secs = 30
+            env = { OURTOOL_LICENSE = "/etc/ourtool.lic" }
+
+            [[converters]]
+            extensions = ["rtf"]
+            command = "pandoc -t markdown"
+        "#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.converters.len(), 2);
+        assert_eq!(config.converters[0].extensions, vec!["dwg", "one"]);
+        assert_eq!(config.converters[0].timeout_secs, 30);
+        assert_eq!(
+            config.converters[0]
+                .env
+                .get("OURTOOL_LICENSE")
+                .map(String::as_str),
+            Some("/etc/ourtool.lic")
+        );
+        assert_eq!(config.converters[1].command, "pandoc -t markdown");
+        assert_eq!(config.converters[1].timeout_secs, 300);
+        assert!(config.converters[1].env.is_empty());
+    }
+
     #[test]
     fn test_with_project_none() {
         let config = AppConfig::default();
@@ -200,6 +1480,35 @@ mod tests {
         assert_eq!(config.embedding_dimension, 1536);
     }
 
+    #[test]
+    fn test_valid_project_names() {
+        assert!(AppConfig::is_valid_project_name("my-proj"));
+        assert!(AppConfig::is_valid_project_name("proj_2.v1"));
+    }
+
+    #[test]
+    fn test_invalid_project_names() {
+        assert!(!AppConfig::is_valid_project_name(""));
+        assert!(!AppConfig::is_valid_project_name(".."));
+        assert!(!AppConfig::is_valid_project_name("../etc"));
+        assert!(!AppConfig::is_valid_project_name("a/b"));
+        assert!(!AppConfig::is_valid_project_name("a\\b"));
+        assert!(!AppConfig::is_valid_project_name(".hidden"));
+    }
+
+    #[test]
+    fn test_ingest_state_path() {
+        let config = AppConfig {
+            tantivy_index_dir: "/data/tantivy".to_string(),
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            config.ingest_state_path(),
+            PathBuf::from("/data/ingest_state.json")
+        );
+        assert_eq!(config.git_cache_dir(), PathBuf::from("/data/git"));
+    }
+
     #[test]
     fn test_default_source_dir() {
         let dir = AppConfig::default_source_dir();
@@ -223,4 +1532,185 @@ mod tests {
         assert_eq!(config.collection_name, "docs");
         assert_eq!(config.listen_port, 7070);
     }
+
+    #[test]
+    fn test_api_keys_redacted() {
+        let config: AppConfig = toml::from_str("openai_api_key = \"sk-secret\"").unwrap();
+        assert_eq!(
+            config.openai_api_key.as_ref().unwrap().expose(),
+            "sk-secret"
+        );
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains("sk-secret"), "{}", debug);
+        assert!(debug.contains("openai_api_key: Some([redacted])"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_credentials_exposed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("config-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "").unwrap();
+        let set_mode =
+            |mode| std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        let with_key = AppConfig {
+            gemini_api_key: Some(Secret::new("g-key")),
+            ..AppConfig::default()
+        };
+        set_mode(0o644);
+        assert!(credentials_exposed(&path, &with_key));
+        assert!(!credentials_exposed(&path, &AppConfig::default()));
+        set_mode(0o600);
+        assert!(!credentials_exposed(&path, &with_key));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_qdrant_api_key() {
+        // The only test that reads QDRANT_API_KEY.
+        std::env::remove_var("QDRANT_API_KEY");
+        assert_eq!(AppConfig::default().qdrant_api_key(), None);
+        std::env::set_var("QDRANT_API_KEY", "from-env");
+        assert_eq!(
+            AppConfig::default().qdrant_api_key().as_deref(),
+            Some("from-env")
+        );
+        let configured = AppConfig {
+            qdrant_api_key: Some(Secret::new("from-config")),
+            ..AppConfig::default()
+        };
+        assert_eq!(configured.qdrant_api_key().as_deref(), Some("from-config"));
+        std::env::remove_var("QDRANT_API_KEY");
+    }
+
+    #[test]
+    fn test_parse_qdrant_table() {
+        let toml_str = r#"
+            collection_name = "big"
+
+            [qdrant]
+            hnsw_m = 32
+            hnsw_ef_construct = 256
+            on_disk_vectors = true
+            title_vector = true
+        "#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.collection_name, "big");
+        assert_eq!(
+            config.qdrant,
+            QdrantCollectionConfig {
+                hnsw_m: Some(32),
+                hnsw_ef_construct: Some(256),
+                on_disk_vectors: Some(true),
+                title_vector: true,
+                ..QdrantCollectionConfig::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_validate_quantization() {
+        let config = |quantization: &str, quantile: Option<f32>| AppConfig {
+            qdrant: QdrantCollectionConfig {
+                quantization: Some(quantization.to_string()),
+                quantization_quantile: quantile,
+                ..QdrantCollectionConfig::default()
+            },
+            ..AppConfig::default()
+        };
+        assert!(config("binary", None).validate().is_ok());
+        assert!(config("scalar", Some(0.95)).validate().is_ok());
+        let err = config("product", None).validate().unwrap_err();
+        assert!(err.to_string().contains("none, scalar, binary"), "{}", err);
+        assert!(config("scalar", Some(0.2)).validate().is_err());
+    }
+
+    #[test]
+    fn test_rest_url() {
+        let config = |url: &str, rest: Option<&str>| AppConfig {
+            qdrant_url: url.to_string(),
+            qdrant_rest_url: rest.map(str::to_string),
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            config("https://qdrant.internal:6334", None).rest_url(),
+            "https://qdrant.internal:6333"
+        );
+        assert_eq!(
+            config("https://xyz.cloud.qdrant.io", None).rest_url(),
+            "https://xyz.cloud.qdrant.io"
+        );
+        assert_eq!(
+            config("http://localhost:6334", Some("http://localhost:8080/")).rest_url(),
+            "http://localhost:8080"
+        );
+        assert!(config("http://localhost:6334", Some("localhost:6333"))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_search_params() {
+        let config = |exact, ef| AppConfig {
+            qdrant: QdrantCollectionConfig {
+                search_exact: exact,
+                search_hnsw_ef: ef,
+                ..QdrantCollectionConfig::default()
+            },
+            ..AppConfig::default()
+        };
+        assert!(config(None, Some(128)).validate().is_ok());
+        assert!(config(None, Some(0)).validate().is_err());
+        // Only warned about: the ef is ignored.
+        let both = config(Some(true), Some(128));
+        assert!(both.validate().is_ok());
+        assert_eq!(both.qdrant.search_params().checked().hnsw_ef, None);
+    }
+
+    #[test]
+    fn test_validate_replication() {
+        let config = |replicas: Option<u32>, write_consistency: Option<u32>| AppConfig {
+            qdrant_url: "https://qdrant.internal:6334".to_string(),
+            qdrant: QdrantCollectionConfig {
+                shard_number: Some(6),
+                replication_factor: replicas,
+                write_consistency_factor: write_consistency,
+                ..QdrantCollectionConfig::default()
+            },
+            ..AppConfig::default()
+        };
+        assert!(config(Some(2), Some(2)).validate().is_ok());
+        assert!(config(None, Some(1)).validate().is_ok());
+        assert!(config(Some(0), None).validate().is_err());
+        let err = config(Some(2), Some(3)).validate().unwrap_err();
+        assert!(err.to_string().contains("can't exceed"), "{}", err);
+        assert!(config(None, Some(2)).validate().is_err());
+
+        assert!(!config(Some(2), None).local_qdrant());
+        assert!(AppConfig::default().local_qdrant());
+        let local = AppConfig {
+            qdrant_url: "http://127.0.0.1:6334".to_string(),
+            ..config(Some(2), None)
+        };
+        assert!(local.local_qdrant());
+        assert!(local.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_lexical_backend() {
+        let config = |backend: &str| AppConfig {
+            lexical_backend: backend.to_string(),
+            ..AppConfig::default()
+        };
+        assert!(!config("tantivy").sparse_lexical());
+        assert!(config("qdrant-sparse").validate().is_ok());
+        assert!(config("qdrant-sparse").sparse_lexical());
+        let err = config("elasticsearch").validate().unwrap_err();
+        assert!(
+            err.to_string().contains("tantivy, qdrant-sparse"),
+            "{}",
+            err
+        );
+    }
 }
@@ -0,0 +1,1293 @@
+//! Embedding providers, shared by ingest and search.
+//!
+//! [`embed`] sends a batch of texts to the configured provider (`openai`,
+//! `gemini`, or `local` with the `local-embed` feature) and returns one
+//! vector per text. What the texts are for, documents or queries, picks the
+//! prefix put before each text and how long a request may take. The `local`
+//! provider loads a named fastembed model, or an ONNX export of any other
+//! model from `local_model_path`. `mock` hashes texts into vectors, for
+//! tests and demos without a key or network.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::AppConfig;
+use crate::http;
+use crate::openai::{self, Endpoint};
+use crate::retry::RetryPolicy;
+
+/// What texts are embedded for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Purpose {
+    /// Chunks being ingested.
+    Document,
+    /// Search queries, which a client is waiting on.
+    Query,
+}
+
+impl Purpose {
+    /// The policy of this purpose's requests: search queries time out
+    /// sooner.
+    fn retry_policy(self, config: &AppConfig) -> RetryPolicy {
+        match self {
+            Purpose::Document => RetryPolicy::new(config),
+            Purpose::Query => RetryPolicy::for_search(config),
+        }
+    }
+}
+
+/// Embed `texts` for `purpose` in a single provider call, one vector per
+/// text.
+pub async fn embed(
+    config: &AppConfig,
+    purpose: Purpose,
+    texts: &[String],
+) -> Result<Vec<Vec<f32>>> {
+    let prefix = input_prefix(config, purpose);
+    let prefixed: Vec<String>;
+    let texts = if prefix.is_empty() {
+        texts
+    } else {
+        prefixed = texts.iter().map(|t| format!("{}{}", prefix, t)).collect();
+        &prefixed
+    };
+    let embeddings = request(config, purpose, texts).await?;
+    match config.embedding_provider.as_str() {
+        "openai" => openai::check_dimensions(config, &embeddings)?,
+        "gemini" => check_gemini_dimensions(config, &embeddings)?,
+        "local" => check_local_dimensions(config, &embeddings)?,
+        _ => {}
+    }
+    Ok(embeddings)
+}
+
+/// The prefix put before texts embedded for `purpose`: the configured one,
+/// or for E5 models E5's.
+pub fn input_prefix(config: &AppConfig, purpose: Purpose) -> &str {
+    let (configured, e5) = match purpose {
+        Purpose::Document => (&config.embedding_document_prefix, "passage: "),
+        Purpose::Query => (&config.embedding_query_prefix, "query: "),
+    };
+    match configured {
+        Some(prefix) => prefix,
+        None if is_e5(config) => e5,
+        None => "",
+    }
+}
+
+/// Whether the configured model is an E5 one, which is trained with
+/// "passage: " and "query: " prefixes.
+fn is_e5(config: &AppConfig) -> bool {
+    let model = match (&config.local_model_path, config.embedding_provider.as_str()) {
+        (Some(path), "local") => path,
+        _ => &config.embedding_model,
+    };
+    model
+        .to_ascii_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|part| part == "e5")
+}
+
+/// Embed `texts` without checking the vectors' size.
+async fn request(config: &AppConfig, purpose: Purpose, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    let embeddings = match config.embedding_provider.as_str() {
+        "openai" => embed_openai(config, purpose, texts).await?,
+        "gemini" => embed_gemini(config, purpose, texts).await?,
+        "local" => {
+            // Inference takes hundreds of milliseconds of CPU; run it off
+            // the async workers so that other requests aren't held up.
+            let (config, texts) = (config.clone(), texts.to_vec());
+            tokio::task::spawn_blocking(move || embed_local(&config, purpose, &texts)).await??
+        }
+        "mock" => texts
+            .iter()
+            .map(|t| embed_mock(t, config.embedding_dimension))
+            .collect(),
+        other => anyhow::bail!(
+            "Unknown embedding_provider '{}'. Supported: openai, gemini, local, mock",
+            other
+        ),
+    };
+    if embeddings.len() != texts.len() {
+        anyhow::bail!(
+            "Embedding provider returned {} embeddings for {} inputs",
+            embeddings.len(),
+            texts.len()
+        );
+    }
+    Ok(embeddings)
+}
+
+/// The size of the vectors the provider returns, from embedding a probe.
+pub async fn probe_dimension(config: &AppConfig) -> Result<usize> {
+    let embeddings = request(config, Purpose::Query, &["dimension probe".to_string()]).await?;
+    Ok(embeddings[0].len())
+}
+
+/// Fail unless the Qdrant collection's vector size (`None` when it doesn't
+/// exist yet) and the size of the provider's vectors (`None` when unknown)
+/// both match `embedding_dimension`, saying which values disagree and how
+/// to fix it.
+pub fn check_dimension(
+    config: &AppConfig,
+    collection_size: Option<u64>,
+    provider_size: Option<usize>,
+) -> Result<()> {
+    let expected = config.embedding_dimension;
+    let mut problems = Vec::new();
+    if let Some(size) = provider_size.filter(|&size| size != expected) {
+        problems.push(format!(
+            "embedding_model '{}' ({}) returns {}-dimensional vectors, but embedding_dimension = {}; \
+             set embedding_dimension = {}",
+            config.embedding_model, config.embedding_provider, size, expected, size
+        ));
+    }
+    if let Some(size) = collection_size.filter(|&size| size != expected as u64) {
+        problems.push(format!(
+            "collection '{}' holds {}-dimensional vectors, but embedding_dimension = {}; \
+             go back to the embedding_model and embedding_dimension it was built with, \
+             or rebuild it with `ragctl reset` and `ragctl ingest`",
+            config.collection_name, size, expected
+        ));
+    }
+    if !problems.is_empty() {
+        anyhow::bail!(
+            "Embedding dimension mismatch: {} (--skip-dimension-check skips this check)",
+            problems.join("; and ")
+        );
+    }
+    Ok(())
+}
+
+/// Check that the configured provider is usable (known, compiled in, API key
+/// and header variables present) without calling it.
+pub fn check_provider_config(config: &AppConfig) -> Result<()> {
+    match config.embedding_provider.as_str() {
+        "openai" => Endpoint::new(config).map(|_| ()),
+        "gemini" => gemini_api_key(config).map(|_| ()),
+        "local" if cfg!(feature = "local-embed") => {
+            Pooling::from_config(config)?;
+            ExecutionProvider::from_config(config)?;
+            local_model_files(config).map(|_| ())
+        }
+        "local" => {
+            anyhow::bail!("embedding_provider = \"local\" requires the 'local-embed' feature")
+        }
+        "mock" => Ok(()),
+        other => anyhow::bail!(
+            "Unknown embedding_provider '{}'. Supported: openai, gemini, local, mock",
+            other
+        ),
+    }
+}
+
+// --- OpenAI provider ---
+
+#[derive(Serialize)]
+struct EmbeddingRequest {
+    model: String,
+    input: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+
+async fn embed_openai(
+    config: &AppConfig,
+    purpose: Purpose,
+    texts: &[String],
+) -> Result<Vec<Vec<f32>>> {
+    let endpoint = Endpoint::new(config)?;
+    let client = http::embedding_client(config)?;
+    let request = EmbeddingRequest {
+        model: config.embedding_model.clone(),
+        input: texts.to_vec(),
+        dimensions: openai::request_dimensions(config),
+    };
+
+    let response = purpose
+        .retry_policy(config)
+        .send("OpenAI", || {
+            endpoint
+                .post(&client, "embeddings")
+                .header("Content-Type", "application/json")
+                .json(&request)
+        })
+        .await?;
+
+    let resp: EmbeddingResponse = response.json().await?;
+    Ok(resp.data.into_iter().map(|d| d.embedding).collect())
+}
+
+// --- Gemini provider ---
+
+#[derive(Serialize)]
+struct GeminiBatchEmbedRequest {
+    requests: Vec<GeminiEmbedRequest>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiEmbedRequest {
+    model: String,
+    content: GeminiContent,
+    task_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_dimensionality: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct GeminiContent {
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(Serialize)]
+struct GeminiPart {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct GeminiBatchEmbedResponse {
+    embeddings: Vec<GeminiEmbeddingValues>,
+}
+
+#[derive(Deserialize)]
+struct GeminiEmbeddingValues {
+    values: Vec<f32>,
+}
+
+/// The Gemini API key: `GEMINI_API_KEY`, else `gemini_api_key`.
+fn gemini_api_key(config: &AppConfig) -> Result<String> {
+    std::env::var("GEMINI_API_KEY")
+        .ok()
+        .filter(|key| !key.is_empty())
+        .or_else(|| {
+            config
+                .gemini_api_key
+                .as_ref()
+                .map(|key| key.expose().to_string())
+        })
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "GEMINI_API_KEY environment variable not set (nor gemini_api_key in the config)"
+            )
+        })
+}
+
+/// Native dimensions of the Gemini embedding models.
+const GEMINI_NATIVE_DIMENSIONS: &[(&str, usize)] = &[
+    ("gemini-embedding-001", 3072),
+    ("text-embedding-004", 768),
+    ("embedding-001", 768),
+];
+
+/// The `outputDimensionality` to request: `embedding_dimension`, when it is
+/// smaller than the model's native size or the model isn't a known one.
+fn gemini_output_dimensionality(config: &AppConfig) -> Option<usize> {
+    let model = config.embedding_model.trim_start_matches("models/");
+    let native = GEMINI_NATIVE_DIMENSIONS
+        .iter()
+        .find(|(name, _)| *name == model)
+        .map(|(_, dimension)| *dimension);
+    native
+        .is_none_or(|native| config.embedding_dimension < native)
+        .then_some(config.embedding_dimension)
+}
+
+/// Fail unless every Gemini vector has `embedding_dimension` values.
+fn check_gemini_dimensions(config: &AppConfig, vectors: &[Vec<f32>]) -> Result<()> {
+    let expected = config.embedding_dimension;
+    if let Some(wrong) = vectors.iter().find(|v| v.len() != expected) {
+        anyhow::bail!(
+            "Gemini model {} returned {}-dimensional vectors, but embedding_dimension is {}: \
+             set embedding_dimension to at most the model's size",
+            config.embedding_model,
+            wrong.len(),
+            expected
+        );
+    }
+    Ok(())
+}
+
+/// Scale `vector` to unit length. Only Gemini's full-size vectors come
+/// normalized; Google recommends normalizing shortened ones.
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+async fn embed_gemini(
+    config: &AppConfig,
+    purpose: Purpose,
+    texts: &[String],
+) -> Result<Vec<Vec<f32>>> {
+    let api_key = gemini_api_key(config)?;
+
+    let base_url = std::env::var("GEMINI_API_BASE")
+        .unwrap_or_else(|_| "https://generativelanguage.googleapis.com/v1beta".into());
+
+    let model = &config.embedding_model;
+    let model_path = if model.starts_with("models/") {
+        model.clone()
+    } else {
+        format!("models/{}", model)
+    };
+
+    let task_type = match purpose {
+        Purpose::Document => "RETRIEVAL_DOCUMENT",
+        Purpose::Query => "RETRIEVAL_QUERY",
+    };
+    let output_dimensionality = gemini_output_dimensionality(config);
+    let requests: Vec<GeminiEmbedRequest> = texts
+        .iter()
+        .map(|t| GeminiEmbedRequest {
+            model: model_path.clone(),
+            content: GeminiContent {
+                parts: vec![GeminiPart { text: t.clone() }],
+            },
+            task_type,
+            output_dimensionality,
+        })
+        .collect();
+
+    let request = GeminiBatchEmbedRequest { requests };
+
+    let client = http::embedding_client(config)?;
+    let url = format!("{}/{}:batchEmbedContents", base_url, model_path);
+
+    let response = purpose
+        .retry_policy(config)
+        .send("Gemini", || {
+            client
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .header("x-goog-api-key", &api_key)
+                .json(&request)
+        })
+        .await?;
+
+    let resp: GeminiBatchEmbedResponse = response.json().await?;
+    let mut vectors: Vec<Vec<f32>> = resp.embeddings.into_iter().map(|e| e.values).collect();
+    if output_dimensionality.is_some() {
+        vectors.iter_mut().for_each(|v| normalize(v));
+    }
+    Ok(vectors)
+}
+
+// --- Mock provider ---
+
+/// A vector for `text` that depends on nothing but the text and
+/// `dimension`: the normalized sum of a pseudo-random vector per lowercased
+/// word, so texts sharing words are near each other. Good enough to run
+/// ingest and search end to end; useless as a measure of retrieval quality.
+fn embed_mock(text: &str, dimension: usize) -> Vec<f32> {
+    let mut vector = vec![0.0f32; dimension];
+    let words = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase);
+    let mut any = false;
+    for word in words {
+        add_word_vector(&mut vector, &word);
+        any = true;
+    }
+    if !any {
+        add_word_vector(&mut vector, text);
+    }
+    normalize(&mut vector);
+    vector
+}
+
+/// Add `word`'s vector to `vector`: values in [-1, 1) drawn by splitmix64
+/// from a seed hashed from the word, the same on every platform.
+fn add_word_vector(vector: &mut [f32], word: &str) {
+    use sha1::{Digest, Sha1};
+
+    let digest = Sha1::digest(word.as_bytes());
+    let mut state = u64::from_le_bytes(digest[..8].try_into().unwrap());
+    for value in vector.iter_mut() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        // The top 24 bits, exactly representable as an f32.
+        *value += (z >> 40) as f32 / (1u64 << 23) as f32 - 1.0;
+    }
+}
+
+// --- Local provider (fastembed) ---
+
+/// How the token vectors of a `local_model_path` model are pooled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pooling {
+    Mean,
+    Cls,
+}
+
+impl Pooling {
+    fn from_config(config: &AppConfig) -> Result<Self> {
+        match config.local_model_pooling.as_str() {
+            "mean" => Ok(Pooling::Mean),
+            "cls" => Ok(Pooling::Cls),
+            other => anyhow::bail!(
+                "Unknown local_model_pooling '{}'. Supported: mean, cls",
+                other
+            ),
+        }
+    }
+}
+
+/// ONNX Runtime execution providers the local model can run on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionProvider {
+    Cpu,
+    Cuda,
+    CoreMl,
+}
+
+impl ExecutionProvider {
+    fn from_config(config: &AppConfig) -> Result<Self> {
+        match config.local_execution_provider.as_str() {
+            "cpu" => Ok(Self::Cpu),
+            "cuda" => Ok(Self::Cuda),
+            "coreml" => Ok(Self::CoreMl),
+            other => anyhow::bail!(
+                "Unknown local_execution_provider '{}'. Supported: cpu, cuda, coreml",
+                other
+            ),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cpu => "cpu",
+            Self::Cuda => "cuda",
+            Self::CoreMl => "coreml",
+        }
+    }
+
+    /// The feature this provider needs, when this build lacks it.
+    fn missing_feature(self) -> Option<&'static str> {
+        match self {
+            Self::Cuda if !cfg!(feature = "local-embed-cuda") => Some("local-embed-cuda"),
+            Self::CoreMl if !cfg!(feature = "local-embed-coreml") => Some("local-embed-coreml"),
+            _ => None,
+        }
+    }
+
+    /// Whether this build has the provider and ONNX Runtime can load it on
+    /// this machine.
+    fn available(self) -> bool {
+        match self {
+            Self::Cpu => true,
+            #[cfg(feature = "local-embed-cuda")]
+            Self::Cuda => {
+                use ort::execution_providers::ExecutionProvider as _;
+                ort::execution_providers::CUDAExecutionProvider::default()
+                    .is_available()
+                    .unwrap_or(false)
+            }
+            #[cfg(feature = "local-embed-coreml")]
+            Self::CoreMl => {
+                use ort::execution_providers::ExecutionProvider as _;
+                ort::execution_providers::CoreMLExecutionProvider::default()
+                    .is_available()
+                    .unwrap_or(false)
+            }
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
+
+/// Set once the fallback to CPU has been warned about.
+static EXECUTION_PROVIDER_WARNED: std::sync::Once = std::sync::Once::new();
+
+/// The execution provider local embeddings run on: the configured one, or
+/// CPU, with a warning the first time, when it can't be used.
+pub fn local_execution_provider(config: &AppConfig) -> Result<ExecutionProvider> {
+    let requested = ExecutionProvider::from_config(config)?;
+    if requested.available() {
+        return Ok(requested);
+    }
+    EXECUTION_PROVIDER_WARNED.call_once(|| {
+        let reason = match requested.missing_feature() {
+            Some(feature) => format!("this build lacks the '{}' feature", feature),
+            None => "ONNX Runtime can't load it on this machine".to_string(),
+        };
+        tracing::warn!(
+            "local_execution_provider = \"{}\" is unavailable ({}); embedding on cpu",
+            requested.name(),
+            reason
+        );
+    });
+    Ok(ExecutionProvider::Cpu)
+}
+
+/// Tokenizer files a `local_model_path` model needs next to it, or in
+/// `local_model_tokenizer_path`.
+const TOKENIZER_FILES: [&str; 4] = [
+    "tokenizer.json",
+    "config.json",
+    "special_tokens_map.json",
+    "tokenizer_config.json",
+];
+
+/// The files of the `local_model_path` model.
+#[derive(Debug, PartialEq)]
+struct LocalModelFiles {
+    onnx: PathBuf,
+    /// In the order of [`TOKENIZER_FILES`].
+    tokenizer: [PathBuf; 4],
+}
+
+/// The files of the configured `local_model_path` model, or `None` when a
+/// named model is configured. Fails naming every file that is missing.
+fn local_model_files(config: &AppConfig) -> Result<Option<LocalModelFiles>> {
+    let Some(onnx) = config.local_model_path.as_deref().map(PathBuf::from) else {
+        return Ok(None);
+    };
+    let dir = match &config.local_model_tokenizer_path {
+        Some(dir) => PathBuf::from(dir),
+        None => onnx.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    let files = LocalModelFiles {
+        tokenizer: TOKENIZER_FILES.map(|name| dir.join(name)),
+        onnx,
+    };
+    let missing: Vec<String> = std::iter::once(&files.onnx)
+        .chain(&files.tokenizer)
+        .filter(|path| !path.is_file())
+        .map(|path| path.display().to_string())
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Local model files not found: {} (local_model_path is the ONNX file; \
+             local_model_tokenizer_path the directory with {}, by default the ONNX file's)",
+            missing.join(", "),
+            TOKENIZER_FILES.join(", ")
+        );
+    }
+    Ok(Some(files))
+}
+
+/// Identifies the configured local model; another one is loaded when it
+/// changes.
+fn local_model_key(config: &AppConfig) -> String {
+    match &config.local_model_path {
+        Some(path) => format!("{} ({} pooling)", path, config.local_model_pooling),
+        None => config.embedding_model.clone(),
+    }
+}
+
+/// Fail unless the local model's vectors have `embedding_dimension` values.
+fn check_local_dimensions(config: &AppConfig, vectors: &[Vec<f32>]) -> Result<()> {
+    let expected = config.embedding_dimension;
+    if let Some(wrong) = vectors.iter().find(|v| v.len() != expected) {
+        anyhow::bail!(
+            "Local model {} returned {}-dimensional vectors, but embedding_dimension is {}: \
+             set embedding_dimension = {}",
+            local_model_key(config),
+            wrong.len(),
+            expected,
+            wrong.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(feature = "local-embed")]
+fn embed_local(config: &AppConfig, _purpose: Purpose, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    with_local_model(config, |model| Ok(model.embed(texts, None)?))
+}
+
+/// Load the local model in the background, so that the first search
+/// doesn't wait for it. Does nothing for the other providers.
+pub fn warm_up(config: &AppConfig) {
+    #[cfg(feature = "local-embed")]
+    if config.embedding_provider == "local" {
+        let config = config.clone();
+        std::thread::spawn(move || {
+            let started = std::time::Instant::now();
+            let name = local_model_key(&config);
+            match with_local_model(&config, |_| Ok(())) {
+                Ok(()) => tracing::info!(
+                    "Local embedding model {} ready after {:.1}s",
+                    name,
+                    started.elapsed().as_secs_f64()
+                ),
+                Err(e) => tracing::warn!("Could not load local embedding model {}: {:#}", name, e),
+            }
+        });
+    }
+    #[cfg(not(feature = "local-embed"))]
+    let _ = config;
+}
+
+/// The loaded local model, kept for the life of the process: loading the
+/// ONNX model takes far longer than embedding with it. Replaced when the
+/// config names another one.
+#[cfg(feature = "local-embed")]
+static LOCAL_MODEL: std::sync::Mutex<Option<(String, fastembed::TextEmbedding)>> =
+    std::sync::Mutex::new(None);
+
+/// Times a local model was loaded.
+#[cfg(feature = "local-embed")]
+static LOCAL_MODEL_LOADS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Run `f` with the configured local model, loading it first if it isn't
+/// the loaded one. Calls take turns: embedding needs the model mutably.
+#[cfg(feature = "local-embed")]
+fn with_local_model<T>(
+    config: &AppConfig,
+    f: impl FnOnce(&mut fastembed::TextEmbedding) -> Result<T>,
+) -> Result<T> {
+    use fastembed::{InitOptions, TextEmbedding};
+
+    let key = local_model_key(config);
+    let mut loaded = LOCAL_MODEL
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    if loaded.as_ref().is_none_or(|(model, _)| *model != key) {
+        let model = match local_model_files(config)? {
+            Some(files) => load_user_defined(config, &files)?,
+            None => {
+                let model_type = resolve_local_model(&config.embedding_model)?;
+                TextEmbedding::try_new(
+                    InitOptions::new(model_type)
+                        .with_execution_providers(execution_providers(config)?),
+                )?
+            }
+        };
+        LOCAL_MODEL_LOADS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        *loaded = Some((key, model));
+    }
+    let (_, model) = loaded.as_mut().expect("model loaded above");
+    f(model)
+}
+
+/// Load a model exported to ONNX, with its tokenizer files.
+#[cfg(feature = "local-embed")]
+fn load_user_defined(
+    config: &AppConfig,
+    files: &LocalModelFiles,
+) -> Result<fastembed::TextEmbedding> {
+    use anyhow::Context;
+    use fastembed::{
+        InitOptionsUserDefined, TextEmbedding, TokenizerFiles, UserDefinedEmbeddingModel,
+    };
+
+    let read = |path: &Path| {
+        std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))
+    };
+    let [tokenizer, model_config, special_tokens_map, tokenizer_config] = &files.tokenizer;
+    let tokenizer_files = TokenizerFiles {
+        tokenizer_file: read(tokenizer)?,
+        config_file: read(model_config)?,
+        special_tokens_map_file: read(special_tokens_map)?,
+        tokenizer_config_file: read(tokenizer_config)?,
+    };
+    let pooling = match Pooling::from_config(config)? {
+        Pooling::Mean => fastembed::Pooling::Mean,
+        Pooling::Cls => fastembed::Pooling::Cls,
+    };
+    let model =
+        UserDefinedEmbeddingModel::new(read(&files.onnx)?, tokenizer_files).with_pooling(pooling);
+    let options =
+        InitOptionsUserDefined::default().with_execution_providers(execution_providers(config)?);
+    TextEmbedding::try_new_from_user_defined(model, options)
+        .with_context(|| format!("Could not load local model {}", files.onnx.display()))
+}
+
+/// The ONNX Runtime execution providers to load the local model with;
+/// none is ONNX Runtime's default, the CPU.
+#[cfg(feature = "local-embed")]
+fn execution_providers(config: &AppConfig) -> Result<Vec<fastembed::ExecutionProviderDispatch>> {
+    #[allow(unused_mut)]
+    let mut providers = Vec::new();
+    match local_execution_provider(config)? {
+        #[cfg(feature = "local-embed-cuda")]
+        ExecutionProvider::Cuda => {
+            providers.push(ort::execution_providers::CUDAExecutionProvider::default().build())
+        }
+        #[cfg(feature = "local-embed-coreml")]
+        ExecutionProvider::CoreMl => {
+            providers.push(ort::execution_providers::CoreMLExecutionProvider::default().build())
+        }
+        _ => {}
+    }
+    Ok(providers)
+}
+
+#[cfg(feature = "local-embed")]
+fn resolve_local_model(model_name: &str) -> Result<fastembed::EmbeddingModel> {
+    use fastembed::EmbeddingModel;
+    match model_name {
+        "multilingual-e5-small" => Ok(EmbeddingModel::MultilingualE5Small),
+        "multilingual-e5-base" => Ok(EmbeddingModel::MultilingualE5Base),
+        _ => anyhow::bail!(
+            "Unknown local embedding model '{}'. Supported: multilingual-e5-small, \
+             multilingual-e5-base, or an ONNX file as local_model_path",
+            model_name
+        ),
+    }
+}
+
+#[cfg(not(feature = "local-embed"))]
+fn embed_local(_config: &AppConfig, _purpose: Purpose, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    anyhow::bail!(
+        "embedding_provider = \"local\" requires the 'local-embed' feature. \
+         Build with: cargo build --features local-embed"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Answer one request on a local port with `body`. Returns the base URL
+    /// and the request as received, head and body.
+    async fn serve_once(body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(head_end) = text.find("\r\n\r\n") {
+                    let length: usize = text[..head_end]
+                        .lines()
+                        .find_map(|l| {
+                            l.to_ascii_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= head_end + 4 + length || n == 0 {
+                        break;
+                    }
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (url, handle)
+    }
+
+    /// The JSON body of a request `serve_once` received.
+    fn json_body(request: &str) -> serde_json::Value {
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body).unwrap()
+    }
+
+    fn texts(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_embed_openai() {
+        let (url, request) =
+            serve_once(r#"{"data":[{"embedding":[0.1,0.2,0.3]},{"embedding":[0.4,0.5,0.6]}]}"#)
+                .await;
+        let config = AppConfig {
+            embedding_base_url: Some(url),
+            embedding_api_key_env: String::new(),
+            embedding_dimension: 3,
+            ..AppConfig::default()
+        };
+        let embeddings = embed(&config, Purpose::Document, &texts(&["alpha", "beta"]))
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![0.1, 0.2, 0.3], vec![0.4, 0.5, 0.6]]);
+
+        let request = request.await.unwrap();
+        assert!(request.starts_with("POST /embeddings "), "{}", request);
+        let body = json_body(&request);
+        assert_eq!(body["model"], "text-embedding-3-small");
+        assert_eq!(body["input"], serde_json::json!(["alpha", "beta"]));
+        // Shorter than the model's 1536, so asked for.
+        assert_eq!(body["dimensions"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_embed_openai_count_mismatch() {
+        let (url, _) = serve_once(r#"{"data":[{"embedding":[0.1,0.2,0.3]}]}"#).await;
+        let config = AppConfig {
+            embedding_base_url: Some(url),
+            embedding_api_key_env: String::new(),
+            embedding_dimension: 3,
+            ..AppConfig::default()
+        };
+        let err = embed(&config, Purpose::Query, &texts(&["alpha", "beta"]))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Embedding provider returned 1 embeddings for 2 inputs"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_embed_gemini() {
+        let (url, request) =
+            serve_once(r#"{"embeddings":[{"values":[3.0,4.0]},{"values":[0.0,2.0]}]}"#).await;
+        // The only test that reads the Gemini variables.
+        std::env::set_var("GEMINI_API_KEY", "test-key");
+        std::env::set_var("GEMINI_API_BASE", &url);
+        let config = AppConfig {
+            embedding_provider: "gemini".to_string(),
+            embedding_model: "text-embedding-004".to_string(),
+            embedding_dimension: 2,
+            ..AppConfig::default()
+        };
+        let embeddings = embed(&config, Purpose::Query, &texts(&["one", "two"]))
+            .await
+            .unwrap();
+        // Shortened, so normalized.
+        assert_eq!(embeddings, vec![vec![0.6, 0.8], vec![0.0, 1.0]]);
+
+        let request = request.await.unwrap();
+        assert!(
+            request.starts_with("POST /models/text-embedding-004:batchEmbedContents "),
+            "{}",
+            request
+        );
+        assert!(
+            request
+                .to_ascii_lowercase()
+                .contains("x-goog-api-key: test-key"),
+            "{}",
+            request
+        );
+        let body = json_body(&request);
+        let requests = body["requests"].as_array().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1],
+            serde_json::json!({
+                "model": "models/text-embedding-004",
+                "content": {"parts": [{"text": "two"}]},
+                "taskType": "RETRIEVAL_QUERY",
+                "outputDimensionality": 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_gemini_request_json() {
+        let config = AppConfig {
+            embedding_provider: "gemini".to_string(),
+            embedding_model: "text-embedding-004".to_string(),
+            embedding_dimension: 768,
+            ..AppConfig::default()
+        };
+        // The native size isn't asked for.
+        let request = GeminiEmbedRequest {
+            model: "models/text-embedding-004".to_string(),
+            content: GeminiContent {
+                parts: vec![GeminiPart {
+                    text: "chunk".to_string(),
+                }],
+            },
+            task_type: "RETRIEVAL_DOCUMENT",
+            output_dimensionality: gemini_output_dimensionality(&config),
+        };
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"model":"models/text-embedding-004","content":{"parts":[{"text":"chunk"}]},"taskType":"RETRIEVAL_DOCUMENT"}"#
+        );
+
+        let shortened = AppConfig {
+            embedding_model: "models/gemini-embedding-001".to_string(),
+            embedding_dimension: 1536,
+            ..config.clone()
+        };
+        assert_eq!(gemini_output_dimensionality(&shortened), Some(1536));
+        let unknown = AppConfig {
+            embedding_model: "gemini-embedding-exp".to_string(),
+            ..config.clone()
+        };
+        assert_eq!(gemini_output_dimensionality(&unknown), Some(768));
+
+        let err = check_gemini_dimensions(&config, &[vec![0.0; 3072]])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("returned 3072-dimensional vectors"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_embed_mock() {
+        let config = AppConfig {
+            embedding_provider: "mock".to_string(),
+            embedding_dimension: 8,
+            ..AppConfig::default()
+        };
+        check_provider_config(&config).unwrap();
+        let input = texts(&[
+            "Rollback procedure",
+            "rollback PROCEDURE!",
+            "Quarterly revenue report",
+            "",
+        ]);
+        let embeddings = embed(&config, Purpose::Document, &input).await.unwrap();
+        let again = embed(&config, Purpose::Document, &input).await.unwrap();
+        assert_eq!(embeddings, again);
+        for vector in &embeddings {
+            assert_eq!(vector.len(), 8);
+            let norm: f32 = vector.iter().map(|v| v * v).sum();
+            assert!((norm - 1.0).abs() < 1e-5, "{}", norm);
+        }
+        // The same words, so the same vector; other words, another one.
+        assert_eq!(embeddings[0], embeddings[1]);
+        assert_ne!(embeddings[0], embeddings[2]);
+        // Pinned, so that a change to the vectors, which would make every
+        // index built with them stale, doesn't go unnoticed.
+        let pinned: Vec<String> = embed_mock("rollback", 4)
+            .iter()
+            .map(|v| format!("{:.4}", v))
+            .collect();
+        assert_eq!(pinned, ["0.7473", "0.2083", "-0.3461", "0.5277"]);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_provider() {
+        let config = AppConfig {
+            embedding_provider: "cohere".to_string(),
+            ..AppConfig::default()
+        };
+        let err = embed(&config, Purpose::Document, &texts(&["x"]))
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Unknown embedding_provider 'cohere'"));
+        assert!(check_provider_config(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_probe_dimension() {
+        let (url, _) = serve_once(r#"{"data":[{"embedding":[0.1,0.2]}]}"#).await;
+        let config = AppConfig {
+            embedding_base_url: Some(url),
+            embedding_api_key_env: String::new(),
+            ..AppConfig::default()
+        };
+        // Not 1536, but the probe reports the size rather than failing on it.
+        assert_eq!(probe_dimension(&config).await.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_check_dimension() {
+        let config = AppConfig::default();
+        assert!(check_dimension(&config, Some(1536), Some(1536)).is_ok());
+        assert!(check_dimension(&config, None, None).is_ok());
+
+        let err = check_dimension(&config, Some(768), Some(1536))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains(
+                "collection 'docs' holds 768-dimensional vectors, but embedding_dimension = 1536"
+            ),
+            "{}",
+            err
+        );
+        assert!(err.contains("ragctl reset"), "{}", err);
+        assert!(!err.contains("returns"), "{}", err);
+
+        let err = check_dimension(&config, None, Some(3072))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("embedding_model 'text-embedding-3-small' (openai) returns 3072-dimensional vectors"),
+            "{}",
+            err
+        );
+        assert!(err.contains("set embedding_dimension = 3072"), "{}", err);
+        assert!(err.ends_with("(--skip-dimension-check skips this check)"));
+    }
+
+    #[test]
+    fn test_input_prefix() {
+        // A hosted model gets none.
+        let config = AppConfig::default();
+        assert_eq!(input_prefix(&config, Purpose::Document), "");
+        assert_eq!(input_prefix(&config, Purpose::Query), "");
+
+        let e5 = AppConfig {
+            embedding_provider: "local".to_string(),
+            embedding_model: "multilingual-e5-base".to_string(),
+            ..AppConfig::default()
+        };
+        assert_eq!(input_prefix(&e5, Purpose::Document), "passage: ");
+        assert_eq!(input_prefix(&e5, Purpose::Query), "query: ");
+        // A model file is named by its path, not embedding_model.
+        let bge = AppConfig {
+            local_model_path: Some("/models/bge-small-en/model.onnx".to_string()),
+            ..e5.clone()
+        };
+        assert_eq!(input_prefix(&bge, Purpose::Query), "");
+        let tuned_e5 = AppConfig {
+            local_model_path: Some("/models/e5-tuned/model.onnx".to_string()),
+            ..e5.clone()
+        };
+        assert_eq!(input_prefix(&tuned_e5, Purpose::Query), "query: ");
+        // "e5" only as a whole part of the name.
+        let not_e5 = AppConfig {
+            embedding_model: "nomic-embed-text-v1.5".to_string(),
+            ..AppConfig::default()
+        };
+        assert_eq!(input_prefix(&not_e5, Purpose::Document), "");
+
+        let configured = AppConfig {
+            embedding_document_prefix: Some(String::new()),
+            embedding_query_prefix: Some(
+                "Represent this sentence for searching relevant passages: ".to_string(),
+            ),
+            ..e5
+        };
+        assert_eq!(input_prefix(&configured, Purpose::Document), "");
+        assert_eq!(
+            input_prefix(&configured, Purpose::Query),
+            "Represent this sentence for searching relevant passages: "
+        );
+
+        // The old names of the fields still load.
+        let old: AppConfig = toml::from_str("local_query_prefix = \"q: \"").unwrap();
+        assert_eq!(old.embedding_query_prefix.as_deref(), Some("q: "));
+    }
+
+    #[tokio::test]
+    async fn test_embed_openai_prefixes() {
+        let config = |url: String| AppConfig {
+            embedding_base_url: Some(url),
+            embedding_api_key_env: String::new(),
+            embedding_model: "intfloat/multilingual-e5-large".to_string(),
+            embedding_dimension: 3,
+            ..AppConfig::default()
+        };
+        let (url, request) = serve_once(r#"{"data":[{"embedding":[0.1,0.2,0.3]}]}"#).await;
+        embed(&config(url), Purpose::Document, &texts(&["alpha"]))
+            .await
+            .unwrap();
+        let body = json_body(&request.await.unwrap());
+        assert_eq!(body["input"], serde_json::json!(["passage: alpha"]));
+
+        let (url, request) = serve_once(r#"{"data":[{"embedding":[0.1,0.2,0.3]}]}"#).await;
+        let search_query = AppConfig {
+            embedding_query_prefix: Some("search_query: ".to_string()),
+            ..config(url)
+        };
+        embed(&search_query, Purpose::Query, &texts(&["where is it"]))
+            .await
+            .unwrap();
+        let body = json_body(&request.await.unwrap());
+        assert_eq!(
+            body["input"],
+            serde_json::json!(["search_query: where is it"])
+        );
+    }
+
+    #[test]
+    fn test_local_model_files() {
+        assert_eq!(local_model_files(&AppConfig::default()).unwrap(), None);
+
+        let dir = std::env::temp_dir().join(format!("local-model-{}", uuid::Uuid::new_v4()));
+        let tokenizer_dir = dir.join("tokenizer");
+        std::fs::create_dir_all(&tokenizer_dir).unwrap();
+        let onnx = dir.join("model.onnx");
+        std::fs::write(&onnx, b"onnx").unwrap();
+        std::fs::write(dir.join("tokenizer.json"), b"{}").unwrap();
+        let config = AppConfig {
+            embedding_provider: "local".to_string(),
+            local_model_path: Some(onnx.display().to_string()),
+            ..AppConfig::default()
+        };
+        // Three of the tokenizer files are missing, and all are named.
+        let err = local_model_files(&config).unwrap_err().to_string();
+        assert!(err.contains("config.json"), "{}", err);
+        assert!(err.contains("special_tokens_map.json"), "{}", err);
+        assert!(err.contains("tokenizer_config.json,"), "{}", err);
+        assert!(!err.contains("model.onnx,"), "{}", err);
+
+        for name in TOKENIZER_FILES {
+            std::fs::write(tokenizer_dir.join(name), b"{}").unwrap();
+        }
+        let config = AppConfig {
+            local_model_tokenizer_path: Some(tokenizer_dir.display().to_string()),
+            ..config
+        };
+        let files = local_model_files(&config).unwrap().unwrap();
+        assert_eq!(files.onnx, onnx);
+        assert_eq!(files.tokenizer[1], tokenizer_dir.join("config.json"));
+
+        std::fs::remove_file(&onnx).unwrap();
+        let err = local_model_files(&config).unwrap_err().to_string();
+        assert!(err.contains("model.onnx"), "{}", err);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_local_pooling_and_dimensions() {
+        assert_eq!(
+            Pooling::from_config(&AppConfig::default()).unwrap(),
+            Pooling::Mean
+        );
+        let config = AppConfig {
+            local_model_path: Some("/models/tuned.onnx".to_string()),
+            local_model_pooling: "cls".to_string(),
+            embedding_dimension: 768,
+            ..AppConfig::default()
+        };
+        assert_eq!(Pooling::from_config(&config).unwrap(), Pooling::Cls);
+        let max = AppConfig {
+            local_model_pooling: "max".to_string(),
+            ..AppConfig::default()
+        };
+        assert!(Pooling::from_config(&max).is_err());
+
+        assert!(check_local_dimensions(&config, &[vec![0.0; 768]]).is_ok());
+        let err = check_local_dimensions(&config, &[vec![0.0; 384]])
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "Local model /models/tuned.onnx (cls pooling) returned 384-dimensional vectors, \
+             but embedding_dimension is 768: set embedding_dimension = 384"
+        );
+    }
+
+    #[test]
+    fn test_local_execution_provider() {
+        let provider = |name: &str| {
+            local_execution_provider(&AppConfig {
+                local_execution_provider: name.to_string(),
+                ..AppConfig::default()
+            })
+        };
+        assert_eq!(provider("cpu").unwrap(), ExecutionProvider::Cpu);
+        // Without the feature, CUDA falls back rather than failing.
+        #[cfg(not(feature = "local-embed-cuda"))]
+        assert_eq!(provider("cuda").unwrap(), ExecutionProvider::Cpu);
+        #[cfg(not(feature = "local-embed-coreml"))]
+        assert_eq!(provider("coreml").unwrap(), ExecutionProvider::Cpu);
+        let err = provider("tpu").unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Unknown local_execution_provider 'tpu'. Supported: cpu, cuda, coreml"
+        );
+        assert_eq!(ExecutionProvider::Cuda.name(), "cuda");
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let config = AppConfig::default();
+        assert_eq!(
+            Purpose::Document.retry_policy(&config),
+            RetryPolicy::new(&config)
+        );
+        assert_eq!(
+            Purpose::Query.retry_policy(&config),
+            RetryPolicy::for_search(&config)
+        );
+    }
+
+    /// Downloads multilingual-e5-small (~100 MB) on first run.
+    #[cfg(feature = "local-embed")]
+    #[test]
+    #[ignore = "downloads a model"]
+    fn test_local_model_loaded_once() {
+        use std::sync::atomic::Ordering;
+        use std::time::Instant;
+
+        let config = AppConfig {
+            embedding_provider: "local".to_string(),
+            embedding_model: "multilingual-e5-small".to_string(),
+            embedding_dimension: 384,
+            ..AppConfig::default()
+        };
+        let texts = texts(&["Rollback procedure for the billing service"]);
+        let mut timings = Vec::new();
+        for _ in 0..5 {
+            let started = Instant::now();
+            let embeddings = embed_local(&config, Purpose::Document, &texts).unwrap();
+            timings.push(started.elapsed());
+            assert_eq!(embeddings[0].len(), 384);
+        }
+        assert_eq!(LOCAL_MODEL_LOADS.load(Ordering::Relaxed), 1);
+        // The first call loads the model; the others only embed.
+        assert!(
+            timings[1..].iter().all(|t| *t < timings[0]),
+            "{:?}",
+            timings
+        );
+    }
+
+    /// Downloads multilingual-e5-small (~100 MB) on first run.
+    #[cfg(feature = "local-embed")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore = "downloads a model"]
+    async fn test_local_embedding_leaves_runtime_responsive() {
+        use std::time::{Duration, Instant};
+
+        let config = AppConfig {
+            embedding_provider: "local".to_string(),
+            embedding_model: "multilingual-e5-small".to_string(),
+            embedding_dimension: 384,
+            ..AppConfig::default()
+        };
+        // Loaded first, so that the timer measures embedding only.
+        embed(&config, Purpose::Query, &texts(&["warm up"]))
+            .await
+            .unwrap();
+
+        // A timer ticking every 10ms, recording how late each tick is.
+        let ticker = tokio::spawn(async {
+            let mut worst = Duration::ZERO;
+            for _ in 0..100 {
+                let started = Instant::now();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                worst = worst.max(started.elapsed().saturating_sub(Duration::from_millis(10)));
+            }
+            worst
+        });
+        let searches: Vec<_> = (0..8)
+            .map(|i| {
+                let config = config.clone();
+                tokio::spawn(async move {
+                    let query = format!("How do I roll back release {}?", i);
+                    embed(&config, Purpose::Query, &[query]).await.unwrap()
+                })
+            })
+            .collect();
+        for search in searches {
+            assert_eq!(search.await.unwrap()[0].len(), 384);
+        }
+        let worst = ticker.await.unwrap();
+        assert!(worst < Duration::from_millis(50), "timer {:?} late", worst);
+    }
+}
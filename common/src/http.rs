@@ -0,0 +1,68 @@
+//! The HTTP client embedding providers share, so that connections (and TLS
+//! sessions) to the provider are reused across requests instead of being
+//! set up for each one. Each request's own deadline is set by its
+//! [`RetryPolicy`](crate::retry::RetryPolicy).
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::config::AppConfig;
+
+const USER_AGENT: &str = concat!("mcp-hybrid-search/", env!("CARGO_PKG_VERSION"));
+
+/// The process's embedding client, built from `config` on first use. Its
+/// clones share one connection pool.
+pub fn embedding_client(config: &AppConfig) -> Result<reqwest::Client> {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client.clone());
+    }
+    let client = build(config)?;
+    Ok(CLIENT.get_or_init(|| client).clone())
+}
+
+fn build(config: &AppConfig) -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(config.embedding_connect_timeout_secs))
+        .user_agent(USER_AGENT)
+        .build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_connect_and_user_agent() {
+        // A server that accepts the request and answers late.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let n = socket.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..n]).to_ascii_lowercase();
+            assert!(
+                request.contains("user-agent: mcp-hybrid-search/"),
+                "{}",
+                request
+            );
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            socket.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await.ok();
+        });
+
+        let started = std::time::Instant::now();
+        let err = build(&AppConfig::default())
+            .unwrap()
+            .get(&url)
+            .timeout(Duration::from_secs(1))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.is_timeout(), "{:?}", err);
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+}
@@ -1,2 +1,9 @@
 pub mod config;
+pub mod embedding;
+pub mod http;
+pub mod logging;
+pub mod openai;
+pub mod qdrant;
+pub mod retry;
+pub mod sparse;
 pub mod types;
@@ -0,0 +1,94 @@
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::EnvFilter;
+
+/// Install the global tracing subscriber for `log_format` ("text" or "json").
+/// `RUST_LOG` takes precedence over `default_filter`.
+pub fn init<W>(log_format: &str, default_filter: &str, writer: W) -> anyhow::Result<()>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let subscriber = subscriber(log_format, default_filter, writer)?;
+    tracing::subscriber::set_global_default(subscriber)?;
+    Ok(())
+}
+
+fn subscriber<W>(
+    log_format: &str,
+    default_filter: &str,
+    writer: W,
+) -> anyhow::Result<Box<dyn tracing::Subscriber + Send + Sync>>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_filter));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+
+    match log_format {
+        "text" => Ok(Box::new(builder.finish())),
+        // One object per line. `span` holds the innermost span's fields and
+        // `spans` every enclosing one, so a tool call's event still carries
+        // the session and request id of the request around it.
+        "json" => Ok(Box::new(
+            builder
+                .json()
+                .with_current_span(true)
+                .with_span_list(true)
+                .finish(),
+        )),
+        other => anyhow::bail!(
+            "Unknown log_format: {} (expected \"text\" or \"json\")",
+            other
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_lines_include_span_fields() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = subscriber("json", "info", move || writer.clone()).unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request", session_id = "s1");
+            let _request = request.enter();
+            let tool_call = tracing::info_span!("tool_call", tool = "search");
+            let _tool_call = tool_call.enter();
+            tracing::info!(duration_ms = 12, "finished");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["fields"]["message"], "finished");
+        assert_eq!(line["fields"]["duration_ms"], 12);
+        assert_eq!(line["span"]["tool"], "search");
+        assert_eq!(line["spans"][0]["session_id"], "s1");
+    }
+
+    #[test]
+    fn test_unknown_format_is_rejected() {
+        assert!(subscriber("xml", "info", std::io::stderr).is_err());
+        assert!(subscriber("text", "info", std::io::stderr).is_ok());
+    }
+}
@@ -0,0 +1,339 @@
+//! The endpoint of the `openai` embedding provider, which also serves any
+//! server speaking the OpenAI embeddings API (TEI, LiteLLM, vLLM, ...).
+//!
+//! Its base URL is `embedding_base_url`, else `OPENAI_API_BASE`, else
+//! OpenAI's. The API key, from `embedding_api_key_env` or else
+//! `openai_api_key`, is sent as a bearer token, and
+//! `embedding_extra_headers` are sent with every request, `${VAR}`s in
+//! their values replaced from the environment. Header values are marked sensitive and left out of `Debug`
+//! output and errors, so keys don't end up in logs.
+//!
+//! Models that can shorten their vectors are sent `embedding_dimension` as
+//! the `dimensions` parameter when it isn't their native size.
+
+use anyhow::Result;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+
+use crate::config::AppConfig;
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Native dimensions of the OpenAI models that take a `dimensions`
+/// parameter.
+const NATIVE_DIMENSIONS: &[(&str, usize)] = &[
+    ("text-embedding-3-small", 1536),
+    ("text-embedding-3-large", 3072),
+];
+
+/// The `dimensions` to request: `embedding_dimension`, when it differs from
+/// the model's native size or `embedding_request_dimensions` is set.
+pub fn request_dimensions(config: &AppConfig) -> Option<usize> {
+    let native = NATIVE_DIMENSIONS
+        .iter()
+        .find(|(model, _)| *model == config.embedding_model)
+        .map(|(_, dimension)| *dimension);
+    let reduced = native.is_some_and(|native| native != config.embedding_dimension);
+    (reduced || config.embedding_request_dimensions).then_some(config.embedding_dimension)
+}
+
+/// Fail unless every vector has `embedding_dimension` values, which the
+/// Qdrant collection is created with.
+pub fn check_dimensions(config: &AppConfig, vectors: &[Vec<f32>]) -> Result<()> {
+    let expected = config.embedding_dimension;
+    let Some(wrong) = vectors.iter().find(|v| v.len() != expected) else {
+        return Ok(());
+    };
+    let hint = if request_dimensions(config).is_some() {
+        format!(
+            "it ignored the `dimensions` parameter; does {} support it?",
+            config.embedding_model
+        )
+    } else {
+        "set embedding_dimension to the model's size, or embedding_request_dimensions = true \
+         if it can shorten its vectors"
+            .to_string()
+    };
+    anyhow::bail!(
+        "The embedding provider returned {}-dimensional vectors, but embedding_dimension is {}: {}",
+        wrong.len(),
+        expected,
+        hint
+    )
+}
+
+/// Where to send embedding requests, and the headers to send with them.
+#[derive(Clone)]
+pub struct Endpoint {
+    pub base_url: String,
+    headers: HeaderMap,
+}
+
+impl std::fmt::Debug for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let headers: Vec<&str> = self.headers.keys().map(HeaderName::as_str).collect();
+        f.debug_struct("Endpoint")
+            .field("base_url", &self.base_url)
+            .field("headers", &headers)
+            .finish()
+    }
+}
+
+impl Endpoint {
+    /// The endpoint `config` and the environment describe.
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        Self::with_env(config, |name| std::env::var(name).ok())
+    }
+
+    /// The endpoint, with environment variables looked up by `env`.
+    pub fn with_env(config: &AppConfig, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let base_url = config
+            .embedding_base_url
+            .clone()
+            .or_else(|| env("OPENAI_API_BASE"))
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+
+        let mut headers = HeaderMap::new();
+        let key_env = config.embedding_api_key_env.as_str();
+        let from_env = Some(key_env)
+            .filter(|name| !name.is_empty())
+            .and_then(&env)
+            .filter(|key| !key.is_empty());
+        let from_config = config.openai_api_key.as_ref().map(|key| key.expose());
+        match (from_env.as_deref(), from_config) {
+            (Some(key), _) => {
+                let value = format!("Bearer {}", key);
+                headers.insert(AUTHORIZATION, sensitive(&value, key_env)?);
+            }
+            (None, Some(key)) => {
+                let value = format!("Bearer {}", key);
+                headers.insert(AUTHORIZATION, sensitive(&value, "openai_api_key")?);
+            }
+            (None, None) if key_env.is_empty() => {}
+            (None, None) => anyhow::bail!(
+                "{} environment variable not set (nor openai_api_key in the config)",
+                key_env
+            ),
+        }
+        for (name, value) in &config.embedding_extra_headers {
+            let header = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+                anyhow::anyhow!("Invalid header name '{}' in embedding_extra_headers", name)
+            })?;
+            let value = substitute(value, &env).map_err(|var| {
+                anyhow::anyhow!(
+                    "embedding_extra_headers: {} uses ${{{}}}, which is not set",
+                    name,
+                    var
+                )
+            })?;
+            headers.insert(header, sensitive(&value, name)?);
+        }
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            headers,
+        })
+    }
+
+    /// A POST to `path` under the base URL, with the endpoint's headers.
+    pub fn post(&self, client: &reqwest::Client, path: &str) -> reqwest::RequestBuilder {
+        client
+            .post(format!("{}/{}", self.base_url, path))
+            .headers(self.headers.clone())
+    }
+}
+
+/// `value` as a header value reqwest keeps out of its logs. Errors name
+/// `what` but never hold the value.
+fn sensitive(value: &str, what: &str) -> Result<HeaderValue> {
+    let mut value = HeaderValue::from_str(value)
+        .map_err(|_| anyhow::anyhow!("Invalid characters in the value of {}", what))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// `value` with each `${VAR}` replaced by `env(VAR)`, or the first variable
+/// that is not set.
+fn substitute(value: &str, env: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let var = &rest[start + 2..start + 2 + len];
+        out.push_str(&rest[..start]);
+        out.push_str(&env(var).ok_or_else(|| var.to_string())?);
+        rest = &rest[start + 3 + len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Secret;
+    use std::collections::HashMap;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_endpoint_from_config() {
+        // Plain OpenAI, with the legacy OPENAI_API_BASE.
+        let config = AppConfig::default();
+        let endpoint = Endpoint::with_env(&config, env(&[("OPENAI_API_KEY", "sk-1")])).unwrap();
+        assert_eq!(endpoint.base_url, "https://api.openai.com/v1");
+        assert_eq!(endpoint.headers[AUTHORIZATION], "Bearer sk-1");
+        let legacy = env(&[
+            ("OPENAI_API_KEY", "sk-1"),
+            ("OPENAI_API_BASE", "http://proxy/v1/"),
+        ]);
+        let endpoint = Endpoint::with_env(&config, legacy).unwrap();
+        assert_eq!(endpoint.base_url, "http://proxy/v1");
+        let err = Endpoint::with_env(&config, env(&[])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "OPENAI_API_KEY environment variable not set (nor openai_api_key in the config)"
+        );
+
+        // A key in the config, which the environment overrides.
+        let keyed = AppConfig {
+            openai_api_key: Some(Secret::new("sk-config")),
+            ..AppConfig::default()
+        };
+        let endpoint = Endpoint::with_env(&keyed, env(&[])).unwrap();
+        assert_eq!(endpoint.headers[AUTHORIZATION], "Bearer sk-config");
+        let endpoint = Endpoint::with_env(&keyed, env(&[("OPENAI_API_KEY", "sk-env")])).unwrap();
+        assert_eq!(endpoint.headers[AUTHORIZATION], "Bearer sk-env");
+
+        // TEI without auth, LiteLLM with its own key variable and headers.
+        let tei = AppConfig {
+            embedding_base_url: Some("http://tei:8080/v1".to_string()),
+            embedding_api_key_env: String::new(),
+            ..AppConfig::default()
+        };
+        let endpoint =
+            Endpoint::with_env(&tei, env(&[("OPENAI_API_BASE", "http://other")])).unwrap();
+        assert_eq!(endpoint.base_url, "http://tei:8080/v1");
+        assert!(endpoint.headers.is_empty());
+
+        let litellm = AppConfig {
+            embedding_api_key_env: "LITELLM_KEY".to_string(),
+            embedding_extra_headers: [
+                ("X-Org".to_string(), "search-${TEAM}".to_string()),
+                ("X-Static".to_string(), "1".to_string()),
+            ]
+            .into(),
+            ..AppConfig::default()
+        };
+        let vars = [("LITELLM_KEY", "lk-2"), ("TEAM", "docs")];
+        let endpoint = Endpoint::with_env(&litellm, env(&vars)).unwrap();
+        assert_eq!(endpoint.headers[AUTHORIZATION], "Bearer lk-2");
+        assert_eq!(endpoint.headers["x-org"], "search-docs");
+        assert_eq!(endpoint.headers["x-static"], "1");
+        let err = Endpoint::with_env(&litellm, env(&[("LITELLM_KEY", "lk-2")])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "embedding_extra_headers: X-Org uses ${TEAM}, which is not set"
+        );
+    }
+
+    #[test]
+    fn test_secrets_not_printed() {
+        let config = AppConfig {
+            embedding_extra_headers: [("X-Token".to_string(), "${TOKEN}".to_string())].into(),
+            ..AppConfig::default()
+        };
+        let vars = [("OPENAI_API_KEY", "sk-secret"), ("TOKEN", "tok-secret")];
+        let endpoint = Endpoint::with_env(&config, env(&vars)).unwrap();
+        let debug = format!("{:?}", endpoint);
+        assert!(debug.contains("x-token"), "{}", debug);
+        assert!(!debug.contains("secret"), "{}", debug);
+        assert!(endpoint.headers.values().all(HeaderValue::is_sensitive));
+        let request = endpoint
+            .post(&reqwest::Client::new(), "embeddings")
+            .build()
+            .unwrap();
+        assert!(!format!("{:?}", request).contains("secret"));
+
+        // A bad value is reported without echoing it.
+        let vars = [("OPENAI_API_KEY", "sk-secret\n"), ("TOKEN", "t")];
+        let err = Endpoint::with_env(&config, env(&vars))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Invalid characters in the value of OPENAI_API_KEY");
+    }
+
+    #[test]
+    fn test_dimensions() {
+        let config = |model: &str, dimension, force| AppConfig {
+            embedding_model: model.to_string(),
+            embedding_dimension: dimension,
+            embedding_request_dimensions: force,
+            ..AppConfig::default()
+        };
+        assert_eq!(request_dimensions(&AppConfig::default()), None);
+        let large = config("text-embedding-3-large", 1024, false);
+        assert_eq!(request_dimensions(&large), Some(1024));
+        assert_eq!(
+            request_dimensions(&config("text-embedding-3-large", 3072, false)),
+            None
+        );
+        assert_eq!(request_dimensions(&config("bge-m3", 512, false)), None);
+        assert_eq!(request_dimensions(&config("bge-m3", 512, true)), Some(512));
+
+        assert!(check_dimensions(&large, &[vec![0.0; 1024], vec![0.0; 1024]]).is_ok());
+        let err = check_dimensions(&large, &[vec![0.0; 3072]]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The embedding provider returned 3072-dimensional vectors, but embedding_dimension \
+             is 1024: it ignored the `dimensions` parameter; does text-embedding-3-large support it?"
+        );
+        let err = check_dimensions(&config("bge-m3", 1536, false), &[vec![0.0; 1024]]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("embedding_request_dimensions = true"));
+    }
+
+    #[tokio::test]
+    async fn test_headers_sent() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}/v1", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request).to_ascii_lowercase()
+        });
+
+        let config = AppConfig {
+            embedding_base_url: Some(base),
+            embedding_extra_headers: [("X-Org".to_string(), "acme".to_string())].into(),
+            ..AppConfig::default()
+        };
+        let endpoint = Endpoint::with_env(&config, env(&[("OPENAI_API_KEY", "sk-3")])).unwrap();
+        endpoint
+            .post(&reqwest::Client::new(), "embeddings")
+            .send()
+            .await
+            .unwrap();
+        let request = server.await.unwrap();
+        assert!(request.starts_with("post /v1/embeddings "), "{}", request);
+        assert!(request.contains("authorization: bearer sk-3\r\n"));
+        assert!(request.contains("x-org: acme\r\n"));
+    }
+}
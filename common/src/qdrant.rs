@@ -0,0 +1,136 @@
+//! Qdrant helpers shared by the server and `ragctl`.
+
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::Result;
+use qdrant_client::qdrant::{facet_value, CountPointsBuilder, FacetCountsBuilder, Filter};
+use qdrant_client::Qdrant;
+
+use crate::config::AppConfig;
+use crate::types::SearchFilters;
+
+/// Clients built so far, by URL and API key. A client's clones share its
+/// channel pool, so each Qdrant is connected to once per process rather
+/// than for every search or ingest batch.
+static CLIENTS: Mutex<Vec<(ClientKey, Qdrant)>> = Mutex::new(Vec::new());
+
+type ClientKey = (String, Option<String>);
+
+/// The Qdrant client for the configured URL and API key, built on first
+/// use. The version check is skipped: it blocks the calling thread on a
+/// health request (defeating tool timeouts) and prints to stdout, which the
+/// server's stdio transport reserves for protocol messages.
+pub fn connect(config: &AppConfig) -> Result<Qdrant> {
+    let key = (config.qdrant_url.clone(), config.qdrant_api_key());
+    let mut clients = CLIENTS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((_, client)) = clients.iter().find(|(k, _)| *k == key) {
+        return Ok(client.clone());
+    }
+    let client = Qdrant::from_url(&key.0)
+        .api_key(key.1.clone())
+        .skip_compatibility_check()
+        .build()?;
+    clients.push((key, client.clone()));
+    Ok(client)
+}
+
+/// Results fetched per wanted one when they're checked against
+/// `path_prefix`, as some may be outside it.
+const PREFIX_OVERFETCH: usize = 4;
+
+/// How many results to ask Qdrant for to end up with `limit`, when
+/// `check_prefix` says they are still checked against `path_prefix`.
+pub fn fetch(limit: usize, check_prefix: bool) -> usize {
+    if check_prefix {
+        limit * PREFIX_OVERFETCH
+    } else {
+        limit
+    }
+}
+
+/// Count the points matching `filter` whose `source_path` is under
+/// `filters.path_prefix`, without fetching them: Qdrant facets the points by
+/// `source_path` and the counts of the documents under the prefix are added
+/// up. Needs a keyword index on `source_path`.
+pub async fn count_under_prefix(
+    client: &Qdrant,
+    config: &AppConfig,
+    filter: Option<Filter>,
+    filters: &SearchFilters,
+) -> Result<u64> {
+    let mut count = CountPointsBuilder::new(&config.collection_name).exact(true);
+    let mut facet = FacetCountsBuilder::new(&config.collection_name, "source_path").exact(true);
+    if let Some(filter) = filter {
+        count = count.filter(filter.clone());
+        facet = facet.filter(filter);
+    }
+    let points = client.count(count).await?.result.map_or(0, |r| r.count);
+    if points == 0 {
+        return Ok(0);
+    }
+    // Every document has a point, so this limit returns all of them.
+    let response = client.facet(facet.limit(points)).await?;
+    Ok(response
+        .hits
+        .iter()
+        .filter(|hit| {
+            let path = hit.value.as_ref().and_then(|v| v.variant.as_ref());
+            matches!(path, Some(facet_value::Variant::StringValue(path)) if filters.matches_path(path))
+        })
+        .map(|hit| hit.count)
+        .sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Secret;
+
+    #[test]
+    fn test_connect_sends_api_key() {
+        let config = AppConfig {
+            qdrant_url: "https://cluster.cloud.qdrant.io:6334".to_string(),
+            qdrant_api_key: Some(Secret::new("qdrant-secret")),
+            ..AppConfig::default()
+        };
+        let client = connect(&config).unwrap();
+        assert_eq!(client.config.api_key.as_deref(), Some("qdrant-secret"));
+        assert_eq!(client.config.uri, "https://cluster.cloud.qdrant.io:6334");
+    }
+
+    #[test]
+    fn test_connect_reuses_client() {
+        let url = format!("http://qdrant-{}:6334", uuid::Uuid::new_v4());
+        let config = AppConfig {
+            qdrant_url: url.clone(),
+            ..AppConfig::default()
+        };
+        let clients_for_url = || {
+            CLIENTS
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|((u, _), _)| *u == url)
+                .count()
+        };
+        connect(&config).unwrap();
+        connect(&config).unwrap();
+        assert_eq!(clients_for_url(), 1);
+        // Another key is another client.
+        let keyed = AppConfig {
+            qdrant_api_key: Some(Secret::new("other")),
+            ..config
+        };
+        assert_eq!(
+            connect(&keyed).unwrap().config.api_key.as_deref(),
+            Some("other")
+        );
+        assert_eq!(clients_for_url(), 2);
+    }
+
+    #[test]
+    fn test_fetch() {
+        assert_eq!(fetch(30, true), 120);
+        assert_eq!(fetch(30, false), 30);
+    }
+}
@@ -0,0 +1,370 @@
+//! Retries of embedding API calls.
+//!
+//! A rate limit (429), a server error (5xx) or a request that never got a
+//! response (connection refused or reset, timed out) is retried, up to
+//! `embedding_max_attempts` attempts in all. Between attempts it waits as
+//! long as the response's `Retry-After` header asks, or else an
+//! exponentially growing delay with jitter, so that the sub-batches ingest
+//! keeps in flight don't retry in lockstep. Any other error status (a bad
+//! API key, a malformed request) fails at once, with the response body in
+//! the error.
+//!
+//! Every attempt has a deadline of its own: `embedding_timeout_secs` for
+//! ingest and the shorter `search_embedding_timeout_secs` for the server's
+//! query embeddings, which a client is waiting on.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use reqwest::{RequestBuilder, Response, StatusCode};
+
+use crate::config::AppConfig;
+
+/// Longest delay between attempts that backoff grows to.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Longest `Retry-After` honored; a provider asking for more is retried
+/// sooner (and most likely fails again) rather than stalling ingest.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// An error status the provider answered with.
+#[derive(Debug, thiserror::Error)]
+#[error("{provider} API error ({status}): {body}")]
+pub struct ApiError {
+    pub provider: String,
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl ApiError {
+    /// Whether `error` is the provider rejecting the request as malformed,
+    /// as it does an input over the model's token limit.
+    pub fn is_bad_request(error: &anyhow::Error) -> bool {
+        error
+            .downcast_ref::<Self>()
+            .is_some_and(|e| e.status == StatusCode::BAD_REQUEST)
+    }
+}
+
+/// How often, and how patiently, a request is retried.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts in all, the first one included.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled before each one after it.
+    pub base_delay: Duration,
+    /// How long an attempt may take, reading the response included.
+    pub timeout: Duration,
+}
+
+impl RetryPolicy {
+    /// The policy for ingest's embedding requests.
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            max_attempts: config.embedding_max_attempts.max(1),
+            base_delay: Duration::from_millis(config.embedding_retry_base_ms),
+            timeout: Duration::from_secs(config.embedding_timeout_secs),
+        }
+    }
+
+    /// The policy for embedding search queries.
+    pub fn for_search(config: &AppConfig) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.search_embedding_timeout_secs),
+            ..Self::new(config)
+        }
+    }
+
+    /// Send the request `build` makes until it succeeds, fails for good or
+    /// runs out of attempts. `provider` names the API in errors and logs.
+    pub async fn send<F>(&self, provider: &str, mut build: F) -> Result<Response>
+    where
+        F: FnMut() -> RequestBuilder,
+    {
+        let mut attempt = 1;
+        loop {
+            let started = Instant::now();
+            let (error, retry_after) = match build().timeout(self.timeout).send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = retry_after(&response);
+                    let body = response.text().await.unwrap_or_default();
+                    let error = anyhow::Error::new(ApiError {
+                        provider: provider.to_string(),
+                        status,
+                        body,
+                    });
+                    if !is_retryable(status) {
+                        return Err(error);
+                    }
+                    (error, retry_after)
+                }
+                // A request that couldn't be built won't be sent by trying again.
+                Err(e) if e.is_builder() => return Err(e.into()),
+                Err(e) if e.is_timeout() => (
+                    anyhow::anyhow!(
+                        "{} API request timed out after {:.1}s",
+                        provider,
+                        started.elapsed().as_secs_f64()
+                    ),
+                    None,
+                ),
+                Err(e) => (
+                    anyhow::anyhow!("{} API request failed: {}", provider, e),
+                    None,
+                ),
+            };
+            if attempt >= self.max_attempts {
+                if attempt == 1 {
+                    return Err(error);
+                }
+                anyhow::bail!("{} (gave up after {} attempts)", error, attempt);
+            }
+            let delay = retry_after.unwrap_or_else(|| self.backoff(attempt));
+            tracing::warn!(
+                "{}; retrying in {:.1}s (attempt {} of {})",
+                error,
+                delay.as_secs_f64(),
+                attempt + 1,
+                self.max_attempts
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// The delay after failed attempt number `attempt`: the exponential
+    /// delay, of which a random half is waived.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(MAX_BACKOFF);
+        exponential / 2 + exponential.mul_f64(fastrand::f64() / 2.0)
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// The wait a response's `Retry-After` header asks for, given in seconds or
+/// as an HTTP date.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    let delay = match value.parse::<u64>() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(_) => {
+            let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+            (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+                .to_std()
+                .unwrap_or_default()
+        }
+    };
+    Some(delay.min(MAX_RETRY_AFTER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Instant;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve `responses` (status line, headers, body) in turn on a local
+    /// port, the last one for any request after them. Returns the URL and
+    /// the count of requests served.
+    async fn serve(
+        responses: Vec<(&'static str, &'static str, &'static str)>,
+    ) -> (String, Arc<AtomicUsize>) {
+        serve_after(Vec::new(), responses).await
+    }
+
+    /// Like `serve`, but answer request `n` only after `delays[n]`.
+    async fn serve_after(
+        delays: Vec<Duration>,
+        responses: Vec<(&'static str, &'static str, &'static str)>,
+    ) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/embeddings", listener.local_addr().unwrap());
+        let served = Arc::new(AtomicUsize::new(0));
+        let count = served.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let n = count.fetch_add(1, Ordering::SeqCst);
+                let (status, headers, body) = responses[n.min(responses.len() - 1)];
+                let delay = delays.get(n).copied().unwrap_or_default();
+                let response = format!(
+                    "HTTP/1.1 {}\r\ncontent-length: {}\r\nconnection: close\r\n{}\r\n{}",
+                    status,
+                    body.len(),
+                    headers,
+                    body
+                );
+                // Answered on a task of its own, so a slow answer doesn't hold
+                // up the retry.
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    socket.write_all(response.as_bytes()).await.ok();
+                    socket.shutdown().await.ok();
+                });
+            }
+        });
+        (url, served)
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    async fn post(policy: &RetryPolicy, url: &str) -> Result<String> {
+        let client = reqwest::Client::new();
+        let response = policy
+            .send("OpenAI", || client.post(url).body("{}"))
+            .await?;
+        Ok(response.text().await?)
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let (url, served) = serve(vec![
+            ("500 Internal Server Error", "", "oops"),
+            ("503 Service Unavailable", "", "busy"),
+            ("200 OK", "", "{\"data\":[]}"),
+        ])
+        .await;
+        assert_eq!(post(&policy(), &url).await.unwrap(), "{\"data\":[]}");
+        assert_eq!(served.load(Ordering::SeqCst), 3);
+
+        // Out of attempts, the last error is reported.
+        let (url, served) = serve(vec![("502 Bad Gateway", "", "upstream down")]).await;
+        let err = post(&policy(), &url).await.unwrap_err().to_string();
+        assert!(err.contains("502 Bad Gateway"), "{}", err);
+        assert!(
+            err.contains("upstream down (gave up after 3 attempts)"),
+            "{}",
+            err
+        );
+        assert_eq!(served.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_honors_retry_after() {
+        let (url, served) = serve(vec![
+            ("429 Too Many Requests", "retry-after: 1\r\n", "slow down"),
+            ("200 OK", "", "ok"),
+        ])
+        .await;
+        let started = Instant::now();
+        assert_eq!(post(&policy(), &url).await.unwrap(), "ok");
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(served.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_fail_at_once() {
+        for status in ["401 Unauthorized", "400 Bad Request"] {
+            let (url, served) = serve(vec![(status, "", "{\"error\":\"invalid api key\"}")]).await;
+            let err = post(&policy(), &url).await.unwrap_err().to_string();
+            assert_eq!(
+                err,
+                format!(
+                    "OpenAI API error ({}): {{\"error\":\"invalid api key\"}}",
+                    status
+                )
+            );
+            assert_eq!(served.load(Ordering::SeqCst), 1);
+        }
+        let (url, _) = serve(vec![("400 Bad Request", "", "too long")]).await;
+        assert!(ApiError::is_bad_request(
+            &post(&policy(), &url).await.unwrap_err()
+        ));
+        let (url, _) = serve(vec![("401 Unauthorized", "", "")]).await;
+        assert!(!ApiError::is_bad_request(
+            &post(&policy(), &url).await.unwrap_err()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_retries_connection_errors() {
+        // Nothing listens on a port just freed.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/embeddings", listener.local_addr().unwrap());
+        drop(listener);
+        let once = RetryPolicy {
+            max_attempts: 1,
+            ..policy()
+        };
+        let err = post(&once, &url).await.unwrap_err().to_string();
+        assert!(err.starts_with("OpenAI API request failed"), "{}", err);
+        let err = post(&policy(), &url).await.unwrap_err().to_string();
+        assert!(err.ends_with("(gave up after 3 attempts)"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_retries_timeouts() {
+        let quick = RetryPolicy {
+            timeout: Duration::from_millis(300),
+            ..policy()
+        };
+        // The first answer comes too late; the retry's in time.
+        let (url, served) =
+            serve_after(vec![Duration::from_secs(5)], vec![("200 OK", "", "ok")]).await;
+        let started = Instant::now();
+        assert_eq!(post(&quick, &url).await.unwrap(), "ok");
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(served.load(Ordering::SeqCst), 2);
+
+        let (url, served) =
+            serve_after(vec![Duration::from_secs(5); 3], vec![("200 OK", "", "ok")]).await;
+        let err = post(&quick, &url).await.unwrap_err().to_string();
+        assert!(
+            err.starts_with("OpenAI API request timed out after 0."),
+            "{}",
+            err
+        );
+        assert!(err.ends_with("s (gave up after 3 attempts)"), "{}", err);
+        assert_eq!(served.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(400),
+            timeout: Duration::from_secs(30),
+        };
+        for (attempt, full) in [(1, 400), (2, 800), (3, 1600)] {
+            let delay = policy.backoff(attempt);
+            assert!(delay >= Duration::from_millis(full / 2), "{:?}", delay);
+            assert!(delay <= Duration::from_millis(full), "{:?}", delay);
+        }
+        assert!(policy.backoff(40) <= MAX_BACKOFF);
+        let config = AppConfig::default();
+        assert_eq!(RetryPolicy::new(&config).max_attempts, 3);
+        assert_eq!(RetryPolicy::new(&config).timeout, Duration::from_secs(30));
+        assert_eq!(
+            RetryPolicy::for_search(&config).timeout,
+            Duration::from_secs(10)
+        );
+    }
+}
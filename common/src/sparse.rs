@@ -0,0 +1,182 @@
+//! BM25-style sparse vectors, the lexical half of hybrid search when
+//! `lexical_backend = "qdrant-sparse"` keeps it in Qdrant instead of
+//! Tantivy.
+//!
+//! Terms are lowercased words, with runs of CJK characters (which aren't
+//! separated by spaces) split into overlapping character pairs, and are
+//! hashed into the sparse vector's index space, so no vocabulary has to be
+//! stored or shared between `ragctl` and the server. A document weighs
+//! each term by BM25's saturated term frequency; the IDF half of BM25 is
+//! Qdrant's, from the vector's IDF modifier, so it stays current as
+//! documents are added.
+
+/// Name of the sparse vector in the collection.
+pub const SPARSE_VECTOR: &str = "bm25";
+
+/// BM25's term frequency saturation.
+const K1: f32 = 1.2;
+
+/// BM25's length normalization.
+const B: f32 = 0.75;
+
+/// Terms of an average chunk, for the length normalization. Qdrant doesn't
+/// know the collection's, so this is the default chunk size's (1000
+/// characters) worth of English words, roughly.
+const AVERAGE_TERMS: f32 = 170.0;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SparseVector {
+    pub indices: Vec<u32>,
+    pub values: Vec<f32>,
+}
+
+/// The vector a chunk's text is stored with.
+pub fn document_vector(text: &str) -> SparseVector {
+    let terms = terms(text);
+    let length = terms.len() as f32;
+    let counts = merge(terms.iter().map(|term| (term_index(term), 1.0)).collect());
+    let norm = K1 * (1.0 - B + B * length / AVERAGE_TERMS);
+    SparseVector {
+        indices: counts.iter().map(|(index, _)| *index).collect(),
+        values: counts
+            .iter()
+            .map(|(_, tf)| tf * (K1 + 1.0) / (tf + norm))
+            .collect(),
+    }
+}
+
+/// The vector a query is searched with: its terms, each weighing 1, so a
+/// chunk's score is the sum of its BM25 weights for them.
+pub fn query_vector(text: &str) -> SparseVector {
+    let terms = terms(text);
+    let indices = merge(terms.iter().map(|term| (term_index(term), 1.0)).collect());
+    SparseVector {
+        indices: indices.iter().map(|(index, _)| *index).collect(),
+        values: vec![1.0; indices.len()],
+    }
+}
+
+/// Sort `entries` by index, adding up the values of duplicates (distinct
+/// terms whose hashes collide count as one).
+fn merge(mut entries: Vec<(u32, f32)>) -> Vec<(u32, f32)> {
+    entries.sort_by_key(|(index, _)| *index);
+    let mut merged: Vec<(u32, f32)> = Vec::with_capacity(entries.len());
+    for (index, value) in entries {
+        match merged.last_mut() {
+            Some(last) if last.0 == index => last.1 += value,
+            _ => merged.push((index, value)),
+        }
+    }
+    merged
+}
+
+fn terms(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut word = String::new();
+    let mut cjk: Vec<char> = Vec::new();
+    for c in text.chars() {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                terms.push(std::mem::take(&mut word));
+            }
+            cjk.push(c);
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk, &mut terms);
+            word.extend(c.to_lowercase());
+        } else {
+            flush_cjk(&mut cjk, &mut terms);
+            if !word.is_empty() {
+                terms.push(std::mem::take(&mut word));
+            }
+        }
+    }
+    flush_cjk(&mut cjk, &mut terms);
+    if !word.is_empty() {
+        terms.push(word);
+    }
+    terms
+}
+
+/// Move the CJK run `cjk` into `terms` as character pairs.
+fn flush_cjk(cjk: &mut Vec<char>, terms: &mut Vec<String>) {
+    match cjk.len() {
+        0 => {}
+        1 => terms.push(cjk[0].to_string()),
+        _ => terms.extend(cjk.windows(2).map(|pair| pair.iter().collect())),
+    }
+    cjk.clear();
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'     // Hiragana, Katakana
+        | '\u{3400}'..='\u{4dbf}'   // CJK Extension A
+        | '\u{4e00}'..='\u{9fff}'   // CJK Unified Ideographs
+        | '\u{ac00}'..='\u{d7af}'   // Hangul syllables
+        | '\u{f900}'..='\u{faff}'   // CJK Compatibility Ideographs
+    )
+}
+
+/// FNV-1a: stable across builds and platforms, unlike std's hasher.
+fn term_index(term: &str) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in term.as_bytes() {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terms() {
+        assert_eq!(
+            terms("Roll back the DB-migration!"),
+            ["roll", "back", "the", "db", "migration"]
+        );
+        assert_eq!(
+            terms("検索エンジン v2"),
+            ["検索", "索エ", "エン", "ンジ", "ジン", "v2"]
+        );
+        assert_eq!(terms("a日b"), ["a", "日", "b"]);
+    }
+
+    #[test]
+    fn test_document_vector() {
+        let vector = document_vector("deploy the deploy script");
+        assert_eq!(vector.indices.len(), 3);
+        assert!(vector.indices.windows(2).all(|pair| pair[0] < pair[1]));
+        let weight = |term: &str| {
+            let at = vector
+                .indices
+                .iter()
+                .position(|i| *i == term_index(term))
+                .unwrap();
+            vector.values[at]
+        };
+        // A repeated term weighs more, but less than twice as much.
+        assert!(weight("deploy") > weight("script"));
+        assert!(weight("deploy") < 2.0 * weight("script"));
+        // The same term weighs less in a longer chunk.
+        let long = document_vector(&format!("script {}", "word ".repeat(400)));
+        let at = long
+            .indices
+            .iter()
+            .position(|i| *i == term_index("script"))
+            .unwrap();
+        assert!(long.values[at] < weight("script"));
+    }
+
+    #[test]
+    fn test_query_vector() {
+        let vector = query_vector("Deploy deploy script");
+        assert_eq!(vector.values, vec![1.0, 1.0]);
+        let mut expected = vec![term_index("deploy"), term_index("script")];
+        expected.sort();
+        assert_eq!(vector.indices, expected);
+        assert_eq!(query_vector("  "), SparseVector::default());
+    }
+}
@@ -1,4 +1,8 @@
 use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::fmt;
+use std::ops::Range;
+use std::time::Instant;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChunkPayload {
@@ -9,6 +13,82 @@ pub struct ChunkPayload {
     pub chunk_index: u32,
     pub text: String,
     pub updated_at: String,
+    /// Tags from the document's front matter.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Headings the chunk falls under, e.g. "Deployment > Rollback".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+    #[serde(flatten)]
+    pub location: ChunkLocation,
+    /// Modification time (RFC3339) of the source file when it was ingested.
+    /// Unlike `updated_at`, the time of ingest, this dates the content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_mtime: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_size_bytes: Option<u64>,
+    /// ISO 639-1 code of the document's language, or "und" when it could
+    /// not be detected. Unset for chunks indexed before detection existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// What turned the file into text, for files that needed converting:
+    /// `markitdown`, `native` (the built-in PDF, DOCX and XLSX readers), or
+    /// the program name of a `[[converters]]` command.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub converter: Option<String>,
+    /// The text was recognized from an image or a scanned PDF, so it may
+    /// hold recognition errors.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ocr: bool,
+    /// Encoding a text file was decoded from, e.g. `UTF-8` or `Shift_JIS`.
+    /// Unset for converted files and for chunks indexed before it was
+    /// recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// The text was over `embedding_max_tokens`, so its vector only covers
+    /// the start of it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub embedding_truncated: bool,
+}
+
+/// Where a chunk sits in its source file, for opening the file at the
+/// chunk. Unset for chunks indexed before locations were recorded, and for
+/// sources without a position to point at (web pages, EPUBs and flattened
+/// JSON/YAML/TOML).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkLocation {
+    /// Character offset of the chunk's first character.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_char: Option<u32>,
+    /// Character offset just past the chunk's last character.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_char: Option<u32>,
+    /// 1-based line the chunk starts on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<u32>,
+    /// The offsets are into the markdown markitdown converted the file to,
+    /// or the text read from a PDF, not into the file itself.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub converted: bool,
+}
+
+/// Namespace of the UUIDv5 chunk ids.
+pub const CHUNK_ID_NAMESPACE: uuid::Uuid =
+    uuid::Uuid::from_u128(0x81422f7d_6603_415f_82b6_bdbe5ad1faf8);
+
+/// Id of chunk `chunk_index` of `source_path`: the UUIDv5 of
+/// `"{source_path}#{chunk_index}"` in [`CHUNK_ID_NAMESPACE`]. Re-ingesting a
+/// document gives its chunks the same ids, so they overwrite the old ones.
+pub fn chunk_id(source_path: &str, chunk_index: u32) -> String {
+    let hash = Sha1::new()
+        .chain_update(CHUNK_ID_NAMESPACE.as_bytes())
+        .chain_update(format!("{}#{}", source_path, chunk_index))
+        .finalize();
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    uuid::Builder::from_sha1_bytes(bytes)
+        .into_uuid()
+        .to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,7 +98,21 @@ pub struct SearchResult {
     pub title: String,
     pub source_path: String,
     pub source_type: String,
+    /// Heading path of the chunk, for chunks split at markdown headings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+    #[serde(flatten)]
+    pub location: ChunkLocation,
+    /// Modification time (RFC3339) of the source file when it was ingested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_mtime: Option<String>,
     pub snippet: String,
+    /// Full chunk text, only populated when requested via `SearchOptions::include_text`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Number of candidate chunks from the same document, set when grouping by document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_match_count: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +120,9 @@ pub struct ChunkDetail {
     pub chunk_id: String,
     pub text: String,
     pub metadata: ChunkMetadata,
+    /// Adjacent chunks from the same document, ordered by chunk_index.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub neighbors: Vec<ChunkDetail>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +131,23 @@ pub struct ChunkMetadata {
     pub source_path: String,
     pub source_type: String,
     pub chunk_index: u32,
+    /// Address of the web page the chunk was fetched from, for citing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(flatten)]
+    pub location: ChunkLocation,
+    /// Modification time (RFC3339) of the source file when it was ingested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_mtime: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_size_bytes: Option<u64>,
+    /// What converted the file to text (`markitdown`, `native` or an
+    /// external converter's program name).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub converter: Option<String>,
+    /// The text was produced by OCR.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ocr: bool,
 }
 
 /// A single exported chunk with its payload and embedding vector.
@@ -41,12 +155,207 @@ pub struct ChunkMetadata {
 pub struct ExportedChunk {
     pub payload: ChunkPayload,
     pub embedding: Vec<f32>,
+    /// The title's vector, from collections with one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title_embedding: Option<Vec<f32>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct SearchFilters {
     pub source_type: Option<String>,
     pub path_prefix: Option<String>,
+    /// Only match chunks carrying every one of these tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Only match chunks of files modified after this time. Chunks without
+    /// a recorded `file_mtime` never match.
+    #[serde(default)]
+    pub modified_after: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only match chunks of documents detected as this language, e.g. "ja".
+    #[serde(default)]
+    pub language: Option<String>,
+    /// `true` only matches chunks whose text came from OCR, `false` leaves
+    /// them out.
+    #[serde(default)]
+    pub ocr: Option<bool>,
+}
+
+impl SearchFilters {
+    /// Whether `source_path` is under `path_prefix` (any path, without one).
+    pub fn matches_path(&self, source_path: &str) -> bool {
+        match self.path_prefix {
+            Some(ref prefix) => source_path.starts_with(prefix.as_str()),
+            None => true,
+        }
+    }
+
+    /// Whether a chunk tagged `chunk_tags` carries every tag in `tags`.
+    pub fn matches_tags<'a>(&self, chunk_tags: impl IntoIterator<Item = &'a str>) -> bool {
+        if self.tags.is_empty() {
+            return true;
+        }
+        let chunk_tags: Vec<&str> = chunk_tags.into_iter().collect();
+        self.tags
+            .iter()
+            .all(|tag| chunk_tags.contains(&tag.as_str()))
+    }
+}
+
+/// Payload field of a chunk's [`path_ancestors`], keyword-indexed so
+/// Qdrant can evaluate `path_prefix` filters, which it has no prefix match
+/// for.
+pub const PATH_PREFIXES: &str = "path_prefixes";
+
+/// The directories `source_path` is in, as the prefixes of it that end in
+/// a separator: `/docs/api/x.md` is in `/`, `/docs/` and `/docs/api/`.
+pub fn path_ancestors(source_path: &str) -> Vec<String> {
+    source_path
+        .match_indices(['/', '\\'])
+        .map(|(i, separator)| source_path[..i + separator.len()].to_string())
+        .collect()
+}
+
+/// The [`path_ancestors`] entry a `path_prefix` filter can be narrowed to:
+/// the prefix up to its last separator, or `None` without one. Paths under
+/// the prefix are all in that directory; when it's the whole prefix, that's
+/// exactly them.
+pub fn path_prefix_directory(prefix: &str) -> Option<&str> {
+    prefix.rfind(['/', '\\']).map(|i| &prefix[..i + 1])
+}
+
+/// Default snippet length in characters.
+pub const DEFAULT_SNIPPET_LENGTH: usize = 200;
+
+/// Default cap on chunks per document when grouping results by document.
+pub const DEFAULT_MAX_CHUNKS_PER_DOCUMENT: usize = 2;
+
+/// Options controlling how search results are selected and rendered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchOptions {
+    /// Attach the full chunk text to each result.
+    pub include_text: bool,
+    /// Maximum snippet length in characters.
+    pub snippet_length: usize,
+    /// Limit how many chunks each source_path may contribute.
+    pub group_by_document: bool,
+    /// Per-document chunk cap used when `group_by_document` is set.
+    pub max_chunks_per_document: usize,
+    /// Wrap matched query terms in BM25 snippets with the configured markers.
+    pub highlight: bool,
+    /// Match the query against the chunks' title vectors instead of their
+    /// text's, in collections with title vectors.
+    pub search_titles: bool,
+    /// HNSW parameters of the vector search, over the config's.
+    pub search_params: VectorSearchParams,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            include_text: false,
+            snippet_length: DEFAULT_SNIPPET_LENGTH,
+            group_by_document: false,
+            max_chunks_per_document: DEFAULT_MAX_CHUNKS_PER_DOCUMENT,
+            highlight: true,
+            search_titles: false,
+            search_params: VectorSearchParams::default(),
+        }
+    }
+}
+
+/// Qdrant's HNSW parameters for a query's vector search. Unset ones take
+/// the `[qdrant]` config's `search_exact` and `search_hnsw_ef`, and unset
+/// there, Qdrant's.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorSearchParams {
+    /// Compare the query with every vector instead of walking the HNSW
+    /// graph: slower, but the true nearest neighbours, to check recall.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exact: Option<bool>,
+    /// Candidates kept while walking the graph; more finds more of the
+    /// true nearest neighbours, more slowly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hnsw_ef: Option<u64>,
+}
+
+impl VectorSearchParams {
+    /// These parameters without `hnsw_ef` if they're also `exact`, which
+    /// doesn't use it, with a warning.
+    pub fn checked(self) -> Self {
+        if self.exact == Some(true) && self.hnsw_ef.is_some() {
+            tracing::warn!("hnsw_ef is ignored by an exact search");
+            Self {
+                hnsw_ef: None,
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+
+    /// The parameters to search with: these, with `defaults` for the unset
+    /// ones. A default `hnsw_ef` is dropped by an exact search.
+    pub fn resolve(self, defaults: Self) -> Self {
+        let exact = self.exact.or(defaults.exact);
+        Self {
+            exact,
+            hnsw_ef: match exact {
+                Some(true) => None,
+                _ => self.hnsw_ef.or(defaults.hnsw_ef),
+            },
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for VectorSearchParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut params = Vec::new();
+        if let Some(exact) = self.exact {
+            params.push(format!("exact={}", exact));
+        }
+        if let Some(ef) = self.hnsw_ef {
+            params.push(format!("hnsw_ef={}", ef));
+        }
+        f.write_str(&params.join(" "))
+    }
+}
+
+/// Wall-clock time spent in each phase of a hybrid search, in milliseconds,
+/// and the HNSW parameters its vector search ran with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchTimings {
+    pub embed_ms: f64,
+    pub vector_ms: f64,
+    pub bm25_ms: f64,
+    pub fuse_ms: f64,
+    pub total_ms: f64,
+    #[serde(default, skip_serializing_if = "VectorSearchParams::is_default")]
+    pub search_params: VectorSearchParams,
+}
+
+impl SearchTimings {
+    /// Milliseconds elapsed since `start`, rounded to microseconds.
+    pub fn since(start: Instant) -> f64 {
+        (start.elapsed().as_secs_f64() * 1_000_000.0).round() / 1000.0
+    }
+}
+
+impl fmt::Display for SearchTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "embed={:.1}ms vector={:.1}ms bm25={:.1}ms fuse={:.1}ms total={:.1}ms",
+            self.embed_ms, self.vector_ms, self.bm25_ms, self.fuse_ms, self.total_ms
+        )?;
+        if !self.search_params.is_default() {
+            write!(f, " {}", self.search_params)?;
+        }
+        Ok(())
+    }
 }
 
 /// Truncate a string to at most `max_chars` characters (UTF-8 safe).
@@ -60,10 +369,97 @@ pub fn truncate_snippet(text: &str, max_chars: usize) -> String {
     }
 }
 
+/// Build a snippet of at most `max_chars` characters centered on the first
+/// occurrence of any query token. Falls back to a plain prefix truncation when
+/// no token is found.
+pub fn centered_snippet(text: &str, query: &str, max_chars: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= max_chars {
+        return text.to_string();
+    }
+
+    let lowered: Vec<char> = chars.iter().map(|c| lower_char(*c)).collect();
+    let hit = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .filter_map(|token| {
+            let needle: Vec<char> = token.chars().map(lower_char).collect();
+            lowered.windows(needle.len()).position(|w| w == needle)
+        })
+        .min();
+
+    let Some(pos) = hit else {
+        return truncate_snippet(text, max_chars);
+    };
+
+    // Keep a little leading context before the match.
+    let start = pos
+        .saturating_sub(max_chars / 4)
+        .min(chars.len() - max_chars);
+    let end = start + max_chars;
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push_str("...");
+    }
+    snippet.extend(&chars[start..end]);
+    if end < chars.len() {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+fn lower_char(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Wrap the given byte ranges of `fragment` in `prefix` / `postfix` markers.
+/// Overlapping ranges are merged.
+pub fn highlight_ranges(
+    fragment: &str,
+    ranges: &[Range<usize>],
+    prefix: &str,
+    postfix: &str,
+) -> String {
+    let mut sorted: Vec<Range<usize>> = ranges.to_vec();
+    sorted.sort_by_key(|r| r.start);
+
+    let mut out = String::with_capacity(fragment.len());
+    let mut pos = 0;
+    for range in sorted {
+        let start = range.start.max(pos);
+        if start >= range.end || range.end > fragment.len() {
+            continue;
+        }
+        out.push_str(&fragment[pos..start]);
+        out.push_str(prefix);
+        out.push_str(&fragment[start..range.end]);
+        out.push_str(postfix);
+        pos = range.end;
+    }
+    out.push_str(&fragment[pos..]);
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_chunk_id() {
+        // Same as Python's uuid.uuid5(CHUNK_ID_NAMESPACE, "/docs/a.md#0").
+        assert_eq!(
+            chunk_id("/docs/a.md", 0),
+            "8652ab2b-0339-5012-bff1-6caa3612031a"
+        );
+        assert_eq!(
+            chunk_id("/docs/a.md", 1),
+            "82f7cba6-28a2-524f-8adf-d0239c718bdb"
+        );
+        assert_eq!(chunk_id("/docs/a.md", 1), chunk_id("/docs/a.md", 1));
+        assert_ne!(chunk_id("/docs/a.md", 0), chunk_id("/docs/b.md", 0));
+    }
+
     #[test]
     fn test_truncate_short_text() {
         assert_eq!(truncate_snippet("hello", 10), "hello");
@@ -83,6 +479,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_truncate_japanese() {
         let text = "あいうえおかきくけこさしすせそ"; // 15 chars
         let result = truncate_snippet(&text, 5);
@@ -90,6 +487,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_truncate_mixed_multibyte() {
         let text = "Hello世界！こんにちは";
         let result = truncate_snippet(&text, 8);
@@ -101,11 +499,180 @@ mod tests {
         assert_eq!(truncate_snippet("", 10), "");
     }
 
+    #[test]
+    fn test_centered_snippet_short_text() {
+        assert_eq!(centered_snippet("hello world", "world", 50), "hello world");
+    }
+
+    #[test]
+    fn test_centered_snippet_centers_on_match() {
+        let text = format!("{} needle {}", "a ".repeat(100), "b ".repeat(100));
+        let snippet = centered_snippet(&text, "Needle", 40);
+        assert!(snippet.contains("needle"));
+        assert!(snippet.starts_with("..."));
+        assert!(snippet.ends_with("..."));
+    }
+
+    #[test]
+    fn test_centered_snippet_no_match_truncates() {
+        let text = "a".repeat(50);
+        assert_eq!(
+            centered_snippet(&text, "zzz", 10),
+            truncate_snippet(&text, 10)
+        );
+    }
+
+    #[test]
+    fn test_highlight_ranges() {
+        let out = highlight_ranges("the quick fox", &[4..9, 10..13], "**", "**");
+        assert_eq!(out, "the **quick** **fox**");
+    }
+
+    #[test]
+    fn test_highlight_ranges_overlapping() {
+        let out = highlight_ranges("abcdef", &[2..4, 1..3], "[", "]");
+        assert_eq!(out, "a[bc][d]ef");
+    }
+
     #[test]
     fn test_search_filters_default() {
         let f = SearchFilters::default();
         assert!(f.source_type.is_none());
         assert!(f.path_prefix.is_none());
+        assert!(f.tags.is_empty());
+        assert!(f.modified_after.is_none());
+    }
+
+    #[test]
+    fn test_search_options_default() {
+        let o = SearchOptions::default();
+        assert!(!o.include_text);
+        assert_eq!(o.snippet_length, DEFAULT_SNIPPET_LENGTH);
+        assert!(!o.group_by_document);
+        assert_eq!(o.max_chunks_per_document, DEFAULT_MAX_CHUNKS_PER_DOCUMENT);
+        assert!(o.highlight);
+    }
+
+    #[test]
+    fn test_search_timings_display() {
+        let t = SearchTimings {
+            embed_ms: 120.25,
+            vector_ms: 8.0,
+            bm25_ms: 2.5,
+            fuse_ms: 0.04,
+            total_ms: 130.79,
+            ..SearchTimings::default()
+        };
+        assert_eq!(
+            t.to_string(),
+            "embed=120.2ms vector=8.0ms bm25=2.5ms fuse=0.0ms total=130.8ms"
+        );
+        let t = SearchTimings {
+            search_params: VectorSearchParams {
+                exact: Some(false),
+                hnsw_ef: Some(256),
+            },
+            ..t
+        };
+        assert!(t
+            .to_string()
+            .ends_with("total=130.8ms exact=false hnsw_ef=256"));
+        assert_eq!(
+            serde_json::to_value(t).unwrap()["search_params"],
+            serde_json::json!({ "exact": false, "hnsw_ef": 256 })
+        );
+    }
+
+    #[test]
+    fn test_path_ancestors() {
+        assert_eq!(
+            path_ancestors("/docs/api/x.md"),
+            ["/", "/docs/", "/docs/api/"]
+        );
+        assert_eq!(path_ancestors(r"C:\docs\x.md"), [r"C:\", r"C:\docs\"]);
+        assert!(path_ancestors("x.md").is_empty());
+
+        assert_eq!(path_prefix_directory("/docs/"), Some("/docs/"));
+        assert_eq!(path_prefix_directory("/docs/ap"), Some("/docs/"));
+        assert_eq!(path_prefix_directory("docs"), None);
+        // Every path under a prefix is in its directory.
+        for path in ["/docs/api/x.md", "/docs/apple.md"] {
+            let directory = path_prefix_directory("/docs/ap").unwrap().to_string();
+            assert!(path_ancestors(path).contains(&directory));
+        }
+
+        let filters = SearchFilters {
+            path_prefix: Some("/docs/".to_string()),
+            ..SearchFilters::default()
+        };
+        assert!(filters.matches_path("/docs/a.md"));
+        assert!(!filters.matches_path("/notes/a.md"));
+        assert!(SearchFilters::default().matches_path("/notes/a.md"));
+    }
+
+    #[test]
+    fn test_matches_tags() {
+        let filters = SearchFilters {
+            tags: vec!["a".to_string(), "b".to_string()],
+            ..SearchFilters::default()
+        };
+        assert!(filters.matches_tags(["b", "c", "a"]));
+        assert!(!filters.matches_tags(["a"]));
+        assert!(!filters.matches_tags([]));
+        assert!(SearchFilters::default().matches_tags([]));
+    }
+
+    #[test]
+    fn test_vector_search_params() {
+        let ef = |ef| VectorSearchParams {
+            exact: None,
+            hnsw_ef: Some(ef),
+        };
+        let exact = VectorSearchParams {
+            exact: Some(true),
+            hnsw_ef: None,
+        };
+        // The query's win over the config's.
+        assert_eq!(ef(64).resolve(ef(128)), ef(64));
+        assert_eq!(VectorSearchParams::default().resolve(ef(128)), ef(128));
+        // An exact search drops the config's ef...
+        assert_eq!(exact.resolve(ef(128)), exact);
+        // ...and the query's, when checked.
+        let both = VectorSearchParams {
+            hnsw_ef: Some(64),
+            ..exact
+        };
+        assert_eq!(both.checked(), exact);
+        assert_eq!(ef(64).checked(), ef(64));
+        // A query can turn the config's exact search off.
+        let inexact = VectorSearchParams {
+            exact: Some(false),
+            hnsw_ef: None,
+        };
+        assert_eq!(inexact.resolve(exact), inexact);
+    }
+
+    #[test]
+    fn test_search_result_omits_missing_text() {
+        let r = SearchResult {
+            chunk_id: "id".to_string(),
+            score: 1.0,
+            title: "t".to_string(),
+            source_path: "/p".to_string(),
+            source_type: "md".to_string(),
+            section: None,
+            location: ChunkLocation::default(),
+            file_mtime: None,
+            snippet: "s".to_string(),
+            text: None,
+            document_match_count: None,
+        };
+        let json = serde_json::to_value(&r).unwrap();
+        assert!(json.get("text").is_none());
+        assert!(json.get("section").is_none());
+        assert!(json.get("start_line").is_none());
+        assert!(json.get("converted").is_none());
+        assert!(json.get("document_match_count").is_none());
     }
 
     #[test]
@@ -118,10 +685,88 @@ mod tests {
             chunk_index: 0,
             text: "content".to_string(),
             updated_at: "2026-01-01T00:00:00Z".to_string(),
+            tags: vec!["billing".to_string()],
+            section: Some("Billing > Refunds".to_string()),
+            location: ChunkLocation {
+                start_char: Some(120),
+                end_char: Some(127),
+                start_line: Some(9),
+                converted: true,
+            },
+            file_mtime: Some("2025-12-31T09:00:00+00:00".to_string()),
+            file_size_bytes: Some(2048),
+            language: Some("en".to_string()),
+            converter: Some("native".to_string()),
+            ocr: true,
+            encoding: Some("Shift_JIS".to_string()),
+            embedding_truncated: true,
         };
         let json = serde_json::to_string(&payload).unwrap();
+        assert!(json.contains("\"start_line\":9"), "{}", json);
         let deserialized: ChunkPayload = serde_json::from_str(&json).unwrap();
         assert_eq!(deserialized.chunk_id, "test-id");
         assert_eq!(deserialized.chunk_index, 0);
+        assert_eq!(deserialized.tags, vec!["billing"]);
+        assert_eq!(deserialized.section.as_deref(), Some("Billing > Refunds"));
+        assert_eq!(deserialized.location, payload.location);
+        assert_eq!(deserialized.file_mtime, payload.file_mtime);
+        assert_eq!(deserialized.file_size_bytes, Some(2048));
+        assert_eq!(deserialized.language.as_deref(), Some("en"));
+        assert_eq!(deserialized.converter.as_deref(), Some("native"));
+        assert!(deserialized.ocr);
+        assert_eq!(deserialized.encoding.as_deref(), Some("Shift_JIS"));
+        assert!(deserialized.embedding_truncated);
+
+        // Exports written before tags, locations, file metadata, languages,
+        // converters, OCR, encodings and truncation flags existed still
+        // load.
+        let mut old = serde_json::to_value(&payload).unwrap();
+        let keys = [
+            "tags",
+            "start_char",
+            "end_char",
+            "start_line",
+            "converted",
+            "file_mtime",
+            "file_size_bytes",
+            "language",
+            "converter",
+            "ocr",
+            "encoding",
+            "embedding_truncated",
+        ];
+        for key in keys {
+            old.as_object_mut().unwrap().remove(key);
+        }
+        let deserialized: ChunkPayload = serde_json::from_value(old).unwrap();
+        assert!(deserialized.tags.is_empty());
+        assert_eq!(deserialized.location, ChunkLocation::default());
+        assert!(deserialized.file_mtime.is_none());
+        assert!(deserialized.file_size_bytes.is_none());
+        assert!(deserialized.language.is_none());
+        assert!(deserialized.converter.is_none());
+        assert!(!deserialized.ocr);
+        assert!(deserialized.encoding.is_none());
+        assert!(!deserialized.embedding_truncated);
+    }
+
+    #[test]
+    fn test_exported_chunk_title_embedding() {
+        // Exports from before title vectors have none.
+        let json = r#"{"payload": {"chunk_id": "c", "source_path": "a.md",
+            "source_type": "md", "title": "A", "chunk_index": 0, "text": "x",
+            "updated_at": "2026-01-01T00:00:00Z"}, "embedding": [0.5]}"#;
+        let chunk: ExportedChunk = serde_json::from_str(json).unwrap();
+        assert_eq!(chunk.title_embedding, None);
+        let json = serde_json::to_value(&chunk).unwrap();
+        assert!(json.get("title_embedding").is_none());
+
+        let chunk = ExportedChunk {
+            title_embedding: Some(vec![0.25]),
+            ..chunk
+        };
+        let read: ExportedChunk =
+            serde_json::from_str(&serde_json::to_string(&chunk).unwrap()).unwrap();
+        assert_eq!(read.title_embedding, Some(vec![0.25]));
     }
 }
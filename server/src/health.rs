@@ -0,0 +1,303 @@
+//! Dependency probes behind the `/health` and `/health/ready` endpoints.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use mcp_hybrid_search_common::config::AppConfig;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use crate::search::{self, qdrant_search, tantivy_search};
+
+/// How long a Qdrant probe may take before the component counts as down.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a probe result is reused, so frequent polling doesn't hammer Qdrant.
+const CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// How often the readiness probe checks the search backends.
+pub const READINESS_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    /// Whether every required component is usable.
+    pub healthy: bool,
+    pub body: Value,
+}
+
+/// Runs [`probe`] at most once per [`CACHE_TTL`]. Concurrent callers wait for
+/// the probe already in progress instead of starting their own.
+#[derive(Default)]
+pub struct HealthChecker {
+    cache: Mutex<Option<(Instant, HealthReport)>>,
+}
+
+impl HealthChecker {
+    pub async fn check(&self, config: &AppConfig) -> HealthReport {
+        let mut cache = self.cache.lock().await;
+        if let Some((checked_at, report)) = cache.as_ref() {
+            if checked_at.elapsed() < CACHE_TTL {
+                return report.clone();
+            }
+        }
+        let report = probe(config).await;
+        *cache = Some((Instant::now(), report.clone()));
+        report
+    }
+}
+
+/// Check Qdrant (collection info), Tantivy (index opens) and the embedding
+/// provider configuration.
+pub async fn probe(config: &AppConfig) -> HealthReport {
+    let qdrant = match tokio::time::timeout(
+        PROBE_TIMEOUT,
+        qdrant_search::get_collection_count(config),
+    )
+    .await
+    {
+        Ok(result) => result.map(|points| {
+            json!({
+                "collection": config.collection_name,
+                "points": points,
+            })
+        }),
+        Err(_) => Err(anyhow::anyhow!(
+            "timed out after {}s",
+            PROBE_TIMEOUT.as_secs()
+        )),
+    };
+    let tantivy = tantivy_search::get_index_count(config).map(|documents| {
+        json!({
+            "index_dir": config.tantivy_index_dir,
+            "documents": documents,
+        })
+    });
+    let embedding = search::check_provider_config(config).map(|()| {
+        json!({
+            "provider": config.embedding_provider,
+            "model": config.embedding_model,
+        })
+    });
+
+    let components = [
+        ("qdrant", qdrant),
+        ("tantivy", tantivy),
+        ("embedding", embedding),
+    ];
+    let healthy = components.iter().all(|(_, result)| result.is_ok());
+    let checks: serde_json::Map<String, Value> = components
+        .into_iter()
+        .map(|(name, result)| (name.to_string(), component_json(result)))
+        .collect();
+
+    HealthReport {
+        healthy,
+        body: json!({
+            "status": if healthy { "ok" } else { "unavailable" },
+            "checks": checks,
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct BackendState {
+    up: bool,
+    consecutive_failures: u32,
+    total_failures: u64,
+    last_error: Option<String>,
+}
+
+/// Readiness of the search backends, fed by a periodic probe. A backend
+/// becomes ready on its first successful probe and drops out again after
+/// `readiness_failure_threshold` consecutive failures, so a single blip
+/// doesn't pull the server out of rotation.
+#[derive(Default)]
+pub struct Readiness {
+    backends: std::sync::Mutex<BTreeMap<&'static str, BackendState>>,
+}
+
+impl Readiness {
+    /// Probe Qdrant (collection info) and Tantivy (index opens) once.
+    pub async fn probe(&self, config: &AppConfig) {
+        let qdrant =
+            tokio::time::timeout(PROBE_TIMEOUT, qdrant_search::get_collection_count(config))
+                .await
+                .unwrap_or_else(|_| {
+                    Err(anyhow::anyhow!(
+                        "timed out after {}s",
+                        PROBE_TIMEOUT.as_secs()
+                    ))
+                });
+        let tantivy = tantivy_search::get_index_count(config);
+
+        let threshold = config.readiness_failure_threshold;
+        self.record("qdrant", qdrant.map(drop), threshold);
+        self.record("tantivy", tantivy.map(drop), threshold);
+    }
+
+    fn record(&self, backend: &'static str, result: anyhow::Result<()>, threshold: u32) {
+        let mut backends = self.backends.lock().unwrap();
+        let state = backends.entry(backend).or_default();
+        match result {
+            Ok(()) => {
+                if !state.up {
+                    tracing::info!("{} is ready", backend);
+                }
+                state.up = true;
+                state.consecutive_failures = 0;
+            }
+            Err(e) => {
+                state.consecutive_failures += 1;
+                state.total_failures += 1;
+                state.last_error = Some(format!("{:#}", e));
+                if state.up && state.consecutive_failures >= threshold.max(1) {
+                    tracing::warn!(
+                        "{} not ready after {} consecutive failures: {:#}",
+                        backend,
+                        state.consecutive_failures,
+                        e
+                    );
+                    state.up = false;
+                }
+            }
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        let backends = self.backends.lock().unwrap();
+        ["qdrant", "tantivy"]
+            .iter()
+            .all(|name| backends.get(name).is_some_and(|state| state.up))
+    }
+
+    /// Response body for `/health/ready`, including the failure counters.
+    pub fn body(&self) -> Value {
+        let ready = self.is_ready();
+        let backends = self.backends.lock().unwrap();
+        let checks: serde_json::Map<String, Value> = ["qdrant", "tantivy"]
+            .into_iter()
+            .map(|name| {
+                let state = backends.get(name).cloned().unwrap_or_default();
+                (name.to_string(), json!(state))
+            })
+            .collect();
+        json!({
+            "status": if ready { "ready" } else { "not_ready" },
+            "checks": checks,
+        })
+    }
+}
+
+fn component_json(result: anyhow::Result<Value>) -> Value {
+    match result {
+        Ok(mut details) => {
+            details["status"] = json!("ok");
+            details
+        }
+        Err(e) => json!({
+            "status": "error",
+            "error": format!("{:#}", e),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unreachable_config() -> AppConfig {
+        AppConfig {
+            qdrant_url: "http://127.0.0.1:1".to_string(),
+            tantivy_index_dir: "/nonexistent/tantivy".to_string(),
+            embedding_provider: "unsupported".to_string(),
+            ..AppConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_reports_each_failure() {
+        let report = probe(&unreachable_config()).await;
+        assert!(!report.healthy);
+        assert_eq!(report.body["status"], "unavailable");
+        for name in ["qdrant", "tantivy", "embedding"] {
+            assert_eq!(report.body["checks"][name]["status"], "error", "{}", name);
+            assert!(report.body["checks"][name]["error"].is_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tantivy_component_ok() {
+        let dir = std::env::temp_dir().join(format!("health-test-{}", uuid::Uuid::new_v4()));
+        let config = AppConfig {
+            tantivy_index_dir: dir.to_string_lossy().to_string(),
+            ..unreachable_config()
+        };
+        // Opening the index for a search creates it when missing.
+        tantivy_search::search(&config, "x", 1, &Default::default(), &Default::default()).unwrap();
+
+        let report = probe(&config).await;
+        let tantivy = &report.body["checks"]["tantivy"];
+        assert_eq!(tantivy["status"], "ok");
+        assert_eq!(tantivy["documents"], 0);
+        assert!(!report.healthy);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_readiness_transitions() {
+        let readiness = Readiness::default();
+        assert!(!readiness.is_ready());
+
+        readiness.record("qdrant", Ok(()), 2);
+        assert!(!readiness.is_ready(), "tantivy has not been probed yet");
+        readiness.record("tantivy", Ok(()), 2);
+        assert!(readiness.is_ready());
+
+        readiness.record("qdrant", Err(anyhow::anyhow!("refused")), 2);
+        assert!(
+            readiness.is_ready(),
+            "one failure stays below the threshold"
+        );
+        readiness.record("qdrant", Err(anyhow::anyhow!("refused")), 2);
+        assert!(!readiness.is_ready());
+
+        let body = readiness.body();
+        assert_eq!(body["status"], "not_ready");
+        assert_eq!(body["checks"]["qdrant"]["consecutive_failures"], 2);
+        assert_eq!(body["checks"]["qdrant"]["total_failures"], 2);
+        assert_eq!(body["checks"]["qdrant"]["last_error"], "refused");
+
+        readiness.record("qdrant", Ok(()), 2);
+        assert!(readiness.is_ready());
+        let body = readiness.body();
+        assert_eq!(body["checks"]["qdrant"]["consecutive_failures"], 0);
+        assert_eq!(body["checks"]["qdrant"]["total_failures"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_probe_unreachable() {
+        let readiness = Readiness::default();
+        readiness.probe(&unreachable_config()).await;
+        assert!(!readiness.is_ready());
+        let body = readiness.body();
+        assert_eq!(body["checks"]["qdrant"]["total_failures"], 1);
+        assert_eq!(body["checks"]["tantivy"]["total_failures"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_checker_caches_result() {
+        let checker = HealthChecker::default();
+        let first = checker.check(&unreachable_config()).await;
+
+        // A different config would change the embedding check, but the
+        // cached report is returned within the TTL.
+        let config = AppConfig {
+            embedding_provider: "openai".to_string(),
+            ..unreachable_config()
+        };
+        let second = checker.check(&config).await;
+        assert_eq!(first.body, second.body);
+    }
+}
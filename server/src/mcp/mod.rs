@@ -1,3 +1,5 @@
+pub mod prompts;
 pub mod protocol;
 pub mod server;
+pub mod session;
 pub mod tools;
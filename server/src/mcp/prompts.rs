@@ -0,0 +1,149 @@
+use mcp_hybrid_search_common::types::SearchResult;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Prompt {
+    pub name: String,
+    pub description: String,
+    pub arguments: Vec<PromptArgument>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptArgument {
+    pub name: String,
+    pub description: String,
+    pub required: bool,
+}
+
+pub enum PromptName {
+    AnswerFromDocs,
+}
+
+impl PromptName {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "answer_from_docs" => Some(Self::AnswerFromDocs),
+            _ => None,
+        }
+    }
+}
+
+/// Arguments for `answer_from_docs`. MCP prompt arguments are always strings.
+#[derive(Debug, Deserialize)]
+pub struct AnswerFromDocsArgs {
+    pub question: Option<String>,
+    pub top_k: Option<String>,
+}
+
+pub fn list_prompts() -> Vec<Prompt> {
+    vec![Prompt {
+        name: "answer_from_docs".to_string(),
+        description: "Answer a question grounded in the indexed documents. Runs a hybrid search and returns a prompt containing the top matching excerpts with instructions to cite sources.".to_string(),
+        arguments: vec![
+            PromptArgument {
+                name: "question".to_string(),
+                description: "The question to answer".to_string(),
+                required: true,
+            },
+            PromptArgument {
+                name: "top_k".to_string(),
+                description: "Number of excerpts to include (default: 5)".to_string(),
+                required: false,
+            },
+        ],
+    }]
+}
+
+/// Build the `prompts/get` result for `answer_from_docs` from search results.
+pub fn answer_from_docs_result(question: &str, results: &[SearchResult]) -> Value {
+    let mut text = String::new();
+    text.push_str(
+        "Answer the question below using only the document excerpts provided. \
+         Cite the source path of every excerpt you rely on, e.g. [source: /path/to/file.md]. \
+         If the excerpts do not contain the answer, say so instead of guessing.\n\n",
+    );
+    text.push_str(&format!("Question: {}\n\n", question));
+
+    if results.is_empty() {
+        text.push_str("No matching excerpts were found in the index.\n");
+    } else {
+        text.push_str("Excerpts:\n");
+        for (i, r) in results.iter().enumerate() {
+            let body = r.text.as_deref().unwrap_or(&r.snippet);
+            let title = match &r.section {
+                Some(section) => format!("{} > {}", r.title, section),
+                None => r.title.clone(),
+            };
+            text.push_str(&format!(
+                "\n[{}] {} (source: {})\n{}\n",
+                i + 1,
+                title,
+                r.source_path,
+                body
+            ));
+        }
+    }
+
+    json!({
+        "description": format!("Grounded answer for: {}", question),
+        "messages": [
+            {
+                "role": "user",
+                "content": {
+                    "type": "text",
+                    "text": text
+                }
+            }
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_result(id: &str, path: &str) -> SearchResult {
+        SearchResult {
+            chunk_id: id.to_string(),
+            score: 0.0,
+            title: format!("Title {}", id),
+            source_path: path.to_string(),
+            source_type: "md".to_string(),
+            section: None,
+            location: Default::default(),
+            file_mtime: None,
+            snippet: "snippet".to_string(),
+            text: Some(format!("full text {}", id)),
+            document_match_count: None,
+        }
+    }
+
+    #[test]
+    fn test_every_listed_prompt_parses() {
+        for prompt in list_prompts() {
+            assert!(PromptName::parse(&prompt.name).is_some(), "{}", prompt.name);
+        }
+    }
+
+    #[test]
+    fn test_answer_from_docs_embeds_sources() {
+        let results = vec![
+            make_result("a", "/docs/a.md"),
+            make_result("b", "/docs/b.md"),
+        ];
+        let value = answer_from_docs_result("What is X?", &results);
+        let text = value["messages"][0]["content"]["text"].as_str().unwrap();
+        assert!(text.contains("Question: What is X?"));
+        assert!(text.contains("/docs/a.md"));
+        assert!(text.contains("full text b"));
+        assert_eq!(value["messages"][0]["role"], "user");
+    }
+
+    #[test]
+    fn test_answer_from_docs_without_results() {
+        let value = answer_from_docs_result("What is X?", &[]);
+        let text = value["messages"][0]["content"]["text"].as_str().unwrap();
+        assert!(text.contains("No matching excerpts"));
+    }
+}
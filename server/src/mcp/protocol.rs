@@ -3,6 +3,8 @@ use serde_json::Value;
 
 #[derive(Debug, Deserialize)]
 pub struct JsonRpcRequest {
+    // Required on the wire but not otherwise inspected.
+    #[allow(dead_code)]
     pub jsonrpc: String,
     pub id: Option<Value>,
     pub method: String,
@@ -28,14 +30,32 @@ pub struct JsonRpcError {
     pub data: Option<Value>,
 }
 
+/// A JSON-RPC notification (a message without an id that expects no response).
+#[derive(Debug, Serialize)]
+pub struct JsonRpcNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: Value,
+}
+
+impl JsonRpcNotification {
+    pub fn new(method: impl Into<String>, params: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            method: method.into(),
+            params,
+        }
+    }
+}
+
+/// MCP protocol versions this server speaks, newest first.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-03-26", "2024-11-05"];
+
 // JSON-RPC error codes
-#[allow(dead_code)]
 pub const PARSE_ERROR: i32 = -32700;
-#[allow(dead_code)]
 pub const INVALID_REQUEST: i32 = -32600;
 pub const METHOD_NOT_FOUND: i32 = -32601;
 pub const INVALID_PARAMS: i32 = -32602;
-#[allow(dead_code)]
 pub const INTERNAL_ERROR: i32 = -32603;
 
 impl JsonRpcResponse {
@@ -62,11 +82,97 @@ impl JsonRpcResponse {
     }
 }
 
+/// A single page of a list result.
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+/// Paginate `items` for `*/list` methods.
+///
+/// The cursor is an opaque string encoding the offset of the next page. With no
+/// cursor the first page is returned; a cursor equal to the item count yields
+/// an empty final page. Anything else is rejected with an error message suitable
+/// for an INVALID_PARAMS response.
+pub fn paginate<T: Clone>(
+    items: &[T],
+    cursor: Option<&str>,
+    page_size: usize,
+) -> Result<Page<T>, String> {
+    let start = match cursor {
+        None => 0,
+        Some(c) => match c.parse::<usize>() {
+            Ok(offset) if offset <= items.len() => offset,
+            _ => return Err(format!("Invalid cursor: {}", c)),
+        },
+    };
+
+    let end = start.saturating_add(page_size).min(items.len());
+    let next_cursor = (end < items.len()).then(|| end.to_string());
+
+    Ok(Page {
+        items: items[start..end].to_vec(),
+        next_cursor,
+    })
+}
+
+/// Extract the optional `cursor` param of a `*/list` request.
+pub fn cursor_param(params: &Option<Value>) -> Result<Option<&str>, String> {
+    match params.as_ref().and_then(|p| p.get("cursor")) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(c)) => Ok(Some(c.as_str())),
+        Some(other) => Err(format!("Invalid cursor: {}", other)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
 
+    #[test]
+    fn test_paginate_first_page_returns_all() {
+        let page = paginate(&[1, 2, 3], None, usize::MAX).unwrap();
+        assert_eq!(page.items, vec![1, 2, 3]);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_paginate_with_page_size() {
+        let page = paginate(&[1, 2, 3], None, 2).unwrap();
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor.as_deref(), Some("2"));
+        let page = paginate(&[1, 2, 3], Some("2"), 2).unwrap();
+        assert_eq!(page.items, vec![3]);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_paginate_trailing_cursor_is_empty() {
+        let page = paginate(&[1, 2, 3], Some("3"), usize::MAX).unwrap();
+        assert!(page.items.is_empty());
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_paginate_invalid_cursor() {
+        assert!(paginate(&[1, 2, 3], Some("4"), usize::MAX).is_err());
+        assert!(paginate(&[1, 2, 3], Some("abc"), usize::MAX).is_err());
+        assert!(paginate(&[1, 2, 3], Some("-1"), usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_cursor_param() {
+        assert_eq!(cursor_param(&None).unwrap(), None);
+        assert_eq!(cursor_param(&Some(json!({}))).unwrap(), None);
+        assert_eq!(
+            cursor_param(&Some(json!({"cursor": "3"}))).unwrap(),
+            Some("3")
+        );
+        assert!(cursor_param(&Some(json!({"cursor": 3}))).is_err());
+    }
+
     #[test]
     fn test_success_response_serialization() {
         let resp = JsonRpcResponse::success(Some(json!(1)), json!({"status": "ok"}));
@@ -1,31 +1,157 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
 use mcp_hybrid_search_common::config::AppConfig;
-use mcp_hybrid_search_common::types::SearchFilters;
+use mcp_hybrid_search_common::types::{
+    SearchFilters, SearchOptions, DEFAULT_MAX_CHUNKS_PER_DOCUMENT, DEFAULT_SNIPPET_LENGTH,
+};
 use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
+use super::prompts::*;
 use super::protocol::*;
+use super::session::{LogLevel, Progress, Session};
 use super::tools::*;
 use crate::search;
 
+/// In-flight tool calls keyed by (session id, JSON-encoded request id).
+type InFlightCalls = Mutex<HashMap<(String, String), CancellationToken>>;
+
 pub struct McpServer {
-    config: AppConfig,
+    /// Swapped whole on reload; requests clone the `Arc` and never hold the
+    /// lock across an `.await`.
+    config: RwLock<Arc<AppConfig>>,
     searcher: search::HybridSearcher,
+    in_flight: InFlightCalls,
+}
+
+/// Removes a tool call from [`McpServer::in_flight`] when it finishes, times
+/// out or is cancelled.
+struct InFlightGuard<'a> {
+    calls: &'a InFlightCalls,
+    key: (String, String),
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.calls.lock().unwrap().remove(&self.key);
+    }
 }
 
 impl McpServer {
     pub async fn new(config: AppConfig) -> anyhow::Result<Self> {
         let searcher = search::HybridSearcher::new(&config)?;
-        Ok(Self { config, searcher })
+        Ok(Self {
+            config: RwLock::new(Arc::new(config)),
+            searcher,
+            in_flight: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn config(&self) -> Arc<AppConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Replace the config used by subsequent requests (see [`crate::reload`]).
+    /// Requests already running keep the config they started with.
+    pub fn set_config(&self, config: AppConfig) {
+        *self.config.write().unwrap() = Arc::new(config);
+    }
+
+    /// Handle a raw JSON-RPC message: a single request or a batch (array) of
+    /// them. Batch responses keep request order and omit notifications.
+    /// Returns `None` when there is nothing to send back.
+    pub async fn handle_message(&self, message: Value, session: &Session) -> Option<Value> {
+        match message {
+            Value::Array(items) if items.is_empty() => Some(json!(JsonRpcResponse::error(
+                Some(Value::Null),
+                INVALID_REQUEST,
+                "Invalid request: empty batch",
+            ))),
+            Value::Array(items) => {
+                let responses: Vec<JsonRpcResponse> = futures::future::join_all(
+                    items
+                        .into_iter()
+                        .map(|item| self.handle_message_item(item, session)),
+                )
+                .await
+                .into_iter()
+                .flatten()
+                .collect();
+                (!responses.is_empty()).then(|| json!(responses))
+            }
+            single => self
+                .handle_message_item(single, session)
+                .await
+                .map(|response| json!(response)),
+        }
+    }
+
+    async fn handle_message_item(&self, item: Value, session: &Session) -> Option<JsonRpcResponse> {
+        // Echo the id of a malformed request when it has a usable one.
+        let id = item
+            .get("id")
+            .filter(|id| id.is_string() || id.is_number())
+            .cloned()
+            .unwrap_or(Value::Null);
+        match serde_json::from_value::<JsonRpcRequest>(item) {
+            Ok(request) => self.handle_request(request, session).await,
+            Err(e) => Some(JsonRpcResponse::error(
+                Some(id),
+                INVALID_REQUEST,
+                format!("Invalid request: {}", e),
+            )),
+        }
+    }
+
+    /// Handle one JSON-RPC request. Returns `None` for notifications (messages
+    /// without an id), which must not be answered.
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(session_id = %session.id, request_id, method = %request.method)
+    )]
+    pub async fn handle_request(
+        &self,
+        request: JsonRpcRequest,
+        session: &Session,
+    ) -> Option<JsonRpcResponse> {
+        if let Some(id) = &request.id {
+            tracing::Span::current().record("request_id", tracing::field::display(id));
+        }
+        session.touch();
+        let is_notification = request.id.is_none();
+        let response = self.dispatch(request, session).await?;
+        (!is_notification).then_some(response)
     }
 
-    pub async fn handle_request(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+    /// Returns `None` only for requests that were cancelled by the client.
+    async fn dispatch(
+        &self,
+        request: JsonRpcRequest,
+        session: &Session,
+    ) -> Option<JsonRpcResponse> {
         tracing::debug!("Handling method: {}", request.method);
 
-        match request.method.as_str() {
-            "initialize" => self.handle_initialize(request.id),
+        let response = match request.method.as_str() {
+            "initialize" => self.handle_initialize(request.id, &request.params),
             "initialized" => JsonRpcResponse::success(request.id, json!({})),
             "notifications/initialized" => JsonRpcResponse::success(request.id, json!({})),
-            "tools/list" => self.handle_tools_list(request.id),
-            "tools/call" => self.handle_tools_call(request.id, request.params).await,
+            "tools/list" => self.handle_tools_list(request.id, &request.params),
+            "tools/call" => {
+                return self
+                    .handle_tools_call(request.id, request.params, session)
+                    .await
+            }
+            "notifications/cancelled" => {
+                self.handle_cancelled(&request.params, session);
+                JsonRpcResponse::success(request.id, json!({}))
+            }
+            "prompts/list" => self.handle_prompts_list(request.id, &request.params),
+            "prompts/get" => self.handle_prompts_get(request.id, request.params).await,
+            "logging/setLevel" => self.handle_set_log_level(request.id, request.params, session),
             "ping" => JsonRpcResponse::success(request.id, json!({})),
             _ => {
                 tracing::warn!("Unknown method: {}", request.method);
@@ -35,14 +161,54 @@ impl McpServer {
                     format!("Method not found: {}", request.method),
                 )
             }
+        };
+        Some(response)
+    }
+
+    /// Cancel the in-flight tool call named by `params.requestId`, if any.
+    fn handle_cancelled(&self, params: &Option<Value>, session: &Session) {
+        let Some(request_id) = params.as_ref().and_then(|p| p.get("requestId")) else {
+            return;
+        };
+        let key = (session.id.clone(), request_id.to_string());
+        if let Some(token) = self.in_flight.lock().unwrap().get(&key) {
+            tracing::info!(
+                "Cancelling request {} for session {}",
+                request_id,
+                session.id
+            );
+            token.cancel();
+        }
+    }
+
+    /// Register a tool call so `notifications/cancelled` can reach it.
+    fn track_call(
+        &self,
+        session: &Session,
+        id: &Value,
+        token: CancellationToken,
+    ) -> InFlightGuard<'_> {
+        let key = (session.id.clone(), id.to_string());
+        self.in_flight.lock().unwrap().insert(key.clone(), token);
+        InFlightGuard {
+            calls: &self.in_flight,
+            key,
         }
     }
 
-    fn handle_initialize(&self, id: Option<Value>) -> JsonRpcResponse {
+    fn handle_initialize(&self, id: Option<Value>, params: &Option<Value>) -> JsonRpcResponse {
+        // Echo the client's version when we support it, otherwise offer our latest.
+        let protocol_version = params
+            .as_ref()
+            .and_then(|p| p.get("protocolVersion"))
+            .and_then(|v| v.as_str())
+            .filter(|v| SUPPORTED_PROTOCOL_VERSIONS.contains(v))
+            .unwrap_or(SUPPORTED_PROTOCOL_VERSIONS[0]);
+
         JsonRpcResponse::success(
             id,
             json!({
-                "protocolVersion": "2024-11-05",
+                "protocolVersion": protocol_version,
                 "serverInfo": {
                     "name": "mcp-server-hybrid-search",
                     "version": env!("CARGO_PKG_VERSION")
@@ -50,18 +216,54 @@ impl McpServer {
                 "capabilities": {
                     "tools": {
                         "listChanged": false
-                    }
+                    },
+                    "prompts": {
+                        "listChanged": false
+                    },
+                    "logging": {}
                 }
             }),
         )
     }
 
-    fn handle_tools_list(&self, id: Option<Value>) -> JsonRpcResponse {
-        let tools = list_tools();
-        JsonRpcResponse::success(id, json!({ "tools": tools }))
+    fn handle_set_log_level(
+        &self,
+        id: Option<Value>,
+        params: Option<Value>,
+        session: &Session,
+    ) -> JsonRpcResponse {
+        let level = params
+            .as_ref()
+            .and_then(|p| p.get("level"))
+            .and_then(|v| v.as_str());
+
+        match level.and_then(LogLevel::parse) {
+            Some(level) => {
+                session.set_log_level(level);
+                tracing::debug!("Session {} log level set to {}", session.id, level.as_str());
+                JsonRpcResponse::success(id, json!({}))
+            }
+            None => JsonRpcResponse::error(
+                id,
+                INVALID_PARAMS,
+                format!("Invalid log level: {}", level.unwrap_or("(missing)")),
+            ),
+        }
     }
 
-    async fn handle_tools_call(&self, id: Option<Value>, params: Option<Value>) -> JsonRpcResponse {
+    fn handle_tools_list(&self, id: Option<Value>, params: &Option<Value>) -> JsonRpcResponse {
+        list_response(id, params, "tools", &list_tools())
+    }
+
+    fn handle_prompts_list(&self, id: Option<Value>, params: &Option<Value>) -> JsonRpcResponse {
+        list_response(id, params, "prompts", &list_prompts())
+    }
+
+    async fn handle_prompts_get(
+        &self,
+        id: Option<Value>,
+        params: Option<Value>,
+    ) -> JsonRpcResponse {
         let params = match params {
             Some(p) => p,
             None => {
@@ -69,87 +271,947 @@ impl McpServer {
             }
         };
 
+        let prompt_name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
+        let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+
+        match PromptName::parse(prompt_name) {
+            Some(PromptName::AnswerFromDocs) => {
+                let args: AnswerFromDocsArgs = match serde_json::from_value(arguments) {
+                    Ok(a) => a,
+                    Err(e) => {
+                        return JsonRpcResponse::error(
+                            id,
+                            INVALID_PARAMS,
+                            format!("Invalid arguments: {}", e),
+                        );
+                    }
+                };
+                let question = match args.question.as_deref().map(str::trim) {
+                    Some(q) if !q.is_empty() => q.to_string(),
+                    _ => {
+                        return JsonRpcResponse::error(
+                            id,
+                            INVALID_PARAMS,
+                            "Missing required argument: question",
+                        );
+                    }
+                };
+                let top_k = match args.top_k.as_deref().map(str::parse::<usize>) {
+                    None => 5,
+                    Some(Ok(k)) if k > 0 => k,
+                    Some(_) => {
+                        return JsonRpcResponse::error(
+                            id,
+                            INVALID_PARAMS,
+                            "top_k must be a positive integer",
+                        );
+                    }
+                };
+
+                let options = SearchOptions {
+                    include_text: true,
+                    ..SearchOptions::default()
+                };
+                match self
+                    .searcher
+                    .search(
+                        &self.config(),
+                        &question,
+                        top_k,
+                        &SearchFilters::default(),
+                        &options,
+                    )
+                    .await
+                {
+                    Ok(results) => {
+                        JsonRpcResponse::success(id, answer_from_docs_result(&question, &results))
+                    }
+                    Err(e) => {
+                        JsonRpcResponse::error(id, INTERNAL_ERROR, format!("Search failed: {}", e))
+                    }
+                }
+            }
+            None => JsonRpcResponse::error(
+                id,
+                INVALID_PARAMS,
+                format!("Unknown prompt: {}", prompt_name),
+            ),
+        }
+    }
+
+    async fn handle_tools_call(
+        &self,
+        id: Option<Value>,
+        params: Option<Value>,
+        session: &Session,
+    ) -> Option<JsonRpcResponse> {
+        let params = match params {
+            Some(p) => p,
+            None => {
+                return Some(JsonRpcResponse::error(id, INVALID_PARAMS, "Missing params"));
+            }
+        };
+
         let tool_name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
 
         let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+        let progress = Progress::from_params(session, &params);
 
-        let result = match ToolName::parse(tool_name) {
-            Some(ToolName::Search) => self.execute_search(arguments).await,
-            Some(ToolName::Get) => self.execute_get(arguments).await,
-            Some(ToolName::GetProjectInfo) => self.execute_get_project_info().await,
-            None => {
-                return JsonRpcResponse::error(
-                    id,
-                    METHOD_NOT_FOUND,
-                    format!("Unknown tool: {}", tool_name),
-                );
+        let Some(tool) = ToolName::parse(tool_name) else {
+            return Some(JsonRpcResponse::error(
+                id,
+                METHOD_NOT_FOUND,
+                format!("Unknown tool: {}", tool_name),
+            ));
+        };
+        let timeout_secs = match timeout_arg(&arguments) {
+            Ok(secs) => secs.unwrap_or(self.config().tool_timeout_secs),
+            Err(e) => return Some(JsonRpcResponse::error(id, INVALID_PARAMS, e.to_string())),
+        };
+
+        let cancel = CancellationToken::new();
+        let _guard = id
+            .as_ref()
+            .map(|id| self.track_call(session, id, cancel.clone()));
+
+        let span = tracing::info_span!(
+            "tool_call",
+            tool = tool_name,
+            duration_ms = tracing::field::Empty
+        );
+        let started = Instant::now();
+        let execution = async {
+            let execution = self.execute_tool(tool, arguments, session, &progress);
+            if timeout_secs == 0 {
+                return execution.await;
             }
+            // Timing out drops the execution future, which also cancels any
+            // embedding or Qdrant request still in flight.
+            tokio::time::timeout(Duration::from_secs(timeout_secs), execution)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(anyhow::anyhow!(
+                        "{} timed out after {}s (embedding provider or Qdrant unreachable?)",
+                        tool_name,
+                        timeout_secs
+                    ))
+                })
         };
+        // Cancellation drops the execution the same way; per the spec a
+        // cancelled request gets no response at all.
+        let result = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => {
+                tracing::info!(parent: &span, "Tool call cancelled");
+                return None;
+            }
+            result = execution.instrument(span.clone()) => result,
+        };
+        let duration_ms = started.elapsed().as_millis() as u64;
+        span.record("duration_ms", duration_ms);
+        tracing::info!(parent: &span, duration_ms, ok = result.is_ok(), "Tool call finished");
 
-        match result {
+        let response = match result {
             Ok(tool_result) => JsonRpcResponse::success(id, json!(tool_result)),
+            Err(e) if e.is::<InvalidParams>() => {
+                JsonRpcResponse::error(id, INVALID_PARAMS, e.to_string())
+            }
             Err(e) => {
+                session
+                    .log(
+                        LogLevel::Error,
+                        "tools/call",
+                        json!({ "tool": tool_name, "error": e.to_string() }),
+                    )
+                    .await;
                 let error_result = ToolResult::error(format!("Error: {}", e));
                 JsonRpcResponse::success(id, json!(error_result))
             }
+        };
+        Some(response)
+    }
+
+    async fn execute_tool(
+        &self,
+        tool: ToolName,
+        arguments: Value,
+        session: &Session,
+        progress: &Progress<'_>,
+    ) -> anyhow::Result<ToolResult> {
+        match tool {
+            ToolName::Search => self.execute_search(arguments, progress).await,
+            ToolName::Get => self.execute_get(arguments).await,
+            ToolName::GetProjectInfo => self.execute_get_project_info(arguments).await,
+            ToolName::ListProjects => self.execute_list_projects(progress).await,
+            ToolName::IndexStatus => self.execute_index_status(session).await,
+            ToolName::Count => self.execute_count(arguments, session).await,
+            ToolName::BatchSearch => self.execute_batch_search(arguments).await,
+        }
+    }
+
+    /// Resolve the config for a call, applying an optional per-request project
+    /// override. The project must be a safe name and an existing collection.
+    async fn config_for(&self, project: Option<&str>) -> anyhow::Result<Arc<AppConfig>> {
+        let Some(project) = project else {
+            return Ok(self.config());
+        };
+
+        if !AppConfig::is_valid_project_name(project) {
+            return Err(InvalidParams(format!(
+                "Invalid project name '{}': use only letters, digits, '-', '_' and '.'",
+                project
+            ))
+            .into());
+        }
+
+        let config = (*self.config()).clone().with_project(Some(project));
+        if !search::qdrant_search::collection_exists(&config).await? {
+            anyhow::bail!(
+                "Project '{}' not found. Use list_projects to see available projects.",
+                project
+            );
         }
+        Ok(Arc::new(config))
     }
 
-    async fn execute_search(&self, arguments: Value) -> anyhow::Result<ToolResult> {
+    async fn execute_search(
+        &self,
+        arguments: Value,
+        progress: &Progress<'_>,
+    ) -> anyhow::Result<ToolResult> {
         let args: SearchArgs = serde_json::from_value(arguments)?;
+        let config = self.config_for(args.project.as_deref()).await?;
         let top_k = args.top_k.unwrap_or(10);
 
-        let filters = SearchFilters {
-            source_type: args.filters.as_ref().and_then(|f| f.source_type.clone()),
-            path_prefix: args.filters.as_ref().and_then(|f| f.path_prefix.clone()),
+        let filters = FilterArgs::to_filters(args.filters.as_ref());
+
+        let options = SearchOptions {
+            include_text: args.include_text.unwrap_or(false),
+            snippet_length: args.snippet_length.unwrap_or(DEFAULT_SNIPPET_LENGTH),
+            group_by_document: args.group_by_document.unwrap_or(false),
+            max_chunks_per_document: args
+                .max_chunks_per_document
+                .unwrap_or(DEFAULT_MAX_CHUNKS_PER_DOCUMENT),
+            highlight: args.highlight.unwrap_or(true),
+            search_titles: args.search_titles.unwrap_or(false),
+            search_params: args.search_params.unwrap_or_default().checked(),
         };
 
-        let results = self
+        let (results, timings) = self
             .searcher
-            .search(&self.config, &args.query, top_k, &filters)
+            .search_with_progress(
+                &config,
+                &args.query,
+                top_k,
+                &filters,
+                &options,
+                |done, total| progress.report(done, Some(total)),
+            )
             .await?;
 
-        let output = json!({
+        let mut output = json!({
             "results": results
         });
+        if args.debug.unwrap_or(false) {
+            output["timings"] = json!(timings);
+        }
 
         Ok(ToolResult::text(serde_json::to_string_pretty(&output)?))
     }
 
-    async fn execute_get_project_info(&self) -> anyhow::Result<ToolResult> {
-        let doc_count = search::qdrant_search::get_collection_count(&self.config)
+    async fn execute_batch_search(&self, arguments: Value) -> anyhow::Result<ToolResult> {
+        let args: BatchSearchArgs = serde_json::from_value(arguments)?;
+        if args.queries.is_empty() {
+            return Err(InvalidParams("queries must not be empty".to_string()).into());
+        }
+        if args.queries.len() > MAX_BATCH_QUERIES {
+            return Err(InvalidParams(format!(
+                "Too many queries: {} (max {})",
+                args.queries.len(),
+                MAX_BATCH_QUERIES
+            ))
+            .into());
+        }
+        let config = self.config_for(args.project.as_deref()).await?;
+
+        let queries: Vec<search::BatchQuery> = args
+            .queries
+            .iter()
+            .map(|q| search::BatchQuery {
+                query: q.query.clone(),
+                top_k: q.top_k.unwrap_or(10),
+                filters: FilterArgs::to_filters(q.filters.as_ref()),
+            })
+            .collect();
+
+        let options = SearchOptions {
+            include_text: args.include_text.unwrap_or(false),
+            snippet_length: args.snippet_length.unwrap_or(DEFAULT_SNIPPET_LENGTH),
+            highlight: args.highlight.unwrap_or(true),
+            ..SearchOptions::default()
+        };
+
+        let results = self
+            .searcher
+            .batch_search(&config, &queries, &options)
+            .await?;
+
+        let output: Vec<Value> = queries
+            .iter()
+            .zip(results)
+            .map(|(q, results)| json!({ "query": q.query, "results": results }))
+            .collect();
+
+        Ok(ToolResult::text(serde_json::to_string_pretty(
+            &json!({ "results": output }),
+        )?))
+    }
+
+    async fn execute_get_project_info(&self, arguments: Value) -> anyhow::Result<ToolResult> {
+        let args: ProjectInfoArgs = serde_json::from_value(arguments)?;
+        let config = self.config_for(args.project.as_deref()).await?;
+
+        let doc_count = search::qdrant_search::get_collection_count(&config)
             .await
             .unwrap_or(0);
+        let latest_file_mtime = search::qdrant_search::latest_file_mtime(&config)
+            .await
+            .unwrap_or(None);
+
+        let execution_provider = match config.embedding_provider.as_str() {
+            "local" => Some(search::local_execution_provider(&config)?.name()),
+            _ => None,
+        };
 
         let output = json!({
-            "collection_name": self.config.collection_name,
+            "collection_name": config.collection_name,
             "document_count": doc_count,
-            "tantivy_index_dir": self.config.tantivy_index_dir,
-            "embedding_provider": self.config.embedding_provider,
-            "embedding_model": self.config.embedding_model,
-            "embedding_dimension": self.config.embedding_dimension,
+            "latest_file_mtime": latest_file_mtime,
+            "tantivy_index_dir": config.tantivy_index_dir,
+            "embedding_provider": config.embedding_provider,
+            "embedding_model": config.embedding_model,
+            "embedding_dimension": config.embedding_dimension,
+            "local_execution_provider": execution_provider,
         });
 
         Ok(ToolResult::text(serde_json::to_string_pretty(&output)?))
     }
 
+    async fn execute_list_projects(&self, progress: &Progress<'_>) -> anyhow::Result<ToolResult> {
+        let config = self.config();
+        let collections = search::qdrant_search::list_collections(&config, |done, total| {
+            progress.report(done, Some(total))
+        })
+        .await?;
+
+        let projects: Vec<Value> = collections
+            .into_iter()
+            .map(|(name, count)| {
+                json!({
+                    "active": name == config.collection_name,
+                    "name": name,
+                    "point_count": count,
+                })
+            })
+            .collect();
+
+        let output = json!({
+            "active_collection": config.collection_name,
+            "projects": projects,
+        });
+
+        Ok(ToolResult::text(serde_json::to_string_pretty(&output)?))
+    }
+
+    async fn execute_count(
+        &self,
+        arguments: Value,
+        session: &Session,
+    ) -> anyhow::Result<ToolResult> {
+        let args: CountArgs = serde_json::from_value(arguments)?;
+        let config = self.config_for(args.project.as_deref()).await?;
+
+        let filters = FilterArgs::to_filters(args.filters.as_ref());
+
+        let tantivy = search::tantivy_search::count(&config, args.query.as_deref(), &filters);
+        let qdrant = search::qdrant_search::count_points(&config, &filters).await;
+        log_backend_failure(session, "count", "tantivy", &tantivy).await;
+        log_backend_failure(session, "count", "qdrant", &qdrant).await;
+
+        let output = json!({
+            "query": args.query,
+            "filters": filters,
+            "tantivy": backend_count_json(&tantivy),
+            "qdrant": backend_count_json(&qdrant),
+        });
+
+        Ok(ToolResult::text(serde_json::to_string_pretty(&output)?))
+    }
+
+    async fn execute_index_status(&self, session: &Session) -> anyhow::Result<ToolResult> {
+        let config = self.config();
+        let qdrant = search::qdrant_search::get_collection_count(&config).await;
+        let tantivy = search::tantivy_search::get_index_count(&config);
+        log_backend_failure(session, "index_status", "qdrant", &qdrant).await;
+        log_backend_failure(session, "index_status", "tantivy", &tantivy).await;
+        let last_ingest = last_ingest_time(&config);
+
+        let output = index_status_json(&config, qdrant, tantivy, last_ingest);
+        Ok(ToolResult::text(serde_json::to_string_pretty(&output)?))
+    }
+
     async fn execute_get(&self, arguments: Value) -> anyhow::Result<ToolResult> {
         let args: GetArgs = serde_json::from_value(arguments)?;
+        let address = args.address()?;
+        let config = self.config_for(args.project.as_deref()).await?;
 
-        let chunk = self
-            .searcher
-            .get_chunk(&self.config, &args.chunk_id)
-            .await?;
+        let chunk = match &address {
+            ChunkAddress::Id(chunk_id) => self.searcher.get_chunk(&config, chunk_id).await?,
+            ChunkAddress::Position {
+                source_path,
+                chunk_index,
+            } => {
+                self.searcher
+                    .get_chunk_at(&config, source_path, *chunk_index)
+                    .await?
+            }
+        };
 
         match chunk {
-            Some(detail) => {
+            Some(mut detail) => {
+                let before = args.before.unwrap_or(0);
+                let after = args.after.unwrap_or(0);
+                if before > 0 || after > 0 {
+                    detail.neighbors = self
+                        .searcher
+                        .get_neighbors(&config, &detail, before, after)
+                        .await?;
+                }
                 let output = json!(detail);
                 Ok(ToolResult::text(serde_json::to_string_pretty(&output)?))
             }
-            None => Ok(ToolResult::error(format!(
-                "Chunk not found: {}",
-                args.chunk_id
-            ))),
+            None => Ok(ToolResult::error(match address {
+                ChunkAddress::Id(chunk_id) => format!("Chunk not found: {}", chunk_id),
+                ChunkAddress::Position {
+                    source_path,
+                    chunk_index,
+                } => format!("Chunk not found: {} #{}", source_path, chunk_index),
+            })),
+        }
+    }
+}
+
+/// Build a paginated `*/list` response with `items` under `key`.
+/// All items fit on the first page; the cursor handling exists so strict
+/// clients that always paginate get a well-formed answer.
+fn list_response<T: Clone + serde::Serialize>(
+    id: Option<Value>,
+    params: &Option<Value>,
+    key: &str,
+    items: &[T],
+) -> JsonRpcResponse {
+    let page = match cursor_param(params).and_then(|c| paginate(items, c, usize::MAX)) {
+        Ok(page) => page,
+        Err(msg) => return JsonRpcResponse::error(id, INVALID_PARAMS, msg),
+    };
+
+    let mut result = json!({ key: page.items });
+    if let Some(next) = page.next_cursor {
+        result["nextCursor"] = json!(next);
+    }
+    JsonRpcResponse::success(id, result)
+}
+
+/// Last ingest time, taken from the ingest state file's modification time.
+fn last_ingest_time(config: &AppConfig) -> Option<String> {
+    std::fs::metadata(config.ingest_state_path())
+        .ok()
+        .and_then(|m| m.modified().ok())
+        .map(|t| {
+            let datetime: chrono::DateTime<chrono::Utc> = t.into();
+            datetime.to_rfc3339()
+        })
+}
+
+/// Surface a degraded backend to the client as a warning log notification.
+async fn log_backend_failure(
+    session: &Session,
+    tool: &str,
+    backend: &str,
+    result: &anyhow::Result<u64>,
+) {
+    if let Err(e) = result {
+        session
+            .log(
+                LogLevel::Warning,
+                tool,
+                json!({ "backend": backend, "error": e.to_string() }),
+            )
+            .await;
+    }
+}
+
+/// Per-backend `{ status, count }` object; errors become a status string.
+fn backend_count_json(result: &anyhow::Result<u64>) -> Value {
+    match result {
+        Ok(count) => json!({ "status": "ok", "count": count }),
+        Err(e) => json!({ "status": format!("error - {}", e), "count": null }),
+    }
+}
+
+/// Build the index_status payload. Backend failures are reported as status
+/// strings rather than errors so the tool still answers when Qdrant is down.
+fn index_status_json(
+    config: &AppConfig,
+    qdrant: anyhow::Result<u64>,
+    tantivy: anyhow::Result<u64>,
+    last_ingest: Option<String>,
+) -> Value {
+    let counts_diverge = match (&qdrant, &tantivy) {
+        (Ok(q), Ok(t)) => Some(q != t),
+        _ => None,
+    };
+    let empty = matches!((&qdrant, &tantivy), (Ok(0), Ok(0)));
+
+    json!({
+        "collection_name": config.collection_name,
+        "qdrant": backend_count_json(&qdrant),
+        "tantivy": backend_count_json(&tantivy),
+        "counts_diverge": counts_diverge,
+        "empty": empty,
+        "last_ingest": last_ingest,
+        "source_dir": AppConfig::default_source_dir().to_string_lossy(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, params: Value) -> JsonRpcRequest {
+        JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(1)),
+            method: method.to_string(),
+            params: Some(params),
         }
     }
+
+    async fn test_server() -> McpServer {
+        McpServer::new(AppConfig::default()).await.unwrap()
+    }
+
+    fn test_session() -> (Session, tokio::sync::mpsc::Receiver<String>) {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        (Session::new("test", tx), rx)
+    }
+
+    async fn call(server: &McpServer, req: JsonRpcRequest) -> JsonRpcResponse {
+        let (session, _rx) = test_session();
+        server.handle_request(req, &session).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_initialize_declares_logging() {
+        let server = test_server().await;
+        let resp = call(&server, request("initialize", json!({}))).await;
+        assert!(resp.result.unwrap()["capabilities"]["logging"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_initialize_negotiates_protocol_version() {
+        let server = test_server().await;
+        let resp = call(
+            &server,
+            request("initialize", json!({"protocolVersion": "2024-11-05"})),
+        )
+        .await;
+        assert_eq!(resp.result.unwrap()["protocolVersion"], "2024-11-05");
+
+        let resp = call(
+            &server,
+            request("initialize", json!({"protocolVersion": "1999-01-01"})),
+        )
+        .await;
+        assert_eq!(
+            resp.result.unwrap()["protocolVersion"],
+            SUPPORTED_PROTOCOL_VERSIONS[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_notification_gets_no_response() {
+        let server = test_server().await;
+        let (session, _rx) = test_session();
+        let mut req = request("notifications/initialized", json!({}));
+        req.id = None;
+        assert!(server.handle_request(req, &session).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_batch_mixed() {
+        let server = test_server().await;
+        let (session, _rx) = test_session();
+        let batch = json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}},
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+            {"jsonrpc": "2.0", "id": "malformed", "params": {}},
+            42,
+            {"jsonrpc": "2.0", "id": 2, "method": "tools/list"},
+        ]);
+        let reply = server.handle_message(batch, &session).await.unwrap();
+        let responses = reply.as_array().unwrap();
+
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["id"], 1);
+        assert!(responses[0]["result"]["serverInfo"].is_object());
+        assert_eq!(responses[1]["id"], "malformed");
+        assert_eq!(responses[1]["error"]["code"], INVALID_REQUEST);
+        assert!(responses[2]["id"].is_null());
+        assert_eq!(responses[2]["error"]["code"], INVALID_REQUEST);
+        assert_eq!(responses[3]["id"], 2);
+        assert!(responses[3]["result"]["tools"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_batch_of_notifications_has_no_reply() {
+        let server = test_server().await;
+        let (session, _rx) = test_session();
+        let batch = json!([
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+        ]);
+        assert!(server.handle_message(batch, &session).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_empty_batch_is_invalid() {
+        let server = test_server().await;
+        let (session, _rx) = test_session();
+        let reply = server.handle_message(json!([]), &session).await.unwrap();
+        assert_eq!(reply["error"]["code"], INVALID_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_single_message_is_not_wrapped() {
+        let server = test_server().await;
+        let (session, _rx) = test_session();
+        let reply = server
+            .handle_message(
+                json!({"jsonrpc": "2.0", "id": 7, "method": "ping"}),
+                &session,
+            )
+            .await
+            .unwrap();
+        assert_eq!(reply["id"], 7);
+    }
+
+    #[tokio::test]
+    async fn test_set_log_level() {
+        let server = test_server().await;
+        let (session, _rx) = test_session();
+        let resp = server
+            .handle_request(
+                request("logging/setLevel", json!({"level": "debug"})),
+                &session,
+            )
+            .await
+            .unwrap();
+        assert!(resp.error.is_none());
+        assert_eq!(session.log_level(), LogLevel::Debug);
+    }
+
+    #[tokio::test]
+    async fn test_set_log_level_invalid() {
+        let server = test_server().await;
+        let resp = call(
+            &server,
+            request("logging/setLevel", json!({"level": "loud"})),
+        )
+        .await;
+        assert_eq!(resp.error.unwrap().code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_tool_error_emits_log_notification() {
+        let server = test_server().await;
+        let (session, mut rx) = test_session();
+        // Missing "query" fails argument parsing before any backend is touched.
+        let resp = server
+            .handle_request(
+                request("tools/call", json!({"name": "search", "arguments": {}})),
+                &session,
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.result.unwrap()["isError"], true);
+
+        let msg: Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(msg["method"], "notifications/message");
+        assert_eq!(msg["params"]["level"], "error");
+        assert_eq!(msg["params"]["data"]["tool"], "search");
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_times_out() {
+        // Accepts connections but never answers, like a hung Qdrant.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = AppConfig {
+            qdrant_url: format!("http://{}", listener.local_addr().unwrap()),
+            ..AppConfig::default()
+        };
+        let server = McpServer::new(config).await.unwrap();
+        let resp = call(
+            &server,
+            request(
+                "tools/call",
+                json!({"name": "get", "arguments": {"chunk_id": "abc", "timeout_secs": 1}}),
+            ),
+        )
+        .await;
+        let result = resp.result.unwrap();
+        assert_eq!(result["isError"], true);
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("get timed out after 1s"));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_tool_call_has_no_response() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = AppConfig {
+            qdrant_url: format!("http://{}", listener.local_addr().unwrap()),
+            ..AppConfig::default()
+        };
+        let server = McpServer::new(config).await.unwrap();
+        let (session, _rx) = test_session();
+        let mut slow = request(
+            "tools/call",
+            json!({"name": "get", "arguments": {"chunk_id": "abc"}}),
+        );
+        slow.id = Some(json!(5));
+
+        let cancel = async {
+            while server.in_flight.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            let mut notification = request("notifications/cancelled", json!({"requestId": 5}));
+            notification.id = None;
+            server.handle_request(notification, &session).await
+        };
+        let (resp, cancel_resp) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(server.handle_request(slow, &session), cancel)
+        })
+        .await
+        .expect("cancellation should end the call promptly");
+
+        assert!(resp.is_none());
+        assert!(cancel_resp.is_none());
+        assert!(server.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_unknown_request_is_ignored() {
+        let server = test_server().await;
+        let (session, _rx) = test_session();
+        let mut notification = request("notifications/cancelled", json!({"requestId": 42}));
+        notification.id = None;
+        assert!(server
+            .handle_request(notification, &session)
+            .await
+            .is_none());
+        assert!(server.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_rejects_invalid_timeout() {
+        let server = test_server().await;
+        let resp = call(
+            &server,
+            request(
+                "tools/call",
+                json!({"name": "search", "arguments": {"query": "x", "timeout_secs": -1}}),
+            ),
+        )
+        .await;
+        assert_eq!(resp.error.unwrap().code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_declares_prompts() {
+        let server = test_server().await;
+        let resp = call(&server, request("initialize", json!({}))).await;
+        assert!(resp.result.unwrap()["capabilities"]["prompts"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_prompts_list() {
+        let server = test_server().await;
+        let resp = call(&server, request("prompts/list", json!({}))).await;
+        let result = resp.result.unwrap();
+        assert_eq!(result["prompts"][0]["name"], "answer_from_docs");
+        assert_eq!(result["prompts"][0]["arguments"][0]["name"], "question");
+        assert_eq!(result["prompts"][0]["arguments"][0]["required"], true);
+    }
+
+    #[tokio::test]
+    async fn test_tools_list_without_cursor_returns_all() {
+        let server = test_server().await;
+        let resp = call(&server, request("tools/list", json!({}))).await;
+        let result = resp.result.unwrap();
+        assert_eq!(
+            result["tools"].as_array().unwrap().len(),
+            list_tools().len()
+        );
+        assert!(result.get("nextCursor").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tools_list_trailing_cursor_is_empty() {
+        let server = test_server().await;
+        let cursor = list_tools().len().to_string();
+        let resp = call(&server, request("tools/list", json!({ "cursor": cursor }))).await;
+        let result = resp.result.unwrap();
+        assert!(result["tools"].as_array().unwrap().is_empty());
+        assert!(result.get("nextCursor").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tools_list_invalid_cursor() {
+        let server = test_server().await;
+        for cursor in [json!("bogus"), json!("999"), json!(5)] {
+            let resp = call(&server, request("tools/list", json!({ "cursor": cursor }))).await;
+            assert_eq!(resp.error.unwrap().code, INVALID_PARAMS);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prompts_list_invalid_cursor() {
+        let server = test_server().await;
+        let resp = call(
+            &server,
+            request("prompts/list", json!({ "cursor": "bogus" })),
+        )
+        .await;
+        assert_eq!(resp.error.unwrap().code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_batch_search_rejects_oversized_batch() {
+        let server = test_server().await;
+        let queries: Vec<Value> = (0..=MAX_BATCH_QUERIES)
+            .map(|i| json!({ "query": format!("q{}", i) }))
+            .collect();
+        let resp = call(
+            &server,
+            request(
+                "tools/call",
+                json!({"name": "batch_search", "arguments": {"queries": queries}}),
+            ),
+        )
+        .await;
+        let err = resp.error.unwrap();
+        assert_eq!(err.code, INVALID_PARAMS);
+        assert!(err.message.contains("Too many queries"));
+    }
+
+    #[tokio::test]
+    async fn test_batch_search_rejects_empty_batch() {
+        let server = test_server().await;
+        let resp = call(
+            &server,
+            request(
+                "tools/call",
+                json!({"name": "batch_search", "arguments": {"queries": []}}),
+            ),
+        )
+        .await;
+        assert_eq!(resp.error.unwrap().code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_prompts_get_unknown_prompt() {
+        let server = test_server().await;
+        let resp = call(&server, request("prompts/get", json!({"name": "nope"}))).await;
+        assert_eq!(resp.error.unwrap().code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_prompts_get_missing_question() {
+        let server = test_server().await;
+        let resp = call(
+            &server,
+            request(
+                "prompts/get",
+                json!({"name": "answer_from_docs", "arguments": {}}),
+            ),
+        )
+        .await;
+        let err = resp.error.unwrap();
+        assert_eq!(err.code, INVALID_PARAMS);
+        assert!(err.message.contains("question"));
+    }
+
+    #[tokio::test]
+    async fn test_prompts_get_invalid_top_k() {
+        let server = test_server().await;
+        let resp = call(
+            &server,
+            request(
+                "prompts/get",
+                json!({"name": "answer_from_docs", "arguments": {"question": "q", "top_k": "x"}}),
+            ),
+        )
+        .await;
+        assert_eq!(resp.error.unwrap().code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_prompts_get_missing_params() {
+        let server = test_server().await;
+        let mut req = request("prompts/get", json!({}));
+        req.params = None;
+        let resp = call(&server, req).await;
+        assert_eq!(resp.error.unwrap().code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_index_status_healthy() {
+        let status = index_status_json(&AppConfig::default(), Ok(10), Ok(10), None);
+        assert_eq!(status["qdrant"]["status"], "ok");
+        assert_eq!(status["qdrant"]["count"], 10);
+        assert_eq!(status["counts_diverge"], false);
+        assert_eq!(status["empty"], false);
+    }
+
+    #[test]
+    fn test_index_status_diverging_counts() {
+        let status = index_status_json(&AppConfig::default(), Ok(10), Ok(7), None);
+        assert_eq!(status["counts_diverge"], true);
+    }
+
+    #[test]
+    fn test_index_status_empty() {
+        let status = index_status_json(&AppConfig::default(), Ok(0), Ok(0), None);
+        assert_eq!(status["empty"], true);
+    }
+
+    #[test]
+    fn test_index_status_qdrant_down() {
+        let status = index_status_json(
+            &AppConfig::default(),
+            Err(anyhow::anyhow!("connection refused")),
+            Ok(5),
+            Some("2026-01-01T00:00:00+00:00".to_string()),
+        );
+        assert!(status["qdrant"]["status"]
+            .as_str()
+            .unwrap()
+            .contains("connection refused"));
+        assert!(status["qdrant"]["count"].is_null());
+        assert_eq!(status["tantivy"]["count"], 5);
+        assert!(status["counts_diverge"].is_null());
+        assert_eq!(status["last_ingest"], "2026-01-01T00:00:00+00:00");
+    }
 }
@@ -0,0 +1,330 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tokio::sync::{mpsc, watch};
+
+use super::protocol::JsonRpcNotification;
+
+/// MCP log levels (RFC 5424 severities), ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LogLevel {
+    pub fn parse(level: &str) -> Option<Self> {
+        match level {
+            "debug" => Some(Self::Debug),
+            "info" => Some(Self::Info),
+            "notice" => Some(Self::Notice),
+            "warning" => Some(Self::Warning),
+            "error" => Some(Self::Error),
+            "critical" => Some(Self::Critical),
+            "alert" => Some(Self::Alert),
+            "emergency" => Some(Self::Emergency),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Notice => "notice",
+            Self::Warning => "warning",
+            Self::Error => "error",
+            Self::Critical => "critical",
+            Self::Alert => "alert",
+            Self::Emergency => "emergency",
+        }
+    }
+}
+
+/// A connected MCP client. Responses and server-initiated notifications are
+/// written to the session's outgoing channel.
+pub struct Session {
+    pub id: String,
+    sender: mpsc::Sender<String>,
+    shared: Arc<SharedState>,
+}
+
+/// State shared between a session and its forks.
+struct SharedState {
+    log_level: Mutex<LogLevel>,
+    last_activity: Mutex<Instant>,
+    closed: watch::Sender<bool>,
+}
+
+impl Session {
+    pub fn new(id: impl Into<String>, sender: mpsc::Sender<String>) -> Self {
+        Self {
+            id: id.into(),
+            sender,
+            shared: Arc::new(SharedState {
+                log_level: Mutex::new(LogLevel::Warning),
+                last_activity: Mutex::new(Instant::now()),
+                closed: watch::Sender::new(false),
+            }),
+        }
+    }
+
+    /// A handle to the same session that writes to a different channel, e.g.
+    /// the SSE stream answering a single HTTP request. Settings such as the
+    /// log level are shared with the original.
+    pub fn fork(&self, sender: mpsc::Sender<String>) -> Self {
+        Self {
+            id: self.id.clone(),
+            sender,
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Record client activity, resetting the idle timer.
+    pub fn touch(&self) {
+        *self.shared.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    /// Time since the client was last active.
+    pub fn idle_time(&self) -> Duration {
+        self.shared.last_activity.lock().unwrap().elapsed()
+    }
+
+    /// Mark the session closed; transports end its streams.
+    pub fn close(&self) {
+        self.shared.closed.send_replace(true);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self.shared.closed.borrow()
+    }
+
+    /// Resolves once [`Session::close`] has been called.
+    pub async fn closed(&self) {
+        let mut closed = self.shared.closed.subscribe();
+        // The sender lives as long as `self`, so this can't fail.
+        let _ = closed.wait_for(|closed| *closed).await;
+    }
+
+    /// Queue a raw message for the client.
+    pub async fn send(&self, message: String) -> Result<(), mpsc::error::SendError<String>> {
+        self.sender.send(message).await
+    }
+
+    /// Queue a raw message without waiting; fails when the channel is full or closed.
+    pub fn try_send(&self, message: String) -> Result<(), mpsc::error::TrySendError<String>> {
+        self.sender.try_send(message)
+    }
+
+    /// Send a JSON-RPC notification. Delivery failures are logged and ignored.
+    pub async fn notify(&self, method: &str, params: Value) {
+        let notification = JsonRpcNotification::new(method, params);
+        match serde_json::to_string(&notification) {
+            Ok(json) => {
+                if self.sender.send(json).await.is_err() {
+                    tracing::debug!("Dropped {} for closed session {}", method, self.id);
+                }
+            }
+            Err(e) => tracing::error!("Failed to serialize notification: {}", e),
+        }
+    }
+
+    /// Non-blocking variant of [`Session::notify`] for best-effort messages.
+    /// The notification is dropped when the channel is full or closed.
+    pub fn try_notify(&self, method: &str, params: Value) {
+        let notification = JsonRpcNotification::new(method, params);
+        match serde_json::to_string(&notification) {
+            Ok(json) => {
+                if self.sender.try_send(json).is_err() {
+                    tracing::debug!("Dropped {} for session {}", method, self.id);
+                }
+            }
+            Err(e) => tracing::error!("Failed to serialize notification: {}", e),
+        }
+    }
+
+    pub fn log_level(&self) -> LogLevel {
+        *self.shared.log_level.lock().unwrap()
+    }
+
+    pub fn set_log_level(&self, level: LogLevel) {
+        *self.shared.log_level.lock().unwrap() = level;
+    }
+
+    /// Emit a `notifications/message` if `level` meets the session's minimum level.
+    pub async fn log(&self, level: LogLevel, logger: &str, data: Value) {
+        if level < self.log_level() {
+            return;
+        }
+        self.notify(
+            "notifications/message",
+            serde_json::json!({
+                "level": level.as_str(),
+                "logger": logger,
+                "data": data,
+            }),
+        )
+        .await;
+    }
+}
+
+/// Progress reporter for a single request. Emits `notifications/progress` when
+/// the client supplied a `_meta.progressToken`, and does nothing otherwise.
+pub struct Progress<'a> {
+    session: &'a Session,
+    token: Option<Value>,
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(session: &'a Session, token: Option<Value>) -> Self {
+        Self { session, token }
+    }
+
+    /// Read the progress token from a request's `params._meta.progressToken`.
+    pub fn from_params(session: &'a Session, params: &Value) -> Self {
+        let token = params
+            .get("_meta")
+            .and_then(|m| m.get("progressToken"))
+            .filter(|t| t.is_string() || t.is_number())
+            .cloned();
+        Self::new(session, token)
+    }
+
+    pub fn report(&self, progress: u64, total: Option<u64>) {
+        let Some(ref token) = self.token else {
+            return;
+        };
+        let mut params = serde_json::json!({
+            "progressToken": token,
+            "progress": progress,
+        });
+        if let Some(total) = total {
+            params["total"] = total.into();
+        }
+        self.session.try_notify("notifications/progress", params);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_log_level_parse_roundtrip() {
+        for name in [
+            "debug",
+            "info",
+            "notice",
+            "warning",
+            "error",
+            "critical",
+            "alert",
+            "emergency",
+        ] {
+            assert_eq!(LogLevel::parse(name).unwrap().as_str(), name);
+        }
+        assert!(LogLevel::parse("verbose").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_log_respects_level() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let session = Session::new("s", tx);
+
+        session.log(LogLevel::Info, "test", json!("hidden")).await;
+        session.log(LogLevel::Error, "test", json!("shown")).await;
+
+        let msg: Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(msg["method"], "notifications/message");
+        assert_eq!(msg["params"]["level"], "error");
+        assert_eq!(msg["params"]["data"], "shown");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_progress_without_token_is_silent() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let session = Session::new("s", tx);
+        let progress = Progress::from_params(&session, &json!({"name": "search"}));
+        progress.report(1, Some(3));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_progress_with_token() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let session = Session::new("s", tx);
+        let progress =
+            Progress::from_params(&session, &json!({"_meta": {"progressToken": "tok-1"}}));
+        progress.report(2, Some(3));
+
+        let msg: Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(msg["method"], "notifications/progress");
+        assert_eq!(msg["params"]["progressToken"], "tok-1");
+        assert_eq!(msg["params"]["progress"], 2);
+        assert_eq!(msg["params"]["total"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_progress_ignores_invalid_token() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let session = Session::new("s", tx);
+        let progress = Progress::from_params(&session, &json!({"_meta": {"progressToken": {}}}));
+        progress.report(1, None);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fork_shares_log_level() {
+        let (tx, _rx) = mpsc::channel(10);
+        let session = Session::new("s", tx);
+        let (fork_tx, mut fork_rx) = mpsc::channel(10);
+        let fork = session.fork(fork_tx);
+
+        session.set_log_level(LogLevel::Debug);
+        fork.log(LogLevel::Info, "test", json!("shown")).await;
+        assert!(fork_rx.try_recv().is_ok());
+        assert_eq!(fork.id, "s");
+    }
+
+    #[tokio::test]
+    async fn test_close_is_shared_with_forks() {
+        let (tx, _rx) = mpsc::channel(10);
+        let session = Session::new("s", tx);
+        let (fork_tx, _fork_rx) = mpsc::channel(10);
+        let fork = session.fork(fork_tx);
+
+        assert!(!fork.is_closed());
+        session.close();
+        assert!(fork.is_closed());
+        // Already closed, so this resolves immediately.
+        fork.closed().await;
+    }
+
+    #[test]
+    fn test_touch_resets_idle_time() {
+        let (tx, _rx) = mpsc::channel(10);
+        let session = Session::new("s", tx);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(session.idle_time() >= Duration::from_millis(20));
+        session.touch();
+        assert!(session.idle_time() < Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn test_set_log_level() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let session = Session::new("s", tx);
+        session.set_log_level(LogLevel::Debug);
+        session.log(LogLevel::Info, "test", json!("shown")).await;
+        assert!(rx.try_recv().is_ok());
+    }
+}
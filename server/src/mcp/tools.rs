@@ -1,6 +1,10 @@
+use mcp_hybrid_search_common::types::{SearchFilters, VectorSearchParams};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
+/// Maximum number of queries accepted by a single `batch_search` call.
+pub const MAX_BATCH_QUERIES: usize = 20;
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Tool {
     pub name: String,
@@ -49,6 +53,10 @@ pub enum ToolName {
     Search,
     Get,
     GetProjectInfo,
+    ListProjects,
+    IndexStatus,
+    Count,
+    BatchSearch,
 }
 
 impl ToolName {
@@ -57,6 +65,10 @@ impl ToolName {
             "search" => Some(Self::Search),
             "get" => Some(Self::Get),
             "get_project_info" => Some(Self::GetProjectInfo),
+            "list_projects" => Some(Self::ListProjects),
+            "index_status" => Some(Self::IndexStatus),
+            "count" => Some(Self::Count),
+            "batch_search" => Some(Self::BatchSearch),
             _ => None,
         }
     }
@@ -67,21 +79,132 @@ pub struct SearchArgs {
     pub query: String,
     pub top_k: Option<usize>,
     pub filters: Option<FilterArgs>,
+    pub include_text: Option<bool>,
+    pub snippet_length: Option<usize>,
+    pub group_by_document: Option<bool>,
+    pub max_chunks_per_document: Option<usize>,
+    pub highlight: Option<bool>,
+    pub search_titles: Option<bool>,
+    pub search_params: Option<VectorSearchParams>,
+    pub project: Option<String>,
+    /// Include per-phase `timings` (milliseconds) in the output.
+    pub debug: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct FilterArgs {
     pub source_type: Option<String>,
     pub path_prefix: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub modified_after: Option<chrono::DateTime<chrono::Utc>>,
+    pub language: Option<String>,
+    pub ocr: Option<bool>,
+}
+
+impl FilterArgs {
+    pub fn to_filters(args: Option<&FilterArgs>) -> SearchFilters {
+        SearchFilters {
+            source_type: args.and_then(|f| f.source_type.clone()),
+            path_prefix: args.and_then(|f| f.path_prefix.clone()),
+            tags: args.and_then(|f| f.tags.clone()).unwrap_or_default(),
+            modified_after: args.and_then(|f| f.modified_after),
+            language: args.and_then(|f| f.language.clone()),
+            ocr: args.and_then(|f| f.ocr),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchSearchArgs {
+    pub queries: Vec<BatchQueryArgs>,
+    pub include_text: Option<bool>,
+    pub snippet_length: Option<usize>,
+    pub highlight: Option<bool>,
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchQueryArgs {
+    pub query: String,
+    pub top_k: Option<usize>,
+    pub filters: Option<FilterArgs>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GetArgs {
-    pub chunk_id: String,
+    pub chunk_id: Option<String>,
+    pub source_path: Option<String>,
+    pub chunk_index: Option<u32>,
+    pub before: Option<u32>,
+    pub after: Option<u32>,
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CountArgs {
+    pub query: Option<String>,
+    pub filters: Option<FilterArgs>,
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ProjectInfoArgs {
+    pub project: Option<String>,
+}
+
+/// How a `get` call identifies its chunk.
+#[derive(Debug, PartialEq)]
+pub enum ChunkAddress {
+    Id(String),
+    Position {
+        source_path: String,
+        chunk_index: u32,
+    },
+}
+
+impl GetArgs {
+    /// Validate that exactly one addressing mode was supplied.
+    pub fn address(&self) -> Result<ChunkAddress, InvalidParams> {
+        match (&self.chunk_id, &self.source_path, self.chunk_index) {
+            (Some(id), None, None) => Ok(ChunkAddress::Id(id.clone())),
+            (None, Some(path), Some(index)) => Ok(ChunkAddress::Position {
+                source_path: path.clone(),
+                chunk_index: index,
+            }),
+            (None, None, None) => Err(InvalidParams(
+                "Either chunk_id or source_path + chunk_index is required".to_string(),
+            )),
+            (None, _, _) => Err(InvalidParams(
+                "source_path and chunk_index must be given together".to_string(),
+            )),
+            (Some(_), _, _) => Err(InvalidParams(
+                "chunk_id cannot be combined with source_path / chunk_index".to_string(),
+            )),
+        }
+    }
+}
+
+/// Tool argument error reported to the client as a JSON-RPC INVALID_PARAMS error
+/// rather than a tool-level error result.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct InvalidParams(pub String);
+
+/// Read the optional per-call `timeout_secs` argument accepted by every tool.
+pub fn timeout_arg(arguments: &Value) -> Result<Option<u64>, InvalidParams> {
+    match arguments.get("timeout_secs") {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => match v.as_u64() {
+            Some(secs) if secs > 0 => Ok(Some(secs)),
+            _ => Err(InvalidParams(
+                "timeout_secs must be a positive integer".to_string(),
+            )),
+        },
+    }
 }
 
 pub fn list_tools() -> Vec<Tool> {
-    vec![
+    let mut tools = vec![
         Tool {
             name: "search".to_string(),
             description: "Search documents using hybrid search (vector + BM25). Returns ranked results from indexed documents.".to_string(),
@@ -96,6 +219,52 @@ pub fn list_tools() -> Vec<Tool> {
                         "type": "number",
                         "description": "Number of results to return (default: 10)"
                     },
+                    "include_text": {
+                        "type": "boolean",
+                        "description": "Include the full chunk text in each result, avoiding a follow-up get call (default: false)"
+                    },
+                    "snippet_length": {
+                        "type": "number",
+                        "description": "Maximum snippet length in characters (default: 200)"
+                    },
+                    "group_by_document": {
+                        "type": "boolean",
+                        "description": "Limit how many chunks each document contributes so other sources are not crowded out (default: false)"
+                    },
+                    "max_chunks_per_document": {
+                        "type": "number",
+                        "description": "Maximum chunks per document when group_by_document is set (default: 2)"
+                    },
+                    "highlight": {
+                        "type": "boolean",
+                        "description": "Wrap matched query terms in snippets with highlight markers (default: true)"
+                    },
+                    "search_titles": {
+                        "type": "boolean",
+                        "description": "Match the query semantically against document titles instead of chunk text, in collections with title vectors (default: false)"
+                    },
+                    "search_params": {
+                        "type": "object",
+                        "description": "Qdrant HNSW parameters for the vector search, over the server's configured defaults",
+                        "properties": {
+                            "exact": {
+                                "type": "boolean",
+                                "description": "Compare with every vector instead of using the HNSW index: slower, but exact, for checking recall"
+                            },
+                            "hnsw_ef": {
+                                "type": "number",
+                                "description": "Candidates kept while searching the HNSW index; higher is more accurate and slower. Ignored by an exact search"
+                            }
+                        }
+                    },
+                    "project": {
+                        "type": "string",
+                        "description": "Project (Qdrant collection) to use for this call instead of the server's active project"
+                    },
+                    "debug": {
+                        "type": "boolean",
+                        "description": "Include per-phase timings (embed, vector, bm25, fuse, total) in milliseconds, and the search_params the vector search used (default: false)"
+                    },
                     "filters": {
                         "type": "object",
                         "properties": {
@@ -106,6 +275,24 @@ pub fn list_tools() -> Vec<Tool> {
                             "path_prefix": {
                                 "type": "string",
                                 "description": "Filter by path prefix"
+                            },
+                            "tags": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "Only match documents whose front matter has all of these tags"
+                            },
+                            "modified_after": {
+                                "type": "string",
+                                "format": "date-time",
+                                "description": "Only match documents whose file was modified after this RFC3339 timestamp"
+                            },
+                            "language": {
+                                "type": "string",
+                                "description": "Only match documents detected as this language (ISO 639-1 code such as \"en\" or \"ja\"; \"und\" when undetermined)"
+                            },
+                            "ocr": {
+                                "type": "boolean",
+                                "description": "true: only match text recognized by OCR from images and scanned PDFs; false: leave it out"
                             }
                         }
                     }
@@ -115,26 +302,386 @@ pub fn list_tools() -> Vec<Tool> {
         },
         Tool {
             name: "get".to_string(),
-            description: "Get the full content of a specific document chunk by its chunk_id.".to_string(),
+            description: "Get the full content of a specific document chunk, addressed either by chunk_id or by source_path + chunk_index.".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
                     "chunk_id": {
                         "type": "string",
-                        "description": "The unique identifier of the chunk"
+                        "description": "The unique identifier of the chunk. Omit when using source_path + chunk_index."
+                    },
+                    "source_path": {
+                        "type": "string",
+                        "description": "Source file path of the chunk. Must be used together with chunk_index instead of chunk_id."
+                    },
+                    "chunk_index": {
+                        "type": "number",
+                        "description": "Zero-based index of the chunk within source_path. Must be used together with source_path."
+                    },
+                    "before": {
+                        "type": "number",
+                        "description": "Number of preceding chunks from the same document to include as neighbors (default: 0)"
+                    },
+                    "after": {
+                        "type": "number",
+                        "description": "Number of following chunks from the same document to include as neighbors (default: 0)"
+                    },
+                    "project": {
+                        "type": "string",
+                        "description": "Project (Qdrant collection) to use for this call instead of the server's active project"
                     }
                 },
-                "required": ["chunk_id"]
+                "oneOf": [
+                    { "required": ["chunk_id"] },
+                    { "required": ["source_path", "chunk_index"] }
+                ]
             }),
         },
         Tool {
             name: "get_project_info".to_string(),
             description: "Get information about the current project: collection name, document count, tantivy index directory, and embedding settings.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Project (Qdrant collection) to use for this call instead of the server's active project"
+                    }
+                },
+                "required": []
+            }),
+        },
+        Tool {
+            name: "list_projects".to_string(),
+            description: "List all projects (Qdrant collections) with their point counts, marking the server's active project.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        },
+        Tool {
+            name: "batch_search".to_string(),
+            description: format!("Run up to {} independent hybrid searches in one call. Returns one result list per query, in the same order.", MAX_BATCH_QUERIES),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "queries": {
+                        "type": "array",
+                        "description": format!("Queries to run (max {})", MAX_BATCH_QUERIES),
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "query": {
+                                    "type": "string",
+                                    "description": "Search query text"
+                                },
+                                "top_k": {
+                                    "type": "number",
+                                    "description": "Number of results to return (default: 10)"
+                                },
+                                "filters": {
+                                    "type": "object",
+                                    "properties": {
+                                        "source_type": {
+                                            "type": "string",
+                                            "description": "Filter by file type (md/txt/pdf/xlsx)"
+                                        },
+                                        "path_prefix": {
+                                            "type": "string",
+                                            "description": "Filter by path prefix"
+                                        },
+                                        "tags": {
+                                            "type": "array",
+                                            "items": { "type": "string" },
+                                            "description": "Only match documents whose front matter has all of these tags"
+                                        },
+                                        "modified_after": {
+                                            "type": "string",
+                                            "format": "date-time",
+                                            "description": "Only match documents whose file was modified after this RFC3339 timestamp"
+                                        },
+                                        "language": {
+                                            "type": "string",
+                                            "description": "Only match documents detected as this language (ISO 639-1 code such as \"en\" or \"ja\"; \"und\" when undetermined)"
+                                        },
+                                        "ocr": {
+                                            "type": "boolean",
+                                            "description": "true: only match text recognized by OCR from images and scanned PDFs; false: leave it out"
+                                        }
+                                    }
+                                }
+                            },
+                            "required": ["query"]
+                        }
+                    },
+                    "include_text": {
+                        "type": "boolean",
+                        "description": "Include the full chunk text in each result (default: false)"
+                    },
+                    "snippet_length": {
+                        "type": "number",
+                        "description": "Maximum snippet length in characters (default: 200)"
+                    },
+                    "highlight": {
+                        "type": "boolean",
+                        "description": "Wrap matched query terms in snippets with highlight markers (default: true)"
+                    },
+                    "project": {
+                        "type": "string",
+                        "description": "Project (Qdrant collection) to use for this call instead of the server's active project"
+                    }
+                },
+                "required": ["queries"]
+            }),
+        },
+        Tool {
+            name: "count".to_string(),
+            description: "Count indexed chunks matching a query and/or filters, without retrieving them. Returns the BM25 (Tantivy) match count and the number of Qdrant points matching the filters.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Full-text query; omit to count by filters alone"
+                    },
+                    "filters": {
+                        "type": "object",
+                        "properties": {
+                            "source_type": {
+                                "type": "string",
+                                "description": "Filter by file type (md/txt/pdf/xlsx)"
+                            },
+                            "path_prefix": {
+                                "type": "string",
+                                "description": "Filter by path prefix"
+                            },
+                            "tags": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "Only match documents whose front matter has all of these tags"
+                            },
+                            "modified_after": {
+                                "type": "string",
+                                "format": "date-time",
+                                "description": "Only match documents whose file was modified after this RFC3339 timestamp"
+                            },
+                            "language": {
+                                "type": "string",
+                                "description": "Only match documents detected as this language (ISO 639-1 code such as \"en\" or \"ja\"; \"und\" when undetermined)"
+                            },
+                            "ocr": {
+                                "type": "boolean",
+                                "description": "true: only match text recognized by OCR from images and scanned PDFs; false: leave it out"
+                            }
+                        }
+                    },
+                    "project": {
+                        "type": "string",
+                        "description": "Project (Qdrant collection) to use for this call instead of the server's active project"
+                    }
+                },
+                "required": []
+            }),
+        },
+        Tool {
+            name: "index_status".to_string(),
+            description: "Report index health: Qdrant point count, Tantivy document count, whether they diverge, last ingest time, and the source directory. Use it to tell whether the index is empty and needs `ragctl ingest`.".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {},
                 "required": []
             }),
         },
-    ]
+    ];
+
+    for tool in &mut tools {
+        tool.input_schema["properties"]["timeout_secs"] = json!({
+            "type": "integer",
+            "description": "Abort the call after this many seconds (default: the server's tool_timeout_secs)",
+            "minimum": 1
+        });
+    }
+    tools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_args(value: Value) -> GetArgs {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_every_listed_tool_parses() {
+        for tool in list_tools() {
+            assert!(ToolName::parse(&tool.name).is_some(), "{}", tool.name);
+        }
+    }
+
+    #[test]
+    fn test_every_tool_accepts_timeout() {
+        for tool in list_tools() {
+            assert_eq!(
+                tool.input_schema["properties"]["timeout_secs"]["type"], "integer",
+                "{}",
+                tool.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_accepts_debug() {
+        let search = list_tools()
+            .into_iter()
+            .find(|t| t.name == "search")
+            .unwrap();
+        assert_eq!(
+            search.input_schema["properties"]["debug"]["type"],
+            "boolean"
+        );
+        let args: SearchArgs =
+            serde_json::from_value(json!({"query": "q", "debug": true})).unwrap();
+        assert_eq!(args.debug, Some(true));
+    }
+
+    #[test]
+    fn test_search_params() {
+        let search = list_tools()
+            .into_iter()
+            .find(|t| t.name == "search")
+            .unwrap();
+        let schema = &search.input_schema["properties"]["search_params"]["properties"];
+        assert_eq!(schema["exact"]["type"], "boolean");
+        assert_eq!(schema["hnsw_ef"]["type"], "number");
+        let args: SearchArgs = serde_json::from_value(
+            json!({"query": "q", "search_params": {"exact": true, "hnsw_ef": 256}}),
+        )
+        .unwrap();
+        let params = args.search_params.unwrap();
+        assert_eq!(params.exact, Some(true));
+        assert_eq!(params.checked().hnsw_ef, None);
+    }
+
+    #[test]
+    fn test_tag_filters() {
+        let tools = list_tools();
+        for name in ["search", "batch_search", "count"] {
+            let tool = tools.iter().find(|t| t.name == name).unwrap();
+            assert!(
+                tool.input_schema.to_string().contains("\"tags\""),
+                "{}",
+                name
+            );
+        }
+        let args: FilterArgs =
+            serde_json::from_value(json!({"tags": ["billing", "architecture"]})).unwrap();
+        let filters = FilterArgs::to_filters(Some(&args));
+        assert_eq!(filters.tags, vec!["billing", "architecture"]);
+        assert!(FilterArgs::to_filters(None).tags.is_empty());
+    }
+
+    #[test]
+    fn test_modified_after_filter() {
+        let tools = list_tools();
+        for name in ["search", "batch_search", "count"] {
+            let tool = tools.iter().find(|t| t.name == name).unwrap();
+            assert!(
+                tool.input_schema.to_string().contains("\"modified_after\""),
+                "{}",
+                name
+            );
+        }
+        let args: FilterArgs =
+            serde_json::from_value(json!({"modified_after": "2026-03-01T12:00:00+02:00"})).unwrap();
+        let filters = FilterArgs::to_filters(Some(&args));
+        assert_eq!(
+            filters.modified_after.unwrap().to_rfc3339(),
+            "2026-03-01T10:00:00+00:00"
+        );
+        assert!(serde_json::from_value::<FilterArgs>(json!({"modified_after": "March"})).is_err());
+    }
+
+    #[test]
+    fn test_language_filter() {
+        let tools = list_tools();
+        for name in ["search", "batch_search", "count"] {
+            let tool = tools.iter().find(|t| t.name == name).unwrap();
+            let filters = &tool.input_schema["properties"]["filters"];
+            let filters = if name == "batch_search" {
+                &tool.input_schema["properties"]["queries"]["items"]["properties"]["filters"]
+            } else {
+                filters
+            };
+            assert_eq!(
+                filters["properties"]["language"]["type"], "string",
+                "{}",
+                name
+            );
+        }
+        let args: FilterArgs = serde_json::from_value(json!({"language": "ja"})).unwrap();
+        let filters = FilterArgs::to_filters(Some(&args));
+        assert_eq!(filters.language.as_deref(), Some("ja"));
+        assert!(FilterArgs::to_filters(None).language.is_none());
+    }
+
+    #[test]
+    fn test_ocr_filter() {
+        let tools = list_tools();
+        for name in ["search", "count"] {
+            let tool = tools.iter().find(|t| t.name == name).unwrap();
+            let filters = &tool.input_schema["properties"]["filters"];
+            assert_eq!(filters["properties"]["ocr"]["type"], "boolean", "{}", name);
+        }
+        let args: FilterArgs = serde_json::from_value(json!({"ocr": false})).unwrap();
+        assert_eq!(FilterArgs::to_filters(Some(&args)).ocr, Some(false));
+        assert!(FilterArgs::to_filters(None).ocr.is_none());
+    }
+
+    #[test]
+    fn test_timeout_arg() {
+        assert_eq!(timeout_arg(&json!({})).unwrap(), None);
+        assert_eq!(
+            timeout_arg(&json!({"timeout_secs": 120})).unwrap(),
+            Some(120)
+        );
+        assert!(timeout_arg(&json!({"timeout_secs": 0})).is_err());
+        assert!(timeout_arg(&json!({"timeout_secs": "10"})).is_err());
+    }
+
+    #[test]
+    fn test_get_address_by_id() {
+        let args = get_args(json!({"chunk_id": "abc"}));
+        assert_eq!(args.address().unwrap(), ChunkAddress::Id("abc".to_string()));
+    }
+
+    #[test]
+    fn test_get_address_by_position() {
+        let args = get_args(json!({"source_path": "/a.md", "chunk_index": 3}));
+        assert_eq!(
+            args.address().unwrap(),
+            ChunkAddress::Position {
+                source_path: "/a.md".to_string(),
+                chunk_index: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_address_missing() {
+        assert!(get_args(json!({})).address().is_err());
+    }
+
+    #[test]
+    fn test_get_address_partial_position() {
+        assert!(get_args(json!({"source_path": "/a.md"})).address().is_err());
+        assert!(get_args(json!({"chunk_index": 1})).address().is_err());
+    }
+
+    #[test]
+    fn test_get_address_both_modes() {
+        let args = get_args(json!({"chunk_id": "abc", "source_path": "/a.md", "chunk_index": 0}));
+        assert!(args.address().is_err());
+    }
 }
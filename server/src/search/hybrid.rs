@@ -1,19 +1,32 @@
 use std::collections::HashMap;
+use std::time::Instant;
 
 use anyhow::Result;
 use mcp_hybrid_search_common::config::AppConfig;
-use mcp_hybrid_search_common::types::{ChunkDetail, SearchFilters, SearchResult};
+use mcp_hybrid_search_common::types::{
+    ChunkDetail, SearchFilters, SearchOptions, SearchResult, SearchTimings,
+};
+use tracing::Instrument;
 
 use super::embedding;
 use super::qdrant_search;
 use super::tantivy_search;
 
+/// One query of a batch search.
+#[derive(Debug, Clone)]
+pub struct BatchQuery {
+    pub query: String,
+    pub top_k: usize,
+    pub filters: SearchFilters,
+}
+
 pub struct HybridSearcher {
     // Stateless for now; could cache index readers in the future
 }
 
 impl HybridSearcher {
-    pub fn new(_config: &AppConfig) -> Result<Self> {
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        embedding::warm_up(config);
         Ok(Self {})
     }
 
@@ -23,20 +36,137 @@ impl HybridSearcher {
         query: &str,
         top_k: usize,
         filters: &SearchFilters,
+        options: &SearchOptions,
     ) -> Result<Vec<SearchResult>> {
+        let (results, _) = self
+            .search_with_progress(config, query, top_k, filters, options, |_, _| {})
+            .await?;
+        Ok(results)
+    }
+
+    /// Like [`HybridSearcher::search`], calling `on_progress(done, total)` as
+    /// each phase (embedding, vector search, BM25 search) completes. Also
+    /// returns how long each phase took.
+    ///
+    /// With `lexical_backend = "qdrant-sparse"` the vector and BM25 searches
+    /// and their fusion are one Qdrant query, timed as the vector search.
+    #[tracing::instrument(name = "search", skip_all, fields(top_k))]
+    pub async fn search_with_progress(
+        &self,
+        config: &AppConfig,
+        query: &str,
+        top_k: usize,
+        filters: &SearchFilters,
+        options: &SearchOptions,
+        on_progress: impl Fn(u64, u64),
+    ) -> Result<(Vec<SearchResult>, SearchTimings)> {
+        let started = Instant::now();
+        let mut timings = SearchTimings::default();
+        let phases = if config.sparse_lexical() { 2 } else { 3 };
+        timings.search_params = options.search_params.resolve(config.qdrant.search_params());
+
         // Get query embedding
-        let query_embedding = embedding::get_embedding(config, query).await?;
+        let phase = Instant::now();
+        let query_embedding = embedding::get_embedding(config, query)
+            .instrument(tracing::debug_span!("embed"))
+            .await?;
+        timings.embed_ms = SearchTimings::since(phase);
+        on_progress(1, phases);
+
+        let results = if config.sparse_lexical() {
+            let phase = Instant::now();
+            let results = sparse_search(config, query, &query_embedding, top_k, filters, options)
+                .instrument(tracing::debug_span!("vector"))
+                .await?;
+            timings.vector_ms = SearchTimings::since(phase);
+            on_progress(2, phases);
+            results
+        } else {
+            // Vector search (top 30)
+            let phase = Instant::now();
+            let vector_results = vector_search(config, query, &query_embedding, filters, options)
+                .instrument(tracing::debug_span!("vector"))
+                .await?;
+            timings.vector_ms = SearchTimings::since(phase);
+            on_progress(2, phases);
+
+            // BM25 search (top 30)
+            let phase = Instant::now();
+            let bm25_results = tracing::debug_span!("bm25")
+                .in_scope(|| tantivy_search::search(config, query, 30, filters, options))?;
+            timings.bm25_ms = SearchTimings::since(phase);
+            on_progress(3, phases);
+
+            let phase = Instant::now();
+            let results = tracing::debug_span!("fuse")
+                .in_scope(|| fuse(&vector_results, &bm25_results, top_k, options));
+            timings.fuse_ms = SearchTimings::since(phase);
+            results
+        };
+        timings.total_ms = SearchTimings::since(started);
+
+        tracing::debug!(
+            embed_ms = timings.embed_ms,
+            vector_ms = timings.vector_ms,
+            bm25_ms = timings.bm25_ms,
+            fuse_ms = timings.fuse_ms,
+            total_ms = timings.total_ms,
+            search_params = %timings.search_params,
+            "Search timings"
+        );
+        Ok((results, timings))
+    }
+
+    /// Run several independent searches. All queries are embedded in a single
+    /// provider call; vector searches run concurrently and BM25 searches run
+    /// on the blocking thread pool. Results are returned in query order.
+    #[tracing::instrument(name = "batch_search", skip_all, fields(queries = queries.len()))]
+    pub async fn batch_search(
+        &self,
+        config: &AppConfig,
+        queries: &[BatchQuery],
+        options: &SearchOptions,
+    ) -> Result<Vec<Vec<SearchResult>>> {
+        let texts: Vec<String> = queries.iter().map(|q| q.query.clone()).collect();
+        let embeddings = embedding::get_embeddings(config, &texts).await?;
 
-        // Vector search (top 30)
-        let vector_results = qdrant_search::search(config, &query_embedding, 30, filters).await?;
+        if config.sparse_lexical() {
+            let searches = queries
+                .iter()
+                .zip(&embeddings)
+                .map(|(q, emb)| sparse_search(config, &q.query, emb, q.top_k, &q.filters, options));
+            return futures::future::try_join_all(searches).await;
+        }
 
-        // BM25 search (top 30)
-        let bm25_results = tantivy_search::search(config, query, 30, filters)?;
+        let vector_searches = queries
+            .iter()
+            .zip(&embeddings)
+            .map(|(q, emb)| vector_search(config, &q.query, emb, &q.filters, options));
 
-        // RRF merge
-        let merged = rrf_merge(&vector_results, &bm25_results, top_k);
+        let bm25_searches = queries.iter().map(|q| {
+            let config = config.clone();
+            let q = q.clone();
+            let options = options.clone();
+            async move {
+                tokio::task::spawn_blocking(move || {
+                    tantivy_search::search(&config, &q.query, 30, &q.filters, &options)
+                })
+                .await?
+            }
+        });
 
-        Ok(merged)
+        let (vector_results, bm25_results) = futures::future::try_join(
+            futures::future::try_join_all(vector_searches),
+            futures::future::try_join_all(bm25_searches),
+        )
+        .await?;
+
+        Ok(queries
+            .iter()
+            .zip(vector_results)
+            .zip(bm25_results)
+            .map(|((q, vector), bm25)| fuse(&vector, &bm25, q.top_k, options))
+            .collect())
     }
 
     pub async fn get_chunk(
@@ -46,6 +176,119 @@ impl HybridSearcher {
     ) -> Result<Option<ChunkDetail>> {
         qdrant_search::get_chunk(config, chunk_id).await
     }
+
+    pub async fn get_chunk_at(
+        &self,
+        config: &AppConfig,
+        source_path: &str,
+        chunk_index: u32,
+    ) -> Result<Option<ChunkDetail>> {
+        let chunks =
+            qdrant_search::get_chunk_range(config, source_path, chunk_index, chunk_index).await?;
+        Ok(chunks.into_iter().next())
+    }
+
+    /// All chunks of the document at `source_path`, in order.
+    pub async fn get_document(
+        &self,
+        config: &AppConfig,
+        source_path: &str,
+    ) -> Result<Vec<ChunkDetail>> {
+        qdrant_search::get_document_chunks(config, source_path).await
+    }
+
+    /// Fetch up to `before` preceding and `after` following chunks of the same
+    /// document, excluding the chunk itself.
+    pub async fn get_neighbors(
+        &self,
+        config: &AppConfig,
+        chunk: &ChunkDetail,
+        before: u32,
+        after: u32,
+    ) -> Result<Vec<ChunkDetail>> {
+        let index = chunk.metadata.chunk_index;
+        let from = index.saturating_sub(before);
+        let to = index.saturating_add(after);
+        let chunks =
+            qdrant_search::get_chunk_range(config, &chunk.metadata.source_path, from, to).await?;
+        Ok(chunks
+            .into_iter()
+            .filter(|c| c.metadata.chunk_index != index)
+            .collect())
+    }
+}
+
+/// The vector half of a Tantivy-fused search: the top 30 chunks, or with
+/// document grouping, the top 30 documents' best chunks from Qdrant's group
+/// search, so one long document can't crowd the others out of the
+/// candidates.
+async fn vector_search(
+    config: &AppConfig,
+    query: &str,
+    query_embedding: &[f32],
+    filters: &SearchFilters,
+    options: &SearchOptions,
+) -> Result<Vec<SearchResult>> {
+    if options.group_by_document {
+        let groups = qdrant_search::search_groups(
+            config,
+            query,
+            query_embedding,
+            30,
+            options.max_chunks_per_document,
+            filters,
+            options,
+        )
+        .await?;
+        Ok(flatten_groups(groups))
+    } else {
+        qdrant_search::search(config, query, query_embedding, 30, filters, options).await
+    }
+}
+
+/// One ranked list of grouped results, document by document, so each
+/// document's chunks stay together in its best chunk's place.
+fn flatten_groups(groups: Vec<qdrant_search::SearchGroup>) -> Vec<SearchResult> {
+    groups.into_iter().flat_map(|group| group.hits).collect()
+}
+
+/// Search with Qdrant's hybrid query, applying document grouping when
+/// requested to as many candidates as the Tantivy path fuses (30 + 30).
+async fn sparse_search(
+    config: &AppConfig,
+    query: &str,
+    query_embedding: &[f32],
+    top_k: usize,
+    filters: &SearchFilters,
+    options: &SearchOptions,
+) -> Result<Vec<SearchResult>> {
+    if options.group_by_document {
+        let ranked =
+            qdrant_search::hybrid_search(config, query, query_embedding, 60, filters, options)
+                .await?;
+        Ok(group_by_document(
+            ranked,
+            options.max_chunks_per_document,
+            top_k,
+        ))
+    } else {
+        qdrant_search::hybrid_search(config, query, query_embedding, top_k, filters, options).await
+    }
+}
+
+/// Merge vector and BM25 rankings, applying document grouping when requested.
+fn fuse(
+    vector_results: &[SearchResult],
+    bm25_results: &[SearchResult],
+    top_k: usize,
+    options: &SearchOptions,
+) -> Vec<SearchResult> {
+    if options.group_by_document {
+        let merged = rrf_merge(vector_results, bm25_results, usize::MAX);
+        group_by_document(merged, options.max_chunks_per_document, top_k)
+    } else {
+        rrf_merge(vector_results, bm25_results, top_k)
+    }
 }
 
 /// Reciprocal Rank Fusion
@@ -89,3 +332,126 @@ fn rrf_merge(
         })
         .collect()
 }
+
+/// Cap the number of chunks each source_path contributes to the ranked list.
+///
+/// Over-cap chunks are skipped so lower-ranked chunks from other documents can
+/// backfill up to `top_k`. Each kept result records how many candidates its
+/// document had in total.
+fn group_by_document(
+    ranked: Vec<SearchResult>,
+    max_per_document: usize,
+    top_k: usize,
+) -> Vec<SearchResult> {
+    let max_per_document = max_per_document.max(1);
+
+    let mut totals: HashMap<String, usize> = HashMap::new();
+    for r in &ranked {
+        *totals.entry(r.source_path.clone()).or_insert(0) += 1;
+    }
+
+    let mut taken: HashMap<String, usize> = HashMap::new();
+    let mut grouped = Vec::new();
+
+    for mut r in ranked {
+        if grouped.len() >= top_k {
+            break;
+        }
+        let count = taken.entry(r.source_path.clone()).or_insert(0);
+        if *count >= max_per_document {
+            continue;
+        }
+        *count += 1;
+        r.document_match_count = totals.get(&r.source_path).copied();
+        grouped.push(r);
+    }
+
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_result(id: &str, path: &str) -> SearchResult {
+        SearchResult {
+            chunk_id: id.to_string(),
+            score: 0.0,
+            title: format!("Title {}", id),
+            source_path: path.to_string(),
+            source_type: "md".to_string(),
+            section: None,
+            location: Default::default(),
+            file_mtime: None,
+            snippet: "snippet".to_string(),
+            text: None,
+            document_match_count: None,
+        }
+    }
+
+    #[test]
+    fn test_group_by_document_collapses() {
+        let ranked = vec![
+            make_result("a1", "/a.md"),
+            make_result("a2", "/a.md"),
+            make_result("a3", "/a.md"),
+            make_result("b1", "/b.md"),
+        ];
+        let grouped = group_by_document(ranked, 2, 10);
+        let ids: Vec<&str> = grouped.iter().map(|r| r.chunk_id.as_str()).collect();
+        assert_eq!(ids, vec!["a1", "a2", "b1"]);
+    }
+
+    #[test]
+    fn test_group_by_document_backfills_to_top_k() {
+        let ranked = vec![
+            make_result("a1", "/a.md"),
+            make_result("a2", "/a.md"),
+            make_result("a3", "/a.md"),
+            make_result("b1", "/b.md"),
+            make_result("c1", "/c.md"),
+        ];
+        let grouped = group_by_document(ranked, 1, 3);
+        let ids: Vec<&str> = grouped.iter().map(|r| r.chunk_id.as_str()).collect();
+        assert_eq!(ids, vec!["a1", "b1", "c1"]);
+    }
+
+    #[test]
+    fn test_group_by_document_reports_total_matches() {
+        let ranked = vec![
+            make_result("a1", "/a.md"),
+            make_result("b1", "/b.md"),
+            make_result("a2", "/a.md"),
+            make_result("a3", "/a.md"),
+        ];
+        let grouped = group_by_document(ranked, 1, 10);
+        assert_eq!(grouped[0].document_match_count, Some(3));
+        assert_eq!(grouped[1].document_match_count, Some(1));
+    }
+
+    #[test]
+    fn test_flatten_groups() {
+        let groups = vec![
+            qdrant_search::SearchGroup {
+                key: "/a.md".to_string(),
+                hits: vec![make_result("a1", "/a.md"), make_result("a2", "/a.md")],
+            },
+            qdrant_search::SearchGroup {
+                key: "/b.md".to_string(),
+                hits: vec![make_result("b1", "/b.md")],
+            },
+        ];
+        let ids: Vec<String> = flatten_groups(groups)
+            .into_iter()
+            .map(|r| r.chunk_id)
+            .collect();
+        assert_eq!(ids, ["a1", "a2", "b1"]);
+    }
+
+    #[test]
+    fn test_group_by_document_zero_cap_treated_as_one() {
+        let ranked = vec![make_result("a1", "/a.md"), make_result("a2", "/a.md")];
+        let grouped = group_by_document(ranked, 0, 10);
+        assert_eq!(grouped.len(), 1);
+    }
+}
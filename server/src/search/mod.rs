@@ -1,6 +1,9 @@
 mod embedding;
 mod hybrid;
 pub mod qdrant_search;
-mod tantivy_search;
+pub mod tantivy_search;
 
-pub use hybrid::HybridSearcher;
+pub use embedding::{
+    check_dimensions, check_provider_config, local_execution_provider, EmbeddingError,
+};
+pub use hybrid::{BatchQuery, HybridSearcher};
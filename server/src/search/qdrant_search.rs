@@ -1,90 +1,689 @@
 use anyhow::Result;
 use mcp_hybrid_search_common::config::AppConfig;
-use mcp_hybrid_search_common::types::{ChunkDetail, ChunkMetadata, SearchFilters, SearchResult};
-use qdrant_client::qdrant::{Condition, Filter, GetPointsBuilder, PointId, SearchPointsBuilder};
-use qdrant_client::Qdrant;
+use mcp_hybrid_search_common::qdrant::{connect, count_under_prefix, fetch};
+use mcp_hybrid_search_common::sparse;
+use mcp_hybrid_search_common::types::{
+    path_prefix_directory, ChunkDetail, ChunkLocation, ChunkMetadata, SearchFilters, SearchOptions,
+    SearchResult, PATH_PREFIXES,
+};
+use qdrant_client::qdrant::{
+    vectors_config, Condition, CountPointsBuilder, DatetimeRange, Direction, Filter,
+    GetPointsBuilder, OrderByBuilder, PayloadIncludeSelector, PointId, PrefetchQueryBuilder, Query,
+    QueryPointsBuilder, Range, RrfBuilder, ScoredPoint, ScrollPointsBuilder, SearchParams,
+    SearchParamsBuilder, SearchPointGroupsBuilder, SearchPointsBuilder, Timestamp, VectorInput,
+};
+use qdrant_client::{Qdrant, QdrantError};
+use std::sync::{Mutex, PoisonError};
 
 pub async fn search(
     config: &AppConfig,
+    query: &str,
     query_embedding: &[f32],
     top_k: usize,
     filters: &SearchFilters,
+    options: &SearchOptions,
 ) -> Result<Vec<SearchResult>> {
-    let client = Qdrant::from_url(&config.qdrant_url).build()?;
+    let client = connect(config)?;
+    let layout = vector_layout(&client, config).await?;
+    let vector = dense_vector(config, layout, options)?;
+    let (filter, check_prefix) = prefix_filter(filters, layout);
 
-    let mut conditions = Vec::new();
+    let mut builder = SearchPointsBuilder::new(
+        &config.collection_name,
+        query_embedding.to_vec(),
+        fetch(top_k, check_prefix) as u64,
+    )
+    .with_payload(true);
+    if let Some(vector) = vector {
+        builder = builder.vector_name(vector);
+    }
+    if let Some(params) = search_params(config, options) {
+        builder = builder.params(params);
+    }
 
-    if let Some(ref source_type) = filters.source_type {
-        conditions.push(Condition::matches("source_type", source_type.clone()));
+    if let Some(filter) = filter {
+        builder = builder.filter(filter);
     }
 
-    let mut builder = SearchPointsBuilder::new(
+    let results = match client.search_points(builder).await {
+        Ok(results) => results,
+        Err(e) => {
+            forget_vector_layout(config);
+            return Err(e.into());
+        }
+    };
+
+    Ok(results
+        .result
+        .iter()
+        .map(|point| to_search_result(point, query, options))
+        .filter(|r| filters.matches_path(&r.source_path))
+        .take(top_k)
+        .collect())
+}
+
+/// Chunks of one document from [`search_groups`], best first.
+#[derive(Debug, Clone)]
+pub struct SearchGroup {
+    /// The document's `source_path`.
+    pub key: String,
+    pub hits: Vec<SearchResult>,
+}
+
+/// Qdrant URLs whose server has no group search (older than 1.2), so
+/// grouped searches there go straight to [`search`].
+static NO_GROUPS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Vector search grouped by document by Qdrant: the best `groups`
+/// documents, each with its best `group_size` chunks. Documents are ordered
+/// by their best chunk.
+///
+/// When the server lacks group search this is a plain [`search`] for
+/// `groups * group_size` chunks, grouped client-side, which may find fewer
+/// documents.
+#[allow(clippy::too_many_arguments)]
+pub async fn search_groups(
+    config: &AppConfig,
+    query: &str,
+    query_embedding: &[f32],
+    groups: usize,
+    group_size: usize,
+    filters: &SearchFilters,
+    options: &SearchOptions,
+) -> Result<Vec<SearchGroup>> {
+    let group_size = group_size.max(1);
+    let unsupported = NO_GROUPS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .contains(&config.qdrant_url);
+    let client = connect(config)?;
+    let layout = vector_layout(&client, config).await?;
+    let vector = dense_vector(config, layout, options)?;
+    let (filter, check_prefix) = prefix_filter(filters, layout);
+    // A `path_prefix` checked on the hits makes the search ungrouped too:
+    // Qdrant would fill its groups with documents outside the prefix.
+    if unsupported || check_prefix {
+        return search_then_group(
+            config,
+            query,
+            query_embedding,
+            groups,
+            group_size,
+            filters,
+            options,
+        )
+        .await;
+    }
+
+    let mut builder = SearchPointGroupsBuilder::new(
         &config.collection_name,
         query_embedding.to_vec(),
-        top_k as u64,
+        groups as u32,
+        "source_path",
+        group_size as u32,
     )
     .with_payload(true);
-
-    if !conditions.is_empty() {
-        builder = builder.filter(Filter::must(conditions));
+    if let Some(vector) = vector {
+        builder = builder.vector_name(vector);
+    }
+    if let Some(params) = search_params(config, options) {
+        builder = builder.params(params);
+    }
+    if let Some(filter) = filter {
+        builder = builder.filter(filter);
     }
 
-    let results = client.search_points(builder).await?;
+    let response = match client.search_groups(builder).await {
+        Ok(response) => response,
+        Err(QdrantError::ResponseError { status })
+            if status.code() == tonic::Code::Unimplemented =>
+        {
+            tracing::info!(
+                "Qdrant at {} has no group search; grouping results client-side",
+                config.qdrant_url
+            );
+            NO_GROUPS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(config.qdrant_url.clone());
+            return search_then_group(
+                config,
+                query,
+                query_embedding,
+                groups,
+                group_size,
+                filters,
+                options,
+            )
+            .await;
+        }
+        Err(e) => {
+            forget_vector_layout(config);
+            return Err(e.into());
+        }
+    };
 
-    let search_results: Vec<SearchResult> = results
+    Ok(response
         .result
+        .map(|r| r.groups)
+        .unwrap_or_default()
         .iter()
-        .map(|point| {
-            let payload = &point.payload;
-            let chunk_id = get_str(payload, "chunk_id");
-            let title = get_str(payload, "title");
-            let source_path = get_str(payload, "source_path");
-            let source_type = get_str(payload, "source_type");
-            let text = get_str(payload, "text");
-            let snippet = mcp_hybrid_search_common::types::truncate_snippet(&text, 200);
-
-            SearchResult {
-                chunk_id,
-                score: point.score as f64,
-                title,
-                source_path,
-                source_type,
-                snippet,
+        .map(|group| {
+            let hits: Vec<SearchResult> = group
+                .hits
+                .iter()
+                .map(|point| to_search_result(point, query, options))
+                .collect();
+            SearchGroup {
+                key: hits
+                    .first()
+                    .map(|hit| hit.source_path.clone())
+                    .unwrap_or_default(),
+                hits,
             }
         })
-        .collect();
+        .collect())
+}
+
+/// The fallback of [`search_groups`].
+async fn search_then_group(
+    config: &AppConfig,
+    query: &str,
+    query_embedding: &[f32],
+    groups: usize,
+    group_size: usize,
+    filters: &SearchFilters,
+    options: &SearchOptions,
+) -> Result<Vec<SearchGroup>> {
+    let results = search(
+        config,
+        query,
+        query_embedding,
+        groups * group_size,
+        filters,
+        options,
+    )
+    .await?;
+    Ok(group_results(results, groups, group_size))
+}
+
+/// Group ranked `results` by `source_path`, keeping the first `groups`
+/// documents and the first `group_size` chunks of each.
+fn group_results(results: Vec<SearchResult>, groups: usize, group_size: usize) -> Vec<SearchGroup> {
+    let mut grouped: Vec<SearchGroup> = Vec::new();
+    for result in results {
+        match grouped.iter().position(|g| g.key == result.source_path) {
+            Some(at) if grouped[at].hits.len() < group_size => grouped[at].hits.push(result),
+            Some(_) => {}
+            None if grouped.len() < groups => grouped.push(SearchGroup {
+                key: result.source_path.clone(),
+                hits: vec![result],
+            }),
+            None => {}
+        }
+    }
+    grouped
+}
+
+/// Hybrid search done entirely by Qdrant, for `lexical_backend =
+/// "qdrant-sparse"`: the dense and sparse vectors' top 30 each, fused by
+/// Reciprocal Rank Fusion with the same k as Tantivy's fusion.
+pub async fn hybrid_search(
+    config: &AppConfig,
+    query: &str,
+    query_embedding: &[f32],
+    limit: usize,
+    filters: &SearchFilters,
+    options: &SearchOptions,
+) -> Result<Vec<SearchResult>> {
+    let client = connect(config)?;
+    let layout = vector_layout(&client, config).await?;
+    if !layout.sparse {
+        anyhow::bail!(
+            "Collection '{}' has no sparse vectors; run `ragctl ingest --force` with \
+             `lexical_backend = \"qdrant-sparse\"` to add them",
+            config.collection_name
+        );
+    }
+    let vector = dense_vector(config, layout, options)?;
+    let (filter, check_prefix) = prefix_filter(filters, layout);
+    let terms = sparse::query_vector(query);
+
+    let mut dense = PrefetchQueryBuilder::default()
+        .query(Query::new_nearest(query_embedding.to_vec()))
+        .limit(30u64);
+    if let Some(vector) = vector {
+        dense = dense.using(vector);
+    }
+    if let Some(params) = search_params(config, options) {
+        dense = dense.params(params);
+    }
+    let mut lexical = PrefetchQueryBuilder::default()
+        .query(Query::new_nearest(VectorInput::new_sparse(
+            terms.indices,
+            terms.values,
+        )))
+        .using(sparse::SPARSE_VECTOR)
+        .limit(30u64);
+    if let Some(ref filter) = filter {
+        dense = dense.filter(filter.clone());
+        lexical = lexical.filter(filter.clone());
+    }
+    let builder = QueryPointsBuilder::new(&config.collection_name)
+        .add_prefetch(dense)
+        .add_prefetch(lexical)
+        .query(Query::new_rrf(RrfBuilder::with_k(60)))
+        .limit(fetch(limit, check_prefix) as u64)
+        .with_payload(true);
+
+    let response = match client.query(builder).await {
+        Ok(response) => response,
+        Err(e) => {
+            forget_vector_layout(config);
+            return Err(e.into());
+        }
+    };
+
+    Ok(response
+        .result
+        .iter()
+        .map(|point| to_search_result(point, query, options))
+        .filter(|r| filters.matches_path(&r.source_path))
+        .take(limit)
+        .collect())
+}
+
+/// The HNSW parameters of a search, `None` to leave them to Qdrant.
+fn search_params(config: &AppConfig, options: &SearchOptions) -> Option<SearchParams> {
+    let params = options.search_params.resolve(config.qdrant.search_params());
+    if params.is_default() {
+        return None;
+    }
+    let mut builder = SearchParamsBuilder::default();
+    if let Some(exact) = params.exact {
+        builder = builder.exact(exact);
+    }
+    if let Some(ef) = params.hnsw_ef {
+        builder = builder.hnsw_ef(ef);
+    }
+    Some(builder.build())
+}
+
+/// The dense vector to search: the title vector for `search_titles`, else
+/// the text vector (`None` for an unnamed one).
+fn dense_vector(
+    config: &AppConfig,
+    layout: VectorLayout,
+    options: &SearchOptions,
+) -> Result<Option<&'static str>> {
+    if options.search_titles {
+        if !layout.title {
+            anyhow::bail!(
+                "Collection '{}' has no title vectors; set `title_vector = true` under \
+                 [qdrant] and re-ingest",
+                config.collection_name
+            );
+        }
+        Ok(Some(TITLE_VECTOR))
+    } else {
+        Ok(layout.named.then_some(TEXT_VECTOR))
+    }
+}
+
+fn to_search_result(point: &ScoredPoint, query: &str, options: &SearchOptions) -> SearchResult {
+    let payload = &point.payload;
+    let text = get_str(payload, "text");
+    SearchResult {
+        chunk_id: get_str(payload, "chunk_id"),
+        score: point.score as f64,
+        title: get_str(payload, "title"),
+        source_path: get_str(payload, "source_path"),
+        source_type: get_str(payload, "source_type"),
+        section: Some(get_str(payload, "section")).filter(|s| !s.is_empty()),
+        location: get_location(payload),
+        file_mtime: get_mtime(payload),
+        snippet: mcp_hybrid_search_common::types::centered_snippet(
+            &text,
+            query,
+            options.snippet_length,
+        ),
+        text: options.include_text.then_some(text),
+        document_match_count: None,
+    }
+}
+
+/// Name of the vector of a chunk's text.
+const TEXT_VECTOR: &str = "text";
+
+/// Name of the optional vector of a chunk's title.
+const TITLE_VECTOR: &str = "title";
 
-    Ok(search_results)
+/// The vectors a collection has: named ones, possibly with a title vector,
+/// or, for collections created before named vectors, an unnamed one; and
+/// whether it has the sparse vector of `lexical_backend = "qdrant-sparse"`,
+/// and `path_prefixes` on every point (indexed once they all do).
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct VectorLayout {
+    named: bool,
+    title: bool,
+    sparse: bool,
+    path_prefixes: bool,
+    /// `source_path` is keyword-indexed, so counts can facet on it.
+    source_paths: bool,
+}
+
+/// Layouts read so far, by Qdrant URL and collection, so a search doesn't
+/// ask for the collection's info first. A failed search forgets its
+/// collection's, in case `ragctl reset` recreated it with another layout.
+static LAYOUTS: Mutex<Vec<(LayoutKey, VectorLayout)>> = Mutex::new(Vec::new());
+
+type LayoutKey = (String, String);
+
+fn layout_key(config: &AppConfig) -> LayoutKey {
+    (config.qdrant_url.clone(), config.collection_name.clone())
+}
+
+async fn vector_layout(client: &Qdrant, config: &AppConfig) -> Result<VectorLayout> {
+    let key = layout_key(config);
+    if let Some((_, layout)) = LAYOUTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .find(|(k, _)| *k == key)
+    {
+        return Ok(*layout);
+    }
+    let info = client
+        .collection_info(&config.collection_name)
+        .await?
+        .result;
+    let indexed = |field| {
+        info.as_ref()
+            .is_some_and(|r| r.payload_schema.contains_key(field))
+    };
+    let path_prefixes = indexed(PATH_PREFIXES);
+    let source_paths = indexed("source_path");
+    let params = info.and_then(|r| r.config).and_then(|c| c.params);
+    let sparse = params
+        .as_ref()
+        .and_then(|p| p.sparse_vectors_config.as_ref())
+        .is_some_and(|s| s.map.contains_key(sparse::SPARSE_VECTOR));
+    let layout = match params.and_then(|p| p.vectors_config).and_then(|v| v.config) {
+        Some(vectors_config::Config::ParamsMap(map)) => VectorLayout {
+            named: true,
+            title: map.map.contains_key(TITLE_VECTOR),
+            sparse,
+            path_prefixes,
+            source_paths,
+        },
+        _ => VectorLayout {
+            named: false,
+            title: false,
+            sparse,
+            path_prefixes,
+            source_paths,
+        },
+    };
+    let mut layouts = LAYOUTS.lock().unwrap_or_else(PoisonError::into_inner);
+    layouts.retain(|(k, _)| *k != key);
+    layouts.push((key, layout));
+    Ok(layout)
+}
+
+fn forget_vector_layout(config: &AppConfig) {
+    let key = layout_key(config);
+    LAYOUTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .retain(|(k, _)| *k != key);
 }
 
 pub async fn get_chunk(config: &AppConfig, chunk_id: &str) -> Result<Option<ChunkDetail>> {
-    let client = Qdrant::from_url(&config.qdrant_url).build()?;
+    let client = connect(config)?;
     let point_id: PointId = chunk_id.to_string().into();
 
     let response = client
         .get_points(GetPointsBuilder::new(&config.collection_name, &[point_id]).with_payload(true))
         .await?;
 
-    if let Some(point) = response.result.first() {
-        let payload = &point.payload;
-        Ok(Some(ChunkDetail {
-            chunk_id: get_str(payload, "chunk_id"),
-            text: get_str(payload, "text"),
-            metadata: ChunkMetadata {
-                title: get_str(payload, "title"),
-                source_path: get_str(payload, "source_path"),
-                source_type: get_str(payload, "source_type"),
-                chunk_index: get_str(payload, "chunk_index").parse().unwrap_or(0),
+    Ok(response
+        .result
+        .first()
+        .map(|point| to_chunk_detail(&point.payload)))
+}
+
+/// Fetch chunks of `source_path` with chunk_index in `[from, to]`, ordered by index.
+pub async fn get_chunk_range(
+    config: &AppConfig,
+    source_path: &str,
+    from: u32,
+    to: u32,
+) -> Result<Vec<ChunkDetail>> {
+    let client = connect(config)?;
+
+    let filter = Filter::must([
+        Condition::matches("source_path", source_path.to_string()),
+        Condition::range(
+            "chunk_index",
+            Range {
+                gte: Some(from as f64),
+                lte: Some(to as f64),
+                ..Default::default()
             },
-        }))
+        ),
+    ]);
+
+    let response = client
+        .scroll(
+            ScrollPointsBuilder::new(&config.collection_name)
+                .filter(filter)
+                .with_payload(true)
+                .limit(to.saturating_sub(from).saturating_add(1)),
+        )
+        .await?;
+
+    let mut chunks: Vec<ChunkDetail> = response
+        .result
+        .iter()
+        .map(|point| to_chunk_detail(&point.payload))
+        .collect();
+    chunks.sort_by_key(|c| c.metadata.chunk_index);
+    Ok(chunks)
+}
+
+/// All chunks of a document, ordered by chunk_index.
+pub async fn get_document_chunks(
+    config: &AppConfig,
+    source_path: &str,
+) -> Result<Vec<ChunkDetail>> {
+    let client = connect(config)?;
+    let filter = Filter::must([Condition::matches("source_path", source_path.to_string())]);
+
+    let mut chunks = Vec::new();
+    let mut offset: Option<PointId> = None;
+    loop {
+        let mut builder = ScrollPointsBuilder::new(&config.collection_name)
+            .filter(filter.clone())
+            .with_payload(true)
+            .limit(256);
+        if let Some(next_offset) = offset.take() {
+            builder = builder.offset(next_offset);
+        }
+
+        let response = client.scroll(builder).await?;
+        chunks.extend(
+            response
+                .result
+                .iter()
+                .map(|point| to_chunk_detail(&point.payload)),
+        );
+
+        offset = response.next_page_offset;
+        if offset.is_none() {
+            break;
+        }
+    }
+    chunks.sort_by_key(|c| c.metadata.chunk_index);
+    Ok(chunks)
+}
+
+fn to_chunk_detail(
+    payload: &std::collections::HashMap<String, qdrant_client::qdrant::Value>,
+) -> ChunkDetail {
+    let source_path = get_str(payload, "source_path");
+    let source_type = get_str(payload, "source_type");
+    // Pages ingested with `ragctl ingest --url` are keyed by their URL.
+    let url = (source_type == "url").then(|| source_path.clone());
+    ChunkDetail {
+        chunk_id: get_str(payload, "chunk_id"),
+        text: get_str(payload, "text"),
+        metadata: ChunkMetadata {
+            title: get_str(payload, "title"),
+            source_path,
+            source_type,
+            chunk_index: get_str(payload, "chunk_index").parse().unwrap_or(0),
+            url,
+            location: get_location(payload),
+            file_mtime: get_mtime(payload),
+            file_size_bytes: get_str(payload, "file_size_bytes").parse().ok(),
+            converter: Some(get_str(payload, "converter")).filter(|s| !s.is_empty()),
+            ocr: get_bool(payload, "ocr"),
+        },
+        neighbors: Vec::new(),
+    }
+}
+
+/// Exact number of points matching the payload filters, with the filter
+/// searches use; 0 when the collection doesn't exist.
+///
+/// A `path_prefix` Qdrant can't match exactly (see [`prefix_filter`]) is
+/// counted by document (see [`count_under_prefix`]), or checked
+/// client-side on the points scrolled when `source_path` isn't indexed.
+pub async fn count_points(config: &AppConfig, filters: &SearchFilters) -> Result<u64> {
+    let client = connect(config)?;
+    if !client.collection_exists(&config.collection_name).await? {
+        return Ok(0);
+    }
+    let layout = vector_layout(&client, config).await?;
+    let (filter, check_prefix) = prefix_filter(filters, layout);
+
+    if !check_prefix {
+        let mut builder = CountPointsBuilder::new(&config.collection_name).exact(true);
+        if let Some(filter) = filter {
+            builder = builder.filter(filter);
+        }
+        let response = client.count(builder).await?;
+        return Ok(response.result.map(|r| r.count).unwrap_or(0));
+    }
+    if layout.source_paths {
+        return count_under_prefix(&client, config, filter, filters).await;
+    }
+
+    let mut total = 0;
+    let mut offset: Option<PointId> = None;
+    loop {
+        let mut builder = ScrollPointsBuilder::new(&config.collection_name)
+            .with_payload(PayloadIncludeSelector::new(vec!["source_path".to_string()]))
+            .limit(1000);
+        if let Some(filter) = filter.clone() {
+            builder = builder.filter(filter);
+        }
+        if let Some(next_offset) = offset.take() {
+            builder = builder.offset(next_offset);
+        }
+
+        let response = client.scroll(builder).await?;
+        total += response
+            .result
+            .iter()
+            .filter(|p| filters.matches_path(&get_str(&p.payload, "source_path")))
+            .count() as u64;
+
+        offset = response.next_page_offset;
+        if offset.is_none() {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// [`payload_filter`] with `path_prefix`, which Qdrant has no prefix match
+/// for: the chunks' `path_prefixes` (the directories they're in) must hold
+/// the prefix's directory. That's exact for a prefix ending in a separator;
+/// the second value is whether the results still need checking against the
+/// prefix, when it's not, or the collection's points predate
+/// `path_prefixes`.
+fn prefix_filter(filters: &SearchFilters, layout: VectorLayout) -> (Option<Filter>, bool) {
+    let mut filter = payload_filter(filters);
+    let Some(ref prefix) = filters.path_prefix else {
+        return (filter, false);
+    };
+    let Some(directory) = path_prefix_directory(prefix).filter(|_| layout.path_prefixes) else {
+        return (filter, true);
+    };
+    filter
+        .get_or_insert_with(Filter::default)
+        .must
+        .push(Condition::matches(PATH_PREFIXES, directory.to_string()));
+    (filter, directory != prefix)
+}
+
+/// Build the Qdrant payload filter for the conditions Qdrant can evaluate.
+fn payload_filter(filters: &SearchFilters) -> Option<Filter> {
+    let mut conditions = Vec::new();
+
+    if let Some(ref source_type) = filters.source_type {
+        conditions.push(Condition::matches("source_type", source_type.clone()));
+    }
+    // A keyword match on a list field matches when any item equals it, so
+    // one condition per tag requires all of them.
+    for tag in &filters.tags {
+        conditions.push(Condition::matches("tags", tag.clone()));
+    }
+    if let Some(ref language) = filters.language {
+        conditions.push(Condition::matches("language", language.clone()));
+    }
+    // Only OCR chunks store `ocr`, so leaving them out excludes `true`
+    // rather than requiring `false`.
+    let mut excluded = Vec::new();
+    match filters.ocr {
+        Some(true) => conditions.push(Condition::matches("ocr", true)),
+        Some(false) => excluded.push(Condition::matches("ocr", true)),
+        None => {}
+    }
+    if let Some(after) = filters.modified_after {
+        conditions.push(Condition::datetime_range(
+            "file_mtime",
+            DatetimeRange {
+                gt: Some(Timestamp {
+                    seconds: after.timestamp(),
+                    nanos: after.timestamp_subsec_nanos() as i32,
+                }),
+                ..Default::default()
+            },
+        ));
+    }
+
+    if conditions.is_empty() && excluded.is_empty() {
+        None
     } else {
-        Ok(None)
+        Some(Filter {
+            must: conditions,
+            must_not: excluded,
+            ..Default::default()
+        })
     }
 }
 
+/// Check whether the configured collection exists.
+pub async fn collection_exists(config: &AppConfig) -> Result<bool> {
+    let client = connect(config)?;
+    Ok(client.collection_exists(&config.collection_name).await?)
+}
+
 /// Get the number of points in the collection.
 pub async fn get_collection_count(config: &AppConfig) -> Result<u64> {
-    let client = Qdrant::from_url(&config.qdrant_url).build()?;
+    let client = connect(config)?;
     let info = client.collection_info(&config.collection_name).await?;
     Ok(info
         .result
@@ -92,6 +691,98 @@ pub async fn get_collection_count(config: &AppConfig) -> Result<u64> {
         .unwrap_or(0))
 }
 
+/// The most recent `file_mtime` in the collection, or `None` when no chunk
+/// has one (or the collection predates the `file_mtime` index).
+pub async fn latest_file_mtime(config: &AppConfig) -> Result<Option<String>> {
+    let client = connect(config)?;
+    let builder = ScrollPointsBuilder::new(&config.collection_name)
+        .with_payload(PayloadIncludeSelector::new(vec!["file_mtime".to_string()]))
+        .order_by(OrderByBuilder::new("file_mtime").direction(Direction::Desc as i32))
+        .limit(1);
+    let response = client.scroll(builder).await?;
+    Ok(response
+        .result
+        .first()
+        .and_then(|point| get_mtime(&point.payload)))
+}
+
+/// Vector size of the collection, or `None` when it doesn't exist yet.
+pub async fn collection_dimension(config: &AppConfig) -> Result<Option<u64>> {
+    let client = connect(config)?;
+    if !client.collection_exists(&config.collection_name).await? {
+        return Ok(None);
+    }
+    let info = client.collection_info(&config.collection_name).await?;
+    Ok(info
+        .result
+        .and_then(|r| r.config)
+        .and_then(|c| c.params)
+        .and_then(|p| p.vectors_config)
+        .and_then(|v| v.config)
+        .and_then(|c| match c {
+            vectors_config::Config::Params(params) => Some(params.size),
+            vectors_config::Config::ParamsMap(map) => map.map.get(TEXT_VECTOR).map(|p| p.size),
+        }))
+}
+
+/// List all Qdrant collections with their point counts, calling
+/// `on_progress(done, total)` after each collection is inspected.
+pub async fn list_collections(
+    config: &AppConfig,
+    on_progress: impl Fn(u64, u64),
+) -> Result<Vec<(String, u64)>> {
+    let client = connect(config)?;
+    let response = client.list_collections().await?;
+    let total = response.collections.len() as u64;
+
+    let mut results = Vec::new();
+    for collection in &response.collections {
+        let name = &collection.name;
+        let count = match client.collection_info(name).await {
+            Ok(info) => info
+                .result
+                .map(|r| r.points_count.unwrap_or(0))
+                .unwrap_or(0),
+            Err(_) => 0,
+        };
+        results.push((name.clone(), count));
+        on_progress(results.len() as u64, total);
+    }
+
+    Ok(results)
+}
+
+/// The chunk location fields of a payload, unset when missing.
+fn get_location(
+    payload: &std::collections::HashMap<String, qdrant_client::qdrant::Value>,
+) -> ChunkLocation {
+    let number = |key| get_str(payload, key).parse().ok();
+    ChunkLocation {
+        start_char: number("start_char"),
+        end_char: number("end_char"),
+        start_line: number("start_line"),
+        converted: get_bool(payload, "converted"),
+    }
+}
+
+/// Whether a boolean payload field is set and true.
+fn get_bool(
+    payload: &std::collections::HashMap<String, qdrant_client::qdrant::Value>,
+    key: &str,
+) -> bool {
+    use qdrant_client::qdrant::value::Kind;
+    matches!(
+        payload.get(key).and_then(|v| v.kind.as_ref()),
+        Some(Kind::BoolValue(true))
+    )
+}
+
+fn get_mtime(
+    payload: &std::collections::HashMap<String, qdrant_client::qdrant::Value>,
+) -> Option<String> {
+    Some(get_str(payload, "file_mtime")).filter(|s| !s.is_empty())
+}
+
 fn get_str(
     payload: &std::collections::HashMap<String, qdrant_client::qdrant::Value>,
     key: &str,
@@ -112,3 +803,75 @@ fn get_str(
         })
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_result(id: &str, path: &str) -> SearchResult {
+        SearchResult {
+            chunk_id: id.to_string(),
+            score: 0.0,
+            title: String::new(),
+            source_path: path.to_string(),
+            source_type: "md".to_string(),
+            section: None,
+            location: Default::default(),
+            file_mtime: None,
+            snippet: String::new(),
+            text: None,
+            document_match_count: None,
+        }
+    }
+
+    #[test]
+    fn test_group_results() {
+        let results = vec![
+            make_result("a1", "/a.md"),
+            make_result("b1", "/b.md"),
+            make_result("a2", "/a.md"),
+            make_result("a3", "/a.md"),
+            make_result("c1", "/c.md"),
+            make_result("b2", "/b.md"),
+        ];
+        let groups = group_results(results, 2, 2);
+        let keys: Vec<&str> = groups.iter().map(|g| g.key.as_str()).collect();
+        assert_eq!(keys, ["/a.md", "/b.md"]);
+        let ids = |g: &SearchGroup| {
+            g.hits
+                .iter()
+                .map(|r| r.chunk_id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&groups[0]), ["a1", "a2"]);
+        assert_eq!(ids(&groups[1]), ["b1", "b2"]);
+    }
+
+    #[test]
+    fn test_prefix_filter() {
+        let layout = |path_prefixes| VectorLayout {
+            named: true,
+            title: false,
+            sparse: false,
+            path_prefixes,
+            source_paths: false,
+        };
+        let filters = |prefix: &str| SearchFilters {
+            path_prefix: Some(prefix.to_string()),
+            source_type: Some("md".to_string()),
+            ..SearchFilters::default()
+        };
+        let (filter, check) = prefix_filter(&filters("/docs/"), layout(true));
+        let must = filter.unwrap().must;
+        assert_eq!(must.len(), 2);
+        assert_eq!(
+            must[1],
+            Condition::matches(PATH_PREFIXES, "/docs/".to_string())
+        );
+        assert!(!check);
+        assert!(prefix_filter(&filters("/docs/ap"), layout(true)).1);
+        let (filter, check) = prefix_filter(&filters("/docs/"), layout(false));
+        assert_eq!(filter.unwrap().must.len(), 1);
+        assert!(check);
+    }
+}
@@ -1,10 +1,16 @@
 use anyhow::Result;
 use mcp_hybrid_search_common::config::AppConfig;
-use mcp_hybrid_search_common::types::{SearchFilters, SearchResult};
+use mcp_hybrid_search_common::types::{
+    centered_snippet, highlight_ranges, ChunkLocation, SearchFilters, SearchOptions, SearchResult,
+};
+use std::ops::Bound;
 use std::path::Path;
-use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
+use tantivy::collector::{Count, TopDocs};
+use tantivy::query::{
+    AllQuery, BooleanQuery, Occur, Query, QueryParser, RangeQuery, RegexQuery, TermQuery,
+};
 use tantivy::schema::*;
+use tantivy::snippet::SnippetGenerator;
 use tantivy::{Index, ReloadPolicy};
 
 /// Name used for the custom tokenizer when configured.
@@ -26,6 +32,16 @@ fn build_schema(tokenizer_name: &str) -> Schema {
     schema_builder.add_text_field("title", text_options.clone());
     schema_builder.add_text_field("body", text_options);
     schema_builder.add_text_field("source_type", STRING | STORED);
+    // Multi-valued; indexes created before tags existed don't have it.
+    schema_builder.add_text_field("tags", STRING | STORED);
+    schema_builder.add_text_field("section", STORED);
+    schema_builder.add_u64_field("start_char", STORED);
+    schema_builder.add_u64_field("end_char", STORED);
+    schema_builder.add_u64_field("start_line", STORED);
+    schema_builder.add_bool_field("converted", STORED);
+    schema_builder.add_date_field("file_mtime", INDEXED | STORED);
+    schema_builder.add_text_field("language", STRING | STORED);
+    schema_builder.add_bool_field("ocr", INDEXED | STORED);
     schema_builder.build()
 }
 
@@ -127,6 +143,7 @@ pub fn search(
     query_str: &str,
     top_k: usize,
     filters: &SearchFilters,
+    options: &SearchOptions,
 ) -> Result<Vec<SearchResult>> {
     let index = open_index(config)?;
     let schema = index.schema();
@@ -136,6 +153,11 @@ pub fn search(
     let title_field = schema.get_field("title").unwrap();
     let body_field = schema.get_field("body").unwrap();
     let source_type_field = schema.get_field("source_type").unwrap();
+    let tags_field = schema.get_field("tags").ok();
+    let section_field = schema.get_field("section").ok();
+    let file_mtime_field = schema.get_field("file_mtime").ok();
+    let language_field = schema.get_field("language").ok();
+    let ocr_field = schema.get_field("ocr").ok();
 
     let reader = index
         .reader_builder()
@@ -148,6 +170,9 @@ pub fn search(
 
     let top_docs = searcher.search(&query, &TopDocs::with_limit(top_k))?;
 
+    let mut snippet_generator = SnippetGenerator::create(&searcher, &*query, body_field)?;
+    snippet_generator.set_max_num_chars(options.snippet_length);
+
     let mut results = Vec::new();
 
     for (score, doc_address) in top_docs {
@@ -158,6 +183,9 @@ pub fn search(
         let title = get_text(&doc, title_field);
         let body = get_text(&doc, body_field);
         let source_type = get_text(&doc, source_type_field);
+        let section = section_field
+            .map(|field| get_text(&doc, field))
+            .filter(|s| !s.is_empty());
 
         if let Some(ref ft) = filters.source_type {
             if &source_type != ft {
@@ -169,8 +197,32 @@ pub fn search(
                 continue;
             }
         }
+        let tags = tags_field
+            .into_iter()
+            .flat_map(|field| doc.get_all(field).filter_map(|v| v.as_str()));
+        if !filters.matches_tags(tags) {
+            continue;
+        }
+        if let Some(ref language) = filters.language {
+            let doc_language = language_field.map(|field| get_text(&doc, field));
+            if doc_language.as_ref() != Some(language) {
+                continue;
+            }
+        }
+        if filters
+            .ocr
+            .is_some_and(|ocr| ocr != is_ocr(&doc, ocr_field))
+        {
+            continue;
+        }
+        let file_mtime = get_mtime(&doc, file_mtime_field);
+        if let Some(after) = filters.modified_after {
+            if file_mtime.is_none_or(|mtime| mtime <= after) {
+                continue;
+            }
+        }
 
-        let snippet = mcp_hybrid_search_common::types::truncate_snippet(&body, 200);
+        let snippet = build_snippet(&snippet_generator, &body, query_str, config, options);
 
         results.push(SearchResult {
             chunk_id,
@@ -178,16 +230,435 @@ pub fn search(
             title,
             source_path,
             source_type,
+            section,
+            location: get_location(&doc, &schema),
+            file_mtime: file_mtime.map(|mtime| mtime.to_rfc3339()),
             snippet,
+            text: options.include_text.then_some(body),
+            document_match_count: None,
         });
     }
 
     Ok(results)
 }
 
+/// Count documents matching `query_str` (or all documents when `None`)
+/// combined with the filters; 0 when there is no index yet.
+pub fn count(config: &AppConfig, query_str: Option<&str>, filters: &SearchFilters) -> Result<u64> {
+    if !Path::new(&config.tantivy_index_dir).exists() {
+        return Ok(0);
+    }
+    let index = open_index(config)?;
+    let schema = index.schema();
+
+    let source_path_field = schema.get_field("source_path").unwrap();
+    let title_field = schema.get_field("title").unwrap();
+    let body_field = schema.get_field("body").unwrap();
+    let source_type_field = schema.get_field("source_type").unwrap();
+
+    let reader = index
+        .reader_builder()
+        .reload_policy(ReloadPolicy::OnCommitWithDelay)
+        .try_into()?;
+    let searcher = reader.searcher();
+
+    let base: Box<dyn Query> = match query_str {
+        Some(q) => QueryParser::for_index(&index, vec![title_field, body_field]).parse_query(q)?,
+        None => Box::new(AllQuery),
+    };
+
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Must, base)];
+    if let Some(ref ft) = filters.source_type {
+        let term = tantivy::Term::from_field_text(source_type_field, ft);
+        clauses.push((
+            Occur::Must,
+            Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+        ));
+    }
+    if let Some(ref prefix) = filters.path_prefix {
+        let pattern = format!("{}.*", escape_regex(prefix));
+        clauses.push((
+            Occur::Must,
+            Box::new(RegexQuery::from_pattern(&pattern, source_path_field)?),
+        ));
+    }
+    if !filters.tags.is_empty() {
+        // An index without the tags field has no tagged documents.
+        let Ok(tags_field) = schema.get_field("tags") else {
+            return Ok(0);
+        };
+        for tag in &filters.tags {
+            let term = tantivy::Term::from_field_text(tags_field, tag);
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+            ));
+        }
+    }
+    if let Some(ref language) = filters.language {
+        // Nor does one without the language field have detected languages.
+        let Ok(language_field) = schema.get_field("language") else {
+            return Ok(0);
+        };
+        let term = tantivy::Term::from_field_text(language_field, language);
+        clauses.push((
+            Occur::Must,
+            Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+        ));
+    }
+    if let Some(ocr) = filters.ocr {
+        // Nor OCR text one without the ocr field, which only OCR chunks set.
+        match schema.get_field("ocr") {
+            Ok(ocr_field) => {
+                let term = tantivy::Term::from_field_bool(ocr_field, true);
+                let occur = if ocr { Occur::Must } else { Occur::MustNot };
+                clauses.push((
+                    occur,
+                    Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+                ));
+            }
+            Err(_) if ocr => return Ok(0),
+            Err(_) => {}
+        }
+    }
+    if let Some(after) = filters.modified_after {
+        // Nor does an index without file_mtime have dated documents.
+        let Ok(file_mtime_field) = schema.get_field("file_mtime") else {
+            return Ok(0);
+        };
+        let Some(nanos) = after.timestamp_nanos_opt() else {
+            return Ok(0);
+        };
+        // Indexed dates are truncated to the second, so this compares seconds.
+        let lower = tantivy::Term::from_field_date(
+            file_mtime_field,
+            tantivy::DateTime::from_timestamp_nanos(nanos),
+        );
+        clauses.push((
+            Occur::Must,
+            Box::new(RangeQuery::new(Bound::Excluded(lower), Bound::Unbounded)),
+        ));
+    }
+
+    let count = searcher.search(&BooleanQuery::new(clauses), &Count)?;
+    Ok(count as u64)
+}
+
+/// Escape regex metacharacters so a literal string can be used in a RegexQuery.
+fn escape_regex(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if "\\.+*?()|[]{}^$#&-~".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Check that `config.tokenizer` is available in this build and, when the
+/// index already exists, matches the tokenizer it was built with. All lindera
+/// languages share one tokenizer name, so switching between them is not
+/// detected here.
+pub fn check_tokenizer(config: &AppConfig) -> Result<()> {
+    let expected = resolve_tokenizer_name(config);
+    let path = Path::new(&config.tantivy_index_dir);
+    if !path.join("meta.json").exists() {
+        let index = Index::create_in_ram(build_schema(expected));
+        return register_tokenizer(&index, config);
+    }
+
+    let index = Index::open_in_dir(path)?;
+    register_tokenizer(&index, config)?;
+    let schema = index.schema();
+    let body = schema.get_field("body")?;
+    let indexed = match schema.get_field_entry(body).field_type() {
+        FieldType::Str(options) => options
+            .get_indexing_options()
+            .map(|o| o.tokenizer().to_string()),
+        _ => None,
+    };
+    match indexed {
+        Some(name) if name == expected => Ok(()),
+        other => anyhow::bail!(
+            "tokenizer = \"{}\" does not match the index at {} (built with {}); \
+             reset and re-ingest to change it",
+            config.tokenizer,
+            config.tantivy_index_dir,
+            other.as_deref().unwrap_or("no tokenizer")
+        ),
+    }
+}
+
+/// Get the number of documents in the index without creating it.
+pub fn get_index_count(config: &AppConfig) -> Result<u64> {
+    let path = Path::new(&config.tantivy_index_dir);
+    if !path.exists() {
+        anyhow::bail!("index not found at {}", config.tantivy_index_dir);
+    }
+    let index = open_index(config)?;
+    let reader = index
+        .reader_builder()
+        .reload_policy(ReloadPolicy::OnCommitWithDelay)
+        .try_into()?;
+    Ok(reader.searcher().num_docs())
+}
+
+/// Build a snippet centered on the matched terms, optionally wrapping them in
+/// the configured highlight markers. Falls back to simple centering when the
+/// match is only in the title.
+fn build_snippet(
+    generator: &SnippetGenerator,
+    body: &str,
+    query_str: &str,
+    config: &AppConfig,
+    options: &SearchOptions,
+) -> String {
+    let snippet = generator.snippet(body);
+    if snippet.is_empty() {
+        return centered_snippet(body, query_str, options.snippet_length);
+    }
+    if options.highlight {
+        highlight_ranges(
+            snippet.fragment(),
+            snippet.highlighted(),
+            &config.highlight_pre_tag,
+            &config.highlight_post_tag,
+        )
+    } else {
+        snippet.fragment().to_string()
+    }
+}
+
+/// Whether the chunk's text came from OCR.
+fn is_ocr(doc: &tantivy::TantivyDocument, field: Option<Field>) -> bool {
+    field
+        .and_then(|field| doc.get_first(field)?.as_bool())
+        .unwrap_or(false)
+}
+
+/// The chunk's location; unset in indexes created before locations were
+/// recorded.
+fn get_location(doc: &tantivy::TantivyDocument, schema: &Schema) -> ChunkLocation {
+    let value = |name| doc.get_first(schema.get_field(name).ok()?);
+    let number = |name| value(name)?.as_u64()?.try_into().ok();
+    ChunkLocation {
+        start_char: number("start_char"),
+        end_char: number("end_char"),
+        start_line: number("start_line"),
+        converted: value("converted")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+    }
+}
+
+/// When the chunk's file was last modified; unset in indexes created before
+/// modification times were recorded.
+fn get_mtime(
+    doc: &tantivy::TantivyDocument,
+    field: Option<Field>,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    let mtime = doc.get_first(field?)?.as_datetime()?;
+    Some(chrono::DateTime::from_timestamp_nanos(
+        mtime.into_timestamp_nanos(),
+    ))
+}
+
 fn get_text(doc: &tantivy::TantivyDocument, field: Field) -> String {
     doc.get_first(field)
         .and_then(|v| v.as_str())
         .unwrap_or("")
         .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::doc;
+
+    fn test_config(dir: &Path) -> AppConfig {
+        AppConfig {
+            tantivy_index_dir: dir.to_string_lossy().to_string(),
+            ..AppConfig::default()
+        }
+    }
+
+    fn write_docs(config: &AppConfig) {
+        let index = open_index(config).unwrap();
+        let schema = index.schema();
+        let chunk_id = schema.get_field("chunk_id").unwrap();
+        let source_path = schema.get_field("source_path").unwrap();
+        let title = schema.get_field("title").unwrap();
+        let body = schema.get_field("body").unwrap();
+        let source_type = schema.get_field("source_type").unwrap();
+        let tags = schema.get_field("tags").unwrap();
+        let start_line = schema.get_field("start_line").unwrap();
+        let file_mtime = schema.get_field("file_mtime").unwrap();
+        let language = schema.get_field("language").unwrap();
+        let ocr = schema.get_field("ocr").unwrap();
+        let date = |s| {
+            let nanos = chrono::DateTime::parse_from_rfc3339(s)
+                .unwrap()
+                .timestamp_nanos_opt()
+                .unwrap();
+            tantivy::DateTime::from_timestamp_nanos(nanos)
+        };
+
+        let mut writer: tantivy::IndexWriter = index.writer(15_000_000).unwrap();
+        for (id, path, ty, text, doc_tags) in [
+            (
+                "1",
+                "/docs/a.md",
+                "md",
+                "billing invoices",
+                &["billing", "finance"][..],
+            ),
+            (
+                "2",
+                "/docs/b.pdf",
+                "pdf",
+                "billing refunds",
+                &["billing"][..],
+            ),
+            ("3", "/other/c.pdf", "pdf", "shipping", &[][..]),
+        ] {
+            let mut document = doc!(
+                chunk_id => id,
+                source_path => path,
+                title => "t",
+                body => text,
+                source_type => ty,
+            );
+            for tag in doc_tags {
+                document.add_text(tags, tag);
+            }
+            document.add_text(language, if id == "3" { "ja" } else { "en" });
+            match id {
+                "1" => {
+                    document.add_u64(start_line, 3);
+                    document.add_date(file_mtime, date("2026-01-02T10:00:00Z"));
+                }
+                "2" => {
+                    document.add_date(file_mtime, date("2025-06-01T00:00:00Z"));
+                    document.add_bool(ocr, true);
+                }
+                _ => {}
+            }
+            writer.add_document(document).unwrap();
+        }
+        writer.commit().unwrap();
+    }
+
+    #[test]
+    fn test_count_without_index() {
+        let dir = std::env::temp_dir().join(format!("tantivy-count-{}", uuid::Uuid::new_v4()));
+        let config = test_config(&dir);
+        let none = SearchFilters::default();
+        assert_eq!(count(&config, None, &none).unwrap(), 0);
+        assert!(!Path::new(&config.tantivy_index_dir).exists());
+    }
+
+    #[test]
+    fn test_count_by_query_and_filters() {
+        let dir = std::env::temp_dir().join(format!("tantivy-count-{}", uuid::Uuid::new_v4()));
+        let config = test_config(&dir);
+        write_docs(&config);
+
+        let none = SearchFilters::default();
+        assert_eq!(count(&config, None, &none).unwrap(), 3);
+        assert_eq!(count(&config, Some("billing"), &none).unwrap(), 2);
+
+        let pdf = SearchFilters {
+            source_type: Some("pdf".to_string()),
+            ..SearchFilters::default()
+        };
+        assert_eq!(count(&config, Some("billing"), &pdf).unwrap(), 1);
+        assert_eq!(count(&config, None, &pdf).unwrap(), 2);
+
+        let docs = SearchFilters {
+            path_prefix: Some("/docs/".to_string()),
+            ..SearchFilters::default()
+        };
+        assert_eq!(count(&config, None, &docs).unwrap(), 2);
+
+        let tags = |tags: &[&str]| SearchFilters {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..SearchFilters::default()
+        };
+        assert_eq!(count(&config, None, &tags(&["billing"])).unwrap(), 2);
+        assert_eq!(
+            count(&config, None, &tags(&["billing", "finance"])).unwrap(),
+            1
+        );
+        assert_eq!(count(&config, None, &tags(&["missing"])).unwrap(), 0);
+
+        let options = SearchOptions::default();
+        let results = search(&config, "billing", 10, &tags(&["finance"]), &options).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_id, "1");
+        assert_eq!(results[0].location.start_line, Some(3));
+        assert_eq!(results[0].location.start_char, None);
+
+        let recent = SearchFilters {
+            modified_after: Some("2026-01-01T00:00:00Z".parse().unwrap()),
+            ..SearchFilters::default()
+        };
+        assert_eq!(count(&config, None, &recent).unwrap(), 1);
+
+        let japanese = SearchFilters {
+            language: Some("ja".to_string()),
+            ..SearchFilters::default()
+        };
+        assert_eq!(count(&config, None, &japanese).unwrap(), 1);
+        assert!(search(&config, "billing", 10, &japanese, &options)
+            .unwrap()
+            .is_empty());
+        let results = search(&config, "billing", 10, &recent, &options).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].file_mtime.as_deref(),
+            Some("2026-01-02T10:00:00+00:00")
+        );
+
+        let ocr = |ocr| SearchFilters {
+            ocr: Some(ocr),
+            ..SearchFilters::default()
+        };
+        assert_eq!(count(&config, None, &ocr(true)).unwrap(), 1);
+        assert_eq!(count(&config, Some("billing"), &ocr(false)).unwrap(), 1);
+        let results = search(&config, "billing", 10, &ocr(false), &options).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk_id, "1");
+        let results = search(&config, "billing", 10, &ocr(true), &options).unwrap();
+        assert_eq!(results[0].chunk_id, "2");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_check_tokenizer() {
+        let dir = std::env::temp_dir().join(format!("tantivy-tokenizer-{}", uuid::Uuid::new_v4()));
+        let config = test_config(&dir);
+        check_tokenizer(&config).unwrap();
+        let unknown = AppConfig {
+            tokenizer: "klingon".to_string(),
+            ..config.clone()
+        };
+        assert!(check_tokenizer(&unknown).is_err());
+
+        write_docs(&config);
+        check_tokenizer(&config).unwrap();
+        let japanese = AppConfig {
+            tokenizer: "japanese".to_string(),
+            ..config
+        };
+        assert!(check_tokenizer(&japanese).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_escape_regex() {
+        assert_eq!(escape_regex("/a.b/c+d"), "/a\\.b/c\\+d");
+    }
+}
@@ -0,0 +1,148 @@
+//! Maintenance endpoints, authenticated with `Authorization: Bearer
+//! <admin_token>`. They answer 404 while `admin_token` is unset.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use super::http::AppState;
+use super::rest::error_response;
+use crate::reload;
+
+/// `POST /admin/reload` — reload the config file and return the effective
+/// config, with secrets redacted.
+pub(super) async fn reload_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Response {
+    let token = state.current_config().admin_token.clone();
+    let Some(token) = token.filter(|t| !t.is_empty()) else {
+        return error_response(
+            StatusCode::NOT_FOUND,
+            "Admin API disabled (set admin_token)",
+        );
+    };
+    if !authorized(&headers, &token) {
+        let mut response = error_response(StatusCode::UNAUTHORIZED, "Invalid admin token");
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, "Bearer".parse().unwrap());
+        return response;
+    }
+
+    match state.reloader.reload(&state.mcp_server).await {
+        Ok(reloaded) => Json(json!({
+            "config": reload::redacted(&reloaded.config),
+            "ignored": reloaded.ignored,
+        }))
+        .into_response(),
+        Err(e) => {
+            tracing::warn!("Config reload failed: {:#}", e);
+            error_response(StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e))
+        }
+    }
+}
+
+fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+}
+
+/// Compare without stopping at the first mismatch, so response timing
+/// doesn't reveal how much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::server::McpServer;
+    use crate::reload::Reloader;
+    use crate::transport::http::spawn_test_app;
+    use mcp_hybrid_search_common::config::AppConfig;
+    use serde_json::Value;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+    }
+
+    #[tokio::test]
+    async fn test_reload_endpoint() {
+        let dir = std::env::temp_dir().join(format!("admin-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let index_dir = dir.join("tantivy").to_string_lossy().to_string();
+        let write_config = |extra: &str| {
+            let toml = format!(
+                "tantivy_index_dir = {:?}\nadmin_token = \"s3cret\"\n{}",
+                index_dir, extra
+            );
+            std::fs::write(&path, toml).unwrap();
+        };
+        write_config("");
+
+        let config = AppConfig::load(path.to_str()).unwrap();
+        let server = McpServer::new(config.clone()).await.unwrap();
+        let mut state = AppState::new(config, server).unwrap();
+        state.reloader =
+            Reloader::new(Some(path.to_string_lossy().to_string()), Default::default());
+        let base = spawn_test_app(state).await;
+        let client = reqwest::Client::new();
+        let url = format!("{}/admin/reload", base);
+
+        let resp = client.post(&url).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = client.post(&url).bearer_auth("wrong").send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        write_config("tool_timeout_secs = 5\nlisten_addr = \"0.0.0.0\"\n");
+        let resp = client
+            .post(&url)
+            .bearer_auth("s3cret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["config"]["tool_timeout_secs"], 5);
+        assert_eq!(body["config"]["listen_addr"], "127.0.0.1");
+        assert_eq!(body["config"]["admin_token"], "[redacted]");
+        assert_eq!(body["ignored"], json!(["listen_addr"]));
+
+        write_config("embedding_dimension = 0\n");
+        let resp = client
+            .post(&url)
+            .bearer_auth("s3cret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_admin_disabled_without_token() {
+        let base = crate::transport::http::spawn_test_server(AppConfig::default()).await;
+        let resp = reqwest::Client::new()
+            .post(format!("{}/admin/reload", base))
+            .bearer_auth("anything")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,509 @@
+//! HTTP server hosting the Streamable HTTP endpoint (`/mcp`) and, when
+//! `legacy_sse` is enabled, the older `/sse` + `/message` pair.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::{HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use mcp_hybrid_search_common::config::AppConfig;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::RwLock;
+use tokio_rustls::TlsAcceptor;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::trace::TraceLayer;
+
+use super::rest::{self, SearchBackend};
+#[cfg(unix)]
+use super::unix;
+use super::{admin, sse, streamable_http, tls};
+use crate::health::{self, HealthChecker, Readiness};
+use crate::mcp::server::McpServer;
+use crate::mcp::session::Session;
+use crate::reload::Reloader;
+use crate::search::HybridSearcher;
+
+pub type SessionId = String;
+pub type Sessions = Arc<RwLock<HashMap<SessionId, Arc<Session>>>>;
+pub type StreamableSessions =
+    Arc<RwLock<HashMap<SessionId, Arc<streamable_http::StreamableSession>>>>;
+
+pub struct AppState {
+    /// The config the server started with, which decides the routes and
+    /// listeners. Request handlers read [`AppState::current_config`] instead
+    /// so they pick up reloads.
+    pub config: AppConfig,
+    pub mcp_server: Arc<McpServer>,
+    /// Searcher backing the REST API.
+    pub searcher: Arc<dyn SearchBackend>,
+    /// Sessions opened through the legacy `/sse` endpoint.
+    pub sessions: Sessions,
+    /// Sessions opened through `initialize` on `/mcp`.
+    pub streamable_sessions: StreamableSessions,
+    pub health: HealthChecker,
+    pub readiness: Readiness,
+    pub reloader: Reloader,
+}
+
+impl AppState {
+    pub fn new(config: AppConfig, mcp_server: McpServer) -> anyhow::Result<Self> {
+        let searcher = Arc::new(HybridSearcher::new(&config)?);
+        Ok(Self::with_searcher(config, mcp_server, searcher))
+    }
+
+    pub fn with_searcher(
+        config: AppConfig,
+        mcp_server: McpServer,
+        searcher: Arc<dyn SearchBackend>,
+    ) -> Self {
+        Self {
+            searcher,
+            config,
+            mcp_server: Arc::new(mcp_server),
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            streamable_sessions: Arc::new(RwLock::new(HashMap::new())),
+            health: HealthChecker::default(),
+            readiness: Readiness::default(),
+            reloader: Reloader::default(),
+        }
+    }
+
+    /// The config as of the last reload.
+    pub fn current_config(&self) -> Arc<AppConfig> {
+        self.mcp_server.config()
+    }
+
+    /// Whether `max_sessions` sessions (of either transport) are already open.
+    pub async fn at_session_limit(&self) -> bool {
+        let legacy = self.sessions.read().await.len();
+        let streamable = self.streamable_sessions.read().await.len();
+        legacy + streamable >= self.current_config().max_sessions
+    }
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    let mut app = Router::new().route(
+        "/mcp",
+        post(streamable_http::post_handler)
+            .get(streamable_http::get_handler)
+            .delete(streamable_http::delete_handler),
+    );
+    if state.config.legacy_sse {
+        app = app
+            .route("/sse", get(sse::sse_handler))
+            .route("/message", post(sse::message_handler));
+    }
+    if state.config.rest_api_enabled {
+        app = app
+            .route("/search", get(rest::search_handler))
+            .route("/chunks/:chunk_id", get(rest::chunk_handler))
+            .route("/documents", get(rest::document_handler));
+    }
+
+    app = app
+        .route("/health", get(health_handler))
+        .route("/health/live", get(live_handler))
+        .route("/health/ready", get(ready_handler))
+        .route("/admin/reload", post(admin::reload_handler))
+        .layer(TraceLayer::new_for_http());
+    if let Some(cors) = cors_layer(&state.config.cors_allowed_origins) {
+        app = app.layer(cors);
+    }
+    app.with_state(state)
+}
+
+/// Build the CORS layer for `cors_allowed_origins`. No origins means no CORS
+/// headers at all (browsers then only allow same-origin use) and `"*"`
+/// allows any origin. Other entries match exactly or, in the form
+/// `https://*.example.com`, any subdomain.
+fn cors_layer(origins: &[String]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
+    if origins.iter().any(|o| o == "*") {
+        return Some(CorsLayer::permissive());
+    }
+
+    let origins = origins.to_vec();
+    Some(
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::predicate(move |origin, _| {
+                origin
+                    .to_str()
+                    .is_ok_and(|origin| origins.iter().any(|p| origin_matches(p, origin)))
+            }))
+            .allow_methods(Any)
+            .allow_headers(Any)
+            .expose_headers([HeaderName::from_static(streamable_http::SESSION_HEADER)]),
+    )
+}
+
+fn origin_matches(pattern: &str, origin: &str) -> bool {
+    match pattern.split_once("*.") {
+        Some((scheme, domain)) => origin
+            .strip_prefix(scheme)
+            .and_then(|rest| rest.strip_suffix(domain))
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => pattern == origin,
+    }
+}
+
+/// Bind to `listen_addr:listen_port`, or to `listen_socket` when set, and
+/// serve until the process is stopped.
+pub async fn run(
+    config: AppConfig,
+    mcp_server: McpServer,
+    reloader: Reloader,
+) -> anyhow::Result<()> {
+    let socket = config.listen_socket.clone();
+    let tcp_addr = if socket.is_none() || config.listen_socket_with_tcp {
+        Some(config.listen_socket_addr()?)
+    } else {
+        None
+    };
+    let tls = tls::acceptor(&config)?;
+    let mut state = AppState::new(config, mcp_server)?;
+    state.reloader = reloader;
+    let state = Arc::new(state);
+    spawn_session_reaper(state.clone());
+    spawn_readiness_probe(state.clone());
+    #[cfg(unix)]
+    spawn_reload_on_sighup(state.clone());
+    let app = router(state);
+
+    let tcp_server = async {
+        match tcp_addr {
+            Some(addr) => serve_tcp(addr, tls, app.clone()).await,
+            None => Ok(()),
+        }
+    };
+    let unix_server = async {
+        match &socket {
+            Some(path) => serve_unix(path, app.clone()).await,
+            None => Ok(()),
+        }
+    };
+    tokio::try_join!(tcp_server, unix_server)?;
+
+    Ok(())
+}
+
+async fn serve_tcp(addr: SocketAddr, tls: Option<TlsAcceptor>, app: Router) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    match tls {
+        Some(acceptor) => {
+            tracing::info!("MCP server starting on https://{}", addr);
+            tls::serve(listener, acceptor, app).await
+        }
+        None => {
+            tracing::info!("MCP server starting on http://{}", addr);
+            Ok(axum::serve(listener, app).await?)
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn serve_unix(path: &str, app: Router) -> anyhow::Result<()> {
+    let listener = unix::bind(Path::new(path))?;
+    tracing::info!("MCP server starting on unix:{}", path);
+    unix::serve(listener, app).await
+}
+
+#[cfg(not(unix))]
+async fn serve_unix(_path: &str, _app: Router) -> anyhow::Result<()> {
+    anyhow::bail!("listen_socket is only supported on Unix")
+}
+
+/// Serve HTTP/1 or HTTP/2 on an accepted connection, for listeners that
+/// `axum::serve` can't drive (TLS, unix sockets).
+pub(super) async fn serve_connection<IO>(io: IO, app: Router, peer: impl std::fmt::Display)
+where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = TowerToHyperService::new(app);
+    if let Err(e) = auto::Builder::new(TokioExecutor::new())
+        .serve_connection_with_upgrades(TokioIo::new(io), service)
+        .await
+    {
+        tracing::debug!("Connection from {} closed with error: {}", peer, e);
+    }
+}
+
+/// Keep `state.readiness` up to date, probing the backends right away and
+/// then every [`health::READINESS_INTERVAL`].
+fn spawn_readiness_probe(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(health::READINESS_INTERVAL);
+        loop {
+            ticker.tick().await;
+            state.readiness.probe(&state.current_config()).await;
+        }
+    });
+}
+
+/// Reload the config whenever the process receives SIGHUP.
+#[cfg(unix)]
+fn spawn_reload_on_sighup(state: Arc<AppState>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            tracing::warn!("Config reload on SIGHUP unavailable: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            tracing::info!("SIGHUP received, reloading config");
+            if let Err(e) = state.reloader.reload(&state.mcp_server).await {
+                tracing::error!("Config reload failed: {:#}", e);
+            }
+        }
+    });
+}
+
+/// Periodically close sessions idle for longer than `session_timeout_secs`.
+fn spawn_session_reaper(state: Arc<AppState>) {
+    let timeout = Duration::from_secs(state.config.session_timeout_secs);
+    if timeout.is_zero() {
+        return;
+    }
+    let period = (timeout / 2).clamp(Duration::from_secs(1), Duration::from_secs(60));
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            reap_idle_sessions(&state, timeout).await;
+        }
+    });
+}
+
+async fn reap_idle_sessions(state: &AppState, timeout: Duration) {
+    state.sessions.write().await.retain(|id, session| {
+        let idle = session.idle_time() >= timeout;
+        if idle {
+            tracing::info!("Closing idle SSE session: {}", id);
+            session.close();
+        }
+        !idle
+    });
+    state.streamable_sessions.write().await.retain(|id, owner| {
+        let idle = owner.session().idle_time() >= timeout;
+        if idle {
+            tracing::info!("Closing idle Streamable HTTP session: {}", id);
+            owner.session().close();
+        }
+        !idle
+    });
+}
+
+/// Serve the router with default backends on an ephemeral local port,
+/// returning its base URL.
+#[cfg(test)]
+pub(super) async fn spawn_test_server(config: AppConfig) -> String {
+    let server = McpServer::new(config.clone()).await.unwrap();
+    spawn_test_app(AppState::new(config, server).unwrap()).await
+}
+
+#[cfg(test)]
+pub(super) async fn spawn_test_app(state: AppState) -> String {
+    let app = router(Arc::new(state));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+/// Probe Qdrant, Tantivy and the embedding provider; 503 when any is unusable.
+async fn health_handler(State(state): State<Arc<AppState>>) -> Response {
+    let report = state.health.check(&state.current_config()).await;
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report.body)).into_response()
+}
+
+/// Static liveness check that never touches the backends.
+async fn live_handler() -> &'static str {
+    "ok"
+}
+
+/// 200 once Qdrant and Tantivy have answered the readiness probe, 503 before
+/// that and after repeated failures.
+async fn ready_handler(State(state): State<Arc<AppState>>) -> Response {
+    let status = if state.readiness.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(state.readiness.body())).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    async fn test_state(config: AppConfig) -> AppState {
+        let server = McpServer::new(config.clone()).await.unwrap();
+        AppState::new(config, server).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reap_idle_sessions() {
+        let state = test_state(AppConfig::default()).await;
+        let (tx, _rx) = mpsc::channel(1);
+        let session = Arc::new(Session::new("idle", tx));
+        state
+            .sessions
+            .write()
+            .await
+            .insert("idle".to_string(), session.clone());
+
+        reap_idle_sessions(&state, Duration::from_secs(60)).await;
+        assert_eq!(state.sessions.read().await.len(), 1);
+
+        reap_idle_sessions(&state, Duration::ZERO).await;
+        assert!(state.sessions.read().await.is_empty());
+        assert!(session.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_health_endpoints() {
+        let config = AppConfig {
+            qdrant_url: "http://127.0.0.1:1".to_string(),
+            tantivy_index_dir: "/nonexistent/tantivy".to_string(),
+            ..AppConfig::default()
+        };
+        let base = spawn_test_server(config).await;
+
+        let resp = reqwest::get(format!("{}/health", base)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["checks"]["qdrant"]["status"], "error");
+
+        let resp = reqwest::get(format!("{}/health/live", base)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // No probe has succeeded yet.
+        let resp = reqwest::get(format!("{}/health/ready", base))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["status"], "not_ready");
+        assert_eq!(body["checks"]["tantivy"]["up"], false);
+    }
+
+    #[test]
+    fn test_origin_matches() {
+        assert!(origin_matches(
+            "https://app.example.com",
+            "https://app.example.com"
+        ));
+        assert!(!origin_matches(
+            "https://app.example.com",
+            "http://app.example.com"
+        ));
+        assert!(origin_matches(
+            "https://*.example.com",
+            "https://api.example.com"
+        ));
+        assert!(origin_matches(
+            "https://*.example.com",
+            "https://a.b.example.com"
+        ));
+        assert!(!origin_matches(
+            "https://*.example.com",
+            "https://example.com"
+        ));
+        assert!(!origin_matches(
+            "https://*.example.com",
+            "https://evilexample.com"
+        ));
+        assert!(!origin_matches(
+            "https://*.example.com",
+            "http://api.example.com"
+        ));
+    }
+
+    async fn allow_origin_header(origins: &[&str], origin: &str) -> Option<String> {
+        let config = AppConfig {
+            cors_allowed_origins: origins.iter().map(|o| o.to_string()).collect(),
+            ..AppConfig::default()
+        };
+        let base = spawn_test_server(config).await;
+        let resp = reqwest::Client::new()
+            .get(format!("{}/health/live", base))
+            .header("Origin", origin)
+            .send()
+            .await
+            .unwrap();
+        resp.headers()
+            .get("access-control-allow-origin")
+            .map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_cors_same_origin_by_default() {
+        assert_eq!(allow_origin_header(&[], "https://evil.test").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_cors_allowed_and_disallowed_origins() {
+        let origins = ["https://app.example.com", "https://*.corp.example"];
+        assert_eq!(
+            allow_origin_header(&origins, "https://app.example.com").await,
+            Some("https://app.example.com".to_string())
+        );
+        assert_eq!(
+            allow_origin_header(&origins, "https://wiki.corp.example").await,
+            Some("https://wiki.corp.example".to_string())
+        );
+        assert_eq!(
+            allow_origin_header(&origins, "https://evil.test").await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cors_wildcard_allows_any() {
+        assert_eq!(
+            allow_origin_header(&["*"], "https://evil.test").await,
+            Some("*".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_limit() {
+        let config = AppConfig {
+            max_sessions: 1,
+            ..AppConfig::default()
+        };
+        let state = test_state(config).await;
+        assert!(!state.at_session_limit().await);
+
+        let (tx, _rx) = mpsc::channel(1);
+        state
+            .sessions
+            .write()
+            .await
+            .insert("s".to_string(), Arc::new(Session::new("s", tx)));
+        assert!(state.at_session_limit().await);
+    }
+}
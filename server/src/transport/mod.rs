@@ -0,0 +1,9 @@
+mod admin;
+pub mod http;
+mod rest;
+mod sse;
+pub mod stdio;
+mod streamable_http;
+mod tls;
+#[cfg(unix)]
+mod unix;
@@ -0,0 +1,384 @@
+//! Plain REST endpoints for clients that don't speak MCP. Enabled with
+//! `rest_api_enabled`; all lookups use the project selected at startup.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::future::BoxFuture;
+use mcp_hybrid_search_common::config::AppConfig;
+use mcp_hybrid_search_common::types::{ChunkDetail, SearchFilters, SearchOptions, SearchResult};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::http::AppState;
+use crate::search::{EmbeddingError, HybridSearcher};
+
+const DEFAULT_TOP_K: usize = 10;
+
+/// The lookups behind the REST API. Implemented by [`HybridSearcher`]; tests
+/// substitute a stub so the handlers can run without Qdrant.
+pub trait SearchBackend: Send + Sync {
+    fn search<'a>(
+        &'a self,
+        config: &'a AppConfig,
+        query: &'a str,
+        top_k: usize,
+        filters: &'a SearchFilters,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<SearchResult>>>;
+
+    fn get_chunk<'a>(
+        &'a self,
+        config: &'a AppConfig,
+        chunk_id: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<Option<ChunkDetail>>>;
+
+    fn get_document<'a>(
+        &'a self,
+        config: &'a AppConfig,
+        source_path: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<ChunkDetail>>>;
+}
+
+impl SearchBackend for HybridSearcher {
+    fn search<'a>(
+        &'a self,
+        config: &'a AppConfig,
+        query: &'a str,
+        top_k: usize,
+        filters: &'a SearchFilters,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<SearchResult>>> {
+        Box::pin(async move {
+            HybridSearcher::search(
+                self,
+                config,
+                query,
+                top_k,
+                filters,
+                &SearchOptions::default(),
+            )
+            .await
+        })
+    }
+
+    fn get_chunk<'a>(
+        &'a self,
+        config: &'a AppConfig,
+        chunk_id: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<Option<ChunkDetail>>> {
+        Box::pin(HybridSearcher::get_chunk(self, config, chunk_id))
+    }
+
+    fn get_document<'a>(
+        &'a self,
+        config: &'a AppConfig,
+        source_path: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<ChunkDetail>>> {
+        Box::pin(HybridSearcher::get_document(self, config, source_path))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct SearchQuery {
+    #[serde(alias = "query")]
+    q: Option<String>,
+    top_k: Option<usize>,
+    source_type: Option<String>,
+    path_prefix: Option<String>,
+    /// Comma-separated; results must carry all of them.
+    tags: Option<String>,
+    /// RFC3339; results must come from files modified after it.
+    modified_after: Option<chrono::DateTime<chrono::Utc>>,
+    language: Option<String>,
+    /// `false` leaves out text recognized by OCR, `true` keeps only it.
+    ocr: Option<bool>,
+}
+
+/// `GET /search?q=...&top_k=5&source_type=...&path_prefix=...&tags=a,b&modified_after=...&language=ja&ocr=false`
+pub(super) async fn search_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SearchQuery>,
+) -> Response {
+    let Some(query) = params.q.filter(|q| !q.trim().is_empty()) else {
+        return error_response(StatusCode::BAD_REQUEST, "Missing query parameter 'q'");
+    };
+    let filters = SearchFilters {
+        source_type: params.source_type,
+        path_prefix: params.path_prefix,
+        tags: params
+            .tags
+            .iter()
+            .flat_map(|tags| tags.split(','))
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect(),
+        modified_after: params.modified_after,
+        language: params.language,
+        ocr: params.ocr,
+    };
+
+    let config = state.current_config();
+    let result = state
+        .searcher
+        .search(
+            &config,
+            &query,
+            params.top_k.unwrap_or(DEFAULT_TOP_K),
+            &filters,
+        )
+        .await;
+
+    match result {
+        Ok(results) => Json(results).into_response(),
+        Err(e) => failure("search", e),
+    }
+}
+
+/// `GET /chunks/{chunk_id}`
+pub(super) async fn chunk_handler(
+    State(state): State<Arc<AppState>>,
+    Path(chunk_id): Path<String>,
+) -> Response {
+    let config = state.current_config();
+    match state.searcher.get_chunk(&config, &chunk_id).await {
+        Ok(Some(chunk)) => Json(chunk).into_response(),
+        Ok(None) => error_response(
+            StatusCode::NOT_FOUND,
+            format!("Chunk not found: {}", chunk_id),
+        ),
+        Err(e) => failure("chunk lookup", e),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct DocumentQuery {
+    path: Option<String>,
+}
+
+/// `GET /documents?path=...` — every chunk of the document, in order.
+pub(super) async fn document_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<DocumentQuery>,
+) -> Response {
+    let Some(path) = params.path.filter(|p| !p.is_empty()) else {
+        return error_response(StatusCode::BAD_REQUEST, "Missing query parameter 'path'");
+    };
+
+    let config = state.current_config();
+    match state.searcher.get_document(&config, &path).await {
+        Ok(chunks) if chunks.is_empty() => error_response(
+            StatusCode::NOT_FOUND,
+            format!("Document not found: {}", path),
+        ),
+        Ok(chunks) => Json(chunks).into_response(),
+        Err(e) => failure("document lookup", e),
+    }
+}
+
+fn failure(what: &str, e: anyhow::Error) -> Response {
+    tracing::error!("REST {} failed: {:#}", what, e);
+    error_response(status_for(&e), format!("{:#}", e))
+}
+
+/// 502 when the failure came from the embedding provider, 500 otherwise.
+fn status_for(e: &anyhow::Error) -> StatusCode {
+    if e.downcast_ref::<EmbeddingError>().is_some() {
+        StatusCode::BAD_GATEWAY
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+pub(super) fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::server::McpServer;
+    use crate::transport::http::{spawn_test_app, spawn_test_server};
+    use mcp_hybrid_search_common::types::ChunkMetadata;
+    use serde_json::Value;
+
+    fn rest_config() -> AppConfig {
+        AppConfig {
+            rest_api_enabled: true,
+            ..AppConfig::default()
+        }
+    }
+
+    fn chunk(source_path: &str, chunk_index: u32) -> ChunkDetail {
+        ChunkDetail {
+            chunk_id: format!("{}#{}", source_path, chunk_index),
+            text: format!("chunk {}", chunk_index),
+            metadata: ChunkMetadata {
+                title: "Title".to_string(),
+                source_path: source_path.to_string(),
+                source_type: "markdown".to_string(),
+                chunk_index,
+                url: None,
+                location: Default::default(),
+                file_mtime: None,
+                file_size_bytes: None,
+                converter: None,
+                ocr: false,
+            },
+            neighbors: Vec::new(),
+        }
+    }
+
+    /// Serves a single document, `docs/a.md`. Search echoes the query as the
+    /// snippet and the configured collection as the title.
+    struct StubBackend;
+
+    impl SearchBackend for StubBackend {
+        fn search<'a>(
+            &'a self,
+            config: &'a AppConfig,
+            query: &'a str,
+            top_k: usize,
+            _filters: &'a SearchFilters,
+        ) -> BoxFuture<'a, anyhow::Result<Vec<SearchResult>>> {
+            Box::pin(async move {
+                Ok(vec![SearchResult {
+                    chunk_id: "docs/a.md#0".to_string(),
+                    score: top_k as f64,
+                    title: config.collection_name.clone(),
+                    source_path: "docs/a.md".to_string(),
+                    source_type: "markdown".to_string(),
+                    section: None,
+                    location: Default::default(),
+                    file_mtime: None,
+                    snippet: query.to_string(),
+                    text: None,
+                    document_match_count: None,
+                }])
+            })
+        }
+
+        fn get_chunk<'a>(
+            &'a self,
+            _config: &'a AppConfig,
+            chunk_id: &'a str,
+        ) -> BoxFuture<'a, anyhow::Result<Option<ChunkDetail>>> {
+            Box::pin(async move { Ok((chunk_id == "docs/a.md#1").then(|| chunk("docs/a.md", 1))) })
+        }
+
+        fn get_document<'a>(
+            &'a self,
+            _config: &'a AppConfig,
+            source_path: &'a str,
+        ) -> BoxFuture<'a, anyhow::Result<Vec<ChunkDetail>>> {
+            Box::pin(async move {
+                Ok(if source_path == "docs/a.md" {
+                    (0..3).map(|i| chunk(source_path, i)).collect()
+                } else {
+                    Vec::new()
+                })
+            })
+        }
+    }
+
+    async fn stub_server(config: AppConfig) -> String {
+        let server = McpServer::new(config.clone()).await.unwrap();
+        let state = AppState::with_searcher(config, server, Arc::new(StubBackend));
+        spawn_test_app(state).await
+    }
+
+    #[tokio::test]
+    async fn test_search_disabled_by_default() {
+        let base = spawn_test_server(AppConfig::default()).await;
+        let resp = reqwest::get(format!("{}/search?q=rust", base))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_search_requires_query() {
+        let base = spawn_test_server(rest_config()).await;
+        let resp = reqwest::get(format!("{}/search?top_k=5", base))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: Value = resp.json().await.unwrap();
+        assert!(body["error"].as_str().unwrap().contains("'q'"));
+    }
+
+    #[tokio::test]
+    async fn test_search_embedding_failure_is_bad_gateway() {
+        let config = AppConfig {
+            embedding_provider: "unsupported".to_string(),
+            ..rest_config()
+        };
+        let base = spawn_test_server(config).await;
+        let resp = reqwest::get(format!("{}/search?q=rust", base))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn test_search_uses_startup_project() {
+        let config = rest_config().with_project(Some("my-proj"));
+        let base = stub_server(config).await;
+        let resp = reqwest::get(format!("{}/search?q=rust&top_k=3", base))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body[0]["title"], "my-proj");
+        assert_eq!(body[0]["snippet"], "rust");
+        assert_eq!(body[0]["score"], 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_get_chunk() {
+        let base = stub_server(rest_config()).await;
+        let resp = reqwest::get(format!("{}/chunks/docs%2Fa.md%231", base))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["chunk_id"], "docs/a.md#1");
+        assert_eq!(body["metadata"]["chunk_index"], 1);
+
+        let resp = reqwest::get(format!("{}/chunks/missing", base))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_document() {
+        let base = stub_server(rest_config()).await;
+        let resp = reqwest::get(format!("{}/documents?path=docs/a.md", base))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Vec<ChunkDetail> = resp.json().await.unwrap();
+        let indexes: Vec<u32> = body.iter().map(|c| c.metadata.chunk_index).collect();
+        assert_eq!(indexes, vec![0, 1, 2]);
+
+        let resp = reqwest::get(format!("{}/documents?path=docs/b.md", base))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = reqwest::get(format!("{}/documents", base)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_status_for_other_errors() {
+        let e = anyhow::anyhow!("qdrant unavailable");
+        assert_eq!(status_for(&e), StatusCode::INTERNAL_SERVER_ERROR);
+        let e = anyhow::Error::from(EmbeddingError(anyhow::anyhow!("no key")));
+        assert_eq!(status_for(&e), StatusCode::BAD_GATEWAY);
+    }
+}
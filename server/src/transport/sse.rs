@@ -0,0 +1,130 @@
+//! Legacy HTTP+SSE transport (MCP 2024-11-05): `GET /sse` opens a stream and
+//! announces a `/message?sessionId=` endpoint that clients POST requests to.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive},
+        Sse,
+    },
+    Json,
+};
+use futures::stream::Stream;
+use tokio::sync::mpsc;
+
+use super::http::{AppState, Sessions};
+use crate::mcp;
+
+pub(super) async fn sse_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    if state.at_session_limit().await {
+        tracing::warn!("Rejecting SSE connection: session limit reached");
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let (tx, mut rx) = mpsc::channel::<String>(100);
+
+    let session = Arc::new(mcp::session::Session::new(session_id.clone(), tx));
+    state
+        .sessions
+        .write()
+        .await
+        .insert(session_id.clone(), session.clone());
+
+    tracing::info!("SSE connection established: {}", session_id);
+
+    let guard = SessionGuard {
+        sessions: state.sessions.clone(),
+        id: session_id,
+    };
+
+    let stream = async_stream::stream! {
+        // Send the endpoint URL
+        let endpoint = format!("/message?sessionId={}", guard.id);
+        yield Ok(Event::default().event("endpoint").data(endpoint));
+
+        // Stream messages until the session is closed (e.g. for being idle)
+        loop {
+            let msg = tokio::select! {
+                msg = rx.recv() => msg,
+                _ = session.closed() => None,
+            };
+            match msg {
+                Some(msg) => {
+                    yield Ok(Event::default().event("message").data(msg));
+                }
+                None => {
+                    tracing::info!("SSE session closed: {}", guard.id);
+                    break;
+                }
+            }
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Removes a session from the map once its SSE stream is dropped, whether it
+/// ended on the server side or the client disconnected.
+struct SessionGuard {
+    sessions: Sessions,
+    id: String,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        let sessions = self.sessions.clone();
+        let id = std::mem::take(&mut self.id);
+        tokio::spawn(async move {
+            sessions.write().await.remove(&id);
+        });
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub(super) struct MessageQuery {
+    #[serde(rename = "sessionId")]
+    session_id: String,
+}
+
+pub(super) async fn message_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<MessageQuery>,
+    Json(message): Json<serde_json::Value>,
+) -> StatusCode {
+    tracing::debug!("Received message for session {}", query.session_id);
+
+    let session = match state.sessions.read().await.get(&query.session_id) {
+        Some(session) if !session.is_closed() => session.clone(),
+        _ => {
+            tracing::warn!("Session not found: {}", query.session_id);
+            return StatusCode::NOT_FOUND;
+        }
+    };
+
+    let response = state.mcp_server.handle_message(message, &session).await;
+    let Some(response) = response else {
+        return StatusCode::ACCEPTED;
+    };
+
+    let response_json = match serde_json::to_string(&response) {
+        Ok(json) => json,
+        Err(e) => {
+            tracing::error!("Failed to serialize response: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+
+    if session.send(response_json).await.is_err() {
+        tracing::warn!("Failed to send response to session {}", query.session_id);
+        return StatusCode::GONE;
+    }
+
+    StatusCode::ACCEPTED
+}
@@ -0,0 +1,167 @@
+//! MCP over stdio: newline-delimited JSON-RPC on stdin, responses and
+//! notifications on stdout. Logs must go to stderr so they never interleave
+//! with protocol output.
+
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use crate::mcp::protocol::{JsonRpcResponse, PARSE_ERROR};
+use crate::mcp::server::McpServer;
+use crate::mcp::session::Session;
+
+/// Serve a single client on the process's stdin/stdout until stdin closes.
+pub async fn run(server: McpServer) -> anyhow::Result<()> {
+    tracing::info!("MCP server running on stdio");
+    serve(
+        Arc::new(server),
+        BufReader::new(tokio::io::stdin()),
+        tokio::io::stdout(),
+    )
+    .await?;
+    tracing::info!("stdin closed, shutting down");
+    Ok(())
+}
+
+/// Read requests from `reader` and write one line per response to `writer`.
+/// Requests are handled concurrently so progress notifications and slow tool
+/// calls don't block the next message. Returns the writer once `reader` hits
+/// EOF and every in-flight request has finished.
+async fn serve<R, W>(server: Arc<McpServer>, reader: R, writer: W) -> anyhow::Result<W>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (tx, mut rx) = mpsc::channel::<String>(100);
+    let session = Arc::new(Session::new("stdio", tx));
+
+    let writer_task = tokio::spawn(async move {
+        let mut writer = writer;
+        while let Some(msg) = rx.recv().await {
+            writer.write_all(msg.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+            writer.flush().await?;
+        }
+        Ok::<_, std::io::Error>(writer)
+    });
+
+    let mut tasks = JoinSet::new();
+    let mut lines = reader.lines();
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let message: Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::warn!("Invalid JSON-RPC message on stdin: {}", e);
+                let response = JsonRpcResponse::error(
+                    Some(Value::Null),
+                    PARSE_ERROR,
+                    format!("Parse error: {}", e),
+                );
+                send_json(&session, &json!(response)).await;
+                continue;
+            }
+        };
+
+        let server = server.clone();
+        let session = session.clone();
+        tasks.spawn(async move {
+            if let Some(reply) = server.handle_message(message, &session).await {
+                send_json(&session, &reply).await;
+            }
+        });
+    }
+
+    while tasks.join_next().await.is_some() {}
+    // Dropping the last session handle closes the channel and ends the writer.
+    drop(session);
+    Ok(writer_task.await??)
+}
+
+async fn send_json(session: &Session, message: &Value) {
+    match serde_json::to_string(message) {
+        Ok(json) => {
+            if session.send(json).await.is_err() {
+                tracing::warn!("stdout writer closed, dropping response");
+            }
+        }
+        Err(e) => tracing::error!("Failed to serialize response: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_hybrid_search_common::config::AppConfig;
+
+    async fn run_lines(input: &str) -> Vec<Value> {
+        let server = Arc::new(McpServer::new(AppConfig::default()).await.unwrap());
+        let output = serve(server, input.as_bytes(), Vec::new()).await.unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_responses_one_per_line() {
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#,
+            "\n",
+        );
+        let mut out = run_lines(input).await;
+        out.sort_by_key(|v| v["id"].as_i64());
+        assert_eq!(out.len(), 2);
+        assert_eq!(
+            out[0]["result"]["serverInfo"]["name"],
+            "mcp-server-hybrid-search"
+        );
+        assert_eq!(out[1]["id"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_notification_produces_no_line() {
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+            "\n\n",
+        );
+        assert!(run_lines(input).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_batch_on_one_line() {
+        let input = concat!(
+            r#"[{"jsonrpc":"2.0","id":1,"method":"ping"},"#,
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"},"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"ping"}]"#,
+            "\n",
+        );
+        let out = run_lines(input).await;
+        assert_eq!(out.len(), 1);
+        let ids: Vec<&Value> = out[0]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| &r["id"])
+            .collect();
+        assert_eq!(ids, [&json!(1), &json!(2)]);
+    }
+
+    #[tokio::test]
+    async fn test_parse_error() {
+        let out = run_lines("not json\n").await;
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0]["error"]["code"], PARSE_ERROR);
+        assert!(out[0]["id"].is_null());
+    }
+}
@@ -0,0 +1,476 @@
+//! Streamable HTTP transport (MCP 2025-03-26): a single `/mcp` endpoint where
+//! POST carries a JSON-RPC message and is answered with JSON or an SSE stream,
+//! GET opens a server-initiated SSE stream and DELETE ends the session.
+//! Sessions are created by `initialize` and identified by `Mcp-Session-Id`.
+
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive},
+        IntoResponse, Response, Sse,
+    },
+    Json,
+};
+use tokio::sync::mpsc;
+
+use super::http::AppState;
+use crate::mcp::session::Session;
+use serde_json::Value;
+
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// A session created on `/mcp`. Messages sent to `session` that aren't tied
+/// to a single POST (e.g. notifications raised while answering with JSON) are
+/// delivered on the GET stream.
+pub struct StreamableSession {
+    session: Session,
+    /// Receiving end of `session`'s channel while no GET stream holds it.
+    stream_rx: Mutex<Option<mpsc::Receiver<String>>>,
+}
+
+impl StreamableSession {
+    fn new(id: String) -> Self {
+        let (tx, rx) = mpsc::channel(100);
+        Self {
+            session: Session::new(id, tx),
+            stream_rx: Mutex::new(Some(rx)),
+        }
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+}
+
+/// Hands the receiver back to its session when a GET stream is dropped, so
+/// the client can reconnect.
+struct StreamGuard {
+    owner: Arc<StreamableSession>,
+    rx: Option<mpsc::Receiver<String>>,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        if let Some(rx) = self.rx.take() {
+            *self.owner.stream_rx.lock().unwrap() = Some(rx);
+        }
+    }
+}
+
+pub(super) async fn post_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(message): Json<Value>,
+) -> Response {
+    let owner = match session_id(&headers) {
+        None if has_method(&message, "initialize") => {
+            if state.at_session_limit().await {
+                tracing::warn!("Rejecting initialize: session limit reached");
+                return (StatusCode::SERVICE_UNAVAILABLE, "Too many sessions").into_response();
+            }
+            let id = uuid::Uuid::new_v4().to_string();
+            let owner = Arc::new(StreamableSession::new(id.clone()));
+            tracing::info!("Streamable HTTP session created: {}", id);
+            state
+                .streamable_sessions
+                .write()
+                .await
+                .insert(id, owner.clone());
+            owner
+        }
+        _ => match lookup(&state, &headers).await {
+            Ok(owner) => owner,
+            Err(response) => return response,
+        },
+    };
+    let session_header = [(SESSION_HEADER, owner.session.id.clone())];
+
+    tracing::debug!("Received message for session {}", owner.session.id);
+
+    if !has_request(&message) {
+        state
+            .mcp_server
+            .handle_message(message, &owner.session)
+            .await;
+        return (StatusCode::ACCEPTED, session_header).into_response();
+    }
+
+    // Each POST gets its own channel so its progress and log notifications
+    // reach the client on the same response as the result.
+    let (tx, mut rx) = mpsc::channel::<String>(100);
+    let request_session = owner.session.fork(tx);
+
+    if accepts_event_stream(&headers) {
+        let server = state.mcp_server.clone();
+        tokio::spawn(async move {
+            let response = server.handle_message(message, &request_session).await;
+            if let Some(response) = response.as_ref().and_then(to_json) {
+                // The client may have gone away; there is no one left to tell.
+                let _ = request_session.send(response).await;
+            }
+        });
+
+        let stream = async_stream::stream! {
+            while let Some(msg) = rx.recv().await {
+                yield Ok::<_, Infallible>(Event::default().event("message").data(msg));
+            }
+        };
+        return (session_header, Sse::new(stream)).into_response();
+    }
+
+    let forward = {
+        let owner = owner.clone();
+        tokio::spawn(async move {
+            while let Some(msg) = rx.recv().await {
+                if owner.session.try_send(msg).is_err() {
+                    tracing::debug!("Dropped notification for session {}", owner.session.id);
+                }
+            }
+        })
+    };
+    let response = state
+        .mcp_server
+        .handle_message(message, &request_session)
+        .await;
+    drop(request_session);
+    let _ = forward.await;
+
+    match response {
+        Some(response) => (session_header, Json(response)).into_response(),
+        None => (StatusCode::ACCEPTED, session_header).into_response(),
+    }
+}
+
+pub(super) async fn get_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Response {
+    let owner = match lookup(&state, &headers).await {
+        Ok(owner) => owner,
+        Err(response) => return response,
+    };
+
+    let Some(rx) = owner.stream_rx.lock().unwrap().take() else {
+        return (
+            StatusCode::CONFLICT,
+            "A stream is already open for this session",
+        )
+            .into_response();
+    };
+    tracing::info!("Streamable HTTP stream opened: {}", owner.session.id);
+
+    let mut guard = StreamGuard {
+        owner,
+        rx: Some(rx),
+    };
+    let stream = async_stream::stream! {
+        loop {
+            let rx = guard.rx.as_mut().expect("receiver is held until drop");
+            let msg = tokio::select! {
+                msg = rx.recv() => msg,
+                _ = guard.owner.session.closed() => None,
+            };
+            match msg {
+                Some(msg) => yield Ok::<_, Infallible>(Event::default().event("message").data(msg)),
+                None => break,
+            }
+        }
+        tracing::info!("Streamable HTTP stream closed: {}", guard.owner.session.id);
+    };
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+pub(super) async fn delete_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Response {
+    let owner = match lookup(&state, &headers).await {
+        Ok(owner) => owner,
+        Err(response) => return response,
+    };
+
+    state
+        .streamable_sessions
+        .write()
+        .await
+        .remove(&owner.session.id);
+    owner.session.close();
+    tracing::info!("Streamable HTTP session ended: {}", owner.session.id);
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
+fn session_id(headers: &HeaderMap) -> Option<&str> {
+    headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok())
+}
+
+async fn lookup(state: &AppState, headers: &HeaderMap) -> Result<Arc<StreamableSession>, Response> {
+    let Some(id) = session_id(headers) else {
+        return Err((StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header").into_response());
+    };
+    state
+        .streamable_sessions
+        .read()
+        .await
+        .get(id)
+        .cloned()
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Unknown session").into_response())
+}
+
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"))
+}
+
+/// Whether the message (or any element of a batch) expects a response.
+fn has_request(message: &Value) -> bool {
+    match message {
+        Value::Array(items) => items.iter().any(has_request),
+        Value::Object(fields) => matches!(fields.get("id"), Some(id) if !id.is_null()),
+        // Malformed messages are answered with an error.
+        _ => true,
+    }
+}
+
+fn has_method(message: &Value, method: &str) -> bool {
+    match message {
+        Value::Array(items) => items.iter().any(|item| has_method(item, method)),
+        _ => message.get("method").and_then(|m| m.as_str()) == Some(method),
+    }
+}
+
+fn to_json(response: &Value) -> Option<String> {
+    serde_json::to_string(response)
+        .map_err(|e| tracing::error!("Failed to serialize response: {}", e))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::http::spawn_test_server;
+    use mcp_hybrid_search_common::config::AppConfig;
+    use serde_json::json;
+
+    fn rpc(id: u64, method: &str) -> Value {
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": {}})
+    }
+
+    async fn initialize(client: &reqwest::Client, base: &str) -> String {
+        let resp = client
+            .post(format!("{}/mcp", base))
+            .header(header::ACCEPT, "application/json")
+            .json(&rpc(1, "initialize"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let session = resp.headers()[SESSION_HEADER].to_str().unwrap().to_string();
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(
+            body["result"]["serverInfo"]["name"],
+            "mcp-server-hybrid-search"
+        );
+        session
+    }
+
+    #[tokio::test]
+    async fn test_post_returns_json() {
+        let base = spawn_test_server(AppConfig::default()).await;
+        let client = reqwest::Client::new();
+        let session = initialize(&client, &base).await;
+
+        let resp = client
+            .post(format!("{}/mcp", base))
+            .header(header::ACCEPT, "application/json")
+            .header(SESSION_HEADER, &session)
+            .json(&rpc(2, "ping"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["id"], 2);
+        assert!(body["result"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_post_returns_event_stream() {
+        let base = spawn_test_server(AppConfig::default()).await;
+        let client = reqwest::Client::new();
+        let session = initialize(&client, &base).await;
+
+        let resp = client
+            .post(format!("{}/mcp", base))
+            .header(header::ACCEPT, "application/json, text/event-stream")
+            .header(SESSION_HEADER, &session)
+            .json(&rpc(2, "tools/list"))
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/event-stream"));
+
+        let body = resp.text().await.unwrap();
+        let data = body
+            .lines()
+            .find_map(|line| line.strip_prefix("data: "))
+            .unwrap();
+        let msg: Value = serde_json::from_str(data).unwrap();
+        assert_eq!(msg["id"], 2);
+        assert!(msg["result"]["tools"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_post_batch() {
+        let base = spawn_test_server(AppConfig::default()).await;
+        let client = reqwest::Client::new();
+        let session = initialize(&client, &base).await;
+
+        let resp = client
+            .post(format!("{}/mcp", base))
+            .header(header::ACCEPT, "application/json")
+            .header(SESSION_HEADER, &session)
+            .json(&json!([rpc(2, "ping"), rpc(3, "tools/list")]))
+            .send()
+            .await
+            .unwrap();
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body[0]["id"], 2);
+        assert_eq!(body[1]["id"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_notification_is_accepted() {
+        let base = spawn_test_server(AppConfig::default()).await;
+        let client = reqwest::Client::new();
+        let session = initialize(&client, &base).await;
+
+        let resp = client
+            .post(format!("{}/mcp", base))
+            .header(SESSION_HEADER, &session)
+            .json(&json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        assert!(resp.text().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_session_header_required() {
+        let base = spawn_test_server(AppConfig::default()).await;
+        let client = reqwest::Client::new();
+
+        let resp = client
+            .post(format!("{}/mcp", base))
+            .json(&rpc(1, "ping"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = client
+            .post(format!("{}/mcp", base))
+            .header(SESSION_HEADER, "nope")
+            .json(&rpc(1, "ping"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_stream_and_delete() {
+        let base = spawn_test_server(AppConfig::default()).await;
+        let client = reqwest::Client::new();
+        let session = initialize(&client, &base).await;
+
+        let stream = client
+            .get(format!("{}/mcp", base))
+            .header(header::ACCEPT, "text/event-stream")
+            .header(SESSION_HEADER, &session)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(stream.status(), StatusCode::OK);
+
+        let second = client
+            .get(format!("{}/mcp", base))
+            .header(SESSION_HEADER, &session)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(second.status(), StatusCode::CONFLICT);
+
+        let resp = client
+            .delete(format!("{}/mcp", base))
+            .header(SESSION_HEADER, &session)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        // Deleting the session ends its stream.
+        stream.text().await.unwrap();
+
+        let resp = client
+            .post(format!("{}/mcp", base))
+            .header(SESSION_HEADER, &session)
+            .json(&rpc(2, "ping"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_rejected_at_session_limit() {
+        let config = AppConfig {
+            max_sessions: 1,
+            ..AppConfig::default()
+        };
+        let base = spawn_test_server(config).await;
+        let client = reqwest::Client::new();
+        initialize(&client, &base).await;
+
+        let resp = client
+            .post(format!("{}/mcp", base))
+            .json(&rpc(1, "initialize"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let resp = client.get(format!("{}/sse", base)).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_sse_can_be_disabled() {
+        let client = reqwest::Client::new();
+
+        let base = spawn_test_server(AppConfig::default()).await;
+        let resp = client.get(format!("{}/sse", base)).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let config = AppConfig {
+            legacy_sse: false,
+            ..AppConfig::default()
+        };
+        let base = spawn_test_server(config).await;
+        let resp = client.get(format!("{}/sse", base)).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,164 @@
+//! HTTPS listener, used when both `tls_cert_path` and `tls_key_path` are set.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::Router;
+use mcp_hybrid_search_common::config::AppConfig;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::crypto::aws_lc_rs;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+use super::http::serve_connection;
+
+/// Build the TLS acceptor from the configured PEM files, or `None` for plain
+/// HTTP. Called before binding so bad files stop the server at startup.
+pub(super) fn acceptor(config: &AppConfig) -> anyhow::Result<Option<TlsAcceptor>> {
+    match (&config.tls_cert_path, &config.tls_key_path) {
+        (None, None) => Ok(None),
+        (Some(cert), Some(key)) => Ok(Some(TlsAcceptor::from(Arc::new(server_config(cert, key)?)))),
+        _ => anyhow::bail!("tls_cert_path and tls_key_path must be set together"),
+    }
+}
+
+fn server_config(cert_path: &str, key_path: &str) -> anyhow::Result<ServerConfig> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("Failed to read TLS certificate {}", cert_path))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in {}", cert_path);
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .with_context(|| format!("Failed to read TLS private key {}", key_path))?;
+
+    // Select the provider explicitly: more than one is compiled in through
+    // our dependencies, which makes rustls refuse to pick a default.
+    ServerConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .with_context(|| {
+            format!(
+                "TLS private key {} does not match certificate {}",
+                key_path, cert_path
+            )
+        })
+}
+
+/// Accept TLS connections and serve `app` on each, like `axum::serve` does
+/// for plain TCP.
+pub(super) async fn serve(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    app: Router,
+) -> anyhow::Result<()> {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                tracing::warn!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+
+        tokio::spawn(async move {
+            match acceptor.accept(stream).await {
+                Ok(stream) => serve_connection(stream, app, peer).await,
+                Err(e) => tracing::debug!("TLS handshake with {} failed: {}", peer, e),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::server::McpServer;
+    use crate::transport::http::{router, AppState};
+
+    const CERT: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/transport/testdata/cert.pem"
+    );
+    const KEY: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/transport/testdata/key.pem"
+    );
+    const OTHER_KEY: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/transport/testdata/other_key.pem"
+    );
+
+    fn tls_config(cert: &str, key: &str) -> AppConfig {
+        AppConfig {
+            tls_cert_path: Some(cert.to_string()),
+            tls_key_path: Some(key.to_string()),
+            ..AppConfig::default()
+        }
+    }
+
+    fn error_text(config: &AppConfig) -> String {
+        format!("{:#}", acceptor(config).err().expect("expected an error"))
+    }
+
+    #[test]
+    fn test_plain_http_by_default() {
+        assert!(acceptor(&AppConfig::default()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_requires_cert_and_key_together() {
+        let config = AppConfig {
+            tls_cert_path: Some(CERT.to_string()),
+            ..AppConfig::default()
+        };
+        assert!(error_text(&config).contains("must be set together"));
+    }
+
+    #[test]
+    fn test_unreadable_files_fail_fast() {
+        let text = error_text(&tls_config("/nonexistent/cert.pem", KEY));
+        assert!(text.contains("/nonexistent/cert.pem"));
+        let text = error_text(&tls_config(CERT, "/nonexistent/key.pem"));
+        assert!(text.contains("/nonexistent/key.pem"));
+    }
+
+    #[test]
+    fn test_mismatched_key_fails_fast() {
+        let text = error_text(&tls_config(CERT, OTHER_KEY));
+        assert!(text.contains("does not match"));
+    }
+
+    #[tokio::test]
+    async fn test_https_handshake() {
+        let config = tls_config(CERT, KEY);
+        let acceptor = acceptor(&config).unwrap().unwrap();
+        let server = McpServer::new(config.clone()).await.unwrap();
+        let app = router(Arc::new(AppState::new(config, server).unwrap()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(serve(listener, acceptor, app));
+
+        let cert = reqwest::Certificate::from_pem(&std::fs::read(CERT).unwrap()).unwrap();
+        let client = reqwest::Client::builder()
+            .add_root_certificate(cert)
+            .build()
+            .unwrap();
+        let resp = client
+            .get(format!("https://localhost:{}/health/live", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        assert_eq!(resp.text().await.unwrap(), "ok");
+
+        // The listener only speaks TLS.
+        let plain = reqwest::get(format!("http://localhost:{}/health/live", port)).await;
+        assert!(plain.is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! Unix domain socket listener, used when `listen_socket` is set. Access is
+//! controlled by the socket file's permissions (0600: owner only).
+
+use std::fs::{DirBuilder, Permissions};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::Path;
+
+use anyhow::Context;
+use axum::Router;
+use tokio::net::UnixListener;
+
+use super::http::serve_connection;
+
+/// Bind `path`, replacing a stale socket left behind by a previous run.
+/// Refuses to touch anything that is not a socket, or a socket another
+/// process is still listening on.
+pub(super) fn bind(path: &Path) -> anyhow::Result<UnixListener> {
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            anyhow::bail!(
+                "listen_socket {} exists and is not a socket",
+                path.display()
+            );
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            anyhow::bail!("listen_socket {} is already in use", path.display());
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+
+    // Bind inside a directory only the owner can enter and move the socket
+    // into place once it is 0600, so nobody can connect in between.
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let staging = parent.join(format!(".mcp-{}", uuid::Uuid::new_v4().simple()));
+    DirBuilder::new()
+        .mode(0o700)
+        .create(&staging)
+        .with_context(|| format!("Failed to create {}", staging.display()))?;
+    let bound = bind_staged(&staging.join("s"), path);
+    std::fs::remove_dir_all(&staging).ok();
+    bound
+}
+
+fn bind_staged(staged: &Path, path: &Path) -> anyhow::Result<UnixListener> {
+    let listener = UnixListener::bind(staged)
+        .with_context(|| format!("Failed to bind listen_socket {}", path.display()))?;
+    std::fs::set_permissions(staged, Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to set permissions on {}", path.display()))?;
+    std::fs::rename(staged, path)
+        .with_context(|| format!("Failed to move socket to {}", path.display()))?;
+    Ok(listener)
+}
+
+/// Accept connections on the socket and serve `app` on each.
+pub(super) async fn serve(listener: UnixListener, app: Router) -> anyhow::Result<()> {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                tracing::warn!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        tokio::spawn(serve_connection(stream, app.clone(), "unix socket"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::server::McpServer;
+    use crate::transport::http::{router, AppState};
+    use mcp_hybrid_search_common::config::AppConfig;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn socket_path() -> PathBuf {
+        std::env::temp_dir().join(format!("mcp-{}.sock", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_serves_over_socket() {
+        let path = socket_path();
+        // A socket file nobody listens on, as left behind by a crash.
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let listener = bind(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A second server must not steal the live socket.
+        let err = bind(&path).unwrap_err();
+        assert!(err.to_string().contains("already in use"));
+
+        let config = AppConfig::default();
+        let server = McpServer::new(config.clone()).await.unwrap();
+        let app = router(Arc::new(AppState::new(config, server).unwrap()));
+        tokio::spawn(serve(listener, app));
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"GET /health/live HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("ok"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_refuses_to_replace_regular_file() {
+        let path = socket_path();
+        std::fs::write(&path, "not a socket").unwrap();
+        let err = bind(&path).unwrap_err();
+        assert!(err.to_string().contains("is not a socket"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}